@@ -0,0 +1,433 @@
+use crate::interpreter::interpreter::EnvValue;
+use crate::interpreter::program::{execute_program, Stats};
+use crate::interpreter::replay::Recording;
+use crate::interpreter::value::render;
+use crate::ir::ast::{
+    Capabilities, Change, Environment, Expression, Io, Level, Limits, Observer, Statement, Strictness,
+};
+use crate::ir::levels::{self, LanguageLevel};
+use crate::ir::{dataflow, desugar};
+use crate::parser::parser::parse_program;
+use crate::tc::type_checker::{check_program_with, CheckedProgram};
+use std::fmt;
+use std::sync::{Arc, Mutex};
+
+/// What a diagnostic is about, from the code in brackets at the start of
+/// its message.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[non_exhaustive]
+pub enum DiagnosticKind {
+    Syntax,
+    Type,
+    Name,
+    Runtime,
+    Level,
+    Warning,
+    Other,
+}
+
+/// An error or warning, with the message the command line prints for it.
+#[derive(Debug, PartialEq, Clone)]
+#[non_exhaustive]
+pub struct Diagnostic {
+    pub kind: DiagnosticKind,
+    pub message: String,
+}
+
+impl Diagnostic {
+    fn new(message: String) -> Diagnostic {
+        let code = message
+            .strip_prefix('[')
+            .and_then(|rest| rest.split_once(']'))
+            .map(|(head, _)| head.split(' ').take_while(|word| *word != "on" && *word != "in"))
+            .map(|words| words.collect::<Vec<_>>().join(" "))
+            .unwrap_or_default();
+        let kind = match code.as_str() {
+            "Syntax Error" => DiagnosticKind::Syntax,
+            "Type Error" => DiagnosticKind::Type,
+            "Name Error" | "Local Name Error" => DiagnosticKind::Name,
+            "Runtime Error" => DiagnosticKind::Runtime,
+            "Level Error" => DiagnosticKind::Level,
+            "Warning" => DiagnosticKind::Warning,
+            _ => DiagnosticKind::Other,
+        };
+        Diagnostic { kind, message }
+    }
+
+    pub fn is_warning(&self) -> bool {
+        self.kind == DiagnosticKind::Warning
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+/// The diagnostics of a step, in the order it found them. A failed step
+/// ends with its error.
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct Diagnostics(Vec<Diagnostic>);
+
+impl Diagnostics {
+    fn error(warnings: Diagnostics, message: String) -> Diagnostics {
+        let mut diagnostics = warnings;
+        diagnostics.0.push(Diagnostic::new(message));
+        diagnostics
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Diagnostic> {
+        self.0.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn errors(&self) -> impl Iterator<Item = &Diagnostic> {
+        self.0.iter().filter(|diagnostic| !diagnostic.is_warning())
+    }
+}
+
+impl fmt::Display for Diagnostics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let lines: Vec<String> = self.0.iter().map(|diagnostic| diagnostic.to_string()).collect();
+        write!(f, "{}", lines.join("\n"))
+    }
+}
+
+impl std::error::Error for Diagnostics {}
+
+/// A value a program produced. Values without a variant of their own, such
+/// as decimals, data type values and functions, are kept as they print.
+#[derive(Debug, PartialEq, Clone)]
+#[non_exhaustive]
+pub enum Value {
+    Bool(bool),
+    Int(i32),
+    Real(f64),
+    Str(String),
+    List(Vec<Value>),
+    Void,
+    Other(String),
+}
+
+impl Value {
+    fn new(value: &EnvValue) -> Value {
+        match value {
+            EnvValue::Exp(exp) => Value::from_expression(exp),
+            _ => Value::Other(render(value)),
+        }
+    }
+
+    fn from_expression(exp: &Expression) -> Value {
+        match exp {
+            Expression::CTrue => Value::Bool(true),
+            Expression::CFalse => Value::Bool(false),
+            Expression::CInt(n) => Value::Int(*n),
+            Expression::CReal(x) => Value::Real(*x),
+            Expression::CString(s) => Value::Str(s.clone()),
+            Expression::CList(items) => Value::List(items.iter().map(Value::from_expression).collect()),
+            Expression::CVoid => Value::Void,
+            _ => Value::Other(render(&EnvValue::Exp(exp.clone()))),
+        }
+    }
+}
+
+/// A parsed and desugared program, ready to check or run.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Program {
+    statements: Vec<Statement>,
+}
+
+/// What running a program produced.
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct Run {
+    /// What a top-level `return` or `main()` returned.
+    pub value: Option<Value>,
+    /// What the program printed.
+    pub output: String,
+    pub warnings: Diagnostics,
+    env: Environment<EnvValue>,
+}
+
+impl Run {
+    /// A top-level variable when the program stopped.
+    pub fn variable(&self, name: &str) -> Option<Value> {
+        self.env.search_frame(name.to_string()).map(Value::new)
+    }
+}
+
+/// A variable a running program bound or rebound, for
+/// `EngineBuilder::observe`. `old` is its previous value in the same call,
+/// and `span` the assignment or call that bound it, as written.
+#[derive(Debug, PartialEq, Clone)]
+#[non_exhaustive]
+pub struct Binding {
+    pub name: String,
+    pub old: Option<Value>,
+    pub new: Value,
+    pub span: String,
+}
+
+/// What a program may do outside the interpreter, for
+/// `EngineBuilder::capability`.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[non_exhaustive]
+pub enum Capability {
+    Network,
+}
+
+/// Parses, checks and runs programs with the same settings, which an
+/// `EngineBuilder` chooses:
+///
+/// ```text
+/// let engine = Engine::builder().max_recursion(1000).step_limit(10_000_000).build();
+/// let run = engine.run(source)?;
+/// ```
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct Engine {
+    input: Option<String>,
+    level: LanguageLevel,
+    strictness: Strictness,
+    limits: Limits,
+    capabilities: Capabilities,
+    observer: Observer<EnvValue>,
+}
+
+/// Settings for an `Engine`. Unset ones keep the defaults of the command
+/// line: no limit on steps, every capability denied, the builtins available
+/// and implicit promotions allowed.
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct EngineBuilder {
+    engine: Engine,
+}
+
+impl EngineBuilder {
+    /// The lines `input()` reads; the process's stdin otherwise.
+    pub fn input(mut self, input: &str) -> EngineBuilder {
+        self.engine.input = Some(input.to_string());
+        self
+    }
+
+    /// Programs using constructs above `level` are rejected when parsed.
+    pub fn level(mut self, level: LanguageLevel) -> EngineBuilder {
+        self.engine.level = level;
+        self
+    }
+
+    /// Calls that may be active at once.
+    pub fn max_recursion(mut self, calls: usize) -> EngineBuilder {
+        self.engine.limits.max_calls = calls;
+        self
+    }
+
+    /// Steps the interpreter may take before the run fails.
+    pub fn step_limit(mut self, steps: u64) -> EngineBuilder {
+        self.engine.limits.max_steps = Some(steps);
+        self
+    }
+
+    pub fn capability(mut self, capability: Capability, allowed: bool) -> EngineBuilder {
+        match capability {
+            Capability::Network => self.engine.capabilities.network = allowed,
+        }
+        self
+    }
+
+    /// Whether programs may call the builtin functions.
+    pub fn prelude(mut self, prelude: bool) -> EngineBuilder {
+        self.engine.strictness.prelude = prelude;
+        self
+    }
+
+    /// Rejects implicit integer promotions, as `n` in `n + 0.5`, instead
+    /// of allowing them.
+    pub fn strict_types(mut self, strict: bool) -> EngineBuilder {
+        self.engine.strictness.promotions = if strict { Level::Deny } else { Level::Allow };
+        self
+    }
+
+    /// Calls `callback` with every binding a program makes, in order.
+    pub fn observe(mut self, callback: impl Fn(&Binding) + Send + Sync + 'static) -> EngineBuilder {
+        self.engine.observer = Observer::new(move |change: &Change<EnvValue>| {
+            callback(&Binding {
+                name: change.name.clone(),
+                old: change.old.as_ref().map(Value::new),
+                new: Value::new(&change.new),
+                span: change.span.clone(),
+            })
+        });
+        self
+    }
+
+    /// Appends every state change of the programs the engine runs to
+    /// `recording`, which a `Replay` steps through. It replaces an
+    /// observer set with `observe`.
+    pub fn record(mut self, recording: &Arc<Mutex<Recording>>) -> EngineBuilder {
+        self.engine.observer = Recording::observer(recording);
+        self
+    }
+
+    pub fn build(self) -> Engine {
+        self.engine
+    }
+}
+
+impl Engine {
+    pub fn new() -> Engine {
+        Engine::default()
+    }
+
+    pub fn builder() -> EngineBuilder {
+        EngineBuilder::default()
+    }
+
+    pub fn parse(&self, source: &str) -> Result<Program, Diagnostics> {
+        let error = |message| Diagnostics::error(Diagnostics::default(), message);
+        let statements = parse_program(source).map_err(error)?;
+        levels::check(&statements, self.level).map_err(error)?;
+        Ok(Program {
+            statements: desugar::program(statements),
+        })
+    }
+
+    /// Type checks `program` and returns its warnings.
+    pub fn check(&self, program: &Program) -> Result<Diagnostics, Diagnostics> {
+        let warnings = self.warnings(program);
+        match check_program_with(&program.statements, self.strictness) {
+            Ok(_) => Ok(warnings),
+            Err(message) => Err(Diagnostics::error(warnings, message)),
+        }
+    }
+
+    fn warnings(&self, program: &Program) -> Diagnostics {
+        Diagnostics(
+            dataflow::program(&program.statements)
+                .iter()
+                .map(|warning| Diagnostic::new(warning.render()))
+                .collect(),
+        )
+    }
+
+    /// Parses, checks and runs `source`, then its `main()` if it has one.
+    pub fn run(&self, source: &str) -> Result<Run, Diagnostics> {
+        let program = self.parse(source)?;
+        let warnings = self.warnings(&program);
+        let checked = CheckedProgram::check_with(program.statements, self.strictness)
+            .map_err(|message| Diagnostics::error(warnings.clone(), message))?;
+
+        let mut env = Environment::new();
+        env.limits = Arc::new(self.limits.clone());
+        env.capabilities = self.capabilities.clone();
+        env.observer = self.observer.clone();
+        env.io = match &self.input {
+            Some(input) => Io::scripted(input),
+            None => Io::captured(),
+        };
+        let value = execute_program(&checked, &mut env, &mut Stats::default())
+            .map_err(|message| Diagnostics::error(warnings.clone(), message))?;
+        Ok(Run {
+            value: value.as_ref().map(Value::new),
+            output: env.io.output().unwrap_or_default(),
+            warnings,
+            env,
+        })
+    }
+}
+
+pub fn parse(source: &str) -> Result<Program, Diagnostics> {
+    Engine::new().parse(source)
+}
+
+pub fn check(program: &Program) -> Result<Diagnostics, Diagnostics> {
+    Engine::new().check(program)
+}
+
+pub fn run(source: &str) -> Result<Run, Diagnostics> {
+    Engine::new().run(source)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn programs_run_through_the_facade() {
+        let run = Engine::builder()
+            .input("4")
+            .build()
+            .run("n = input()\nprint(n)\ndef main() -> TReal:\n    return 2.5")
+            .unwrap();
+        assert_eq!(run.value, Some(Value::Real(2.5)));
+        assert_eq!(run.output, "4\n");
+        assert_eq!(run.variable("n"), Some(Value::Str(String::from("4"))));
+        assert!(run.warnings.is_empty());
+    }
+
+    #[test]
+    fn failures_are_reported_as_diagnostics() {
+        let kinds = |result: Result<Run, Diagnostics>| -> Vec<DiagnosticKind> {
+            result.unwrap_err().iter().map(|diagnostic| diagnostic.kind).collect()
+        };
+        assert_eq!(kinds(run("x = = 1")), vec![DiagnosticKind::Syntax]);
+        assert_eq!(kinds(run("def f(n: TInteger) -> TInteger:\n    return n\ny = f(True)")), vec![DiagnosticKind::Type]);
+        assert_eq!(kinds(run("x = decimal(1.0) / decimal(0.0)")), vec![DiagnosticKind::Runtime]);
+        assert_eq!(
+            kinds(Engine::builder().level(LanguageLevel::Expressions).build().run("def f() -> TInteger:\n    return 1")),
+            vec![DiagnosticKind::Level]
+        );
+
+        let program = parse("def f(n: TInteger) -> TInteger:\n    return n\ny = f(True)").unwrap();
+        assert_eq!(
+            check(&program).unwrap_err().to_string(),
+            "[Type Error on '__main__()'] 'f()' has mismatched arguments: expected 'TInteger', found 'TBool'."
+        );
+    }
+    #[test]
+    fn builders_configure_the_engine() {
+        let countdown = "def down(n: TInteger) -> TInteger:\n    return down(n - 1)\nx = down(3)";
+        let error = |engine: Engine, source: &str| engine.run(source).unwrap_err().to_string();
+        assert_eq!(
+            error(Engine::builder().max_recursion(5).build(), countdown),
+            "[Resource Exhausted] more than 5 calls are active; is the recursion missing a base case?"
+        );
+        assert_eq!(
+            error(Engine::builder().step_limit(1000).build(), "x = 0\nwhile x >= 0:\n    x = x + 1"),
+            "[Resource Exhausted] the program took more than 1000 steps."
+        );
+        assert_eq!(
+            error(Engine::builder().prelude(false).build(), "print(1)"),
+            "[Name Error on '__main__()'] 'print()' is not defined."
+        );
+        assert_eq!(
+            error(Engine::builder().strict_types(true).build(), "x = 1 + 0.5"),
+            "[Type Error on '__main__()'] implicit promotion of `1` from 'TInteger' to 'TReal'; write `1.0`."
+        );
+        assert!(Engine::builder().capability(Capability::Network, true).build().run("x = 1 + 0.5").is_ok());
+
+        let bindings = std::sync::Arc::new(std::sync::Mutex::new(vec![]));
+        let engine = Engine::builder()
+            .observe({
+                let bindings = bindings.clone();
+                move |binding: &Binding| bindings.lock().unwrap().push(binding.clone())
+            })
+            .build();
+        engine.run("x = 1\nx = x + 1").unwrap();
+        assert_eq!(
+            bindings.lock().unwrap().last(),
+            Some(&Binding {
+                name: String::from("x"),
+                old: Some(Value::Int(1)),
+                new: Value::Int(2),
+                span: String::from("x = x + 1"),
+            })
+        );
+    }
+}
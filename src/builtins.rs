@@ -0,0 +1,13 @@
+pub mod bytes;
+pub mod codec;
+pub mod csv;
+pub mod format;
+pub mod functional;
+#[cfg(feature = "http")]
+pub mod http;
+pub mod inspect;
+pub mod io;
+pub mod matrix;
+pub mod numeric;
+pub mod reflect;
+pub mod registry;
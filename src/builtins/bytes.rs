@@ -0,0 +1,294 @@
+use crate::builtins::registry::{argument, expect_arity, mismatched, Builtin};
+use crate::interpreter::interpreter::EnvValue;
+use crate::ir::ast::{Environment, Expression, Type};
+
+type ErrorMessage = String;
+
+pub const BUILTINS: &[Builtin] = &[
+    Builtin {
+        name: "len",
+        check: check_len,
+        eval: eval_len,
+    },
+    Builtin {
+        name: "byte_at",
+        check: check_byte_at,
+        eval: eval_byte_at,
+    },
+    Builtin {
+        name: "slice",
+        check: check_slice,
+        eval: eval_slice,
+    },
+    Builtin {
+        name: "to_bytes",
+        check: check_to_bytes,
+        eval: eval_to_bytes,
+    },
+    Builtin {
+        name: "from_bytes",
+        check: check_from_bytes,
+        eval: eval_from_bytes,
+    },
+];
+
+fn expect_types(name: &str, args: &[Type], expected: &[Type]) -> Result<(), ErrorMessage> {
+    expect_arity(name, args, expected.len())?;
+    for (arg, param) in args.iter().zip(expected) {
+        if arg != param {
+            return Err(format!(
+                "[Type Error] '{}()' has mismatched arguments: expected '{:?}', found '{:?}'.",
+                name, param, arg
+            ));
+        }
+    }
+    Ok(())
+}
+
+fn bytes_arg<'a>(name: &str, args: &'a [EnvValue], i: usize) -> Result<&'a Vec<u8>, ErrorMessage> {
+    match argument(name, args, i)? {
+        EnvValue::Exp(Expression::CBytes(b)) => Ok(b),
+        other => Err(mismatched(name, "bytes", other)),
+    }
+}
+
+fn string_arg<'a>(name: &str, args: &'a [EnvValue], i: usize) -> Result<&'a String, ErrorMessage> {
+    match argument(name, args, i)? {
+        EnvValue::Exp(Expression::CString(s)) => Ok(s),
+        other => Err(mismatched(name, "a string", other)),
+    }
+}
+
+fn int_arg(name: &str, args: &[EnvValue], i: usize) -> Result<i32, ErrorMessage> {
+    match argument(name, args, i)? {
+        EnvValue::Exp(Expression::CInt(i)) => Ok(*i),
+        other => Err(mismatched(name, "an integer", other)),
+    }
+}
+
+fn check_len(args: &[Type], _env: &Environment<Type>) -> Result<Type, ErrorMessage> {
+    expect_arity("len", args, 1)?;
+    match args[0] {
+        Type::TBytes | Type::TString | Type::TList(_) | Type::TDict(_, _) => Ok(Type::TInteger),
+        _ => Err(format!(
+            "[Type Error] 'len()' expects bytes, a string, a list or a dict, found '{:?}'.",
+            args[0]
+        )),
+    }
+}
+
+/// Strings are measured in characters, not in bytes.
+fn eval_len(args: Vec<EnvValue>, _env: &Environment<EnvValue>) -> Result<EnvValue, ErrorMessage> {
+    let len = match argument("len", &args, 0)? {
+        EnvValue::Exp(Expression::CBytes(b)) => b.len(),
+        EnvValue::Exp(Expression::CString(s)) => s.chars().count(),
+        EnvValue::Exp(Expression::CList(items)) => items.len(),
+        EnvValue::Exp(Expression::CDict(entries)) => entries.len(),
+        other => return Err(mismatched("len", "bytes, a string, a list or a dict", other)),
+    };
+    Ok(EnvValue::Exp(Expression::CInt(len as i32)))
+}
+
+fn check_byte_at(args: &[Type], _env: &Environment<Type>) -> Result<Type, ErrorMessage> {
+    expect_types("byte_at", args, &[Type::TBytes, Type::TInteger])?;
+    Ok(Type::TInteger)
+}
+
+fn eval_byte_at(args: Vec<EnvValue>, _env: &Environment<EnvValue>) -> Result<EnvValue, ErrorMessage> {
+    let bytes = bytes_arg("byte_at", &args, 0)?;
+    let index = int_arg("byte_at", &args, 1)?;
+
+    match usize::try_from(index).ok().and_then(|i| bytes.get(i)) {
+        Some(byte) => Ok(EnvValue::Exp(Expression::CInt(*byte as i32))),
+        None => Err(format!(
+            "[Runtime Error] index {} out of range for bytes of length {}.",
+            index,
+            bytes.len()
+        )),
+    }
+}
+
+fn check_slice(args: &[Type], _env: &Environment<Type>) -> Result<Type, ErrorMessage> {
+    expect_types("slice", args, &[Type::TBytes, Type::TInteger, Type::TInteger])?;
+    Ok(Type::TBytes)
+}
+
+/// Bounds are clamped to the bytes, as in Python: `slice(b, 2, 100)` is
+/// everything from the third byte on, and an empty range gives `b""`.
+fn eval_slice(args: Vec<EnvValue>, _env: &Environment<EnvValue>) -> Result<EnvValue, ErrorMessage> {
+    let bytes = bytes_arg("slice", &args, 0)?;
+    let clamp = |i: i32| (i.max(0) as usize).min(bytes.len());
+    let (start, end) = (clamp(int_arg("slice", &args, 1)?), clamp(int_arg("slice", &args, 2)?));
+
+    let sliced = if start < end {
+        bytes[start..end].to_vec()
+    } else {
+        vec![]
+    };
+    Ok(EnvValue::Exp(Expression::CBytes(sliced)))
+}
+
+fn check_to_bytes(args: &[Type], _env: &Environment<Type>) -> Result<Type, ErrorMessage> {
+    expect_types("to_bytes", args, &[Type::TString, Type::TString])?;
+    Ok(Type::TResult(Box::new(Type::TBytes), Box::new(Type::TString)))
+}
+
+fn eval_to_bytes(args: Vec<EnvValue>, _env: &Environment<EnvValue>) -> Result<EnvValue, ErrorMessage> {
+    let text = string_arg("to_bytes", &args, 0)?;
+    let encoded = match string_arg("to_bytes", &args, 1)?.as_str() {
+        "utf-8" => Ok(text.as_bytes().to_vec()),
+        "ascii" => encode_narrow(text, 0x7F, "ascii"),
+        "latin-1" => encode_narrow(text, 0xFF, "latin-1"),
+        other => Err(format!("unknown encoding '{}'", other)),
+    };
+    Ok(EnvValue::Exp(wrap_result(encoded.map(Expression::CBytes))))
+}
+
+fn check_from_bytes(args: &[Type], _env: &Environment<Type>) -> Result<Type, ErrorMessage> {
+    expect_types("from_bytes", args, &[Type::TBytes, Type::TString])?;
+    Ok(Type::TResult(Box::new(Type::TString), Box::new(Type::TString)))
+}
+
+fn eval_from_bytes(args: Vec<EnvValue>, _env: &Environment<EnvValue>) -> Result<EnvValue, ErrorMessage> {
+    let bytes = bytes_arg("from_bytes", &args, 0)?;
+    let decoded = match string_arg("from_bytes", &args, 1)?.as_str() {
+        "utf-8" => String::from_utf8(bytes.clone()).map_err(|e| {
+            format!(
+                "invalid utf-8 byte at position {}",
+                e.utf8_error().valid_up_to()
+            )
+        }),
+        "ascii" => match bytes.iter().position(|b| !b.is_ascii()) {
+            Some(position) => Err(format!("invalid ascii byte at position {}", position)),
+            None => Ok(bytes.iter().map(|b| *b as char).collect()),
+        },
+        "latin-1" => Ok(bytes.iter().map(|b| *b as char).collect()),
+        other => Err(format!("unknown encoding '{}'", other)),
+    };
+    Ok(EnvValue::Exp(wrap_result(decoded.map(Expression::CString))))
+}
+
+fn encode_narrow(text: &str, max: u32, encoding: &str) -> Result<Vec<u8>, ErrorMessage> {
+    text.chars()
+        .map(|c| match c as u32 {
+            code if code <= max => Ok(code as u8),
+            _ => Err(format!("character '{}' cannot be encoded as {}", c, encoding)),
+        })
+        .collect()
+}
+
+fn wrap_result(result: Result<Expression, ErrorMessage>) -> Expression {
+    match result {
+        Ok(e) => Expression::COk(Box::new(e)),
+        Err(e) => Expression::CErr(Box::new(Expression::CString(e))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interpreter::interpreter::eval;
+    use crate::ir::ast::Expression::*;
+    use crate::ir::ast::Type::*;
+    use crate::tc::type_checker::check_exp;
+
+    fn call(name: &str, args: Vec<Expression>) -> Result<EnvValue, (String, Option<Expression>)> {
+        let env: Environment<EnvValue> = Environment::new();
+        eval(FuncCall(name.to_string(), args), &env)
+    }
+
+    #[test]
+    fn bytes_length_indexing_and_slicing() {
+        let data = CBytes(vec![10, 20, 30, 40]);
+
+        assert_eq!(call("len", vec![data.clone()]), Ok(EnvValue::Exp(CInt(4))));
+        assert_eq!(
+            call("len", vec![CString("olá".to_string())]),
+            Ok(EnvValue::Exp(CInt(3)))
+        );
+        assert_eq!(
+            call("byte_at", vec![data.clone(), CInt(2)]),
+            Ok(EnvValue::Exp(CInt(30)))
+        );
+        assert_eq!(
+            call("byte_at", vec![data.clone(), CInt(4)]),
+            Err((
+                String::from("[Runtime Error] index 4 out of range for bytes of length 4."),
+                None
+            ))
+        );
+        assert_eq!(
+            call("slice", vec![data.clone(), CInt(1), CInt(100)]),
+            Ok(EnvValue::Exp(CBytes(vec![20, 30, 40])))
+        );
+        assert_eq!(
+            call("slice", vec![data, CInt(3), CInt(1)]),
+            Ok(EnvValue::Exp(CBytes(vec![])))
+        );
+    }
+
+    #[test]
+    fn string_conversions_with_encodings() {
+        let text = CString("olá".to_string());
+
+        assert_eq!(
+            call("to_bytes", vec![text.clone(), CString("utf-8".to_string())]),
+            Ok(EnvValue::Exp(COk(Box::new(CBytes(vec![111, 108, 195, 161])))))
+        );
+        assert_eq!(
+            call("to_bytes", vec![text.clone(), CString("latin-1".to_string())]),
+            Ok(EnvValue::Exp(COk(Box::new(CBytes(vec![111, 108, 225])))))
+        );
+        assert_eq!(
+            call("to_bytes", vec![text, CString("ascii".to_string())]),
+            Ok(EnvValue::Exp(CErr(Box::new(CString(
+                "character 'á' cannot be encoded as ascii".to_string()
+            )))))
+        );
+        assert_eq!(
+            call(
+                "from_bytes",
+                vec![CBytes(vec![111, 108, 225]), CString("utf-8".to_string())]
+            ),
+            Ok(EnvValue::Exp(CErr(Box::new(CString(
+                "invalid utf-8 byte at position 2".to_string()
+            )))))
+        );
+        assert_eq!(
+            call(
+                "from_bytes",
+                vec![CBytes(vec![111, 108, 225]), CString("latin-1".to_string())]
+            ),
+            Ok(EnvValue::Exp(COk(Box::new(CString("olá".to_string())))))
+        );
+    }
+
+    #[test]
+    fn check_bytes_builtins() {
+        let env: Environment<Type> = Environment::new();
+
+        assert_eq!(check_exp(CBytes(vec![]), &env), Ok(TBytes));
+        assert_eq!(
+            check_exp(
+                FuncCall("slice".to_string(), vec![CBytes(vec![]), CInt(0), CInt(1)]),
+                &env
+            ),
+            Ok(TBytes)
+        );
+        assert_eq!(
+            check_exp(FuncCall("len".to_string(), vec![CInt(1)]), &env),
+            Err(String::from(
+                "[Type Error] 'len()' expects bytes, a string, a list or a dict, found 'TInteger'."
+            ))
+        );
+        assert_eq!(
+            check_exp(
+                FuncCall("byte_at".to_string(), vec![CString("a".to_string()), CInt(0)]),
+                &env
+            ),
+            Err(String::from(
+                "[Type Error] 'byte_at()' has mismatched arguments: expected 'TBytes', found 'TString'."
+            ))
+        );
+    }
+}
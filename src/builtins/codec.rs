@@ -0,0 +1,591 @@
+use crate::builtins::registry::{argument, expect_arity, mismatched, Builtin};
+use crate::interpreter::interpreter::{within_limits, EnvValue};
+use crate::ir::ast::{AdtValue, Environment, Expression, Type};
+use crate::ir::complex::Complex;
+use crate::ir::decimal::Decimal;
+
+type ErrorMessage = String;
+
+/*
+ * Binary format (version 1)
+ *
+ *   value := VERSION item
+ *   item  := tag payload
+ *
+ * Integers and lengths are LEB128 varints (integers are zigzag encoded
+ * first), reals are 8 bytes little-endian, strings are a length
+ * followed by their UTF-8 bytes and decimals are the string of their
+ * digits, as in `-1.50`. Complex numbers are their real and imaginary
+ * parts, as two reals. ADT values carry the ADT name, the
+ * constructor name, the constructor tag and their arguments, so decoding
+ * can check them against the declarations in scope.
+ */
+const VERSION: u8 = 1;
+
+const TAG_VOID: u8 = 0x00;
+const TAG_TRUE: u8 = 0x01;
+const TAG_FALSE: u8 = 0x02;
+const TAG_INT: u8 = 0x03;
+const TAG_REAL: u8 = 0x04;
+const TAG_STRING: u8 = 0x05;
+const TAG_LIST: u8 = 0x06;
+const TAG_DICT: u8 = 0x07;
+const TAG_JUST: u8 = 0x08;
+const TAG_NOTHING: u8 = 0x09;
+const TAG_OK: u8 = 0x0A;
+const TAG_ERR: u8 = 0x0B;
+const TAG_ADT: u8 = 0x0C;
+const TAG_BYTES: u8 = 0x0D;
+const TAG_DECIMAL: u8 = 0x0E;
+const TAG_COMPLEX: u8 = 0x0F;
+const TAG_TUPLE: u8 = 0x10;
+
+/// How deep items may nest inside one another. Decoding recurses once per
+/// level, so without a bound a crafted input could overflow the stack; the
+/// bound leaves room for decoding deep inside a running program.
+const MAX_DEPTH: usize = 128;
+
+pub const BUILTINS: &[Builtin] = &[
+    Builtin {
+        name: "encode",
+        check: check_encode,
+        eval: eval_encode,
+    },
+    Builtin {
+        name: "decode",
+        check: check_decode,
+        eval: eval_decode,
+    },
+];
+
+fn check_encode(args: &[Type], _env: &Environment<Type>) -> Result<Type, ErrorMessage> {
+    expect_arity("encode", args, 1)?;
+    Ok(Type::TString)
+}
+
+/// Encodes a value as base64 text, so it can be stored in a string.
+fn eval_encode(args: Vec<EnvValue>, _env: &Environment<EnvValue>) -> Result<EnvValue, ErrorMessage> {
+    let bytes = encode_value(argument("encode", &args, 0)?)?;
+    Ok(EnvValue::Exp(Expression::CString(base64_encode(&bytes))))
+}
+
+fn check_decode(args: &[Type], _env: &Environment<Type>) -> Result<Type, ErrorMessage> {
+    expect_arity("decode", args, 1)?;
+    if args[0] != Type::TString && args[0] != Type::TBytes {
+        return Err(format!(
+            "[Type Error] 'decode()' has mismatched arguments: expected 'TString' or 'TBytes', found '{:?}'.",
+            args[0]
+        ));
+    }
+    Ok(Type::TResult(Box::new(Type::TAny), Box::new(Type::TString)))
+}
+
+/// Decodes either the base64 text produced by `encode` or the raw encoded
+/// bytes. Malformed input is not a runtime error: it is reported as an `Err`.
+/// A decoded value over the limits is, as is any other value built over them.
+fn eval_decode(args: Vec<EnvValue>, env: &Environment<EnvValue>) -> Result<EnvValue, ErrorMessage> {
+    let bytes = match argument("decode", &args, 0)? {
+        EnvValue::Exp(Expression::CString(s)) => base64_decode(s),
+        EnvValue::Exp(Expression::CBytes(b)) => Ok(b.clone()),
+        other => return Err(mismatched("decode", "a string or bytes", other)),
+    };
+
+    let decoded = bytes
+        .and_then(|bytes| decode_value(&bytes))
+        .and_then(|exp| check_adts(&exp, env).map(|_| exp));
+
+    let result = match decoded {
+        Ok(e) => Expression::COk(Box::new(e)),
+        Err(e) => Expression::CErr(Box::new(Expression::CString(e))),
+    };
+    within_limits(EnvValue::Exp(result), env).map_err(|(e, _)| e)
+}
+
+// Decoded ADT values must agree with the declarations of the program.
+fn check_adts(exp: &Expression, env: &Environment<EnvValue>) -> Result<(), ErrorMessage> {
+    match exp {
+        Expression::ADTValue(value) => {
+            let constructor = env
+                .get_type(&value.adt)
+                .and_then(|constructors| constructors.get(value.tag))
+                .filter(|vc| vc.name == value.constructor && vc.types.len() == value.fields.len());
+            if constructor.is_none() {
+                return Err(format!(
+                    "constructor '{}' does not match the declaration of ADT '{}'",
+                    value.constructor, value.adt
+                ));
+            }
+            value.fields.iter().try_for_each(|arg| check_adts(arg, env))
+        }
+        Expression::CList(items) | Expression::CTuple(items) => items.iter().try_for_each(|item| check_adts(item, env)),
+        Expression::CDict(entries) => entries
+            .iter()
+            .try_for_each(|(k, v)| check_adts(k, env).and_then(|_| check_adts(v, env))),
+        Expression::CJust(e) | Expression::COk(e) | Expression::CErr(e) => check_adts(e, env),
+        _ => Ok(()),
+    }
+}
+
+pub fn encode_value(value: &EnvValue) -> Result<Vec<u8>, ErrorMessage> {
+    let mut out = vec![VERSION];
+    match value {
+        EnvValue::Exp(exp) => encode_expression(exp, &mut out, 0)?,
+        EnvValue::Func(func) => {
+            return Err(format!(
+                "[Runtime Error] function '{}' cannot be encoded.",
+                func.name
+            ))
+        }
+        EnvValue::TestEnvironment(_) => {
+            return Err(String::from(
+                "[Runtime Error] test modules cannot be encoded.",
+            ))
+        }
+    }
+    Ok(out)
+}
+
+pub fn decode_value(bytes: &[u8]) -> Result<Expression, ErrorMessage> {
+    let mut reader = Reader { bytes, pos: 0, depth: 0 };
+    let version = reader.byte()?;
+    if version != VERSION {
+        return Err(format!("unsupported encoding version {}", version));
+    }
+    let exp = reader.expression()?;
+    if reader.pos != bytes.len() {
+        return Err(String::from("trailing bytes after encoded value"));
+    }
+    Ok(exp)
+}
+
+/// Encodes `exp`, nested in `depth` items. Values nested deeper than the
+/// decoder accepts are refused, so whatever `encode` writes reads back.
+fn encode_expression(exp: &Expression, out: &mut Vec<u8>, depth: usize) -> Result<(), ErrorMessage> {
+    if depth == MAX_DEPTH {
+        return Err(format!(
+            "[Runtime Error] values nested deeper than {} levels cannot be encoded.",
+            MAX_DEPTH
+        ));
+    }
+    match exp {
+        Expression::CVoid => out.push(TAG_VOID),
+        Expression::CTrue => out.push(TAG_TRUE),
+        Expression::CFalse => out.push(TAG_FALSE),
+        Expression::CInt(v) => {
+            out.push(TAG_INT);
+            write_varint(((*v << 1) ^ (*v >> 31)) as u32 as u64, out);
+        }
+        Expression::CReal(v) => {
+            out.push(TAG_REAL);
+            out.extend_from_slice(&v.to_le_bytes());
+        }
+        Expression::CDecimal(d) => {
+            out.push(TAG_DECIMAL);
+            write_str(&d.to_string(), out);
+        }
+        Expression::CComplex(z) => {
+            out.push(TAG_COMPLEX);
+            out.extend_from_slice(&z.re.to_le_bytes());
+            out.extend_from_slice(&z.im.to_le_bytes());
+        }
+        Expression::CString(s) => {
+            out.push(TAG_STRING);
+            write_str(s, out);
+        }
+        Expression::CBytes(b) => {
+            out.push(TAG_BYTES);
+            write_varint(b.len() as u64, out);
+            out.extend_from_slice(b);
+        }
+        Expression::CList(items) | Expression::CTuple(items) => {
+            out.push(if let Expression::CList(_) = exp { TAG_LIST } else { TAG_TUPLE });
+            write_varint(items.len() as u64, out);
+            for item in items {
+                encode_expression(item, out, depth + 1)?;
+            }
+        }
+        Expression::CDict(entries) => {
+            out.push(TAG_DICT);
+            write_varint(entries.len() as u64, out);
+            for (k, v) in entries {
+                encode_expression(k, out, depth + 1)?;
+                encode_expression(v, out, depth + 1)?;
+            }
+        }
+        Expression::CJust(e) => {
+            out.push(TAG_JUST);
+            encode_expression(e, out, depth + 1)?;
+        }
+        Expression::CNothing => out.push(TAG_NOTHING),
+        Expression::COk(e) => {
+            out.push(TAG_OK);
+            encode_expression(e, out, depth + 1)?;
+        }
+        Expression::CErr(e) => {
+            out.push(TAG_ERR);
+            encode_expression(e, out, depth + 1)?;
+        }
+        Expression::ADTValue(value) => {
+            out.push(TAG_ADT);
+            write_str(&value.adt, out);
+            write_str(&value.constructor, out);
+            write_varint(value.tag as u64, out);
+            write_varint(value.fields.len() as u64, out);
+            for arg in &value.fields {
+                encode_expression(arg, out, depth + 1)?;
+            }
+        }
+        _ => {
+            return Err(format!(
+                "[Runtime Error] expression '{:?}' is not a value and cannot be encoded.",
+                exp
+            ))
+        }
+    }
+    Ok(())
+}
+
+fn write_varint(mut value: u64, out: &mut Vec<u8>) {
+    loop {
+        let byte = (value & 0x7F) as u8;
+        value >>= 7;
+        if value == 0 {
+            out.push(byte);
+            return;
+        }
+        out.push(byte | 0x80);
+    }
+}
+
+fn write_str(s: &str, out: &mut Vec<u8>) {
+    write_varint(s.len() as u64, out);
+    out.extend_from_slice(s.as_bytes());
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+    /// Items open around the one being read.
+    depth: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn byte(&mut self) -> Result<u8, ErrorMessage> {
+        let byte = *self
+            .bytes
+            .get(self.pos)
+            .ok_or_else(|| String::from("unexpected end of input"))?;
+        self.pos += 1;
+        Ok(byte)
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8], ErrorMessage> {
+        if self.bytes.len() - self.pos < len {
+            return Err(String::from("unexpected end of input"));
+        }
+        let slice = &self.bytes[self.pos..self.pos + len];
+        self.pos += len;
+        Ok(slice)
+    }
+
+    fn varint(&mut self) -> Result<u64, ErrorMessage> {
+        let mut value: u64 = 0;
+        for shift in (0..64).step_by(7) {
+            let byte = self.byte()?;
+            value |= ((byte & 0x7F) as u64) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err(String::from("varint is too long"))
+    }
+
+    fn length(&mut self) -> Result<usize, ErrorMessage> {
+        let len = self.varint()? as usize;
+        // Every encoded element takes at least one byte.
+        if len > self.bytes.len() - self.pos {
+            return Err(String::from("length exceeds the input size"));
+        }
+        Ok(len)
+    }
+
+    fn real(&mut self) -> Result<f64, ErrorMessage> {
+        let mut buf = [0u8; 8];
+        buf.copy_from_slice(self.take(8)?);
+        Ok(f64::from_le_bytes(buf))
+    }
+
+    fn string(&mut self) -> Result<String, ErrorMessage> {
+        let len = self.length()?;
+        String::from_utf8(self.take(len)?.to_vec()).map_err(|_| String::from("invalid UTF-8 string"))
+    }
+
+    fn expression(&mut self) -> Result<Expression, ErrorMessage> {
+        if self.depth == MAX_DEPTH {
+            return Err(format!("values nest deeper than {} levels", MAX_DEPTH));
+        }
+        self.depth += 1;
+        let exp = self.item();
+        self.depth -= 1;
+        exp
+    }
+
+    fn item(&mut self) -> Result<Expression, ErrorMessage> {
+        let tag = self.byte()?;
+        Ok(match tag {
+            TAG_VOID => Expression::CVoid,
+            TAG_TRUE => Expression::CTrue,
+            TAG_FALSE => Expression::CFalse,
+            TAG_INT => {
+                let zigzag = u32::try_from(self.varint()?)
+                    .map_err(|_| String::from("integer out of range"))?;
+                Expression::CInt(((zigzag >> 1) as i32) ^ -((zigzag & 1) as i32))
+            }
+            TAG_REAL => Expression::CReal(self.real()?),
+            TAG_DECIMAL => {
+                let digits = self.string()?;
+                let value = Decimal::parse(&digits).ok_or_else(|| format!("invalid decimal '{}'", digits))?;
+                Expression::CDecimal(value)
+            }
+            TAG_COMPLEX => Expression::CComplex(Complex::new(self.real()?, self.real()?)),
+            TAG_STRING => Expression::CString(self.string()?),
+            TAG_BYTES => {
+                let len = self.length()?;
+                Expression::CBytes(self.take(len)?.to_vec())
+            }
+            TAG_LIST | TAG_TUPLE => {
+                let len = self.length()?;
+                let mut items = Vec::with_capacity(len);
+                for _ in 0..len {
+                    items.push(self.expression()?);
+                }
+                match tag {
+                    TAG_LIST => Expression::CList(items),
+                    _ => Expression::CTuple(items),
+                }
+            }
+            TAG_DICT => {
+                let len = self.length()?;
+                let mut entries = Vec::with_capacity(len);
+                for _ in 0..len {
+                    entries.push((self.expression()?, self.expression()?));
+                }
+                Expression::CDict(entries)
+            }
+            TAG_JUST => Expression::CJust(Box::new(self.expression()?)),
+            TAG_NOTHING => Expression::CNothing,
+            TAG_OK => Expression::COk(Box::new(self.expression()?)),
+            TAG_ERR => Expression::CErr(Box::new(self.expression()?)),
+            TAG_ADT => {
+                let adt_name = self.string()?;
+                let constructor_name = self.string()?;
+                let constructor_tag = self.varint()? as usize;
+                let len = self.length()?;
+                let mut args = Vec::with_capacity(len);
+                for _ in 0..len {
+                    args.push(Box::new(self.expression()?));
+                }
+                Expression::ADTValue(AdtValue::new(adt_name, constructor_name, constructor_tag, args.into()))
+            }
+            _ => return Err(format!("unknown value tag 0x{:02X}", tag)),
+        })
+    }
+}
+
+const BASE64_ALPHABET: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+pub fn base64_encode(bytes: &[u8]) -> String {
+    let mut out = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let b = [
+            chunk[0],
+            *chunk.get(1).unwrap_or(&0),
+            *chunk.get(2).unwrap_or(&0),
+        ];
+        let n = ((b[0] as u32) << 16) | ((b[1] as u32) << 8) | b[2] as u32;
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(BASE64_ALPHABET[(n >> (18 - 6 * i) & 0x3F) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+pub fn base64_decode(text: &str) -> Result<Vec<u8>, ErrorMessage> {
+    let text = text.trim_end_matches('=');
+    let mut out = Vec::with_capacity(text.len() * 3 / 4);
+    let mut buffer: u32 = 0;
+    let mut bits = 0;
+
+    for c in text.bytes() {
+        let value = BASE64_ALPHABET
+            .iter()
+            .position(|&a| a == c)
+            .ok_or_else(|| format!("invalid base64 character '{}'", c as char))?;
+        buffer = (buffer << 6) | value as u32;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            out.push((buffer >> bits) as u8);
+            buffer &= (1 << bits) - 1;
+        }
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interpreter::interpreter::eval;
+    use crate::ir::ast::Expression::*;
+    use crate::ir::ast::Type::*;
+    use crate::ir::ast::{Function, Limits, ValueConstructor};
+
+    fn round_trip(exp: Expression) {
+        let value = EnvValue::Exp(exp);
+        let bytes = encode_value(&value).unwrap();
+        assert_eq!(decode_value(&bytes).map(EnvValue::Exp), Ok(value));
+    }
+
+    #[test]
+    fn values_round_trip() {
+        round_trip(CInt(0));
+        round_trip(CInt(-1));
+        round_trip(CInt(i32::MAX));
+        round_trip(CInt(i32::MIN));
+        round_trip(CReal(-2.5));
+        round_trip(CDecimal(Decimal::parse("-1.50").unwrap()));
+        round_trip(CComplex(Complex::new(1.5, -2.0)));
+        round_trip(CString("olá, mundo".to_string()));
+        round_trip(CBytes(vec![0, 255, 10]));
+        round_trip(CList(vec![CTrue, CFalse, CVoid]));
+        round_trip(CDict(vec![(CString("k".to_string()), CNothing)]));
+        round_trip(COk(Box::new(CJust(Box::new(CInt(7))))));
+        round_trip(CErr(Box::new(CString("e".to_string()))));
+        round_trip(ADTValue(AdtValue::new(
+            "Shape".to_string(),
+            "Circle".to_string(),
+            0,
+            vec![Box::new(CReal(1.0))].into(),
+        )));
+    }
+
+    #[test]
+    fn small_integers_are_compact() {
+        assert_eq!(
+            encode_value(&EnvValue::Exp(CInt(-1))),
+            Ok(vec![VERSION, TAG_INT, 0x01])
+        );
+    }
+
+    #[test]
+    fn malformed_input_is_rejected() {
+        assert_eq!(
+            decode_value(&[VERSION, TAG_STRING, 10, b'a']),
+            Err(String::from("length exceeds the input size"))
+        );
+        assert_eq!(
+            decode_value(&[2, TAG_TRUE]),
+            Err(String::from("unsupported encoding version 2"))
+        );
+        assert_eq!(
+            decode_value(&[VERSION, 0x7F]),
+            Err(String::from("unknown value tag 0x7F"))
+        );
+        assert!(encode_value(&EnvValue::Func(Function::new())).is_err());
+    }
+
+    #[test]
+    fn deeply_nested_input_is_rejected() {
+        let mut bytes = vec![VERSION];
+        bytes.extend(std::iter::repeat_n(TAG_JUST, 200_000));
+        bytes.push(TAG_VOID);
+        assert_eq!(decode_value(&bytes), Err(String::from("values nest deeper than 128 levels")));
+
+        let mut nested = CVoid;
+        for _ in 0..MAX_DEPTH - 1 {
+            nested = CJust(Box::new(nested));
+        }
+        round_trip(nested.clone());
+
+        // What the decoder would reject is not encoded in the first place
+        assert_eq!(
+            encode_value(&EnvValue::Exp(CJust(Box::new(nested)))),
+            Err(String::from("[Runtime Error] values nested deeper than 128 levels cannot be encoded."))
+        );
+    }
+
+    #[test]
+    fn decoded_values_are_within_the_limits() {
+        let mut env: Environment<EnvValue> = Environment::new();
+        let encoded = |exp: Expression| CBytes(encode_value(&EnvValue::Exp(exp)).unwrap());
+        let list = encoded(CList(vec![CInt(1), CInt(2), CInt(3)]));
+        let text = encoded(CString("abcd".to_string()));
+        env.limits = std::sync::Arc::new(Limits {
+            max_list_length: 2,
+            max_string_length: 3,
+            ..Limits::default()
+        });
+
+        assert_eq!(
+            eval(FuncCall("decode".to_string(), vec![list]), &env),
+            Err((String::from("[Resource Exhausted] list of 3 elements exceeds the limit of 2."), None))
+        );
+        assert_eq!(
+            eval(FuncCall("decode".to_string(), vec![text]), &env),
+            Err((String::from("[Resource Exhausted] string of 4 bytes exceeds the limit of 3."), None))
+        );
+    }
+
+    #[test]
+    fn base64_round_trip() {
+        for len in 0..8 {
+            let bytes: Vec<u8> = (0..len).map(|i| (i * 37 + 200) as u8).collect();
+            assert_eq!(base64_decode(&base64_encode(&bytes)), Ok(bytes));
+        }
+        assert_eq!(base64_encode(b"Man"), "TWFu");
+        assert_eq!(base64_encode(b"Ma"), "TWE=");
+    }
+
+    #[test]
+    fn decode_checks_adts_in_scope() {
+        let mut env: Environment<EnvValue> = Environment::new();
+        env.insert_type(
+            "Shape".to_string(),
+            vec![ValueConstructor {
+                name: "Circle".to_string(),
+                types: vec![TReal],
+            }],
+        );
+        let circle = ADTValue(AdtValue::new(
+            "Shape".to_string(),
+            "Circle".to_string(),
+            0,
+            vec![Box::new(CReal(2.0))].into(),
+        ));
+        let square = ADTValue(AdtValue::new("Shape".to_string(), "Square".to_string(), 1, vec![].into()));
+
+        let decode = |exp: Expression| {
+            let text = match eval(FuncCall("encode".to_string(), vec![exp]), &env) {
+                Ok(EnvValue::Exp(text)) => text,
+                other => panic!("{:?}", other),
+            };
+            eval(FuncCall("decode".to_string(), vec![text]), &env)
+        };
+
+        assert_eq!(
+            decode(circle.clone()),
+            Ok(EnvValue::Exp(COk(Box::new(circle))))
+        );
+        assert_eq!(
+            decode(square),
+            Ok(EnvValue::Exp(CErr(Box::new(CString(
+                "constructor 'Square' does not match the declaration of ADT 'Shape'".to_string()
+            )))))
+        );
+    }
+}
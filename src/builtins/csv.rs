@@ -0,0 +1,243 @@
+use crate::builtins::registry::{argument, expect_arity, mismatched, Builtin};
+use crate::interpreter::interpreter::EnvValue;
+use crate::ir::ast::{Environment, Expression, Type};
+
+type ErrorMessage = String;
+
+pub const BUILTINS: &[Builtin] = &[
+    Builtin {
+        name: "parse_csv",
+        check: check_parse_csv,
+        eval: eval_parse_csv,
+    },
+    Builtin {
+        name: "write_csv",
+        check: check_write_csv,
+        eval: eval_write_csv,
+    },
+];
+
+fn rows_type() -> Type {
+    Type::TList(Box::new(Type::TList(Box::new(Type::TString))))
+}
+
+fn check_parse_csv(args: &[Type], _env: &Environment<Type>) -> Result<Type, ErrorMessage> {
+    expect_arity("parse_csv", args, 1)?;
+    if args[0] != Type::TString {
+        return Err(format!(
+            "[Type Error] 'parse_csv()' has mismatched arguments: expected 'TString', found '{:?}'.",
+            args[0]
+        ));
+    }
+    Ok(rows_type())
+}
+
+fn eval_parse_csv(args: Vec<EnvValue>, _env: &Environment<EnvValue>) -> Result<EnvValue, ErrorMessage> {
+    let text = match argument("parse_csv", &args, 0)? {
+        EnvValue::Exp(Expression::CString(s)) => s,
+        other => return Err(mismatched("parse_csv", "a string", other)),
+    };
+
+    let rows = parse(text)?;
+    Ok(EnvValue::Exp(Expression::CList(
+        rows.into_iter()
+            .map(|row| Expression::CList(row.into_iter().map(Expression::CString).collect()))
+            .collect(),
+    )))
+}
+
+/// An empty list literal types as `TList(TAny)`, so it is accepted too.
+fn check_write_csv(args: &[Type], _env: &Environment<Type>) -> Result<Type, ErrorMessage> {
+    expect_arity("write_csv", args, 1)?;
+    match &args[0] {
+        Type::TList(row) => match row.as_ref() {
+            Type::TAny => Ok(Type::TString),
+            Type::TList(field) if matches!(field.as_ref(), Type::TString | Type::TAny) => {
+                Ok(Type::TString)
+            }
+            _ => Err(format!(
+                "[Type Error] 'write_csv()' has mismatched arguments: expected '{:?}', found '{:?}'.",
+                rows_type(),
+                args[0]
+            )),
+        },
+        _ => Err(format!(
+            "[Type Error] 'write_csv()' has mismatched arguments: expected '{:?}', found '{:?}'.",
+            rows_type(),
+            args[0]
+        )),
+    }
+}
+
+fn eval_write_csv(args: Vec<EnvValue>, _env: &Environment<EnvValue>) -> Result<EnvValue, ErrorMessage> {
+    let value = argument("write_csv", &args, 0)?;
+    let rows = match value {
+        EnvValue::Exp(Expression::CList(rows)) => rows,
+        _ => return Err(mismatched("write_csv", "a list of lists of strings", value)),
+    };
+
+    let mut output = String::new();
+    for row in rows {
+        let fields = match row {
+            Expression::CList(fields) => fields,
+            _ => return Err(mismatched("write_csv", "a list of lists of strings", value)),
+        };
+        let line: Vec<String> = fields
+            .iter()
+            .map(|field| match field {
+                Expression::CString(s) => Ok(quote(s)),
+                _ => Err(mismatched("write_csv", "a list of lists of strings", value)),
+            })
+            .collect::<Result<_, _>>()?;
+        output.push_str(&line.join(","));
+        output.push('\n');
+    }
+    Ok(EnvValue::Exp(Expression::CString(output)))
+}
+
+/// Fields are only quoted when they have to be, following RFC 4180.
+fn quote(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+/// RFC 4180 parsing: quoted fields may contain commas, newlines and doubled
+/// quotes, and both `\n` and `\r\n` end a record. A trailing newline does
+/// not start an empty record.
+fn parse(text: &str) -> Result<Vec<Vec<String>>, ErrorMessage> {
+    let mut rows = vec![];
+    let mut row = vec![];
+    let mut field = String::new();
+    let mut chars = text.chars().peekable();
+    let mut line = 1;
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if field.is_empty() => {
+                let start = line;
+                loop {
+                    match chars.next() {
+                        Some('"') if chars.peek() == Some(&'"') => {
+                            chars.next();
+                            field.push('"');
+                        }
+                        Some('"') => break,
+                        Some(c) => {
+                            if c == '\n' {
+                                line += 1;
+                            }
+                            field.push(c);
+                        }
+                        None => {
+                            return Err(format!(
+                                "[Runtime Error] unterminated quoted field starting on line {}.",
+                                start
+                            ))
+                        }
+                    }
+                }
+            }
+            ',' => row.push(std::mem::take(&mut field)),
+            '\r' if chars.peek() == Some(&'\n') => {}
+            '\n' => {
+                row.push(std::mem::take(&mut field));
+                rows.push(std::mem::take(&mut row));
+                line += 1;
+            }
+            c => field.push(c),
+        }
+    }
+
+    if !field.is_empty() || !row.is_empty() {
+        row.push(field);
+        rows.push(row);
+    }
+    Ok(rows)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interpreter::interpreter::eval;
+    use crate::ir::ast::Expression::*;
+    use crate::tc::type_checker::check_exp;
+
+    fn strings(fields: &[&str]) -> Vec<String> {
+        fields.iter().map(|f| f.to_string()).collect()
+    }
+
+    #[test]
+    fn parse_handles_quotes_and_line_endings() {
+        assert_eq!(
+            parse("name,age\r\n\"Silva, Ana\",31\n\"say \"\"hi\"\"\",\"a\nb\"\n"),
+            Ok(vec![
+                strings(&["name", "age"]),
+                strings(&["Silva, Ana", "31"]),
+                strings(&["say \"hi\"", "a\nb"]),
+            ])
+        );
+        assert_eq!(parse("a,,\n"), Ok(vec![strings(&["a", "", ""])]));
+        assert_eq!(parse(""), Ok(vec![]));
+        assert_eq!(
+            parse("a\n\"b,c\n"),
+            Err(String::from(
+                "[Runtime Error] unterminated quoted field starting on line 2."
+            ))
+        );
+    }
+
+    #[test]
+    fn write_csv_round_trips_through_parse_csv() {
+        let env: Environment<EnvValue> = Environment::new();
+        let rows = CList(vec![
+            CList(vec![CString("id".to_string()), CString("note".to_string())]),
+            CList(vec![CString("1".to_string()), CString("a, \"b\"".to_string())]),
+        ]);
+
+        let written = eval(FuncCall("write_csv".to_string(), vec![rows.clone()]), &env);
+        assert_eq!(
+            written,
+            Ok(EnvValue::Exp(CString(
+                "id,note\n1,\"a, \"\"b\"\"\"\n".to_string()
+            )))
+        );
+
+        let text = match written {
+            Ok(EnvValue::Exp(text)) => text,
+            _ => unreachable!(),
+        };
+        assert_eq!(
+            eval(FuncCall("parse_csv".to_string(), vec![text]), &env),
+            Ok(EnvValue::Exp(rows))
+        );
+    }
+
+    #[test]
+    fn check_csv_builtins() {
+        let env: Environment<Type> = Environment::new();
+
+        assert_eq!(
+            check_exp(
+                FuncCall("parse_csv".to_string(), vec![CString("a,b".to_string())]),
+                &env
+            ),
+            Ok(rows_type())
+        );
+        assert_eq!(
+            check_exp(FuncCall("write_csv".to_string(), vec![CList(vec![])]), &env),
+            Ok(Type::TString)
+        );
+        assert_eq!(
+            check_exp(
+                FuncCall("write_csv".to_string(), vec![CList(vec![CInt(1)])]),
+                &env
+            ),
+            Err(String::from(
+                "[Type Error] 'write_csv()' has mismatched arguments: expected 'TList(TList(TString))', found 'TList(TInteger)'."
+            ))
+        );
+    }
+}
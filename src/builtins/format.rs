@@ -0,0 +1,240 @@
+use crate::builtins::registry::{argument, mismatched, Builtin};
+use crate::interpreter::interpreter::EnvValue;
+use crate::interpreter::value::{render, type_name};
+use crate::ir::ast::{Environment, Expression, Type};
+
+type ErrorMessage = String;
+
+pub const BUILTINS: &[Builtin] = &[Builtin {
+    name: "format",
+    check: check_format,
+    eval: eval_format,
+}];
+
+/// `format(spec, v1, ..., vn)` takes a string and any number of values of
+/// any type; whether they fit the placeholders of `spec` is only known
+/// when it runs.
+fn check_format(args: &[Type], _env: &Environment<Type>) -> Result<Type, ErrorMessage> {
+    match args.first() {
+        Some(Type::TString) => Ok(Type::TString),
+        Some(other) => Err(format!(
+            "[Type Error] 'format()' expects a string as its first argument, found '{:?}'.",
+            other
+        )),
+        None => Err(String::from(
+            "[Type Error] 'format()' expects a string as its first argument.",
+        )),
+    }
+}
+
+/// Replaces each `{}` of the spec with the next value, written as `print`
+/// writes it. A placeholder may say how, as in `{:>8.2}`: an optional fill
+/// character and alignment (`<`, `>` or `^`), a minimum width and a
+/// precision, the digits of a real or the characters kept of a string.
+/// Numbers align right unless told otherwise, everything else left; `{{`
+/// and `}}` stand for the braces themselves.
+fn eval_format(args: Vec<EnvValue>, _env: &Environment<EnvValue>) -> Result<EnvValue, ErrorMessage> {
+    let spec = match argument("format", &args, 0)? {
+        EnvValue::Exp(Expression::CString(spec)) => spec.clone(),
+        other => return Err(mismatched("format", "a string", other)),
+    };
+    let values: Vec<EnvValue> = args.into_iter().skip(1).collect();
+
+    let mut out = String::new();
+    let mut used = 0;
+    let mut chars = spec.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '{' if chars.peek() == Some(&'{') => {
+                chars.next();
+                out.push('{');
+            }
+            '}' if chars.peek() == Some(&'}') => {
+                chars.next();
+                out.push('}');
+            }
+            '{' => {
+                let mut placeholder = String::new();
+                loop {
+                    match chars.next() {
+                        Some('}') => break,
+                        Some(c) => placeholder.push(c),
+                        None => return Err(invalid(&format!("{{{}", placeholder), "is never closed")),
+                    }
+                }
+                let Some(value) = values.get(used) else {
+                    return Err(format!(
+                        "[Runtime Error] 'format()' has more placeholders than the {} values it was given.",
+                        values.len()
+                    ));
+                };
+                used += 1;
+                let text = match placeholder.strip_prefix(':') {
+                    Some(options) => match Placeholder::parse(options) {
+                        Some(placeholder) => placeholder.apply(value)?,
+                        None => return Err(invalid(&format!("{{{}}}", placeholder), "is not a valid placeholder")),
+                    },
+                    None if placeholder.is_empty() => Placeholder::default().apply(value)?,
+                    None => return Err(invalid(&format!("{{{}}}", placeholder), "is not a valid placeholder")),
+                };
+                out.push_str(&text);
+            }
+            '}' => return Err(invalid("}", "has no '{' before it; write '}}' for a brace")),
+            c => out.push(c),
+        }
+    }
+
+    if used < values.len() {
+        return Err(format!(
+            "[Runtime Error] 'format()' was given {} values, but the spec only has {} placeholders.",
+            values.len(),
+            used
+        ));
+    }
+    Ok(EnvValue::Exp(Expression::CString(out)))
+}
+
+fn invalid(text: &str, problem: &str) -> ErrorMessage {
+    format!("[Runtime Error] 'format()': '{}' {}.", text, problem)
+}
+
+#[derive(Default)]
+struct Placeholder {
+    fill: Option<char>,
+    align: Option<char>,
+    width: usize,
+    precision: Option<usize>,
+}
+
+impl Placeholder {
+    /// Reads the options after the `:` of a placeholder, as in `*^10.3`.
+    fn parse(options: &str) -> Option<Placeholder> {
+        let chars: Vec<char> = options.chars().collect();
+        let is_align = |c: Option<&char>| c.is_some_and(|c| "<>^".contains(*c));
+        let (fill, align, rest) = if is_align(chars.get(1)) {
+            (Some(chars[0]), Some(chars[1]), &chars[2..])
+        } else if is_align(chars.first()) {
+            (None, Some(chars[0]), &chars[1..])
+        } else {
+            (None, None, &chars[..])
+        };
+
+        let digits = |chars: &[char]| chars.iter().take_while(|c| c.is_ascii_digit()).count();
+        let width_digits = digits(rest);
+        let width = match width_digits {
+            0 => 0,
+            n => rest[..n].iter().collect::<String>().parse().ok()?,
+        };
+        let precision = match &rest[width_digits..] {
+            [] => None,
+            ['.', tail @ ..] if !tail.is_empty() && digits(tail) == tail.len() => {
+                Some(tail.iter().collect::<String>().parse().ok()?)
+            }
+            _ => return None,
+        };
+        Some(Placeholder { fill, align, width, precision })
+    }
+
+    fn apply(&self, value: &EnvValue) -> Result<String, ErrorMessage> {
+        let numeric = matches!(
+            value,
+            EnvValue::Exp(Expression::CInt(_) | Expression::CReal(_) | Expression::CDecimal(_))
+        );
+        let text = match (value, self.precision) {
+            (EnvValue::Exp(Expression::CReal(x)), Some(digits)) => format!("{:.*}", digits, x),
+            (EnvValue::Exp(Expression::CString(s)), Some(length)) => s.chars().take(length).collect(),
+            (EnvValue::Exp(Expression::CString(s)), None) => s.clone(),
+            (value, Some(digits)) => {
+                return Err(format!(
+                    "[Runtime Error] 'format()' cannot write a value of type '{}' with precision {}; only reals and strings take one.",
+                    type_name(value),
+                    digits
+                ))
+            }
+            (value, None) => render(value),
+        };
+
+        let padding = self.width.saturating_sub(text.chars().count());
+        let fill = |n: usize| std::iter::repeat_n(self.fill.unwrap_or(' '), n).collect::<String>();
+        Ok(match self.align.unwrap_or(if numeric { '>' } else { '<' }) {
+            '>' => format!("{}{}", fill(padding), text),
+            '^' => format!("{}{}{}", fill(padding / 2), text, fill(padding - padding / 2)),
+            _ => format!("{}{}", text, fill(padding)),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interpreter::interpreter::eval;
+    use crate::ir::ast::Expression::*;
+    use crate::tc::type_checker::check_exp;
+
+    fn format(args: Vec<Expression>) -> Result<String, String> {
+        let env: Environment<EnvValue> = Environment::new();
+        match eval(FuncCall("format".to_string(), args), &env) {
+            Ok(EnvValue::Exp(CString(text))) => Ok(text),
+            Ok(other) => panic!("expected a string, found {:?}", other),
+            Err((message, _)) => Err(message),
+        }
+    }
+
+    fn text(s: &str) -> Expression {
+        CString(s.to_string())
+    }
+
+    #[test]
+    fn format_pads_aligns_and_rounds() {
+        let row = |name: &str, price: f64| format(vec![text("|{:<6}|{:>8.2}|"), text(name), CReal(price)]);
+        assert_eq!(row("apple", 1.5), Ok(String::from("|apple |    1.50|")));
+        assert_eq!(row("kiwi", 12.345), Ok(String::from("|kiwi  |   12.35|")));
+
+        assert_eq!(format(vec![text("{} + {} = {}"), CInt(1), CInt(2), CInt(3)]), Ok(String::from("1 + 2 = 3")));
+        assert_eq!(format(vec![text("[{:4}] [{:4}]"), CInt(7), text("ab")]), Ok(String::from("[   7] [ab  ]")));
+        assert_eq!(format(vec![text("{:*^7}"), text("mid")]), Ok(String::from("**mid**")));
+        assert_eq!(format(vec![text("{:.3}"), text("truncated")]), Ok(String::from("tru")));
+        assert_eq!(format(vec![text("{{{}}}"), CTrue]), Ok(String::from("{True}")));
+        assert_eq!(format(vec![text("{}"), CList(vec![text("a")])]), Ok(String::from("[\"a\"]")));
+    }
+
+    #[test]
+    fn format_reports_specs_that_do_not_fit_its_values() {
+        assert_eq!(
+            format(vec![text("{} and {}"), CInt(1)]),
+            Err(String::from("[Runtime Error] 'format()' has more placeholders than the 1 values it was given."))
+        );
+        assert_eq!(
+            format(vec![text("{}"), CInt(1), CInt(2)]),
+            Err(String::from("[Runtime Error] 'format()' was given 2 values, but the spec only has 1 placeholders."))
+        );
+        assert_eq!(
+            format(vec![text("{:>x}"), CInt(1)]),
+            Err(String::from("[Runtime Error] 'format()': '{:>x}' is not a valid placeholder."))
+        );
+        assert_eq!(
+            format(vec![text("{:"), CInt(1)]),
+            Err(String::from("[Runtime Error] 'format()': '{:' is never closed."))
+        );
+        assert_eq!(
+            format(vec![text("}"), CInt(1)]),
+            Err(String::from("[Runtime Error] 'format()': '}' has no '{' before it; write '}}' for a brace."))
+        );
+        assert_eq!(
+            format(vec![text("{:.2}"), CInt(1)]),
+            Err(String::from(
+                "[Runtime Error] 'format()' cannot write a value of type 'Int' with precision 2; only reals and strings take one."
+            ))
+        );
+    }
+
+    #[test]
+    fn check_format_needs_a_string_spec() {
+        let env: Environment<Type> = Environment::new();
+        assert_eq!(check_exp(FuncCall("format".to_string(), vec![text("{}"), CReal(1.0)]), &env), Ok(Type::TString));
+        assert_eq!(
+            check_exp(FuncCall("format".to_string(), vec![CInt(1)]), &env),
+            Err(String::from("[Type Error] 'format()' expects a string as its first argument, found 'TInteger'."))
+        );
+    }
+}
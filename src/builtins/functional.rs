@@ -0,0 +1,339 @@
+use crate::builtins::registry::{argument, expect_arity, mismatched, Builtin};
+use crate::interpreter::interpreter::EnvValue;
+use crate::ir::ast::{Environment, Expression, Function, Statement, Type};
+
+type ErrorMessage = String;
+
+pub const BUILTINS: &[Builtin] = &[
+    Builtin {
+        name: "partial",
+        check: check_partial,
+        eval: eval_partial,
+    },
+    Builtin {
+        name: "enumerate",
+        check: check_enumerate,
+        eval: eval_enumerate,
+    },
+    Builtin {
+        name: "zip",
+        check: check_zip,
+        eval: eval_zip,
+    },
+];
+
+/// `partial(f, a1, ..., ak)` has the type of `f` without its first `k`
+/// parameters.
+fn check_partial(args: &[Type], _env: &Environment<Type>) -> Result<Type, ErrorMessage> {
+    let (kind, params) = match args.first() {
+        Some(Type::TFunction(kind, params)) => (kind, params),
+        Some(other) => {
+            return Err(format!(
+                "[Type Error] 'partial()' expects a function as its first argument, found '{:?}'.",
+                other
+            ))
+        }
+        None => {
+            return Err(String::from(
+                "[Type Error] 'partial()' expects a function as its first argument.",
+            ))
+        }
+    };
+
+    let bound = &args[1..];
+    if bound.len() > params.len() {
+        return Err(format!(
+            "[Type Error] 'partial()' binds {} arguments, but the function takes {}.",
+            bound.len(),
+            params.len()
+        ));
+    }
+    for (arg, param) in bound.iter().zip(params) {
+        if arg != param {
+            return Err(format!(
+                "[Type Error] 'partial()' has mismatched arguments: expected '{:?}', found '{:?}'.",
+                param, arg
+            ));
+        }
+    }
+
+    Ok(Type::TFunction(kind.clone(), params[bound.len()..].to_vec()))
+}
+
+/// The result is an ordinary closure: its body calls the original function
+/// with the bound values, which are captured under names no program can
+/// write, followed by the remaining parameters.
+fn eval_partial(args: Vec<EnvValue>, _env: &Environment<EnvValue>) -> Result<EnvValue, ErrorMessage> {
+    let (func, target) = match argument("partial", &args, 0)? {
+        EnvValue::Func(func) => (func.clone(), Expression::Closure(Box::new(func.clone()), vec![])),
+        EnvValue::Exp(closure @ Expression::Closure(func, _)) => (*func.clone(), closure.clone()),
+        other => return Err(mismatched("partial", "a function", other)),
+    };
+
+    let target_name = format!("<partial {}>", func.name);
+    let mut captured = vec![(target_name.clone(), target)];
+    let mut call_args = vec![];
+
+    for (i, arg) in args.into_iter().skip(1).enumerate() {
+        let name = format!("<partial arg {}>", i);
+        match arg {
+            EnvValue::Exp(e) => captured.push((name.clone(), e)),
+            EnvValue::Func(f) => captured.push((name.clone(), Expression::Closure(Box::new(f), vec![]))),
+            EnvValue::TestEnvironment(_) => {
+                return Err(String::from("[Runtime Error] 'partial()' cannot bind a test module."))
+            }
+        }
+        call_args.push(Expression::Var(name));
+    }
+
+    let params = func.params.clone().unwrap_or_default();
+    if call_args.len() > params.len() {
+        return Err(format!(
+            "[Runtime Error] 'partial()' binds {} arguments, but '{}' takes {}.",
+            call_args.len(),
+            func.name,
+            params.len()
+        ));
+    }
+    let remaining = params[call_args.len()..].to_vec();
+    call_args.extend(remaining.iter().map(|(name, _)| Expression::Var(name.clone())));
+
+    let partial = Function {
+        name: format!("partial_{}", func.name),
+        kind: func.kind.clone(),
+        params: Some(remaining),
+        body: Some(Box::new(Statement::Return(Box::new(Expression::FuncCall(
+            target_name,
+            call_args,
+        ))))),
+    };
+
+    Ok(EnvValue::Exp(Expression::Closure(Box::new(partial), captured)))
+}
+
+/// `enumerate(xs)` pairs each element of `xs` with its position:
+/// `[a]` becomes `[(TInteger, a)]`.
+fn check_enumerate(args: &[Type], _env: &Environment<Type>) -> Result<Type, ErrorMessage> {
+    expect_arity("enumerate", args, 1)?;
+    let item = list_item("enumerate", &args[0])?;
+    Ok(Type::TList(Box::new(Type::TTuple(vec![Type::TInteger, item]))))
+}
+
+/// `zip(xs, ys)` pairs the elements of two lists: `[a]` and `[b]` become
+/// `[(a, b)]`.
+fn check_zip(args: &[Type], _env: &Environment<Type>) -> Result<Type, ErrorMessage> {
+    expect_arity("zip", args, 2)?;
+    let left = list_item("zip", &args[0])?;
+    let right = list_item("zip", &args[1])?;
+    Ok(Type::TList(Box::new(Type::TTuple(vec![left, right]))))
+}
+
+fn list_item(name: &str, kind: &Type) -> Result<Type, ErrorMessage> {
+    match kind {
+        Type::TList(item) => Ok(*item.clone()),
+        other => Err(format!(
+            "[Type Error] '{}()' expects lists, found '{:?}'.",
+            name, other
+        )),
+    }
+}
+
+fn eval_enumerate(args: Vec<EnvValue>, _env: &Environment<EnvValue>) -> Result<EnvValue, ErrorMessage> {
+    let pairs = list_items("enumerate", &args, 0)?
+        .iter()
+        .enumerate()
+        .map(|(i, item)| Expression::CTuple(vec![Expression::CInt(i as i32), item.clone()]))
+        .collect();
+    Ok(EnvValue::Exp(Expression::CList(pairs)))
+}
+
+/// The pairs stop with the shorter list.
+fn eval_zip(args: Vec<EnvValue>, _env: &Environment<EnvValue>) -> Result<EnvValue, ErrorMessage> {
+    let pairs = list_items("zip", &args, 0)?
+        .iter()
+        .zip(list_items("zip", &args, 1)?)
+        .map(|(left, right)| Expression::CTuple(vec![left.clone(), right.clone()]))
+        .collect();
+    Ok(EnvValue::Exp(Expression::CList(pairs)))
+}
+
+fn list_items<'a>(name: &str, args: &'a [EnvValue], i: usize) -> Result<&'a [Expression], ErrorMessage> {
+    match argument(name, args, i)? {
+        EnvValue::Exp(Expression::CList(items)) => Ok(items),
+        other => Err(mismatched(name, "a list", other)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interpreter::interpreter::{eval, run, ControlFlow};
+    use crate::ir::ast::Expression::*;
+    use crate::ir::ast::Pattern;
+    use crate::ir::ast::Statement::*;
+    use crate::ir::ast::Type::*;
+    use crate::tc::type_checker::check_exp;
+
+    fn sub() -> Function {
+        Function {
+            name: "sub".to_string(),
+            kind: Some(TInteger),
+            params: Some(vec![("a".to_string(), TInteger), ("b".to_string(), TInteger)]),
+            body: Some(Box::new(Return(Box::new(Sub(
+                Box::new(Var("a".to_string())),
+                Box::new(Var("b".to_string())),
+            ))))),
+        }
+    }
+
+    #[test]
+    fn partial_fixes_leading_arguments() {
+        let env: Environment<EnvValue> = Environment::new();
+        let program = Sequence(
+            Box::new(FuncDef(sub())),
+            Box::new(Sequence(
+                Box::new(Assignment(
+                    "ten_minus".to_string(),
+                    Box::new(FuncCall(
+                        "partial".to_string(),
+                        vec![Var("sub".to_string()), CInt(10)],
+                    )),
+                    None,
+                )),
+                Box::new(Assignment(
+                    "r".to_string(),
+                    Box::new(FuncCall("ten_minus".to_string(), vec![CInt(3)])),
+                    None,
+                )),
+            )),
+        );
+
+        match run(program, &env) {
+            Ok(ControlFlow::Continue(new_env)) => assert_eq!(
+                new_env.search_frame("r".to_string()),
+                Some(&EnvValue::Exp(CInt(7)))
+            ),
+            Ok(ControlFlow::Return(_)) => panic!("unexpected return"),
+            Err(s) => panic!("{}", s),
+        }
+    }
+
+    #[test]
+    fn check_partial_drops_bound_parameters() {
+        let mut env: Environment<Type> = Environment::new();
+        env.insert_variable(
+            "sub".to_string(),
+            TFunction(Box::new(Some(TInteger)), vec![TInteger, TInteger]),
+        );
+
+        assert_eq!(
+            check_exp(
+                FuncCall("partial".to_string(), vec![Var("sub".to_string()), CInt(1)]),
+                &env
+            ),
+            Ok(TFunction(Box::new(Some(TInteger)), vec![TInteger]))
+        );
+        assert_eq!(
+            check_exp(
+                FuncCall("partial".to_string(), vec![Var("sub".to_string()), CTrue]),
+                &env
+            ),
+            Err(String::from(
+                "[Type Error] 'partial()' has mismatched arguments: expected 'TInteger', found 'TBool'."
+            ))
+        );
+        assert_eq!(
+            check_exp(
+                FuncCall(
+                    "partial".to_string(),
+                    vec![Var("sub".to_string()), CInt(1), CInt(2), CInt(3)]
+                ),
+                &env
+            ),
+            Err(String::from(
+                "[Type Error] 'partial()' binds 3 arguments, but the function takes 2."
+            ))
+        );
+    }
+
+    #[test]
+    fn enumerate_and_zip_pair_up_list_elements() {
+        let mut env: Environment<EnvValue> = Environment::new();
+        let words = CList(vec![CString("a".to_string()), CString("b".to_string())]);
+        env.insert_variable("words".to_string(), EnvValue::Exp(words));
+        env.insert_variable("found".to_string(), EnvValue::Exp(CString(String::new())));
+        let call = |name: &str, args: Vec<Expression>| eval(FuncCall(name.to_string(), args), &env).map_err(|(e, _)| e);
+        let pair = |left: Expression, right: Expression| CTuple(vec![left, right]);
+
+        assert_eq!(
+            call("enumerate", vec![Var("words".to_string())]),
+            Ok(EnvValue::Exp(CList(vec![
+                pair(CInt(0), CString("a".to_string())),
+                pair(CInt(1), CString("b".to_string())),
+            ])))
+        );
+        assert_eq!(
+            call("zip", vec![CList(vec![CInt(1), CInt(2), CInt(3)]), Var("words".to_string())]),
+            Ok(EnvValue::Exp(CList(vec![
+                pair(CInt(1), CString("a".to_string())),
+                pair(CInt(2), CString("b".to_string())),
+            ])))
+        );
+
+        let var = |name: &str| Pattern::Variable(name.to_string());
+        let second = Pattern::List(vec![Pattern::Wildcard, Pattern::Tuple(vec![var("i"), var("word")])]);
+        let arms = vec![(second, None, Box::new(Assignment("found".to_string(), Box::new(Var("word".to_string())), None)))];
+        let program = Match(Box::new(FuncCall("enumerate".to_string(), vec![Var("words".to_string())])), arms);
+        match run(program, &env) {
+            Ok(ControlFlow::Continue(new_env)) => assert_eq!(
+                new_env.search_frame("found".to_string()),
+                Some(&EnvValue::Exp(CString("b".to_string())))
+            ),
+            _ => panic!("expected the second pair to match"),
+        }
+    }
+
+    #[test]
+    fn pairs_key_dictionaries() {
+        let env: Environment<EnvValue> = Environment::new();
+        let pair = |left: Expression, right: Expression| CTuple(vec![left, right]);
+        let dict = CDict(vec![
+            (pair(CInt(1), CInt(2)), CString("a".to_string())),
+            (pair(CInt(2), CInt(1)), CString("b".to_string())),
+            (pair(CInt(1), CInt(2)), CString("c".to_string())),
+        ]);
+
+        assert_eq!(
+            eval(dict, &env),
+            Ok(EnvValue::Exp(CDict(vec![
+                (pair(CInt(1), CInt(2)), CString("c".to_string())),
+                (pair(CInt(2), CInt(1)), CString("b".to_string())),
+            ])))
+        );
+        assert_eq!(
+            eval(CDict(vec![(pair(CInt(1), CReal(0.5)), CVoid)]), &env).map_err(|(e, _)| e),
+            Err(String::from("[Runtime Error] unhashable type: real numbers cannot be used as keys."))
+        );
+    }
+
+    #[test]
+    fn check_enumerate_and_zip_are_generic() {
+        let mut env: Environment<Type> = Environment::new();
+        env.insert_variable("words".to_string(), TList(Box::new(TString)));
+        env.insert_variable("flags".to_string(), TList(Box::new(TBool)));
+        let check = |name: &str, args: Vec<&str>| {
+            check_exp(FuncCall(name.to_string(), args.into_iter().map(|arg| Var(arg.to_string())).collect()), &env)
+        };
+
+        assert_eq!(check("enumerate", vec!["words"]), Ok(TList(Box::new(TTuple(vec![TInteger, TString])))));
+        assert_eq!(check("zip", vec!["flags", "words"]), Ok(TList(Box::new(TTuple(vec![TBool, TString])))));
+        assert_eq!(
+            check_exp(FuncCall("zip".to_string(), vec![Var("words".to_string()), CInt(1)]), &env),
+            Err(String::from("[Type Error] 'zip()' expects lists, found 'TInteger'."))
+        );
+        assert_eq!(
+            check("enumerate", vec![]),
+            Err(String::from("[Type Error] 'enumerate()' expected 1 arguments, found 0."))
+        );
+    }
+}
@@ -0,0 +1,262 @@
+use crate::builtins::registry::{argument, expect_arity, mismatched, Builtin};
+use crate::interpreter::interpreter::EnvValue;
+use crate::ir::ast::{Environment, Expression, Type};
+
+use std::io::{Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::Duration;
+
+type ErrorMessage = String;
+
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+pub const BUILTINS: &[Builtin] = &[
+    Builtin {
+        name: "http_get",
+        check: check_http_get,
+        eval: eval_http_get,
+    },
+    Builtin {
+        name: "http_post",
+        check: check_http_post,
+        eval: eval_http_post,
+    },
+];
+
+fn expect_strings(name: &str, args: &[Type], arity: usize) -> Result<(), ErrorMessage> {
+    expect_arity(name, args, arity)?;
+    match args.iter().find(|arg| **arg != Type::TString) {
+        Some(arg) => Err(format!(
+            "[Type Error] '{}()' has mismatched arguments: expected 'TString', found '{:?}'.",
+            name, arg
+        )),
+        None => Ok(()),
+    }
+}
+
+fn string_arg<'a>(name: &str, args: &'a [EnvValue], i: usize) -> Result<&'a str, ErrorMessage> {
+    match argument(name, args, i)? {
+        EnvValue::Exp(Expression::CString(s)) => Ok(s),
+        other => Err(mismatched(name, "a string", other)),
+    }
+}
+
+fn response_type() -> Type {
+    Type::TResult(Box::new(Type::TString), Box::new(Type::TString))
+}
+
+fn check_http_get(args: &[Type], _env: &Environment<Type>) -> Result<Type, ErrorMessage> {
+    expect_strings("http_get", args, 1)?;
+    Ok(response_type())
+}
+
+fn eval_http_get(args: Vec<EnvValue>, env: &Environment<EnvValue>) -> Result<EnvValue, ErrorMessage> {
+    require_network("http_get", env)?;
+    Ok(wrap_result(request("GET", string_arg("http_get", &args, 0)?, None)))
+}
+
+fn check_http_post(args: &[Type], _env: &Environment<Type>) -> Result<Type, ErrorMessage> {
+    expect_strings("http_post", args, 2)?;
+    Ok(response_type())
+}
+
+fn eval_http_post(args: Vec<EnvValue>, env: &Environment<EnvValue>) -> Result<EnvValue, ErrorMessage> {
+    require_network("http_post", env)?;
+    Ok(wrap_result(request(
+        "POST",
+        string_arg("http_post", &args, 0)?,
+        Some(string_arg("http_post", &args, 1)?),
+    )))
+}
+
+/// A denied capability is a runtime error rather than an `Err` value: the
+/// program cannot recover from running in a sandbox.
+fn require_network(name: &str, env: &Environment<EnvValue>) -> Result<(), ErrorMessage> {
+    if !env.capabilities.network {
+        return Err(format!(
+            "[Runtime Error] '{}()' requires the network capability, which is disabled.",
+            name
+        ));
+    }
+    Ok(())
+}
+
+fn wrap_result(result: Result<String, ErrorMessage>) -> EnvValue {
+    EnvValue::Exp(match result {
+        Ok(body) => Expression::COk(Box::new(Expression::CString(body))),
+        Err(e) => Expression::CErr(Box::new(Expression::CString(e))),
+    })
+}
+
+/// Splits `http://host[:port][/path]` into its parts. Only plain HTTP is
+/// supported, since TLS would need a dependency.
+fn parse_url(url: &str) -> Result<(String, u16, String), ErrorMessage> {
+    let rest = url
+        .strip_prefix("http://")
+        .ok_or_else(|| format!("unsupported url '{}': only http:// is supported", url))?;
+
+    let (authority, path) = match rest.find('/') {
+        Some(i) => (&rest[..i], &rest[i..]),
+        None => (rest, "/"),
+    };
+
+    let (host, port) = match authority.rsplit_once(':') {
+        Some((host, port)) => (
+            host,
+            port.parse::<u16>()
+                .map_err(|_| format!("invalid port in url '{}'", url))?,
+        ),
+        None => (authority, 80),
+    };
+
+    if host.is_empty() {
+        return Err(format!("missing host in url '{}'", url));
+    }
+    Ok((host.to_string(), port, path.to_string()))
+}
+
+/// Sends a HTTP/1.0 request, so the server closes the connection after the
+/// response and the body is never chunked.
+fn request(method: &str, url: &str, body: Option<&str>) -> Result<String, ErrorMessage> {
+    let (host, port, path) = parse_url(url)?;
+
+    let addr = (host.as_str(), port)
+        .to_socket_addrs()
+        .map_err(|e| format!("cannot resolve '{}': {}", host, e))?
+        .next()
+        .ok_or_else(|| format!("cannot resolve '{}'", host))?;
+
+    let mut stream =
+        TcpStream::connect_timeout(&addr, TIMEOUT).map_err(|e| format!("connection failed: {}", e))?;
+    stream
+        .set_read_timeout(Some(TIMEOUT))
+        .and_then(|_| stream.set_write_timeout(Some(TIMEOUT)))
+        .map_err(|e| format!("connection failed: {}", e))?;
+
+    let body = body.unwrap_or("");
+    let mut message = format!("{} {} HTTP/1.0\r\nHost: {}\r\n", method, path, host);
+    if method == "POST" {
+        message.push_str(&format!("Content-Length: {}\r\n", body.len()));
+    }
+    message.push_str("\r\n");
+    message.push_str(body);
+
+    stream
+        .write_all(message.as_bytes())
+        .map_err(|e| format!("request failed: {}", e))?;
+
+    let mut response = vec![];
+    stream
+        .read_to_end(&mut response)
+        .map_err(|e| format!("request failed: {}", e))?;
+
+    parse_response(&response)
+}
+
+fn parse_response(response: &[u8]) -> Result<String, ErrorMessage> {
+    let response = String::from_utf8_lossy(response);
+    let (head, body) = response
+        .split_once("\r\n\r\n")
+        .ok_or_else(|| "malformed response".to_string())?;
+
+    let status_line = head.lines().next().unwrap_or("");
+    let status = status_line
+        .split_whitespace()
+        .nth(1)
+        .and_then(|code| code.parse::<u16>().ok())
+        .ok_or_else(|| format!("malformed status line '{}'", status_line))?;
+
+    if (200..300).contains(&status) {
+        Ok(body.to_string())
+    } else {
+        Err(format!(
+            "server answered '{}'",
+            status_line.split_once(' ').map_or("", |(_, reason)| reason)
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interpreter::interpreter::eval;
+    use crate::ir::ast::Expression::*;
+    use std::net::TcpListener;
+    use std::thread;
+
+    fn serve_once(response: &'static str) -> (String, thread::JoinHandle<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/data", listener.local_addr().unwrap());
+
+        let handle = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut buffer = [0; 1024];
+            let read = stream.read(&mut buffer).unwrap();
+            stream.write_all(response.as_bytes()).unwrap();
+            String::from_utf8_lossy(&buffer[..read]).to_string()
+        });
+        (url, handle)
+    }
+
+    fn networked_env() -> Environment<EnvValue> {
+        let mut env = Environment::new();
+        env.capabilities.network = true;
+        env
+    }
+
+    #[test]
+    fn network_is_denied_by_default() {
+        let env: Environment<EnvValue> = Environment::new();
+
+        assert_eq!(
+            eval(
+                FuncCall(
+                    "http_get".to_string(),
+                    vec![CString("http://localhost/".to_string())]
+                ),
+                &env
+            ),
+            Err((
+                String::from("[Runtime Error] 'http_get()' requires the network capability, which is disabled."),
+                None
+            ))
+        );
+    }
+
+    #[test]
+    fn get_and_post_return_the_body() {
+        let (url, server) = serve_once("HTTP/1.0 200 OK\r\n\r\nhello");
+        assert_eq!(
+            eval(FuncCall("http_get".to_string(), vec![CString(url)]), &networked_env()),
+            Ok(EnvValue::Exp(COk(Box::new(CString("hello".to_string())))))
+        );
+        assert!(server.join().unwrap().starts_with("GET /data HTTP/1.0\r\n"));
+
+        let (url, server) = serve_once("HTTP/1.0 404 Not Found\r\n\r\n");
+        assert_eq!(
+            eval(
+                FuncCall(
+                    "http_post".to_string(),
+                    vec![CString(url), CString("x=1".to_string())]
+                ),
+                &networked_env()
+            ),
+            Ok(EnvValue::Exp(CErr(Box::new(CString(
+                "server answered '404 Not Found'".to_string()
+            )))))
+        );
+        assert!(server.join().unwrap().ends_with("Content-Length: 3\r\n\r\nx=1"));
+    }
+
+    #[test]
+    fn urls_are_validated() {
+        assert_eq!(
+            parse_url("http://example.com:8080"),
+            Ok(("example.com".to_string(), 8080, "/".to_string()))
+        );
+        assert_eq!(
+            parse_url("https://example.com/"),
+            Err("unsupported url 'https://example.com/': only http:// is supported".to_string())
+        );
+    }
+}
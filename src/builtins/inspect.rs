@@ -0,0 +1,223 @@
+use crate::builtins::registry::{argument, expect_arity, expect_values, Builtin};
+use crate::interpreter::interpreter::{invoke, EnvValue};
+use crate::interpreter::value::{render, type_name};
+use crate::ir::ast::{Environment, Expression, Type};
+use crate::tc::type_checker::satisfies;
+
+type ErrorMessage = String;
+
+pub const BUILTINS: &[Builtin] = &[
+    Builtin {
+        name: "dir",
+        check: check_dir,
+        eval: eval_dir,
+    },
+    Builtin {
+        name: "type_of",
+        check: check_type_of,
+        eval: eval_type_of,
+    },
+    Builtin {
+        name: "locals",
+        check: check_locals,
+        eval: eval_locals,
+    },
+    Builtin {
+        name: "show",
+        check: check_show,
+        eval: eval_show,
+    },
+];
+
+fn check_dir(args: &[Type], _env: &Environment<Type>) -> Result<Type, ErrorMessage> {
+    expect_arity("dir", args, 0)?;
+    Ok(Type::TList(Box::new(Type::TString)))
+}
+
+/// Every name visible from the current scope, walking the frames up to
+/// `__main__`, in alphabetical order.
+fn eval_dir(_args: Vec<EnvValue>, env: &Environment<EnvValue>) -> Result<EnvValue, ErrorMessage> {
+    let mut names: Vec<String> = vec![];
+    let mut curr_scope = Some(env.scope_key());
+
+    while let Some(key) = curr_scope {
+        let frame = env.get_frame(key);
+        names.extend(frame.variables.keys().cloned());
+        curr_scope = frame.parent_key.clone();
+    }
+    names.extend(env.globals.names());
+
+    names.sort();
+    names.dedup();
+
+    Ok(EnvValue::Exp(Expression::CList(
+        names.into_iter().map(Expression::CString).collect(),
+    )))
+}
+
+fn check_type_of(args: &[Type], _env: &Environment<Type>) -> Result<Type, ErrorMessage> {
+    expect_arity("type_of", args, 1)?;
+    Ok(Type::TString)
+}
+
+fn eval_type_of(args: Vec<EnvValue>, _env: &Environment<EnvValue>) -> Result<EnvValue, ErrorMessage> {
+    expect_values("type_of", &args, 1)?;
+    Ok(EnvValue::Exp(Expression::CString(type_name(&args[0]))))
+}
+
+fn check_locals(args: &[Type], _env: &Environment<Type>) -> Result<Type, ErrorMessage> {
+    expect_arity("locals", args, 0)?;
+    Ok(Type::TDict(Box::new(Type::TString), Box::new(Type::TAny)))
+}
+
+/// Snapshot of the value bindings of the current frame, ordered by name.
+/// Functions and test modules are not values, so they are left out.
+fn eval_locals(_args: Vec<EnvValue>, env: &Environment<EnvValue>) -> Result<EnvValue, ErrorMessage> {
+    let frame = env.get_frame(env.scope_key());
+    let mut bindings: Vec<(&String, &Expression)> = frame
+        .variables
+        .iter()
+        .filter_map(|(name, value)| match value {
+            EnvValue::Exp(exp) => Some((name, exp)),
+            _ => None,
+        })
+        .collect();
+
+    bindings.sort_by_key(|(name, _)| *name);
+
+    Ok(EnvValue::Exp(Expression::CDict(
+        bindings
+            .into_iter()
+            .map(|(name, exp)| (Expression::CString(name.clone()), exp.clone()))
+            .collect(),
+    )))
+}
+
+fn check_show(args: &[Type], env: &Environment<Type>) -> Result<Type, ErrorMessage> {
+    expect_arity("show", args, 1)?;
+    if !satisfies(&args[0], "Show", env) {
+        return Err(format!(
+            "[Type Error] 'show()' requires an instance of 'Show', found '{:?}'.",
+            args[0]
+        ));
+    }
+    Ok(Type::TString)
+}
+
+/// ADTs are shown by the `show` method of their instance, everything else
+/// as it would be written in the source.
+fn eval_show(args: Vec<EnvValue>, env: &Environment<EnvValue>) -> Result<EnvValue, ErrorMessage> {
+    if let EnvValue::Exp(Expression::ADTValue(value)) = argument("show", &args, 0)? {
+        if let Some(method) = env.instance_method("Show", &value.adt, "show") {
+            return invoke(method, &[], args, env).map_err(|(e, _)| e);
+        }
+    }
+    Ok(EnvValue::Exp(Expression::CString(render(&args[0]))))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interpreter::interpreter::{eval, run, ControlFlow};
+    use crate::ir::ast::Expression::*;
+    use crate::ir::ast::Function;
+    use crate::ir::ast::Statement::*;
+    use crate::ir::ast::Type::*;
+    use crate::tc::type_checker::check_exp;
+
+    #[test]
+    fn dir_lists_names_in_scope() {
+        let mut env: Environment<EnvValue> = Environment::new();
+        env.insert_variable("y".to_string(), EnvValue::Exp(CInt(2)));
+        env.insert_variable("x".to_string(), EnvValue::Exp(CInt(1)));
+        env.insert_variable("f".to_string(), EnvValue::Func(Function::new()));
+
+        assert_eq!(
+            eval(FuncCall("dir".to_string(), vec![]), &env),
+            Ok(EnvValue::Exp(CList(vec![
+                CString("f".to_string()),
+                CString("x".to_string()),
+                CString("y".to_string()),
+            ])))
+        );
+    }
+
+    #[test]
+    fn type_of_returns_runtime_type_names() {
+        let env: Environment<EnvValue> = Environment::new();
+        let type_of = |exp: Expression| eval(FuncCall("type_of".to_string(), vec![exp]), &env);
+
+        assert_eq!(type_of(CInt(1)), Ok(EnvValue::Exp(CString("Int".to_string()))));
+        assert_eq!(type_of(CReal(1.5)), Ok(EnvValue::Exp(CString("Real".to_string()))));
+        assert_eq!(
+            type_of(CJust(Box::new(CTrue))),
+            Ok(EnvValue::Exp(CString("Maybe".to_string())))
+        );
+        assert_eq!(
+            eval(FuncCall("type_of".to_string(), vec![]), &env),
+            Err((String::from("[Runtime Error] 'type_of()' expected 1 arguments, found 0."), None))
+        );
+    }
+
+    #[test]
+    fn locals_snapshots_the_current_frame() {
+        let env: Environment<EnvValue> = Environment::new();
+        let program = Sequence(
+            Box::new(Assignment("b".to_string(), Box::new(CInt(2)), Some(TInteger))),
+            Box::new(Sequence(
+                Box::new(Assignment("a".to_string(), Box::new(CTrue), Some(TBool))),
+                Box::new(Assignment(
+                    "scope".to_string(),
+                    Box::new(FuncCall("locals".to_string(), vec![])),
+                    None,
+                )),
+            )),
+        );
+
+        match run(program, &env) {
+            Ok(ControlFlow::Continue(new_env)) => assert_eq!(
+                new_env.search_frame("scope".to_string()),
+                Some(&EnvValue::Exp(CDict(vec![
+                    (CString("a".to_string()), CTrue),
+                    (CString("b".to_string()), CInt(2)),
+                ])))
+            ),
+            Ok(ControlFlow::Return(_)) => panic!("unexpected return"),
+            Err(s) => panic!("{}", s),
+        }
+    }
+
+    #[test]
+    fn show_renders_values() {
+        let env: Environment<EnvValue> = Environment::new();
+
+        assert_eq!(
+            eval(FuncCall("show".to_string(), vec![CList(vec![CInt(1), CInt(2)])]), &env),
+            Ok(EnvValue::Exp(CString("[1, 2]".to_string())))
+        );
+        assert_eq!(
+            check_exp(FuncCall("show".to_string(), vec![CReal(1.0)]), &Environment::new()),
+            Ok(TString)
+        );
+    }
+
+    #[test]
+    fn check_inspection_builtins() {
+        let env: Environment<Type> = Environment::new();
+
+        assert_eq!(
+            check_exp(FuncCall("dir".to_string(), vec![]), &env),
+            Ok(TList(Box::new(TString)))
+        );
+        assert_eq!(
+            check_exp(FuncCall("type_of".to_string(), vec![CInt(1)]), &env),
+            Ok(TString)
+        );
+        assert_eq!(
+            check_exp(FuncCall("type_of".to_string(), vec![]), &env),
+            Err(String::from(
+                "[Type Error] 'type_of()' expected 1 arguments, found 0."
+            ))
+        );
+    }
+}
@@ -0,0 +1,167 @@
+use crate::builtins::registry::{argument, expect_arity, mismatched, Builtin};
+use crate::interpreter::interpreter::EnvValue;
+use crate::interpreter::value::render;
+use crate::ir::ast::{Environment, Expression, Type};
+
+type ErrorMessage = String;
+
+pub const BUILTINS: &[Builtin] = &[
+    Builtin {
+        name: "print",
+        check: check_print,
+        eval: eval_print,
+    },
+    Builtin {
+        name: "input",
+        check: check_input,
+        eval: eval_input,
+    },
+    Builtin {
+        name: "print_table",
+        check: check_print_table,
+        eval: eval_print_table,
+    },
+];
+
+fn check_print(args: &[Type], _env: &Environment<Type>) -> Result<Type, ErrorMessage> {
+    expect_arity("print", args, 1)?;
+    Ok(Type::TVoid)
+}
+
+/// Writes a line to the environment's output. Strings are written as they
+/// are, other values as they would be written in the source.
+fn eval_print(args: Vec<EnvValue>, env: &Environment<EnvValue>) -> Result<EnvValue, ErrorMessage> {
+    env.io.write(&format!("{}\n", text(&args[0])));
+    Ok(EnvValue::Exp(Expression::CVoid))
+}
+
+fn text(value: &EnvValue) -> String {
+    match value {
+        EnvValue::Exp(Expression::CString(text)) => text.clone(),
+        value => render(value),
+    }
+}
+
+/// Rows are lists or tuples, so the cells of a row may differ in type when
+/// it is a tuple.
+fn check_print_table(args: &[Type], _env: &Environment<Type>) -> Result<Type, ErrorMessage> {
+    expect_arity("print_table", args, 1)?;
+    match &args[0] {
+        Type::TList(row) if matches!(**row, Type::TList(_) | Type::TTuple(_) | Type::TAny) => Ok(Type::TVoid),
+        other => Err(format!(
+            "[Type Error] 'print_table()' expects a list of rows, found '{:?}'.",
+            other
+        )),
+    }
+}
+
+/// Writes one line per row, with the cells of each column padded to the
+/// widest of them. Cells are written as `print` writes values, numbers
+/// aligned right and the rest left; columns are two spaces apart.
+fn eval_print_table(args: Vec<EnvValue>, env: &Environment<EnvValue>) -> Result<EnvValue, ErrorMessage> {
+    let value = argument("print_table", &args, 0)?;
+    let rows: Vec<Vec<(String, bool)>> = match value {
+        EnvValue::Exp(Expression::CList(rows)) => rows
+            .iter()
+            .map(|row| match row {
+                Expression::CList(cells) | Expression::CTuple(cells) => Ok(cells
+                    .iter()
+                    .map(|cell| {
+                        let numeric = matches!(cell, Expression::CInt(_) | Expression::CReal(_) | Expression::CDecimal(_));
+                        (text(&EnvValue::Exp(cell.clone())), numeric)
+                    })
+                    .collect()),
+                _ => Err(mismatched("print_table", "a list of rows", value)),
+            })
+            .collect::<Result<_, _>>()?,
+        _ => return Err(mismatched("print_table", "a list of rows", value)),
+    };
+
+    let mut widths: Vec<usize> = vec![];
+    for row in &rows {
+        for (column, (cell, _)) in row.iter().enumerate() {
+            let width = cell.chars().count();
+            match widths.get_mut(column) {
+                Some(widest) => *widest = (*widest).max(width),
+                None => widths.push(width),
+            }
+        }
+    }
+
+    let mut out = String::new();
+    for row in rows {
+        let cells: Vec<String> = row
+            .into_iter()
+            .zip(&widths)
+            .map(|((cell, numeric), width)| match numeric {
+                true => format!("{:>1$}", cell, width),
+                false => format!("{:<1$}", cell, width),
+            })
+            .collect();
+        out.push_str(cells.join("  ").trim_end());
+        out.push('\n');
+    }
+    env.io.write(&out);
+    Ok(EnvValue::Exp(Expression::CVoid))
+}
+
+fn check_input(args: &[Type], _env: &Environment<Type>) -> Result<Type, ErrorMessage> {
+    expect_arity("input", args, 0)?;
+    Ok(Type::TString)
+}
+
+fn eval_input(_args: Vec<EnvValue>, env: &Environment<EnvValue>) -> Result<EnvValue, ErrorMessage> {
+    match env.io.read_line() {
+        Some(line) => Ok(EnvValue::Exp(Expression::CString(line))),
+        None => Err(String::from("[Runtime Error] 'input()' reached the end of the input.")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interpreter::interpreter::eval;
+    use crate::ir::ast::Expression::*;
+    use crate::ir::ast::Io;
+    use crate::tc::type_checker::check_exp;
+
+    #[test]
+    fn print_and_input_use_the_environment_streams() {
+        let mut env: Environment<EnvValue> = Environment::new();
+        env.io = Io::scripted("first\nsecond\n");
+        let input = || eval(FuncCall("input".to_string(), vec![]), &env);
+
+        assert_eq!(input(), Ok(EnvValue::Exp(CString("first".to_string()))));
+        assert_eq!(input(), Ok(EnvValue::Exp(CString("second".to_string()))));
+        assert_eq!(
+            input(),
+            Err((String::from("[Runtime Error] 'input()' reached the end of the input."), None))
+        );
+
+        for value in [CString("hi".to_string()), CList(vec![CInt(1), CString("a".to_string())])] {
+            eval(FuncCall("print".to_string(), vec![value]), &env).unwrap();
+        }
+        assert_eq!(env.io.output(), Some(String::from("hi\n[1, \"a\"]\n")));
+    }
+
+    #[test]
+    fn print_table_aligns_columns() {
+        let mut env: Environment<EnvValue> = Environment::new();
+        env.io = Io::captured();
+        let row = |name: &str, n: i32, time: f64| CTuple(vec![CString(name.to_string()), CInt(n), CReal(time)]);
+        let header = CTuple(vec![CString("sort".to_string()), CString("n".to_string()), CString("seconds".to_string())]);
+        let rows = CList(vec![header, row("bubble", 1000, 2.5), row("merge", 100000, 0.25)]);
+        eval(FuncCall("print_table".to_string(), vec![rows]), &env).unwrap();
+        assert_eq!(
+            env.io.output(),
+            Some(String::from("sort    n       seconds\nbubble    1000      2.5\nmerge   100000     0.25\n"))
+        );
+
+        let mut types: Environment<Type> = Environment::new();
+        types.insert_variable("names".to_string(), Type::TList(Box::new(Type::TString)));
+        assert_eq!(
+            check_exp(FuncCall("print_table".to_string(), vec![Var("names".to_string())]), &types),
+            Err(String::from("[Type Error] 'print_table()' expects a list of rows, found 'TList(TString)'."))
+        );
+    }
+}
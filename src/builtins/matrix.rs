@@ -0,0 +1,272 @@
+use crate::builtins::registry::{argument, expect_arity, mismatched, Builtin};
+use crate::interpreter::interpreter::EnvValue;
+use crate::ir::ast::{Environment, Expression, Type};
+
+type ErrorMessage = String;
+
+pub const BUILTINS: &[Builtin] = &[
+    Builtin {
+        name: "identity",
+        check: check_identity,
+        eval: eval_identity,
+    },
+    Builtin {
+        name: "transpose",
+        check: check_transpose,
+        eval: eval_transpose,
+    },
+    Builtin {
+        name: "matmul",
+        check: check_matmul,
+        eval: eval_matmul,
+    },
+    Builtin {
+        name: "show_matrix",
+        check: check_show_matrix,
+        eval: eval_show_matrix,
+    },
+];
+
+/// Matrices are lists of rows of reals.
+fn matrix_type() -> Type {
+    Type::TList(Box::new(Type::TList(Box::new(Type::TReal))))
+}
+
+/// An empty list literal types as `TList(TAny)`, so it is accepted too.
+fn expect_matrix(name: &str, arg: &Type) -> Result<(), ErrorMessage> {
+    let accepted = match arg {
+        Type::TList(row) => match row.as_ref() {
+            Type::TAny => true,
+            Type::TList(item) => matches!(item.as_ref(), Type::TReal | Type::TAny),
+            _ => false,
+        },
+        _ => false,
+    };
+    if accepted {
+        return Ok(());
+    }
+    Err(format!(
+        "[Type Error] '{}()' has mismatched arguments: expected '{:?}', found '{:?}'.",
+        name,
+        matrix_type(),
+        arg
+    ))
+}
+
+/// The rows of a matrix argument, which must all have the same length.
+fn matrix_arg(name: &str, args: &[EnvValue], i: usize) -> Result<Vec<Vec<f64>>, ErrorMessage> {
+    let value = argument(name, args, i)?;
+    let rows = match value {
+        EnvValue::Exp(Expression::CList(rows)) => rows,
+        _ => return Err(mismatched(name, "a list of rows of reals", value)),
+    };
+    let rows: Vec<Vec<f64>> = rows
+        .iter()
+        .map(|row| match row {
+            Expression::CList(items) => items
+                .iter()
+                .map(|item| match item {
+                    Expression::CReal(x) => Ok(*x),
+                    _ => Err(mismatched(name, "a list of rows of reals", value)),
+                })
+                .collect(),
+            _ => Err(mismatched(name, "a list of rows of reals", value)),
+        })
+        .collect::<Result<_, _>>()?;
+
+    if let Some(row) = rows.iter().position(|row| row.len() != rows[0].len()) {
+        return Err(format!(
+            "[Runtime Error] '{}()' expects rows of the same length: row 0 has {} columns, row {} has {}.",
+            name,
+            rows[0].len(),
+            row,
+            rows[row].len()
+        ));
+    }
+    Ok(rows)
+}
+
+fn matrix_value(rows: Vec<Vec<f64>>) -> EnvValue {
+    EnvValue::Exp(Expression::CList(
+        rows.into_iter()
+            .map(|row| Expression::CList(row.into_iter().map(Expression::CReal).collect()))
+            .collect(),
+    ))
+}
+
+fn columns(rows: &[Vec<f64>]) -> usize {
+    rows.first().map_or(0, |row| row.len())
+}
+
+fn check_identity(args: &[Type], _env: &Environment<Type>) -> Result<Type, ErrorMessage> {
+    expect_arity("identity", args, 1)?;
+    if args[0] != Type::TInteger {
+        return Err(format!(
+            "[Type Error] 'identity()' has mismatched arguments: expected 'TInteger', found '{:?}'.",
+            args[0]
+        ));
+    }
+    Ok(matrix_type())
+}
+
+fn eval_identity(args: Vec<EnvValue>, _env: &Environment<EnvValue>) -> Result<EnvValue, ErrorMessage> {
+    let size = match argument("identity", &args, 0)? {
+        EnvValue::Exp(Expression::CInt(n)) if *n >= 0 => *n as usize,
+        EnvValue::Exp(Expression::CInt(n)) => {
+            return Err(format!("[Runtime Error] 'identity()' expects a non-negative size, found {}.", n))
+        }
+        other => return Err(mismatched("identity", "an integer", other)),
+    };
+    let rows = (0..size)
+        .map(|i| (0..size).map(|j| if i == j { 1.0 } else { 0.0 }).collect())
+        .collect();
+    Ok(matrix_value(rows))
+}
+
+fn check_transpose(args: &[Type], _env: &Environment<Type>) -> Result<Type, ErrorMessage> {
+    expect_arity("transpose", args, 1)?;
+    expect_matrix("transpose", &args[0])?;
+    Ok(matrix_type())
+}
+
+fn eval_transpose(args: Vec<EnvValue>, _env: &Environment<EnvValue>) -> Result<EnvValue, ErrorMessage> {
+    let rows = matrix_arg("transpose", &args, 0)?;
+    let transposed = (0..columns(&rows))
+        .map(|j| rows.iter().map(|row| row[j]).collect())
+        .collect();
+    Ok(matrix_value(transposed))
+}
+
+fn check_matmul(args: &[Type], _env: &Environment<Type>) -> Result<Type, ErrorMessage> {
+    expect_arity("matmul", args, 2)?;
+    expect_matrix("matmul", &args[0])?;
+    expect_matrix("matmul", &args[1])?;
+    Ok(matrix_type())
+}
+
+/// The product of an `n x m` and an `m x p` matrix, which is `n x p`.
+fn eval_matmul(args: Vec<EnvValue>, _env: &Environment<EnvValue>) -> Result<EnvValue, ErrorMessage> {
+    let a = matrix_arg("matmul", &args, 0)?;
+    let b = matrix_arg("matmul", &args, 1)?;
+    if columns(&a) != b.len() {
+        return Err(format!(
+            "[Runtime Error] 'matmul()' cannot multiply a {}x{} matrix by a {}x{} matrix.",
+            a.len(),
+            columns(&a),
+            b.len(),
+            columns(&b)
+        ));
+    }
+
+    let product = a
+        .iter()
+        .map(|row| {
+            (0..columns(&b))
+                .map(|j| row.iter().zip(&b).map(|(x, b_row)| x * b_row[j]).sum())
+                .collect()
+        })
+        .collect();
+    Ok(matrix_value(product))
+}
+
+fn check_show_matrix(args: &[Type], _env: &Environment<Type>) -> Result<Type, ErrorMessage> {
+    expect_arity("show_matrix", args, 1)?;
+    expect_matrix("show_matrix", &args[0])?;
+    Ok(Type::TString)
+}
+
+/// One line per row, with the columns right-aligned:
+///
+/// ```text
+/// [ 1.0  0.5]
+/// [-2.0 10.0]
+/// ```
+fn eval_show_matrix(args: Vec<EnvValue>, _env: &Environment<EnvValue>) -> Result<EnvValue, ErrorMessage> {
+    let rows = matrix_arg("show_matrix", &args, 0)?;
+    let cells: Vec<Vec<String>> = rows
+        .iter()
+        .map(|row| row.iter().map(|x| format!("{:?}", x)).collect())
+        .collect();
+    let widths: Vec<usize> = (0..columns(&rows))
+        .map(|j| cells.iter().map(|row| row[j].len()).max().unwrap_or(0))
+        .collect();
+
+    let lines: Vec<String> = cells
+        .iter()
+        .map(|row| {
+            let padded: Vec<String> = row
+                .iter()
+                .zip(&widths)
+                .map(|(cell, width)| format!("{:>width$}", cell, width = width))
+                .collect();
+            format!("[{}]", padded.join(" "))
+        })
+        .collect();
+    Ok(EnvValue::Exp(Expression::CString(lines.join("\n"))))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interpreter::interpreter::eval;
+    use crate::ir::ast::Expression::*;
+    use crate::tc::type_checker::check_exp;
+
+    fn call(name: &str, args: Vec<Expression>) -> Result<EnvValue, (String, Option<Expression>)> {
+        let env: Environment<EnvValue> = Environment::new();
+        eval(FuncCall(name.to_string(), args), &env)
+    }
+
+    fn matrix(rows: &[&[f64]]) -> Expression {
+        CList(rows.iter().map(|row| CList(row.iter().map(|x| CReal(*x)).collect())).collect())
+    }
+
+    #[test]
+    fn matrix_operations() {
+        let a = matrix(&[&[1.0, 2.0, 3.0], &[4.0, 5.0, 6.0]]);
+
+        assert_eq!(
+            call("transpose", vec![a.clone()]),
+            Ok(EnvValue::Exp(matrix(&[&[1.0, 4.0], &[2.0, 5.0], &[3.0, 6.0]])))
+        );
+        assert_eq!(
+            call("matmul", vec![a.clone(), FuncCall("transpose".to_string(), vec![a.clone()])]),
+            Ok(EnvValue::Exp(matrix(&[&[14.0, 32.0], &[32.0, 77.0]])))
+        );
+        assert_eq!(
+            call("matmul", vec![FuncCall("identity".to_string(), vec![CInt(2)]), a.clone()]),
+            Ok(EnvValue::Exp(a.clone()))
+        );
+        assert_eq!(call("transpose", vec![CList(vec![])]), Ok(EnvValue::Exp(CList(vec![]))));
+        assert_eq!(
+            call("show_matrix", vec![matrix(&[&[1.0, 0.5], &[-2.0, 10.0]])]),
+            Ok(EnvValue::Exp(CString("[ 1.0  0.5]\n[-2.0 10.0]".to_string())))
+        );
+    }
+
+    #[test]
+    fn matrix_shapes_are_checked() {
+        assert_eq!(
+            call("matmul", vec![matrix(&[&[1.0, 2.0]]), matrix(&[&[1.0, 2.0]])]),
+            Err((
+                String::from("[Runtime Error] 'matmul()' cannot multiply a 1x2 matrix by a 1x2 matrix."),
+                None
+            ))
+        );
+        assert_eq!(
+            call("transpose", vec![matrix(&[&[1.0, 2.0], &[3.0]])]),
+            Err((
+                String::from(
+                    "[Runtime Error] 'transpose()' expects rows of the same length: row 0 has 2 columns, row 1 has 1."
+                ),
+                None
+            ))
+        );
+        assert_eq!(
+            check_exp(FuncCall("transpose".to_string(), vec![CList(vec![CList(vec![CInt(1)])])]), &Environment::new()),
+            Err(String::from(
+                "[Type Error] 'transpose()' has mismatched arguments: expected 'TList(TList(TReal))', found 'TList(TList(TInteger))'."
+            ))
+        );
+    }
+}
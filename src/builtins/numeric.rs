@@ -0,0 +1,405 @@
+use crate::builtins::registry::{argument, expect_arity, mismatched, Builtin};
+use crate::interpreter::interpreter::EnvValue;
+use crate::ir::ast::{Environment, Expression, Type};
+use crate::ir::complex::Complex;
+use crate::ir::decimal::{Decimal, MAX_SCALE};
+
+type ErrorMessage = String;
+
+pub const BUILTINS: &[Builtin] = &[
+    Builtin {
+        name: "decimal",
+        check: check_decimal,
+        eval: eval_decimal,
+    },
+    Builtin {
+        name: "parse_decimal",
+        check: check_parse_decimal,
+        eval: eval_parse_decimal,
+    },
+    Builtin {
+        name: "to_real",
+        check: check_to_real,
+        eval: eval_to_real,
+    },
+    Builtin {
+        name: "to_int",
+        check: check_to_int,
+        eval: eval_to_int,
+    },
+    Builtin {
+        name: "round",
+        check: check_round,
+        eval: eval_round,
+    },
+    Builtin {
+        name: "complex",
+        check: check_complex,
+        eval: eval_complex,
+    },
+    Builtin {
+        name: "re",
+        check: check_re,
+        eval: eval_re,
+    },
+    Builtin {
+        name: "im",
+        check: check_im,
+        eval: eval_im,
+    },
+    Builtin {
+        name: "arg",
+        check: check_arg,
+        eval: eval_arg,
+    },
+    Builtin {
+        name: "conj",
+        check: check_conj,
+        eval: eval_conj,
+    },
+    Builtin {
+        name: "abs",
+        check: check_abs,
+        eval: eval_abs,
+    },
+    Builtin {
+        name: "approx_eq",
+        check: check_approx_eq,
+        eval: eval_approx_eq,
+    },
+    Builtin {
+        name: "exact_eq",
+        check: check_exact_eq,
+        eval: eval_exact_eq,
+    },
+];
+
+fn expect_one_of(name: &str, arg: &Type, expected: &[Type]) -> Result<(), ErrorMessage> {
+    if expected.contains(arg) {
+        return Ok(());
+    }
+    let names: Vec<String> = expected.iter().map(|kind| format!("'{:?}'", kind)).collect();
+    Err(format!(
+        "[Type Error] '{}()' has mismatched arguments: expected {}, found '{:?}'.",
+        name,
+        names.join(" or "),
+        arg
+    ))
+}
+
+fn decimal_arg(name: &str, value: &EnvValue) -> Result<Decimal, ErrorMessage> {
+    match value {
+        EnvValue::Exp(Expression::CDecimal(d)) => Ok(*d),
+        EnvValue::Exp(Expression::CInt(n)) => Ok(Decimal::from(*n)),
+        _ => Err(mismatched(name, "a decimal", value)),
+    }
+}
+
+fn check_decimal(args: &[Type], _env: &Environment<Type>) -> Result<Type, ErrorMessage> {
+    expect_arity("decimal", args, 1)?;
+    expect_one_of("decimal", &args[0], &[Type::TInteger, Type::TReal, Type::TDecimal])?;
+    Ok(Type::TDecimal)
+}
+
+/// Reals are converted through the shortest text that reads back as the
+/// same real, so `decimal(0.1)` is `0.1d` and not the binary fraction
+/// closest to it.
+fn eval_decimal(args: Vec<EnvValue>, _env: &Environment<EnvValue>) -> Result<EnvValue, ErrorMessage> {
+    let value = match argument("decimal", &args, 0)? {
+        EnvValue::Exp(Expression::CReal(x)) => {
+            let text = x.to_string();
+            let places = text.split_once('.').map_or(0, |(_, fraction)| fraction.len());
+            let cut = text.len() - places.saturating_sub(MAX_SCALE as usize);
+            Decimal::parse(text[..cut].trim_end_matches('.'))
+                .ok_or_else(|| format!("[Runtime Error] 'decimal()' cannot convert {:?}.", x))?
+        }
+        value => decimal_arg("decimal", value)?,
+    };
+    Ok(EnvValue::Exp(Expression::CDecimal(value)))
+}
+
+fn check_parse_decimal(args: &[Type], _env: &Environment<Type>) -> Result<Type, ErrorMessage> {
+    expect_arity("parse_decimal", args, 1)?;
+    expect_one_of("parse_decimal", &args[0], &[Type::TString])?;
+    Ok(Type::TResult(Box::new(Type::TDecimal), Box::new(Type::TString)))
+}
+
+/// Text that is not a decimal, such as user input, is reported as an `Err`.
+fn eval_parse_decimal(args: Vec<EnvValue>, _env: &Environment<EnvValue>) -> Result<EnvValue, ErrorMessage> {
+    let text = match argument("parse_decimal", &args, 0)? {
+        EnvValue::Exp(Expression::CString(s)) => s.trim(),
+        other => return Err(mismatched("parse_decimal", "a string", other)),
+    };
+    Ok(EnvValue::Exp(match Decimal::parse(text) {
+        Some(value) => Expression::COk(Box::new(Expression::CDecimal(value))),
+        None => Expression::CErr(Box::new(Expression::CString(format!("invalid decimal '{}'", text)))),
+    }))
+}
+
+fn check_to_real(args: &[Type], _env: &Environment<Type>) -> Result<Type, ErrorMessage> {
+    expect_arity("to_real", args, 1)?;
+    expect_one_of("to_real", &args[0], &[Type::TInteger, Type::TReal, Type::TDecimal])?;
+    Ok(Type::TReal)
+}
+
+fn eval_to_real(args: Vec<EnvValue>, _env: &Environment<EnvValue>) -> Result<EnvValue, ErrorMessage> {
+    let value = match argument("to_real", &args, 0)? {
+        EnvValue::Exp(Expression::CReal(x)) => *x,
+        value => decimal_arg("to_real", value)?.to_f64(),
+    };
+    Ok(EnvValue::Exp(Expression::CReal(value)))
+}
+
+fn check_to_int(args: &[Type], _env: &Environment<Type>) -> Result<Type, ErrorMessage> {
+    expect_arity("to_int", args, 1)?;
+    expect_one_of("to_int", &args[0], &[Type::TInteger, Type::TReal, Type::TDecimal])?;
+    Ok(Type::TInteger)
+}
+
+/// The integer part, rounding towards zero as Python's `int` does.
+fn eval_to_int(args: Vec<EnvValue>, _env: &Environment<EnvValue>) -> Result<EnvValue, ErrorMessage> {
+    let whole = match argument("to_int", &args, 0)? {
+        EnvValue::Exp(Expression::CReal(x)) if !x.is_finite() => {
+            return Err(format!("[Runtime Error] 'to_int()' cannot convert {:?}.", x))
+        }
+        EnvValue::Exp(Expression::CReal(x)) => x.trunc() as i128,
+        value => decimal_arg("to_int", value)?.trunc(),
+    };
+    match i32::try_from(whole) {
+        Ok(n) => Ok(EnvValue::Exp(Expression::CInt(n))),
+        Err(_) => Err(format!("[Runtime Error] 'to_int()' got {}, which is out of range for an integer.", whole)),
+    }
+}
+
+fn check_round(args: &[Type], _env: &Environment<Type>) -> Result<Type, ErrorMessage> {
+    expect_arity("round", args, 2)?;
+    expect_one_of("round", &args[0], &[Type::TDecimal])?;
+    expect_one_of("round", &args[1], &[Type::TInteger])?;
+    Ok(Type::TDecimal)
+}
+
+/// Rounds half to even, as is usual for money: `round(2.345d, 2)` is
+/// `2.34d` and `round(2.355d, 2)` is `2.36d`.
+fn eval_round(args: Vec<EnvValue>, _env: &Environment<EnvValue>) -> Result<EnvValue, ErrorMessage> {
+    let value = decimal_arg("round", argument("round", &args, 0)?)?;
+    let places = match argument("round", &args, 1)? {
+        EnvValue::Exp(Expression::CInt(n)) => *n,
+        other => return Err(mismatched("round", "an integer", other)),
+    };
+    match u32::try_from(places) {
+        Ok(places) => Ok(EnvValue::Exp(Expression::CDecimal(value.rounded(places)))),
+        Err(_) => Err(format!("[Runtime Error] 'round()' expects a non-negative number of places, found {}.", places)),
+    }
+}
+
+fn real_arg(name: &str, value: &EnvValue) -> Result<f64, ErrorMessage> {
+    match value {
+        EnvValue::Exp(Expression::CReal(x)) => Ok(*x),
+        EnvValue::Exp(Expression::CInt(n)) => Ok(*n as f64),
+        _ => Err(mismatched(name, "an integer or a real", value)),
+    }
+}
+
+fn check_approx_eq(args: &[Type], _env: &Environment<Type>) -> Result<Type, ErrorMessage> {
+    expect_arity("approx_eq", args, 2)?;
+    for arg in args {
+        expect_one_of("approx_eq", arg, &[Type::TInteger, Type::TReal])?;
+    }
+    Ok(Type::TBool)
+}
+
+/// Equal up to rounding: within `1e-9` of each other, or of the larger of
+/// the two for large reals, so `approx_eq(0.1 + 0.2, 0.3)` is `True`.
+fn eval_approx_eq(args: Vec<EnvValue>, _env: &Environment<EnvValue>) -> Result<EnvValue, ErrorMessage> {
+    let a = real_arg("approx_eq", argument("approx_eq", &args, 0)?)?;
+    let b = real_arg("approx_eq", argument("approx_eq", &args, 1)?)?;
+    Ok(EnvValue::Exp(bool_value(approx::relative_eq!(a, b, epsilon = 1e-9, max_relative = 1e-9))))
+}
+
+fn check_exact_eq(args: &[Type], _env: &Environment<Type>) -> Result<Type, ErrorMessage> {
+    expect_arity("exact_eq", args, 2)?;
+    for arg in args {
+        expect_one_of("exact_eq", arg, &[Type::TInteger, Type::TReal])?;
+    }
+    Ok(Type::TBool)
+}
+
+/// `==` on reals, for code that means the exact comparison.
+fn eval_exact_eq(args: Vec<EnvValue>, _env: &Environment<EnvValue>) -> Result<EnvValue, ErrorMessage> {
+    let a = real_arg("exact_eq", argument("exact_eq", &args, 0)?)?;
+    let b = real_arg("exact_eq", argument("exact_eq", &args, 1)?)?;
+    Ok(EnvValue::Exp(bool_value(a == b)))
+}
+
+fn bool_value(value: bool) -> Expression {
+    match value {
+        true => Expression::CTrue,
+        false => Expression::CFalse,
+    }
+}
+
+fn complex_arg(name: &str, args: &[EnvValue]) -> Result<Complex, ErrorMessage> {
+    match argument(name, args, 0)? {
+        EnvValue::Exp(Expression::CComplex(z)) => Ok(*z),
+        value => Ok(Complex::from(real_arg(name, value)?)),
+    }
+}
+
+fn check_complex(args: &[Type], _env: &Environment<Type>) -> Result<Type, ErrorMessage> {
+    expect_arity("complex", args, 2)?;
+    for arg in args {
+        expect_one_of("complex", arg, &[Type::TInteger, Type::TReal])?;
+    }
+    Ok(Type::TComplex)
+}
+
+fn eval_complex(args: Vec<EnvValue>, _env: &Environment<EnvValue>) -> Result<EnvValue, ErrorMessage> {
+    let re = real_arg("complex", argument("complex", &args, 0)?)?;
+    let im = real_arg("complex", argument("complex", &args, 1)?)?;
+    let z = Complex::new(re, im);
+    Ok(EnvValue::Exp(Expression::CComplex(z)))
+}
+
+/// `re`, `im` and `arg` take any number that promotes to a complex one.
+fn check_complex_part(name: &str, args: &[Type]) -> Result<Type, ErrorMessage> {
+    expect_arity(name, args, 1)?;
+    expect_one_of(name, &args[0], &[Type::TInteger, Type::TReal, Type::TComplex])?;
+    Ok(Type::TReal)
+}
+
+fn check_re(args: &[Type], _env: &Environment<Type>) -> Result<Type, ErrorMessage> {
+    check_complex_part("re", args)
+}
+
+fn check_im(args: &[Type], _env: &Environment<Type>) -> Result<Type, ErrorMessage> {
+    check_complex_part("im", args)
+}
+
+fn check_arg(args: &[Type], _env: &Environment<Type>) -> Result<Type, ErrorMessage> {
+    check_complex_part("arg", args)
+}
+
+fn eval_re(args: Vec<EnvValue>, _env: &Environment<EnvValue>) -> Result<EnvValue, ErrorMessage> {
+    Ok(EnvValue::Exp(Expression::CReal(complex_arg("re", &args)?.re)))
+}
+
+fn eval_im(args: Vec<EnvValue>, _env: &Environment<EnvValue>) -> Result<EnvValue, ErrorMessage> {
+    Ok(EnvValue::Exp(Expression::CReal(complex_arg("im", &args)?.im)))
+}
+
+/// The phase in radians, in `(-pi, pi]`.
+fn eval_arg(args: Vec<EnvValue>, _env: &Environment<EnvValue>) -> Result<EnvValue, ErrorMessage> {
+    Ok(EnvValue::Exp(Expression::CReal(complex_arg("arg", &args)?.arg())))
+}
+
+fn check_conj(args: &[Type], _env: &Environment<Type>) -> Result<Type, ErrorMessage> {
+    expect_arity("conj", args, 1)?;
+    expect_one_of("conj", &args[0], &[Type::TComplex])?;
+    Ok(Type::TComplex)
+}
+
+fn eval_conj(args: Vec<EnvValue>, _env: &Environment<EnvValue>) -> Result<EnvValue, ErrorMessage> {
+    Ok(EnvValue::Exp(Expression::CComplex(complex_arg("conj", &args)?.conj())))
+}
+
+fn check_abs(args: &[Type], _env: &Environment<Type>) -> Result<Type, ErrorMessage> {
+    expect_arity("abs", args, 1)?;
+    expect_one_of("abs", &args[0], &[Type::TInteger, Type::TReal, Type::TDecimal, Type::TComplex])?;
+    match args[0] {
+        Type::TComplex => Ok(Type::TReal),
+        ref kind => Ok(kind.clone()),
+    }
+}
+
+/// The magnitude of a number, of the same type except for complex numbers,
+/// whose magnitude is a real.
+fn eval_abs(args: Vec<EnvValue>, _env: &Environment<EnvValue>) -> Result<EnvValue, ErrorMessage> {
+    let magnitude = match argument("abs", &args, 0)? {
+        EnvValue::Exp(Expression::CInt(n)) => match n.checked_abs() {
+            Some(n) => Expression::CInt(n),
+            None => return Err(format!("[Runtime Error] 'abs()' of {} is out of range for an integer.", n)),
+        },
+        EnvValue::Exp(Expression::CReal(x)) => Expression::CReal(x.abs()),
+        EnvValue::Exp(Expression::CDecimal(d)) => Expression::CDecimal(d.abs()),
+        EnvValue::Exp(Expression::CComplex(z)) => Expression::CReal(z.abs()),
+        other => return Err(mismatched("abs", "a number", other)),
+    };
+    Ok(EnvValue::Exp(magnitude))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interpreter::interpreter::eval;
+    use crate::ir::ast::Expression::*;
+    use crate::tc::type_checker::check_exp;
+
+    fn call(name: &str, args: Vec<Expression>) -> Result<EnvValue, (String, Option<Expression>)> {
+        let env: Environment<EnvValue> = Environment::new();
+        eval(FuncCall(name.to_string(), args), &env)
+    }
+
+    fn decimal(text: &str) -> Expression {
+        CDecimal(Decimal::parse(text).unwrap())
+    }
+
+    #[test]
+    fn conversions_between_numbers() {
+        assert_eq!(call("decimal", vec![CReal(0.1)]), Ok(EnvValue::Exp(decimal("0.1"))));
+        assert_eq!(call("decimal", vec![CInt(3)]), Ok(EnvValue::Exp(decimal("3"))));
+        assert_eq!(
+            call("decimal", vec![CReal(f64::INFINITY)]),
+            Err((String::from("[Runtime Error] 'decimal()' cannot convert inf."), None))
+        );
+        assert_eq!(call("to_real", vec![decimal("1.25")]), Ok(EnvValue::Exp(CReal(1.25))));
+        assert_eq!(call("to_int", vec![decimal("-7.99")]), Ok(EnvValue::Exp(CInt(-7))));
+        assert_eq!(call("to_int", vec![CReal(2.5)]), Ok(EnvValue::Exp(CInt(2))));
+        assert_eq!(call("round", vec![decimal("2.345"), CInt(2)]), Ok(EnvValue::Exp(decimal("2.34"))));
+        let sum = || Add(Box::new(CReal(0.1)), Box::new(CReal(0.2)));
+        assert_eq!(call("approx_eq", vec![sum(), CReal(0.3)]), Ok(EnvValue::Exp(CTrue)));
+        assert_eq!(call("approx_eq", vec![CReal(1.0), CInt(2)]), Ok(EnvValue::Exp(CFalse)));
+        assert_eq!(call("exact_eq", vec![sum(), CReal(0.3)]), Ok(EnvValue::Exp(CFalse)));
+        assert_eq!(
+            call("parse_decimal", vec![CString(" 19.90 ".to_string())]),
+            Ok(EnvValue::Exp(COk(Box::new(decimal("19.90")))))
+        );
+        assert_eq!(
+            call("parse_decimal", vec![CString("1,50".to_string())]),
+            Ok(EnvValue::Exp(CErr(Box::new(CString("invalid decimal '1,50'".to_string())))))
+        );
+    }
+
+    #[test]
+    fn complex_functions() {
+        let z = CComplex(Complex::new(3.0, -4.0));
+
+        assert_eq!(call("complex", vec![CInt(3), CReal(-4.0)]), Ok(EnvValue::Exp(z.clone())));
+        assert_eq!(call("re", vec![z.clone()]), Ok(EnvValue::Exp(CReal(3.0))));
+        assert_eq!(call("im", vec![z.clone()]), Ok(EnvValue::Exp(CReal(-4.0))));
+        assert_eq!(call("abs", vec![z.clone()]), Ok(EnvValue::Exp(CReal(5.0))));
+        assert_eq!(call("arg", vec![CInt(-1)]), Ok(EnvValue::Exp(CReal(std::f64::consts::PI))));
+        assert_eq!(call("conj", vec![z]), Ok(EnvValue::Exp(CComplex(Complex::new(3.0, 4.0)))));
+        assert_eq!(call("abs", vec![CInt(-3)]), Ok(EnvValue::Exp(CInt(3))));
+        assert_eq!(call("abs", vec![decimal("-1.50")]), Ok(EnvValue::Exp(decimal("1.50"))));
+    }
+
+    #[test]
+    fn conversions_check_their_arguments() {
+        let env: Environment<Type> = Environment::new();
+        assert_eq!(check_exp(FuncCall("to_real".to_string(), vec![decimal("1")]), &env), Ok(Type::TReal));
+        assert_eq!(
+            check_exp(FuncCall("round".to_string(), vec![CReal(1.5), CInt(0)]), &env),
+            Err(String::from("[Type Error] 'round()' has mismatched arguments: expected 'TDecimal', found 'TReal'."))
+        );
+        assert_eq!(
+            check_exp(FuncCall("abs".to_string(), vec![CComplex(Complex::new(0.0, 1.0))]), &env),
+            Ok(Type::TReal)
+        );
+        assert_eq!(
+            check_exp(FuncCall("im".to_string(), vec![decimal("1")]), &env),
+            Err(String::from(
+                "[Type Error] 'im()' has mismatched arguments: expected 'TInteger' or 'TReal' or 'TComplex', found 'TDecimal'."
+            ))
+        );
+    }
+}
@@ -0,0 +1,209 @@
+use crate::builtins::registry::{argument, expect_arity, mismatched, Builtin};
+use crate::interpreter::interpreter::EnvValue;
+use crate::ir::ast::{Environment, Expression, Type};
+
+type ErrorMessage = String;
+
+pub const BUILTINS: &[Builtin] = &[
+    Builtin {
+        name: "constructors_of",
+        check: check_constructors_of,
+        eval: eval_constructors_of,
+    },
+    Builtin {
+        name: "arity_of",
+        check: check_arity_of,
+        eval: eval_arity_of,
+    },
+];
+
+fn expect_string(name: &str, args: &[Type]) -> Result<(), ErrorMessage> {
+    expect_arity(name, args, 1)?;
+    if args[0] != Type::TString {
+        return Err(format!(
+            "[Type Error] '{}()' has mismatched arguments: expected 'TString', found '{:?}'.",
+            name, args[0]
+        ));
+    }
+    Ok(())
+}
+
+fn string_arg<'a>(name: &str, args: &'a [EnvValue]) -> Result<&'a str, ErrorMessage> {
+    match argument(name, args, 0)? {
+        EnvValue::Exp(Expression::CString(s)) => Ok(s),
+        other => Err(mismatched(name, "a string", other)),
+    }
+}
+
+fn check_constructors_of(args: &[Type], _env: &Environment<Type>) -> Result<Type, ErrorMessage> {
+    expect_string("constructors_of", args)?;
+    Ok(Type::TList(Box::new(Type::TString)))
+}
+
+/// Constructor names of an ADT, in declaration (tag) order.
+fn eval_constructors_of(
+    args: Vec<EnvValue>,
+    env: &Environment<EnvValue>,
+) -> Result<EnvValue, ErrorMessage> {
+    let adt_name = string_arg("constructors_of", &args)?.to_string();
+
+    match env.get_type(&adt_name) {
+        Some(constructors) => Ok(EnvValue::Exp(Expression::CList(
+            constructors
+                .iter()
+                .map(|vc| Expression::CString(vc.name.clone()))
+                .collect(),
+        ))),
+        None => Err(format!("[Runtime Error] ADT '{}' is not defined.", adt_name)),
+    }
+}
+
+fn check_arity_of(args: &[Type], _env: &Environment<Type>) -> Result<Type, ErrorMessage> {
+    expect_string("arity_of", args)?;
+    Ok(Type::TInteger)
+}
+
+/// Number of arguments of a constructor. Constructor names are not
+/// qualified, so a name declared by more than one ADT is rejected.
+fn eval_arity_of(args: Vec<EnvValue>, env: &Environment<EnvValue>) -> Result<EnvValue, ErrorMessage> {
+    let constructor_name = string_arg("arity_of", &args)?;
+
+    let mut found: Vec<(&String, usize)> = env
+        .type_env
+        .iter()
+        .filter_map(|(adt_name, constructors)| {
+            constructors
+                .iter()
+                .find(|vc| vc.name == constructor_name)
+                .map(|vc| (adt_name, vc.types.len()))
+        })
+        .collect();
+
+    match found.len() {
+        0 => Err(format!(
+            "[Runtime Error] constructor '{}' is not defined.",
+            constructor_name
+        )),
+        1 => Ok(EnvValue::Exp(Expression::CInt(found[0].1 as i32))),
+        _ => {
+            found.sort();
+            Err(format!(
+                "[Runtime Error] constructor '{}' is ambiguous: declared by {}.",
+                constructor_name,
+                found
+                    .iter()
+                    .map(|(adt_name, _)| format!("'{}'", adt_name))
+                    .collect::<Vec<String>>()
+                    .join(", ")
+            ))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interpreter::interpreter::eval;
+    use crate::ir::ast::Expression::*;
+    use crate::ir::ast::Type::*;
+    use crate::ir::ast::ValueConstructor;
+    use crate::tc::type_checker::check_exp;
+
+    fn shapes_env() -> Environment<EnvValue> {
+        let mut env = Environment::new();
+        env.insert_type(
+            "Shape".to_string(),
+            vec![
+                ValueConstructor {
+                    name: "Circle".to_string(),
+                    types: vec![TReal],
+                },
+                ValueConstructor {
+                    name: "Rectangle".to_string(),
+                    types: vec![TReal, TReal],
+                },
+            ],
+        );
+        env
+    }
+
+    #[test]
+    fn constructors_of_lists_constructors_in_tag_order() {
+        let env = shapes_env();
+        let call = FuncCall(
+            "constructors_of".to_string(),
+            vec![CString("Shape".to_string())],
+        );
+
+        assert_eq!(
+            eval(call, &env),
+            Ok(EnvValue::Exp(CList(vec![
+                CString("Circle".to_string()),
+                CString("Rectangle".to_string()),
+            ])))
+        );
+    }
+
+    #[test]
+    fn arity_of_counts_constructor_arguments() {
+        let mut env = shapes_env();
+        let arity_of = |name: &str, env: &Environment<EnvValue>| {
+            eval(
+                FuncCall("arity_of".to_string(), vec![CString(name.to_string())]),
+                env,
+            )
+        };
+
+        assert_eq!(arity_of("Rectangle", &env), Ok(EnvValue::Exp(CInt(2))));
+        assert_eq!(
+            arity_of("Square", &env),
+            Err((
+                String::from("[Runtime Error] constructor 'Square' is not defined."),
+                None
+            ))
+        );
+
+        env.insert_type(
+            "Window".to_string(),
+            vec![ValueConstructor {
+                name: "Rectangle".to_string(),
+                types: vec![],
+            }],
+        );
+        assert_eq!(
+            arity_of("Rectangle", &env),
+            Err((
+                String::from("[Runtime Error] constructor 'Rectangle' is ambiguous: declared by 'Shape', 'Window'."),
+                None
+            ))
+        );
+
+        // Unchecked programs may pass anything
+        assert_eq!(
+            eval(FuncCall("arity_of".to_string(), vec![CInt(1)]), &env),
+            Err((
+                String::from("[Runtime Error] 'arity_of()' expects a string, found a value of type 'Int'."),
+                None
+            ))
+        );
+    }
+
+    #[test]
+    fn check_reflection_builtins() {
+        let env: Environment<Type> = Environment::new();
+
+        assert_eq!(
+            check_exp(
+                FuncCall("arity_of".to_string(), vec![CString("Circle".to_string())]),
+                &env
+            ),
+            Ok(TInteger)
+        );
+        assert_eq!(
+            check_exp(FuncCall("constructors_of".to_string(), vec![CInt(1)]), &env),
+            Err(String::from(
+                "[Type Error] 'constructors_of()' has mismatched arguments: expected 'TString', found 'TInteger'."
+            ))
+        );
+    }
+}
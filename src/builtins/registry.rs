@@ -0,0 +1,108 @@
+use std::collections::HashMap;
+
+use once_cell::sync::Lazy;
+
+use crate::builtins::{bytes, codec, csv, format, functional, inspect, io, matrix, numeric, reflect};
+#[cfg(feature = "http")]
+use crate::builtins::http;
+use crate::interpreter::interpreter::EnvValue;
+use crate::interpreter::value::type_name;
+use crate::ir::ast::{Environment, Type};
+
+type ErrorMessage = String;
+
+/// A function implemented in Rust and callable from R-Python programs.
+///
+/// `check` receives the types of the arguments and returns the type of the
+/// call, so builtins can be generic over their argument types. `eval`
+/// receives the already evaluated arguments.
+pub struct Builtin {
+    pub name: &'static str,
+    pub check: fn(&[Type], &Environment<Type>) -> Result<Type, ErrorMessage>,
+    pub eval: fn(Vec<EnvValue>, &Environment<EnvValue>) -> Result<EnvValue, ErrorMessage>,
+}
+
+const MODULES: &[&[Builtin]] = &[
+    inspect::BUILTINS,
+    reflect::BUILTINS,
+    codec::BUILTINS,
+    bytes::BUILTINS,
+    numeric::BUILTINS,
+    matrix::BUILTINS,
+    csv::BUILTINS,
+    format::BUILTINS,
+    functional::BUILTINS,
+    io::BUILTINS,
+    #[cfg(feature = "http")]
+    http::BUILTINS,
+];
+
+/// Every builtin by name, built on the first lookup. Calls to builtins
+/// are resolved on every evaluation, so they are not a scan of `MODULES`.
+static INDEX: Lazy<HashMap<&'static str, &'static Builtin>> = Lazy::new(|| {
+    let mut index = HashMap::new();
+    for builtin in MODULES.iter().flat_map(|module| module.iter()) {
+        index.entry(builtin.name).or_insert(builtin);
+    }
+    index
+});
+
+/// Builtins are only consulted after user definitions, so programs may
+/// shadow them.
+pub fn lookup(name: &str) -> Option<&'static Builtin> {
+    INDEX.get(name).copied()
+}
+
+/// The names of every builtin, for completion.
+pub fn names() -> impl Iterator<Item = &'static str> {
+    MODULES.iter().flat_map(|module| module.iter()).map(|builtin| builtin.name)
+}
+
+pub fn expect_arity(name: &str, args: &[Type], arity: usize) -> Result<(), ErrorMessage> {
+    if args.len() != arity {
+        return Err(format!(
+            "[Type Error] '{}()' expected {} arguments, found {}.",
+            name,
+            arity,
+            args.len()
+        ));
+    }
+    Ok(())
+}
+
+/// `expect_arity` for the values of a call, which programs run without the
+/// checker may get wrong.
+pub fn expect_values(name: &str, args: &[EnvValue], arity: usize) -> Result<(), ErrorMessage> {
+    if args.len() != arity {
+        return Err(format!(
+            "[Runtime Error] '{}()' expected {} arguments, found {}.",
+            name,
+            arity,
+            args.len()
+        ));
+    }
+    Ok(())
+}
+
+/// The `i`th argument of a call. Programs run without the checker may pass
+/// fewer arguments than a builtin takes, which is an error, not a panic.
+pub fn argument<'a>(name: &str, args: &'a [EnvValue], i: usize) -> Result<&'a EnvValue, ErrorMessage> {
+    args.get(i).ok_or_else(|| {
+        format!(
+            "[Runtime Error] '{}()' expected at least {} arguments, found {}.",
+            name,
+            i + 1,
+            args.len()
+        )
+    })
+}
+
+/// The error for an argument the checker would have rejected.
+pub fn mismatched(name: &str, expected: &str, found: &EnvValue) -> ErrorMessage {
+    format!(
+        "[Runtime Error] '{}()' expects {}, found a value of type '{}'.",
+        name,
+        expected,
+        type_name(found)
+    )
+}
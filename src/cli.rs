@@ -0,0 +1,79 @@
+pub mod dump;
+#[cfg(feature = "line-editor")]
+pub mod editor;
+pub mod judge;
+pub mod lint;
+pub mod locale;
+pub mod project;
+pub mod repl;
+pub mod viz;
+
+use locale::Language;
+
+const USAGE: &str = "usage: rpython <command> [options] <file>
+
+commands:
+  run [--record=<out>] [--timeline=<out>] [<file>|<dir>]
+      run a file, or the project whose rpython.toml is in <dir> (default: .);
+      --record writes every state change of the run to <out>, for a replay;
+      --timeline writes them as a JSON timeline, for a step-through visualizer
+  check [--trace] [--workspace] [<file>|<dir>]
+      type check a file or a project and report lint warnings; --trace prints
+      the derivation of every type instead; --workspace also reports imports
+      never used and module definitions no importer uses
+  test [<file>|<dir>]
+      run the test_... functions of a file or a project
+  repl [<dir>]
+      start an interactive session; modules are looked up in <dir> or its project roots
+  dump --stage=ast|typed|desugared [--json] <file>
+      print the program as it leaves a stage of the pipeline
+  judge --cases=<dir> [--time-limit=ms] <file>
+      run the program on every NAME.in in <dir> and compare its output with NAME.out
+  lint <file>
+      report constant conditions, divisions by zero and out-of-range indexing
+  viz --what=ast|cfg [--format=dot|mermaid] <file>
+      draw the syntax tree or the control-flow graph of each function";
+
+/// Runs the command in `args` (without the program name) and returns what
+/// it prints on success.
+///
+/// Project commands print their diagnostics in the language of the
+/// manifest; the others in the one `RPYTHON_LANG` selects, English by
+/// default.
+pub fn run(args: &[String]) -> Result<String, String> {
+    let language = Language::selected(Language::default());
+    match args.first().map(String::as_str) {
+        Some("run") => project::run(&args[1..]),
+        Some("check") => project::check(&args[1..]),
+        Some("test") => project::test(&args[1..]),
+        Some("repl") => repl::run(&args[1..]).map_err(|message| locale::translate(&message, language)),
+        Some("dump") => dump::run(&args[1..]).map_err(|message| locale::translate(&message, language)),
+        Some("judge") => judge::run(&args[1..]).map_err(|message| locale::translate(&message, language)),
+        Some("lint") => locale::localize(lint::run(&args[1..]), language),
+        Some("viz") => viz::run(&args[1..]).map_err(|message| locale::translate(&message, language)),
+        Some(command) => Err(format!("unknown command '{}'\n\n{}", command, USAGE)),
+        None => Err(USAGE.to_string()),
+    }
+}
+
+/// Splits `args` into `--name=value` options, `--flag` switches and the
+/// positional arguments.
+pub fn split_options(args: &[String]) -> (Vec<(&str, Option<&str>)>, Vec<&str>) {
+    let mut options = vec![];
+    let mut positional = vec![];
+
+    for arg in args {
+        match arg.strip_prefix("--") {
+            Some(option) => match option.split_once('=') {
+                Some((name, value)) => options.push((name, Some(value))),
+                None => options.push((option, None)),
+            },
+            None => positional.push(arg.as_str()),
+        }
+    }
+    (options, positional)
+}
+
+pub fn read_source(path: &str) -> Result<String, String> {
+    std::fs::read_to_string(path).map_err(|e| format!("cannot read '{}': {}", path, e))
+}
@@ -0,0 +1,145 @@
+use crate::cli::{read_source, split_options};
+use crate::ir::ast::{Name, Statement, Type};
+use crate::ir::{desugar, json};
+use crate::parser::parser::parse_program;
+use crate::tc::type_checker;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Stage {
+    /// The syntax tree as the parser builds it.
+    Ast,
+    /// The core program with the types the checker gives each top-level name.
+    Typed,
+    /// The core program the checker and the interpreter consume.
+    Desugared,
+}
+
+impl Stage {
+    fn name(&self) -> &'static str {
+        match self {
+            Stage::Ast => "ast",
+            Stage::Typed => "typed",
+            Stage::Desugared => "desugared",
+        }
+    }
+}
+
+pub fn run(args: &[String]) -> Result<String, String> {
+    let (options, positional) = split_options(args);
+    let mut stage = Stage::Ast;
+    let mut as_json = false;
+
+    for (name, value) in options {
+        match (name, value) {
+            ("stage", Some("ast")) => stage = Stage::Ast,
+            ("stage", Some("typed")) => stage = Stage::Typed,
+            ("stage", Some("desugared")) => stage = Stage::Desugared,
+            ("stage", value) => {
+                return Err(format!(
+                    "unknown stage '{}': expected ast, typed or desugared",
+                    value.unwrap_or("")
+                ))
+            }
+            ("json", None) => as_json = true,
+            _ => return Err(format!("unknown option '--{}'", name)),
+        }
+    }
+
+    let path = match positional.as_slice() {
+        [path] => *path,
+        _ => return Err(String::from("dump expects exactly one file")),
+    };
+    dump(&read_source(path)?, stage, as_json)
+}
+
+/// Runs the pipeline on `source` up to `stage` and prints the result.
+pub fn dump(source: &str, stage: Stage, as_json: bool) -> Result<String, String> {
+    let mut program = parse_program(source)?;
+    if stage != Stage::Ast {
+        program = desugar::program(program);
+    }
+    let types = match stage {
+        Stage::Typed => Some(check_program(&program)?),
+        _ => None,
+    };
+
+    if as_json {
+        let mut out = format!(
+            "{{\"stage\": {}, \"program\": {}",
+            json::string(stage.name()),
+            json::statements(&program)
+        );
+        if let Some(types) = &types {
+            out.push_str(&format!(", \"types\": {}", json::bindings(types)));
+        }
+        out.push_str("}\n");
+        return Ok(out);
+    }
+
+    let mut out = String::new();
+    for stmt in &program {
+        out.push_str(&format!("{:#?}\n", stmt));
+    }
+    if let Some(types) = &types {
+        out.push_str("\n-- types --\n");
+        for (name, kind) in types {
+            out.push_str(&format!("{}: {:?}\n", name, kind));
+        }
+    }
+    Ok(out)
+}
+
+/// The types of the names the program binds at the top level, sorted by
+/// name.
+fn check_program(program: &[Statement]) -> Result<Vec<(Name, Type)>, String> {
+    let env = type_checker::check_program(program)?;
+    Ok(env
+        .get_frame(env.scope_key())
+        .variables
+        .iter()
+        .map(|(name, kind)| (name.clone(), kind.clone()))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PROGRAM: &str = "x = 1\nname = \"ann\"\nx = x * 2";
+
+    #[test]
+    fn dump_prints_each_stage() {
+        let ast = dump(PROGRAM, Stage::Ast, false).unwrap();
+        assert!(ast.starts_with("Assignment(\n    \"x\",\n"));
+
+        let piped = "y = 1\ny = y |> inc";
+        assert!(dump(piped, Stage::Ast, false).unwrap().contains("Pipe("));
+        assert!(!dump(piped, Stage::Desugared, false).unwrap().contains("Pipe("));
+
+        let typed = dump(PROGRAM, Stage::Typed, false).unwrap();
+        assert!(typed.ends_with("-- types --\nname: TString\nx: TInteger\n"));
+
+        assert_eq!(
+            dump("x = 1", Stage::Typed, true),
+            Ok(String::from(
+                "{\"stage\": \"typed\", \"program\": [{\"Assignment\": [\"x\", {\"CInt\": [1]}, \"TInteger\"]}], \"types\": {\"x\": \"TInteger\"}}\n"
+            ))
+        );
+    }
+
+    #[test]
+    fn dump_reports_errors() {
+        assert_eq!(
+            dump("x = 1\ny = z * 2", Stage::Typed, false),
+            Err(String::from("[Name Error on '__main__'] 'z' is not defined."))
+        );
+        assert_eq!(
+            dump("x = 1\ny = * 2", Stage::Ast, false),
+            Err(String::from("[Syntax Error] unexpected input on line 2: 'y = * 2'."))
+        );
+        assert_eq!(
+            run(&["--stage=cfg".to_string(), "a.rpy".to_string()]),
+            Err(String::from("unknown stage 'cfg': expected ast, typed or desugared"))
+        );
+    }
+}
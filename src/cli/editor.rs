@@ -0,0 +1,239 @@
+use std::io::{self, Read, Write};
+use std::process::{Command, Stdio};
+
+/// A minimal line editor for terminals: cursor movement, history and tab
+/// completion. The terminal is switched to raw mode with `stty` while a line
+/// is read.
+pub struct Editor {
+    history: Vec<String>,
+}
+
+enum Key {
+    Char(char),
+    Enter,
+    Tab,
+    Backspace,
+    Delete,
+    Left,
+    Right,
+    Up,
+    Down,
+    Home,
+    End,
+    Interrupt,
+    Eof,
+    Other,
+}
+
+impl Editor {
+    pub fn new(history: Vec<String>) -> Editor {
+        Editor { history }
+    }
+
+    /// Reads a line, starting with `initial` already typed. `complete`
+    /// gives the candidates for the text before the cursor, and `start`
+    /// where the word they replace begins. `None` means end of input.
+    pub fn read_line(
+        &mut self,
+        prompt: &str,
+        initial: &str,
+        complete: &dyn Fn(&str) -> Vec<String>,
+        start: fn(&str) -> usize,
+    ) -> io::Result<Option<String>> {
+        let saved = stty(&["-g"])?;
+        stty(&["-icanon", "-echo", "min", "1"])?;
+        let line = self.edit(prompt, initial, complete, start);
+        stty(&[saved.trim()])?;
+        println!();
+        if let Ok(Some(line)) = &line {
+            if !line.trim().is_empty() {
+                self.history.push(line.clone());
+            }
+        }
+        line
+    }
+
+    fn edit(
+        &mut self,
+        prompt: &str,
+        initial: &str,
+        complete: &dyn Fn(&str) -> Vec<String>,
+        start: fn(&str) -> usize,
+    ) -> io::Result<Option<String>> {
+        let mut line: Vec<char> = initial.chars().collect();
+        let mut cursor = line.len();
+        let mut recalled = self.history.len();
+        let mut draft = vec![];
+        let mut stdin = io::stdin().lock();
+
+        loop {
+            redraw(prompt, &line, cursor)?;
+            match read_key(&mut stdin)? {
+                Key::Char(c) => {
+                    line.insert(cursor, c);
+                    cursor += 1;
+                }
+                Key::Enter => return Ok(Some(line.into_iter().collect())),
+                Key::Eof if line.is_empty() => return Ok(None),
+                Key::Eof | Key::Delete if cursor < line.len() => {
+                    line.remove(cursor);
+                }
+                Key::Backspace if cursor > 0 => {
+                    cursor -= 1;
+                    line.remove(cursor);
+                }
+                Key::Left if cursor > 0 => cursor -= 1,
+                Key::Right if cursor < line.len() => cursor += 1,
+                Key::Home => cursor = 0,
+                Key::End => cursor = line.len(),
+                Key::Up if recalled > 0 => {
+                    if recalled == self.history.len() {
+                        draft = line.clone();
+                    }
+                    recalled -= 1;
+                    line = self.history[recalled].chars().collect();
+                    cursor = line.len();
+                }
+                Key::Down if recalled < self.history.len() => {
+                    recalled += 1;
+                    line = match self.history.get(recalled) {
+                        Some(entry) => entry.chars().collect(),
+                        None => draft.clone(),
+                    };
+                    cursor = line.len();
+                }
+                Key::Interrupt => {
+                    line.clear();
+                    cursor = 0;
+                }
+                Key::Tab => {
+                    let before: String = line[..cursor].iter().collect();
+                    let word = start(&before);
+                    if before[word..].is_empty() {
+                        for _ in 0..4 {
+                            line.insert(cursor, ' ');
+                            cursor += 1;
+                        }
+                        continue;
+                    }
+                    let candidates = complete(&before);
+                    let common = common_prefix(&candidates);
+                    let typed = before[word..].chars().count();
+                    if common.chars().count() > typed {
+                        let rest: Vec<char> = common.chars().skip(typed).collect();
+                        line.splice(cursor..cursor, rest.iter().copied());
+                        cursor += rest.len();
+                    } else if candidates.len() > 1 {
+                        print!("\r\n{}\r\n", candidates.join("  "));
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+/// The longest prefix all of `words` share.
+fn common_prefix(words: &[String]) -> String {
+    let Some(first) = words.first() else {
+        return String::new();
+    };
+    let mut prefix = first.as_str();
+    for word in &words[1..] {
+        let end = prefix
+            .char_indices()
+            .zip(word.chars())
+            .find(|((_, a), b)| a != b)
+            .map_or(prefix.len().min(word.len()), |((i, _), _)| i);
+        prefix = &prefix[..end];
+    }
+    prefix.to_string()
+}
+
+fn redraw(prompt: &str, line: &[char], cursor: usize) -> io::Result<()> {
+    let text: String = line.iter().collect();
+    let mut stdout = io::stdout();
+    write!(stdout, "\r\x1b[K{}{}", prompt, text)?;
+    if cursor < line.len() {
+        write!(stdout, "\x1b[{}D", line.len() - cursor)?;
+    }
+    stdout.flush()
+}
+
+fn read_byte(input: &mut impl Read) -> io::Result<Option<u8>> {
+    let mut byte = [0];
+    match input.read(&mut byte)? {
+        0 => Ok(None),
+        _ => Ok(Some(byte[0])),
+    }
+}
+
+fn read_key(input: &mut impl Read) -> io::Result<Key> {
+    let Some(byte) = read_byte(input)? else {
+        return Ok(Key::Eof);
+    };
+    Ok(match byte {
+        b'\r' | b'\n' => Key::Enter,
+        b'\t' => Key::Tab,
+        0x7f | 0x08 => Key::Backspace,
+        0x01 => Key::Home,
+        0x05 => Key::End,
+        0x03 => Key::Interrupt,
+        0x04 => Key::Eof,
+        0x1b => match (read_byte(input)?, read_byte(input)?) {
+            (Some(b'['), Some(b'A')) => Key::Up,
+            (Some(b'['), Some(b'B')) => Key::Down,
+            (Some(b'['), Some(b'C')) => Key::Right,
+            (Some(b'['), Some(b'D')) => Key::Left,
+            (Some(b'['), Some(b'H')) => Key::Home,
+            (Some(b'['), Some(b'F')) => Key::End,
+            (Some(b'['), Some(b'3')) => {
+                read_byte(input)?;
+                Key::Delete
+            }
+            _ => Key::Other,
+        },
+        byte if byte < 0x20 => Key::Other,
+        byte => {
+            let length = match byte {
+                0xf0.. => 4,
+                0xe0.. => 3,
+                0xc0.. => 2,
+                _ => 1,
+            };
+            let mut bytes = vec![byte];
+            for _ in 1..length {
+                bytes.extend(read_byte(input)?);
+            }
+            match String::from_utf8(bytes).ok().and_then(|s| s.chars().next()) {
+                Some(c) => Key::Char(c),
+                None => Key::Other,
+            }
+        }
+    })
+}
+
+fn stty(args: &[&str]) -> io::Result<String> {
+    let output = Command::new("stty")
+        .args(args)
+        .stdin(Stdio::inherit())
+        .output()?;
+    if !output.status.success() {
+        return Err(io::Error::other(String::from_utf8_lossy(&output.stderr).to_string()));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn completion_inserts_the_common_prefix() {
+        let words = |words: &[&str]| words.iter().map(|w| w.to_string()).collect::<Vec<_>>();
+        assert_eq!(common_prefix(&words(&["total", "totem"])), "tot");
+        assert_eq!(common_prefix(&words(&["print"])), "print");
+        assert_eq!(common_prefix(&words(&["len", "length"])), "len");
+        assert_eq!(common_prefix(&[]), "");
+    }
+}
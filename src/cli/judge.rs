@@ -0,0 +1,171 @@
+use crate::cli::{read_source, split_options};
+use crate::interpreter::interpreter::{EnvValue, TIMEOUT};
+use crate::interpreter::program::{execute_program, Stats};
+use crate::ir::ast::{Environment, Io, Limits};
+use crate::ir::desugar;
+use crate::parser::parser::parse_program;
+use crate::tc::type_checker::CheckedProgram;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::Path;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+const DEFAULT_TIME_LIMIT: u64 = 2000;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Verdict {
+    Accepted,
+    WrongAnswer,
+    TimeLimitExceeded,
+    RuntimeError,
+}
+
+impl Verdict {
+    pub fn code(&self) -> &'static str {
+        match self {
+            Verdict::Accepted => "AC",
+            Verdict::WrongAnswer => "WA",
+            Verdict::TimeLimitExceeded => "TLE",
+            Verdict::RuntimeError => "RE",
+        }
+    }
+}
+
+pub fn run(args: &[String]) -> Result<String, String> {
+    let (options, positional) = split_options(args);
+    let mut cases = None;
+    let mut time_limit = DEFAULT_TIME_LIMIT;
+
+    for (name, value) in options {
+        match (name, value) {
+            ("cases", Some(dir)) => cases = Some(dir),
+            ("time-limit", Some(ms)) => {
+                time_limit = ms
+                    .parse()
+                    .map_err(|_| format!("invalid time limit '{}': expected milliseconds", ms))?
+            }
+            ("cases" | "time-limit", None) => return Err(format!("option '--{}' expects a value", name)),
+            _ => return Err(format!("unknown option '--{}'", name)),
+        }
+    }
+
+    let path = match positional.as_slice() {
+        [path] => *path,
+        _ => return Err(String::from("judge expects exactly one file")),
+    };
+    let cases = cases.ok_or_else(|| String::from("judge expects a '--cases' directory"))?;
+    judge(&read_source(path)?, Path::new(cases), Duration::from_millis(time_limit))
+}
+
+/// Runs `source` once for every `NAME.in` file in `cases` and compares what
+/// it prints with `NAME.out`. Prints one verdict per case and a summary,
+/// as an error when a case fails.
+pub fn judge(source: &str, cases: &Path, time_limit: Duration) -> Result<String, String> {
+    let program = CheckedProgram::check(desugar::program(parse_program(source)?))?;
+
+    let entries = std::fs::read_dir(cases)
+        .map_err(|e| format!("cannot read '{}': {}", cases.display(), e))?;
+    let mut names: Vec<String> = entries
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            let name = entry.file_name().into_string().ok()?;
+            name.strip_suffix(".in").map(str::to_string)
+        })
+        .collect();
+    names.sort();
+    if names.is_empty() {
+        return Err(format!("no '.in' files in '{}'", cases.display()));
+    }
+
+    let mut report = String::new();
+    let mut passed = 0;
+    for name in &names {
+        let read = |extension: &str| read_source(&cases.join(format!("{}.{}", name, extension)).to_string_lossy());
+        let verdict = judge_case(&program, &read("in")?, &read("out")?, time_limit);
+        if verdict == Verdict::Accepted {
+            passed += 1;
+        }
+        report.push_str(&format!("{}: {}\n", name, verdict.code()));
+    }
+    report.push_str(&format!("{}/{} passed\n", passed, names.len()));
+    match passed < names.len() {
+        true => Err(report),
+        false => Ok(report),
+    }
+}
+
+/// Runs a desugared program on `input` and judges its output against
+/// `expected`. Trailing spaces and trailing blank lines are not significant.
+pub fn judge_case(program: &CheckedProgram, input: &str, expected: &str, time_limit: Duration) -> Verdict {
+    let mut env: Environment<EnvValue> = Environment::new();
+    env.io = Io::scripted(input);
+    env.limits = Arc::new(Limits {
+        deadline: Some(Instant::now() + time_limit),
+        ..Default::default()
+    });
+
+    let io = env.io.clone();
+    let outcome = panic::catch_unwind(AssertUnwindSafe(|| {
+        execute_program(program, &mut env, &mut Stats::default())
+    }));
+    match outcome {
+        Ok(Ok(_)) if normalize(&io.output().unwrap_or_default()) == normalize(expected) => Verdict::Accepted,
+        Ok(Ok(_)) => Verdict::WrongAnswer,
+        Ok(Err(message)) if message.starts_with(TIMEOUT) => Verdict::TimeLimitExceeded,
+        Ok(Err(_)) | Err(_) => Verdict::RuntimeError,
+    }
+}
+
+fn normalize(output: &str) -> Vec<&str> {
+    let mut lines: Vec<&str> = output.lines().map(str::trim_end).collect();
+    while lines.last() == Some(&"") {
+        lines.pop();
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn verdict(source: &str, input: &str, expected: &str) -> Verdict {
+        let program = CheckedProgram::check(desugar::program(parse_program(source).unwrap())).unwrap();
+        judge_case(&program, input, expected, Duration::from_millis(200))
+    }
+
+    #[test]
+    fn verdicts() {
+        let echo = "def main() -> TVoid:\n    line = input()\n    print(line)";
+        assert_eq!(verdict(echo, "hello\n", "hello  \n\n"), Verdict::Accepted);
+        assert_eq!(verdict(echo, "hello\n", "bye\n"), Verdict::WrongAnswer);
+        assert_eq!(verdict(echo, "", "hello\n"), Verdict::RuntimeError);
+        assert_eq!(verdict("while 1 < 2:\n    x = 1", "", ""), Verdict::TimeLimitExceeded);
+
+        // A runaway recursion is a runtime error, not an abort of the judge
+        let forever = "def f(n: TInteger) -> TInteger:\n    return f(n + 1)\nx = f(0)";
+        assert_eq!(verdict(forever, "", ""), Verdict::RuntimeError);
+    }
+
+    #[test]
+    fn judge_reports_every_case() {
+        let dir = std::env::temp_dir().join(format!("rpython-judge-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        for (name, input, output) in [("a", "1\n", "1\n"), ("b", "2\n", "3\n")] {
+            std::fs::write(dir.join(format!("{}.in", name)), input).unwrap();
+            std::fs::write(dir.join(format!("{}.out", name)), output).unwrap();
+        }
+
+        let report = judge("x = input()\nprint(x)", &dir, Duration::from_secs(1));
+        std::fs::write(dir.join("b.out"), "2\n").unwrap();
+        let source = dir.join("echo.rpy");
+        std::fs::write(&source, "x = input()\nprint(x)").unwrap();
+        let cases = format!("--cases={}", dir.display());
+        let passed = run(&[cases, String::from("--time-limit=1000"), source.to_string_lossy().to_string()]);
+        let spaced = run(&[String::from("--cases"), dir.to_string_lossy().to_string()]);
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(report, Err(String::from("a: AC\nb: WA\n1/2 passed\n")));
+        assert_eq!(passed, Ok(String::from("a: AC\nb: AC\n2/2 passed\n")));
+        assert_eq!(spaced, Err(String::from("option '--cases' expects a value")));
+    }
+}
@@ -0,0 +1,41 @@
+use crate::cli::{read_source, split_options};
+use crate::ir::{dataflow, desugar};
+use crate::parser::parser::parse_located;
+
+pub fn run(args: &[String]) -> Result<String, String> {
+    let (options, positional) = split_options(args);
+    if let Some((name, _)) = options.first() {
+        return Err(format!("unknown option '--{}'", name));
+    }
+
+    let path = match positional.as_slice() {
+        [path] => *path,
+        _ => return Err(String::from("lint expects exactly one file")),
+    };
+    lint(&read_source(path)?)
+}
+
+/// Prints one line per warning the dataflow analyses find in `source`.
+pub fn lint(source: &str) -> Result<String, String> {
+    let program = desugar::program(parse_located(source)?);
+    Ok(dataflow::program(&program)
+        .iter()
+        .map(|warning| format!("{}\n", warning.render()))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lint_prints_warnings() {
+        assert_eq!(lint("x = 1"), Ok(String::new()));
+        assert_eq!(
+            lint("d = 0\ny = 4 / d"),
+            Ok(String::from(
+                "[Warning] in '__main__', line 2, column 1: `y = 4 / d`: division by zero: 'd' is always 0.\n"
+            ))
+        );
+    }
+}
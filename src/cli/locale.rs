@@ -0,0 +1,334 @@
+/// The language diagnostics are printed in.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub enum Language {
+    #[default]
+    English,
+    Portuguese,
+}
+
+/// The environment variable that selects the language, overriding the
+/// manifest.
+pub const LANGUAGE_VARIABLE: &str = "RPYTHON_LANG";
+
+impl Language {
+    /// A language by its code, as in `pt`, `pt_BR.UTF-8` or `en`.
+    pub fn parse(text: &str) -> Option<Language> {
+        let code = text.split(['_', '-', '.']).next().unwrap_or_default();
+        match code.to_ascii_lowercase().as_str() {
+            "en" => Some(Language::English),
+            "pt" => Some(Language::Portuguese),
+            _ => None,
+        }
+    }
+
+    /// The language `RPYTHON_LANG` selects, or `configured` when it is
+    /// unset or names no language.
+    pub fn selected(configured: Language) -> Language {
+        std::env::var(LANGUAGE_VARIABLE)
+            .ok()
+            .and_then(|text| Language::parse(&text))
+            .unwrap_or(configured)
+    }
+}
+
+/// The error codes in brackets at the start of a message.
+const CODES: &[(&str, &str)] = &[
+    ("Type Error", "Erro de Tipo"),
+    ("Runtime Error", "Erro de Execução"),
+    ("Syntax Error", "Erro de Sintaxe"),
+    ("Local Name Error", "Erro de Nome Local"),
+    ("Name Error", "Erro de Nome"),
+    ("Level Error", "Erro de Nível"),
+    ("Manifest Error", "Erro de Manifesto"),
+    ("Warning", "Aviso"),
+];
+
+/// English message templates and their Portuguese translations. A `{}`
+/// matches any text, which is itself translated when a template matches it
+/// whole. More specific templates come first.
+const MESSAGES: &[(&str, &str)] = &[
+    // parser
+    ("unexpected input on line {}: '{}'.", "entrada inesperada na linha {}: '{}'."),
+    ("return statement outside function.", "comando return fora de uma função."),
+    (
+        "contracts are only allowed at the start of a function body.",
+        "contratos só são permitidos no início do corpo de uma função.",
+    ),
+    (
+        "invariants are only allowed at the start of a while body.",
+        "invariantes só são permitidos no início do corpo de um while.",
+    ),
+    (
+        "constants are only allowed at the top level; '{}' is declared in '{}()'.",
+        "constantes só são permitidas no nível mais externo; '{}' é declarada em '{}()'.",
+    ),
+    ("only one 'decreases' clause is allowed.", "só é permitida uma cláusula 'decreases'."),
+    ("'{}' is used in '{}' without 'import {}'.", "'{}' é usado em '{}' sem 'import {}'."),
+    (
+        "'{}' is used in '{}', but no loaded module declares 'module {}'.",
+        "'{}' é usado em '{}', mas nenhum módulo carregado declara 'module {}'.",
+    ),
+    ("module '{}' has no '{}'.", "o módulo '{}' não tem '{}'."),
+    // checker
+    (
+        "'{}' has mismatched arguments: expected {}, found '{}'.",
+        "'{}' tem argumentos incompatíveis: esperado {}, encontrado '{}'.",
+    ),
+    (
+        "'{}' has mismatched types: expected '{}', found '{}'.",
+        "'{}' tem tipos incompatíveis: esperado '{}', encontrado '{}'.",
+    ),
+    (
+        "list elements have mismatched types: expected '{}', found '{}'.",
+        "os elementos da lista têm tipos incompatíveis: esperado '{}', encontrado '{}'.",
+    ),
+    (
+        "dict entries have mismatched types: expected '{}', found '{}'.",
+        "as entradas do dicionário têm tipos incompatíveis: esperado '{}', encontrado '{}'.",
+    ),
+    ("dict keys must be hashable, found '{}'.", "as chaves de um dicionário devem ser hasheáveis, encontrado '{}'."),
+    ("'{}' expected {} arguments, found {}.", "'{}' esperava {} argumentos, encontrou {}."),
+    ("  expected because of {}", "  esperado por causa de {}"),
+    ("  found from {}", "  encontrado a partir de {}"),
+    ("{}, then {}", "{}, depois {}"),
+    ("if expression must be boolean.", "a expressão do if deve ser booleana."),
+    ("while expression must be boolean.", "a expressão do while deve ser booleana."),
+    ("the guard of '{}' must be boolean.", "a guarda de '{}' deve ser booleana."),
+    ("loop invariant must be boolean, found '{}'.", "o invariante do laço deve ser booleano, encontrado '{}'."),
+    ("expecting numeric type values.", "esperava valores de tipo numérico."),
+    ("expecting boolean type values.", "esperava valores de tipo booleano."),
+    ("expecting a boolean type value.", "esperava um valor de tipo booleano."),
+    ("expecting a maybe or result type value.", "esperava um valor de tipo maybe ou result."),
+    (
+        "'?' on a '{}' cannot return from '{}()', which returns '{}'.",
+        "'?' em um '{}' não pode retornar de '{}()', que retorna '{}'.",
+    ),
+    ("expecting a maybe type value.", "esperava um valor de tipo maybe."),
+    ("expecting a result type value.", "esperava um valor de tipo result."),
+    ("complex numbers are not ordered.", "números complexos não são ordenados."),
+    ("cannot mix 'TDecimal' and '{}'; convert {}.", "não é possível misturar 'TDecimal' e '{}'; converta {}."),
+    ("one side with decimal() or to_real()", "um dos lados com decimal() ou to_real()"),
+    ("the decimal with to_real()", "o decimal com to_real()"),
+    (
+        "cannot compare values of ADT '{}' with values of ADT '{}'.",
+        "não é possível comparar valores do ADT '{}' com valores do ADT '{}'.",
+    ),
+    ("cannot compare '{}' with '{}'.", "não é possível comparar '{}' com '{}'."),
+    ("'{}' requires an instance of '{}' for '{}'.", "'{}' requer uma instância de '{}' para '{}'."),
+    ("cannot access local variable '{}'.", "não é possível acessar a variável local '{}'."),
+    ("ADT constructor '{}' not found in ADT '{}'.", "o construtor '{}' não existe no ADT '{}'."),
+    ("ADT '{}' is not defined.", "o ADT '{}' não está definido."),
+    ("constructor '{}' is not defined.", "o construtor '{}' não está definido."),
+    ("'{}' is not defined.", "'{}' não está definido."),
+    (
+        "implicit promotion of `{}` from '{}' to '{}'; write `{}`.",
+        "promoção implícita de `{}` de '{}' para '{}'; escreva `{}`.",
+    ),
+    (
+        "`{} == {}` compares reals exactly, which rounding defeats; write `approx_eq({}, {})`, or `exact_eq({}, {})` if exact equality is meant.",
+        "`{} == {}` compara reais exatamente, o que o arredondamento frustra; escreva `approx_eq({}, {})`, ou `exact_eq({}, {})` se a igualdade exata for intencional.",
+    ),
+    (
+        "the match on '{}' is not exhaustive; no arm matches {}.",
+        "o match sobre '{}' não é exaustivo; nenhum braço casa com {}.",
+    ),
+    (
+        "the pattern '{}' does not match {}; use a match to handle them.",
+        "o padrão '{}' não casa com {}; use um match para tratá-los.",
+    ),
+    (
+        "the constant '{}' is already bound; constants need a name of their own.",
+        "a constante '{}' já está definida; constantes precisam de um nome só delas.",
+    ),
+    (
+        "the value of the constant '{}' must be known before the program runs, but '{}' is not.",
+        "o valor da constante '{}' deve ser conhecido antes de o programa rodar, mas '{}' não é.",
+    ),
+    ("'{}' is a constant and cannot be bound again.", "'{}' é uma constante e não pode ser redefinida."),
+    (
+        "the arm '{}' is unreachable; the arms before it match everything it does.",
+        "o braço '{}' é inalcançável; os braços anteriores casam com tudo o que ele casa.",
+    ),
+    (
+        "the arm '{}' is unreachable; no value of type '{}' can be built.",
+        "o braço '{}' é inalcançável; nenhum valor do tipo '{}' pode ser construído.",
+    ),
+    (
+        "ADT '{}' has no constructors, so no value of it can be built.",
+        "o ADT '{}' não tem construtores, então nenhum valor dele pode ser construído.",
+    ),
+    (
+        "'{}' changes type across while iterations: '{}' before an iteration, '{}' after it.",
+        "'{}' muda de tipo entre iterações do while: '{}' antes de uma iteração, '{}' depois dela.",
+    ),
+    // language levels
+    (
+        "{} need language level {} ({}), but this course is at level {} ({}): `{}`.",
+        "{} precisam do nível de linguagem {} ({}), mas este curso está no nível {} ({}): `{}`.",
+    ),
+    ("expressions, if and while", "expressões, if e while"),
+    ("functions", "funções"),
+    ("data types and match", "tipos de dados e match"),
+    ("function definitions", "definições de funções"),
+    ("functions inside functions", "funções dentro de funções"),
+    ("return statements", "comandos return"),
+    ("data type declarations", "declarações de tipos de dados"),
+    ("instance declarations", "declarações de instâncias"),
+    ("match statements", "comandos match"),
+    ("function types", "tipos de funções"),
+    ("data type values", "valores de tipos de dados"),
+    ("function values", "funções como valores"),
+    // runtime
+    ("'{}' reached the end of the input.", "'{}' chegou ao fim da entrada."),
+    (
+        "'{}' reached the end of its body without returning a '{}' value.",
+        "'{}' chegou ao fim do corpo sem retornar um valor '{}'.",
+    ),
+    ("decimal division by zero.", "divisão decimal por zero."),
+    ("complex division by zero.", "divisão complexa por zero."),
+    ("decimal overflow.", "estouro decimal."),
+    ("index {} out of range for bytes of length {}.", "índice {} fora do intervalo para bytes de tamanho {}."),
+    (
+        "'{}' requires the network capability, which is disabled.",
+        "'{}' requer a capacidade de rede, que está desabilitada.",
+    ),
+    ("Program panicked trying to unwrap.", "O programa falhou ao tentar fazer unwrap."),
+    // warnings
+    ("in '{}', line {}, column {}: `{}`: {}", "em '{}', linha {}, coluna {}: `{}`: {}"),
+    ("in '{}': `{}`: {}", "em '{}': `{}`: {}"),
+    ("in '{}': {}", "em '{}': {}"),
+    ("division by zero: '{}' is always 0.", "divisão por zero: '{}' é sempre 0."),
+    ("condition is always true.", "a condição é sempre verdadeira."),
+    ("condition is always false.", "a condição é sempre falsa."),
+    ("import '{}' is never used.", "o import '{}' nunca é usado."),
+    ("'{}' is never used outside module '{}'.", "'{}' nunca é usado fora do módulo '{}'."),
+    // manifest
+    ("{}, line {}: {}", "{}, linha {}: {}"),
+    ("unknown key '{}'.", "chave desconhecida '{}'."),
+];
+
+/// The output and the error of a command in `language`.
+pub fn localize(result: Result<String, String>, language: Language) -> Result<String, String> {
+    result
+        .map(|output| translate(&output, language))
+        .map_err(|message| translate(&message, language))
+}
+
+/// `message` in `language`, line by line. Text the catalog does not cover
+/// is left in English, so nothing of a message is lost.
+pub fn translate(message: &str, language: Language) -> String {
+    match language {
+        Language::English => message.to_string(),
+        Language::Portuguese => message.split('\n').map(line).collect::<Vec<_>>().join("\n"),
+    }
+}
+
+/// A line is `[Code] body`, `[Code on 'f()'] body` or a bare body.
+fn line(text: &str) -> String {
+    let header = text
+        .strip_prefix('[')
+        .and_then(|rest| rest.split_once("] "))
+        .and_then(|(head, body)| Some((code(head)?, body)));
+    match header {
+        Some((head, body)) => format!("[{}] {}", head, body_text(body)),
+        None => body_text(text),
+    }
+}
+
+fn code(head: &str) -> Option<String> {
+    let (name, scope) = match head.split_once(" on ").or_else(|| head.split_once(" in ")) {
+        Some((name, scope)) => (name, Some(scope)),
+        None => (head, None),
+    };
+    let (_, translated) = CODES.iter().find(|(english, _)| *english == name)?;
+    Some(match scope {
+        Some(scope) => format!("{} em {}", translated, scope),
+        None => translated.to_string(),
+    })
+}
+
+fn body_text(text: &str) -> String {
+    MESSAGES
+        .iter()
+        .find_map(|(english, portuguese)| {
+            let holes = holes(english, text)?;
+            let mut out = String::new();
+            let mut pieces = portuguese.split("{}");
+            out.push_str(pieces.next().unwrap_or_default());
+            for (hole, piece) in holes.iter().zip(pieces) {
+                out.push_str(&line(hole));
+                out.push_str(piece);
+            }
+            Some(out)
+        })
+        .unwrap_or_else(|| text.to_string())
+}
+
+/// The text each `{}` of `template` stands for in `text`. Every hole but
+/// the last takes as little as it can.
+fn holes<'a>(template: &str, text: &'a str) -> Option<Vec<&'a str>> {
+    let mut pieces = template.split("{}");
+    let mut rest = text.strip_prefix(pieces.next().unwrap_or_default())?;
+    let pieces: Vec<&str> = pieces.collect();
+    let mut holes = vec![];
+
+    for (i, piece) in pieces.iter().enumerate() {
+        let end = match i + 1 == pieces.len() {
+            true => rest.strip_suffix(piece)?.len(),
+            false => rest.find(piece)?,
+        };
+        holes.push(&rest[..end]);
+        rest = &rest[end + piece.len()..];
+    }
+    rest.is_empty().then_some(holes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pt(message: &str) -> String {
+        translate(message, Language::Portuguese)
+    }
+
+    #[test]
+    fn messages_are_translated_by_template() {
+        assert_eq!(
+            pt("[Syntax Error] unexpected input on line 3: 'x = = 1'."),
+            "[Erro de Sintaxe] entrada inesperada na linha 3: 'x = = 1'."
+        );
+        assert_eq!(
+            pt("[Type Error on '__main__()'] 'inc()' has mismatched arguments: expected 'TInteger', found 'TReal'.\n  expected because of 'def inc(n: TInteger) -> TInteger'\n  found from 'x = 2.5', then 'y = x'"),
+            "[Erro de Tipo em '__main__()'] 'inc()' tem argumentos incompatíveis: esperado 'TInteger', encontrado 'TReal'.\n  esperado por causa de 'def inc(n: TInteger) -> TInteger'\n  encontrado a partir de 'x = 2.5', depois 'y = x'"
+        );
+        assert_eq!(pt("[Runtime Error] decimal division by zero."), "[Erro de Execução] divisão decimal por zero.");
+        assert_eq!(
+            pt("[Warning] in '__main__', line 2, column 1: `y = 4 / d`: division by zero: 'd' is always 0."),
+            "[Aviso] em '__main__', linha 2, coluna 1: `y = 4 / d`: divisão por zero: 'd' é sempre 0."
+        );
+        assert_eq!(
+            pt("[Level Error] function definitions need language level 2 (functions), but this course is at level 1 (expressions, if and while): `def double(...)`."),
+            "[Erro de Nível] definições de funções precisam do nível de linguagem 2 (funções), mas este curso está no nível 1 (expressões, if e while): `def double(...)`."
+        );
+    }
+
+    #[test]
+    fn nested_messages_are_translated_with_their_code() {
+        assert_eq!(
+            pt("in '/tmp/probe/a.rpy': [Syntax Error] unexpected input on line 1: 'x = = 1'."),
+            "em '/tmp/probe/a.rpy': [Erro de Sintaxe] entrada inesperada na linha 1: 'x = = 1'."
+        );
+    }
+
+    #[test]
+    fn uncovered_text_stays_in_english() {
+        assert_eq!(pt("[Type Error] a new message."), "[Erro de Tipo] a new message.");
+        assert_eq!(pt("[Custom] 'x' is not defined."), "[Custom] 'x' is not defined.");
+        assert_eq!(
+            translate("[Runtime Error] decimal overflow.", Language::English),
+            "[Runtime Error] decimal overflow."
+        );
+        assert_eq!(Language::parse("pt_BR.UTF-8"), Some(Language::Portuguese));
+        assert_eq!(Language::parse("fr"), None);
+    }
+}
@@ -0,0 +1,541 @@
+use crate::cli::locale::{self, Language};
+use crate::cli::split_options;
+use crate::interpreter::interpreter::{eval, EnvValue};
+use crate::interpreter::program::{execute_program, execute_statements, with_stack, Stats};
+use crate::interpreter::property::{check_property, Counterexample};
+use crate::interpreter::replay::Recording;
+use crate::interpreter::value::render;
+use crate::ir::ast::{Environment, Expression, Function, Level, Limits, Name, Statement, Strictness, Type};
+use crate::ir::levels::{self, LanguageLevel};
+use crate::ir::{dataflow, desugar};
+use crate::parser::loader::Loader;
+use crate::tc::trace;
+use crate::tc::type_checker::{check_program_with, CheckedProgram};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+pub const MANIFEST: &str = "rpython.toml";
+
+/// Generated cases a property test runs on.
+const PROPERTY_RUNS: usize = 100;
+
+/// The `[project]` table of an `rpython.toml`:
+///
+/// ```toml
+/// [project]
+/// name = "shapes"
+/// roots = ["src", "lib"]   # where modules are looked up, in order
+/// entry = "main"           # the module that runs
+/// prelude = ["util"]       # modules loaded before the entry module
+/// promotions = "warn"      # implicit int promotions: "allow", "warn" or "deny"
+/// real_equality = "deny"   # `==` on reals: "allow", "warn" (the default) or "deny"
+/// level = "2"              # the language level of the course, from "1" to "4"
+/// language = "pt"          # diagnostics in "en" or "pt"; RPYTHON_LANG overrides it
+/// ```
+#[derive(Debug, PartialEq, Clone)]
+pub struct Manifest {
+    pub name: String,
+    /// Source roots, relative to the directory of the manifest.
+    pub roots: Vec<PathBuf>,
+    pub entry: Name,
+    pub prelude: Vec<Name>,
+    /// Set for a lone file run outside a project.
+    pub entry_path: Option<PathBuf>,
+    pub strictness: Strictness,
+    /// Constructs above it are rejected before the program is checked.
+    pub level: LanguageLevel,
+    pub language: Language,
+}
+
+impl Manifest {
+    /// Reads `rpython.toml` from `dir`.
+    pub fn read(dir: &Path) -> Result<Manifest, String> {
+        let path = dir.join(MANIFEST);
+        let text = std::fs::read_to_string(&path)
+            .map_err(|e| format!("cannot read '{}': {}", path.display(), e))?;
+        Manifest::parse(&text, dir)
+    }
+
+    /// A project made of the file at `path` alone; its imports are looked
+    /// up next to it.
+    pub fn file(path: &Path) -> Manifest {
+        let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+        Manifest {
+            name: stem.to_string(),
+            roots: vec![path.parent().unwrap_or(Path::new(".")).to_path_buf()],
+            entry: stem.to_string(),
+            prelude: vec![],
+            entry_path: Some(path.to_path_buf()),
+            strictness: Strictness::default(),
+            level: LanguageLevel::default(),
+            language: Language::default(),
+        }
+    }
+
+    /// Parses the subset of TOML a manifest needs: a `[project]` table of
+    /// strings and arrays of strings, and `#` comments, alone on a line or
+    /// after a value.
+    pub fn parse(text: &str, dir: &Path) -> Result<Manifest, String> {
+        let mut manifest = Manifest {
+            name: dir.file_name().unwrap_or_default().to_string_lossy().to_string(),
+            roots: vec![dir.join("src")],
+            entry: String::from("main"),
+            prelude: vec![],
+            entry_path: None,
+            strictness: Strictness::default(),
+            level: LanguageLevel::default(),
+            language: Language::default(),
+        };
+
+        for (number, line) in text.lines().enumerate() {
+            let error = |message: String| {
+                format!("[Manifest Error] {}, line {}: {}", MANIFEST, number + 1, message)
+            };
+            let line = without_comment(line).trim();
+            if line.is_empty() || line == "[project]" {
+                continue;
+            }
+            if line.starts_with('[') {
+                return Err(error(format!("unknown table '{}'.", line)));
+            }
+
+            let (key, value) = line
+                .split_once('=')
+                .ok_or_else(|| error(String::from("expected 'key = value'.")))?;
+            let value = manifest_value(value.trim()).map_err(error)?;
+            match (key.trim(), value) {
+                ("name", Value::String(name)) => manifest.name = name,
+                ("entry", Value::String(entry)) => manifest.entry = entry,
+                ("roots", Value::Strings(roots)) => {
+                    manifest.roots = roots.iter().map(|root| dir.join(root)).collect()
+                }
+                ("prelude", Value::Strings(prelude)) => manifest.prelude = prelude,
+                ("promotions", Value::String(level)) => {
+                    manifest.strictness.promotions = manifest_level("promotions", &level).map_err(error)?
+                }
+                ("real_equality", Value::String(level)) => {
+                    manifest.strictness.real_equality = manifest_level("real_equality", &level).map_err(error)?
+                }
+                ("level", Value::String(level)) => {
+                    manifest.level = LanguageLevel::parse(&level).ok_or_else(|| {
+                        error(format!("'level' must be one of \"1\" to \"4\", found '{}'.", level))
+                    })?
+                }
+                ("language", Value::String(language)) => {
+                    manifest.language = Language::parse(&language).ok_or_else(|| {
+                        error(format!("'language' must be \"en\" or \"pt\", found '{}'.", language))
+                    })?
+                }
+                ("name" | "entry" | "promotions" | "real_equality" | "level" | "language", _) => {
+                    return Err(error(format!("'{}' must be a string.", key.trim())))
+                }
+                ("roots" | "prelude", _) => {
+                    return Err(error(format!("'{}' must be an array of strings.", key.trim())))
+                }
+                (key, _) => return Err(error(format!("unknown key '{}'.", key))),
+            }
+        }
+        Ok(manifest)
+    }
+
+    /// Loads the prelude and the entry module with everything they import.
+    pub fn loader(&self) -> Result<Loader, String> {
+        let mut loader = Loader::new(self.roots.clone());
+        for module in &self.prelude {
+            loader.load(module)?;
+        }
+        match &self.entry_path {
+            Some(path) => loader.load_file(&self.entry, path)?,
+            None => loader.load(&self.entry)?,
+        }
+        Ok(loader)
+    }
+
+    /// The modules of the project as one desugared program within the
+    /// language level.
+    pub fn load(&self) -> Result<Vec<Statement>, String> {
+        let program = self.loader()?.program()?;
+        levels::check(&program, self.level)?;
+        Ok(desugar::program(program))
+    }
+}
+
+enum Value {
+    String(String),
+    Strings(Vec<String>),
+}
+
+fn manifest_level(key: &str, level: &str) -> Result<Level, String> {
+    match level {
+        "allow" => Ok(Level::Allow),
+        "warn" => Ok(Level::Warn),
+        "deny" => Ok(Level::Deny),
+        _ => Err(format!("'{}' must be \"allow\", \"warn\" or \"deny\", found '{}'.", key, level)),
+    }
+}
+
+/// `line` up to a `#` that is not inside a string.
+fn without_comment(line: &str) -> &str {
+    let mut quoted = false;
+    for (i, c) in line.char_indices() {
+        match c {
+            '"' => quoted = !quoted,
+            '#' if !quoted => return &line[..i],
+            _ => {}
+        }
+    }
+    line
+}
+
+fn manifest_value(text: &str) -> Result<Value, String> {
+    let string = |text: &str| match text.strip_prefix('"').and_then(|t| t.strip_suffix('"')) {
+        Some(s) if !s.contains('"') => Ok(s.to_string()),
+        _ => Err(format!("expected a string, found '{}'.", text)),
+    };
+
+    match text.strip_prefix('[').and_then(|t| t.strip_suffix(']')) {
+        Some(items) if items.trim().is_empty() => Ok(Value::Strings(vec![])),
+        Some(items) => Ok(Value::Strings(
+            items
+                .split(',')
+                .map(|item| string(item.trim()))
+                .collect::<Result<_, _>>()?,
+        )),
+        None => Ok(Value::String(string(text)?)),
+    }
+}
+
+/// The project a command works on: a `.rpy` file, or a directory with an
+/// `rpython.toml` (the current one by default).
+fn project(command: &str, args: &[String]) -> Result<Manifest, String> {
+    let (options, positional) = split_options(args);
+    if let Some((name, _)) = options.first() {
+        return Err(format!("unknown option '--{}'", name));
+    }
+
+    match positional.as_slice() {
+        [] => Manifest::read(Path::new(".")),
+        [target] if target.ends_with(".rpy") => Ok(Manifest::file(Path::new(target))),
+        [target] => Manifest::read(Path::new(target)),
+        _ => Err(format!("{} expects at most one file or project directory", command)),
+    }
+}
+
+/// `rpython run`: type checks the program, runs it, then its `main()` if it
+/// has one. With `--record=<file>` it also writes every state change of the
+/// run to the file, for a replay, and with `--timeline=<file>` the same
+/// changes as a JSON timeline, for a visualizer; both even when the run
+/// fails.
+pub fn run(args: &[String]) -> Result<String, String> {
+    let option = |prefix: &str| args.iter().find_map(|arg| arg.strip_prefix(prefix)).map(PathBuf::from);
+    let (record, timeline) = (option("--record="), option("--timeline="));
+    let args: Vec<String> = args
+        .iter()
+        .filter(|arg| !arg.starts_with("--record=") && !arg.starts_with("--timeline="))
+        .cloned()
+        .collect();
+    let project = project("run", &args)?;
+    let run = || {
+        let program = CheckedProgram::check_with(project.load()?, project.strictness)?;
+        let mut env = Environment::new();
+        let recording = Arc::new(Mutex::new(Recording::default()));
+        if record.is_some() || timeline.is_some() {
+            env.observer = Recording::observer(&recording);
+        }
+        let result = execute_program(&program, &mut env, &mut Stats::default());
+        if let Some(path) = &record {
+            recording.lock().unwrap().save(path)?;
+        }
+        if let Some(path) = &timeline {
+            std::fs::write(path, recording.lock().unwrap().to_timeline())
+                .map_err(|e| format!("cannot write '{}': {}", path.display(), e))?;
+        }
+        result.map(|_| String::new())
+    };
+    locale::localize(run(), Language::selected(project.language))
+}
+
+/// `rpython check`: type checks the program and prints the warnings the
+/// strictness settings ask for, then the lint warnings. With `--trace` it
+/// prints the derivation of every type instead, followed by the error if
+/// the program does not check.
+pub fn check(args: &[String]) -> Result<String, String> {
+    let traced = args.iter().any(|arg| arg == "--trace");
+    let workspace = args.iter().any(|arg| arg == "--workspace");
+    let args: Vec<String> = args
+        .iter()
+        .filter(|arg| *arg != "--trace" && *arg != "--workspace")
+        .cloned()
+        .collect();
+    let project = project("check", &args)?;
+    let check = || {
+        let program = project.load()?;
+        if traced {
+            let (checked, steps) = trace::record(|| check_program_with(&program, project.strictness));
+            let derivation = trace::render(&steps);
+            return match checked {
+                Ok(_) => Ok(derivation),
+                Err(message) => Err(format!("{}{}", derivation, message)),
+            };
+        }
+        let (_, lints) = check_program_with(&program, project.strictness)?;
+        let unused = match workspace {
+            true => project.loader()?.unused()?,
+            false => vec![],
+        };
+        Ok(lints
+            .into_iter()
+            .chain(dataflow::program(&program).iter().map(|warning| warning.render()))
+            .chain(unused)
+            .map(|warning| format!("{}\n", warning))
+            .collect())
+    };
+    locale::localize(check(), Language::selected(project.language))
+}
+
+/// `rpython test`: type checks the program, runs its top level, then every
+/// function named `test_...`. A test fails when it raises an error or
+/// returns `False`. A test with parameters is a property: it runs on generated
+/// arguments of the declared types, and a failing case is shrunk before it
+/// is reported.
+pub fn test(args: &[String]) -> Result<String, String> {
+    let project = project("test", args)?;
+    let test = || test_program(&CheckedProgram::check_with(project.load()?, project.strictness)?);
+    locale::localize(test(), Language::selected(project.language))
+}
+
+pub fn test_program(program: &CheckedProgram) -> Result<String, String> {
+    with_stack(&Limits::default(), || run_tests(program))?
+}
+
+fn run_tests(program: &CheckedProgram) -> Result<String, String> {
+    let mut env = Environment::new();
+    execute_statements(program.statements(), &mut env, &mut Stats::default())?;
+
+    let tests: Vec<(Name, Vec<(Name, Type)>)> = env
+        .get_frame(env.scope_key())
+        .variables
+        .iter()
+        .filter(|(name, _)| name.starts_with("test_"))
+        .filter_map(|(name, value)| Some((name.clone(), parameters(value)?)))
+        .collect();
+
+    let mut report = String::new();
+    let mut passed = 0;
+    for (name, params) in &tests {
+        let call = |args: &[Expression]| {
+            match eval(Expression::FuncCall(name.clone(), args.to_vec()), &env) {
+                Ok(EnvValue::Exp(Expression::CFalse)) => Err(String::from("it returned False")),
+                Ok(_) => Ok(()),
+                Err((message, _)) => Err(message),
+            }
+        };
+        let result = match params.is_empty() {
+            true => call(&[]),
+            false => {
+                let types: Vec<Type> = params.iter().map(|(_, kind)| kind.clone()).collect();
+                let seed = name.bytes().fold(17, |seed: u64, b| seed.wrapping_mul(31).wrapping_add(b as u64));
+                check_property(&types, &env.type_env, PROPERTY_RUNS, seed, call)
+                    .map_err(|counterexample| falsified(params, &counterexample))
+            }
+        };
+        match result {
+            Ok(()) => {
+                passed += 1;
+                report.push_str(&format!("{}: ok\n", name));
+            }
+            Err(message) => report.push_str(&format!("{}: FAILED: {}\n", name, message)),
+        }
+    }
+    report.push_str(&format!("{}/{} passed\n", passed, tests.len()));
+
+    if passed == tests.len() {
+        Ok(report)
+    } else {
+        Err(report)
+    }
+}
+
+/// The parameters of a function value.
+fn parameters(value: &EnvValue) -> Option<Vec<(Name, Type)>> {
+    let params = |func: &Function| func.params.clone().unwrap_or_default();
+    match value {
+        EnvValue::Func(func) => Some(params(func)),
+        EnvValue::Exp(Expression::Closure(func, _)) => Some(params(func)),
+        _ => None,
+    }
+}
+
+fn falsified(params: &[(Name, Type)], counterexample: &Counterexample) -> String {
+    let show = |args: &[Expression]| {
+        params
+            .iter()
+            .zip(args)
+            .map(|((name, _), arg)| format!("{} = {}", name, render(&EnvValue::Exp(arg.clone()))))
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+    if counterexample.original.is_empty() {
+        return counterexample.message.clone();
+    }
+    format!(
+        "falsified after {} runs by {} (shrunk {} times from {}): {}",
+        counterexample.runs,
+        show(&counterexample.shrunk),
+        counterexample.shrinks,
+        show(&counterexample.original),
+        counterexample.message
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn manifest_tables() {
+        let dir = Path::new("shapes");
+        let manifest = Manifest::parse(
+            "# a project\n[project]\nname = \"shapes\"\nroots = [\"src\", \"lib\"]\nprelude = [\"util\"]\n",
+            dir,
+        );
+        assert_eq!(
+            manifest,
+            Ok(Manifest {
+                name: String::from("shapes"),
+                roots: vec![dir.join("src"), dir.join("lib")],
+                entry: String::from("main"),
+                prelude: vec![String::from("util")],
+                entry_path: None,
+                strictness: Strictness::default(),
+                level: LanguageLevel::default(),
+                language: Language::default(),
+            })
+        );
+        assert_eq!(
+            Manifest::parse("language = \"pt\"", dir).map(|manifest| manifest.language),
+            Ok(Language::Portuguese)
+        );
+        assert_eq!(
+            Manifest::parse("level = \"2\"", dir).map(|manifest| manifest.level),
+            Ok(LanguageLevel::Functions)
+        );
+        assert_eq!(
+            Manifest::parse("promotions = \"deny\"", dir).map(|manifest| manifest.strictness.promotions),
+            Ok(Level::Deny)
+        );
+        assert_eq!(
+            Manifest::parse("promotions = \"never\"", dir),
+            Err(String::from(
+                "[Manifest Error] rpython.toml, line 1: 'promotions' must be \"allow\", \"warn\" or \"deny\", found 'never'."
+            ))
+        );
+
+        assert_eq!(
+            Manifest::parse("[project]\nentry = [\"a\"]", dir),
+            Err(String::from("[Manifest Error] rpython.toml, line 2: 'entry' must be a string."))
+        );
+        assert_eq!(
+            Manifest::parse("version = \"1\"", dir),
+            Err(String::from("[Manifest Error] rpython.toml, line 1: unknown key 'version'."))
+        );
+        assert_eq!(
+            Manifest::parse("real_equality = \"allow\"", dir).map(|manifest| manifest.strictness.real_equality),
+            Ok(Level::Allow)
+        );
+    }
+
+    #[test]
+    fn manifest_values_take_trailing_comments() {
+        let dir = Path::new("shapes");
+        let documented = "[project]
+name = \"shapes\"
+roots = [\"src\", \"lib\"]   # where modules are looked up, in order
+entry = \"main\"           # the module that runs
+prelude = [\"util\"]       # modules loaded before the entry module
+promotions = \"warn\"      # implicit int promotions: \"allow\", \"warn\" or \"deny\"
+real_equality = \"deny\"   # `==` on reals: \"allow\", \"warn\" (the default) or \"deny\"
+level = \"2\"              # the language level of the course, from \"1\" to \"4\"
+language = \"pt\"          # diagnostics in \"en\" or \"pt\"; RPYTHON_LANG overrides it
+";
+        let manifest = Manifest::parse(documented, dir).unwrap();
+        assert_eq!(manifest.roots, vec![dir.join("src"), dir.join("lib")]);
+        assert_eq!(manifest.prelude, vec![String::from("util")]);
+        assert_eq!(manifest.strictness.real_equality, Level::Deny);
+        assert_eq!(manifest.level, LanguageLevel::Functions);
+        assert_eq!(manifest.language, Language::Portuguese);
+        assert_eq!(
+            Manifest::parse("name = \"#1\" # the first", dir).map(|manifest| manifest.name),
+            Ok(String::from("#1"))
+        );
+    }
+
+    #[test]
+    fn project_commands_use_the_loader() {
+        let dir = std::env::temp_dir().join(format!("rpython-project-{}", std::process::id()));
+        let files = [
+            (MANIFEST, "[project]\nroots = [\"src\"]\nprelude = [\"prelude\"]"),
+            ("src/prelude.rpy", "base = 10"),
+            ("src/math.rpy", "def double(n: TInteger) -> TInteger:\n    return n * 2"),
+            (
+                "src/main.rpy",
+                "import math\ndef test_double() -> TBool:\n    return double(base) == 20\ndef test_base() -> TBool:\n    return base == 0",
+            ),
+        ];
+        for (path, source) in files {
+            let path = dir.join(path);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, source).unwrap();
+        }
+
+        let args = vec![dir.to_string_lossy().to_string()];
+        let checked = check(&args);
+        let traced = check(&[String::from("--trace"), args[0].clone()]);
+        let workspace = check(&[String::from("--workspace"), args[0].clone()]);
+        let tested = test(&args);
+        let record = format!("--record={}", dir.join("run.rec").display());
+        let timeline = format!("--timeline={}", dir.join("run.json").display());
+        let ran = run(&[record, timeline, args[0].clone()]);
+        let recording = Recording::load(&dir.join("run.rec"));
+        let timeline = std::fs::read_to_string(dir.join("run.json"));
+        std::fs::write(dir.join(MANIFEST), "[project]\nroots = [\"src\"]\nprelude = [\"prelude\"]\nlevel = \"1\"").unwrap();
+        let gated = check(&args);
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(checked, Ok(String::new()));
+        assert_eq!(workspace, Ok(String::new()));
+        assert_eq!(ran, Ok(String::new()));
+        assert_eq!(
+            recording.map(|recording| recording.events.iter().map(|event| event.span.clone()).collect()),
+            Ok(vec![String::from("base = 10")])
+        );
+        assert!(timeline.unwrap().starts_with(r#"{"version": 1, "steps": [{"step": 1, "span": "base = 10","#));
+        assert!(traced.unwrap().contains("\n    double(base) == 20 : TBool (expected TBool)\n"));
+        assert_eq!(
+            gated,
+            Err(String::from(
+                "[Level Error] function definitions need language level 2 (functions), but this course is at level 1 (expressions, if and while): `def double(...)`."
+            ))
+        );
+        assert_eq!(
+            tested,
+            Err(String::from(
+                "test_base: FAILED: it returned False\ntest_double: ok\n1/2 passed\n"
+            ))
+        );
+    }
+
+    #[test]
+    fn tests_with_parameters_are_properties() {
+        let source = "def test_identity(n: TInteger) -> TBool:\n    return n == n * 1\ndef test_small(n: TInteger, xs: [TInteger]) -> TBool:\n    return n < 5";
+        let program = desugar::program(crate::parser::parser::parse_program(source).unwrap());
+        let report = test_program(&CheckedProgram::check(program).unwrap()).unwrap_err();
+        let lines: Vec<&str> = report.lines().collect();
+
+        assert_eq!(lines[0], "test_identity: ok");
+        assert!(lines[1].starts_with("test_small: FAILED: falsified after "));
+        assert!(lines[1].contains(" by n = 5, xs = [] (shrunk "));
+        assert!(lines[1].ends_with("): it returned False"));
+        assert_eq!(lines[2], "1/2 passed");
+    }
+}
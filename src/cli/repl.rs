@@ -0,0 +1,625 @@
+use crate::builtins::registry;
+#[cfg(feature = "line-editor")]
+use crate::cli::editor;
+use crate::cli::project::{Manifest, MANIFEST};
+use crate::cli::{read_source, split_options};
+use crate::interpreter::interpreter::{self, eval, ControlFlow, EnvValue};
+use crate::interpreter::program::with_stack;
+use crate::interpreter::value::{render, Printer};
+use crate::ir::ast::{Environment, Name, Statement, Type};
+use crate::ir::{calls, desugar, modules, slots, tags};
+use crate::parser::loader::Loader;
+use crate::parser::parser::{parse_expression, parse_program, KEYWORDS};
+use crate::tc::type_checker::{self, check_exp, check_stmt};
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+const PROMPT: &str = ">>> ";
+const CONTINUATION: &str = "... ";
+const HISTORY: &str = ".rpython_history";
+const COMMANDS: &[&str] = &["help", "history", "load", "quit", "reload", "save", "time"];
+
+pub fn run(args: &[String]) -> Result<String, String> {
+    let (options, positional) = split_options(args);
+    if let Some((name, _)) = options.first() {
+        return Err(format!("unknown option '--{}'", name));
+    }
+    let dir = match positional.as_slice() {
+        [] => Path::new("."),
+        [dir] => Path::new(*dir),
+        _ => return Err(String::from("repl expects at most one project directory")),
+    };
+    let roots = match dir.join(MANIFEST).is_file() {
+        true => Manifest::read(dir)?.roots,
+        false => vec![dir.to_path_buf()],
+    };
+
+    let mut session = Session::new(roots);
+    let home = std::env::var_os("HOME").map(|home| PathBuf::from(home).join(HISTORY));
+    session.history = std::env::var_os("RPYTHON_HISTORY").map(PathBuf::from).or(home);
+    let mut input = Input::new(&session);
+    loop {
+        let prompt = if session.continuing() { CONTINUATION } else { PROMPT };
+        let line = match input.read_line(&session, prompt)? {
+            None => break,
+            Some(line) if line.trim() == ":quit" => break,
+            Some(line) => line,
+        };
+        let limits = session.env.limits.clone();
+        if let Some(output) = with_stack(&limits, || session.feed(line.trim_end_matches(['\n', '\r'])))? {
+            if !output.is_empty() {
+                println!("{}", output);
+            }
+        }
+    }
+    Ok(String::new())
+}
+
+/// Where the lines of a session come from: a line editor with completion
+/// on a terminal when the `line-editor` feature is on, plain standard input
+/// otherwise.
+enum Input {
+    Stdin,
+    #[cfg(feature = "line-editor")]
+    Editor(editor::Editor),
+}
+
+impl Input {
+    #[cfg_attr(not(feature = "line-editor"), allow(unused_variables))]
+    fn new(session: &Session) -> Input {
+        #[cfg(feature = "line-editor")]
+        if std::io::IsTerminal::is_terminal(&std::io::stdin()) {
+            let history = session.history.as_ref().and_then(|path| std::fs::read_to_string(path).ok());
+            let history = history.unwrap_or_default().lines().map(String::from).collect();
+            return Input::Editor(editor::Editor::new(history));
+        }
+        Input::Stdin
+    }
+
+    #[cfg_attr(not(feature = "line-editor"), allow(unused_variables))]
+    fn read_line(&mut self, session: &Session, prompt: &str) -> Result<Option<String>, String> {
+        match self {
+            Input::Stdin => {
+                print!("{}", prompt);
+                std::io::stdout().flush().map_err(|e| e.to_string())?;
+                let mut line = String::new();
+                match std::io::stdin().read_line(&mut line) {
+                    Ok(0) => Ok(None),
+                    Ok(_) => Ok(Some(line)),
+                    Err(e) => Err(e.to_string()),
+                }
+            }
+            #[cfg(feature = "line-editor")]
+            Input::Editor(editor) => editor
+                .read_line(prompt, &session.indent(), &|before| session.complete(before), word_start)
+                .map_err(|e| e.to_string()),
+        }
+    }
+}
+
+/// Where the word being completed at the end of `line` starts.
+pub fn word_start(line: &str) -> usize {
+    if line.starts_with(':') {
+        return 0;
+    }
+    line.rfind(|c: char| !(c.is_alphanumeric() || c == '_' || c == '.'))
+        .map_or(0, |i| i + 1)
+}
+
+/// An interactive session: entries run one after another in the same
+/// environment, and modules are looked up under `roots`.
+pub struct Session {
+    env: Environment<EnvValue>,
+    /// What the checker knows about the bindings, for the entries it accepts.
+    types: Environment<Type>,
+    roots: Vec<PathBuf>,
+    /// The names each imported module defines.
+    modules: BTreeMap<Name, Vec<Name>>,
+    /// Lines of an entry that spans several lines.
+    pending: Vec<String>,
+    /// The source of every statement entry that ran, for `:save`.
+    accepted: Vec<String>,
+    /// The file every line of input is appended to.
+    pub history: Option<PathBuf>,
+    /// How the values of expressions are shown, as set with `:depth`.
+    printer: Printer,
+}
+
+impl Session {
+    pub fn new(roots: Vec<PathBuf>) -> Session {
+        Session {
+            env: Environment::new(),
+            types: Environment::new(),
+            roots,
+            modules: BTreeMap::new(),
+            pending: vec![],
+            accepted: vec![],
+            history: None,
+            printer: Printer::default(),
+        }
+    }
+
+    /// Whether the session waits for more lines of the current entry.
+    pub fn continuing(&self) -> bool {
+        !self.pending.is_empty()
+    }
+
+    /// Feeds a line of input and returns what to print once it completes
+    /// an entry. A line ending in `:` opens a block, which a blank line
+    /// closes; lines starting with `:` are commands.
+    pub fn feed(&mut self, line: &str) -> Option<String> {
+        if let (Some(path), false) = (&self.history, line.trim().is_empty()) {
+            // History is a convenience: failing to write it is not an error
+            let file = std::fs::OpenOptions::new().create(true).append(true).open(path);
+            if let Ok(mut file) = file {
+                let _ = writeln!(file, "{}", line);
+            }
+        }
+        if self.pending.is_empty() {
+            if let Some(command) = line.trim().strip_prefix(':') {
+                return Some(self.command(command));
+            }
+            if line.trim().is_empty() {
+                return None;
+            }
+        }
+
+        // Lines inside open brackets continue the line they were opened on
+        match self.pending.last_mut() {
+            Some(last) if open_brackets(last) > 0 => {
+                last.push(' ');
+                last.push_str(line.trim());
+            }
+            _ => self.pending.push(line.to_string()),
+        }
+        let source = self.pending.join("\n");
+        let opens_block = self.pending.iter().any(|line| line.trim_end().ends_with(':'));
+        let closed = !opens_block || line.trim().is_empty();
+        if closed && open_brackets(self.pending.last().unwrap()) <= 0 {
+            self.pending.clear();
+            return Some(self.entry(&source));
+        }
+        None
+    }
+
+    /// The indentation to offer for the next line of an unfinished entry:
+    /// that of the last line, one level deeper after a line ending in `:`.
+    pub fn indent(&self) -> String {
+        let Some(last) = self.pending.last() else {
+            return String::new();
+        };
+        let depth = last.len() - last.trim_start().len();
+        let extra = if last.trim_end().ends_with(':') { 4 } else { 0 };
+        " ".repeat(depth + extra)
+    }
+
+    /// Completions for the word `line` ends with: names in scope, builtins,
+    /// keywords, ADTs and their constructors, or commands after a `:`.
+    pub fn complete(&self, line: &str) -> Vec<String> {
+        if let Some(command) = line.strip_prefix(':') {
+            return COMMANDS
+                .iter()
+                .filter(|name| name.starts_with(command))
+                .map(|name| format!(":{}", name))
+                .collect();
+        }
+
+        let word = &line[word_start(line)..];
+        if word.is_empty() {
+            return vec![];
+        }
+
+        let frame = self.env.get_frame(self.env.scope_key());
+        let adts = self.env.type_env.iter().flat_map(|(adt, constructors)| {
+            std::iter::once(adt.clone()).chain(constructors.iter().map(|c| c.name.clone()))
+        });
+        let mut names: std::collections::BTreeSet<String> =
+            frame.variables.keys().cloned().chain(adts).collect();
+        names.extend(registry::names().chain(KEYWORDS.iter().copied()).map(String::from));
+        names.into_iter().filter(|name| name.starts_with(word)).collect()
+    }
+
+    /// Runs an entry: an expression, whose value is shown, or statements.
+    pub fn entry(&mut self, source: &str) -> String {
+        if let Ok(exp) = parse_expression(source) {
+            let exp = desugar::expression(exp);
+            match check_exp(exp.clone(), &self.types) {
+                Err(message) => return message,
+                Ok(Type::TVoid) => return eval(exp, &self.env).err().map(|(e, _)| e).unwrap_or_default(),
+                Ok(_) => {}
+            }
+            return match eval(exp, &self.env) {
+                Ok(value) => self.printer.render(&value),
+                Err((message, _)) => message,
+            };
+        }
+
+        match self.statements(source) {
+            Ok(()) => String::new(),
+            Err(message) => message,
+        }
+    }
+
+    /// Runs statements, remembering their source when they succeed.
+    fn statements(&mut self, source: &str) -> Result<(), String> {
+        let program = desugar::program(parse_program(source)?);
+        for stmt in &program {
+            if let Statement::Import(module) = stmt {
+                self.import(module)?;
+            }
+        }
+        self.execute(&program)?;
+        self.accepted.push(source.trim_end().to_string());
+        Ok(())
+    }
+
+    fn command(&mut self, command: &str) -> String {
+        let words: Vec<&str> = command.split_whitespace().collect();
+        let result = match words.as_slice() {
+            ["reload", module] => self.reload(module),
+            ["save", path] => self.save(Path::new(path)),
+            ["load", path] => self.load(Path::new(path)),
+            ["history"] => self.show_history(),
+            ["time", ..] => self.time(command.trim_start()["time".len()..].trim()),
+            ["depth"] => Ok(match self.printer.max_depth {
+                Some(depth) => format!("values are shown {} levels deep", depth),
+                None => String::from("values are shown whole"),
+            }),
+            ["depth", "off"] => {
+                self.printer.max_depth = None;
+                Ok(String::new())
+            }
+            ["depth", depth] => match depth.parse() {
+                Ok(depth) => {
+                    self.printer.max_depth = Some(depth);
+                    Ok(String::new())
+                }
+                Err(_) => Err(format!(":depth expects a number of levels or 'off', found '{}'", depth)),
+            },
+            ["help"] => Ok(String::from(
+                ":reload <module>  re-read a module and replace its definitions\n\
+                 :save <file>      write the definitions entered so far to a file\n\
+                 :load <file>      run a file saved with :save\n\
+                 :history          show the last lines of input\n\
+                 :time <expr>      evaluate an expression and report the time, steps and allocations it took\n\
+                 :depth <n>|off    show values only <n> levels deep, or whole\n\
+                 :quit             leave the session",
+            )),
+            _ => Err(format!("unknown command ':{}'; try ':help'", command.trim())),
+        };
+        result.unwrap_or_else(|message| message)
+    }
+
+    /// Evaluates `source` once and reports its value with the wall-clock
+    /// time, the evaluation steps and the compound values it allocated.
+    fn time(&mut self, source: &str) -> Result<String, String> {
+        if source.is_empty() {
+            return Err(String::from(":time expects an expression"));
+        }
+        let exp = desugar::expression(parse_expression(source)?);
+        let (steps, allocations) = self.env.counters.snapshot();
+        let started = std::time::Instant::now();
+        let value = eval(exp, &self.env).map_err(|(message, _)| message)?;
+        let elapsed = started.elapsed();
+        let (after_steps, after_allocations) = self.env.counters.snapshot();
+        Ok(format!(
+            "{}\ntime: {:?}, steps: {}, allocations: {}",
+            self.printer.render(&value),
+            elapsed,
+            after_steps - steps,
+            after_allocations - allocations
+        ))
+    }
+
+    /// Loads `module` and the modules it imports that the session does not
+    /// have yet.
+    fn import(&mut self, module: &str) -> Result<(), String> {
+        if self.modules.contains_key(module) {
+            return Ok(());
+        }
+        let mut loader = Loader::new(self.roots.clone());
+        loader.load(module)?;
+        for linked in loader.linked()? {
+            if !self.modules.contains_key(&linked.name) {
+                let program = desugar::program(linked.statements);
+                self.execute(&program)?;
+                let names = modules::definitions(&program).into_iter().collect();
+                self.modules.insert(linked.name, names);
+            }
+        }
+        Ok(())
+    }
+
+    /// Re-reads `module` and swaps its definitions into the session, leaving
+    /// every other binding alone. Nothing changes unless the new version
+    /// checks and runs.
+    pub fn reload(&mut self, module: &str) -> Result<String, String> {
+        let failed = |message: String| format!("[Reload Error] '{}' was not reloaded: {}", module, message);
+
+        let mut loader = Loader::new(self.roots.clone());
+        loader.load(module).map_err(failed)?;
+        let mut linked = loader.linked().map_err(failed)?;
+        let program = desugar::program(linked.pop().unwrap().statements);
+        for dependency in linked {
+            self.import(&dependency.name).map_err(failed)?;
+        }
+
+        let mut types = self.types.clone();
+        for stmt in &program {
+            match check_stmt(stmt.clone(), &types).map_err(failed)? {
+                type_checker::ControlFlow::Continue(new_types) => types = new_types,
+                type_checker::ControlFlow::Return(_) => break,
+            }
+        }
+        let mut env = self.env.clone();
+        for stmt in &program {
+            match interpreter::run(stmt.clone(), &env).map_err(failed)? {
+                ControlFlow::Continue(new_env) => env = new_env,
+                ControlFlow::Return(_) => break,
+            }
+        }
+
+        let names: Vec<Name> = modules::definitions(&program).into_iter().collect();
+        let mut report = format!("reloaded '{}'", module);
+        for name in &names {
+            let old = self.types.search_frame(name.clone());
+            match (old, types.search_frame(name.clone())) {
+                (Some(old), Some(new)) if old != new => {
+                    report.push_str(&format!("\n  {}: {:?} -> {:?}", name, old, new))
+                }
+                (None, Some(new)) => report.push_str(&format!("\n  {}: new, {:?}", name, new)),
+                _ => {}
+            }
+        }
+
+        self.env = env;
+        self.types = types;
+        self.modules.insert(module.to_string(), names);
+        Ok(report)
+    }
+
+    /// Writes the statement entries that ran, in order, as a program that
+    /// `:load` (or `rpython run`) replays.
+    pub fn save(&self, path: &Path) -> Result<String, String> {
+        let mut source = self.accepted.join("\n");
+        source.push('\n');
+        std::fs::write(path, source).map_err(|e| format!("cannot write '{}': {}", path.display(), e))?;
+        Ok(format!("saved {} entries to '{}'", self.accepted.len(), path.display()))
+    }
+
+    pub fn load(&mut self, path: &Path) -> Result<String, String> {
+        let source = read_source(&path.to_string_lossy())?;
+        self.statements(&source)?;
+        Ok(format!("loaded '{}'", path.display()))
+    }
+
+    fn show_history(&self) -> Result<String, String> {
+        let Some(path) = &self.history else {
+            return Err(String::from("history is not being kept"));
+        };
+        let history = std::fs::read_to_string(path).unwrap_or_default();
+        let lines: Vec<&str> = history.lines().collect();
+        let start = lines.len().saturating_sub(20);
+        Ok((start..lines.len())
+            .map(|i| format!("{:>4}  {}", i + 1, lines[i]))
+            .collect::<Vec<_>>()
+            .join("\n"))
+    }
+
+    /// Runs statements in the session, each once the checker accepts it.
+    /// The first ill-typed statement is reported and nothing after it runs.
+    fn execute(&mut self, program: &[Statement]) -> Result<(), String> {
+        for stmt in program {
+            if let type_checker::ControlFlow::Continue(types) = check_stmt(stmt.clone(), &self.types)? {
+                self.types = types;
+            }
+            let stmt = calls::statement(slots::statement(tags::statement(stmt.clone(), &self.types)));
+            match interpreter::run(stmt, &self.env)? {
+                ControlFlow::Continue(env) => self.env = env,
+                ControlFlow::Return(value) => {
+                    return Err(format!("'return' outside a function: {}", render(&value)))
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn lookup(&self, name: &str) -> Option<&EnvValue> {
+        self.env.search_frame(name.to_string())
+    }
+}
+
+/// How many more brackets `source` opens than it closes, outside strings.
+fn open_brackets(source: &str) -> i32 {
+    let mut depth = 0;
+    let mut in_string = false;
+    for c in source.chars() {
+        match c {
+            '"' => in_string = !in_string,
+            '(' | '[' | '{' if !in_string => depth += 1,
+            ')' | ']' | '}' if !in_string => depth -= 1,
+            _ => {}
+        }
+    }
+    depth
+}
+
+impl Default for Session {
+    fn default() -> Self {
+        Session::new(vec![PathBuf::from(".")])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ir::ast::Expression;
+
+    #[test]
+    fn entries_share_the_session() {
+        let mut session = Session::default();
+        assert_eq!(session.feed("x = 20"), Some(String::new()));
+        assert_eq!(session.feed("def inc(n: TInteger) -> TInteger:"), None);
+        assert!(session.continuing());
+        assert_eq!(session.feed("    return n + 1"), None);
+        assert_eq!(session.feed(""), Some(String::new()));
+        assert_eq!(session.feed("inc(x) * 2"), Some(String::from("42")));
+        assert_eq!(
+            session.feed(":frobnicate"),
+            Some(String::from("unknown command ':frobnicate'; try ':help'"))
+        );
+        assert_eq!(session.lookup("x"), Some(&EnvValue::Exp(Expression::CInt(20))));
+    }
+
+    #[test]
+    fn ill_typed_entries_are_reported_and_not_run() {
+        let mut session = Session::default();
+        for entry in ["arity_of(1)", "type_of()", "len(5)", "n = len(5)"] {
+            assert!(session.feed(entry).unwrap().starts_with("[Type Error]"), "{}", entry);
+        }
+        assert_eq!(session.lookup("n"), None);
+        assert_eq!(session.feed("n = len(\"abc\")"), Some(String::new()));
+        assert_eq!(session.feed("n"), Some(String::from("3")));
+    }
+
+    #[test]
+    fn reload_swaps_a_module_and_reports_type_changes() {
+        let dir = std::env::temp_dir().join(format!("rpython-reload-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let write = |source: &str| std::fs::write(dir.join("shapes.rpy"), source).unwrap();
+
+        write("module shapes\ndef area(side: TInteger) -> TInteger:\n    return side * side");
+        let mut session = Session::new(vec![dir.clone()]);
+        let imported = session.feed("import shapes");
+        let kept = session.feed("kept = 7");
+        let before = session.feed("shapes.area(3)");
+
+        write("module shapes\nsides = 4\ndef area(side: TInteger) -> TBool:\n    return side > 2");
+        let reloaded = session.reload("shapes");
+        let after = session.feed("shapes.area(3)");
+
+        write("module shapes\ndef area(side: TInteger) -> TBool:\n    return side");
+        let broken = session.reload("shapes");
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!((imported, kept), (Some(String::new()), Some(String::new())));
+        assert_eq!(before, Some(String::from("9")));
+        assert_eq!(
+            reloaded,
+            Ok(String::from(
+                "reloaded 'shapes'\n  shapes.area: TFunction(Some(TInteger), [TInteger]) -> TFunction(Some(TBool), [TInteger])\n  shapes.sides: new, TInteger"
+            ))
+        );
+        assert_eq!(after, Some(String::from("True")));
+        assert!(broken.unwrap_err().starts_with("[Reload Error] 'shapes' was not reloaded: [Type Error"));
+        assert_eq!(session.feed("shapes.area(1)"), Some(String::from("False")));
+        assert_eq!(session.lookup("kept"), Some(&EnvValue::Exp(Expression::CInt(7))));
+    }
+
+    #[test]
+    fn sessions_can_be_saved_and_restored() {
+        let dir = std::env::temp_dir().join(format!("rpython-session-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let (saved, history) = (dir.join("session.rpy"), dir.join("history"));
+
+        let mut session = Session {
+            history: Some(history.clone()),
+            ..Session::default()
+        };
+        for line in ["x = 20", "oops = = 1", "def inc(n: TInteger) -> TInteger:", "    return n + 1", "", "inc(x)"] {
+            session.feed(line);
+        }
+        let save = session.feed(&format!(":save {}", saved.display()));
+
+        let mut restored = Session::default();
+        let load = restored.feed(&format!(":load {}", saved.display()));
+        let value = restored.feed("inc(x)");
+        let (source, history) = (std::fs::read_to_string(&saved), std::fs::read_to_string(&history));
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(save, Some(format!("saved 2 entries to '{}'", saved.display())));
+        assert_eq!(
+            source.unwrap(),
+            "x = 20\ndef inc(n: TInteger) -> TInteger:\n    return n + 1\n"
+        );
+        assert_eq!(load, Some(format!("loaded '{}'", saved.display())));
+        assert_eq!(value, Some(String::from("21")));
+        assert_eq!(history.unwrap().lines().count(), 6);
+    }
+
+    #[test]
+    fn completion_and_multi_line_entries() {
+        let mut session = Session::default();
+        session.feed("total = 0");
+        session.feed("totem = 1");
+
+        assert_eq!(session.complete("x = tot"), vec!["total", "totem"]);
+        assert_eq!(session.complete("y = pri"), vec!["print", "print_table"]);
+        assert_eq!(session.complete("whi"), vec!["while"]);
+        assert_eq!(session.complete(":re"), vec![":reload"]);
+        assert!(session.complete("x = ").is_empty());
+
+        // Open brackets continue an entry until they are closed
+        assert_eq!(session.feed("x = (1 +"), None);
+        assert_eq!(session.indent(), "");
+        assert_eq!(session.feed("     2)"), Some(String::new()));
+        assert_eq!(session.feed("x"), Some(String::from("3")));
+
+        assert_eq!(session.feed("if total == 0:"), None);
+        assert_eq!(session.indent(), "    ");
+    }
+
+    #[test]
+    fn time_reports_steps_and_allocations() {
+        let mut session = Session::default();
+        session.feed("def count(n: TInteger) -> TInteger:");
+        session.feed("    if n == 0:");
+        session.feed("        return 0");
+        session.feed("    else:");
+        session.feed("        return n + count(n - 1)");
+        session.feed("");
+
+        let report = |output: String| -> (String, u64, u64) {
+            let (value, stats) = output.split_once('\n').unwrap();
+            let field = |name: &str| {
+                let start = stats.find(name).unwrap() + name.len();
+                stats[start..].split(|c: char| !c.is_ascii_digit()).next().unwrap().parse().unwrap()
+            };
+            (value.to_string(), field("steps: "), field("allocations: "))
+        };
+        // Entries run on a stack sized for their calls, as in `repl`
+        let mut time = |source: &str| {
+            let limits = session.env.limits.clone();
+            with_stack(&limits, || session.feed(source)).unwrap().unwrap()
+        };
+        let (value, small, _) = report(time(":time count(2)"));
+        let (_, large, _) = report(time(":time count(8)"));
+        assert_eq!(value, "3");
+        assert!(large > 2 * small);
+
+        let (value, _, allocations) = report(time(":time Just(count(3))"));
+        assert_eq!(value, "Just(6)");
+        assert_eq!(allocations, 1);
+
+        assert_eq!(session.feed(":time"), Some(String::from(":time expects an expression")));
+    }
+
+    #[test]
+    fn depth_bounds_how_deep_values_are_shown() {
+        let mut session = Session::default();
+        session.feed("x = Just(((1, (2, 3)), 4))");
+        assert_eq!(session.feed("x"), Some(String::from("Just(((1, (2, 3)), 4))")));
+
+        assert_eq!(session.feed(":depth 2"), Some(String::new()));
+        assert_eq!(session.feed("x"), Some(String::from("Just(((1, (...)), 4))")));
+        assert_eq!(session.feed(":depth"), Some(String::from("values are shown 2 levels deep")));
+        assert_eq!(
+            session.feed(":depth two"),
+            Some(String::from(":depth expects a number of levels or 'off', found 'two'"))
+        );
+
+        session.feed(":depth off");
+        assert_eq!(session.feed("x"), Some(String::from("Just(((1, (2, 3)), 4))")));
+    }
+}
@@ -0,0 +1,476 @@
+use crate::cli::{read_source, split_options};
+use crate::ir::ast::{CallSite, Expression, Function, InfixOperator, Statement};
+use crate::ir::cfg::{self, Cfg, Terminator};
+use crate::ir::{desugar, pretty};
+use crate::parser::parser::parse_program;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum What {
+    /// The syntax tree as the parser builds it.
+    Ast,
+    /// One control-flow graph per function, plus one for the top level.
+    Cfg,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Format {
+    Dot,
+    Mermaid,
+}
+
+pub fn run(args: &[String]) -> Result<String, String> {
+    let (options, positional) = split_options(args);
+    let mut what = What::Ast;
+    let mut format = Format::Dot;
+
+    for (name, value) in options {
+        match (name, value) {
+            ("what", Some("ast")) => what = What::Ast,
+            ("what", Some("cfg")) => what = What::Cfg,
+            ("what", value) => {
+                return Err(format!(
+                    "unknown graph '{}': expected ast or cfg",
+                    value.unwrap_or("")
+                ))
+            }
+            ("format", Some("dot")) => format = Format::Dot,
+            ("format", Some("mermaid")) => format = Format::Mermaid,
+            ("format", value) => {
+                return Err(format!(
+                    "unknown format '{}': expected dot or mermaid",
+                    value.unwrap_or("")
+                ))
+            }
+            _ => return Err(format!("unknown option '--{}'", name)),
+        }
+    }
+
+    let path = match positional.as_slice() {
+        [path] => *path,
+        _ => return Err(String::from("viz expects exactly one file")),
+    };
+    viz(&read_source(path)?, what, format)
+}
+
+/// Draws the syntax tree or the control-flow graphs of `source`.
+pub fn viz(source: &str, what: What, format: Format) -> Result<String, String> {
+    let program = parse_program(source)?;
+    let graph = match what {
+        What::Ast => ast_graph(&program),
+        What::Cfg => cfg_graph(&cfg::program(&desugar::program(program))),
+    };
+    Ok(match format {
+        Format::Dot => graph.dot(),
+        Format::Mermaid => graph.mermaid(),
+    })
+}
+
+/// A graph ready to be printed; nodes with a cluster are drawn inside a box
+/// with the cluster's title.
+#[derive(Default)]
+struct Graph {
+    clusters: Vec<String>,
+    nodes: Vec<(String, String, Option<usize>)>,
+    edges: Vec<(String, String, Option<String>)>,
+}
+
+impl Graph {
+    fn dot(&self) -> String {
+        let escape = |text: &str| text.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\l");
+        let node = |(id, label, _): &(String, String, Option<usize>)| {
+            format!("\"{}\" [label=\"{}\\l\"];\n", id, escape(label))
+        };
+
+        let mut out = String::from("digraph program {\n  node [shape=box, fontname=monospace];\n");
+        for (id, label, cluster) in &self.nodes {
+            if cluster.is_none() {
+                out.push_str(&format!("  {}", node(&(id.clone(), label.clone(), None))));
+            }
+        }
+        for (index, title) in self.clusters.iter().enumerate() {
+            out.push_str(&format!(
+                "  subgraph cluster_{} {{\n    label=\"{}\";\n",
+                index,
+                escape(title)
+            ));
+            for n in self.nodes.iter().filter(|(_, _, c)| *c == Some(index)) {
+                out.push_str(&format!("    {}", node(n)));
+            }
+            out.push_str("  }\n");
+        }
+        for (from, to, label) in &self.edges {
+            match label {
+                Some(label) => out.push_str(&format!(
+                    "  \"{}\" -> \"{}\" [label=\"{}\"];\n",
+                    from,
+                    to,
+                    escape(label)
+                )),
+                None => out.push_str(&format!("  \"{}\" -> \"{}\";\n", from, to)),
+            }
+        }
+        out.push_str("}\n");
+        out
+    }
+
+    fn mermaid(&self) -> String {
+        let escape = |text: &str| text.replace('"', "#quot;").replace('\n', "<br/>");
+        let node = |(id, label, _): &(String, String, Option<usize>)| {
+            format!("{}[\"{}\"]\n", id, escape(label))
+        };
+
+        let mut out = String::from("flowchart TD\n");
+        for n in self.nodes.iter().filter(|(_, _, c)| c.is_none()) {
+            out.push_str(&format!("  {}", node(n)));
+        }
+        for (index, title) in self.clusters.iter().enumerate() {
+            out.push_str(&format!("  subgraph cluster_{} [\"{}\"]\n", index, escape(title)));
+            for n in self.nodes.iter().filter(|(_, _, c)| *c == Some(index)) {
+                out.push_str(&format!("    {}", node(n)));
+            }
+            out.push_str("  end\n");
+        }
+        for (from, to, label) in &self.edges {
+            match label {
+                Some(label) => {
+                    out.push_str(&format!("  {} -->|\"{}\"| {}\n", from, escape(label), to))
+                }
+                None => out.push_str(&format!("  {} --> {}\n", from, to)),
+            }
+        }
+        out
+    }
+}
+
+fn cfg_graph(cfgs: &[Cfg]) -> Graph {
+    let mut graph = Graph::default();
+
+    for (index, cfg) in cfgs.iter().enumerate() {
+        let id = |block: usize| format!("f{}_b{}", index, block);
+        graph.clusters.push(cfg.name.clone());
+
+        for block in &cfg.blocks {
+            let mut lines = vec![match block.id {
+                b if b == cfg.entry => format!("B{} (entry)", b),
+                b if b == cfg.exit => format!("B{} (exit)", b),
+                b => format!("B{}", b),
+            }];
+            lines.extend(block.statements.iter().map(|(stmt, _)| pretty::statement(stmt)));
+            match &block.terminator {
+                Terminator::Branch(cond, _, _) => lines.push(format!("if {}", pretty::expression(cond))),
+                Terminator::Match(exp, _) => lines.push(format!("match {}", pretty::expression(exp))),
+                Terminator::Return(exp, _) => lines.push(format!("return {}", pretty::expression(exp))),
+                Terminator::Goto(_) | Terminator::Exit => {}
+            }
+            graph.nodes.push((id(block.id), lines.join("\n"), Some(index)));
+
+            let labels: Vec<Option<String>> = match &block.terminator {
+                Terminator::Branch(..) => vec![Some("true".to_string()), Some("false".to_string())],
+                Terminator::Match(_, arms) => arms
+                    .iter()
+                    .map(|(pattern, guard, _)| Some(pretty::arm(pattern, guard.as_ref())))
+                    .collect(),
+                _ => vec![None],
+            };
+            for (to, label) in block.terminator.successors().into_iter().zip(labels) {
+                graph.edges.push((id(block.id), id(to), label));
+            }
+        }
+    }
+    graph
+}
+
+fn ast_graph(program: &[Statement]) -> Graph {
+    let mut graph = Graph::default();
+    let root = add_node(&mut graph, "Program".to_string());
+    for stmt in program {
+        let child = statement_node(&mut graph, stmt);
+        graph.edges.push((root.clone(), child, None));
+    }
+    graph
+}
+
+fn add_node(graph: &mut Graph, label: String) -> String {
+    let id = format!("n{}", graph.nodes.len());
+    graph.nodes.push((id.clone(), label, None));
+    id
+}
+
+/// Adds a node labelled `label` with an edge to each of `children`.
+fn add_tree(graph: &mut Graph, label: String, children: Vec<String>) -> String {
+    let id = add_node(graph, label);
+    for child in children {
+        graph.edges.push((id.clone(), child, None));
+    }
+    id
+}
+
+fn statement_node(graph: &mut Graph, stmt: &Statement) -> String {
+    let exp = |graph: &mut Graph, e: &Expression| expression_node(graph, e);
+
+    match stmt {
+        Statement::VarDeclaration(name) => add_node(graph, format!("VarDeclaration {}", name)),
+        Statement::ValDeclaration(name) => add_node(graph, format!("ValDeclaration {}", name)),
+        Statement::Assignment(name, e, _) => {
+            let children = vec![exp(graph, e)];
+            add_tree(graph, format!("Assignment {}", name), children)
+        }
+        Statement::ConstDeclaration(name, _, e) => {
+            let children = vec![exp(graph, e)];
+            add_tree(graph, format!("ConstDeclaration {}", name), children)
+        }
+        Statement::Destructure(pattern, e) => {
+            let children = vec![exp(graph, e)];
+            add_tree(graph, format!("Destructure {}", pretty::pattern(pattern)), children)
+        }
+        Statement::AugmentedAssignment(name, operator, e) => {
+            let children = vec![exp(graph, e)];
+            add_tree(graph, format!("AugmentedAssignment {} {:?}", name, operator), children)
+        }
+        Statement::IfThenElse(cond, then_stmt, else_stmt) => {
+            let mut children = vec![exp(graph, cond), statement_node(graph, then_stmt)];
+            if let Some(else_stmt) = else_stmt {
+                children.push(statement_node(graph, else_stmt));
+            }
+            add_tree(graph, "IfThenElse".to_string(), children)
+        }
+        Statement::IfChain(branches, else_stmt) => {
+            let mut children = vec![];
+            for (cond, then_stmt) in branches {
+                let branch = vec![exp(graph, cond), statement_node(graph, then_stmt)];
+                children.push(add_tree(graph, "Branch".to_string(), branch));
+            }
+            if let Some(else_stmt) = else_stmt {
+                children.push(statement_node(graph, else_stmt));
+            }
+            add_tree(graph, "IfChain".to_string(), children)
+        }
+        Statement::While(cond, body) => {
+            let children = vec![exp(graph, cond), statement_node(graph, body)];
+            add_tree(graph, "While".to_string(), children)
+        }
+        Statement::Block(stmts) => {
+            let children = stmts.iter().map(|s| statement_node(graph, s)).collect();
+            add_tree(graph, "Block".to_string(), children)
+        }
+        Statement::Sequence(s1, s2) => {
+            let children = vec![statement_node(graph, s1), statement_node(graph, s2)];
+            add_tree(graph, "Sequence".to_string(), children)
+        }
+        Statement::AssertTrue(e, _) => {
+            let children = vec![exp(graph, e)];
+            add_tree(graph, "AssertTrue".to_string(), children)
+        }
+        Statement::AssertFalse(e, _) => {
+            let children = vec![exp(graph, e)];
+            add_tree(graph, "AssertFalse".to_string(), children)
+        }
+        Statement::AssertEQ(lhs, rhs, _) => {
+            let children = vec![exp(graph, lhs), exp(graph, rhs)];
+            add_tree(graph, "AssertEQ".to_string(), children)
+        }
+        Statement::AssertNEQ(lhs, rhs, _) => {
+            let children = vec![exp(graph, lhs), exp(graph, rhs)];
+            add_tree(graph, "AssertNEQ".to_string(), children)
+        }
+        Statement::AssertFails(_) => add_node(graph, "AssertFails".to_string()),
+        Statement::TestDef(func) => function_node(graph, "TestDef", func),
+        Statement::FuncDef(func) => function_node(graph, "FuncDef", func),
+        Statement::ModTestDef(name, stmt) => {
+            let children = vec![statement_node(graph, stmt)];
+            add_tree(graph, format!("ModTestDef {}", name), children)
+        }
+        Statement::Return(e) => {
+            let children = vec![exp(graph, e)];
+            add_tree(graph, "Return".to_string(), children)
+        }
+        Statement::Requires(e) => {
+            let children = vec![exp(graph, e)];
+            add_tree(graph, "Requires".to_string(), children)
+        }
+        Statement::Ensures(e) => {
+            let children = vec![exp(graph, e)];
+            add_tree(graph, "Ensures".to_string(), children)
+        }
+        Statement::Invariant(e) => {
+            let children = vec![exp(graph, e)];
+            add_tree(graph, "Invariant".to_string(), children)
+        }
+        Statement::Decreases(e) => {
+            let children = vec![exp(graph, e)];
+            add_tree(graph, "Decreases".to_string(), children)
+        }
+        Statement::Locals(layout) => add_node(graph, format!("Locals {}", layout.join(", "))),
+        Statement::Call(e) => {
+            let children = vec![exp(graph, e)];
+            add_tree(graph, "Call".to_string(), children)
+        }
+        Statement::Import(module) => add_node(graph, format!("Import {}", module)),
+        Statement::Module(module) => add_node(graph, format!("Module {}", module)),
+        Statement::ADTDeclaration(name, _, constructors) => {
+            let children = constructors
+                .iter()
+                .map(|c| add_node(graph, c.name.clone()))
+                .collect();
+            add_tree(graph, format!("ADTDeclaration {}", name), children)
+        }
+        Statement::Match(e, cases) => {
+            let mut children = vec![exp(graph, e)];
+            for (pattern, guard, stmt) in cases {
+                let mut case = vec![add_node(graph, pretty::pattern(pattern))];
+                if let Some(guard) = guard {
+                    let guard = vec![exp(graph, guard)];
+                    case.push(add_tree(graph, "Guard".to_string(), guard));
+                }
+                case.push(statement_node(graph, stmt));
+                children.push(add_tree(graph, "Case".to_string(), case));
+            }
+            add_tree(graph, "Match".to_string(), children)
+        }
+        Statement::FixityDeclaration(..) => add_node(graph, pretty::statement(stmt)),
+        Statement::At(_, stmt) => statement_node(graph, stmt),
+        Statement::FuncClauses(name, _, clauses) => {
+            let mut children = vec![];
+            for clause in clauses {
+                let mut case: Vec<String> = clause
+                    .params
+                    .iter()
+                    .map(|(pattern, _)| add_node(graph, pretty::pattern(pattern)))
+                    .collect();
+                if let Some(guard) = &clause.guard {
+                    let guard = vec![exp(graph, guard)];
+                    case.push(add_tree(graph, "Guard".to_string(), guard));
+                }
+                case.push(exp(graph, &clause.value));
+                children.push(add_tree(graph, "Clause".to_string(), case));
+            }
+            add_tree(graph, format!("FuncClauses {}", name), children)
+        }
+        Statement::InstanceDeclaration(class, adt_name, methods) => {
+            let children = methods
+                .iter()
+                .map(|m| function_node(graph, "FuncDef", m))
+                .collect();
+            add_tree(graph, format!("InstanceDeclaration {} {}", class, adt_name), children)
+        }
+    }
+}
+
+fn function_node(graph: &mut Graph, tag: &str, func: &Function) -> String {
+    let children = func
+        .body
+        .iter()
+        .map(|body| statement_node(graph, body))
+        .collect();
+    add_tree(graph, format!("{} {}", tag, func.name), children)
+}
+
+fn expression_node(graph: &mut Graph, exp: &Expression) -> String {
+    let (label, children): (String, Vec<&Expression>) = match exp {
+        Expression::CList(items) => ("CList".to_string(), items.iter().collect()),
+        Expression::CTuple(items) => ("CTuple".to_string(), items.iter().collect()),
+        Expression::CDict(entries) => (
+            "CDict".to_string(),
+            entries.iter().flat_map(|(k, v)| [k, v]).collect(),
+        ),
+        Expression::FuncCall(name, args) | Expression::CachedCall(CallSite { name, .. }, args) => {
+            (format!("FuncCall {}", name), args.iter().collect())
+        }
+        Expression::Pipe(value, name, args) => (
+            format!("Pipe {}", name),
+            std::iter::once(value.as_ref()).chain(args).collect(),
+        ),
+        Expression::Infix(first, rest) => (
+            format!(
+                "Infix {}",
+                rest.iter()
+                    .map(|(op, _)| match op {
+                        InfixOperator::Arithmetic(operator) => format!("{:?}", operator),
+                        InfixOperator::Function(name) => name.clone(),
+                    })
+                    .collect::<Vec<String>>()
+                    .join(" ")
+            ),
+            std::iter::once(first.as_ref()).chain(rest.iter().map(|(_, operand)| operand)).collect(),
+        ),
+        Expression::Add(l, r) => ("Add".to_string(), vec![l, r]),
+        Expression::Sub(l, r) => ("Sub".to_string(), vec![l, r]),
+        Expression::Mul(l, r) => ("Mul".to_string(), vec![l, r]),
+        Expression::Div(l, r) => ("Div".to_string(), vec![l, r]),
+        Expression::And(l, r) => ("And".to_string(), vec![l, r]),
+        Expression::Or(l, r) => ("Or".to_string(), vec![l, r]),
+        Expression::EQ(l, r) => ("EQ".to_string(), vec![l, r]),
+        Expression::GT(l, r) => ("GT".to_string(), vec![l, r]),
+        Expression::LT(l, r) => ("LT".to_string(), vec![l, r]),
+        Expression::GTE(l, r) => ("GTE".to_string(), vec![l, r]),
+        Expression::LTE(l, r) => ("LTE".to_string(), vec![l, r]),
+        Expression::Not(e) => ("Not".to_string(), vec![e]),
+        Expression::COk(e) => ("COk".to_string(), vec![e]),
+        Expression::CErr(e) => ("CErr".to_string(), vec![e]),
+        Expression::CJust(e) => ("CJust".to_string(), vec![e]),
+        Expression::Unwrap(e) => ("Unwrap".to_string(), vec![e]),
+        Expression::IsError(e) => ("IsError".to_string(), vec![e]),
+        Expression::IsNothing(e) => ("IsNothing".to_string(), vec![e]),
+        Expression::Propagate(e) => ("Propagate".to_string(), vec![e]),
+        Expression::ADTConstructor(adt_name, constructor_name, args) => (
+            format!("ADTConstructor {}.{}", adt_name, constructor_name),
+            args.iter().map(|arg| arg.as_ref()).collect(),
+        ),
+        leaf => (pretty::expression(leaf), vec![]),
+    };
+
+    let children = children
+        .into_iter()
+        .map(|child| expression_node(graph, child))
+        .collect();
+    add_tree(graph, label, children)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PROGRAM: &str =
+        "def f(x: TInteger) -> TInteger:\n    if x > 0:\n        return 1\n    else:\n        return 0\ny = f(2)";
+
+    #[test]
+    fn ast_is_drawn_as_a_tree() {
+        let dot = viz("x = 1 + y", What::Ast, Format::Dot).unwrap();
+        assert!(dot.starts_with("digraph program {\n"));
+        assert!(dot.contains("\"n4\" [label=\"Assignment x\\l\"];"));
+        assert!(dot.contains("\"n0\" -> \"n4\";"));
+
+        let mermaid = viz("x = 1 + y", What::Ast, Format::Mermaid).unwrap();
+        assert_eq!(
+            mermaid,
+            "flowchart TD\n  n0[\"Program\"]\n  n1[\"1\"]\n  n2[\"y\"]\n  n3[\"Add\"]\n  n4[\"Assignment x\"]\n  n3 --> n1\n  n3 --> n2\n  n4 --> n3\n  n0 --> n4\n"
+        );
+    }
+
+    #[test]
+    fn cfg_has_a_cluster_per_function() {
+        let dot = viz(PROGRAM, What::Cfg, Format::Dot).unwrap();
+        assert!(dot.contains("subgraph cluster_0 {\n    label=\"__main__\";"));
+        assert!(dot.contains("subgraph cluster_1 {\n    label=\"f\";"));
+        assert!(dot.contains("\"f1_b0\" [label=\"B0 (entry)\\lif x > 0\\l\"];"));
+        assert!(dot.contains("\"f1_b0\" -> \"f1_b2\" [label=\"true\"];"));
+
+        let mermaid = viz(PROGRAM, What::Cfg, Format::Mermaid).unwrap();
+        assert!(mermaid.contains("  subgraph cluster_1 [\"f\"]\n"));
+        assert!(mermaid.contains("f1_b2[\"B2<br/>return 1\"]"));
+        assert!(mermaid.contains("f1_b3[\"B3<br/>return 0\"]"));
+        assert!(mermaid.contains("f1_b0 -->|\"false\"| f1_b3"));
+    }
+
+    #[test]
+    fn viz_rejects_unknown_options() {
+        assert_eq!(
+            run(&["--what=types".to_string(), "a.rpy".to_string()]),
+            Err(String::from("unknown graph 'types': expected ast or cfg"))
+        );
+        assert_eq!(
+            run(&["--format=svg".to_string(), "a.rpy".to_string()]),
+            Err(String::from("unknown format 'svg': expected dot or mermaid"))
+        );
+    }
+}
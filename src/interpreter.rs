@@ -1 +1,7 @@
+pub mod debugger;
+pub mod generate;
 pub mod interpreter;
+pub mod program;
+pub mod property;
+pub mod replay;
+pub mod value;
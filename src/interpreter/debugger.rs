@@ -0,0 +1,235 @@
+use crate::interpreter::interpreter::{eval, run, ControlFlow, EnvValue};
+use crate::interpreter::value::render;
+use crate::ir::ast::{Breakpoint, Change, Environment, Expression, Function, Name, Observer, Paused, Statement};
+use crate::ir::desugar;
+use crate::parser::parser::{parse_expression, parse_program};
+use crate::tc::trace;
+
+/// Prefix of the errors of a paused frame.
+pub const DEBUGGER_ERROR: &str = "[Debugger Error]";
+
+impl Paused<'_, EnvValue> {
+    /// The value of the expression `source` in the paused frame, which sees
+    /// the variables of the frame and the functions of the program.
+    /// Evaluating never changes the frame, nor pauses at a breakpoint.
+    pub fn eval(&self, source: &str) -> Result<EnvValue, String> {
+        let exp = desugar::expression(parse_expression(source)?);
+        eval(exp, &detached(self.env)).map_err(|(message, _)| message)
+    }
+
+    /// The variables of the paused frame.
+    pub fn variables(&self) -> Vec<(Name, EnvValue)> {
+        let frame = self.env.get_frame(self.env.scope_key());
+        frame.variables.iter().map(|(name, value)| (name.clone(), value.clone())).collect()
+    }
+
+    /// Runs the statements in `source`, such as `x = 0`, in the paused
+    /// frame, which the program sees when it resumes. Only a mutable
+    /// debugger may; nothing changes unless every statement runs.
+    pub fn execute(&mut self, source: &str) -> Result<(), String> {
+        if !self.mutable {
+            return Err(format!(
+                "{} the frame paused at '{}' is read-only; make the debugger mutable to change it.",
+                DEBUGGER_ERROR, self.location
+            ));
+        }
+        let mut env = detached(self.env);
+        for stmt in desugar::program(parse_program(source)?) {
+            match run(stmt, &env)? {
+                ControlFlow::Continue(new_env) => env = new_env,
+                ControlFlow::Return(_) => {
+                    return Err(format!("{} a paused frame cannot return.", DEBUGGER_ERROR));
+                }
+            }
+        }
+        env.observer = self.env.observer.clone();
+        *self.env = env;
+        Ok(())
+    }
+}
+
+/// A breakpoint as written: `call f` for the calls to `f`, `watch x` for
+/// the changes to `x`, `watch x when x < 0` for the bindings of `x` that
+/// leave `x < 0` true, and any other text for the statements it reads as.
+pub fn breakpoint(spec: &str) -> Result<Breakpoint, String> {
+    let spec = spec.trim();
+    let mut words = spec.splitn(2, char::is_whitespace);
+    match (words.next(), words.next().map(str::trim)) {
+        (Some("call"), Some(name)) => Ok(Breakpoint::Call(name.to_string())),
+        (Some("watch"), Some(rest)) => match rest.split_once(" when ") {
+            Some((name, condition)) => {
+                let condition = desugar::expression(parse_expression(condition)?);
+                Ok(Breakpoint::Watch(name.trim().to_string(), Some(condition)))
+            }
+            None => Ok(Breakpoint::Watch(rest.to_string(), None)),
+        },
+        _ => Ok(Breakpoint::Statement(spec.to_string())),
+    }
+}
+
+/// `env` without its observer, so what the debugger runs is neither
+/// reported nor paused.
+fn detached(env: &Environment<EnvValue>) -> Environment<EnvValue> {
+    let mut env = env.clone();
+    env.observer = Observer::default();
+    env
+}
+
+/// Pauses before `stmt` when it is a breakpoint.
+pub fn statement(stmt: &Statement, env: &mut Environment<EnvValue>) {
+    let observer = env.observer.clone();
+    let Some(debugger) = observer.debugging() else {
+        return;
+    };
+    let subject = trace::subject(stmt);
+    let hit = debugger
+        .breakpoints
+        .iter()
+        .any(|breakpoint| matches!(breakpoint, Breakpoint::Statement(text) if text.trim() == subject));
+    if hit {
+        debugger.pause(&mut Paused {
+            location: subject,
+            env,
+            mutable: debugger.mutable,
+        });
+    }
+}
+
+/// Pauses a call to `func` that has bound its parameters, when the
+/// function is a breakpoint. The call reads as `inc(2)`.
+pub fn call(func: &Function, env: &mut Environment<EnvValue>) {
+    let observer = env.observer.clone();
+    let Some(debugger) = observer.debugging() else {
+        return;
+    };
+    if debugger.breakpoints.contains(&Breakpoint::Call(func.name.clone())) {
+        let args: Vec<String> = func
+            .params
+            .iter()
+            .flatten()
+            .filter_map(|(param, _)| env.search_frame(param.clone()).map(render))
+            .collect();
+        debugger.pause(&mut Paused {
+            location: format!("{}({})", func.name, args.join(", ")),
+            env,
+            mutable: debugger.mutable,
+        });
+    }
+}
+
+/// Pauses after `change` when it trips a watchpoint: a watch without a
+/// condition trips when the value changes, one with a condition when the
+/// condition holds in the frame. A condition that fails to evaluate, as
+/// one reading a variable the frame lacks, does not hold.
+pub fn watch(change: &Change<EnvValue>, env: &mut Environment<EnvValue>) {
+    let observer = env.observer.clone();
+    let Some(debugger) = observer.debugging() else {
+        return;
+    };
+    let hit = debugger.breakpoints.iter().any(|breakpoint| match breakpoint {
+        Breakpoint::Watch(name, None) => *name == change.name && change.old.as_ref() != Some(&change.new),
+        Breakpoint::Watch(name, Some(condition)) => {
+            *name == change.name
+                && matches!(eval(condition.clone(), &detached(env)), Ok(EnvValue::Exp(Expression::CTrue)))
+        }
+        _ => false,
+    });
+    if hit {
+        debugger.pause(&mut Paused {
+            location: change.span.clone(),
+            env,
+            mutable: debugger.mutable,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interpreter::program::{execute_statements, Stats};
+    use crate::ir::ast::{Debugger, Expression};
+    use std::sync::{Arc, Mutex};
+
+    fn debug(source: &str, debugger: Debugger<EnvValue>) -> Environment<EnvValue> {
+        let mut env = Environment::new();
+        env.observer = Observer::debugger(debugger);
+        execute_statements(&crate::rpy!(source), &mut env, &mut Stats::default()).unwrap();
+        env
+    }
+
+    #[test]
+    fn paused_frames_evaluate_expressions() {
+        let seen = Arc::new(Mutex::new(vec![]));
+        let log = seen.clone();
+        let debugger = Debugger::new(
+            vec![Breakpoint::Statement(String::from("total = total + i")), Breakpoint::Call(String::from("twice"))],
+            move |paused: &mut Paused<EnvValue>| {
+                let value = match paused.location.as_str() {
+                    "twice(4)" => paused.eval("n + twice(1)"),
+                    _ => paused.eval("total * 10 + i"),
+                };
+                let refused = paused.execute("total = 0").unwrap_err();
+                assert!(refused.starts_with("[Debugger Error] the frame paused at"), "{}", refused);
+                log.lock().unwrap().push(format!("{}: {}", paused.location, render(&value.unwrap())));
+            },
+        );
+        let env = debug(
+            "
+            def twice(n: TInteger) -> TInteger:
+                return n * 2
+            total = 0
+            i = 1
+            while i < 3:
+                total = total + i
+                i = i + 1
+            x = twice(4)
+            ",
+            debugger,
+        );
+        assert_eq!(
+            *seen.lock().unwrap(),
+            vec!["total = total + i: 1", "total = total + i: 12", "twice(4): 6"]
+        );
+        assert_eq!(env.search_frame(String::from("total")), Some(&EnvValue::Exp(Expression::CInt(3))));
+    }
+
+    #[test]
+    fn mutable_debuggers_change_the_paused_frame() {
+        let debugger = Debugger::new(vec![Breakpoint::Statement(String::from("y = x + 1"))], |paused: &mut Paused<EnvValue>| {
+            assert_eq!(paused.variables().len(), 1);
+            assert!(paused.execute("x = 41 +").unwrap_err().starts_with("[Syntax Error]"));
+            paused.execute("x = 41").unwrap();
+        });
+        let env = debug("x = 1\ny = x + 1", debugger.mutable());
+        assert_eq!(env.search_frame(String::from("y")), Some(&EnvValue::Exp(Expression::CInt(42))));
+    }
+
+    #[test]
+    fn watchpoints_pause_after_the_bindings_they_watch() {
+        let seen = Arc::new(Mutex::new(vec![]));
+        let log = seen.clone();
+        let breakpoints = vec![breakpoint("watch x when x < 0").unwrap(), breakpoint(" watch  y ").unwrap()];
+        let debugger = Debugger::new(breakpoints, move |paused: &mut Paused<EnvValue>| {
+            let x = paused.eval("x").map(|value| render(&value)).unwrap_or_default();
+            log.lock().unwrap().push(format!("{} (x = {})", paused.location, x));
+        });
+        debug(
+            "
+            x = 1
+            y = 0
+            x = x - 2
+            y = 0
+            x = x - 2
+            y = 5
+            ",
+            debugger,
+        );
+        assert_eq!(
+            *seen.lock().unwrap(),
+            vec!["y = 0 (x = 1)", "x = x - 2 (x = -1)", "x = x - 2 (x = -3)", "y = 5 (x = -3)"]
+        );
+        assert_eq!(breakpoint("call f"), Ok(Breakpoint::Call(String::from("f"))));
+        assert_eq!(breakpoint("i = i + 1"), Ok(Breakpoint::Statement(String::from("i = i + 1"))));
+        assert!(breakpoint("watch x when x <").unwrap_err().starts_with("[Syntax Error]"));
+    }
+}
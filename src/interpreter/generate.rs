@@ -0,0 +1,232 @@
+use crate::ir::ast::{AdtValue, Expression, Name, Type, ValueConstructor};
+use crate::ir::complex::Complex;
+use crate::ir::decimal::Decimal;
+use std::collections::BTreeMap;
+
+/// Declared ADTs, as in `Environment::type_env`.
+pub type Adts = BTreeMap<Name, Vec<ValueConstructor>>;
+
+/// Random values of a given type, for property tests and fuzzing. The same
+/// seed produces the same values, so a failure can be replayed.
+pub struct Generator<'a> {
+    rng: Rng,
+    adts: &'a Adts,
+    /// The types type variables stand for, as in `a` of `[a]`. Unbound
+    /// variables are integers.
+    bindings: BTreeMap<Name, Type>,
+    /// Bounds numbers, the length of strings, lists and dicts, and the
+    /// nesting of ADT values.
+    pub size: usize,
+}
+
+impl<'a> Generator<'a> {
+    pub fn new(seed: u64, adts: &'a Adts) -> Generator<'a> {
+        Generator {
+            rng: Rng::new(seed),
+            adts,
+            bindings: BTreeMap::new(),
+            size: 10,
+        }
+    }
+
+    /// Instantiates the type variable `var` with `kind`.
+    pub fn bind(mut self, var: &str, kind: Type) -> Generator<'a> {
+        self.bindings.insert(var.to_string(), kind);
+        self
+    }
+
+    /// `kind` with its type variables instantiated and the constructors of
+    /// the ADTs it names looked up.
+    pub fn resolve(&self, kind: &Type) -> Type {
+        let resolve = |kind: &Type| Box::new(self.resolve(kind));
+        match kind {
+            Type::TVar(var, _) => self.bindings.get(var).cloned().unwrap_or(Type::TInteger),
+            Type::TList(item) => Type::TList(resolve(item)),
+            Type::TDict(key, value) => Type::TDict(resolve(key), resolve(value)),
+            Type::TMaybe(item) => Type::TMaybe(resolve(item)),
+            Type::TResult(ok, err) => Type::TResult(resolve(ok), resolve(err)),
+            Type::Tadt(name, constructors) if constructors.is_empty() => match self.adts.get(name) {
+                Some(constructors) => Type::Tadt(name.clone(), constructors.clone()),
+                None => kind.clone(),
+            },
+            _ => kind.clone(),
+        }
+    }
+
+    /// A random value of type `kind`, no larger than `size`.
+    pub fn value(&mut self, kind: &Type) -> Result<Expression, String> {
+        let kind = self.resolve(kind);
+        self.generate(&kind, self.size)
+    }
+
+    fn generate(&mut self, kind: &Type, size: usize) -> Result<Expression, String> {
+        let rng = &mut self.rng;
+        Ok(match kind {
+            Type::TInteger => Expression::CInt(rng.int(size)),
+            Type::TBool if rng.below(2) == 0 => Expression::CFalse,
+            Type::TBool => Expression::CTrue,
+            Type::TReal => Expression::CReal(rng.int(100 * size) as f64 / 100.0),
+            Type::TDecimal => Expression::CDecimal(Decimal::new(rng.int(100 * size) as i128, 2)),
+            Type::TComplex => Expression::CComplex(Complex::new(
+                rng.int(100 * size) as f64 / 100.0,
+                rng.int(100 * size) as f64 / 100.0,
+            )),
+            Type::TString => {
+                const CHARS: &[u8] = b"abcxyz AZ09_";
+                let length = rng.below(size + 1);
+                Expression::CString((0..length).map(|_| CHARS[rng.below(CHARS.len())] as char).collect())
+            }
+            Type::TBytes => Expression::CBytes((0..rng.below(size + 1)).map(|_| rng.below(256) as u8).collect()),
+            Type::TVoid => Expression::CVoid,
+            Type::TList(item) => {
+                let length = rng.below(size + 1);
+                Expression::CList((0..length).map(|_| self.generate(item, size)).collect::<Result<_, _>>()?)
+            }
+            Type::TDict(key, value) => {
+                let mut entries: Vec<(Expression, Expression)> = vec![];
+                for _ in 0..rng.below(size + 1) {
+                    let k = self.generate(key, size)?;
+                    if !entries.iter().any(|(other, _)| *other == k) {
+                        entries.push((k, self.generate(value, size)?));
+                    }
+                }
+                Expression::CDict(entries)
+            }
+            Type::TMaybe(_) if rng.below(4) == 0 => Expression::CNothing,
+            Type::TMaybe(item) => Expression::CJust(Box::new(self.generate(item, size)?)),
+            Type::TResult(ok, _) if rng.below(2) == 0 => Expression::COk(Box::new(self.generate(ok, size)?)),
+            Type::TResult(_, err) => Expression::CErr(Box::new(self.generate(err, size)?)),
+            Type::Tadt(name, _) => {
+                let constructors = constructors(kind, self.adts)?;
+                // Recursive constructors are left out once the size runs out
+                let choices: Vec<usize> = (0..constructors.len())
+                    .filter(|&tag| size > 0 || !mentions(&constructors[tag], name))
+                    .collect();
+                let Some(&tag) = choices.get(rng.below(choices.len())) else {
+                    return Err(format!("[Property Error] cannot build a finite '{}' value.", name));
+                };
+                let args = constructors[tag]
+                    .types
+                    .iter()
+                    .map(|field| self.generate(&self.resolve(field), size / 2).map(Box::new))
+                    .collect::<Result<_, _>>()?;
+                Expression::ADTValue(AdtValue::new(name.clone(), constructors[tag].name.clone(), tag, args))
+            }
+            _ => return Err(format!("[Property Error] cannot generate values of type {:?}.", kind)),
+        })
+    }
+}
+
+/// A small xorshift generator.
+struct Rng(u64);
+
+impl Rng {
+    fn new(seed: u64) -> Rng {
+        Rng(seed.max(1))
+    }
+
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    /// A number in `0..n`.
+    fn below(&mut self, n: usize) -> usize {
+        (self.next() % n.max(1) as u64) as usize
+    }
+
+    /// A number in `-size..=size`.
+    fn int(&mut self, size: usize) -> i32 {
+        self.below(2 * size + 1) as i32 - size as i32
+    }
+}
+
+/// The constructors of the ADT `kind`, looked up in `adts` when the type
+/// does not carry them.
+pub fn constructors(kind: &Type, adts: &Adts) -> Result<Vec<ValueConstructor>, String> {
+    match kind {
+        Type::Tadt(_, constructors) if !constructors.is_empty() => Ok(constructors.clone()),
+        Type::Tadt(name, _) => adts
+            .get(name)
+            .cloned()
+            .ok_or_else(|| format!("[Property Error] unknown type '{}'.", name)),
+        _ => Err(format!("[Property Error] {:?} is not an ADT.", kind)),
+    }
+}
+
+fn mentions(constructor: &ValueConstructor, adt: &str) -> bool {
+    constructor
+        .types
+        .iter()
+        .any(|field| matches!(field, Type::Tadt(name, _) if name == adt))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn depth(value: &Expression) -> usize {
+        match value {
+            Expression::ADTValue(value) => 1 + value.fields.iter().map(|arg| depth(arg)).max().unwrap_or(0),
+            _ => 0,
+        }
+    }
+
+    #[test]
+    fn values_respect_their_type_and_size() {
+        let adts = Adts::new();
+        let list = Type::TList(Box::new(Type::TInteger));
+        let mut generator = Generator::new(42, &adts);
+        generator.size = 5;
+        for _ in 0..100 {
+            let Expression::CList(items) = generator.value(&list).unwrap() else {
+                panic!("expected a list");
+            };
+            assert!(items.len() <= 5);
+            assert!(items.iter().all(|item| matches!(item, Expression::CInt(n) if n.abs() <= 5)));
+        }
+
+        let values = |seed| {
+            let mut generator = Generator::new(seed, &adts);
+            (0..20).map(|_| generator.value(&list).unwrap()).collect::<Vec<_>>()
+        };
+        assert_eq!(values(7), values(7));
+        assert_ne!(values(7), values(8));
+
+        let mut generator = Generator::new(1, &adts);
+        assert!(generator.value(&Type::TFunction(Box::new(None), vec![])).is_err());
+    }
+
+    #[test]
+    fn type_variables_and_adts_are_instantiated() {
+        let adts = Adts::from([(
+            String::from("Tree"),
+            vec![
+                ValueConstructor { name: String::from("Leaf"), types: vec![] },
+                ValueConstructor {
+                    name: String::from("Node"),
+                    types: vec![
+                        Type::Tadt(String::from("Tree"), vec![]),
+                        Type::TVar(String::from("a"), vec![]),
+                        Type::Tadt(String::from("Tree"), vec![]),
+                    ],
+                },
+            ],
+        )]);
+        let mut generator = Generator::new(3, &adts).bind("a", Type::TBool);
+        generator.size = 8;
+
+        let bools = Type::TList(Box::new(Type::TVar(String::from("a"), vec![])));
+        let Ok(Expression::CList(items)) = generator.value(&bools) else {
+            panic!("expected a list");
+        };
+        assert!(items.iter().all(|item| matches!(item, Expression::CTrue | Expression::CFalse)));
+
+        let tree = Type::Tadt(String::from("Tree"), vec![]);
+        let trees: Vec<Expression> = (0..50).map(|_| generator.value(&tree).unwrap()).collect();
+        assert!(trees.iter().all(|tree| depth(tree) <= 5));
+        assert!(trees.iter().any(|tree| depth(tree) > 1));
+    }
+}
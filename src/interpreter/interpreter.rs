@@ -1,9 +1,43 @@
-use std::collections::HashSet;
-
-use crate::ir::ast::{Environment, Expression, Function, Name, Statement, TestEnvironment};
+use std::cell::RefCell;
+use std::collections::hash_map::Entry;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::time::Instant;
+
+use crate::builtins::registry;
+use crate::interpreter::debugger;
+use crate::interpreter::value::{check_limits, hash_key, render, type_name, ValueKey};
+use crate::ir::ast::{
+    annotations, equal_values, AdtValue, CallSite, Change, Environment, Expression, Function, Mark, Name, Observer, Pattern,
+    Snapshot, Statement, TestEnvironment, Type,
+};
+use crate::ir::classes::builtin_instance;
+use crate::ir::complex::Complex;
+use crate::ir::decimal::Decimal;
+use crate::ir::{desugar, pretty};
 
 type ErrorMessage = (String, Option<Expression>);
 
+/// Prefix of the error raised when no arm of a `match` applies, so that it
+/// can be told apart from other runtime errors.
+pub const MATCH_FAILURE: &str = "[Match Failure]";
+
+/// Prefix of the error raised when a program exceeds one of the
+/// environment's `Limits`.
+pub const RESOURCE_EXHAUSTED: &str = "[Resource Exhausted]";
+
+/// Prefix of the error raised when a program runs past its deadline.
+pub const TIMEOUT: &str = "[Timeout]";
+
+/// Prefix of the error raised when a `requires` or `ensures` clause fails.
+pub const CONTRACT_VIOLATION: &str = "[Contract Violation]";
+
+/// Prefix of the error raised when a loop `invariant` fails.
+pub const INVARIANT_VIOLATED: &str = "[Invariant Violated]";
+
+/// Prefix of the error raised when a `decreases` measure does not decrease
+/// or drops below zero.
+pub const NON_TERMINATION: &str = "[Termination Error]";
+
 #[derive(Clone, Debug, PartialEq)]
 pub enum EnvValue {
     Exp(Expression),
@@ -17,6 +51,7 @@ pub enum ControlFlow {
 }
 
 pub fn eval(exp: Expression, env: &Environment<EnvValue>) -> Result<EnvValue, ErrorMessage> {
+    env.counters.step();
     match exp {
         Expression::Add(lhs, rhs) => add(*lhs, *rhs, env),
         Expression::Sub(lhs, rhs) => sub(*lhs, *rhs, env),
@@ -31,6 +66,10 @@ pub fn eval(exp: Expression, env: &Environment<EnvValue>) -> Result<EnvValue, Er
         Expression::GTE(lhs, rhs) => gte(*lhs, *rhs, env),
         Expression::LTE(lhs, rhs) => lte(*lhs, *rhs, env),
         Expression::Var(name) => lookup(name, env),
+        Expression::Slot(slot, name) => match env.current_frame().and_then(|frame| frame.variables.slot(slot)) {
+            Some(value) => Ok(value.clone()),
+            None => lookup(name, env),
+        },
         Expression::COk(e) => eval_ok(*e, env),
         Expression::CErr(e) => eval_err(*e, env),
         Expression::CJust(e) => eval_just(*e, env),
@@ -39,7 +78,15 @@ pub fn eval(exp: Expression, env: &Environment<EnvValue>) -> Result<EnvValue, Er
         Expression::IsError(e) => eval_iserror_expression(*e, env),
         Expression::IsNothing(e) => eval_isnothing_expression(*e, env),
         Expression::FuncCall(name, args) => call(name, args, env),
+        Expression::CachedCall(site, args) => cached_call(site, args, env),
         Expression::ADTConstructor(adt_name,constructor_name,args ) => adtconstructor_eval(adt_name,constructor_name, args, env),
+        Expression::ADTValue(..) => Ok(EnvValue::Exp(exp)),
+        Expression::Closure(..) => Ok(EnvValue::Exp(exp)),
+        Expression::CList(items) => eval_list(items, env),
+        Expression::CTuple(items) => eval_tuple(items, env),
+        Expression::CDict(entries) => eval_dict(entries, env),
+        Expression::Pipe(..) => Err((desugar::surface_error("Pipe"), None)),
+        Expression::Infix(..) => Err((desugar::surface_error("Infix"), None)),
         _ if is_constant(exp.clone()) => Ok(EnvValue::Exp(exp)),
         _ => Err((String::from("Not implemented yet."), None)),
     }
@@ -64,13 +111,38 @@ pub fn run(stmt: Statement, env: &Environment<EnvValue>) -> Result<ControlFlow,
     }
 }
 
+/// The `Err` or `Nothing` that `tryUnwrap` or `?` found surfaces here,
+/// however deeply the statement that raised it is nested, and turns into
+/// a return of that value.
 fn execute(stmt: Statement, env: &Environment<EnvValue>) -> Result<ControlFlow, ErrorMessage> {
+    match execute_statement(stmt, env) {
+        Err((s, Some(exp))) if s == "Propagate" => propagate_error(exp, env),
+        Err((s, _)) => Err((s, None)),
+        result => result,
+    }
+}
+
+fn execute_statement(stmt: Statement, env: &Environment<EnvValue>) -> Result<ControlFlow, ErrorMessage> {
+    env.counters.step();
     let mut new_env = env.clone();
+    debugger::statement(&stmt, &mut new_env);
 
-    let result = match stmt {
-        Statement::Assignment(name, exp, _) => {
+    match stmt {
+        Statement::Assignment(name, exp, kind) => {
+            let span = assignment_span(&new_env, &name, &exp, kind);
             let value = eval(*exp, &new_env)?;
-            new_env.insert_variable(name, value); // Remove the tuple
+            bind(&mut new_env, name, value, span, false);
+            check_bindings(&new_env)?;
+            Ok(ControlFlow::Continue(new_env))
+        }
+
+        Statement::ConstDeclaration(name, kind, exp) => {
+            define_constant(name, kind, *exp, &mut new_env)?;
+            Ok(ControlFlow::Continue(new_env))
+        }
+
+        Statement::Destructure(pattern, exp) => {
+            destructure(pattern, *exp, &mut new_env)?;
             Ok(ControlFlow::Continue(new_env))
         }
 
@@ -78,15 +150,9 @@ fn execute(stmt: Statement, env: &Environment<EnvValue>) -> Result<ControlFlow,
             let value = eval(*cond, &new_env)?;
 
             match value {
-                EnvValue::Exp(Expression::CTrue) => match *stmt_then {
-                    Statement::Block(stmts) => execute_block(stmts, &new_env),
-                    _ => execute(*stmt_then, &new_env),
-                },
+                EnvValue::Exp(Expression::CTrue) => execute(*stmt_then, &new_env),
                 EnvValue::Exp(Expression::CFalse) => match stmt_else {
-                    Some(else_stmt) => match *else_stmt {
-                        Statement::Block(stmts) => execute_block(stmts, &new_env),
-                        _ => execute(*else_stmt, &new_env),
-                    },
+                    Some(else_stmt) => execute(*else_stmt, &new_env),
                     None => Ok(ControlFlow::Continue(new_env)),
                 },
                 _ => Err(("Condition must evaluate to a boolean".to_string(), None)),
@@ -96,17 +162,58 @@ fn execute(stmt: Statement, env: &Environment<EnvValue>) -> Result<ControlFlow,
         Statement::Block(stmts) => execute_block(stmts, &new_env),
 
         Statement::While(cond, stmt) => {
+            // Each iteration is a block, as the type checker assumes
+            let (annotations, body) = match annotations(&stmt) {
+                (annotations, Statement::Block(stmts)) => (annotations, Statement::Block(stmts)),
+                (annotations, stmt) => (annotations, Statement::Block(vec![stmt])),
+            };
+            let invariants = &annotations.invariants;
+            let check = new_env.check_invariants && !invariants.is_empty();
+            let what = format!("the loop 'while {}'", pretty::expression(&cond));
+            let mut iteration = 0;
+            if check {
+                check_invariants(invariants, &cond, iteration, &new_env)?;
+            }
             let mut value = eval(*cond.clone(), &new_env)?;
 
             loop {
+                check_deadline(&new_env)?;
                 match value {
-                    EnvValue::Exp(Expression::CTrue) => match execute(*stmt.clone(), &new_env)? {
-                        ControlFlow::Continue(control_env) => {
-                            new_env = control_env;
-                            value = eval(*cond.clone(), &new_env)?;
+                    EnvValue::Exp(Expression::CTrue) => {
+                        let before = match annotations.decreases.first() {
+                            Some(clause) => Some((clause, measure(clause, &what, &new_env)?)),
+                            None => None,
+                        };
+
+                        match execute(body.clone(), &new_env)? {
+                            ControlFlow::Continue(control_env) => {
+                                new_env = control_env;
+                                iteration += 1;
+                                if check {
+                                    check_invariants(invariants, &cond, iteration, &new_env)?;
+                                }
+                                if let Some((clause, before)) = before {
+                                    let after = measure(clause, &what, &new_env)?;
+                                    if after >= before {
+                                        return Err((
+                                            format!(
+                                                "{} the measure '{}' of {} did not decrease in iteration {}: it went from {} to {}.",
+                                                NON_TERMINATION,
+                                                pretty::expression(clause),
+                                                what,
+                                                iteration,
+                                                before,
+                                                after
+                                            ),
+                                            None,
+                                        ));
+                                    }
+                                }
+                                value = eval(*cond.clone(), &new_env)?;
+                            }
+                            ControlFlow::Return(value) => return Ok(ControlFlow::Return(value)),
                         }
-                        ControlFlow::Return(value) => return Ok(ControlFlow::Return(value)),
-                    },
+                    }
                     EnvValue::Exp(Expression::CFalse) => return Ok(ControlFlow::Continue(new_env)),
                     _ => unreachable!(),
                 }
@@ -205,7 +312,13 @@ fn execute(stmt: Statement, env: &Environment<EnvValue>) -> Result<ControlFlow,
             ControlFlow::Return(value) => return Ok(ControlFlow::Return(value)),
         },
         Statement::FuncDef(func) => {
-            new_env.insert_variable(func.name.clone(), EnvValue::Func(func.clone()));
+            let captured = capture(&func, &new_env)?;
+            let value = if captured.is_empty() {
+                EnvValue::Func(func.clone())
+            } else {
+                EnvValue::Exp(Expression::Closure(Box::new(func.clone()), captured))
+            };
+            new_env.insert_variable(func.name.clone(), value);
             Ok(ControlFlow::Continue(new_env))
         }
 
@@ -213,45 +326,55 @@ fn execute(stmt: Statement, env: &Environment<EnvValue>) -> Result<ControlFlow,
             let exp_value = eval(*exp, &new_env)?;
             Ok(ControlFlow::Return(exp_value))
         }
+
+        Statement::Call(exp) => {
+            eval(*exp, &new_env)?;
+            Ok(ControlFlow::Continue(new_env))
+        }
+
+        // The loader has already placed the module's definitions before
+        // the import, under their qualified names
+        Statement::Import(_) | Statement::Module(_) => Ok(ControlFlow::Continue(new_env)),
         
-        Statement::ADTDeclaration(name, constructors) => {
+        Statement::ADTDeclaration(name, _, constructors) => {
             // Insert the ADT into the new environment
             new_env.insert_type(name, constructors);
             // Return the new environment along with ControlFlow
             Ok(ControlFlow::Continue(new_env))
         }
 
-        Statement::Match(exp, cases) => {
-            let value = eval(*exp, &new_env)?;
-        
-            for (pattern, stmt) in cases {
-                if matches_pattern(&value, &pattern, &new_env)? {
-                    return match *stmt {
-                        Statement::Block(stmts) => execute_block(stmts, &new_env),
-                        _ => execute(*stmt, &new_env),
-                    };
-                }
-            }
-            
-            Err(("No matching pattern found".to_string(), None))
+        Statement::InstanceDeclaration(class, adt_name, methods) => {
+            new_env.insert_instance(class, adt_name, methods);
+            Ok(ControlFlow::Continue(new_env))
         }
-        
-        _ => Err((String::from("not implemented yet"), None)),
-        
-    };
 
-    match result {
-        Ok(v) => Ok(v),
-        Err((s, opt)) => {
-            if s != "Propagate".to_string() {
-                return Err((s, None));
-            } else {
-                return propagate_error(opt.unwrap(), env);
-            }
+        Statement::Match(exp, cases) => execute_match(*exp, cases, &new_env),
+
+        Statement::Invariant(_) | Statement::Decreases(_) => Err((
+            String::from("[Syntax Error] invariants are only allowed at the start of a while body."),
+            None,
+        )),
+
+        Statement::Requires(_) | Statement::Ensures(_) => Err((
+            String::from("[Syntax Error] contracts are only allowed at the start of a function body."),
+            None,
+        )),
+
+        // Calls number the locals of the layout before running the body;
+        // anywhere else, the body's variables are simply bound by name
+        Statement::Locals(_) => Ok(ControlFlow::Continue(new_env)),
+
+        Statement::IfChain(..) => Err((desugar::surface_error("IfChain"), None)),
+        Statement::AugmentedAssignment(..) => {
+            Err((desugar::surface_error("AugmentedAssignment"), None))
         }
-    }
+        Statement::FuncClauses(..) => Err((desugar::surface_error("FuncClauses"), None)),
+        Statement::FixityDeclaration(..) => Err((desugar::surface_error("FixityDeclaration"), None)),
+        Statement::At(_, stmt) => execute(*stmt, env),
 
-    
+        _ => Err((String::from("not implemented yet"), None)),
+        
+    }
 }
 
 
@@ -262,13 +385,14 @@ fn adtconstructor_eval(
     env: &Environment<EnvValue>,
 ) -> Result<EnvValue, (String, Option<Expression>)> {
     if let Some(constructors) = env.get_type(&adt_name) {
-        let value_constructor = constructors.iter().find(|vc| vc.name == constructor_name);
-        
-        if let Some(vc) = value_constructor {
+        let tag = constructors.iter().position(|vc| vc.name == constructor_name);
+
+        if let Some(tag) = tag {
+            let vc = &constructors[tag];
             if vc.types.len() != args.len() {
                 return Err((
                     format!(
-                        "Error: Constructor {} expects {} arguments, but received {}",
+                        "[Runtime Error] '{}' expected {} arguments, found {}.",
                         constructor_name,
                         vc.types.len(),
                         args.len()
@@ -279,79 +403,237 @@ fn adtconstructor_eval(
 
             let evaluated_args: Result<Vec<Box<Expression>>, (String, Option<Expression>)> = args
                 .into_iter()
-                .map(|arg| {
-                    eval(*arg, env).and_then(|res| match res {
-                        EnvValue::Exp(e) => Ok(Box::new(e)),
-                        _ => Err((
-                            String::from("Error: Expected expression in ADT constructor arguments"),
-                            None,
-                        )),
-                    })
-                })
+                .map(|arg| eval(*arg, env).and_then(into_expression).map(Box::new))
                 .collect();
 
-            evaluated_args.map(|evaluated| {
-                EnvValue::Exp(Expression::ADTConstructor(adt_name, constructor_name, evaluated))
+            evaluated_args.and_then(|evaluated| {
+                within_limits(
+                    EnvValue::Exp(Expression::ADTValue(AdtValue::new(adt_name, constructor_name, tag, evaluated.into()))),
+                    env,
+                )
             })
         } else {
             Err((
                 format!(
-                    "Error: Constructor {} not found in ADT {}",
+                    "[Runtime Error] ADT constructor '{}' not found in ADT '{}'.",
                     constructor_name, adt_name
                 ),
                 None,
             ))
         }
     } else {
-        Err((format!("Error: ADT {} not found", adt_name), None))
+        Err((format!("[Runtime Error] ADT '{}' is not defined.", adt_name), None))
     }
 }
 
-
-
+/// Whether `value` matches `pattern`, collecting in `bindings` the parts
+/// of it that the variables of the pattern stand for.
 fn matches_pattern(
     value: &EnvValue,
-    pattern: &Expression,
+    pattern: &Pattern,
     env: &Environment<EnvValue>,
-) -> Result<bool, ErrorMessage> {
-    match (value, pattern) {
-        // Caso o padrão seja um construtor de ADT
+    bindings: &mut Vec<(Name, EnvValue)>,
+) -> bool {
+    let all = |patterns: &[Pattern], values: Vec<&Expression>, bindings: &mut Vec<(Name, EnvValue)>| {
+        patterns.len() == values.len()
+            && patterns
+                .iter()
+                .zip(values)
+                .all(|(pattern, value)| matches_pattern(&EnvValue::Exp(value.clone()), pattern, env, bindings))
+    };
+
+    match (pattern, value) {
+        (Pattern::Wildcard, _) => true,
+        (Pattern::Variable(name), value) => {
+            bindings.push((name.clone(), value.clone()));
+            true
+        }
+        (Pattern::Literal(literal), EnvValue::Exp(exp)) => exp == literal,
+        (Pattern::Tagged(adt_name, _, tag, args), EnvValue::Exp(Expression::ADTValue(value))) => {
+            value.tag == *tag
+                && *adt_name == value.adt
+                && all(args, value.fields.iter().map(|field| &**field).collect(), bindings)
+        }
+        // Only in programs that did not go through the checker
         (
-            EnvValue::Exp(Expression::ADTConstructor(adt_name1, constructor_name1, args1)),
-            Expression::ADTConstructor(adt_name2, constructor_name2, args2),
+            Pattern::Constructor(adt_name, constructor_name, args),
+            EnvValue::Exp(Expression::ADTValue(value)),
         ) => {
-            // Verifica se o nome do ADT e o construtor correspondem
-            if adt_name1 == adt_name2 && constructor_name1 == constructor_name2 {
-                // Verifica se os argumentos correspondem
-                for (arg1, arg2) in args1.iter().zip(args2.iter()) {
-                    let arg_value = eval(*arg1.clone(), env)?;
-                    if !matches_pattern(&arg_value, arg2, env)? {
-                        return Ok(false);
-                    }
-                }
-                Ok(true)
-            } else {
-                Ok(false)
+            *adt_name == value.adt
+                && env.constructor_tag(adt_name, constructor_name) == Some(value.tag)
+                && all(args, value.fields.iter().map(|field| &**field).collect(), bindings)
+        }
+        (Pattern::List(items), EnvValue::Exp(Expression::CList(values)))
+        | (Pattern::Tuple(items), EnvValue::Exp(Expression::CTuple(values))) => {
+            all(items, values.iter().collect(), bindings)
+        }
+        (Pattern::ListRest(items, rest), EnvValue::Exp(Expression::CList(values))) if values.len() >= items.len() => {
+            let (first, others) = values.split_at(items.len());
+            let matched = all(items, first.iter().collect(), bindings);
+            if let (true, Some(name)) = (matched, rest) {
+                bindings.push((name.clone(), EnvValue::Exp(Expression::CList(others.to_vec()))));
+            }
+            matched
+        }
+        (Pattern::As(pattern, whole), value) => {
+            let matched = matches_pattern(value, pattern, env, bindings);
+            if matched {
+                bindings.push((whole.clone(), value.clone()));
             }
+            matched
+        }
+        (Pattern::StartsWith(prefix), EnvValue::Exp(Expression::CString(value))) => value.starts_with(prefix),
+        (Pattern::EndsWith(suffix), EnvValue::Exp(Expression::CString(value))) => value.ends_with(suffix),
+        _ => false,
+    }
+}
+
+/// Runs the first arm of a `match` whose pattern matches the value of
+/// `exp` and whose guard holds. Kept out of `execute_statement`, whose
+/// frame every nested call pays for.
+fn execute_match(
+    exp: Expression,
+    cases: Vec<(Pattern, Option<Expression>, Box<Statement>)>,
+    env: &Environment<EnvValue>,
+) -> Result<ControlFlow, ErrorMessage> {
+    let value = eval(exp, env)?;
+
+    for (pattern, guard, stmt) in cases {
+        let mut bindings = vec![];
+        if matches_pattern(&value, &pattern, env, &mut bindings) && guard_holds(guard, &bindings, env)? {
+            return execute_arm(&pattern, bindings, *stmt, env);
         }
+    }
+
+    Err((
+        format!(
+            "{} no pattern matches {} of type '{}'.",
+            MATCH_FAILURE,
+            render(&value),
+            type_name(&value)
+        ),
+        None,
+    ))
+}
+
+/// Whether the guard of an arm whose pattern matched holds, with the
+/// variables of the pattern bound to `bindings`. An arm without a guard
+/// always runs.
+fn guard_holds(
+    guard: Option<Expression>,
+    bindings: &[(Name, EnvValue)],
+    env: &Environment<EnvValue>,
+) -> Result<bool, ErrorMessage> {
+    let Some(guard) = guard else {
+        return Ok(true);
+    };
+    let mut guard_env = env.clone();
+    for (name, value) in bindings {
+        guard_env.insert_variable(name.clone(), value.clone());
+    }
+    match eval(guard, &guard_env)? {
+        EnvValue::Exp(Expression::CTrue) => Ok(true),
+        EnvValue::Exp(Expression::CFalse) => Ok(false),
+        _ => Err(("Guard must evaluate to a boolean".to_string(), None)),
+    }
+}
 
-        // Caso o padrão seja uma constante (como um número ou booleano)
-        (EnvValue::Exp(exp1), exp2) if is_constant(exp2.clone()) => Ok(exp1 == exp2),
+/// Runs the arm of a `match` whose `pattern` matched, with the variables
+/// of the pattern bound to `bindings`. Like a block, the arm keeps them
+/// and the names it binds to itself.
+fn execute_arm(
+    pattern: &Pattern,
+    bindings: Vec<(Name, EnvValue)>,
+    stmt: Statement,
+    env: &Environment<EnvValue>,
+) -> Result<ControlFlow, ErrorMessage> {
+    let guard = BlockGuard::enter(env);
+    let mut arm_env = env.clone();
+    for (name, value) in bindings {
+        let span = arm_env.observer.is_set().then(|| pretty::pattern(pattern));
+        bind(&mut arm_env, name, value, span, false);
+    }
+    match execute(stmt, &arm_env)? {
+        ControlFlow::Continue(new_env) => Ok(ControlFlow::Continue(guard.leave(new_env))),
+        ControlFlow::Return(value) => Ok(ControlFlow::Return(value)),
+    }
+}
 
-        // Outros casos podem ser adicionados aqui (como variáveis, etc.)
-        _ => Err(("Pattern not supported".to_string(), None)),
+/// Binds the variables of `pattern` to the parts of the value of `exp`
+/// that they match, in the current frame, as assignments would.
+fn define_constant(name: Name, kind: Type, exp: Expression, env: &mut Environment<EnvValue>) -> Result<(), ErrorMessage> {
+    let span = env
+        .observer
+        .is_set()
+        .then(|| pretty::statement(&Statement::ConstDeclaration(name.clone(), kind, Box::new(exp.clone()))));
+    let value = eval(exp, env)?;
+    bind(env, name.clone(), value, span, false);
+    env.insert_constant(name);
+    Ok(())
+}
 
+fn destructure(pattern: Pattern, exp: Expression, env: &mut Environment<EnvValue>) -> Result<(), ErrorMessage> {
+    let span = env
+        .observer
+        .is_set()
+        .then(|| pretty::statement(&Statement::Destructure(pattern.clone(), Box::new(exp.clone()))));
+    let value = eval(exp, env)?;
+    let mut bindings = vec![];
+    if !matches_pattern(&value, &pattern, env, &mut bindings) {
+        return Err((
+            format!(
+                "{} the pattern '{}' does not match {} of type '{}'.",
+                MATCH_FAILURE,
+                pretty::pattern(&pattern),
+                render(&value),
+                type_name(&value)
+            ),
+            None,
+        ));
+    }
+    for (name, value) in bindings {
+        bind(env, name, value, span.clone(), false);
     }
+    check_bindings(env)
 }
 
+/// Remembers the frame and the names bound in it when a block is entered.
+///
+/// Leaving the block through the guard pops any frame pushed inside it and
+/// drops the temporaries, i.e. the names first bound inside the block.
+/// Assignments to names that already existed are kept. Early returns and
+/// errors never hand the block's environment back to the caller, so they
+/// cannot leak frames or temporaries either.
+struct BlockGuard {
+    scope_key: (Name, i32),
+    bound: Mark,
+}
 
+impl BlockGuard {
+    fn enter(env: &Environment<EnvValue>) -> BlockGuard {
+        BlockGuard {
+            scope_key: env.scope_key(),
+            bound: env.get_frame(env.scope_key()).variables.mark(),
+        }
+    }
 
+    fn leave(self, mut env: Environment<EnvValue>) -> Environment<EnvValue> {
+        while env.recursion > self.scope_key.1 {
+            env.remove_frame();
+        }
+        if let Some(frame) = env.stack.get_mut(&self.scope_key) {
+            frame.variables.unbind_since(&self.bound);
+        }
+        env
+    }
+}
 
 //helper function for executing blocks
 fn execute_block(
     stmts: Vec<Statement>,
     env: &Environment<EnvValue>,
 ) -> Result<ControlFlow, ErrorMessage> {
+    let guard = BlockGuard::enter(env);
     let mut current_env = env.clone();
 
     for stmt in stmts {
@@ -360,131 +642,712 @@ fn execute_block(
             ControlFlow::Return(value) => return Ok(ControlFlow::Return(value)),
         }
     }
-    Ok(ControlFlow::Continue(current_env))
+    Ok(ControlFlow::Continue(guard.leave(current_env)))
 }
 
-fn call(
-    name: Name,
-    args: Vec<Expression>,
+/// Checks a value built by the program against the limits, and counts it.
+pub fn within_limits(value: EnvValue, env: &Environment<EnvValue>) -> Result<EnvValue, ErrorMessage> {
+    check_limits(&value, &env.limits).map_err(|e| (e, None))?;
+    // Scalars live inline in their `EnvValue`, so there is nothing to
+    // intern or share: building `True`, `Nothing` or `7` allocates nothing
+    let scalar = matches!(
+        value,
+        EnvValue::Exp(Expression::CTrue | Expression::CFalse | Expression::CInt(_) | Expression::CReal(_) | Expression::CDecimal(_) | Expression::CComplex(_) | Expression::CVoid | Expression::CNothing)
+    );
+    if !scalar {
+        env.counters.allocate();
+    }
+    Ok(value)
+}
+
+/// An assignment as written, when an observer wants to know. Kept out of
+/// `execute_statement`, whose frame every nested call pays for.
+fn assignment_span(env: &Environment<EnvValue>, name: &Name, exp: &Expression, kind: Option<Type>) -> Option<String> {
+    env.observer.is_set().then(|| {
+        pretty::statement(&Statement::Assignment(name.clone(), Box::new(exp.clone()), kind))
+    })
+}
+
+/// Binds `name` in the current frame, and tells the observer when `span`
+/// says where, which callers only work out when there is an observer. A
+/// watchpoint the binding trips pauses it right after.
+fn bind(env: &mut Environment<EnvValue>, name: Name, value: EnvValue, span: Option<String>, parameter: bool) {
+    match span {
+        Some(span) => {
+            let old = env.search_frame(name.clone()).cloned();
+            env.insert_variable(name.clone(), value.clone());
+            let change = Change {
+                name,
+                old,
+                new: value,
+                span,
+                scope: env.scope_name(),
+                depth: env.calls,
+                parameter,
+            };
+            env.observer.notify(&change);
+            debugger::watch(&change, env);
+        }
+        None => env.insert_variable(name, value),
+    }
+}
+
+fn check_deadline(env: &Environment<EnvValue>) -> Result<(), ErrorMessage> {
+    if let Some(deadline) = env.limits.deadline {
+        if Instant::now() >= deadline {
+            return Err((
+                format!("{} the program did not finish before its deadline.", TIMEOUT),
+                None,
+            ));
+        }
+    }
+    if let Some(max_steps) = env.limits.max_steps {
+        if env.counters.snapshot().0 > max_steps {
+            return Err((
+                format!("{} the program took more than {} steps.", RESOURCE_EXHAUSTED, max_steps),
+                None,
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Checks the invariants of a loop on entry (`iteration` 0) and after each
+/// iteration. A failure lists the values of the names the invariant reads.
+fn check_invariants(
+    invariants: &[Expression],
+    cond: &Expression,
+    iteration: usize,
     env: &Environment<EnvValue>,
-) -> Result<EnvValue, ErrorMessage> {
-    // Use search_frame instead of get
-    match env.search_frame(name.clone()) {
-        Some(EnvValue::Func(func)) => {
-            let mut new_env = Environment::new();
+) -> Result<(), ErrorMessage> {
+    for invariant in invariants {
+        let value = eval(invariant.clone(), env)?;
+        if value == EnvValue::Exp(Expression::CTrue) {
+            continue;
+        }
 
-            // Copy global functions
-            let mut curr_scope = env.scope_key();
-            loop {
-                let frame = env.get_frame(curr_scope.clone());
-                for (name, value) in &frame.variables {
-                    if let EnvValue::Func(_) = value {
-                        new_env.insert_variable(name.clone(), value.clone());
-                    }
-                }
-                match &frame.parent_key {
-                    Some(parent) => curr_scope = parent.clone(),
-                    None => break,
-                }
+        let mut names = BTreeSet::new();
+        free_variables_exp(invariant, &mut names);
+        let bindings: Vec<String> = names
+            .iter()
+            .filter_map(|name| match search_scopes(name, env) {
+                Some(value @ EnvValue::Exp(_)) => Some(format!("{} = {}", name, render(value))),
+                _ => None,
+            })
+            .collect();
+        let when = match iteration {
+            0 => String::from("on entry"),
+            n => format!("after iteration {}", n),
+        };
+
+        return Err((
+            format!(
+                "{} '{}' of the loop 'while {}' does not hold {}: it evaluated to {} with {}.",
+                INVARIANT_VIOLATED,
+                pretty::expression(invariant),
+                pretty::expression(cond),
+                when,
+                render(&value),
+                bindings.join(", ")
+            ),
+            None,
+        ));
+    }
+    Ok(())
+}
+
+fn check_bindings(env: &Environment<EnvValue>) -> Result<(), ErrorMessage> {
+    let bindings: usize = env.stack.values().map(|frame| frame.variables.len()).sum();
+    if bindings > env.limits.max_bindings {
+        return Err((
+            format!(
+                "{} {} variables are bound, more than the limit of {}.",
+                RESOURCE_EXHAUSTED, bindings, env.limits.max_bindings
+            ),
+            None,
+        ));
+    }
+    Ok(())
+}
+
+/// Closures capture by value: a function sees the bindings of its free
+/// variables as they were when its definition was executed. A function
+/// defined inside a loop keeps the values of the iteration that defined it,
+/// even when the loop variable changes later in that same iteration, so
+/// there is no late binding as with Python's lambdas in a loop. Loops that
+/// bind a fresh variable per iteration get the same semantics, since every
+/// iteration runs as a block.
+fn capture(
+    func: &Function,
+    env: &Environment<EnvValue>,
+) -> Result<Vec<(Name, Expression)>, ErrorMessage> {
+    let mut names = BTreeSet::new();
+    if let Some(body) = &func.body {
+        free_variables_stmt(body, &mut names);
+    }
+    names.remove(&func.name);
+    for (param, _) in func.params.iter().flatten() {
+        names.remove(param);
+    }
+
+    let mut names: Vec<Name> = names.into_iter().collect();
+    names.sort();
+
+    let mut captured = vec![];
+    for name in names {
+        if let Some(value) = search_scopes(&name, env) {
+            if let EnvValue::Exp(_) | EnvValue::Func(_) = value {
+                captured.push((name, into_expression(value.clone())?));
             }
+        }
+    }
+    Ok(captured)
+}
 
-            // Bind arguments
-            if let Some(params) = &func.params {
-                for (param, arg) in params.iter().zip(args) {
-                    let arg_value = eval(arg, env)?;
-                    new_env.insert_variable(param.0.clone(), arg_value);
-                }
+fn search_scopes<'a>(name: &Name, env: &'a Environment<EnvValue>) -> Option<&'a EnvValue> {
+    search_frames(name, env).or_else(|| env.globals.get(name))
+}
+
+fn search_frames<'a>(name: &Name, env: &'a Environment<EnvValue>) -> Option<&'a EnvValue> {
+    let mut curr_scope = env.scope_key();
+    loop {
+        let frame = env.get_frame(curr_scope);
+        if let Some(value) = frame.variables.get(name) {
+            return Some(value);
+        }
+        curr_scope = frame.parent_key.clone()?;
+    }
+}
+
+/// The functions a call made from `env` sees: the globals of `env`, under
+/// the function values bound in its frames. Those are usually none, and
+/// the call shares the snapshot of its caller.
+fn visible_functions(env: &Environment<EnvValue>) -> std::sync::Arc<Snapshot<EnvValue>> {
+    let mut bound = BTreeMap::new();
+    let mut curr_scope = Some(env.scope_key());
+    while let Some(key) = curr_scope {
+        let frame = env.get_frame(key);
+        for (name, value) in &frame.variables {
+            if let EnvValue::Func(_) | EnvValue::Exp(Expression::Closure(..)) = value {
+                bound.entry(name.clone()).or_insert_with(|| value.clone());
             }
+        }
+        curr_scope = frame.parent_key.clone();
+    }
+    Snapshot::with(&env.globals, bound)
+}
 
-            // Execute function
-            match execute(*func.body.as_ref().unwrap().clone(), &new_env)? {
-                ControlFlow::Return(value) => Ok(value),
-                ControlFlow::Continue(_) => {
-                    Err(("Function did not return a value".to_string(), None))
+// Every name read by a statement; over-approximating with locals is fine,
+// they are simply rebound when the body runs.
+fn free_variables_stmt(stmt: &Statement, names: &mut BTreeSet<Name>) {
+    match stmt {
+        Statement::Assignment(_, exp, _)
+        | Statement::ConstDeclaration(_, _, exp)
+        | Statement::Destructure(_, exp)
+        | Statement::Return(exp)
+        | Statement::Call(exp)
+        | Statement::Requires(exp)
+        | Statement::Ensures(exp)
+        | Statement::Invariant(exp)
+        | Statement::Decreases(exp) => free_variables_exp(exp, names),
+        Statement::IfThenElse(cond, then_stmt, else_stmt) => {
+            free_variables_exp(cond, names);
+            free_variables_stmt(then_stmt, names);
+            if let Some(else_stmt) = else_stmt {
+                free_variables_stmt(else_stmt, names);
+            }
+        }
+        Statement::While(cond, body) => {
+            free_variables_exp(cond, names);
+            free_variables_stmt(body, names);
+        }
+        Statement::Block(stmts) => stmts.iter().for_each(|s| free_variables_stmt(s, names)),
+        Statement::Sequence(s1, s2) => {
+            free_variables_stmt(s1, names);
+            free_variables_stmt(s2, names);
+        }
+        Statement::AssertTrue(exp, _) | Statement::AssertFalse(exp, _) => {
+            free_variables_exp(exp, names)
+        }
+        Statement::AssertEQ(lhs, rhs, _) | Statement::AssertNEQ(lhs, rhs, _) => {
+            free_variables_exp(lhs, names);
+            free_variables_exp(rhs, names);
+        }
+        Statement::FuncDef(func) => {
+            if let Some(body) = &func.body {
+                free_variables_stmt(body, names);
+            }
+        }
+        Statement::Match(exp, cases) => {
+            free_variables_exp(exp, names);
+            for (_, guard, stmt) in cases {
+                if let Some(guard) = guard {
+                    free_variables_exp(guard, names);
                 }
+                free_variables_stmt(stmt, names);
             }
         }
-        _ => Err((format!("Function {} not found", name), None)),
+        _ => {}
     }
 }
 
-/* Error propagation functions:
-    -> extract_error_value
-    -> propagate_error
-*/
-fn extract_error_value(
-    exp: Expression,
-    env: &Environment<EnvValue>,
-) -> Result<String, ErrorMessage> {
-    // Gets expression and returns the value inside (works with constants and Error types)
+fn free_variables_exp(exp: &Expression, names: &mut BTreeSet<Name>) {
     match exp {
-        Expression::COk(e) => extract_error_value(*e, env),
-        Expression::CErr(e) => extract_error_value(*e, env),
-        Expression::CJust(e) => extract_error_value(*e, env),
-        Expression::CTrue => Ok("True".to_string()),
-        Expression::CFalse => Ok("False".to_string()),
-        Expression::CInt(value) => Ok(value.to_string()),
-        Expression::CReal(value) => Ok(value.to_string()),
-        Expression::CString(value) => Ok(value.to_string()),
-        Expression::CNothing => Ok("Nothing".to_string()),
-        _ => Err((String::from("Nothing to extract from."), None)),
+        Expression::Var(name) | Expression::Slot(_, name) => {
+            names.insert(name.clone());
+        }
+        Expression::FuncCall(name, args) | Expression::CachedCall(CallSite { name, .. }, args) => {
+            names.insert(name.clone());
+            args.iter().for_each(|arg| free_variables_exp(arg, names));
+        }
+        Expression::Add(l, r)
+        | Expression::Sub(l, r)
+        | Expression::Mul(l, r)
+        | Expression::Div(l, r)
+        | Expression::And(l, r)
+        | Expression::Or(l, r)
+        | Expression::EQ(l, r)
+        | Expression::GT(l, r)
+        | Expression::LT(l, r)
+        | Expression::GTE(l, r)
+        | Expression::LTE(l, r) => {
+            free_variables_exp(l, names);
+            free_variables_exp(r, names);
+        }
+        Expression::Not(e)
+        | Expression::COk(e)
+        | Expression::CErr(e)
+        | Expression::CJust(e)
+        | Expression::Unwrap(e)
+        | Expression::IsError(e)
+        | Expression::IsNothing(e)
+        | Expression::Propagate(e) => free_variables_exp(e, names),
+        Expression::CList(items) | Expression::CTuple(items) => {
+            items.iter().for_each(|item| free_variables_exp(item, names))
+        }
+        Expression::CDict(entries) => entries.iter().for_each(|(k, v)| {
+            free_variables_exp(k, names);
+            free_variables_exp(v, names);
+        }),
+        Expression::ADTConstructor(_, _, args) => {
+            args.iter().for_each(|arg| free_variables_exp(arg, names))
+        }
+        _ => {}
     }
 }
 
-fn propagate_error(
-    exp: Expression,
-    env: &Environment<EnvValue>,
-) -> Result<ControlFlow, ErrorMessage> {
-    // Checks error value and propagates it (terminates code if on highest level function)
-    if env.scope_key().1 == 0 {
-        match eval(exp, &env) {
-            Ok(EnvValue::Exp(new_value)) => match extract_error_value(new_value, &env) {
-                Ok(s) => Err((
-                    String::from(format!("Program terminated with errors: {}", s)),
-                    None,
-                )),
-                _ => Err(("Program terminated with errors".to_string(), None)),
-            },
-            _ => Err((
-                "Program panicked and trying to terminate with errors".to_string(),
-                None,
-            )),
-        }
-    } else {
-        return Ok(ControlFlow::Return(EnvValue::Exp(Expression::CErr(
-            Box::new(exp),
-        ))));
+/// Function values become closures when stored inside other values.
+fn into_expression(value: EnvValue) -> Result<Expression, ErrorMessage> {
+    match value {
+        EnvValue::Exp(e) => Ok(e),
+        EnvValue::Func(func) => Ok(Expression::Closure(Box::new(func), vec![])),
+        _ => Err(("Expression not recognized.".to_string(), None)),
     }
 }
 
-fn execute_tests(
-    tests_set: Vec<(String, Option<String>)>,
+fn call(
+    name: Name,
+    args: Vec<Expression>,
     env: &Environment<EnvValue>,
-) -> Result<HashSet<(String, String, Option<String>)>, String> {
-    let mut results = HashSet::new();
-    let cur_scope = env.scope_key();
-    let frame: crate::ir::ast::Frame<EnvValue> = env.get_frame(cur_scope.clone()).clone();
+) -> Result<EnvValue, ErrorMessage> {
+    // The callee is borrowed from the scope it is bound in, not copied:
+    // calls never change the bindings of their caller
+    let callee = match search_scopes(&name, env) {
+        Some(EnvValue::Func(func)) => Some((func, &[][..])),
+        Some(EnvValue::Exp(Expression::Closure(func, captured))) => Some((&**func, captured.as_slice())),
+        _ => None,
+    };
 
-    for (mod_test, test) in tests_set {
-        match frame.variables.get(&mod_test) {
-            Some(EnvValue::TestEnvironment(test_module)) => {
-                let mut test_env = test_module.env.clone();
-                let mod_test_scope = test_env.scope_key();
-                let test_frame = test_env.get_frame(mod_test_scope);
+    let mut arg_values = vec![];
+    for arg in args {
+        arg_values.push(eval(arg, env)?);
+    }
 
-                if test != None {
-                    test_env = match run(
-                        Statement::FuncDef(
-                            match test_frame.clone().tests.get(&test.clone().unwrap()) {
-                                Some(real_test) => real_test.clone(),
-                                None => {
-                                    return Err(format!(
-                                        "{teste} is not a test",
-                                        teste = &test.clone().unwrap()
-                                    ))
-                                }
-                            },
-                        ),
+    match callee {
+        Some((func, captured)) => invoke(func, captured, arg_values, env),
+        _ => match registry::lookup(&name) {
+            Some(builtin) => {
+                let value = (builtin.eval)(arg_values, env).map_err(|e| (e, None))?;
+                within_limits(value, env)
+            }
+            None => Err((format!("Function {} not found", name), None)),
+        },
+    }
+}
+
+/// A call through `site`, which skips looking the name up among the global
+/// functions once the site has cached the callee for the snapshot of
+/// `env`. The frames are still searched first, since their bindings shadow
+/// the globals.
+fn cached_call(site: CallSite, args: Vec<Expression>, env: &Environment<EnvValue>) -> Result<EnvValue, ErrorMessage> {
+    if search_frames(&site.name, env).is_some() {
+        return call(site.name, args, env);
+    }
+    let callee = site.resolve(env.globals.version(), || match env.globals.get(&site.name)? {
+        EnvValue::Func(func) => Some((func.clone(), vec![])),
+        EnvValue::Exp(Expression::Closure(func, captured)) => Some(((**func).clone(), captured.clone())),
+        _ => None,
+    });
+    let Some(callee) = callee else {
+        return call(site.name, args, env);
+    };
+
+    let mut arg_values = vec![];
+    for arg in args {
+        arg_values.push(eval(arg, env)?);
+    }
+    let (func, captured) = callee.as_ref();
+    invoke(func, captured, arg_values, env)
+}
+
+/// Runs `func` on already evaluated arguments, in a fresh environment that
+/// only sees the global functions, the declarations and `captured`.
+pub fn invoke(
+    func: &Function,
+    captured: &[(Name, Expression)],
+    args: Vec<EnvValue>,
+    env: &Environment<EnvValue>,
+) -> Result<EnvValue, ErrorMessage> {
+    check_deadline(env)?;
+    if env.calls as usize >= env.limits.max_calls {
+        return Err((
+            format!(
+                "{} more than {} calls are active; is the recursion missing a base case?",
+                RESOURCE_EXHAUSTED, env.limits.max_calls
+            ),
+            None,
+        ));
+    }
+
+    // The call as an observer sees it, as `inc(2)`
+    let span = env.observer.is_set().then(|| {
+        let args: Vec<String> = args.iter().map(render).collect();
+        format!("{}({})", func.name, args.join(", "))
+    });
+
+    let mut new_env = pooled_environment();
+    new_env.calls = env.calls + 1;
+    new_env.capabilities = env.capabilities.clone();
+    new_env.type_env = env.type_env.clone();
+    new_env.instances = env.instances.clone();
+    new_env.limits = env.limits.clone();
+    new_env.check_contracts = env.check_contracts;
+    new_env.check_invariants = env.check_invariants;
+    new_env.measures.clone_from(&env.measures);
+    new_env.io = env.io.clone();
+    new_env.counters = env.counters.clone();
+    new_env.observer = env.observer.clone();
+    new_env.globals = visible_functions(env);
+
+    // The body runs in its own frame, so a propagated error returns from
+    // the function instead of terminating the program. The frame does not
+    // keep a copy of the body, which can be large
+    new_env.insert_frame(func.signature());
+
+    let result = run_call(func, captured, args, span, env, &mut new_env);
+    recycle(new_env);
+    result
+}
+
+thread_local! {
+    // The environments of finished calls, emptied, which later calls run
+    // in instead of allocating their own. Nested calls take one each, so
+    // the pool holds about as many as the deepest recursion so far.
+    static POOL: RefCell<Vec<Environment<EnvValue>>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Past this many, the environments of finished calls are dropped.
+const POOLED: usize = 256;
+
+/// An empty environment, with only the main frame.
+fn pooled_environment() -> Environment<EnvValue> {
+    POOL.with(|pool| pool.borrow_mut().pop()).unwrap_or_else(Environment::new)
+}
+
+/// Empties the environment of a finished call and puts it in the pool,
+/// keeping its main frame, its main scope and what its maps allocated.
+fn recycle(mut env: Environment<EnvValue>) {
+    while env.recursion > 0 {
+        env.remove_frame();
+    }
+    if let Some(frame) = env.stack.get_mut(&env.scope_key()) {
+        frame.variables.clear();
+        frame.tests.clear();
+        frame.origins.clear();
+        frame.constants.clear();
+    }
+    env.measures.clear();
+    env.observer = Observer::default();
+    POOL.with(|pool| {
+        let mut pool = pool.borrow_mut();
+        if pool.len() < POOLED {
+            pool.push(env);
+        }
+    });
+}
+
+/// Binds the arguments of a call in `new_env`, whose frame `invoke` made,
+/// and runs the body there.
+fn run_call(
+    func: &Function,
+    captured: &[(Name, Expression)],
+    args: Vec<EnvValue>,
+    span: Option<String>,
+    env: &Environment<EnvValue>,
+    new_env: &mut Environment<EnvValue>,
+) -> Result<EnvValue, ErrorMessage> {
+    // The locals the checker numbered live in slots of the call's frame
+    let (annotations, body) = func.contracts();
+    if let Some(layout) = &annotations.locals {
+        new_env.number_locals(layout.clone());
+    }
+
+    // Bind captured values, then the closure itself for recursion
+    for (name, value) in captured.iter() {
+        new_env.insert_variable(name.clone(), EnvValue::Exp(value.clone()));
+    }
+    if !captured.is_empty() {
+        new_env.insert_variable(
+            func.name.clone(),
+            EnvValue::Exp(Expression::Closure(Box::new(func.clone()), captured.to_vec())),
+        );
+    }
+
+    // Bind arguments, resolving the instances required by type variables
+    if let Some(params) = &func.params {
+        for ((param, kind), arg_value) in params.iter().zip(args) {
+            if let Type::TVar(_, classes) = kind {
+                for class in classes {
+                    if !has_runtime_instance(class, &arg_value, env) {
+                        return Err((
+                            format!(
+                                "[Runtime Error] '{}()' requires an instance of '{}' for '{}'.",
+                                func.name,
+                                class,
+                                type_name(&arg_value)
+                            ),
+                            None,
+                        ));
+                    }
+                }
+            }
+            bind(new_env, param.clone(), arg_value, span.clone(), true);
+        }
+    }
+    debugger::call(func, new_env);
+
+    if new_env.check_contracts {
+        for clause in &annotations.requires {
+            check_contract(func, "precondition", clause, new_env)?;
+        }
+    }
+
+    // A recursive call must have a smaller measure than the call it comes
+    // from, however many other calls are in between
+    if let Some(clause) = annotations.decreases.first() {
+        let current = measure(clause, &format!("'{}()'", func.name), new_env)?;
+        let caller = env.measures.iter().rev().find(|(name, _)| *name == func.name);
+        if let Some((_, caller)) = caller.filter(|(_, caller)| current >= *caller) {
+            return Err((
+                format!(
+                    "{} the measure '{}' of '{}()' did not decrease in a recursive call: it went from {} to {}.",
+                    NON_TERMINATION,
+                    pretty::expression(clause),
+                    func.name,
+                    caller,
+                    current
+                ),
+                None,
+            ));
+        }
+        new_env.measures.push((func.name.clone(), current));
+    }
+
+    // Execute function
+    let result = match execute(body, new_env)? {
+        ControlFlow::Return(value) => value,
+        ControlFlow::Continue(_) => match &func.kind {
+            Some(Type::TVoid) => EnvValue::Exp(Expression::CVoid),
+            kind => {
+                return Err((
+                    format!(
+                        "[Runtime Error] '{}()' reached the end of its body without returning a '{:?}' value.",
+                        func.name,
+                        kind.clone().unwrap_or(Type::TAny)
+                    ),
+                    None,
+                ))
+            }
+        },
+    };
+
+    // Postconditions see the arguments as they were passed
+    if new_env.check_contracts && !annotations.ensures.is_empty() {
+        new_env.insert_variable(String::from("result"), result.clone());
+        for clause in &annotations.ensures {
+            check_contract(func, "postcondition", clause, new_env)?;
+        }
+    }
+    Ok(result)
+}
+
+/// Evaluates a `decreases` measure of `what`, which must be a non-negative
+/// integer.
+fn measure(clause: &Expression, what: &str, env: &Environment<EnvValue>) -> Result<i32, ErrorMessage> {
+    match eval(clause.clone(), env)? {
+        EnvValue::Exp(Expression::CInt(value)) if value >= 0 => Ok(value),
+        EnvValue::Exp(Expression::CInt(value)) => Err((
+            format!(
+                "{} the measure '{}' of {} is negative: {}.",
+                NON_TERMINATION,
+                pretty::expression(clause),
+                what,
+                value
+            ),
+            None,
+        )),
+        value => Err((
+            format!(
+                "[Runtime Error] the measure '{}' of {} must be an integer, found {}.",
+                pretty::expression(clause),
+                what,
+                render(&value)
+            ),
+            None,
+        )),
+    }
+}
+
+fn check_contract(
+    func: &Function,
+    kind: &str,
+    clause: &Expression,
+    env: &Environment<EnvValue>,
+) -> Result<(), ErrorMessage> {
+    match eval(clause.clone(), env)? {
+        EnvValue::Exp(Expression::CTrue) => Ok(()),
+        value => Err((
+            format!(
+                "{} {} '{}' of '{}()' does not hold: it evaluated to {}.",
+                CONTRACT_VIOLATION,
+                kind,
+                pretty::expression(clause),
+                func.name,
+                render(&value)
+            ),
+            None,
+        )),
+    }
+}
+
+fn has_runtime_instance(class: &str, value: &EnvValue, env: &Environment<EnvValue>) -> bool {
+    match value {
+        EnvValue::Exp(Expression::CInt(_)) => builtin_instance(class, &Type::TInteger),
+        EnvValue::Exp(Expression::CReal(_)) => builtin_instance(class, &Type::TReal),
+        EnvValue::Exp(Expression::CDecimal(_)) => builtin_instance(class, &Type::TDecimal),
+        EnvValue::Exp(Expression::CComplex(_)) => builtin_instance(class, &Type::TComplex),
+        EnvValue::Exp(Expression::CString(_)) => builtin_instance(class, &Type::TString),
+        EnvValue::Exp(Expression::CTrue | Expression::CFalse) => builtin_instance(class, &Type::TBool),
+        EnvValue::Exp(Expression::ADTValue(value)) => class == "Eq" || env.has_instance(class, &value.adt),
+        _ => false,
+    }
+}
+
+/// Operators on ADT values use the methods of the ADT's instance, if it
+/// declares one; `None` leaves the operator to its default behavior.
+fn instance_method_call(
+    class: &str,
+    method: &str,
+    args: Vec<EnvValue>,
+    env: &Environment<EnvValue>,
+) -> Result<Option<EnvValue>, ErrorMessage> {
+    let adt_name = match &args[0] {
+        EnvValue::Exp(Expression::ADTValue(value)) => value.adt.clone(),
+        _ => return Ok(None),
+    };
+    match env.instance_method(class, &adt_name, method) {
+        Some(func) => invoke(func, &[], args, env).map(Some),
+        None => Ok(None),
+    }
+}
+
+/* Error propagation functions:
+    -> extract_error_value
+    -> propagate_error
+*/
+fn extract_error_value(
+    exp: Expression,
+    env: &Environment<EnvValue>,
+) -> Result<String, ErrorMessage> {
+    // Gets expression and returns the value inside (works with constants and Error types)
+    match exp {
+        Expression::COk(e) => extract_error_value(*e, env),
+        Expression::CErr(e) => extract_error_value(*e, env),
+        Expression::CJust(e) => extract_error_value(*e, env),
+        Expression::CTrue => Ok("True".to_string()),
+        Expression::CFalse => Ok("False".to_string()),
+        Expression::CInt(value) => Ok(value.to_string()),
+        Expression::CReal(value) => Ok(value.to_string()),
+        Expression::CDecimal(value) => Ok(value.to_string()),
+        Expression::CComplex(value) => Ok(value.to_string()),
+        Expression::CString(value) => Ok(value.to_string()),
+        Expression::CNothing => Ok("Nothing".to_string()),
+        _ => Err((String::from("Nothing to extract from."), None)),
+    }
+}
+
+fn propagate_error(
+    exp: Expression,
+    env: &Environment<EnvValue>,
+) -> Result<ControlFlow, ErrorMessage> {
+    // Checks error value and propagates it (terminates code if on highest level function)
+    if env.scope_key().1 == 0 {
+        match eval(exp, &env) {
+            Ok(EnvValue::Exp(new_value)) => match extract_error_value(new_value, &env) {
+                Ok(s) => Err((
+                    String::from(format!("Program terminated with errors: {}", s)),
+                    None,
+                )),
+                _ => Err(("Program terminated with errors".to_string(), None)),
+            },
+            _ => Err((
+                "Program panicked and trying to terminate with errors".to_string(),
+                None,
+            )),
+        }
+    } else {
+        return Ok(ControlFlow::Return(EnvValue::Exp(exp)));
+    }
+}
+
+fn execute_tests(
+    tests_set: Vec<(String, Option<String>)>,
+    env: &Environment<EnvValue>,
+) -> Result<HashSet<(String, String, Option<String>)>, String> {
+    let mut results = HashSet::new();
+    let cur_scope = env.scope_key();
+    let frame: crate::ir::ast::Frame<EnvValue> = env.get_frame(cur_scope.clone()).clone();
+
+    for (mod_test, test) in tests_set {
+        match frame.variables.get(&mod_test) {
+            Some(EnvValue::TestEnvironment(test_module)) => {
+                let mut test_env = test_module.env.clone();
+                let mod_test_scope = test_env.scope_key();
+                let test_frame = test_env.get_frame(mod_test_scope);
+
+                if test != None {
+                    test_env = match run(
+                        Statement::FuncDef(
+                            match test_frame.clone().tests.get(&test.clone().unwrap()) {
+                                Some(real_test) => real_test.clone(),
+                                None => {
+                                    return Err(format!(
+                                        "{teste} is not a test",
+                                        teste = &test.clone().unwrap()
+                                    ))
+                                }
+                            },
+                        ),
                         &test_env,
                     ) {
                         Ok(ControlFlow::Continue(new_env)) => new_env,
@@ -555,35 +1418,38 @@ fn is_constant(exp: Expression) -> bool {
         Expression::CVoid => true,
         Expression::CInt(_) => true,
         Expression::CReal(_) => true,
+        Expression::CDecimal(_) => true,
+        Expression::CComplex(_) => true,
         Expression::CString(_) => true,
+        Expression::CBytes(_) => true,
         Expression::CNothing => true,
         _ => false,
     }
 }
 
 fn lookup(name: String, env: &Environment<EnvValue>) -> Result<EnvValue, ErrorMessage> {
-    let mut curr_scope = env.scope_key();
-
-    loop {
-        let frame = env.get_frame(curr_scope.clone());
-
-        match frame.variables.get(&name) {
-            Some(value) => return Ok(value.clone()),
-            None => curr_scope = frame.parent_key.clone().unwrap(),
-        }
+    match search_scopes(&name, env) {
+        Some(value) => Ok(value.clone()),
+        None => Err((format!("[Name Error] '{}' is not defined.", name), None)),
     }
 }
 
 /* Arithmetic Operations */
-fn eval_binary_arith_op<F>(
+// Decimals and complex numbers have their own operations; integers, and
+// reals for complex numbers, are promoted when mixed with them.
+fn eval_binary_arith_op<F, D, C>(
     lhs: Expression,
     rhs: Expression,
     env: &Environment<EnvValue>,
     op: F,
+    decimal_op: D,
+    complex_op: C,
     error_msg: &str,
 ) -> Result<EnvValue, ErrorMessage>
 where
     F: Fn(f64, f64) -> f64,
+    D: Fn(Decimal, Decimal) -> Result<Decimal, String>,
+    C: Fn(Complex, Complex) -> Result<Complex, String>,
 {
     let v1 = eval(lhs, env)?;
     let v2 = eval(rhs, env)?;
@@ -601,20 +1467,75 @@ where
         (EnvValue::Exp(Expression::CReal(v1)), EnvValue::Exp(Expression::CReal(v2))) => {
             Ok(EnvValue::Exp(Expression::CReal(op(v1, v2))))
         }
+        (EnvValue::Exp(v1), EnvValue::Exp(v2)) => match (as_decimal(&v1), as_decimal(&v2)) {
+            (Some(d1), Some(d2)) => decimal_op(d1, d2)
+                .map(|d| EnvValue::Exp(Expression::CDecimal(d)))
+                .map_err(|e| (e, None)),
+            _ => match (as_complex(&v1), as_complex(&v2)) {
+                (Some(z1), Some(z2)) => complex_op(z1, z2)
+                    .map(|z| EnvValue::Exp(Expression::CComplex(z)))
+                    .map_err(|e| (e, None)),
+                _ => Err((error_msg.to_string(), None)),
+            },
+        },
         _ => Err((error_msg.to_string(), None)),
     }
 }
 
+// Decimals, and integers promoted to decimals when mixed with them.
+fn as_decimal(value: &Expression) -> Option<Decimal> {
+    match value {
+        Expression::CDecimal(d) => Some(*d),
+        Expression::CInt(n) => Some(Decimal::from(*n)),
+        _ => None,
+    }
+}
+
+// Complex numbers, and the integers and reals promoted to them.
+fn as_complex(value: &Expression) -> Option<Complex> {
+    match value {
+        Expression::CComplex(z) => Some(*z),
+        Expression::CReal(x) => Some(Complex::from(*x)),
+        Expression::CInt(n) => Some(Complex::from(*n as f64)),
+        _ => None,
+    }
+}
+
+fn decimal_overflow() -> String {
+    String::from("[Runtime Error] decimal overflow.")
+}
+
+// Evaluates both operands; ADT operands with a `Num` instance are handled
+// by it, anything else is handed back (evaluated) to the numeric operator.
+fn num_instance_call(
+    method: &str,
+    lhs: Expression,
+    rhs: Expression,
+    env: &Environment<EnvValue>,
+) -> Result<Result<EnvValue, (Expression, Expression)>, ErrorMessage> {
+    let (v1, v2) = (eval(lhs, env)?, eval(rhs, env)?);
+    match instance_method_call("Num", method, vec![v1.clone(), v2.clone()], env)? {
+        Some(result) => Ok(Ok(result)),
+        None => Ok(Err((into_expression(v1)?, into_expression(v2)?))),
+    }
+}
+
 fn add(
     lhs: Expression,
     rhs: Expression,
     env: &Environment<EnvValue>,
 ) -> Result<EnvValue, ErrorMessage> {
+    let (lhs, rhs) = match num_instance_call("add", lhs, rhs, env)? {
+        Ok(result) => return Ok(result),
+        Err(operands) => operands,
+    };
     eval_binary_arith_op(
         lhs,
         rhs,
         env,
         |a, b| a + b,
+        |a, b| a.checked_add(b).ok_or_else(decimal_overflow),
+        |a, b| Ok(a + b),
         "addition '(+)' is only defined for numbers (integers and real).",
     )
 }
@@ -624,11 +1545,17 @@ fn sub(
     rhs: Expression,
     env: &Environment<EnvValue>,
 ) -> Result<EnvValue, ErrorMessage> {
+    let (lhs, rhs) = match num_instance_call("sub", lhs, rhs, env)? {
+        Ok(result) => return Ok(result),
+        Err(operands) => operands,
+    };
     eval_binary_arith_op(
         lhs,
         rhs,
         env,
         |a, b| a - b,
+        |a, b| a.checked_sub(b).ok_or_else(decimal_overflow),
+        |a, b| Ok(a - b),
         "subtraction '(-)' is only defined for numbers (integers and real).",
     )
 }
@@ -638,11 +1565,17 @@ fn mul(
     rhs: Expression,
     env: &Environment<EnvValue>,
 ) -> Result<EnvValue, ErrorMessage> {
+    let (lhs, rhs) = match num_instance_call("mul", lhs, rhs, env)? {
+        Ok(result) => return Ok(result),
+        Err(operands) => operands,
+    };
     eval_binary_arith_op(
         lhs,
         rhs,
         env,
         |a, b| a * b,
+        |a, b| a.checked_mul(b).ok_or_else(decimal_overflow),
+        |a, b| Ok(a * b),
         "multiplication '(*)' is only defined for numbers (integers and real).",
     )
 }
@@ -652,11 +1585,29 @@ fn div(
     rhs: Expression,
     env: &Environment<EnvValue>,
 ) -> Result<EnvValue, ErrorMessage> {
+    let (lhs, rhs) = match num_instance_call("div", lhs, rhs, env)? {
+        Ok(result) => return Ok(result),
+        Err(operands) => operands,
+    };
     eval_binary_arith_op(
         lhs,
         rhs,
         env,
         |a, b| a / b,
+        |a, b| {
+            if b == Decimal::from(0) {
+                Err(String::from("[Runtime Error] decimal division by zero."))
+            } else {
+                a.checked_div(b).ok_or_else(decimal_overflow)
+            }
+        },
+        |a, b| {
+            if b.is_zero() {
+                Err(String::from("[Runtime Error] complex division by zero."))
+            } else {
+                Ok(a / b)
+            }
+        },
         "division '(/)' is only defined for numbers (integers and real).",
     )
 }
@@ -768,6 +1719,12 @@ where
         (EnvValue::Exp(Expression::CReal(v1)), EnvValue::Exp(Expression::CReal(v2))) => {
             Ok(EnvValue::Exp(op(v1, v2)))
         }
+        // Decimals are compared exactly, and their ordering (-1, 0 or 1)
+        // compared with 0 in its place
+        (EnvValue::Exp(v1), EnvValue::Exp(v2)) => match (as_decimal(&v1), as_decimal(&v2)) {
+            (Some(d1), Some(d2)) => Ok(EnvValue::Exp(op(d1.cmp(&d2) as i32 as f64, 0.0))),
+            _ => Err((error_msg.to_string(), None)),
+        },
         _ => Err((error_msg.to_string(), None)),
     }
 }
@@ -777,46 +1734,189 @@ fn eq(
     rhs: Expression,
     env: &Environment<EnvValue>,
 ) -> Result<EnvValue, ErrorMessage> {
-    eval_binary_rel_op(
-        lhs,
-        rhs,
-        env,
-        |a, b| {
-            if a == b {
-                Expression::CTrue
-            } else {
-                Expression::CFalse
+    let v1 = eval(lhs, env)?;
+    let v2 = eval(rhs, env)?;
+
+    match (v1, v2) {
+        (EnvValue::Exp(e1), EnvValue::Exp(e2)) => values_equal(e1, e2, env).map(|equal| EnvValue::Exp(bool_value(equal))),
+        (v1, v2) => Err((
+            format!("(==) cannot compare {} with {}.", render(&v1), render(&v2)),
+            None,
+        )),
+    }
+}
+
+// Values are equal when they are built the same way from equal parts:
+// numbers by value, whatever their type, strings, bytes and booleans
+// exactly, tuples and lists item by item, and dictionaries when they
+// have the same keys with equal values, in any order. ADTs with an `Eq`
+// instance are compared by its `eq` method.
+fn values_equal(e1: Expression, e2: Expression, env: &Environment<EnvValue>) -> Result<bool, ErrorMessage> {
+    let all = |items1: Vec<Expression>, items2: Vec<Expression>| -> Result<bool, ErrorMessage> {
+        if items1.len() != items2.len() {
+            return Ok(false);
+        }
+        for (item1, item2) in items1.into_iter().zip(items2) {
+            if !values_equal(item1, item2, env)? {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    };
+
+    match (e1, e2) {
+        (e1 @ Expression::ADTValue(..), e2 @ Expression::ADTValue(..)) => {
+            let args = vec![EnvValue::Exp(e1.clone()), EnvValue::Exp(e2.clone())];
+            match instance_method_call("Eq", "eq", args, env)? {
+                Some(result) => Ok(result == EnvValue::Exp(Expression::CTrue)),
+                None => Ok(equal_values(&e1, &e2)),
+            }
+        }
+        (e1, e2) if is_wrapper(&e1) || is_wrapper(&e2) => wrapped_eq(e1, e2, env),
+        (e1, e2) if matches!(e1, Expression::CComplex(_)) || matches!(e2, Expression::CComplex(_)) => {
+            match (as_complex(&e1), as_complex(&e2)) {
+                (Some(z1), Some(z2)) => Ok(z1 == z2),
+                _ => Err(not_comparable(e1, e2)),
+            }
+        }
+        (Expression::CString(s1), Expression::CString(s2)) => Ok(s1 == s2),
+        (Expression::CBytes(b1), Expression::CBytes(b2)) => Ok(b1 == b2),
+        (b1 @ (Expression::CTrue | Expression::CFalse), b2 @ (Expression::CTrue | Expression::CFalse)) => Ok(b1 == b2),
+        (Expression::CVoid, Expression::CVoid) => Ok(true),
+        (Expression::CTuple(items1), Expression::CTuple(items2)) | (Expression::CList(items1), Expression::CList(items2)) => {
+            all(items1, items2)
+        }
+        (Expression::CDict(entries1), Expression::CDict(mut entries2)) => {
+            if entries1.len() != entries2.len() {
+                return Ok(false);
             }
+            for (key, value) in entries1 {
+                let Some(i) = entries2.iter().position(|(other, _)| *other == key) else {
+                    return Ok(false);
+                };
+                if !values_equal(value, entries2.swap_remove(i).1, env)? {
+                    return Ok(false);
+                }
+            }
+            Ok(true)
+        }
+        (e1, e2) => match eval_binary_rel_op(e1.clone(), e2.clone(), env, |a, b| bool_value(a == b), "") {
+            Ok(EnvValue::Exp(equal)) => Ok(equal == Expression::CTrue),
+            _ => Err(not_comparable(e1, e2)),
         },
-        "(==) is only defined for numbers (integers and real).",
+    }
+}
+
+fn not_comparable(e1: Expression, e2: Expression) -> (String, Option<Expression>) {
+    (
+        format!(
+            "(==) cannot compare {} with {}.",
+            render(&EnvValue::Exp(e1)),
+            render(&EnvValue::Exp(e2))
+        ),
+        None,
     )
 }
 
-fn gt(
-    lhs: Expression,
-    rhs: Expression,
-    env: &Environment<EnvValue>,
-) -> Result<EnvValue, ErrorMessage> {
-    eval_binary_rel_op(
-        lhs,
-        rhs,
-        env,
-        |a, b| {
-            if a > b {
-                Expression::CTrue
-            } else {
-                Expression::CFalse
-            }
-        },
-        "(>) is only defined for numbers (integers and real).",
+fn is_wrapper(value: &Expression) -> bool {
+    matches!(
+        value,
+        Expression::CJust(_) | Expression::CNothing | Expression::COk(_) | Expression::CErr(_)
     )
 }
 
-fn lt(
-    lhs: Expression,
-    rhs: Expression,
-    env: &Environment<EnvValue>,
+// Maybe and Result values are equal when the same constructor wraps equal
+// payloads. As in the type checker, comparing a wrapper with anything else
+// is an error rather than False.
+fn wrapped_eq(e1: Expression, e2: Expression, env: &Environment<EnvValue>) -> Result<bool, ErrorMessage> {
+    match (e1, e2) {
+        (Expression::CNothing, Expression::CNothing) => Ok(true),
+        (Expression::CJust(a), Expression::CJust(b))
+        | (Expression::COk(a), Expression::COk(b))
+        | (Expression::CErr(a), Expression::CErr(b)) => values_equal(*a, *b, env),
+        (Expression::CJust(_), Expression::CNothing)
+        | (Expression::CNothing, Expression::CJust(_))
+        | (Expression::COk(_), Expression::CErr(_))
+        | (Expression::CErr(_), Expression::COk(_)) => Ok(false),
+        (e1, e2) => {
+            let hint = if is_wrapper(&e1) && is_wrapper(&e2) { "." } else { "; did you mean unwrap?" };
+            Err((
+                format!(
+                    "(==) cannot compare {} with {}{}",
+                    render(&EnvValue::Exp(e1)),
+                    render(&EnvValue::Exp(e2)),
+                    hint
+                ),
+                None,
+            ))
+        }
+    }
+}
+
+// Relational operators on ADTs are all derived from the `lt` method of their
+// `Ord` instance: `a > b` is `lt(b, a)` and `a >= b` is `not lt(a, b)`.
+fn ord_instance_call(
+    lhs: Expression,
+    rhs: Expression,
+    swap: bool,
+    negate: bool,
+    env: &Environment<EnvValue>,
+) -> Result<Result<EnvValue, (Expression, Expression)>, ErrorMessage> {
+    let (v1, v2) = (eval(lhs, env)?, eval(rhs, env)?);
+    let args = if swap {
+        vec![v2.clone(), v1.clone()]
+    } else {
+        vec![v1.clone(), v2.clone()]
+    };
+    match instance_method_call("Ord", "lt", args, env)? {
+        Some(EnvValue::Exp(Expression::CTrue)) => Ok(Ok(EnvValue::Exp(bool_value(!negate)))),
+        Some(EnvValue::Exp(Expression::CFalse)) => Ok(Ok(EnvValue::Exp(bool_value(negate)))),
+        Some(_) => Err((String::from("[Runtime Error] 'lt' must return a boolean."), None)),
+        None => Ok(Err((into_expression(v1)?, into_expression(v2)?))),
+    }
+}
+
+fn bool_value(value: bool) -> Expression {
+    if value {
+        Expression::CTrue
+    } else {
+        Expression::CFalse
+    }
+}
+
+fn gt(
+    lhs: Expression,
+    rhs: Expression,
+    env: &Environment<EnvValue>,
+) -> Result<EnvValue, ErrorMessage> {
+    let (lhs, rhs) = match ord_instance_call(lhs, rhs, true, false, env)? {
+        Ok(result) => return Ok(result),
+        Err(operands) => operands,
+    };
+    eval_binary_rel_op(
+        lhs,
+        rhs,
+        env,
+        |a, b| {
+            if a > b {
+                Expression::CTrue
+            } else {
+                Expression::CFalse
+            }
+        },
+        "(>) is only defined for numbers (integers and real).",
+    )
+}
+
+fn lt(
+    lhs: Expression,
+    rhs: Expression,
+    env: &Environment<EnvValue>,
 ) -> Result<EnvValue, ErrorMessage> {
+    let (lhs, rhs) = match ord_instance_call(lhs, rhs, false, false, env)? {
+        Ok(result) => return Ok(result),
+        Err(operands) => operands,
+    };
     eval_binary_rel_op(
         lhs,
         rhs,
@@ -837,6 +1937,10 @@ fn gte(
     rhs: Expression,
     env: &Environment<EnvValue>,
 ) -> Result<EnvValue, ErrorMessage> {
+    let (lhs, rhs) = match ord_instance_call(lhs, rhs, false, true, env)? {
+        Ok(result) => return Ok(result),
+        Err(operands) => operands,
+    };
     eval_binary_rel_op(
         lhs,
         rhs,
@@ -857,6 +1961,10 @@ fn lte(
     rhs: Expression,
     env: &Environment<EnvValue>,
 ) -> Result<EnvValue, ErrorMessage> {
+    let (lhs, rhs) = match ord_instance_call(lhs, rhs, true, true, env)? {
+        Ok(result) => return Ok(result),
+        Err(operands) => operands,
+    };
     eval_binary_rel_op(
         lhs,
         rhs,
@@ -885,21 +1993,20 @@ fn eval_unwrap_expression(
     }
 }
 
+/// `e?` is the value inside `Just` or `Ok`. An `Err` or `Nothing` leaves
+/// through the error channel, with the value itself, until `execute`
+/// turns it into the return of the enclosing function.
 fn eval_propagate_expression(
     exp: Expression,
     env: &Environment<EnvValue>,
 ) -> Result<EnvValue, ErrorMessage> {
-    ////QUATRO Fazer teste com recursão pls :D
     let v = eval(exp, env)?;
-    //let mut *new_env = env.clone();
     match v {
         EnvValue::Exp(Expression::CJust(e)) => Ok(EnvValue::Exp(*e)),
         EnvValue::Exp(Expression::COk(e)) => Ok(EnvValue::Exp(*e)),
-        EnvValue::Exp(Expression::CErr(e)) => Err(("Propagate".to_string(), Some(*e))),
-        EnvValue::Exp(Expression::CNothing) => Err((
-            "Propagate".to_string(),
-            Some(Expression::CString("Couldn't unwrap Nothing".to_string())),
-        )),
+        EnvValue::Exp(failure @ (Expression::CErr(_) | Expression::CNothing)) => {
+            Err(("Propagate".to_string(), Some(failure)))
+        }
         _ => Err((String::from("'propagate' is expects a Just or Ok."), None)),
     }
 }
@@ -930,10 +2037,53 @@ fn eval_iserror_expression(
     }
 }
 
+fn eval_list(items: Vec<Expression>, env: &Environment<EnvValue>) -> Result<EnvValue, ErrorMessage> {
+    let mut values = vec![];
+    for item in items {
+        values.push(into_expression(eval(item, env)?)?);
+    }
+    within_limits(EnvValue::Exp(Expression::CList(values)), env)
+}
+
+fn eval_tuple(items: Vec<Expression>, env: &Environment<EnvValue>) -> Result<EnvValue, ErrorMessage> {
+    let mut values = vec![];
+    for item in items {
+        values.push(into_expression(eval(item, env)?)?);
+    }
+    within_limits(EnvValue::Exp(Expression::CTuple(values)), env)
+}
+
+// Entries keep the position of the first insertion of each key; a repeated
+// key only replaces the value.
+fn eval_dict(
+    entries: Vec<(Expression, Expression)>,
+    env: &Environment<EnvValue>,
+) -> Result<EnvValue, ErrorMessage> {
+    let mut positions: HashMap<ValueKey, usize> = HashMap::new();
+    let mut values: Vec<(Expression, Expression)> = vec![];
+    for (key, value) in entries {
+        let key = eval(key, env)?;
+        let key_hash = hash_key(&key).map_err(|e| (e, None))?;
+        let value = into_expression(eval(value, env)?)?;
+        let key = match key {
+            EnvValue::Exp(e) => e,
+            _ => unreachable!(),
+        };
+        match positions.entry(key_hash) {
+            Entry::Occupied(index) => values[*index.get()].1 = value,
+            Entry::Vacant(index) => {
+                index.insert(values.len());
+                values.push((key, value));
+            }
+        }
+    }
+    within_limits(EnvValue::Exp(Expression::CDict(values)), env)
+}
+
 fn eval_just(exp: Expression, env: &Environment<EnvValue>) -> Result<EnvValue, ErrorMessage> {
     let v = eval(exp, env)?;
     match v {
-        EnvValue::Exp(e) => Ok(EnvValue::Exp(Expression::CJust(Box::new(e)))),
+        EnvValue::Exp(e) => within_limits(EnvValue::Exp(Expression::CJust(Box::new(e))), env),
         _ => Err(("Expression not recognized.".to_string(), None)),
     }
 }
@@ -941,7 +2091,7 @@ fn eval_just(exp: Expression, env: &Environment<EnvValue>) -> Result<EnvValue, E
 fn eval_ok(exp: Expression, env: &Environment<EnvValue>) -> Result<EnvValue, ErrorMessage> {
     let v = eval(exp, env)?;
     match v {
-        EnvValue::Exp(e) => Ok(EnvValue::Exp(Expression::COk(Box::new(e)))),
+        EnvValue::Exp(e) => within_limits(EnvValue::Exp(Expression::COk(Box::new(e))), env),
         _ => Err(("Expression not recognized.".to_string(), None)),
     }
 }
@@ -949,7 +2099,7 @@ fn eval_ok(exp: Expression, env: &Environment<EnvValue>) -> Result<EnvValue, Err
 fn eval_err(exp: Expression, env: &Environment<EnvValue>) -> Result<EnvValue, ErrorMessage> {
     let v = eval(exp, env)?;
     match v {
-        EnvValue::Exp(e) => Ok(EnvValue::Exp(Expression::CErr(Box::new(e)))),
+        EnvValue::Exp(e) => within_limits(EnvValue::Exp(Expression::CErr(Box::new(e))), env),
         _ => Err(("Expression not recognized.".to_string(), None)),
     }
 }
@@ -960,11 +2110,23 @@ mod tests {
     use crate::ir::ast::Expression::*;
     use crate::ir::ast::Function;
     use crate::ir::ast::Statement::*;
-    use std::collections::HashMap;
+    use std::collections::BTreeMap;
     use crate::ir::ast::Type::*;
     use crate::ir::ast::{Environment,Expression, Statement,Type, ValueConstructor};
     use approx::relative_eq;
 
+    /// Runs the statements of a snippet one after another.
+    fn run_snippet(program: Vec<Statement>) -> Result<Environment<EnvValue>, String> {
+        let mut env = Environment::new();
+        for stmt in program {
+            match run(stmt, &env)? {
+                ControlFlow::Continue(new_env) => env = new_env,
+                ControlFlow::Return(value) => return Err(format!("returned {:?}", value)),
+            }
+        }
+        Ok(env)
+    }
+
     #[test]
     fn eval_constant() {
         let env: Environment<EnvValue> = Environment::new();
@@ -1280,62 +2442,293 @@ mod tests {
     }
 
     #[test]
-    fn eval_summation() {
-        /*
-         * (a test case for the following program)
-         *
-         * > x: TInteger = 10
-         * > y: TInteger = 0
-         * > while x >= 0:
-         * >   y = y + x
-         * >   x = x - 1
-         *
-         * After executing this program, 'x' must be zero and
-         * 'y' must be 55.
-         */
+    fn while_iterations_are_blocks() {
+        // A body that is not a block still scopes the names it binds
+        let body = Sequence(
+            Box::new(Assignment("x".to_string(), Box::new(Add(Box::new(Var("x".to_string())), Box::new(CInt(1)))), None)),
+            Box::new(Assignment("last".to_string(), Box::new(Var("x".to_string())), None)),
+        );
+        let env = run_snippet(vec![
+            Assignment("x".to_string(), Box::new(CInt(0)), Some(TInteger)),
+            While(Box::new(LT(Box::new(Var("x".to_string())), Box::new(CInt(3)))), Box::new(body)),
+        ])
+        .unwrap();
+
+        assert_eq!(env.search_frame("x".to_string()), Some(&EnvValue::Exp(CInt(3))));
+        assert_eq!(env.search_frame("last".to_string()), None);
+    }
+
+    #[test]
+    fn match_strings_by_prefix_and_suffix() {
+        let env = run_snippet(crate::rpy!(r#"
+            def respond(command: TString) -> TString:
+                match command {
+                    "quit" => return "bye",
+                    startswith "GET " => return "fetch",
+                    endswith "?" => return "answer",
+                    _ => return "unknown"
+                }
+            a = respond("quit")
+            b = respond("GET /index")
+            c = respond("why?")
+            d = respond("GET?")
+            e = respond("quit now")
+        "#))
+        .unwrap();
+
+        let responses: Vec<String> = ["a", "b", "c", "d", "e"]
+            .iter()
+            .map(|name| render(env.search_frame(name.to_string()).unwrap()))
+            .collect();
+        assert_eq!(responses, vec!["\"bye\"", "\"fetch\"", "\"answer\"", "\"answer\"", "\"unknown\""]);
+    }
+
+    #[test]
+    fn match_guards_fall_through_when_false() {
+        let env = run_snippet(crate::rpy!(r#"
+            def sign(n: TInteger) -> TString:
+                match n {
+                    x if x > 0 => return "positive",
+                    0 => return "zero",
+                    x if x > -10 => return "slightly negative",
+                    _ => return "negative"
+                }
+            a = sign(5)
+            b = sign(0)
+            c = sign(-3)
+            d = sign(-30)
+        "#))
+        .unwrap();
+
+        let signs: Vec<String> = ["a", "b", "c", "d"]
+            .iter()
+            .map(|name| render(env.search_frame(name.to_string()).unwrap()))
+            .collect();
+        assert_eq!(signs, vec!["\"positive\"", "\"zero\"", "\"slightly negative\"", "\"negative\""]);
+        assert_eq!(env.search_frame("x".to_string()), None);
+    }
+
+    #[test]
+    fn functions_defined_in_branches() {
+        let env = run_snippet(crate::rpy!("
+            def outer(k: TInteger) -> TInteger:
+                if k > 0:
+                    def inner(n: TInteger) -> TInteger:
+                        return n + k
+                    return inner(1)
+                else:
+                    return 0
+            y = 0
+            if y == 0:
+                def fact(n: TInteger) -> TInteger:
+                    if n == 0:
+                        return 1
+                    else:
+                        return n * fact(n - 1)
+                y = fact(4)
+            z = outer(4)
+        "))
+        .unwrap();
+
+        assert_eq!(env.search_frame("y".to_string()), Some(&EnvValue::Exp(CInt(24))));
+        assert_eq!(env.search_frame("z".to_string()), Some(&EnvValue::Exp(CInt(5))));
+        assert_eq!(env.search_frame("fact".to_string()), None);
+    }
+
+    #[test]
+    fn local_helpers_close_over_the_parameters_of_their_function() {
+        let env = run_snippet(crate::rpy!("
+            def outer(k: TInteger) -> TInteger:
+                def helper(n: TInteger) -> TInteger:
+                    if n == 0:
+                        return k
+                    return helper(n - 1) + 1
+                def twice(n: TInteger) -> TInteger:
+                    return helper(n) + helper(n)
+                k = k + 100
+                return twice(2)
+            x = outer(1)
+        "))
+        .unwrap();
+
+        // Captured by value when `helper` was defined, before `k` changed
+        assert_eq!(env.search_frame("x".to_string()), Some(&EnvValue::Exp(CInt(6))));
+        assert_eq!(env.search_frame("helper".to_string()), None);
+        let escaped = run_snippet(crate::rpy!("
+            def outer(k: TInteger) -> TInteger:
+                def helper(n: TInteger) -> TInteger:
+                    return n + k
+                return helper(1)
+            x = outer(1)
+            y = helper(2)
+        "));
+        assert_eq!(escaped.unwrap_err(), "Function helper not found");
+    }
+
+    #[test]
+    fn functions_bound_in_calls_shadow_globals_at_any_depth() {
+        // Deep enough for the snapshots of the calls to be flattened
+        let program = crate::rpy!("
+            def base(m: TInteger) -> TInteger:
+                return 0
+            def depth(n: TInteger) -> TInteger:
+                def base(m: TInteger) -> TInteger:
+                    return m
+                if n == 0:
+                    return base(7)
+                else:
+                    return depth(n - 1)
+            x = depth(20)
+            y = base(7)
+        ");
+        let env = std::thread::Builder::new()
+            .stack_size(64 << 20)
+            .spawn(move || run_snippet(program).unwrap())
+            .unwrap()
+            .join()
+            .unwrap();
+
+        assert_eq!(env.search_frame("x".to_string()), Some(&EnvValue::Exp(CInt(7))));
+        assert_eq!(env.search_frame("y".to_string()), Some(&EnvValue::Exp(CInt(0))));
+    }
+
+    #[test]
+    fn finished_calls_leave_their_environment_empty_in_the_pool() {
+        let env = run_snippet(crate::rpy!("
+            def count(n: TInteger) -> TInteger:
+                local = n * 2
+                if n == 0:
+                    return 0
+                return 1 + count(n - 1)
+            x = count(3)
+            y = count(2)
+        "))
+        .unwrap();
+        assert_eq!(env.search_frame("x".to_string()), Some(&EnvValue::Exp(CInt(3))));
+        assert_eq!(env.search_frame("y".to_string()), Some(&EnvValue::Exp(CInt(2))));
+
+        // The second call ran in the environments the first one left
+        POOL.with(|pool| {
+            let pool = pool.borrow();
+            assert_eq!(pool.len(), 4);
+            for env in pool.iter() {
+                assert_eq!((env.recursion, env.stack.len()), (0, 1));
+                assert!(env.get_frame(env.scope_key()).variables.is_empty());
+                assert!(env.measures.is_empty());
+            }
+        });
+    }
 
+    #[test]
+    fn closures_in_loops_keep_their_iteration() {
+        let env = run_snippet(crate::rpy!("
+            def first() -> TInteger:
+                return 0 - 1
+            def last() -> TInteger:
+                return 0 - 1
+            i = 0
+            while i < 3:
+                def get() -> TInteger:
+                    return i * 10
+                i = i + 1
+                if i == 1:
+                    first = get
+                else:
+                    last = get
+            a = first()
+            b = last()
+        "))
+        .unwrap();
+
+        assert_eq!(env.search_frame("i".to_string()), Some(&EnvValue::Exp(CInt(3))));
+        assert_eq!(env.search_frame("a".to_string()), Some(&EnvValue::Exp(CInt(0))));
+        assert_eq!(env.search_frame("b".to_string()), Some(&EnvValue::Exp(CInt(20))));
+    }
+
+    #[test]
+    fn eq_on_maybe_and_result() {
         let env: Environment<EnvValue> = Environment::new();
+        let eq = |l: Expression, r: Expression| eval(EQ(Box::new(l), Box::new(r)), &env);
+        let just = |e: Expression| CJust(Box::new(e));
 
-        let a1 = Assignment(String::from("x"), Box::new(CInt(10)), Some(TInteger));
-        let a2 = Assignment(String::from("y"), Box::new(CInt(0)), Some(TInteger));
-        let a3 = Assignment(
-            String::from("y"),
-            Box::new(Add(
-                Box::new(Var(String::from("y"))),
-                Box::new(Var(String::from("x"))),
-            )),
-            None,
+        assert_eq!(eq(just(CInt(1)), just(CReal(1.0))), Ok(EnvValue::Exp(CTrue)));
+        assert_eq!(eq(just(CInt(1)), CNothing), Ok(EnvValue::Exp(CFalse)));
+        assert_eq!(eq(CNothing, CNothing), Ok(EnvValue::Exp(CTrue)));
+        assert_eq!(eq(COk(Box::new(CInt(1))), CErr(Box::new(CInt(1)))), Ok(EnvValue::Exp(CFalse)));
+        assert_eq!(
+            eq(just(CInt(1)), CInt(1)),
+            Err((String::from("(==) cannot compare Just(1) with 1; did you mean unwrap?"), None))
         );
-        let a4 = Assignment(
-            String::from("x"),
-            Box::new(Sub(Box::new(Var(String::from("x"))), Box::new(CInt(1)))),
-            None,
+        assert_eq!(
+            eq(just(CInt(1)), COk(Box::new(CInt(1)))),
+            Err((String::from("(==) cannot compare Just(1) with Ok(1)."), None))
         );
+    }
 
-        let seq1 = Sequence(Box::new(a3), Box::new(a4));
-
-        let while_statement = While(
-            Box::new(GT(Box::new(Var(String::from("x"))), Box::new(CInt(0)))),
-            Box::new(seq1),
+    #[test]
+    fn decimal_arithmetic_is_exact() {
+        let env = run_snippet(crate::rpy!("
+            total = 0.10d + 0.20d
+            exact = total == 0.30d
+            share = 10.00d / 4
+            third = 1d / 3
+            change = 20 - 3 * 6.65d
+            cheaper = change < 1
+        "))
+        .unwrap();
+        let value = |name: &str| render(env.search_frame(name.to_string()).unwrap());
+
+        assert_eq!(value("total"), "0.30d");
+        assert_eq!(value("exact"), "True");
+        assert_eq!(value("share"), "2.50d");
+        assert_eq!(value("third"), "0.3333333333d");
+        assert_eq!(value("change"), "0.05d");
+        assert_eq!(value("cheaper"), "True");
+        assert_eq!(
+            run_snippet(crate::rpy!("x = 1.50d / 0")).err(),
+            Some(String::from("[Runtime Error] decimal division by zero."))
         );
+    }
 
-        let seq2 = Sequence(Box::new(a2), Box::new(while_statement));
-        let program = Sequence(Box::new(a1), Box::new(seq2));
+    #[test]
+    fn complex_arithmetic() {
+        let env = run_snippet(crate::rpy!("
+            z = 1 + 2j
+            rotated = z * 1j
+            w = (3.0 - 4j) / 2
+            unit = rotated / rotated == 1
+            same = z == (1.0+2.0j)
+            real = 2j * 2j == -4
+        "))
+        .unwrap();
+        let value = |name: &str| render(env.search_frame(name.to_string()).unwrap());
+
+        assert_eq!(value("z"), "(1.0+2.0j)");
+        assert_eq!(value("rotated"), "(-2.0+1.0j)");
+        assert_eq!(value("w"), "(1.5-2.0j)");
+        assert_eq!(value("unit"), "True");
+        assert_eq!(value("same"), "True");
+        assert_eq!(value("real"), "True");
+        assert_eq!(
+            run_snippet(crate::rpy!("x = 1j / 0")).err(),
+            Some(String::from("[Runtime Error] complex division by zero."))
+        );
+    }
 
-        match execute(program, &env) {
-            Ok(ControlFlow::Continue(new_env)) => {
-                assert_eq!(
-                    new_env.search_frame("y".to_string()),
-                    Some(&EnvValue::Exp(CInt(55)))
-                );
-                assert_eq!(
-                    new_env.search_frame("x".to_string()),
-                    Some(&EnvValue::Exp(CInt(0)))
-                );
-            }
-            Ok(ControlFlow::Return(_)) => assert!(false),
-            Err(s) => assert!(false, "{:?}", s),
-        }
+    #[test]
+    fn eval_summation() {
+        let env = run_snippet(crate::rpy!(
+            "
+            x = 10
+            y = 0
+            while x > 0:
+                y = y + x
+                x = x - 1
+            "
+        ))
+        .unwrap();
+        assert_eq!(env.search_frame("y".to_string()), Some(&EnvValue::Exp(CInt(55))));
+        assert_eq!(env.search_frame("x".to_string()), Some(&EnvValue::Exp(CInt(0))));
     }
 
     #[test]
@@ -1458,58 +2851,20 @@ mod tests {
 
     #[test]
     fn eval_if_then_optional_else() {
-        /*
-         * Test for simple if-then-else statement
-         *
-         * > x: TInteger = 1
-         * > y: TInteger = 0
-         * > if x == y:
-         * >   y = 1
-         * > else:
-         * >    y = 2
-         * >    if x < 0:
-         * >        y = 5
-         *
-         * After executing, 'y' should be 2.
-         */
-
-        let env: Environment<EnvValue> = Environment::new();
-
-        let second_condition = LT(Box::new(Var(String::from("x"))), Box::new(CInt(0)));
-        let second_then_stmt = Assignment(String::from("y"), Box::new(CInt(5)), None);
-
-        let second_if_stmt =
-            IfThenElse(Box::new(second_condition), Box::new(second_then_stmt), None);
-
-        let else_setup_stmt = Assignment(String::from("y"), Box::new(CInt(2)), None);
-        let else_stmt = Sequence(Box::new(else_setup_stmt), Box::new(second_if_stmt));
-
-        let first_condition = EQ(
-            Box::new(Var(String::from("x"))),
-            Box::new(Var(String::from("y"))),
-        );
-        let first_then_stmt = Assignment(String::from("y"), Box::new(CInt(1)), None);
-
-        let first_if_stmt = IfThenElse(
-            Box::new(first_condition),
-            Box::new(first_then_stmt),
-            Some(Box::new(else_stmt)),
-        );
-
-        let second_assignment = Assignment(String::from("y"), Box::new(CInt(0)), Some(TInteger));
-        let setup_stmt = Sequence(Box::new(second_assignment), Box::new(first_if_stmt));
-
-        let first_assignment = Assignment(String::from("x"), Box::new(CInt(1)), Some(TInteger));
-        let program = Sequence(Box::new(first_assignment), Box::new(setup_stmt));
-
-        match run(program, &env) {
-            Ok(ControlFlow::Continue(new_env)) => assert_eq!(
-                new_env.search_frame("y".to_string()),
-                Some(&EnvValue::Exp(CInt(2)))
-            ),
-            Ok(ControlFlow::Return(_)) => assert!(false),
-            Err(s) => assert!(false, "{:?}", s),
-        }
+        let env = run_snippet(crate::rpy!(
+            "
+            x = 1
+            y = 0
+            if x == y:
+                y = 1
+            else:
+                y = 2
+                if x < 0:
+                    y = 5
+            "
+        ))
+        .unwrap();
+        assert_eq!(env.search_frame("y".to_string()), Some(&EnvValue::Exp(CInt(2))));
     }
 
     // #[test]
@@ -1609,122 +2964,32 @@ mod tests {
 
     #[test]
     fn eval_complex_sequence() {
-        /*
-         * Sequence with multiple assignments and expressions
-         *
-         * > x: TInteger = 5
-         * > y: TInteger = 0
-         * > z: TInteger = 2 * x + 3
-         *
-         * After executing, 'x' should be 5, 'y' should be 0, and 'z' should be 13.
-         */
-
-        let env: Environment<EnvValue> = Environment::new();
-
-        let a1 = Assignment(String::from("x"), Box::new(CInt(5)), Some(TInteger));
-        let a2 = Assignment(String::from("y"), Box::new(CInt(0)), Some(TInteger));
-        let a3 = Assignment(
-            String::from("z"),
-            Box::new(Add(
-                Box::new(Mul(Box::new(CInt(2)), Box::new(Var(String::from("x"))))),
-                Box::new(CInt(3)),
-            )),
-            Some(TInteger),
-        );
-
-        let program = Sequence(Box::new(a1), Box::new(Sequence(Box::new(a2), Box::new(a3))));
-
-        match run(program, &env) {
-            Ok(ControlFlow::Continue(new_env)) => {
-                assert_eq!(
-                    new_env.search_frame("x".to_string()),
-                    Some(&EnvValue::Exp(CInt(5)))
-                );
-                assert_eq!(
-                    new_env.search_frame("y".to_string()),
-                    Some(&EnvValue::Exp(CInt(0)))
-                );
-                assert_eq!(
-                    new_env.search_frame("z".to_string()),
-                    Some(&EnvValue::Exp(CInt(13)))
-                );
-            }
-            Ok(ControlFlow::Return(_)) => assert!(false),
-            Err(s) => assert!(false, "{:?}", s),
-        }
+        let env = run_snippet(crate::rpy!("x = 5\ny = 0\nz = 2 * x + 3")).unwrap();
+        assert_eq!(env.search_frame("x".to_string()), Some(&EnvValue::Exp(CInt(5))));
+        assert_eq!(env.search_frame("y".to_string()), Some(&EnvValue::Exp(CInt(0))));
+        assert_eq!(env.search_frame("z".to_string()), Some(&EnvValue::Exp(CInt(13))));
     }
 
     #[test]
     fn recursive_func_def_call() {
-        /*
-         * Test for a recursive function
-         *
-         * > def fibonacci(n: TInteger) -> TInteger:
-         * >    if n < 1:
-         * >        return 0
-         * >
-         * >    if n <= 2:
-         * >        return n - 1
-         * >
-         * >    return fibonacci(n - 1) + fibonacci(n - 2)
-         * >
-         * > fib: TInteger = fibonacci(10)
-         *
-         * After executing, 'fib' should be 34.
-         */
-
-        let env: Environment<EnvValue> = Environment::new();
-
-        let func = FuncDef(Function {
-            name: "fibonacci".to_string(),
-            kind: Some(TInteger),
-            params: Some(vec![("n".to_string(), TInteger)]),
-            body: Some(Box::new(Sequence(
-                Box::new(IfThenElse(
-                    Box::new(LT(Box::new(Var("n".to_string())), Box::new(CInt(1)))),
-                    Box::new(Return(Box::new(CInt(0)))),
-                    None,
-                )),
-                Box::new(Sequence(
-                    Box::new(IfThenElse(
-                        Box::new(LTE(Box::new(Var("n".to_string())), Box::new(CInt(2)))),
-                        Box::new(Return(Box::new(Sub(
-                            Box::new(Var("n".to_string())),
-                            Box::new(CInt(1)),
-                        )))),
-                        None,
-                    )),
-                    Box::new(Return(Box::new(Add(
-                        Box::new(FuncCall(
-                            "fibonacci".to_string(),
-                            vec![Sub(Box::new(Var("n".to_string())), Box::new(CInt(1)))],
-                        )),
-                        Box::new(FuncCall(
-                            "fibonacci".to_string(),
-                            vec![Sub(Box::new(Var("n".to_string())), Box::new(CInt(2)))],
-                        )),
-                    )))),
-                )),
-            ))),
-        });
-
-        let program = Sequence(
-            Box::new(func),
-            Box::new(Assignment(
-                "fib".to_string(),
-                Box::new(FuncCall("fibonacci".to_string(), vec![CInt(10)])),
-                Some(TInteger),
-            )),
+        let env = run_snippet(crate::rpy!(
+            "
+            def fibonacci(n: TInteger) -> TInteger:
+                if n < 1:
+                    return 0
+                elif n <= 2:
+                    return n - 1
+                else:
+                    return fibonacci(n - 1) + fibonacci(n - 2)
+            fib = fibonacci(8)
+            "
+        ))
+        .unwrap();
+        assert_eq!(env.search_frame("fib".to_string()), Some(&EnvValue::Exp(CInt(13))));
+        assert_eq!(
+            eval(crate::rpy!(expr "fibonacci(fib - 6) * 2"), &env),
+            Ok(EnvValue::Exp(CInt(16)))
         );
-
-        match run(program, &env) {
-            Ok(ControlFlow::Continue(new_env)) => assert_eq!(
-                new_env.search_frame("fib".to_string()),
-                Some(&EnvValue::Exp(CInt(34)))
-            ),
-            Ok(ControlFlow::Return(_)) => assert!(false),
-            Err(s) => assert!(false, "{:?}", s),
-        }
     }
 
     #[test]
@@ -1802,7 +3067,7 @@ mod tests {
             Box::new(Sequence(Box::new(func_soma_mut), mod_test_def)),
         ));
 
-        let real_hash: HashMap<String, Function> = HashMap::from([(
+        let real_hash: BTreeMap<String, Function> = BTreeMap::from([(
             "test".to_string(),
             Function {
                 name: "test".to_string(),
@@ -2299,6 +3564,7 @@ mod tests {
         // Declare the Maybe ADT
         let maybe_adt = Statement::ADTDeclaration(
             "Maybe".to_string(),
+            vec![],
             vec![
                 ValueConstructor {
                     name: "Just".to_string(),
@@ -2346,12 +3612,94 @@ mod tests {
         let result = eval(circle_expr, &env);
 
         assert!(result.is_ok());
-        if let Ok(EnvValue::Exp(Expression::ADTConstructor(_, _, args))) = result {
-            assert_eq!(args.len(), 1);
+        if let Ok(EnvValue::Exp(Expression::ADTValue(value))) = result {
+            assert_eq!(value.tag, 0);
+            assert_eq!(value.fields.len(), 1);
         } else {
             panic!("Failed to evaluate ADT constructor");
         }
-    
+
+        let error = |exp| eval(exp, &env).unwrap_err().0;
+        assert_eq!(
+            error(Expression::ADTConstructor("Shape".to_string(), "Circle".to_string(), vec![])),
+            "[Runtime Error] 'Circle' expected 1 arguments, found 0."
+        );
+        assert_eq!(
+            error(Expression::ADTConstructor("Shape".to_string(), "Square".to_string(), vec![])),
+            "[Runtime Error] ADT constructor 'Square' not found in ADT 'Shape'."
+        );
+        assert_eq!(
+            error(Expression::ADTConstructor("Tree".to_string(), "Leaf".to_string(), vec![])),
+            "[Runtime Error] ADT 'Tree' is not defined."
+        );
+    }
+
+    #[test]
+    fn test_adt_constructor_tags() {
+        let mut env = Environment::new();
+        env.insert_type("Shape".to_string(), vec![
+            ValueConstructor { name: "Circle".to_string(), types: vec![TReal] },
+            ValueConstructor { name: "Rectangle".to_string(), types: vec![TReal, TReal] },
+        ]);
+
+        assert_eq!(env.constructor_tag(&"Shape".to_string(), &"Circle".to_string()), Some(0));
+        assert_eq!(env.constructor_tag(&"Shape".to_string(), &"Rectangle".to_string()), Some(1));
+        assert_eq!(env.constructor_tag(&"Shape".to_string(), &"Square".to_string()), None);
+
+        let rectangle = Expression::ADTConstructor(
+            "Shape".to_string(),
+            "Rectangle".to_string(),
+            vec![Box::new(CReal(3.0)), Box::new(CReal(4.0))],
+        );
+
+        match eval(rectangle, &env) {
+            Ok(EnvValue::Exp(ADTValue(value))) => {
+                assert_eq!(value.constructor, "Rectangle");
+                assert_eq!(value.tag, 1);
+            }
+            other => panic!("Expected an ADT value, found {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_adt_equality_by_tag() {
+        let mut env = Environment::new();
+        env.insert_type("Shape".to_string(), vec![
+            ValueConstructor { name: "Circle".to_string(), types: vec![TReal] },
+            ValueConstructor { name: "Square".to_string(), types: vec![TReal] },
+        ]);
+
+        let circle = |r: f64| {
+            Box::new(ADTConstructor("Shape".to_string(), "Circle".to_string(), vec![Box::new(CReal(r))]))
+        };
+        let square = Box::new(ADTConstructor("Shape".to_string(), "Square".to_string(), vec![Box::new(CReal(1.0))]));
+
+        assert_eq!(eval(EQ(circle(1.0), circle(1.0)), &env), Ok(EnvValue::Exp(CTrue)));
+        assert_eq!(eval(EQ(circle(1.0), circle(2.0)), &env), Ok(EnvValue::Exp(CFalse)));
+        assert_eq!(eval(EQ(circle(1.0), square), &env), Ok(EnvValue::Exp(CFalse)));
+    }
+
+    #[test]
+    fn equality_compares_values_structurally() {
+        let env: Environment<EnvValue> = Environment::new();
+        let eq = |l: Expression, r: Expression| eval(EQ(Box::new(l), Box::new(r)), &env);
+        let text = |s: &str| CString(s.to_string());
+        let dict = |entries: Vec<(&str, i32)>| CDict(entries.into_iter().map(|(k, v)| (text(k), CInt(v))).collect());
+
+        assert_eq!(eq(text("a"), text("a")), Ok(EnvValue::Exp(CTrue)));
+        assert_eq!(eq(CTrue, CFalse), Ok(EnvValue::Exp(CFalse)));
+        assert_eq!(eq(CTuple(vec![CInt(1), text("a")]), CTuple(vec![CReal(1.0), text("a")])), Ok(EnvValue::Exp(CTrue)));
+        assert_eq!(eq(CList(vec![CInt(1)]), CList(vec![CInt(1), CInt(2)])), Ok(EnvValue::Exp(CFalse)));
+        assert_eq!(
+            eq(CJust(Box::new(CTuple(vec![text("a")]))), CJust(Box::new(CTuple(vec![text("a")])))),
+            Ok(EnvValue::Exp(CTrue))
+        );
+        assert_eq!(eq(dict(vec![("a", 1), ("b", 2)]), dict(vec![("b", 2), ("a", 1)])), Ok(EnvValue::Exp(CTrue)));
+        assert_eq!(eq(dict(vec![("a", 1)]), dict(vec![("a", 2)])), Ok(EnvValue::Exp(CFalse)));
+        assert_eq!(
+            eq(text("1"), CInt(1)),
+            Err((String::from("(==) cannot compare \"1\" with 1."), None))
+        );
     }
     #[test]
     fn test_complex_adt() {
@@ -2361,6 +3709,7 @@ mod tests {
         // Declare the Shape ADT
         let shape_adt = Statement::ADTDeclaration(
             "Shape".to_string(),
+            vec![],
             vec![
                 ValueConstructor {
                     name: "Circle".to_string(),
@@ -2483,6 +3832,7 @@ mod tests {
         // Declare the Shape ADT
         let shape_adt = Statement::ADTDeclaration(
             "Shape".to_string(),
+            vec![],
             vec![
                 ValueConstructor {
                     name: "Circle".to_string(),
@@ -2587,6 +3937,7 @@ mod tests {
         // Declara a ADT Shape com dois construtores: Circle e Rectangle
         let shape_adt = Statement::ADTDeclaration(
             "Shape".to_string(),
+            vec![],
             vec![
                 ValueConstructor {
                     name: "Circle".to_string(),
@@ -2647,12 +3998,14 @@ mod tests {
             vec![
                 // Caso 1: Circle
                 (
-                    Expression::ADTConstructor("Shape".to_string(), "Circle".to_string(), vec![]),
+                    Pattern::Constructor("Shape".to_string(), "Circle".to_string(), vec![Pattern::Wildcard]),
+                    None,
                     Box::new(Statement::Return(Box::new(Expression::CString("It's a circle!".to_string())))),
                 ),
                 // Caso 2: Rectangle
                 (
-                    Expression::ADTConstructor("Shape".to_string(), "Rectangle".to_string(), vec![]),
+                    Pattern::Constructor("Shape".to_string(), "Rectangle".to_string(), vec![Pattern::Wildcard; 2]),
+                    None,
                     Box::new(Statement::Return(Box::new(Expression::CString("It's a rectangle!".to_string())))),
                 ),
             ],
@@ -2682,6 +4035,7 @@ mod tests {
         // Declara a ADT FiguraGeometrica com três construtores: Circle, Rectangle e Triangle
         let figura_adt = Statement::ADTDeclaration(
             "FiguraGeometrica".to_string(),
+            vec![],
             vec![
                 ValueConstructor {
                     name: "Círculo".to_string(),
@@ -2789,17 +4143,20 @@ mod tests {
             vec![
                 // Caso 1: Círculo -> Área = π * r^2
                 (
-                    Expression::ADTConstructor("FiguraGeometrica".to_string(), "Círculo".to_string(), vec![]),
+                    Pattern::Constructor("FiguraGeometrica".to_string(), "Círculo".to_string(), vec![Pattern::Wildcard]),
+                    None,
                     Box::new(Statement::Return(Box::new(Expression::CReal(3.14 * 5.0 * 5.0)))), // Área do círculo
                 ),
                 // Caso 2: Retângulo -> Área = largura * altura
                 (
-                    Expression::ADTConstructor("FiguraGeometrica".to_string(), "Retângulo".to_string(), vec![]),
+                    Pattern::Constructor("FiguraGeometrica".to_string(), "Retângulo".to_string(), vec![Pattern::Wildcard; 2]),
+                    None,
                     Box::new(Statement::Return(Box::new(Expression::CReal(3.0 * 7.0)))), // Área do retângulo
                 ),
                 // Caso 3: Triângulo -> Área = (base * altura) / 2
                 (
-                    Expression::ADTConstructor("FiguraGeometrica".to_string(), "Triângulo".to_string(), vec![]),
+                    Pattern::Constructor("FiguraGeometrica".to_string(), "Triângulo".to_string(), vec![Pattern::Wildcard; 2]),
+                    None,
                     Box::new(Statement::Return(Box::new(Expression::CReal(0.5 * 6.0 * 4.0)))), // Área do triângulo
                 ),
             ],
@@ -2818,4 +4175,963 @@ mod tests {
         }
     }
 
-}
\ No newline at end of file
+    #[test]
+    fn test_match_failure_describes_scrutinee() {
+        let mut env: Environment<EnvValue> = Environment::new();
+        env.insert_type(
+            "Shape".to_string(),
+            vec![
+                crate::ir::ast::ValueConstructor {
+                    name: "Circle".to_string(),
+                    types: vec![Type::TReal],
+                },
+                crate::ir::ast::ValueConstructor {
+                    name: "Square".to_string(),
+                    types: vec![Type::TReal],
+                },
+            ],
+        );
+
+        let match_stmt = Statement::Match(
+            Box::new(Expression::ADTConstructor(
+                "Shape".to_string(),
+                "Circle".to_string(),
+                vec![Box::new(Expression::CReal(1.5))],
+            )),
+            vec![(
+                Pattern::Constructor("Shape".to_string(), "Square".to_string(), vec![Pattern::Wildcard]),
+                None,
+                Box::new(Statement::Return(Box::new(Expression::CInt(1)))),
+            )],
+        );
+
+        match execute(match_stmt, &env) {
+            Err((s, _)) => assert_eq!(
+                s,
+                "[Match Failure] no pattern matches Circle(1.5) of type 'Shape'."
+            ),
+            _ => panic!("expected a match failure"),
+        }
+    }
+
+    #[test]
+    fn match_patterns_bind_what_they_match() {
+        let mut env: Environment<EnvValue> = Environment::new();
+        env.insert_type(
+            "Pair".to_string(),
+            vec![crate::ir::ast::ValueConstructor { name: "Pair".to_string(), types: vec![Type::TInteger, Type::TAny] }],
+        );
+        env.insert_variable("x".to_string(), EnvValue::Exp(Expression::CInt(0)));
+        let pair = |first: i32, second: Expression| {
+            Expression::ADTConstructor(
+                "Pair".to_string(),
+                "Pair".to_string(),
+                vec![Box::new(Expression::CInt(first)), Box::new(second)],
+            )
+        };
+        let var = |name: &str| Pattern::Variable(name.to_string());
+        let arms = vec![
+            (
+                Pattern::Constructor("Pair".to_string(), "Pair".to_string(), vec![Pattern::Literal(Expression::CInt(1)), var("n")]),
+                None,
+                Box::new(Statement::Assignment("x".to_string(), Box::new(Expression::Var("n".to_string())), None)),
+            ),
+            (
+                Pattern::Constructor(
+                    "Pair".to_string(),
+                    "Pair".to_string(),
+                    vec![Pattern::Wildcard, Pattern::List(vec![var("head"), Pattern::Wildcard])],
+                ),
+                None,
+                Box::new(Statement::Assignment("x".to_string(), Box::new(Expression::Var("head".to_string())), None)),
+            ),
+        ];
+        let run = |value: Expression| {
+            let stmt = Statement::Match(Box::new(value), arms.clone());
+            match execute(stmt, &env) {
+                Ok(ControlFlow::Continue(env)) => {
+                    let frame = env.get_frame(env.scope_key());
+                    frame.variables.keys().cloned().collect::<Vec<_>>().join(" ")
+                        + &format!(" = {}", render(frame.variables.get("x").unwrap()))
+                }
+                Ok(ControlFlow::Return(_)) => unreachable!(),
+                Err((message, _)) => message,
+            }
+        };
+
+        assert_eq!(run(pair(1, Expression::CInt(5))), "x = 5");
+        let list = |items: Vec<i32>| Expression::CList(items.into_iter().map(Expression::CInt).collect());
+        assert_eq!(run(pair(2, list(vec![7, 8]))), "x = 7");
+        assert_eq!(
+            run(pair(2, list(vec![7]))),
+            "[Match Failure] no pattern matches Pair(2, [7]) of type 'Pair'."
+        );
+    }
+
+    #[test]
+    fn match_list_patterns_recurse_over_the_rest() {
+        let source = "
+            def total(xs: [TInteger]) -> TInteger:
+                match xs {
+                    [] => return 0,
+                    x :: rest => return x + total(rest)
+                }
+            def describe(xs: [TInteger]) -> TString:
+                match xs {
+                    [first, second, others @ ..] => return \"two or more\",
+                    [_, ..] => return \"one\",
+                    _ => return \"none\"
+                }
+            sum = total(numbers)
+            many = describe(numbers)
+            one = describe(single)
+            ";
+        let mut env: Environment<EnvValue> = Environment::new();
+        let list = |items: Vec<i32>| EnvValue::Exp(Expression::CList(items.into_iter().map(Expression::CInt).collect()));
+        env.insert_variable("numbers".to_string(), list(vec![1, 2, 3, 4]));
+        env.insert_variable("single".to_string(), list(vec![7]));
+        crate::interpreter::program::execute_statements(
+            &crate::rpy!(source),
+            &mut env,
+            &mut crate::interpreter::program::Stats::default(),
+        )
+        .unwrap();
+
+        let value = |name: &str| env.search_frame(name.to_string()).map(render);
+        assert_eq!(value("sum"), Some(String::from("10")));
+        assert_eq!(value("many"), Some(String::from("\"two or more\"")));
+        assert_eq!(value("one"), Some(String::from("\"one\"")));
+
+        let mut bindings = vec![];
+        let pattern = Pattern::ListRest(vec![Pattern::Variable("x".to_string())], Some("rest".to_string()));
+        assert!(matches_pattern(&list(vec![1, 2]), &pattern, &env, &mut bindings));
+        assert_eq!(
+            bindings,
+            vec![("x".to_string(), EnvValue::Exp(Expression::CInt(1))), ("rest".to_string(), list(vec![2]))]
+        );
+        assert!(!matches_pattern(&list(vec![]), &pattern, &env, &mut vec![]));
+    }
+
+    #[test]
+    fn match_destructures_nested_constructors() {
+        let mut env: Environment<EnvValue> = Environment::new();
+        let list_type = Type::Tadt("List".to_string(), vec![]);
+        env.insert_type(
+            "List".to_string(),
+            vec![
+                crate::ir::ast::ValueConstructor { name: "Nil".to_string(), types: vec![] },
+                crate::ir::ast::ValueConstructor { name: "Cons".to_string(), types: vec![Type::TInteger, list_type] },
+            ],
+        );
+        let list = |items: &[i32]| {
+            items.iter().rev().fold(Expression::ADTConstructor("List".to_string(), "Nil".to_string(), vec![]), |tail, item| {
+                Expression::ADTConstructor("List".to_string(), "Cons".to_string(), vec![Box::new(Expression::CInt(*item)), Box::new(tail)])
+            })
+        };
+        env.insert_variable("sum".to_string(), EnvValue::Exp(Expression::CInt(-1)));
+        let var = |name: &str| Pattern::Variable(name.to_string());
+        let cons = |head, tail| Pattern::Constructor("List".to_string(), "Cons".to_string(), vec![head, tail]);
+        let nil = Pattern::Constructor("List".to_string(), "Nil".to_string(), vec![]);
+        let sum = |names: [&str; 2]| {
+            Box::new(Statement::Assignment(
+                "sum".to_string(),
+                Box::new(Expression::Add(Box::new(Expression::Var(names[0].to_string())), Box::new(Expression::Var(names[1].to_string())))),
+                None,
+            ))
+        };
+        let arms = vec![
+            (cons(var("x"), cons(var("y"), nil.clone())), None, sum(["x", "y"])),
+            (cons(var("x"), cons(Pattern::Wildcard, cons(var("z"), Pattern::Wildcard))), None, sum(["x", "z"])),
+            (Pattern::Wildcard, None, Box::new(Statement::Assignment("sum".to_string(), Box::new(Expression::CInt(0)), None))),
+        ];
+        let run = |items: &[i32]| {
+            let stmt = Statement::Match(Box::new(list(items)), arms.clone());
+            match execute(stmt, &env) {
+                Ok(ControlFlow::Continue(env)) => env.search_frame("sum".to_string()).cloned(),
+                _ => None,
+            }
+        };
+
+        let sums: Vec<Option<EnvValue>> = [&[1, 2][..], &[1, 2, 3, 4], &[1]].iter().map(|items| run(items)).collect();
+        assert_eq!(
+            sums,
+            vec![
+                Some(EnvValue::Exp(Expression::CInt(3))),
+                Some(EnvValue::Exp(Expression::CInt(4))),
+                Some(EnvValue::Exp(Expression::CInt(0))),
+            ]
+        );
+    }
+
+    #[test]
+    fn destructuring_binds_the_parts_of_a_value() {
+        let mut env: Environment<EnvValue> = Environment::new();
+        env.insert_type(
+            "Shape".to_string(),
+            vec![
+                crate::ir::ast::ValueConstructor { name: "Circle".to_string(), types: vec![Type::TReal] },
+                crate::ir::ast::ValueConstructor { name: "Rectangle".to_string(), types: vec![Type::TReal, Type::TReal] },
+            ],
+        );
+        let rectangle = Expression::ADTConstructor(
+            "Shape".to_string(),
+            "Rectangle".to_string(),
+            vec![Box::new(Expression::CReal(2.0)), Box::new(Expression::CReal(3.0))],
+        );
+        let var = |name: &str| Pattern::Variable(name.to_string());
+        let shape = |name: &str, args| Pattern::Constructor("Shape".to_string(), name.to_string(), args);
+
+        let stmt = Statement::Destructure(shape("Rectangle", vec![var("w"), var("h")]), Box::new(rectangle.clone()));
+        match execute(stmt, &env) {
+            Ok(ControlFlow::Continue(new_env)) => {
+                assert_eq!(new_env.search_frame("w".to_string()), Some(&EnvValue::Exp(Expression::CReal(2.0))));
+                assert_eq!(new_env.search_frame("h".to_string()), Some(&EnvValue::Exp(Expression::CReal(3.0))));
+            }
+            _ => panic!("expected the sides of the rectangle to be bound"),
+        }
+
+        let stmt = Statement::Destructure(shape("Circle", vec![var("r")]), Box::new(rectangle));
+        assert_eq!(
+            execute(stmt, &env).err().map(|(message, _)| message),
+            Some(String::from(
+                "[Match Failure] the pattern 'Shape Circle r' does not match Rectangle(2.0, 3.0) of type 'Shape'."
+            ))
+        );
+    }
+
+    #[test]
+    fn test_block_drops_temporaries_and_keeps_updates() {
+        let env: Environment<EnvValue> = Environment::new();
+        let program = Statement::Sequence(
+            Box::new(Statement::Assignment("x".to_string(), Box::new(Expression::CInt(1)), None)),
+            Box::new(Statement::Block(vec![
+                Statement::Assignment("tmp".to_string(), Box::new(Expression::CInt(2)), None),
+                Statement::Block(vec![Statement::Assignment(
+                    "x".to_string(),
+                    Box::new(Expression::Var("tmp".to_string())),
+                    None,
+                )]),
+            ])),
+        );
+
+        match execute(program, &env) {
+            Ok(ControlFlow::Continue(new_env)) => {
+                assert_eq!(new_env.search_frame("x".to_string()), Some(&EnvValue::Exp(Expression::CInt(2))));
+                assert_eq!(new_env.search_frame("tmp".to_string()), None);
+                assert_eq!(new_env.stack.len(), 1);
+            }
+            _ => panic!("expected the block to complete"),
+        }
+    }
+
+    #[test]
+    fn test_block_exit_on_return_and_error() {
+        let env: Environment<EnvValue> = Environment::new();
+
+        let early_return = Statement::Block(vec![
+            Statement::Assignment("tmp".to_string(), Box::new(Expression::CInt(1)), None),
+            Statement::Block(vec![
+                Statement::Return(Box::new(Expression::Var("tmp".to_string()))),
+                Statement::Assignment("unreachable".to_string(), Box::new(Expression::CInt(0)), None),
+            ]),
+        ]);
+        match execute(early_return, &env) {
+            Ok(ControlFlow::Return(value)) => assert_eq!(value, EnvValue::Exp(Expression::CInt(1))),
+            _ => panic!("expected an early return"),
+        }
+
+        let failing = Statement::Block(vec![
+            Statement::Assignment("tmp".to_string(), Box::new(Expression::CInt(1)), None),
+            Statement::Block(vec![Statement::AssertTrue(
+                Box::new(Expression::CFalse),
+                "failed inside a block".to_string(),
+            )]),
+        ]);
+        assert!(execute(failing, &env).is_err());
+        assert_eq!(env.search_frame("tmp".to_string()), None);
+        assert_eq!(env.stack.len(), 1);
+    }
+
+    fn make_adder_program() -> Statement {
+        let adder_type = Type::TFunction(Box::new(Some(Type::TInteger)), vec![Type::TInteger]);
+        let add = Function {
+            name: "add".to_string(),
+            kind: Some(Type::TInteger),
+            params: Some(vec![("x".to_string(), Type::TInteger)]),
+            body: Some(Box::new(Statement::Return(Box::new(Expression::Add(
+                Box::new(Expression::Var("x".to_string())),
+                Box::new(Expression::Var("n".to_string())),
+            ))))),
+        };
+        let make_adder = Function {
+            name: "make_adder".to_string(),
+            kind: Some(adder_type.clone()),
+            params: Some(vec![("n".to_string(), Type::TInteger)]),
+            body: Some(Box::new(Statement::Sequence(
+                Box::new(Statement::FuncDef(add)),
+                Box::new(Statement::Return(Box::new(Expression::Var("add".to_string())))),
+            ))),
+        };
+
+        Statement::Sequence(
+            Box::new(Statement::FuncDef(make_adder)),
+            Box::new(Statement::Sequence(
+                Box::new(Statement::Assignment(
+                    "adders".to_string(),
+                    Box::new(Expression::CList(vec![
+                        Expression::FuncCall("make_adder".to_string(), vec![Expression::CInt(1)]),
+                        Expression::FuncCall("make_adder".to_string(), vec![Expression::CInt(2)]),
+                    ])),
+                    Some(Type::TList(Box::new(adder_type.clone()))),
+                )),
+                Box::new(Statement::Sequence(
+                    Box::new(Statement::Assignment(
+                        "add10".to_string(),
+                        Box::new(Expression::FuncCall(
+                            "make_adder".to_string(),
+                            vec![Expression::CInt(10)],
+                        )),
+                        Some(adder_type),
+                    )),
+                    Box::new(Statement::Assignment(
+                        "r".to_string(),
+                        Box::new(Expression::FuncCall("add10".to_string(), vec![Expression::CInt(5)])),
+                        Some(Type::TInteger),
+                    )),
+                )),
+            )),
+        )
+    }
+
+    #[test]
+    fn test_closures_returned_and_stored() {
+        let env: Environment<EnvValue> = Environment::new();
+
+        match execute(make_adder_program(), &env) {
+            Ok(ControlFlow::Continue(new_env)) => {
+                assert_eq!(
+                    new_env.search_frame("r".to_string()),
+                    Some(&EnvValue::Exp(Expression::CInt(15)))
+                );
+                match new_env.search_frame("adders".to_string()) {
+                    Some(EnvValue::Exp(Expression::CList(items))) => {
+                        let captured: Vec<_> = items
+                            .iter()
+                            .map(|item| match item {
+                                Expression::Closure(_, captured) => captured.clone(),
+                                _ => panic!("expected a closure"),
+                            })
+                            .collect();
+                        assert_eq!(
+                            captured,
+                            vec![
+                                vec![("n".to_string(), Expression::CInt(1))],
+                                vec![("n".to_string(), Expression::CInt(2))],
+                            ]
+                        );
+                    }
+                    _ => panic!("expected a list of closures"),
+                }
+            }
+            _ => panic!("expected the program to complete"),
+        }
+    }
+
+    #[test]
+    fn test_closures_capture_by_value() {
+        let env: Environment<EnvValue> = Environment::new();
+        let get = Function {
+            name: "get".to_string(),
+            kind: Some(Type::TInteger),
+            params: None,
+            body: Some(Box::new(Statement::Return(Box::new(Expression::Var("i".to_string()))))),
+        };
+        let program = Statement::Block(vec![
+            Statement::Assignment("i".to_string(), Box::new(Expression::CInt(1)), None),
+            Statement::FuncDef(get),
+            Statement::Assignment("i".to_string(), Box::new(Expression::CInt(2)), None),
+            Statement::Return(Box::new(Expression::FuncCall("get".to_string(), vec![]))),
+        ]);
+
+        match execute(program, &env) {
+            Ok(ControlFlow::Return(value)) => assert_eq!(value, EnvValue::Exp(Expression::CInt(1))),
+            _ => panic!("expected a return"),
+        }
+    }
+
+    fn sizes_with_ord_instance() -> Statement {
+        let size = |name: &str| Expression::ADTConstructor("Size".to_string(), name.to_string(), vec![]);
+        let size_type = Type::Tadt(
+            "Size".to_string(),
+            vec![
+                crate::ir::ast::ValueConstructor { name: "Small".to_string(), types: vec![] },
+                crate::ir::ast::ValueConstructor { name: "Large".to_string(), types: vec![] },
+            ],
+        );
+        let lt = Function {
+            name: "lt".to_string(),
+            kind: Some(Type::TBool),
+            params: Some(vec![("a".to_string(), size_type.clone()), ("b".to_string(), size_type.clone())]),
+            body: Some(Box::new(Statement::Return(Box::new(Expression::And(
+                Box::new(Expression::EQ(Box::new(Expression::Var("a".to_string())), Box::new(size("Small")))),
+                Box::new(Expression::EQ(Box::new(Expression::Var("b".to_string())), Box::new(size("Large")))),
+            ))))),
+        };
+        let ord_a = Type::TVar("a".to_string(), vec!["Ord".to_string()]);
+        let larger = Function {
+            name: "larger".to_string(),
+            kind: Some(ord_a.clone()),
+            params: Some(vec![("x".to_string(), ord_a.clone()), ("y".to_string(), ord_a)]),
+            body: Some(Box::new(Statement::IfThenElse(
+                Box::new(Expression::GT(
+                    Box::new(Expression::Var("x".to_string())),
+                    Box::new(Expression::Var("y".to_string())),
+                )),
+                Box::new(Statement::Return(Box::new(Expression::Var("x".to_string())))),
+                Some(Box::new(Statement::Return(Box::new(Expression::Var("y".to_string()))))),
+            ))),
+        };
+
+        let constructors = match size_type {
+            Type::Tadt(_, constructors) => constructors,
+            _ => unreachable!(),
+        };
+        Statement::Sequence(
+            Box::new(Statement::ADTDeclaration("Size".to_string(), vec![], constructors)),
+            Box::new(Statement::Sequence(
+                Box::new(Statement::InstanceDeclaration("Ord".to_string(), "Size".to_string(), vec![lt])),
+                Box::new(Statement::FuncDef(larger)),
+            )),
+        )
+    }
+
+    #[test]
+    fn test_type_class_instances_drive_operators() {
+        let env: Environment<EnvValue> = Environment::new();
+        let size = |name: &str| Expression::ADTConstructor("Size".to_string(), name.to_string(), vec![]);
+
+        let env = match execute(sizes_with_ord_instance(), &env) {
+            Ok(ControlFlow::Continue(new_env)) => new_env,
+            _ => panic!("expected the declarations to run"),
+        };
+
+        assert_eq!(
+            eval(Expression::GT(Box::new(size("Large")), Box::new(size("Small"))), &env),
+            Ok(EnvValue::Exp(Expression::CTrue))
+        );
+        assert_eq!(
+            eval(Expression::LTE(Box::new(size("Large")), Box::new(size("Small"))), &env),
+            Ok(EnvValue::Exp(Expression::CFalse))
+        );
+        assert_eq!(
+            eval(Expression::FuncCall("larger".to_string(), vec![size("Small"), size("Large")]), &env),
+            Ok(EnvValue::Exp(Expression::ADTValue(AdtValue::new("Size".to_string(), "Large".to_string(), 1, vec![].into()))))
+        );
+        assert_eq!(
+            eval(Expression::FuncCall("larger".to_string(), vec![Expression::CInt(3), Expression::CInt(2)]), &env),
+            Ok(EnvValue::Exp(Expression::CInt(3)))
+        );
+        assert_eq!(
+            eval(
+                Expression::FuncCall(
+                    "larger".to_string(),
+                    vec![Expression::CString("a".to_string()), Expression::CString("b".to_string())]
+                ),
+                &env
+            ),
+            Err((
+                String::from("[Runtime Error] 'larger()' requires an instance of 'Ord' for 'String'."),
+                None
+            ))
+        );
+    }
+
+    #[test]
+    fn test_void_functions_yield_void() {
+        let env: Environment<EnvValue> = Environment::new();
+        let log = Function {
+            name: "log".to_string(),
+            kind: Some(TVoid),
+            params: Some(vec![("x".to_string(), TInteger)]),
+            body: Some(Box::new(Assignment("y".to_string(), Box::new(Var("x".to_string())), None))),
+        };
+        let positive = Function {
+            name: "positive".to_string(),
+            kind: Some(TInteger),
+            params: Some(vec![("x".to_string(), TInteger)]),
+            body: Some(Box::new(IfThenElse(
+                Box::new(GT(Box::new(Var("x".to_string())), Box::new(CInt(0)))),
+                Box::new(Return(Box::new(Var("x".to_string())))),
+                None,
+            ))),
+        };
+        let program = Sequence(
+            Box::new(FuncDef(log)),
+            Box::new(Sequence(
+                Box::new(FuncDef(positive)),
+                Box::new(Assignment(
+                    "r".to_string(),
+                    Box::new(FuncCall("log".to_string(), vec![CInt(1)])),
+                    None,
+                )),
+            )),
+        );
+
+        let env = match run(program, &env) {
+            Ok(ControlFlow::Continue(new_env)) => new_env,
+            Ok(ControlFlow::Return(_)) => panic!("unexpected return"),
+            Err(s) => panic!("{}", s),
+        };
+        assert_eq!(env.search_frame("r".to_string()), Some(&EnvValue::Exp(CVoid)));
+        assert_eq!(
+            eval(FuncCall("positive".to_string(), vec![CInt(0)]), &env),
+            Err((
+                String::from("[Runtime Error] 'positive()' reached the end of its body without returning a 'TInteger' value."),
+                None
+            ))
+        );
+    }
+
+    fn early_return_matrix() -> Vec<(&'static str, Statement, Expression)> {
+        let x = || Box::new(Var("x".to_string()));
+        let y = || Box::new(Var("y".to_string()));
+        let increment = |name: &str| {
+            Assignment(
+                name.to_string(),
+                Box::new(Add(Box::new(Var(name.to_string())), Box::new(CInt(1)))),
+                None,
+            )
+        };
+        let x_above_2 = || Box::new(GT(x(), Box::new(CInt(2))));
+
+        vec![
+            (
+                "if inside while",
+                While(
+                    Box::new(CTrue),
+                    Box::new(Block(vec![
+                        IfThenElse(x_above_2(), Box::new(Return(x())), None),
+                        increment("x"),
+                    ])),
+                ),
+                CInt(3),
+            ),
+            (
+                "match arm inside while",
+                While(
+                    Box::new(CTrue),
+                    Box::new(Match(
+                        x_above_2(),
+                        vec![
+                            (Pattern::Literal(CTrue), None, Box::new(Return(Box::new(Mul(x(), Box::new(CInt(10))))))),
+                            (Pattern::Literal(CFalse), None, Box::new(increment("x"))),
+                        ],
+                    )),
+                ),
+                CInt(30),
+            ),
+            (
+                "while inside while",
+                While(
+                    Box::new(CTrue),
+                    Box::new(Block(vec![
+                        Assignment("y".to_string(), Box::new(CInt(0)), None),
+                        While(
+                            Box::new(CTrue),
+                            Box::new(Block(vec![
+                                IfThenElse(
+                                    Box::new(GT(y(), x())),
+                                    Box::new(Return(Box::new(Add(
+                                        Box::new(Mul(y(), Box::new(CInt(100)))),
+                                        x(),
+                                    )))),
+                                    None,
+                                ),
+                                increment("y"),
+                            ])),
+                        ),
+                        increment("x"),
+                    ])),
+                ),
+                CInt(100),
+            ),
+            (
+                "block inside if inside match arm",
+                Match(
+                    x(),
+                    vec![(
+                        Pattern::Literal(CInt(0)),
+                        None,
+                        Box::new(Block(vec![
+                            IfThenElse(
+                                Box::new(CTrue),
+                                Box::new(Block(vec![Return(Box::new(CInt(7)))])),
+                                None,
+                            ),
+                            Return(Box::new(CInt(8))),
+                        ])),
+                    )],
+                ),
+                CInt(7),
+            ),
+            (
+                "statements after a return",
+                Block(vec![
+                    Return(Box::new(CInt(1))),
+                    Assignment("x".to_string(), Box::new(Propagate(Box::new(CErr(Box::new(CInt(2)))))), None),
+                ]),
+                CInt(1),
+            ),
+            (
+                "return after the loop",
+                Sequence(
+                    Box::new(While(Box::new(LT(x(), Box::new(CInt(3)))), Box::new(increment("x")))),
+                    Box::new(Return(x())),
+                ),
+                CInt(3),
+            ),
+            (
+                "propagation inside match inside while",
+                While(
+                    Box::new(CTrue),
+                    Box::new(Match(
+                        x_above_2(),
+                        vec![
+                            (
+                                Pattern::Literal(CTrue),
+                                None,
+                                Box::new(Assignment(
+                                    "v".to_string(),
+                                    Box::new(Propagate(Box::new(CErr(Box::new(CString("boom".to_string())))))),
+                                    None,
+                                )),
+                            ),
+                            (Pattern::Literal(CFalse), None, Box::new(increment("x"))),
+                        ],
+                    )),
+                ),
+                CErr(Box::new(CString("boom".to_string()))),
+            ),
+        ]
+    }
+
+    #[test]
+    fn test_early_return_matrix() {
+        for (case, body, expected) in early_return_matrix() {
+            let mut env: Environment<EnvValue> = Environment::new();
+            env.insert_variable(
+                "f".to_string(),
+                EnvValue::Func(Function {
+                    name: "f".to_string(),
+                    kind: Some(TInteger),
+                    params: Some(vec![("x".to_string(), TInteger)]),
+                    body: Some(Box::new(body)),
+                }),
+            );
+
+            assert_eq!(
+                eval(FuncCall("f".to_string(), vec![CInt(0)]), &env),
+                Ok(EnvValue::Exp(expected)),
+                "{}",
+                case
+            );
+        }
+    }
+
+    #[test]
+    fn test_limits_raise_resource_exhausted() {
+        let mut env: Environment<EnvValue> = Environment::new();
+        env.limits = std::sync::Arc::new(crate::ir::ast::Limits {
+            max_bindings: 2,
+            max_list_length: 3,
+            ..Default::default()
+        });
+        let error = |message: &str| Err((message.to_string(), None));
+
+        assert_eq!(
+            eval(CList(vec![CInt(1), CInt(2), CInt(3), CInt(4)]), &env),
+            error("[Resource Exhausted] list of 4 elements exceeds the limit of 3.")
+        );
+        assert_eq!(
+            eval(
+                FuncCall("parse_csv".to_string(), vec![CString("a,b,c,d".to_string())]),
+                &env
+            ),
+            error("[Resource Exhausted] list of 4 elements exceeds the limit of 3.")
+        );
+
+        // Functions run under the caller's limits
+        env.insert_variable(
+            "make".to_string(),
+            EnvValue::Func(Function {
+                name: "make".to_string(),
+                kind: Some(TList(Box::new(TInteger))),
+                params: Some(vec![("n".to_string(), TInteger)]),
+                body: Some(Box::new(Return(Box::new(CList(vec![
+                    Var("n".to_string()),
+                    Var("n".to_string()),
+                    Var("n".to_string()),
+                    Var("n".to_string()),
+                ]))))),
+            }),
+        );
+        assert_eq!(
+            eval(FuncCall("make".to_string(), vec![CInt(1)]), &env),
+            error("[Resource Exhausted] list of 4 elements exceeds the limit of 3.")
+        );
+
+        let program = Sequence(
+            Box::new(Assignment("a".to_string(), Box::new(CInt(1)), None)),
+            Box::new(Assignment("b".to_string(), Box::new(CInt(2)), None)),
+        );
+        match run(program, &env) {
+            Err(s) => assert_eq!(
+                s,
+                "[Resource Exhausted] 3 variables are bound, more than the limit of 2."
+            ),
+            _ => panic!("expected the bindings limit to be exceeded"),
+        }
+    }
+
+    #[test]
+    fn calls_share_the_function_table_of_their_caller() {
+        use crate::ir::build::{func, ret};
+
+        // A call should not pay for the functions it does not use, even
+        // when it binds functions of its own
+        let mut functions = BTreeMap::new();
+        for i in 0..300 {
+            let name = format!("unused_{}", i);
+            functions.insert(name.clone(), EnvValue::Func(func(&name).returns(TInteger).function(vec![ret(i)])));
+        }
+        let mut env: Environment<EnvValue> = Environment::new();
+        env.globals = Snapshot::with(&env.globals, functions);
+        env.insert_frame(Function::new());
+        env.insert_variable("n".to_string(), EnvValue::Exp(CInt(18)));
+        assert!(std::sync::Arc::ptr_eq(&visible_functions(&env), &env.globals));
+
+        // A local helper is a layer on top of the table, not a copy of it
+        env.insert_variable("base".to_string(), EnvValue::Func(Function::new()));
+        let mut callee: Environment<EnvValue> = Environment::new();
+        callee.globals = visible_functions(&env);
+        assert!(callee.globals.get(&"base".to_string()).is_some());
+        assert!(callee.globals.get(&"unused_299".to_string()).is_some());
+        callee.insert_frame(Function::new());
+        assert!(std::sync::Arc::ptr_eq(&visible_functions(&callee), &callee.globals));
+    }
+
+    #[test]
+    fn test_deadline_aborts_loops_and_recursion() {
+        let with_deadline = |deadline: std::time::Duration| {
+            let mut env: Environment<EnvValue> = Environment::new();
+            env.limits = std::sync::Arc::new(crate::ir::ast::Limits {
+                deadline: Some(Instant::now() + deadline),
+                ..Default::default()
+            });
+            env
+        };
+        let timeout = String::from("[Timeout] the program did not finish before its deadline.");
+
+        let forever = While(
+            Box::new(CTrue),
+            Box::new(Assignment("x".to_string(), Box::new(CInt(1)), None)),
+        );
+        let started = Instant::now();
+        match run(forever, &with_deadline(std::time::Duration::from_millis(50))) {
+            Err(s) => assert_eq!(s, timeout),
+            _ => panic!("expected the loop to time out"),
+        }
+        assert!(started.elapsed() < std::time::Duration::from_secs(5));
+
+        let mut env = with_deadline(std::time::Duration::ZERO);
+        env.insert_variable(
+            "f".to_string(),
+            EnvValue::Func(Function {
+                name: "f".to_string(),
+                kind: Some(TInteger),
+                params: Some(vec![]),
+                body: Some(Box::new(Return(Box::new(FuncCall("f".to_string(), vec![]))))),
+            }),
+        );
+        assert_eq!(
+            eval(FuncCall("f".to_string(), vec![]), &env),
+            Err((timeout, None))
+        );
+    }
+
+    #[test]
+    fn test_iteration_order_is_deterministic() {
+        let env: Environment<EnvValue> = Environment::new();
+        let assign = |name: &str, value: i32| Assignment(name.to_string(), Box::new(CInt(value)), None);
+        let sum = Function {
+            name: "sum".to_string(),
+            kind: Some(TInteger),
+            params: Some(vec![]),
+            body: Some(Box::new(Return(Box::new(Add(
+                Box::new(Var("z".to_string())),
+                Box::new(Add(Box::new(Var("a".to_string())), Box::new(Var("m".to_string())))),
+            ))))),
+        };
+        let program = Sequence(
+            Box::new(assign("z", 1)),
+            Box::new(Sequence(
+                Box::new(assign("a", 2)),
+                Box::new(Sequence(Box::new(assign("m", 3)), Box::new(FuncDef(sum)))),
+            )),
+        );
+
+        let env = match run(program, &env) {
+            Ok(ControlFlow::Continue(new_env)) => new_env,
+            _ => panic!("expected the program to run"),
+        };
+        let names: Vec<&Name> = env.get_frame(env.scope_key()).variables.keys().collect();
+        assert_eq!(names, vec!["a", "m", "sum", "z"]);
+
+        match env.search_frame("sum".to_string()) {
+            Some(EnvValue::Exp(Closure(_, captured))) => assert_eq!(
+                captured.iter().map(|(name, _)| name.as_str()).collect::<Vec<_>>(),
+                vec!["a", "m", "z"]
+            ),
+            _ => panic!("expected a closure"),
+        }
+    }
+
+    #[test]
+    fn test_contracts_are_checked_at_call_and_return() {
+        let source = "def half(n: TInteger) -> TInteger:\n    requires n >= 0\n    ensures result * 2 == n\n    return n / 2";
+        let program = crate::parser::parser::parse_program(source).unwrap();
+        let mut env: Environment<EnvValue> = Environment::new();
+        for stmt in program {
+            match execute(stmt, &env) {
+                Ok(ControlFlow::Continue(new_env)) => env = new_env,
+                _ => panic!("expected the definition to run"),
+            }
+        }
+        let half = |n: i32, env: &Environment<EnvValue>| eval(FuncCall("half".to_string(), vec![CInt(n)]), env);
+
+        assert_eq!(half(8, &env), Ok(EnvValue::Exp(CInt(4))));
+        assert_eq!(
+            half(-2, &env),
+            Err((
+                String::from("[Contract Violation] precondition 'n >= 0' of 'half()' does not hold: it evaluated to False."),
+                None
+            ))
+        );
+        assert_eq!(
+            half(7, &env),
+            Err((
+                String::from("[Contract Violation] postcondition '(result * 2) == n' of 'half()' does not hold: it evaluated to False."),
+                None
+            ))
+        );
+
+        env.check_contracts = false;
+        assert_eq!(half(7, &env), Ok(EnvValue::Exp(CInt(3))));
+    }
+
+    #[test]
+    fn test_loop_invariants_under_debug_flag() {
+        let source = "i = 0\nwhile i < 5:\n    invariant i * 2 < limit\n    i = i + 1";
+        let program = crate::ir::desugar::program(crate::parser::parser::parse_program(source).unwrap());
+        let run_with = |check_invariants: bool| {
+            let mut env: Environment<EnvValue> = Environment::new();
+            env.check_invariants = check_invariants;
+            env.insert_variable("limit".to_string(), EnvValue::Exp(CInt(7)));
+            program
+                .iter()
+                .try_fold(env, |env, stmt| match run(stmt.clone(), &env)? {
+                    ControlFlow::Continue(env) => Ok(env),
+                    ControlFlow::Return(_) => Err(String::from("unexpected return")),
+                })
+                .map(|env| env.search_frame("i".to_string()).cloned())
+        };
+
+        assert_eq!(run_with(false), Ok(Some(EnvValue::Exp(CInt(5)))));
+        assert_eq!(
+            run_with(true),
+            Err(String::from(
+                "[Invariant Violated] '(i * 2) < limit' of the loop 'while i < 5' does not hold after iteration 4: it evaluated to False with i = 4, limit = 7."
+            ))
+        );
+    }
+
+    #[test]
+    fn test_decreasing_measures() {
+        let run_source = |source: &str| {
+            let program = crate::ir::desugar::program(crate::parser::parser::parse_program(source).unwrap());
+            program
+                .into_iter()
+                .try_fold(Environment::new(), |env, stmt| match run(stmt, &env)? {
+                    ControlFlow::Continue(env) => Ok(env),
+                    ControlFlow::Return(_) => Err(String::from("unexpected return")),
+                })
+                .map(|env| env.search_frame("x".to_string()).cloned())
+        };
+
+        assert_eq!(
+            run_source("x = 0\nwhile x < 3:\n    decreases 3 - x\n    x += 1"),
+            Ok(Some(EnvValue::Exp(CInt(3))))
+        );
+        assert_eq!(
+            run_source("x = 0\nwhile x < 3:\n    decreases 3 - x\n    x += 0"),
+            Err(String::from("[Termination Error] the measure '3 - x' of the loop 'while x < 3' did not decrease in iteration 1: it went from 3 to 3."))
+        );
+        assert_eq!(
+            run_source("x = 0 - 1\nwhile x < 3:\n    decreases x\n    x += 1"),
+            Err(String::from("[Termination Error] the measure 'x' of the loop 'while x < 3' is negative: -1."))
+        );
+
+        let countdown = |step: &str| {
+            format!(
+                "def count(n: TInteger) -> TInteger:\n    decreases n\n    if n == 0:\n        return 0\n    else:\n        return count({})\nx = count(3)",
+                step
+            )
+        };
+        assert_eq!(run_source(&countdown("n - 1")), Ok(Some(EnvValue::Exp(CInt(0)))));
+        assert_eq!(
+            run_source(&countdown("n")),
+            Err(String::from("[Termination Error] the measure 'n' of 'count()' did not decrease in a recursive call: it went from 3 to 3."))
+        );
+    }
+    #[test]
+    fn observers_see_every_binding_change() {
+        use crate::ir::ast::Observer;
+        use std::sync::{Arc, Mutex};
+
+        let changes = Arc::new(Mutex::new(vec![]));
+        let mut env = Environment::new();
+        env.observer = Observer::new({
+            let changes = changes.clone();
+            move |change: &Change<EnvValue>| {
+                let value = |value: &EnvValue| render(value);
+                changes.lock().unwrap().push(format!(
+                    "{}: {} -> {} at `{}`",
+                    change.name,
+                    change.old.as_ref().map_or(String::from("unbound"), value),
+                    value(&change.new),
+                    change.span
+                ))
+            }
+        });
+        let program = crate::rpy!("
+            x = 1
+            def inc(n: TInteger) -> TInteger:
+                return n + 1
+            x = inc(x)
+        ");
+        for stmt in program {
+            if let ControlFlow::Continue(new_env) = run(stmt, &env).unwrap() {
+                env = new_env;
+            }
+        }
+
+        assert_eq!(
+            *changes.lock().unwrap(),
+            vec![
+                "x: unbound -> 1 at `x = 1`",
+                "n: unbound -> 1 at `inc(1)`",
+                "x: 1 -> 2 at `x = inc(x)`",
+            ]
+        );
+    }
+}
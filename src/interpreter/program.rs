@@ -0,0 +1,455 @@
+use crate::interpreter::interpreter::{eval, run, ControlFlow, EnvValue, RESOURCE_EXHAUSTED};
+use crate::ir::ast::{Environment, Expression, Io, Limits, Statement};
+use crate::ir::{dataflow, desugar};
+use crate::parser::parser::{parse_located, parse_program};
+use crate::tc::type_checker::CheckedProgram;
+use std::panic;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Native stack reserved for each interpreted call: about twice what a call
+/// whose body nests a loop, two `if`s and a `match` takes. Unoptimized
+/// builds give every temporary of `eval` a slot of its own and need
+/// several times more.
+const CALL_STACK: usize = if cfg!(debug_assertions) { 1 << 20 } else { 128 << 10 };
+
+/// Everything a run of a whole program produced.
+#[derive(Debug, Clone)]
+pub struct ProgramOutcome {
+    /// What a top-level `return` or `main()` returned.
+    pub value: Option<EnvValue>,
+    /// What the program printed.
+    pub stdout: String,
+    /// Syntax, type and runtime errors and lint warnings, in the order the
+    /// pipeline found them. Warnings do not stop the run; a program with a
+    /// syntax or type error does not run at all.
+    pub diagnostics: Vec<String>,
+    /// The top-level bindings when the program stopped.
+    pub final_env: Environment<EnvValue>,
+    pub stats: Stats,
+}
+
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct Stats {
+    /// Top-level statements that ran to completion.
+    pub statements: usize,
+    pub elapsed: Duration,
+}
+
+impl ProgramOutcome {
+    pub fn succeeded(&self) -> bool {
+        !self.diagnostics.iter().any(|d| !d.starts_with("[Warning]"))
+    }
+}
+
+/// Parses, checks and runs `source`, capturing what it prints.
+pub fn run_program(source: &str) -> ProgramOutcome {
+    let mut env = Environment::new();
+    env.io = Io::captured();
+    run_program_in(source, env)
+}
+
+/// Like `run_program`, but starts from `env`, whose streams and limits the
+/// program uses.
+pub fn run_program_in(source: &str, env: Environment<EnvValue>) -> ProgramOutcome {
+    let started = Instant::now();
+    let io = env.io.clone();
+    let mut outcome = ProgramOutcome {
+        value: None,
+        stdout: String::new(),
+        diagnostics: vec![],
+        final_env: env,
+        stats: Stats::default(),
+    };
+
+    let program = match parse_program(source) {
+        Ok(program) => desugar::program(program),
+        Err(message) => {
+            outcome.diagnostics.push(message);
+            return outcome;
+        }
+    };
+    let checked = CheckedProgram::check(program.clone());
+    if let Err(message) = &checked {
+        outcome.diagnostics.push(message.clone());
+    }
+    // Parsed again with positions, for the warnings to point at the code
+    let located = parse_located(source).map(desugar::program).unwrap_or_default();
+    outcome
+        .diagnostics
+        .extend(dataflow::program(&located).iter().map(|warning| warning.render()));
+    let Ok(program) = checked else {
+        return outcome;
+    };
+
+    let mut env = outcome.final_env.clone();
+    match execute_program(&program, &mut env, &mut outcome.stats) {
+        Ok(value) => outcome.value = value,
+        Err(message) => outcome.diagnostics.push(message),
+    }
+    outcome.final_env = env;
+    outcome.stdout = io.output().unwrap_or_default();
+    outcome.stats.elapsed = started.elapsed();
+    outcome
+}
+
+/// Runs the top-level statements of a checked program, then `main()` if
+/// the program defines it, and returns what either returned. `env` is left
+/// with the top-level bindings.
+pub fn execute_program(
+    program: &CheckedProgram,
+    env: &mut Environment<EnvValue>,
+    stats: &mut Stats,
+) -> Result<Option<EnvValue>, String> {
+    let limits = env.limits.clone();
+    with_stack(&limits, || execute_checked(program, env, stats))?
+}
+
+fn execute_checked(
+    program: &CheckedProgram,
+    env: &mut Environment<EnvValue>,
+    stats: &mut Stats,
+) -> Result<Option<EnvValue>, String> {
+    if let Some(value) = execute_statements(program.statements(), env, stats)? {
+        return Ok(Some(value));
+    }
+
+    match env.search_frame("main".to_string()) {
+        Some(EnvValue::Func(_) | EnvValue::Exp(Expression::Closure(..))) => {
+            let value = eval(Expression::FuncCall("main".to_string(), vec![]), env);
+            value.map(Some).map_err(|(message, _)| message)
+        }
+        _ => Ok(None),
+    }
+}
+
+/// Runs `f` on a thread with a stack for `limits.max_calls` calls, so that
+/// a runaway recursion ends in the call limit error instead of overflowing
+/// the stack, which aborts the whole process. A panic in `f` is raised
+/// again in the caller.
+pub fn with_stack<T: Send>(limits: &Limits, f: impl FnOnce() -> T + Send) -> Result<T, String> {
+    let size = limits.max_calls.saturating_add(1).saturating_mul(CALL_STACK);
+    thread::scope(|scope| {
+        let worker = thread::Builder::new().stack_size(size).spawn_scoped(scope, f).map_err(|e| {
+            format!("{} no stack for {} calls: {}", RESOURCE_EXHAUSTED, limits.max_calls, e)
+        })?;
+        Ok(worker.join().unwrap_or_else(|payload| panic::resume_unwind(payload)))
+    })
+}
+
+/// Runs the top-level statements only, returning the value of a top-level
+/// `return`.
+pub fn execute_statements(
+    program: &[Statement],
+    env: &mut Environment<EnvValue>,
+    stats: &mut Stats,
+) -> Result<Option<EnvValue>, String> {
+    for stmt in program {
+        match run(stmt.clone(), env)? {
+            ControlFlow::Continue(new_env) => *env = new_env,
+            ControlFlow::Return(value) => return Ok(Some(value)),
+        }
+        stats.statements += 1;
+    }
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Runs `source`, indented as a whole, and returns what it printed; it
+    /// must run without diagnostics.
+    fn printed(source: &str) -> String {
+        let outcome = run_program(&crate::parser::parser::dedent(source));
+        assert_eq!(outcome.diagnostics, Vec::<String>::new());
+        outcome.stdout
+    }
+
+    /// The diagnostics of running `source`, indented as a whole.
+    fn diagnostics(source: &str) -> Vec<String> {
+        run_program(&crate::parser::parser::dedent(source)).diagnostics
+    }
+
+    #[test]
+    fn run_program_collects_output_value_and_diagnostics() {
+        let outcome = run_program("x = 20\ndef main() -> TInteger:\n    print(\"hi\")\n    return x + 1");
+        assert!(outcome.succeeded());
+        assert_eq!(outcome.value, Some(EnvValue::Exp(Expression::CInt(21))));
+        assert_eq!(outcome.stdout, "hi\n");
+        assert_eq!(outcome.stats.statements, 2);
+        assert_eq!(
+            outcome.final_env.search_frame("x".to_string()),
+            Some(&EnvValue::Exp(Expression::CInt(20)))
+        );
+
+        // Type errors keep the program from running
+        let outcome = run_program("d = 0\nprint(\"before\")\ny = 4 / d\nz = missing(1)\nprint(\"after\")");
+        assert!(!outcome.succeeded());
+        assert_eq!(outcome.stdout, "");
+        assert_eq!(outcome.stats.statements, 0);
+        assert_eq!(outcome.value, None);
+        assert_eq!(outcome.diagnostics[0], "[Name Error on '__main__()'] 'missing()' is not defined.");
+        assert!(outcome.diagnostics.contains(&String::from(
+            "[Warning] in '__main__', line 3, column 1: `y = 4 / d`: division by zero: 'd' is always 0."
+        )));
+
+        let mut env = Environment::new();
+        env.io = Io::scripted("");
+        let outcome = run_program_in("print(\"before\")\nx = input()\nprint(\"after\")", env);
+        assert!(!outcome.succeeded());
+        assert_eq!(outcome.stdout, "before\n");
+        assert_eq!(outcome.stats.statements, 1);
+        assert!(outcome.diagnostics.last().unwrap().contains("input"));
+
+        let outcome = run_program("x = = 1");
+        assert_eq!(outcome.diagnostics.len(), 1);
+        assert_eq!(outcome.stats.statements, 0);
+    }
+
+    #[test]
+    fn runaway_recursion_hits_the_call_limit() {
+        let source = "
+            def down(n: TInteger) -> TInteger:
+                i = 0
+                while i < 1:
+                    i = i + 1
+                    if n > 0:
+                        match n {
+                            0 => return 0,
+                            _ => return 1 + down(n + 1)
+                        }
+                return 0
+            x = down(1)
+        ";
+        assert_eq!(
+            diagnostics(source),
+            vec![format!(
+                "{} more than {} calls are active; is the recursion missing a base case?",
+                RESOURCE_EXHAUSTED,
+                Limits::default().max_calls
+            )]
+        );
+    }
+
+    #[test]
+    fn stacks_are_sized_for_the_call_limit() {
+        let limits = |max_calls| Limits {
+            max_calls,
+            ..Limits::default()
+        };
+        assert_eq!(with_stack(&limits(8), || 7), Ok(7));
+
+        // No thread has room for this many calls
+        let refused = with_stack(&limits(usize::MAX), || 7).unwrap_err();
+        assert!(refused.starts_with(&format!("{} no stack for {} calls", RESOURCE_EXHAUSTED, usize::MAX)));
+    }
+
+    #[test]
+    fn match_arms_test_literals_of_the_scrutinee_type() {
+        let source = "
+            def describe(n: TInteger) -> TString:
+                match n {
+                    0 => return \"zero\",
+                    -1 => return \"minus one\",
+                    _ => return \"other\"
+                }
+            def agrees(answer: TString) -> TBool:
+                match answer {
+                    \"yes\" => return True,
+                    _ => return False
+                }
+            def halves(x: TReal) -> TBool:
+                match x {
+                    0.5 => return True,
+                    _ => return False
+                }
+            print(describe(0))
+            print(describe(-1))
+            print(describe(7))
+            print(agrees(\"yes\"))
+            print(halves(0.25))
+        ";
+        assert_eq!(printed(source), "zero\nminus one\nother\nTrue\nFalse\n");
+
+        assert_eq!(
+            diagnostics("def f(n: TInteger) -> TInteger:\n    match n {\n        \"yes\" => return 1,\n        _ => return 0\n    }"),
+            vec![String::from("[Type Error in 'f'] the pattern '\"yes\"' cannot match a value of type 'TInteger'.")]
+        );
+    }
+
+    #[test]
+    fn tuples_are_built_destructured_and_matched() {
+        let source = "
+            def split(n: TInteger) -> (TInteger, TBool):
+                return (n * 2, n > 0)
+            def describe(pair: (TInteger, TBool)) -> TString:
+                match pair {
+                    (0, _) => return \"zero\",
+                    (n, True) => return \"positive\",
+                    _ => return \"negative\"
+                }
+            (doubled, positive) = split(21)
+            nested = (\"x\", (1, 2.5))
+            (name, (i, r)) = nested
+            print(doubled)
+            print(positive)
+            print(describe(split(0)))
+            print(describe(split(3)))
+            print(describe(split(-3)))
+            print(name)
+            print(r)
+            print(nested)
+        ";
+        assert_eq!(printed(source), "42\nTrue\nzero\npositive\nnegative\nx\n2.5\n(\"x\", (1, 2.5))\n");
+
+        assert_eq!(
+            diagnostics("pair = (1, True)\n(a, b, c) = pair"),
+            vec![String::from("[Type Error in '__main__'] the pattern '(a, b, c)' cannot match a value of type 'TTuple([TInteger, TBool])'.")]
+        );
+        assert_eq!(
+            diagnostics("pair = (1, True)\n(a, b) = pair\nc = a + b"),
+            vec![String::from("[Type Error] expecting numeric type values.")]
+        );
+    }
+
+    #[test]
+    fn as_patterns_bind_the_whole_value_for_the_guard_and_the_arm() {
+        let source = "
+            def same(p: (TInteger, TInteger)) -> TBool:
+                match p {
+                    (x, y) => return x == y
+                }
+            def describe(p: (TInteger, TInteger)) -> TString:
+                match p {
+                    (x, _) as pair if same(pair) => return format(\"{} twice\", x),
+                    (0, _) as pair => return format(\"{} starts at zero\", pair),
+                    _ => return \"other\"
+                }
+            print(describe((2, 2)))
+            print(describe((0, 5)))
+            print(describe((1, 5)))
+        ";
+        assert_eq!(printed(source), "2 twice\n(0, 5) starts at zero\nother\n");
+
+        assert_eq!(
+            diagnostics("pair = (1, 2)\nmatch pair {\n    (x, y) as x => print(y)\n}"),
+            vec![String::from("[Type Error in '__main__'] 'x' is bound twice in the pattern.")]
+        );
+    }
+
+    #[test]
+    fn adts_without_values_need_no_arms() {
+        let source = "
+            adt Never
+            adt Outcome = Done Int | Failed Never
+            adt Stream = Next Int Stream
+            def absurd(n: Never) -> TInteger:
+                match n {
+                }
+                return 0
+            def value(o: Outcome) -> TInteger:
+                match o {
+                    Outcome Done n => return n
+                }
+                return 0
+            def first(s: Stream) -> TInteger:
+                match s {
+                    _ => return 0
+                }
+                return 0
+            print(1)
+        ";
+        assert_eq!(printed(source), "1\n");
+    }
+
+    #[test]
+    fn equality_reads_any_two_values_of_a_type() {
+        let source = "
+            name = \"ada\"
+            point = (1, \"x\")
+            found = Just(point)
+            print(name == \"ada\")
+            print(name != \"bob\")
+            print(True != False)
+            print(point == (1, \"y\"))
+            print(found == Just((1, \"x\")))
+        ";
+        assert_eq!(printed(source), "True\nTrue\nTrue\nFalse\nTrue\n");
+    }
+
+    #[test]
+    fn question_marks_return_nothing_and_errors_early() {
+        let source = "
+            def half(n: TInteger) -> TMaybe[TInteger]:
+                if n == 0:
+                    return Nothing
+                return Just(n / 2)
+            def quarter(n: TInteger) -> TMaybe[TInteger]:
+                h = half(n)?
+                return Just(half(h)?)
+            def check(n: TInteger) -> TResult[TInteger, TString]:
+                if n < 0:
+                    return Err(\"negative\")
+                return Ok(n)
+            def twice(n: TInteger) -> TResult[TInteger, TString]:
+                return Ok(check(n)? * 2)
+            print(quarter(8))
+            print(quarter(0))
+            print(twice(3))
+            print(twice(-1))
+        ";
+        assert_eq!(printed(source), "Just(2)\nNothing\nOk(6)\nErr(\"negative\")\n");
+    }
+
+    #[test]
+    fn functions_apply_infix_between_backticks() {
+        let source = "
+            def max(a: TInteger, b: TInteger) -> TInteger:
+                if a > b:
+                    return a
+                return b
+            def pow(a: TInteger, b: TInteger) -> TInteger:
+                if b == 0:
+                    return 1
+                return a * pow(a, b - 1)
+            infixl 6 max
+            infixr 8 pow
+            print(1 + 5 `max` 2 * 3)
+            print(2 `pow` 3 `pow` 2)
+            print((1 `max` 4) * 2 `pow` 2)
+        ";
+        assert_eq!(printed(source), "6\n512\n16\n");
+    }
+
+    #[test]
+    fn functions_defined_by_clauses_return_the_first_that_matches() {
+        let source = "
+            def fact(0) -> TInteger = 1
+            def fact(n: TInteger) = n * fact(n - 1)
+            def sign(n: TInteger) -> TString if n < 0 = \"negative\"
+            def sign(0) = \"zero\"
+            def sign(_) = \"positive\"
+            def pick(True, x: TInteger, _) -> TInteger = x
+            def pick(False, _, y: TInteger) = y
+            print(fact(5))
+            print(sign(0 - 3))
+            print(sign(0))
+            print(sign(7))
+            print(pick(False, 1, 2))
+        ";
+        assert_eq!(printed(source), "120\nnegative\nzero\npositive\n2\n");
+
+        assert_eq!(
+            diagnostics("def half(0) -> TInteger = 0\nx = half(3)"),
+            vec![String::from("[Match Failure] no pattern matches 3 of type 'Int'.")]
+        );
+
+        // A clause the clauses before it cover never runs
+        let program = CheckedProgram::check(crate::rpy!("def f(_) -> TInteger = 1\ndef f(0) = 2")).unwrap();
+        assert_eq!(
+            program.lints(),
+            ["[Warning] in 'f': the arm '0' is unreachable; the arms before it match everything it does."]
+        );
+    }
+}
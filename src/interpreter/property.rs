@@ -0,0 +1,278 @@
+use crate::interpreter::generate::{constructors, Adts, Generator};
+use crate::ir::ast::{AdtValue, Expression, Type};
+use crate::ir::complex::Complex;
+use crate::ir::decimal::Decimal;
+
+/// How many shrinking steps are tried before the smallest case found so
+/// far is reported.
+const MAX_SHRINKS: usize = 1_000;
+
+/// A value that makes a property fail, before and after shrinking.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Counterexample {
+    /// How many generated cases ran, the failing one included.
+    pub runs: usize,
+    pub original: Vec<Expression>,
+    pub shrunk: Vec<Expression>,
+    pub shrinks: usize,
+    /// Why the shrunk case fails.
+    pub message: String,
+}
+
+/// Calls `property` on `runs` argument lists of the given types, each
+/// generated a little larger than the one before. The first failing list is
+/// shrunk while it keeps failing, one argument at a time.
+pub fn check_property(
+    types: &[Type],
+    adts: &Adts,
+    runs: usize,
+    seed: u64,
+    mut property: impl FnMut(&[Expression]) -> Result<(), String>,
+) -> Result<(), Counterexample> {
+    let mut generator = Generator::new(seed, adts);
+    let types: Vec<Type> = types.iter().map(|kind| generator.resolve(kind)).collect();
+    for run in 0..runs {
+        generator.size = 2 + run / 2;
+        let args = types
+            .iter()
+            .map(|kind| generator.value(kind))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|message| Counterexample {
+                runs: run,
+                original: vec![],
+                shrunk: vec![],
+                shrinks: 0,
+                message,
+            })?;
+
+        if let Err(message) = property(&args) {
+            let (shrunk, shrinks, message) = minimize(&types, adts, args.clone(), message, &mut property);
+            return Err(Counterexample {
+                runs: run + 1,
+                original: args,
+                shrunk,
+                shrinks,
+                message,
+            });
+        }
+    }
+    Ok(())
+}
+
+fn minimize(
+    types: &[Type],
+    adts: &Adts,
+    mut args: Vec<Expression>,
+    mut message: String,
+    property: &mut impl FnMut(&[Expression]) -> Result<(), String>,
+) -> (Vec<Expression>, usize, String) {
+    let mut shrinks = 0;
+    'smaller: while shrinks < MAX_SHRINKS {
+        for (i, kind) in types.iter().enumerate() {
+            for candidate in shrink(&args[i], kind, adts) {
+                let mut trial = args.clone();
+                trial[i] = candidate;
+                if let Err(failure) = property(&trial) {
+                    args = trial;
+                    message = failure;
+                    shrinks += 1;
+                    continue 'smaller;
+                }
+            }
+        }
+        break;
+    }
+    (args, shrinks, message)
+}
+
+/// Values simpler than `value`, the simplest first: numbers closer to zero,
+/// shorter strings and lists, and ADT values with fewer constructors.
+pub fn shrink(value: &Expression, kind: &Type, adts: &Adts) -> Vec<Expression> {
+    let mut candidates = match (value, kind) {
+        (Expression::CInt(n), _) => {
+            let mut smaller = vec![0, n / 2, n - n.signum()];
+            if *n < 0 {
+                smaller.insert(1, -n);
+            }
+            smaller.into_iter().map(Expression::CInt).collect()
+        }
+        (Expression::CReal(x), _) => {
+            vec![Expression::CReal(0.0), Expression::CReal(x.trunc()), Expression::CReal(x / 2.0)]
+        }
+        (Expression::CComplex(z), _) => {
+            vec![Complex::from(0.0), Complex::from(z.re), Complex::new(0.0, z.im), Complex::new(z.re / 2.0, z.im / 2.0)]
+                .into_iter()
+                .map(Expression::CComplex)
+                .collect()
+        }
+        (Expression::CDecimal(d), _) => {
+            vec![Decimal::from(0), Decimal::new(d.trunc(), 0), d.rounded(d.scale().saturating_sub(1))]
+                .into_iter()
+                .map(Expression::CDecimal)
+                .collect()
+        }
+        (Expression::CTrue, _) => vec![Expression::CFalse],
+        (Expression::CString(s), _) => {
+            let chars: Vec<char> = s.chars().collect();
+            shorter(&chars)
+                .into_iter()
+                .map(|chars| Expression::CString(chars.into_iter().collect()))
+                .collect()
+        }
+        (Expression::CBytes(bytes), _) => shorter(bytes).into_iter().map(Expression::CBytes).collect(),
+        (Expression::CList(items), Type::TList(item)) => {
+            let mut smaller: Vec<Expression> = shorter(items).into_iter().map(Expression::CList).collect();
+            for (i, element) in items.iter().enumerate() {
+                for simpler in shrink(element, item, adts) {
+                    let mut items = items.clone();
+                    items[i] = simpler;
+                    smaller.push(Expression::CList(items));
+                }
+            }
+            smaller
+        }
+        (Expression::CDict(entries), Type::TDict(_, value)) => {
+            let mut smaller: Vec<Expression> = shorter(entries).into_iter().map(Expression::CDict).collect();
+            for (i, (_, v)) in entries.iter().enumerate() {
+                for simpler in shrink(v, value, adts) {
+                    let mut entries = entries.clone();
+                    entries[i].1 = simpler;
+                    smaller.push(Expression::CDict(entries));
+                }
+            }
+            smaller
+        }
+        (Expression::CJust(inner), Type::TMaybe(item)) => std::iter::once(Expression::CNothing)
+            .chain(shrink(inner, item, adts).into_iter().map(|e| Expression::CJust(Box::new(e))))
+            .collect(),
+        (Expression::COk(inner), Type::TResult(ok, _)) => {
+            shrink(inner, ok, adts).into_iter().map(|e| Expression::COk(Box::new(e))).collect()
+        }
+        (Expression::CErr(inner), Type::TResult(_, err)) => {
+            shrink(inner, err, adts).into_iter().map(|e| Expression::CErr(Box::new(e))).collect()
+        }
+        (Expression::ADTValue(value), Type::Tadt(..)) => {
+            let Ok(constructors) = constructors(kind, adts) else {
+                return vec![];
+            };
+            let fields = &constructors[value.tag].types;
+            // Arguments of the same type can replace the whole value
+            let mut smaller: Vec<Expression> = value
+                .fields
+                .iter()
+                .zip(fields)
+                .filter(|(_, field)| matches!(field, Type::Tadt(field, _) if *field == value.adt))
+                .map(|(arg, _)| (**arg).clone())
+                .collect();
+            smaller.extend(
+                constructors[..value.tag]
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, constructor)| constructor.types.is_empty())
+                    .map(|(tag, constructor)| {
+                        Expression::ADTValue(AdtValue::new(value.adt.clone(), constructor.name.clone(), tag, vec![].into()))
+                    }),
+            );
+            for (i, (arg, field)) in value.fields.iter().zip(fields).enumerate() {
+                for simpler in shrink(arg, field, adts) {
+                    let mut simpler_value = value.clone();
+                    *simpler_value.fields[i] = simpler;
+                    smaller.push(Expression::ADTValue(simpler_value));
+                }
+            }
+            smaller
+        }
+        _ => vec![],
+    };
+    let mut seen = vec![];
+    candidates.retain(|candidate| {
+        let fresh = candidate != value && !seen.contains(candidate);
+        seen.push(candidate.clone());
+        fresh
+    });
+    candidates
+}
+
+/// `items` without its second half, its first half, then each element.
+fn shorter<T: Clone>(items: &[T]) -> Vec<Vec<T>> {
+    if items.is_empty() {
+        return vec![];
+    }
+    let mut smaller = vec![vec![], items[..items.len() / 2].to_vec(), items[items.len() / 2..].to_vec()];
+    for i in 0..items.len() {
+        let mut items = items.to_vec();
+        items.remove(i);
+        smaller.push(items);
+    }
+    smaller
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ir::ast::ValueConstructor;
+
+    fn largest(values: &Expression) -> i32 {
+        match values {
+            Expression::CList(items) => items.iter().map(largest).max().unwrap_or(0),
+            Expression::CInt(n) => *n,
+            _ => 0,
+        }
+    }
+
+    #[test]
+    fn failing_lists_shrink_to_a_minimal_case() {
+        let list = Type::TList(Box::new(Type::TInteger));
+        // "no element reaches 10"
+        let result = check_property(&[list], &Adts::new(), 100, 7, |args| match largest(&args[0]) {
+            n if n < 10 => Ok(()),
+            n => Err(format!("found {}", n)),
+        });
+
+        let counterexample = result.unwrap_err();
+        assert_eq!(counterexample.shrunk, vec![Expression::CList(vec![Expression::CInt(10)])]);
+        assert_eq!(counterexample.message, "found 10");
+        assert!(counterexample.shrinks > 0);
+
+        let ints = [Type::TInteger, Type::TInteger];
+        assert_eq!(check_property(&ints, &Adts::new(), 100, 7, |_| Ok(())), Ok(()));
+    }
+
+    #[test]
+    fn adt_values_shrink_to_simpler_constructors() {
+        let tree = Type::Tadt(String::from("Tree"), vec![]);
+        let adts = Adts::from([(
+            String::from("Tree"),
+            vec![
+                ValueConstructor { name: String::from("Leaf"), types: vec![] },
+                ValueConstructor { name: String::from("Node"), types: vec![tree.clone(), Type::TInteger, tree.clone()] },
+            ],
+        )]);
+        let value = |constructor: &str, tag, fields: Vec<Box<Expression>>| {
+            Expression::ADTValue(AdtValue::new(String::from("Tree"), String::from(constructor), tag, fields.into()))
+        };
+        let leaf = || Box::new(value("Leaf", 0, vec![]));
+        let node = |left, n, right| value("Node", 1, vec![left, Box::new(Expression::CInt(n)), right]);
+
+        let value = node(Box::new(node(leaf(), 3, leaf())), -4, leaf());
+        let candidates = shrink(&value, &tree, &adts);
+        assert_eq!(candidates[0], node(leaf(), 3, leaf()));
+        assert_eq!(candidates[1], *leaf());
+
+        // "no tree holds a negative number"
+        fn negative(tree: &Expression) -> bool {
+            match tree {
+                Expression::ADTValue(value) => value.fields.iter().any(|arg| match &**arg {
+                    Expression::CInt(n) => *n < 0,
+                    other => negative(other),
+                }),
+                _ => false,
+            }
+        }
+        let result = check_property(&[tree], &adts, 200, 3, |args| match negative(&args[0]) {
+            true => Err(String::from("negative")),
+            false => Ok(()),
+        });
+        assert_eq!(result.unwrap_err().shrunk, vec![node(leaf(), -1, leaf())]);
+    }
+}
@@ -0,0 +1,353 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use crate::interpreter::interpreter::EnvValue;
+use crate::interpreter::value::render;
+use crate::ir::ast::{Change, Name, Observer};
+use crate::ir::json;
+
+/// A state change of a recorded run, as `Change` describes it. Values are
+/// kept as they print, so every run can be recorded, functions included.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Event {
+    pub name: Name,
+    pub old: Option<String>,
+    pub new: String,
+    pub span: String,
+    pub scope: Name,
+    pub depth: u32,
+    pub parameter: bool,
+}
+
+/// The state changes of a run, in the order it made them.
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct Recording {
+    pub events: Vec<Event>,
+}
+
+/// Written as one line per event, with tab-separated fields and `-` for
+/// a missing old value.
+const HEADER: &str = "# rpython recording 1";
+
+impl Recording {
+    /// An observer that appends every change of a run to `recording`.
+    pub fn observer(recording: &Arc<Mutex<Recording>>) -> Observer<EnvValue> {
+        let recording = recording.clone();
+        Observer::new(move |change: &Change<EnvValue>| {
+            recording.lock().unwrap().events.push(Event {
+                name: change.name.clone(),
+                old: change.old.as_ref().map(render),
+                new: render(&change.new),
+                span: change.span.clone(),
+                scope: change.scope.clone(),
+                depth: change.depth,
+                parameter: change.parameter,
+            })
+        })
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        std::fs::write(path, self.to_text())
+            .map_err(|e| format!("cannot write '{}': {}", path.display(), e))
+    }
+
+    pub fn load(path: &Path) -> Result<Recording, String> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| format!("cannot read '{}': {}", path.display(), e))?;
+        Recording::parse(&text).map_err(|message| format!("'{}': {}", path.display(), message))
+    }
+
+    pub fn to_text(&self) -> String {
+        let mut out = format!("{}\n", HEADER);
+        for event in &self.events {
+            let fields = [
+                event.depth.to_string(),
+                escape(&event.scope),
+                String::from(if event.parameter { "param" } else { "assign" }),
+                escape(&event.name),
+                event.old.as_deref().map_or(String::from("-"), escape),
+                escape(&event.new),
+                escape(&event.span),
+            ];
+            out.push_str(&fields.join("\t"));
+            out.push('\n');
+        }
+        out
+    }
+
+    /// The run as a JSON timeline, for a visualizer that steps through it
+    /// in the browser: one step per event, with the binding it made, the
+    /// statement that made it and the call stack after it, the top level
+    /// first, e.g.
+    ///
+    /// `{"version": 1, "steps": [{"step": 1, "span": "x = 1", "scope":
+    /// "__main__", "name": "x", "old": null, "new": "1", "parameter": false,
+    /// "stack": [{"scope": "__main__", "variables": {"x": "1"}}]}]}`
+    ///
+    /// Values are strings, written as `print` writes them.
+    pub fn to_timeline(&self) -> String {
+        let mut frames = vec![];
+        let steps: Vec<String> = self
+            .events
+            .iter()
+            .enumerate()
+            .map(|(index, event)| {
+                apply(&mut frames, event);
+                let stack: Vec<String> = frames
+                    .iter()
+                    .map(|frame| {
+                        let variables: Vec<String> = frame
+                            .variables
+                            .iter()
+                            .map(|(name, value)| format!("{}: {}", json::string(name), json::string(value)))
+                            .collect();
+                        format!(
+                            "{{\"scope\": {}, \"variables\": {{{}}}}}",
+                            json::string(&frame.scope),
+                            variables.join(", ")
+                        )
+                    })
+                    .collect();
+                format!(
+                    "{{\"step\": {}, \"span\": {}, \"scope\": {}, \"name\": {}, \"old\": {}, \"new\": {}, \"parameter\": {}, \"stack\": [{}]}}",
+                    index + 1,
+                    json::string(&event.span),
+                    json::string(&event.scope),
+                    json::string(&event.name),
+                    event.old.as_deref().map_or(String::from("null"), json::string),
+                    json::string(&event.new),
+                    event.parameter,
+                    stack.join(", ")
+                )
+            })
+            .collect();
+        format!("{{\"version\": 1, \"steps\": [{}]}}", steps.join(", "))
+    }
+
+    pub fn parse(text: &str) -> Result<Recording, String> {
+        let mut lines = text.lines().enumerate();
+        if lines.next().map(|(_, line)| line) != Some(HEADER) {
+            return Err(String::from("not a recording."));
+        }
+
+        let mut events = vec![];
+        for (number, line) in lines {
+            let error = || format!("line {} is not an event.", number + 1);
+            let fields: Vec<&str> = line.split('\t').collect();
+            let [depth, scope, kind, name, old, new, span] = fields.as_slice() else {
+                return Err(error());
+            };
+            events.push(Event {
+                name: unescape(name),
+                old: (*old != "-").then(|| unescape(old)),
+                new: unescape(new),
+                span: unescape(span),
+                scope: unescape(scope),
+                depth: depth.parse().map_err(|_| error())?,
+                parameter: match *kind {
+                    "param" => true,
+                    "assign" => false,
+                    _ => return Err(error()),
+                },
+            });
+        }
+        Ok(Recording { events })
+    }
+}
+
+/// Fields never contain a raw tab or line break, and a lone `-` only
+/// stands for a missing value.
+fn escape(text: &str) -> String {
+    let escaped = text.replace('\\', "\\\\").replace('\t', "\\t").replace('\n', "\\n");
+    match escaped.as_str() {
+        "-" => String::from("\\-"),
+        _ => escaped,
+    }
+}
+
+fn unescape(text: &str) -> String {
+    let mut out = String::new();
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        match (c, c == '\\') {
+            (_, true) => match chars.next() {
+                Some('t') => out.push('\t'),
+                Some('n') => out.push('\n'),
+                Some(other) => out.push(other),
+                None => out.push('\\'),
+            },
+            (c, false) => out.push(c),
+        }
+    }
+    out
+}
+
+/// The live variables of a call, for a replay.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Frame {
+    pub scope: Name,
+    pub variables: BTreeMap<Name, String>,
+}
+
+/// Steps forward and backward through a recording. The state after a step
+/// is rebuilt from the start, so stepping back is always exact.
+#[derive(Debug, Clone)]
+pub struct Replay {
+    recording: Recording,
+    position: usize,
+}
+
+impl Replay {
+    pub fn new(recording: Recording) -> Replay {
+        Replay { recording, position: 0 }
+    }
+
+    /// How many events have been applied.
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    pub fn len(&self) -> usize {
+        self.recording.events.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.recording.events.is_empty()
+    }
+
+    /// Applies the next event and returns it, or `None` at the end.
+    pub fn forward(&mut self) -> Option<&Event> {
+        let event = self.recording.events.get(self.position)?;
+        self.position += 1;
+        Some(event)
+    }
+
+    /// Undoes the last applied event and returns it, or `None` at the
+    /// start.
+    pub fn backward(&mut self) -> Option<&Event> {
+        self.position = self.position.checked_sub(1)?;
+        self.recording.events.get(self.position)
+    }
+
+    pub fn seek(&mut self, position: usize) {
+        self.position = position.min(self.len());
+    }
+
+    /// The frames after the applied events, the top level first. A frame
+    /// ends when an event happens in a shallower one, and a call starts a
+    /// new frame when it binds a parameter the frame already has.
+    pub fn frames(&self) -> Vec<Frame> {
+        let mut frames: Vec<Frame> = vec![];
+        for event in &self.recording.events[..self.position] {
+            apply(&mut frames, event);
+        }
+        frames
+    }
+}
+
+fn apply(frames: &mut Vec<Frame>, event: &Event) {
+    let depth = event.depth as usize;
+    frames.truncate(depth + 1);
+    let stale = frames.get(depth).is_some_and(|frame| {
+        frame.scope != event.scope || (event.parameter && frame.variables.contains_key(&event.name))
+    });
+    if stale {
+        frames.truncate(depth);
+    }
+    while frames.len() <= depth {
+        frames.push(Frame {
+            scope: event.scope.clone(),
+            variables: BTreeMap::new(),
+        });
+    }
+    frames[depth].variables.insert(event.name.clone(), event.new.clone());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interpreter::program::{execute_statements, Stats};
+    use crate::ir::ast::Environment;
+
+    fn record(source: &str) -> Recording {
+        let recording = Arc::new(Mutex::new(Recording::default()));
+        let mut env = Environment::new();
+        env.observer = Recording::observer(&recording);
+        execute_statements(&crate::rpy!(source), &mut env, &mut Stats::default()).unwrap();
+        let recording = recording.lock().unwrap().clone();
+        recording
+    }
+
+    fn variables(frame: &Frame) -> Vec<(&str, &str)> {
+        frame.variables.iter().map(|(name, value)| (name.as_str(), value.as_str())).collect()
+    }
+
+    #[test]
+    fn replays_step_both_ways() {
+        let recording = record(
+            "
+            x = 1
+            def double(n: TInteger) -> TInteger:
+                m = n * 2
+                return m
+            x = double(x)
+            y = double(5)
+            ",
+        );
+        let mut replay = Replay::new(recording);
+        assert_eq!(replay.len(), 7);
+
+        replay.seek(3);
+        let frames = replay.frames();
+        assert_eq!(frames.len(), 2);
+        assert_eq!((frames[1].scope.as_str(), variables(&frames[1])), ("double", vec![("m", "2"), ("n", "1")]));
+
+        assert_eq!(replay.forward().map(|event| event.span.as_str()), Some("x = double(x)"));
+        assert_eq!(replay.forward().map(|event| event.span.as_str()), Some("double(5)"));
+        let frames = replay.frames();
+        assert_eq!(variables(&frames[0]), vec![("x", "2")]);
+        assert_eq!(variables(&frames[1]), vec![("n", "5")]);
+
+        assert_eq!(replay.backward().map(|event| event.name.as_str()), Some("n"));
+        assert_eq!(replay.frames().len(), 1);
+        replay.seek(0);
+        assert_eq!(replay.backward(), None);
+        assert!(replay.frames().is_empty());
+    }
+
+    #[test]
+    fn recordings_survive_a_round_trip() {
+        let recording = record("s = \"a\\tb\"\nt = \"-\"\ns = \"c\"");
+        let text = recording.to_text();
+        assert_eq!(text.lines().nth(3), Some("0\t__main__\tassign\ts\t\"a\\\\\\\\tb\"\t\"c\"\ts = \"c\""));
+        assert_eq!(Recording::parse(&text), Ok(recording));
+        assert_eq!(Recording::parse("x"), Err(String::from("not a recording.")));
+    }
+
+    #[test]
+    fn timelines_carry_the_stack_of_every_step() {
+        let recording = record(
+            "
+            def double(n: TInteger) -> TInteger:
+                return n * 2
+            s = \"hi\"
+            s = double(2)
+            ",
+        );
+        assert_eq!(
+            recording.to_timeline(),
+            concat!(
+                r#"{"version": 1, "steps": ["#,
+                r#"{"step": 1, "span": "s = \"hi\"", "scope": "__main__", "name": "s", "old": null, "new": "\"hi\"", "parameter": false, "#,
+                r#""stack": [{"scope": "__main__", "variables": {"s": "\"hi\""}}]}, "#,
+                r#"{"step": 2, "span": "double(2)", "scope": "double", "name": "n", "old": null, "new": "2", "parameter": true, "#,
+                r#""stack": [{"scope": "__main__", "variables": {"s": "\"hi\""}}, {"scope": "double", "variables": {"n": "2"}}]}, "#,
+                r#"{"step": 3, "span": "s = double(2)", "scope": "__main__", "name": "s", "old": "\"hi\"", "new": "4", "parameter": false, "#,
+                r#""stack": [{"scope": "__main__", "variables": {"s": "4"}}]}"#,
+                "]}"
+            )
+        );
+        assert_eq!(Recording::default().to_timeline(), r#"{"version": 1, "steps": []}"#);
+    }
+}
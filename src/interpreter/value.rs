@@ -0,0 +1,569 @@
+use crate::interpreter::interpreter::{EnvValue, RESOURCE_EXHAUSTED};
+use crate::ir::ast::{Expression, Limits, Name};
+use crate::ir::pretty;
+use std::fmt;
+
+/// Hashable view of a runtime value, used to key dictionaries.
+///
+/// Two values produce the same key exactly when `==` considers them equal,
+/// so `Hash` and structural equality never disagree. Only immutable values
+/// with a total equality are hashable: reals and complex numbers (NaN
+/// breaks equality),
+/// functions and the ADT constructors carrying arguments are rejected.
+/// Decimals are keyed by their value, as integers when they have no
+/// fractional part, since `1.50d == 1.5d` and `2.0d == 2`. A tuple is keyed
+/// by the keys of its items, so it is hashable when all of them are.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum ValueKey {
+    Int(i32),
+    Decimal(i128, u32),
+    Bool(bool),
+    Str(String),
+    Bytes(Vec<u8>),
+    Nothing,
+    Constructor(Name, usize),
+    Tuple(Vec<ValueKey>),
+}
+
+pub fn hash_key(value: &EnvValue) -> Result<ValueKey, String> {
+    match value {
+        EnvValue::Exp(exp) => expression_key(exp),
+        EnvValue::Func(func) => Err(format!(
+            "[Runtime Error] unhashable type: function '{}' cannot be used as a key.",
+            func.name
+        )),
+        EnvValue::TestEnvironment(_) => Err(String::from(
+            "[Runtime Error] unhashable type: test modules cannot be used as keys.",
+        )),
+    }
+}
+
+fn expression_key(exp: &Expression) -> Result<ValueKey, String> {
+    match exp {
+        Expression::CInt(value) => Ok(ValueKey::Int(*value)),
+        Expression::CDecimal(value) => {
+            let value = value.normalized();
+            match i32::try_from(value.units()) {
+                Ok(n) if value.scale() == 0 => Ok(ValueKey::Int(n)),
+                _ => Ok(ValueKey::Decimal(value.units(), value.scale())),
+            }
+        }
+        Expression::CTrue => Ok(ValueKey::Bool(true)),
+        Expression::CFalse => Ok(ValueKey::Bool(false)),
+        Expression::CString(value) => Ok(ValueKey::Str(value.clone())),
+        Expression::CBytes(value) => Ok(ValueKey::Bytes(value.clone())),
+        Expression::CNothing => Ok(ValueKey::Nothing),
+        Expression::CTuple(items) => items.iter().map(expression_key).collect::<Result<_, _>>().map(ValueKey::Tuple),
+        Expression::ADTValue(value) if value.fields.is_empty() => {
+            Ok(ValueKey::Constructor(value.adt.clone(), value.tag))
+        }
+        Expression::ADTValue(value) => Err(format!(
+            "[Runtime Error] unhashable type: constructor '{}' has arguments.",
+            value.constructor
+        )),
+        Expression::CReal(_) => Err(String::from(
+            "[Runtime Error] unhashable type: real numbers cannot be used as keys.",
+        )),
+        Expression::CComplex(_) => Err(String::from(
+            "[Runtime Error] unhashable type: complex numbers cannot be used as keys.",
+        )),
+        _ => Err(String::from(
+            "[Runtime Error] unhashable type: only integers, decimals, booleans, strings, bytes, nullary constructors and tuples of them can be used as keys.",
+        )),
+    }
+}
+
+/// Name of the runtime type of a value, as `type_of` reports it: `Int`,
+/// `List` or the name of an ADT, not a type annotation such as `TInteger`.
+pub fn type_name(value: &EnvValue) -> String {
+    match value {
+        EnvValue::Exp(exp) => match exp {
+            Expression::CTrue | Expression::CFalse => String::from("Bool"),
+            Expression::CInt(_) => String::from("Int"),
+            Expression::CReal(_) => String::from("Real"),
+            Expression::CDecimal(_) => String::from("Decimal"),
+            Expression::CComplex(_) => String::from("Complex"),
+            Expression::CString(_) => String::from("String"),
+            Expression::CBytes(_) => String::from("Bytes"),
+            Expression::CVoid => String::from("Void"),
+            Expression::CList(_) => String::from("List"),
+            Expression::CTuple(_) => String::from("Tuple"),
+            Expression::CDict(_) => String::from("Dict"),
+            Expression::CJust(_) | Expression::CNothing => String::from("Maybe"),
+            Expression::COk(_) | Expression::CErr(_) => String::from("Result"),
+            Expression::ADTValue(value) => value.adt.clone(),
+            Expression::Closure(..) => String::from("Function"),
+            _ => String::from("Any"),
+        },
+        EnvValue::Func(_) => String::from("Function"),
+        EnvValue::TestEnvironment(_) => String::from("TestModule"),
+    }
+}
+
+/// Source-like rendering of a value, used in diagnostics.
+pub fn render(value: &EnvValue) -> String {
+    Printer::default().render(value)
+}
+
+/// Values display as `render` writes them, as `Cons(1, Cons(2, Nil))`.
+impl fmt::Display for EnvValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&render(self))
+    }
+}
+
+/// Expressions that are values display as `render` writes them; any other
+/// displays as its source.
+impl fmt::Display for Expression {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&Printer::default().expression(self, 0))
+    }
+}
+
+/// Writes values as `render` does, up to a nesting depth: the lists,
+/// tuples, dictionaries and constructor arguments below `max_depth` levels
+/// are written as `...`, so a deep value prints as `[[...], 1]`.
+///
+/// Values are trees the interpreter copies on every binding, so no part is
+/// ever shared with another nor refers back to itself; only their depth
+/// has to be bounded.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Printer {
+    pub max_depth: Option<usize>,
+}
+
+impl Printer {
+    pub fn new(max_depth: usize) -> Printer {
+        Printer { max_depth: Some(max_depth) }
+    }
+
+    pub fn render(&self, value: &EnvValue) -> String {
+        match value {
+            EnvValue::Exp(exp) => self.expression(exp, 0),
+            EnvValue::Func(func) => format!("<function {}>", func.name),
+            EnvValue::TestEnvironment(test) => format!("<test module {}>", test.name),
+        }
+    }
+
+    /// Writes `exp` as if it were nested `depth` levels deep. Values such
+    /// as long cons lists nest arbitrarily deep, so the parts still to be
+    /// written are kept on an explicit stack instead of recursing.
+    fn expression(&self, exp: &Expression, depth: usize) -> String {
+        enum Piece<'a> {
+            Text(&'static str),
+            Value(&'a Expression, usize),
+        }
+        // Pushes `values` to be written separated by commas, first on top
+        fn push_all<'a>(pieces: &mut Vec<Piece<'a>>, values: Vec<&'a Expression>, depth: usize) {
+            for (i, value) in values.into_iter().rev().enumerate() {
+                if i > 0 {
+                    pieces.push(Piece::Text(", "));
+                }
+                pieces.push(Piece::Value(value, depth));
+            }
+        }
+
+        let mut out = String::new();
+        let mut pieces = vec![Piece::Value(exp, depth)];
+        while let Some(piece) = pieces.pop() {
+            let (exp, depth) = match piece {
+                Piece::Text(text) => {
+                    out.push_str(text);
+                    continue;
+                }
+                Piece::Value(exp, depth) => (exp, depth),
+            };
+            if let Some(elided) = self.elided(exp, depth) {
+                out.push_str(&elided);
+                continue;
+            }
+            match exp {
+                Expression::CJust(e) | Expression::COk(e) | Expression::CErr(e) => {
+                    out.push_str(match exp {
+                        Expression::CJust(_) => "Just(",
+                        Expression::COk(_) => "Ok(",
+                        _ => "Err(",
+                    });
+                    pieces.extend([Piece::Text(")"), Piece::Value(e, depth)]);
+                }
+                Expression::CList(items) => {
+                    out.push('[');
+                    pieces.push(Piece::Text("]"));
+                    push_all(&mut pieces, items.iter().collect(), depth + 1);
+                }
+                Expression::CTuple(items) => {
+                    out.push('(');
+                    pieces.push(Piece::Text(")"));
+                    push_all(&mut pieces, items.iter().collect(), depth + 1);
+                }
+                Expression::CDict(entries) => {
+                    out.push('{');
+                    pieces.push(Piece::Text("}"));
+                    for (i, (key, value)) in entries.iter().enumerate().rev() {
+                        pieces.extend([Piece::Value(value, depth + 1), Piece::Text(": "), Piece::Value(key, depth + 1)]);
+                        if i > 0 {
+                            pieces.push(Piece::Text(", "));
+                        }
+                    }
+                }
+                Expression::ADTValue(value) if !value.fields.is_empty() => {
+                    out.push_str(&value.constructor);
+                    out.push('(');
+                    pieces.push(Piece::Text(")"));
+                    push_all(&mut pieces, value.fields.iter().map(|arg| &**arg).collect(), depth + 1);
+                }
+                other => out.push_str(&scalar(other)),
+            }
+        }
+        out
+    }
+
+    /// What a container below `max_depth` levels is written as.
+    fn elided(&self, exp: &Expression, depth: usize) -> Option<String> {
+        if self.max_depth.is_none_or(|max| depth < max) {
+            return None;
+        }
+        match exp {
+            Expression::CList(_) => Some(String::from("[...]")),
+            Expression::CTuple(_) => Some(String::from("(...)")),
+            Expression::CDict(_) => Some(String::from("{...}")),
+            Expression::ADTValue(value) if !value.fields.is_empty() => Some(format!("{}(...)", value.constructor)),
+            _ => None,
+        }
+    }
+}
+
+/// Writes an expression with no values inside: a constant, a constructor
+/// without arguments, a closure or the source of a non-value.
+fn scalar(exp: &Expression) -> String {
+    match exp {
+        Expression::CTrue => String::from("True"),
+        Expression::CFalse => String::from("False"),
+        Expression::CInt(value) => value.to_string(),
+        Expression::CReal(value) => pretty::real(*value),
+        Expression::CDecimal(value) => format!("{}d", value),
+        Expression::CComplex(value) => value.to_string(),
+        Expression::CString(value) => format!("{:?}", value),
+        Expression::CBytes(value) => format!(
+            "b\"{}\"",
+            value
+                .iter()
+                .map(|b| std::ascii::escape_default(*b).to_string())
+                .collect::<String>()
+        ),
+        Expression::CVoid => String::from("None"),
+        Expression::CNothing => String::from("Nothing"),
+        Expression::ADTValue(value) => value.constructor.clone(),
+        Expression::Closure(func, _) => format!("<function {}>", func.name),
+        _ => pretty::expression(exp),
+    }
+}
+
+/// Checks a value against the size and nesting limits. The walk keeps the
+/// parts still to be checked on an explicit stack, and stops at
+/// `max_depth`, so no value can make it overflow the host's stack.
+pub fn check_limits(value: &EnvValue, limits: &Limits) -> Result<(), String> {
+    let check_length = |kind: &str, unit: &str, length: usize, max: usize| {
+        if length > max {
+            return Err(format!(
+                "{} {} of {} {} exceeds the limit of {}.",
+                RESOURCE_EXHAUSTED, kind, length, unit, max
+            ));
+        }
+        Ok(())
+    };
+
+    let mut pending = match value {
+        EnvValue::Exp(exp) => vec![(exp, 0)],
+        _ => vec![],
+    };
+    while let Some((exp, depth)) = pending.pop() {
+        let parts: Vec<&Expression> = match exp {
+            Expression::CString(s) => {
+                check_length("string", "bytes", s.len(), limits.max_string_length)?;
+                continue;
+            }
+            Expression::CBytes(b) => {
+                check_length("bytes value", "bytes", b.len(), limits.max_string_length)?;
+                continue;
+            }
+            Expression::CList(items) => {
+                check_length("list", "elements", items.len(), limits.max_list_length)?;
+                items.iter().collect()
+            }
+            Expression::CTuple(items) => items.iter().collect(),
+            Expression::CDict(entries) => {
+                check_length("dict", "entries", entries.len(), limits.max_list_length)?;
+                entries.iter().flat_map(|(k, v)| [k, v]).collect()
+            }
+            Expression::CJust(e) | Expression::COk(e) | Expression::CErr(e) => vec![e.as_ref()],
+            Expression::ADTValue(value) => value.fields.iter().map(|arg| arg.as_ref()).collect(),
+            _ => continue,
+        };
+        if depth >= limits.max_depth {
+            return Err(format!(
+                "{} values may only be nested {} levels deep.",
+                RESOURCE_EXHAUSTED, limits.max_depth
+            ));
+        }
+        // The parts are checked in order, first on top
+        pending.extend(parts.into_iter().rev().map(|part| (part, depth + 1)));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ir::ast::Expression::*;
+    use crate::interpreter::interpreter::eval;
+    use crate::ir::ast::{AdtValue, Environment, Function};
+    use crate::ir::decimal::Decimal;
+    use std::collections::hash_map::DefaultHasher;
+    use std::hash::{Hash, Hasher};
+
+    fn hash_of(value: &EnvValue) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        hash_key(value).unwrap().hash(&mut hasher);
+        hasher.finish()
+    }
+
+    #[test]
+    fn equal_values_have_equal_hashes() {
+        let s1 = EnvValue::Exp(CString("key".to_string()));
+        let s2 = EnvValue::Exp(CString("key".to_string()));
+
+        assert_eq!(hash_key(&s1), hash_key(&s2));
+        assert_eq!(hash_of(&s1), hash_of(&s2));
+        assert_ne!(
+            hash_key(&EnvValue::Exp(CInt(1))),
+            hash_key(&EnvValue::Exp(CTrue))
+        );
+    }
+
+    #[test]
+    fn nullary_constructors_are_keyed_by_tag() {
+        let red = EnvValue::Exp(ADTValue(AdtValue::new("Color".to_string(), "Red".to_string(), 0, vec![].into())));
+        let green = EnvValue::Exp(ADTValue(AdtValue::new("Color".to_string(), "Green".to_string(), 1, vec![].into())));
+
+        assert_eq!(
+            hash_key(&red),
+            Ok(ValueKey::Constructor("Color".to_string(), 0))
+        );
+        assert_ne!(hash_key(&red), hash_key(&green));
+    }
+
+    #[test]
+    fn equal_tuples_key_to_the_same_value() {
+        let key = |items: Vec<Expression>| {
+            items
+                .into_iter()
+                .map(|item| hash_key(&EnvValue::Exp(item)))
+                .collect::<Result<Vec<_>, _>>()
+                .map(ValueKey::Tuple)
+        };
+        let two = || CString("two".to_string());
+        let mut dict = std::collections::HashMap::new();
+        dict.insert(key(vec![CInt(2), two()]).unwrap(), "first");
+        dict.insert(key(vec![CDecimal(Decimal::parse("2.0").unwrap()), two()]).unwrap(), "second");
+
+        assert_eq!(dict.len(), 1);
+        assert_eq!(dict.get(&key(vec![CInt(2), two()]).unwrap()), Some(&"second"));
+        assert_ne!(key(vec![CInt(2), two()]), key(vec![two(), CInt(2)]));
+        assert_ne!(key(vec![CInt(1)]), key(vec![CInt(1), CInt(1)]));
+    }
+
+    #[test]
+    fn unhashable_values_are_rejected() {
+        let circle = EnvValue::Exp(ADTValue(AdtValue::new(
+            "Shape".to_string(),
+            "Circle".to_string(),
+            0,
+            vec![Box::new(CReal(1.0))].into(),
+        )));
+
+        assert_eq!(
+            hash_key(&circle),
+            Err(String::from(
+                "[Runtime Error] unhashable type: constructor 'Circle' has arguments."
+            ))
+        );
+        assert!(hash_key(&EnvValue::Exp(CReal(1.5))).is_err());
+        assert_eq!(
+            hash_key(&EnvValue::Func(Function::new())),
+            Err(String::from(
+                "[Runtime Error] unhashable type: function '__main__' cannot be used as a key."
+            ))
+        );
+    }
+
+    #[test]
+    fn values_render_like_source() {
+        let circle = EnvValue::Exp(ADTValue(AdtValue::new(
+            "Shape".to_string(),
+            "Circle".to_string(),
+            0,
+            vec![Box::new(CReal(1.0))].into(),
+        )));
+        let dict = EnvValue::Exp(CDict(vec![(
+            CString("k".to_string()),
+            CList(vec![CJust(Box::new(CInt(1))), CNothing]),
+        )]));
+
+        assert_eq!(render(&circle), "Circle(1.0)");
+        assert_eq!(render(&dict), "{\"k\": [Just(1), Nothing]}");
+        assert_eq!(render(&EnvValue::Exp(CBytes(vec![104, 0]))), "b\"h\\x00\"");
+    }
+
+    #[test]
+    fn printers_elide_what_nests_past_their_depth() {
+        let tree = EnvValue::Exp(CList(vec![
+            CList(vec![CTuple(vec![CInt(1), CInt(2)])]),
+            CJust(Box::new(CDict(vec![(CInt(1), CList(vec![]))]))),
+            ADTValue(AdtValue::new("Tree".to_string(), "Node".to_string(), 1, vec![Box::new(CList(vec![]))].into())),
+            ADTValue(AdtValue::new("Tree".to_string(), "Leaf".to_string(), 0, vec![].into())),
+            CInt(3),
+        ]));
+
+        assert_eq!(render(&tree), "[[(1, 2)], Just({1: []}), Node([]), Leaf, 3]");
+        assert_eq!(Printer::new(2).render(&tree), "[[(...)], Just({1: [...]}), Node([...]), Leaf, 3]");
+        assert_eq!(Printer::new(1).render(&tree), "[[...], Just({...}), Node(...), Leaf, 3]");
+        assert_eq!(Printer::new(0).render(&tree), "[...]");
+        assert_eq!(Printer::new(0).render(&EnvValue::Exp(CInt(3))), "3");
+    }
+
+    #[test]
+    fn values_display_as_source() {
+        let adt = |name: &str, tag: usize, args: Vec<Expression>| {
+            ADTValue(AdtValue::new("List".to_string(), name.to_string(), tag, args.into_iter().map(Box::new).collect()))
+        };
+        let list = adt("Cons", 1, vec![CInt(1), adt("Cons", 1, vec![CInt(2), adt("Nil", 0, vec![])])]);
+
+        assert_eq!(EnvValue::Exp(list.clone()).to_string(), "Cons(1, Cons(2, Nil))");
+        assert_eq!(
+            CTuple(vec![CJust(Box::new(COk(Box::new(CString("a".to_string()))))), CList(vec![list, CNothing])]).to_string(),
+            "(Just(Ok(\"a\")), [Cons(1, Cons(2, Nil)), Nothing])"
+        );
+        assert_eq!(Add(Box::new(Var("x".to_string())), Box::new(CInt(1))).to_string(), "x + 1");
+    }
+
+    #[test]
+    fn limits_bound_sizes_and_nesting() {
+        let limits = Limits {
+            max_list_length: 2,
+            max_string_length: 3,
+            max_depth: 2,
+            ..Limits::default()
+        };
+        let check = |exp: Expression| check_limits(&EnvValue::Exp(exp), &limits);
+
+        assert_eq!(check(CList(vec![CJust(Box::new(CInt(1))), CInt(2)])), Ok(()));
+        assert_eq!(
+            check(CList(vec![CInt(1), CInt(2), CInt(3)])),
+            Err(String::from("[Resource Exhausted] list of 3 elements exceeds the limit of 2."))
+        );
+        assert_eq!(
+            check(CJust(Box::new(CString("abcd".to_string())))),
+            Err(String::from("[Resource Exhausted] string of 4 bytes exceeds the limit of 3."))
+        );
+        assert_eq!(
+            check(CList(vec![CJust(Box::new(COk(Box::new(CInt(1)))))])),
+            Err(String::from("[Resource Exhausted] values may only be nested 2 levels deep."))
+        );
+    }
+
+    #[test]
+    fn deep_values_clone_and_drop_without_recursion() {
+        let cons = |head: Expression, tail: Expression| {
+            ADTValue(AdtValue::new("List".to_string(), "Cons".to_string(), 1, vec![Box::new(head), Box::new(tail)].into()))
+        };
+        let mut list = ADTValue(AdtValue::new("List".to_string(), "Nil".to_string(), 0, vec![].into()));
+        for n in 0..200_000 {
+            let item = if n % 2 == 0 { CInt(n) } else { CJust(Box::new(CList(vec![CInt(n)]))) };
+            list = cons(item, list);
+        }
+
+        let copy = list.clone();
+        drop(list);
+
+        let (mut length, mut sum) = (0, 0i64);
+        let mut rest = &copy;
+        while let ADTValue(AdtValue { tag: 1, fields, .. }) = rest {
+            match &*fields[0] {
+                CInt(n) => sum += *n as i64,
+                CJust(inner) => assert!(matches!(&**inner, CList(items) if items.len() == 1)),
+                other => panic!("unexpected {:?}", other),
+            }
+            length += 1;
+            rest = &fields[1];
+        }
+        assert_eq!(length, 200_000);
+        assert_eq!(sum, (0..200_000i64).step_by(2).sum());
+    }
+
+    #[test]
+    fn deep_values_compare_and_print_without_recursion() {
+        let cons = |head: Expression, tail: Expression| {
+            ADTValue(AdtValue::new("List".to_string(), "Cons".to_string(), 1, vec![Box::new(head), Box::new(tail)].into()))
+        };
+        let mut list = ADTValue(AdtValue::new("List".to_string(), "Nil".to_string(), 0, vec![].into()));
+        for n in 0..200_000 {
+            let item = if n % 2 == 0 { CInt(n) } else { CJust(Box::new(CList(vec![CInt(n)]))) };
+            list = cons(item, list);
+        }
+        let other = cons(CInt(200_000), list.clone());
+
+        assert!(list == list.clone());
+        assert!(list != other);
+        let mut env: Environment<EnvValue> = Environment::new();
+        env.insert_variable("xs".to_string(), EnvValue::Exp(list.clone()));
+        env.insert_variable("ys".to_string(), EnvValue::Exp(other.clone()));
+        let xs_eq_ys = eval(EQ(Box::new(Var("xs".to_string())), Box::new(Var("ys".to_string()))), &env);
+        assert_eq!(xs_eq_ys, Ok(EnvValue::Exp(CFalse)));
+
+        let cons_fields = r#"ADTValue(AdtValue { adt: "List", constructor: "Cons", tag: 1, fields: ["#;
+        let printed = format!("{:?}", other);
+        assert!(printed.starts_with(&format!(
+            "{}CInt(200000), {}CJust(CList([CInt(199999)])), {}",
+            cons_fields, cons_fields, cons_fields
+        )));
+        assert!(printed.contains(r#"[CInt(0), ADTValue(AdtValue { adt: "List", constructor: "Nil", tag: 0, fields: [] })] })"#));
+        assert!(printed.ends_with(&"] })".repeat(200_001)));
+        let small = cons(CDict(vec![(CString("k".to_string()), COk(Box::new(CTuple(vec![CTrue]))))]), CVoid);
+        assert_eq!(
+            format!("{:?}", small),
+            format!(r#"{}CDict([(CString("k"), COk(CTuple([CTrue])))]), CVoid] }})"#, cons_fields)
+        );
+    }
+
+    #[test]
+    fn deep_values_render_and_check_limits_without_recursion() {
+        let cons = |head: Expression, tail: Expression| {
+            ADTValue(AdtValue::new("List".to_string(), "Cons".to_string(), 1, vec![Box::new(head), Box::new(tail)].into()))
+        };
+        let mut list = ADTValue(AdtValue::new("List".to_string(), "Nil".to_string(), 0, vec![].into()));
+        for n in 0..200_000 {
+            let item = if n % 2 == 0 { CInt(n) } else { CJust(Box::new(CList(vec![CInt(n)]))) };
+            list = cons(item, list);
+        }
+        let value = EnvValue::Exp(list);
+
+        let printed = value.to_string();
+        assert!(printed.starts_with("Cons(Just([199999]), Cons(199998, Cons(Just([199997])"));
+        assert!(printed.ends_with(&format!("Cons(Just([1]), Cons(0, Nil){}", ")".repeat(199_999))));
+        assert_eq!(
+            Printer::new(3).render(&value),
+            "Cons(Just([199999]), Cons(199998, Cons(Just([...]), Cons(...))))"
+        );
+
+        let limits = |max_depth: usize| Limits {
+            max_depth,
+            ..Limits::default()
+        };
+        assert_eq!(check_limits(&value, &limits(200_001)), Ok(()));
+        assert_eq!(
+            check_limits(&value, &limits(1_000)),
+            Err(String::from("[Resource Exhausted] values may only be nested 1000 levels deep."))
+        );
+    }
+}
@@ -1 +1,16 @@
 pub mod ast;
+pub mod build;
+pub mod calls;
+pub mod cfg;
+pub mod classes;
+pub mod complex;
+pub mod dataflow;
+pub mod decimal;
+pub mod desugar;
+pub mod inline;
+pub mod json;
+pub mod levels;
+pub mod modules;
+pub mod pretty;
+pub mod slots;
+pub mod tags;
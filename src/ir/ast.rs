@@ -1,35 +1,661 @@
 pub type Name = String;
 
+use crate::ir::complex::Complex;
+use crate::ir::decimal::Decimal;
 use nom::IResult;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+use std::io::BufRead;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
 
+/// Frames and environments use ordered maps, so iterating over variables,
+/// tests, frames or declared types always visits them sorted by name.
+/// Nothing a program observes depends on hashing, and the same program
+/// produces the same output on every run.
 #[derive(Debug, PartialEq, Clone)]
 pub struct Frame<A> {
     pub parent_function: Option<Function>,
     pub parent_key: Option<(Name, i32)>,
-    pub variables: HashMap<Name, A>,
-    pub tests: HashMap<Name, Function>,
+    pub variables: Variables<A>,
+    pub tests: BTreeMap<Name, Function>,
+    /// The statements that gave each variable its type, earliest first.
+    /// Only the type checker records them, to explain its errors.
+    pub origins: BTreeMap<Name, Vec<String>>,
+    /// The variables bound by `const` declarations, which nothing rebinds.
+    pub constants: BTreeSet<Name>,
 }
 
 impl<A> Frame<A> {
     pub fn new(func: Option<Function>, key: Option<(Name, i32)>) -> Frame<A> {
-        let variables: HashMap<Name, A> = HashMap::new();
-        let tests: HashMap<Name, Function> = HashMap::new();
+        let variables: Variables<A> = Variables::new();
+        let tests: BTreeMap<Name, Function> = BTreeMap::new();
         return Frame {
             parent_function: func,
             parent_key: key,
             variables,
             tests,
+            origins: BTreeMap::new(),
+            constants: BTreeSet::new(),
         };
     }
 }
 
+/// The variables of a frame. In the frame of a call to a function whose
+/// locals `slots::assign` numbered, those live in a vector, in the slot
+/// the function's layout gives them, and the interpreter reads them by
+/// slot; the other variables, and those of every other frame, are kept by
+/// name. The layout keeps the names of the slots, for lookups by name and
+/// for diagnostics.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Variables<A> {
+    named: BTreeMap<Name, A>,
+    layout: Option<Arc<Vec<Name>>>,
+    slots: Vec<Option<A>>,
+}
+
+/// The variables bound at some point, so that those bound after it can be
+/// unbound.
+pub struct Mark {
+    named: BTreeSet<Name>,
+    slots: Vec<bool>,
+}
+
+impl<A> Variables<A> {
+    pub fn new() -> Variables<A> {
+        Variables {
+            named: BTreeMap::new(),
+            layout: None,
+            slots: vec![],
+        }
+    }
+
+    /// Gives the locals of `layout` their slots, all unbound.
+    pub fn number(&mut self, layout: Arc<Vec<Name>>) {
+        self.slots.clear();
+        self.slots.resize_with(layout.len(), || None);
+        self.layout = Some(layout);
+    }
+
+    fn slot_of(&self, name: &str) -> Option<usize> {
+        self.layout.as_ref()?.iter().position(|local| local == name)
+    }
+
+    /// The value in `slot`, unbound if this frame has no such slot.
+    pub fn slot(&self, slot: usize) -> Option<&A> {
+        self.slots.get(slot)?.as_ref()
+    }
+
+    pub fn get(&self, name: &str) -> Option<&A> {
+        match self.slot_of(name) {
+            Some(slot) => self.slot(slot),
+            None => self.named.get(name),
+        }
+    }
+
+    pub fn contains_key(&self, name: &str) -> bool {
+        self.get(name).is_some()
+    }
+
+    pub fn insert(&mut self, name: Name, value: A) {
+        match self.slot_of(&name) {
+            Some(slot) => self.slots[slot] = Some(value),
+            None => {
+                self.named.insert(name, value);
+            }
+        }
+    }
+
+    /// The bound variables, sorted by name.
+    pub fn iter(&self) -> std::vec::IntoIter<(&Name, &A)> {
+        let mut bound: Vec<(&Name, &A)> = self.named.iter().collect();
+        if let Some(layout) = &self.layout {
+            bound.extend(layout.iter().zip(&self.slots).filter_map(|(name, value)| Some((name, value.as_ref()?))));
+        }
+        bound.sort_by_key(|(name, _)| *name);
+        bound.into_iter()
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &Name> {
+        self.iter().map(|(name, _)| name)
+    }
+
+    pub fn len(&self) -> usize {
+        self.named.len() + self.slots.iter().filter(|value| value.is_some()).count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Unbinds every variable, keeping the slots.
+    pub fn clear(&mut self) {
+        self.named.clear();
+        self.slots.iter_mut().for_each(|value| *value = None);
+    }
+
+    pub fn mark(&self) -> Mark {
+        Mark {
+            named: self.named.keys().cloned().collect(),
+            slots: self.slots.iter().map(Option::is_some).collect(),
+        }
+    }
+
+    /// Unbinds the variables bound since `mark` was taken.
+    pub fn unbind_since(&mut self, mark: &Mark) {
+        self.named.retain(|name, _| mark.named.contains(name));
+        for (slot, value) in self.slots.iter_mut().enumerate() {
+            if !mark.slots.get(slot).copied().unwrap_or(false) {
+                *value = None;
+            }
+        }
+    }
+}
+
+impl<A> Default for Variables<A> {
+    fn default() -> Variables<A> {
+        Variables::new()
+    }
+}
+
+impl<'a, A> IntoIterator for &'a Variables<A> {
+    type Item = (&'a Name, &'a A);
+    type IntoIter = std::vec::IntoIter<(&'a Name, &'a A)>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// Bindings shared by many environments. Each layer holds the few names
+/// bound where it was taken and points to the snapshot it was taken on top
+/// of; once shared, a layer never changes. Lookups look at the newest layer
+/// first, so its bindings shadow older ones.
+#[derive(Debug)]
+pub struct Snapshot<A> {
+    bindings: BTreeMap<Name, A>,
+    parent: Option<Arc<Snapshot<A>>>,
+    layers: usize,
+    version: u64,
+}
+
+/// Numbers the snapshots, so no two ever have the same version.
+static VERSIONS: AtomicU64 = AtomicU64::new(0);
+
+fn next_version() -> u64 {
+    VERSIONS.fetch_add(1, Ordering::Relaxed)
+}
+
+impl<A: PartialEq> PartialEq for Snapshot<A> {
+    fn eq(&self, other: &Snapshot<A>) -> bool {
+        self.bindings == other.bindings && self.parent == other.parent && self.layers == other.layers
+    }
+}
+
+/// Past this many layers a snapshot is flattened into one, so lookups stay
+/// cheap however deep the calls that took them.
+const MAX_LAYERS: usize = 8;
+
+impl<A> Snapshot<A> {
+    pub fn empty() -> Arc<Snapshot<A>> {
+        Arc::new(Snapshot {
+            bindings: BTreeMap::new(),
+            parent: None,
+            layers: 1,
+            version: next_version(),
+        })
+    }
+
+    /// Tells this snapshot apart from every other one. Since a snapshot
+    /// never changes, what a name resolves to in it stays the same for as
+    /// long as its version does.
+    pub fn version(&self) -> u64 {
+        self.version
+    }
+
+    pub fn get(&self, name: &Name) -> Option<&A> {
+        let mut layer = self;
+        loop {
+            if let Some(value) = layer.bindings.get(name) {
+                return Some(value);
+            }
+            layer = layer.parent.as_deref()?;
+        }
+    }
+
+    /// Every name bound in some layer, each once.
+    pub fn names(&self) -> Vec<Name> {
+        let mut names = vec![];
+        let mut layer = Some(self);
+        while let Some(current) = layer {
+            names.extend(current.bindings.keys().cloned());
+            layer = current.parent.as_deref();
+        }
+        names.sort();
+        names.dedup();
+        names
+    }
+}
+
+impl<A: Clone> Snapshot<A> {
+    /// `snapshot` with `bindings` on top. Taking no bindings shares the
+    /// snapshot as it is.
+    pub fn with(snapshot: &Arc<Snapshot<A>>, bindings: BTreeMap<Name, A>) -> Arc<Snapshot<A>> {
+        if bindings.is_empty() {
+            return snapshot.clone();
+        }
+        if snapshot.layers < MAX_LAYERS {
+            return Arc::new(Snapshot {
+                bindings,
+                parent: Some(snapshot.clone()),
+                layers: snapshot.layers + 1,
+                version: next_version(),
+            });
+        }
+
+        let mut flat = bindings;
+        let mut layer = Some(snapshot.as_ref());
+        while let Some(current) = layer {
+            for (name, value) in &current.bindings {
+                flat.entry(name.clone()).or_insert_with(|| value.clone());
+            }
+            layer = current.parent.as_deref();
+        }
+        Arc::new(Snapshot {
+            bindings: flat,
+            parent: None,
+            layers: 1,
+            version: next_version(),
+        })
+    }
+}
+
 #[derive(Debug, PartialEq, Clone)]
 pub struct Environment<A> {
     pub scope: Function,
     pub recursion: i32,
-    pub stack: HashMap<(Name, i32), Frame<A>>,
-    pub type_env: HashMap<Name, Vec<ValueConstructor>>,
+    pub stack: BTreeMap<(Name, i32), Frame<A>>,
+    /// Declared types and instances are shared with the environments of
+    /// calls, and copied only by the rare call that declares some.
+    pub type_env: Arc<BTreeMap<Name, Vec<ValueConstructor>>>,
+    pub capabilities: Capabilities,
+    pub instances: Arc<Vec<(Name, Name, Vec<Function>)>>,
+    pub limits: Arc<Limits>,
+    /// Whether `requires` and `ensures` clauses are checked. Turning them
+    /// off trades the safety net for speed.
+    pub check_contracts: bool,
+    /// Whether loop `invariant` annotations are checked. They are a
+    /// debugging aid, so they are off unless a host asks for them.
+    pub check_invariants: bool,
+    /// The `decreases` measure of each active call that declares one,
+    /// innermost last, so recursive calls can be compared with their caller.
+    pub measures: Vec<(Name, i32)>,
+    /// Calls active below the top level.
+    pub calls: u32,
+    pub io: Io,
+    pub counters: Arc<Counters>,
+    /// The functions a call can see besides those bound in its own frames.
+    /// A call shares the snapshot of its caller, adding a layer only for
+    /// the functions its caller bound, so a call costs the same however
+    /// many functions the program defines.
+    pub globals: Arc<Snapshot<A>>,
+    pub strictness: Strictness,
+    pub lints: Lints,
+    pub observer: Observer<A>,
+}
+
+/// Where `print` writes and `input` reads. Clones share the same streams,
+/// so a host keeps a handle and reads the output after the program ran.
+#[derive(Clone, Default)]
+pub struct Io(Arc<Mutex<Streams>>);
+
+/// `None` stands for the process's own stdin or stdout.
+#[derive(Default)]
+struct Streams {
+    input: Option<VecDeque<String>>,
+    output: Option<String>,
+}
+
+impl Io {
+    /// Streams that read the lines of `input` and capture what is written.
+    pub fn scripted(input: &str) -> Io {
+        Io(Arc::new(Mutex::new(Streams {
+            input: Some(input.lines().map(String::from).collect()),
+            output: Some(String::new()),
+        })))
+    }
+
+    /// Streams that read the process's stdin and capture what is written.
+    pub fn captured() -> Io {
+        Io(Arc::new(Mutex::new(Streams {
+            input: None,
+            output: Some(String::new()),
+        })))
+    }
+
+    /// The next line of input without its line ending, or `None` at the end.
+    pub fn read_line(&self) -> Option<String> {
+        let mut streams = self.0.lock().unwrap();
+        match &mut streams.input {
+            Some(lines) => lines.pop_front(),
+            None => {
+                let mut line = String::new();
+                match std::io::stdin().lock().read_line(&mut line) {
+                    Ok(0) | Err(_) => None,
+                    Ok(_) => Some(line.trim_end_matches(['\n', '\r']).to_string()),
+                }
+            }
+        }
+    }
+
+    pub fn write(&self, text: &str) {
+        let mut streams = self.0.lock().unwrap();
+        match &mut streams.output {
+            Some(output) => output.push_str(text),
+            None => print!("{}", text),
+        }
+    }
+
+    /// Everything written so far, when the output is captured.
+    pub fn output(&self) -> Option<String> {
+        self.0.lock().unwrap().output.clone()
+    }
+}
+
+impl std::fmt::Debug for Io {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.output() {
+            Some(_) => write!(f, "Io(scripted)"),
+            None => write!(f, "Io(console)"),
+        }
+    }
+}
+
+/// Environments are compared by their bindings, so the streams never make
+/// two of them differ.
+impl PartialEq for Io {
+    fn eq(&self, _other: &Io) -> bool {
+        true
+    }
+}
+
+/// How much work the interpreter did: statements and expressions evaluated,
+/// and lists, dicts and other compound values built. Environments made for
+/// function calls share the counters of their caller, so a host takes the
+/// difference of two `snapshot`s around whatever it measures.
+///
+/// Allocations are only counted, not tracked: values are trees copied on
+/// assignment and closures capture copies too, so no value is shared and
+/// none can outlive its environment or take part in a cycle. Live counts,
+/// a peak and leak checks belong here once values share a heap.
+#[derive(Debug, Default)]
+pub struct Counters {
+    steps: AtomicU64,
+    allocations: AtomicU64,
+}
+
+impl Counters {
+    pub fn step(&self) {
+        self.steps.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn allocate(&self) {
+        self.allocations.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// The steps and allocations counted so far.
+    pub fn snapshot(&self) -> (u64, u64) {
+        (
+            self.steps.load(Ordering::Relaxed),
+            self.allocations.load(Ordering::Relaxed),
+        )
+    }
+}
+
+/// Counters measure a run; they never make two environments differ.
+impl PartialEq for Counters {
+    fn eq(&self, _other: &Counters) -> bool {
+        true
+    }
+}
+
+/// How strictly the type checker treats code some courses want written out
+/// explicitly.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Strictness {
+    /// Integers that arithmetic and comparisons turn into reals, decimals
+    /// or complex numbers, as `n` in `n + 0.5`.
+    pub promotions: Level,
+    /// Whether programs may call the builtin functions. Courses where
+    /// students write their own `len` turn it off.
+    pub prelude: bool,
+    /// `==` on reals, as `0.1 + 0.2 == 0.3`, which rounding makes false.
+    /// Warned about unless a course allows it.
+    pub real_equality: Level,
+}
+
+impl Default for Strictness {
+    fn default() -> Self {
+        Strictness {
+            promotions: Level::default(),
+            prelude: true,
+            real_equality: Level::Warn,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub enum Level {
+    #[default]
+    Allow,
+    Warn,
+    Deny,
+}
+
+/// Warnings about code the type checker accepted. Clones share the list,
+/// so a host keeps a handle and reads it once the program is checked.
+#[derive(Debug, Clone, Default)]
+pub struct Lints(Arc<Mutex<Vec<String>>>);
+
+impl Lints {
+    pub fn push(&self, warning: String) {
+        self.0.lock().unwrap().push(warning);
+    }
+
+    pub fn take(&self) -> Vec<String> {
+        std::mem::take(&mut *self.0.lock().unwrap())
+    }
+}
+
+/// Like the streams, lints never make two environments differ.
+impl PartialEq for Lints {
+    fn eq(&self, _other: &Lints) -> bool {
+        true
+    }
+}
+
+/// A binding a running program made or changed: `old` is what `name` was
+/// bound to in the same frame before, and `span` is the code that bound it,
+/// as written. The frame is that of a call to `scope`, `depth` calls below
+/// the top level; a `parameter` is bound by the call itself.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Change<A> {
+    pub name: Name,
+    pub old: Option<A>,
+    pub new: A,
+    pub span: String,
+    pub scope: Name,
+    pub depth: u32,
+    pub parameter: bool,
+}
+
+/// A callback told of every `Change`, for debuggers and tools that animate
+/// the state of a program, and the `Debugger` that pauses it, if any.
+/// Clones share both, so the calls a program makes report to the same
+/// observer.
+pub struct Observer<A>(Option<Arc<Hooks<A>>>);
+
+/// Behind one pointer so an `Observer` is a thin one: every environment
+/// carries one, and the interpreter keeps many environments on its stack.
+struct Hooks<A> {
+    changes: Option<Callback<A>>,
+    debugger: Option<Debugger<A>>,
+}
+
+type Callback<A> = Box<dyn Fn(&Change<A>) + Send + Sync>;
+
+impl<A> Observer<A> {
+    pub fn new(callback: impl Fn(&Change<A>) + Send + Sync + 'static) -> Observer<A> {
+        Observer(Some(Arc::new(Hooks {
+            changes: Some(Box::new(callback)),
+            debugger: None,
+        })))
+    }
+
+    /// An observer that only pauses the program where `debugger` says.
+    pub fn debugger(debugger: Debugger<A>) -> Observer<A> {
+        Observer(Some(Arc::new(Hooks {
+            changes: None,
+            debugger: Some(debugger),
+        })))
+    }
+
+    /// Whether anyone listens, so callers only describe changes then. A
+    /// debugger listens for its watchpoints.
+    pub fn is_set(&self) -> bool {
+        self.0.is_some()
+    }
+
+    pub fn notify(&self, change: &Change<A>) {
+        if let Some(callback) = self.0.as_ref().and_then(|hooks| hooks.changes.as_ref()) {
+            callback(change);
+        }
+    }
+
+    pub fn debugging(&self) -> Option<&Debugger<A>> {
+        self.0.as_ref().and_then(|hooks| hooks.debugger.as_ref())
+    }
+}
+
+impl<A> Default for Observer<A> {
+    fn default() -> Self {
+        Observer(None)
+    }
+}
+
+impl<A> Clone for Observer<A> {
+    fn clone(&self) -> Self {
+        Observer(self.0.clone())
+    }
+}
+
+impl<A> std::fmt::Debug for Observer<A> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.0.is_some() {
+            true => write!(f, "Observer(set)"),
+            false => write!(f, "Observer(none)"),
+        }
+    }
+}
+
+/// Like the streams, observers never make two environments differ.
+impl<A> PartialEq for Observer<A> {
+    fn eq(&self, _other: &Observer<A>) -> bool {
+        true
+    }
+}
+
+/// Where a debugger pauses a program: before a statement whose first line
+/// reads as `Statement` says (`x = x + 1`, `while i < n`), when a call to
+/// the function `Call` names has bound its parameters, or after a binding
+/// of the variable `Watch` names changes its value or, given a condition,
+/// leaves the condition true.
+#[derive(Debug, PartialEq, Clone)]
+pub enum Breakpoint {
+    Statement(String),
+    Call(Name),
+    Watch(Name, Option<Expression>),
+}
+
+/// Pauses a program at its breakpoints and hands the paused frame to
+/// `pause`. Only a `mutable` debugger may change the frame.
+pub struct Debugger<A> {
+    pub breakpoints: Vec<Breakpoint>,
+    pub mutable: bool,
+    pause: Pause<A>,
+}
+
+type Pause<A> = Box<dyn for<'a> Fn(&mut Paused<'a, A>) + Send + Sync>;
+
+impl<A> Debugger<A> {
+    pub fn new(breakpoints: Vec<Breakpoint>, pause: impl Fn(&mut Paused<A>) + Send + Sync + 'static) -> Debugger<A> {
+        Debugger {
+            breakpoints,
+            mutable: false,
+            pause: Box::new(pause),
+        }
+    }
+
+    pub fn mutable(mut self) -> Debugger<A> {
+        self.mutable = true;
+        self
+    }
+
+    pub fn pause(&self, paused: &mut Paused<A>) {
+        (self.pause)(paused)
+    }
+}
+
+/// A frame paused at a breakpoint: `location` is the statement or the call
+/// it is paused at, as written.
+pub struct Paused<'a, A> {
+    pub location: String,
+    pub(crate) env: &'a mut Environment<A>,
+    pub(crate) mutable: bool,
+}
+
+/// What a program is allowed to do outside the interpreter. Everything is
+/// denied by default, so hosts opt in to each capability explicitly.
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct Capabilities {
+    pub network: bool,
+}
+
+/// Caps on the memory a program may use. Exceeding one is a runtime error
+/// prefixed with `RESOURCE_EXHAUSTED`, never an abort of the host.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Limits {
+    /// Variables bound across all frames of an environment.
+    pub max_bindings: usize,
+    /// Elements of a list or entries of a dict.
+    pub max_list_length: usize,
+    /// Characters of a string or bytes of a bytes value.
+    pub max_string_length: usize,
+    /// Nesting of lists, dicts, ADT values, `Just`, `Ok` and `Err`.
+    pub max_depth: usize,
+    /// Wall-clock time after which the program is aborted with a `TIMEOUT`
+    /// error. It is checked on every loop iteration and function call.
+    pub deadline: Option<Instant>,
+    /// Calls active at once, as Python's recursion limit. `execute_program`
+    /// runs on a stack sized for this many calls.
+    pub max_calls: usize,
+    /// Steps the interpreter may take, counted as `Counters` counts them
+    /// and checked where the deadline is.
+    pub max_steps: Option<u64>,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Limits {
+            max_bindings: 100_000,
+            max_list_length: 10_000_000,
+            max_string_length: 64 * 1024 * 1024,
+            max_depth: 1_000,
+            deadline: None,
+            max_calls: 1_000,
+            max_steps: None,
+        }
+    }
 }
 
 impl<A> Environment<A> {
@@ -40,8 +666,21 @@ impl<A> Environment<A> {
         return Environment {
             scope,
             recursion: 0,
-            stack: HashMap::from([(("__main__".to_string(), 0), frame)]),
-            type_env: HashMap::new(),
+            stack: BTreeMap::from([(("__main__".to_string(), 0), frame)]),
+            type_env: Arc::new(BTreeMap::new()),
+            capabilities: Capabilities::default(),
+            instances: Arc::new(vec![]),
+            limits: Arc::new(Limits::default()),
+            check_contracts: true,
+            check_invariants: false,
+            measures: vec![],
+            calls: 0,
+            io: Io::default(),
+            counters: Arc::new(Counters::default()),
+            globals: Snapshot::empty(),
+            strictness: Strictness::default(),
+            lints: Lints::default(),
+            observer: Observer::default(),
         };
     }
 
@@ -90,20 +729,103 @@ impl<A> Environment<A> {
         self.recursion = recursion;
     }
 
+    /// The frame of the current scope, found without building its key.
+    pub fn current_frame(&self) -> Option<&Frame<A>> {
+        self.stack
+            .iter()
+            .find(|((name, recursion), _)| *recursion == self.recursion && *name == self.scope.name)
+            .map(|(_, frame)| frame)
+    }
+
+    /// Gives the locals of `layout` their slots in the current frame.
+    pub fn number_locals(&mut self, layout: Arc<Vec<Name>>) {
+        if let Some(frame) = self.stack.get_mut(&self.scope_key()) {
+            frame.variables.number(layout);
+        }
+    }
+
     pub fn insert_variable(&mut self, name: Name, kind: A) -> () {
         if let Some(frame) = self.stack.get_mut(&self.scope_key()) {
             frame.variables.insert(name, kind);
         }
     }
 
+    /// Makes the variable `name` of the current frame a constant, which
+    /// the checker refuses to bind again.
+    pub fn insert_constant(&mut self, name: Name) {
+        if let Some(frame) = self.stack.get_mut(&self.scope_key()) {
+            frame.constants.insert(name);
+        }
+    }
+
+    /// Whether `name` is a constant visible in the current scope.
+    pub fn is_constant(&self, name: &Name) -> bool {
+        let mut key = Some(self.scope_key());
+        while let Some(frame) = key.and_then(|key| self.stack.get(&key)) {
+            if frame.variables.contains_key(name) {
+                return frame.constants.contains(name);
+            }
+            key = frame.parent_key.clone();
+        }
+        false
+    }
+
+    pub fn insert_origin(&mut self, name: Name, origin: Vec<String>) {
+        if let Some(frame) = self.stack.get_mut(&self.scope_key()) {
+            frame.origins.insert(name, origin);
+        }
+    }
+
+    /// Where the variable `name` visible in the current scope got its type.
+    pub fn origin(&self, name: &Name) -> Option<&Vec<String>> {
+        let mut frame = self.stack.get(&self.scope_key())?;
+        loop {
+            if frame.variables.contains_key(name) {
+                return frame.origins.get(name);
+            }
+            frame = self.stack.get(frame.parent_key.as_ref()?)?;
+        }
+    }
+
     pub fn insert_type(&mut self, name:Name, constructors: Vec<ValueConstructor>){
-        self.type_env.insert(name, constructors);
+        Arc::make_mut(&mut self.type_env).insert(name, constructors);
     }
 
     pub fn get_type(&self, name: &Name) -> Option<&Vec<ValueConstructor>> {
         self.type_env.get(name)
     }
 
+    /// Tags are the constructor positions in the ADT declaration, so they
+    /// are fixed as soon as the declaration is inserted in the type_env.
+    pub fn constructor_tag(&self, adt_name: &Name, constructor_name: &Name) -> Option<usize> {
+        self.type_env
+            .get(adt_name)?
+            .iter()
+            .position(|vc| vc.name == *constructor_name)
+    }
+
+    /// Instances are keyed by class and ADT name; primitive types have the
+    /// fixed instances of `classes::builtin_instance`.
+    pub fn insert_instance(&mut self, class: Name, adt_name: Name, methods: Vec<Function>) {
+        let instances = Arc::make_mut(&mut self.instances);
+        instances.retain(|(c, a, _)| *c != class || *a != adt_name);
+        instances.push((class, adt_name, methods));
+    }
+
+    pub fn has_instance(&self, class: &str, adt_name: &str) -> bool {
+        self.instances
+            .iter()
+            .any(|(c, a, _)| c == class && a == adt_name)
+    }
+
+    pub fn instance_method(&self, class: &str, adt_name: &str, method: &str) -> Option<&Function> {
+        self.instances
+            .iter()
+            .find(|(c, a, _)| c == class && a == adt_name)?
+            .2
+            .iter()
+            .find(|func| func.name == method)
+    }
 
     pub fn insert_test(&mut self, name: Name, test: Function) -> () {
         if let Some(frame) = self.stack.get_mut(&self.scope_key()) {
@@ -130,6 +852,75 @@ impl Function {
             body: None,
         };
     }
+
+    /// The function without its body, for the frame of a call, which only
+    /// needs to know what it is a call to.
+    pub fn signature(&self) -> Function {
+        Function {
+            name: self.name.clone(),
+            kind: self.kind.clone(),
+            params: self.params.clone(),
+            body: None,
+        }
+    }
+
+    /// Splits the body into the annotations at its start and the
+    /// statements that follow them.
+    pub fn contracts(&self) -> (Annotations, Statement) {
+        match self.body.as_deref() {
+            Some(body) => annotations(body),
+            None => (Annotations::default(), Statement::Block(vec![])),
+        }
+    }
+}
+
+/// Annotations at the start of a function or while body. Functions take
+/// `requires`, `ensures` and `decreases`, and checked ones the layout of
+/// their locals; loops take `invariant` and `decreases`.
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct Annotations {
+    pub requires: Vec<Expression>,
+    pub ensures: Vec<Expression>,
+    pub invariants: Vec<Expression>,
+    pub decreases: Vec<Expression>,
+    pub locals: Option<Arc<Vec<Name>>>,
+}
+
+/// Splits a body into its leading annotations and the statements that
+/// follow them. Bodies without annotations are returned unchanged.
+pub fn annotations(body: &Statement) -> (Annotations, Statement) {
+    let mut found = Annotations::default();
+    let stmts = match body {
+        Statement::Block(stmts) => stmts.clone(),
+        stmt => vec![stmt.clone()],
+    };
+    if !stmts.first().is_some_and(is_annotation) {
+        return (found, body.clone());
+    }
+
+    let mut rest = stmts.into_iter().peekable();
+    while let Some(annotation) = rest.next_if(is_annotation) {
+        match annotation {
+            Statement::Requires(exp) => found.requires.push(*exp),
+            Statement::Ensures(exp) => found.ensures.push(*exp),
+            Statement::Invariant(exp) => found.invariants.push(*exp),
+            Statement::Decreases(exp) => found.decreases.push(*exp),
+            Statement::Locals(layout) => found.locals = Some(layout),
+            _ => unreachable!(),
+        }
+    }
+    (found, Statement::Block(rest.collect()))
+}
+
+fn is_annotation(stmt: &Statement) -> bool {
+    matches!(
+        stmt,
+        Statement::Requires(_)
+            | Statement::Ensures(_)
+            | Statement::Invariant(_)
+            | Statement::Decreases(_)
+            | Statement::Locals(_)
+    )
 }
 
 #[derive(Debug, PartialEq, Clone)]
@@ -147,19 +938,25 @@ impl<A> TestEnvironment<A> {
     }
 }
 #[derive(Clone, Debug, PartialEq)]
+#[non_exhaustive]
 pub enum Type {
     TInteger,
     TBool,
     TReal,
+    TDecimal,
+    TComplex,
     TString,
+    TBytes,
     TVoid,
     TFunction(Box<Option<Type>>, Vec<Type>),
     TList(Box<Type>),
     TTuple(Vec<Type>),
+    TDict(Box<Type>, Box<Type>), // Key, Value
     TMaybe(Box<Type>),
     TResult(Box<Type>, Box<Type>), // Ok, Error
     TAny,
     Tadt(Name, Vec<ValueConstructor>),
+    TVar(Name, Vec<Name>), // Type variable, classes it must belong to
 }
 
 #[derive(Debug,PartialEq, Clone)]
@@ -175,14 +972,24 @@ pub enum Expression {
     CFalse,
     CInt(i32),
     CReal(f64),
+    CDecimal(Decimal),
+    CComplex(Complex),
     CString(String),
+    CBytes(Vec<u8>),
     CVoid,
 
+    /* collections */
+    CList(Vec<Expression>),
+    CDict(Vec<(Expression, Expression)>),
+    CTuple(Vec<Expression>),
+
     /* variable reference */
     Var(Name),
+    Slot(usize, Name), // a local of the function it is read in, by its slot; made by `slots::assign`
 
     /* function call */
     FuncCall(Name, Vec<Expression>),
+    CachedCall(CallSite, Vec<Expression>), // a `FuncCall` that remembers its callee, made by `calls::cache`
 
     /* arithmetic expressions over numbers */
     Add(Box<Expression>, Box<Expression>),
@@ -215,13 +1022,418 @@ pub enum Expression {
     Propagate(Box<Expression>),
 
     ADTConstructor(Name, Name, Vec<Box<Expression>>),
+
+    /* runtime ADT value, built by evaluating an `ADTConstructor` */
+    ADTValue(AdtValue),
+
+    /* runtime function value: (function, captured bindings) */
+    Closure(Box<Function>, Vec<(Name, Expression)>),
+
+    /* surface syntax, removed by `desugar` */
+    Pipe(Box<Expression>, Name, Vec<Expression>), // value |> f(args)
+    Infix(Box<Expression>, Vec<(InfixOperator, Expression)>), // a + b `max` c, grouped by fixity
+}
+
+/// Where a checked program calls a function, with the callee it found
+/// there among the global functions. Clones share the cache, so it
+/// outlives the copies of a body the interpreter makes, and calls of a
+/// recursive function find themselves without looking up their name.
+#[derive(Debug, Clone)]
+pub struct CallSite {
+    pub name: Name,
+    cache: Arc<Mutex<Cached>>,
+}
+
+/// A function value as a call finds it: the function and what it captured.
+pub type Callee = (Function, Vec<(Name, Expression)>);
+
+/// What a call site knows about the snapshot with the version it names.
+#[derive(Debug, Default)]
+enum Cached {
+    #[default]
+    Nothing,
+    Seen(u64),
+    Resolved(u64, Option<Arc<Callee>>),
+}
+
+impl CallSite {
+    pub fn new(name: Name) -> CallSite {
+        CallSite {
+            name,
+            cache: Arc::new(Mutex::new(Cached::Nothing)),
+        }
+    }
+
+    /// The callee the name resolves to in the snapshot with `version`, as
+    /// `lookup` finds it. `lookup` runs, and copies the callee, only on the
+    /// second call under the same version, so a site whose snapshot changes
+    /// on every call never copies one; until then there is no callee, and
+    /// the caller looks the name up as usual.
+    pub fn resolve(&self, version: u64, lookup: impl FnOnce() -> Option<Callee>) -> Option<Arc<Callee>> {
+        let mut cache = self.cache.lock().unwrap();
+        match &*cache {
+            Cached::Resolved(seen, callee) if *seen == version => callee.clone(),
+            Cached::Seen(seen) if *seen == version => {
+                let callee = lookup().map(Arc::new);
+                *cache = Cached::Resolved(version, callee.clone());
+                callee
+            }
+            _ => {
+                *cache = Cached::Seen(version);
+                None
+            }
+        }
+    }
+}
+
+/// Sites are the same when they call the same name, whatever they cached.
+impl PartialEq for CallSite {
+    fn eq(&self, other: &CallSite) -> bool {
+        self.name == other.name
+    }
+}
+
+/// A value built by an ADT constructor. Its fields are evaluated values, so
+/// matching, equality and printing read them without walking any syntax.
+/// Matching and equality compare the ADT and the tag; the constructor name
+/// is only printed.
+#[derive(Debug, Clone)]
+pub struct AdtValue {
+    pub adt: Name,
+    pub constructor: Name,
+    pub tag: usize,
+    pub fields: Fields,
+}
+
+impl AdtValue {
+    pub fn new(adt: Name, constructor: Name, tag: usize, fields: Fields) -> AdtValue {
+        AdtValue {
+            adt,
+            constructor,
+            tag,
+            fields,
+        }
+    }
+}
+
+impl PartialEq for AdtValue {
+    fn eq(&self, other: &AdtValue) -> bool {
+        self.tag == other.tag && self.adt == other.adt && self.fields == other.fields
+    }
+}
+
+/// The arguments of an ADT value. Values such as long cons lists nest
+/// arbitrarily deep through them, so cloning, dropping, comparing and
+/// printing them walk the value with an explicit stack instead of
+/// recursing, and cannot overflow the host's stack.
+#[derive(Default)]
+#[allow(clippy::vec_box)]
+pub struct Fields(Vec<Box<Expression>>);
+
+impl Fields {
+    pub fn into_vec(mut self) -> Vec<Box<Expression>> {
+        std::mem::take(&mut self.0)
+    }
+}
+
+impl From<Vec<Box<Expression>>> for Fields {
+    fn from(args: Vec<Box<Expression>>) -> Fields {
+        Fields(args)
+    }
+}
+
+impl FromIterator<Box<Expression>> for Fields {
+    fn from_iter<I: IntoIterator<Item = Box<Expression>>>(args: I) -> Fields {
+        Fields(args.into_iter().collect())
+    }
+}
+
+impl std::ops::Deref for Fields {
+    type Target = Vec<Box<Expression>>;
+
+    fn deref(&self) -> &Vec<Box<Expression>> {
+        &self.0
+    }
+}
+
+impl std::ops::DerefMut for Fields {
+    fn deref_mut(&mut self) -> &mut Vec<Box<Expression>> {
+        &mut self.0
+    }
+}
+
+impl IntoIterator for Fields {
+    type Item = Box<Expression>;
+    type IntoIter = std::vec::IntoIter<Box<Expression>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.into_vec().into_iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a Fields {
+    type Item = &'a Box<Expression>;
+    type IntoIter = std::slice::Iter<'a, Box<Expression>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.iter()
+    }
+}
+
+impl PartialEq for Fields {
+    fn eq(&self, other: &Fields) -> bool {
+        self.len() == other.len() && self.iter().zip(other.iter()).all(|(a, b)| equal_values(a, b))
+    }
+}
+
+/// Whether `a` and `b` are the same value, compared as `AdtValue` compares
+/// ADT values.
+pub fn equal_values(a: &Expression, b: &Expression) -> bool {
+    let mut pending = vec![(a, b)];
+    while let Some(pair) = pending.pop() {
+        match pair {
+            (Expression::ADTValue(v1), Expression::ADTValue(v2)) => {
+                if v1.tag != v2.tag || v1.adt != v2.adt || v1.fields.len() != v2.fields.len() {
+                    return false;
+                }
+                pending.extend(v1.fields.iter().zip(v2.fields.iter()).map(|(a, b)| (&**a, &**b)));
+            }
+            (Expression::CList(items1), Expression::CList(items2))
+            | (Expression::CTuple(items1), Expression::CTuple(items2)) => {
+                if items1.len() != items2.len() {
+                    return false;
+                }
+                pending.extend(items1.iter().zip(items2));
+            }
+            (Expression::CDict(entries1), Expression::CDict(entries2)) => {
+                if entries1.len() != entries2.len() {
+                    return false;
+                }
+                for ((k1, v1), (k2, v2)) in entries1.iter().zip(entries2) {
+                    pending.extend([(k1, k2), (v1, v2)]);
+                }
+            }
+            (Expression::CJust(a), Expression::CJust(b))
+            | (Expression::COk(a), Expression::COk(b))
+            | (Expression::CErr(a), Expression::CErr(b)) => pending.push((&**a, &**b)),
+            (a, b) => {
+                if a != b {
+                    return false;
+                }
+            }
+        }
+    }
+    true
+}
+
+impl std::fmt::Debug for Fields {
+    /// Writes the arguments as the derived `Debug` of `Expression` would,
+    /// on one line even under `{:#?}`.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        enum Piece<'a> {
+            Text(&'static str),
+            Value(&'a Expression),
+        }
+        // Pushes `values` to be written separated by commas, first on top
+        fn push_all<'a>(pieces: &mut Vec<Piece<'a>>, values: Vec<&'a Expression>) {
+            for (i, value) in values.into_iter().rev().enumerate() {
+                if i > 0 {
+                    pieces.push(Piece::Text(", "));
+                }
+                pieces.push(Piece::Value(value));
+            }
+        }
+
+        let mut pieces = vec![Piece::Text("]")];
+        push_all(&mut pieces, self.iter().map(|arg| &**arg).collect());
+        f.write_str("[")?;
+        while let Some(piece) = pieces.pop() {
+            let value = match piece {
+                Piece::Text(text) => {
+                    f.write_str(text)?;
+                    continue;
+                }
+                Piece::Value(value) => value,
+            };
+            match value {
+                Expression::ADTValue(AdtValue {
+                    adt,
+                    constructor,
+                    tag,
+                    fields,
+                }) => {
+                    write!(
+                        f,
+                        "ADTValue(AdtValue {{ adt: {:?}, constructor: {:?}, tag: {}, fields: [",
+                        adt, constructor, tag
+                    )?;
+                    pieces.push(Piece::Text("] })"));
+                    push_all(&mut pieces, fields.iter().map(|arg| &**arg).collect());
+                }
+                Expression::CList(items) | Expression::CTuple(items) => {
+                    f.write_str(if matches!(value, Expression::CList(_)) { "CList([" } else { "CTuple([" })?;
+                    pieces.push(Piece::Text("])"));
+                    push_all(&mut pieces, items.iter().collect());
+                }
+                Expression::CDict(entries) => {
+                    f.write_str("CDict([")?;
+                    pieces.push(Piece::Text("])"));
+                    for (i, (key, value)) in entries.iter().enumerate().rev() {
+                        pieces.extend([Piece::Text(")"), Piece::Value(value), Piece::Text(", "), Piece::Value(key)]);
+                        pieces.push(Piece::Text(if i > 0 { ", (" } else { "(" }));
+                    }
+                }
+                Expression::CJust(inner) | Expression::COk(inner) | Expression::CErr(inner) => {
+                    f.write_str(match value {
+                        Expression::CJust(_) => "CJust(",
+                        Expression::COk(_) => "COk(",
+                        _ => "CErr(",
+                    })?;
+                    pieces.extend([Piece::Text(")"), Piece::Value(inner)]);
+                }
+                other => write!(f, "{:?}", other)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The values nested in `value` that it holds directly, moved out of it and
+/// left as `CVoid`, so dropping `value` itself recurses no further.
+fn take_values(value: &mut Expression, into: &mut Vec<Expression>) {
+    match value {
+        Expression::ADTValue(value) => into.extend(value.fields.0.drain(..).map(|arg| *arg)),
+        Expression::CList(items) | Expression::CTuple(items) => into.append(items),
+        Expression::CDict(entries) => {
+            for (key, value) in entries.drain(..) {
+                into.push(key);
+                into.push(value);
+            }
+        }
+        Expression::CJust(inner) | Expression::COk(inner) | Expression::CErr(inner) => {
+            into.push(std::mem::replace(&mut **inner, Expression::CVoid))
+        }
+        _ => {}
+    }
+}
+
+impl Drop for Fields {
+    fn drop(&mut self) {
+        let mut pending: Vec<Expression> = self.0.drain(..).map(|arg| *arg).collect();
+        while let Some(mut value) = pending.pop() {
+            take_values(&mut value, &mut pending);
+        }
+    }
+}
+
+impl Clone for Fields {
+    fn clone(&self) -> Fields {
+        // Values are cloned after the values they hold, which wait on
+        // `done`; a node is rebuilt once all of its values are there
+        enum Step<'a> {
+            Enter(&'a Expression),
+            Build(&'a Expression),
+        }
+        let mut steps: Vec<Step> = self.0.iter().rev().map(|arg| Step::Enter(arg)).collect();
+        let mut done: Vec<Expression> = vec![];
+
+        while let Some(step) = steps.pop() {
+            match step {
+                Step::Enter(value) => {
+                    let held: Vec<&Expression> = match value {
+                        Expression::ADTValue(value) => value.fields.0.iter().map(|arg| &**arg).collect(),
+                        Expression::CList(items) | Expression::CTuple(items) => items.iter().collect(),
+                        Expression::CJust(inner) | Expression::COk(inner) | Expression::CErr(inner) => vec![inner],
+                        _ => {
+                            done.push(value.clone());
+                            continue;
+                        }
+                    };
+                    steps.push(Step::Build(value));
+                    steps.extend(held.into_iter().rev().map(Step::Enter));
+                }
+                Step::Build(value) => {
+                    let mut take = |n: usize| done.split_off(done.len() - n);
+                    let built = match value {
+                        Expression::ADTValue(value) => Expression::ADTValue(AdtValue {
+                            fields: Fields(take(value.fields.len()).into_iter().map(Box::new).collect()),
+                            adt: value.adt.clone(),
+                            constructor: value.constructor.clone(),
+                            tag: value.tag,
+                        }),
+                        Expression::CList(items) => Expression::CList(take(items.len())),
+                        Expression::CTuple(items) => Expression::CTuple(take(items.len())),
+                        Expression::CJust(_) => Expression::CJust(Box::new(take(1).remove(0))),
+                        Expression::COk(_) => Expression::COk(Box::new(take(1).remove(0))),
+                        Expression::CErr(_) => Expression::CErr(Box::new(take(1).remove(0))),
+                        _ => unreachable!(),
+                    };
+                    done.push(built);
+                }
+            }
+        }
+        Fields(done.into_iter().map(Box::new).collect())
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum ArithmeticOperator {
+    Add,
+    Sub,
+    Mul,
+    Div,
+}
+
+/// An operator of an infix chain: arithmetic, or a function of two
+/// arguments written between backticks, as in ``a `max` b``.
+#[derive(Debug, PartialEq, Clone)]
+pub enum InfixOperator {
+    Arithmetic(ArithmeticOperator),
+    Function(Name),
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Associativity {
+    Left,
+    Right,
+}
+
+/// How tightly an infix operator binds, from 0 to 9, and how a run of
+/// operators as tight groups: `+` and `-` are `infixl 6`, `*` and `/`
+/// `infixl 7`, and a function nobody declared `infixl 9`.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Fixity {
+    pub associativity: Associativity,
+    pub precedence: u8,
+}
+
+impl Fixity {
+    pub const DEFAULT: Fixity = Fixity {
+        associativity: Associativity::Left,
+        precedence: 9,
+    };
+}
+
+impl ArithmeticOperator {
+    pub fn fixity(&self) -> Fixity {
+        let precedence = match self {
+            ArithmeticOperator::Add | ArithmeticOperator::Sub => 6,
+            ArithmeticOperator::Mul | ArithmeticOperator::Div => 7,
+        };
+        Fixity {
+            associativity: Associativity::Left,
+            precedence,
+        }
+    }
 }
 
 #[derive(Debug, PartialEq, Clone)]
 pub enum Statement {
     VarDeclaration(Name),
     ValDeclaration(Name),
+    ConstDeclaration(Name, Type, Box<Expression>), // `const PI: TReal = 3.14159`, top level only
     Assignment(Name, Box<Expression>, Option<Type>),
+    Destructure(Pattern, Box<Expression>), // `Shape Circle r = s`, `(a, b) = pair`
     IfThenElse(Box<Expression>, Box<Statement>, Option<Box<Statement>>),
     While(Box<Expression>, Box<Statement>),
     Block(Vec<Statement>),
@@ -235,8 +1447,97 @@ pub enum Statement {
     AssertFails(String),
     FuncDef(Function),
     Return(Box<Expression>),
-    ADTDeclaration(Name, Vec<ValueConstructor>),
-    Match(Box<Expression>, Vec<(Expression, Box<Statement>)>),
+    Call(Box<Expression>), // a call evaluated for its effects, as in `print(x)`
+    Import(Name), // `import a.b`, resolved by the module loader before the program runs
+    Module(Name), // `module a.b` header, whose names the loader qualifies as `a.b.name`
+    ADTDeclaration(Name, Vec<Name>, Vec<ValueConstructor>), // name, type parameters, constructors
+    Match(Box<Expression>, Vec<(Pattern, Option<Expression>, Box<Statement>)>), // arms: pattern, guard, body
+    InstanceDeclaration(Name, Name, Vec<Function>), // Class, ADT, methods
+
+    /* contracts, only allowed at the start of a function body */
+    Requires(Box<Expression>),
+    Ensures(Box<Expression>), // may read the returned value as `result`
+
+    /* loop annotations, only allowed at the start of a while body */
+    Invariant(Box<Expression>),
+    Decreases(Box<Expression>), // also allowed on functions
+
+    /* the layout of a function's locals, first in its body, added by `slots::assign` */
+    Locals(Arc<Vec<Name>>),
+
+    /* surface syntax, removed by `desugar` */
+    IfChain(Vec<(Expression, Statement)>, Option<Box<Statement>>), // if/elif branches, else
+    AugmentedAssignment(Name, ArithmeticOperator, Box<Expression>), // x += e
+    FuncClauses(Name, Option<Type>, Vec<Clause>), // def f(p1, p2) -> T = e, one clause per line
+    FixityDeclaration(Name, Fixity), // infixl 6 max
+
+    /* where a statement starts in the source, only from `parse_located` */
+    At(Position, Box<Statement>),
+}
+
+impl Statement {
+    /// The statement without the position `parse_located` wraps it in.
+    pub fn unlocated(&self) -> &Statement {
+        match self {
+            Statement::At(_, stmt) => stmt.unlocated(),
+            stmt => stmt,
+        }
+    }
+}
+
+/// A place in the source, both counted from 1.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Position {
+    pub line: usize,
+    pub column: usize,
+}
+
+/// An equation of a function defined by cases, as
+/// `def len(List Cons _ t) = 1 + len(t)`: the pattern each argument must
+/// match, with the type written after it if any, a guard, and the value
+/// the function returns when they match.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Clause {
+    pub params: Vec<(Pattern, Option<Type>)>,
+    pub guard: Option<Expression>,
+    pub value: Expression,
+}
+
+/// What an arm of a `match` compares the value with. A `Variable` matches
+/// anything and binds it in the arm; `Wildcard` (`_`) matches anything
+/// and binds nothing. `StartsWith` and `EndsWith` match the strings with
+/// that prefix or suffix.
+#[derive(Debug, PartialEq, Clone)]
+pub enum Pattern {
+    Literal(Expression),
+    Wildcard,
+    Variable(Name),
+    Constructor(Name, Name, Vec<Pattern>), // ADT, constructor, one pattern per argument
+    Tagged(Name, Name, usize, Vec<Pattern>), // a `Constructor` with its tag, resolved by `tags::resolve`
+    Tuple(Vec<Pattern>),
+    List(Vec<Pattern>), // lists of exactly this length
+    ListRest(Vec<Pattern>, Option<Name>), // lists that start with these, the rest bound to the name if any
+    As(Box<Pattern>, Name), // the pattern, with the whole value bound to the name
+    StartsWith(String),
+    EndsWith(String),
+}
+
+impl Pattern {
+    /// The names the pattern binds, left to right.
+    pub fn variables(&self) -> Vec<Name> {
+        match self {
+            Pattern::Variable(name) => vec![name.clone()],
+            Pattern::Constructor(_, _, args)
+            | Pattern::Tagged(_, _, _, args)
+            | Pattern::Tuple(args)
+            | Pattern::List(args) => {
+                args.iter().flat_map(Pattern::variables).collect()
+            }
+            Pattern::ListRest(items, rest) => items.iter().flat_map(Pattern::variables).chain(rest.clone()).collect(),
+            Pattern::As(pattern, whole) => pattern.variables().into_iter().chain([whole.clone()]).collect(),
+            Pattern::Literal(_) | Pattern::Wildcard | Pattern::StartsWith(_) | Pattern::EndsWith(_) => vec![],
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -0,0 +1,251 @@
+use crate::ir::ast::{Expression, Function, Name, Statement, Type};
+use crate::ir::decimal::Decimal;
+
+/// Functions that build syntax trees without spelling out the boxes, for
+/// hosts that construct programs directly and for tests. Every operand takes
+/// anything that converts into an expression, so numbers, booleans and
+/// string literals can be passed as they are:
+///
+/// ```ignore
+/// func("double").param("n", TInteger).returns(TInteger).body(vec![
+///     ret(mul(var("n"), 2)),
+/// ])
+/// ```
+///
+/// An `Expression` also converts into a `Box<Expression>`, so `.into()`
+/// fills any boxed field of the enums directly.
+pub fn int(n: i32) -> Expression {
+    Expression::CInt(n)
+}
+
+pub fn real(x: f64) -> Expression {
+    Expression::CReal(x)
+}
+
+/// An exact decimal written as in the source without its suffix, as in
+/// `decimal("1.50")`.
+pub fn decimal(text: &str) -> Expression {
+    Expression::CDecimal(Decimal::parse(text).expect("a decimal literal"))
+}
+
+pub fn string(s: &str) -> Expression {
+    Expression::CString(s.to_string())
+}
+
+pub fn boolean(b: bool) -> Expression {
+    b.into()
+}
+
+pub fn var(name: &str) -> Expression {
+    Expression::Var(name.to_string())
+}
+
+pub fn call<I>(name: &str, args: I) -> Expression
+where
+    I: IntoIterator,
+    I::Item: Into<Expression>,
+{
+    Expression::FuncCall(name.to_string(), args.into_iter().map(Into::into).collect())
+}
+
+pub fn list<I>(items: I) -> Expression
+where
+    I: IntoIterator,
+    I::Item: Into<Expression>,
+{
+    Expression::CList(items.into_iter().map(Into::into).collect())
+}
+
+fn boxed(e: impl Into<Expression>) -> Box<Expression> {
+    Box::new(e.into())
+}
+
+macro_rules! binary {
+    ($($name:ident => $variant:ident),* $(,)?) => {
+        $(
+            pub fn $name(lhs: impl Into<Expression>, rhs: impl Into<Expression>) -> Expression {
+                Expression::$variant(boxed(lhs), boxed(rhs))
+            }
+        )*
+    };
+}
+
+binary! {
+    add => Add, sub => Sub, mul => Mul, div => Div,
+    and => And, or => Or,
+    eq => EQ, lt => LT, gt => GT, lte => LTE, gte => GTE,
+}
+
+macro_rules! unary {
+    ($($name:ident => $variant:ident),* $(,)?) => {
+        $(
+            pub fn $name(e: impl Into<Expression>) -> Expression {
+                Expression::$variant(boxed(e))
+            }
+        )*
+    };
+}
+
+unary! {
+    not => Not,
+    just => CJust, ok => COk, err => CErr,
+    unwrap => Unwrap, is_error => IsError, is_nothing => IsNothing, propagate => Propagate,
+}
+
+pub fn nothing() -> Expression {
+    Expression::CNothing
+}
+
+/// `name = e`, typed like the parser types it: literals declare their type.
+pub fn assign(name: &str, e: impl Into<Expression>) -> Statement {
+    let e = e.into();
+    let kind = match &e {
+        Expression::CInt(_) => Some(Type::TInteger),
+        Expression::CReal(_) => Some(Type::TReal),
+        Expression::CDecimal(_) => Some(Type::TDecimal),
+        Expression::CComplex(_) => Some(Type::TComplex),
+        Expression::CString(_) => Some(Type::TString),
+        Expression::CBytes(_) => Some(Type::TBytes),
+        Expression::CTrue | Expression::CFalse => Some(Type::TBool),
+        _ => None,
+    };
+    Statement::Assignment(name.to_string(), Box::new(e), kind)
+}
+
+pub fn ret(e: impl Into<Expression>) -> Statement {
+    Statement::Return(boxed(e))
+}
+
+/// A call evaluated for its effects, as in `print(x)`.
+pub fn perform(e: impl Into<Expression>) -> Statement {
+    Statement::Call(boxed(e))
+}
+
+pub fn if_then(cond: impl Into<Expression>, then: Vec<Statement>) -> Statement {
+    Statement::IfThenElse(boxed(cond), Box::new(Statement::Block(then)), None)
+}
+
+pub fn if_else(cond: impl Into<Expression>, then: Vec<Statement>, otherwise: Vec<Statement>) -> Statement {
+    Statement::IfThenElse(
+        boxed(cond),
+        Box::new(Statement::Block(then)),
+        Some(Box::new(Statement::Block(otherwise))),
+    )
+}
+
+pub fn while_loop(cond: impl Into<Expression>, body: Vec<Statement>) -> Statement {
+    Statement::While(boxed(cond), Box::new(Statement::Block(body)))
+}
+
+/// Starts a function definition: `func("f").param("n", TInteger)
+/// .returns(TInteger).body(...)`.
+pub fn func(name: &str) -> FunctionBuilder {
+    FunctionBuilder {
+        name: name.to_string(),
+        kind: Type::TVoid,
+        params: vec![],
+    }
+}
+
+pub struct FunctionBuilder {
+    name: Name,
+    kind: Type,
+    params: Vec<(Name, Type)>,
+}
+
+impl FunctionBuilder {
+    pub fn param(mut self, name: &str, kind: Type) -> FunctionBuilder {
+        self.params.push((name.to_string(), kind));
+        self
+    }
+
+    pub fn returns(mut self, kind: Type) -> FunctionBuilder {
+        self.kind = kind;
+        self
+    }
+
+    pub fn function(self, body: Vec<Statement>) -> Function {
+        Function {
+            name: self.name,
+            kind: Some(self.kind),
+            params: Some(self.params),
+            body: Some(Box::new(Statement::Block(body))),
+        }
+    }
+
+    /// The definition of the function, as a statement.
+    pub fn body(self, body: Vec<Statement>) -> Statement {
+        Statement::FuncDef(self.function(body))
+    }
+}
+
+impl From<i32> for Expression {
+    fn from(n: i32) -> Expression {
+        Expression::CInt(n)
+    }
+}
+
+impl From<f64> for Expression {
+    fn from(x: f64) -> Expression {
+        Expression::CReal(x)
+    }
+}
+
+impl From<bool> for Expression {
+    fn from(b: bool) -> Expression {
+        if b {
+            Expression::CTrue
+        } else {
+            Expression::CFalse
+        }
+    }
+}
+
+/// A string literal; variables are built with `var`.
+impl From<&str> for Expression {
+    fn from(s: &str) -> Expression {
+        string(s)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interpreter::program::{execute_program, Stats};
+    use crate::ir::ast::Environment;
+    use crate::ir::ast::Type::*;
+    use crate::parser::parser::parse_program;
+    use crate::tc::type_checker::CheckedProgram;
+
+    #[test]
+    fn built_programs_match_parsed_ones() {
+        let program = vec![
+            func("fact").param("n", TInteger).returns(TInteger).body(vec![if_else(
+                eq(var("n"), 0),
+                vec![ret(1)],
+                vec![ret(mul(var("n"), call("fact", [sub(var("n"), 1)])))],
+            )]),
+            assign("greeting", "hi"),
+            assign("x", call("fact", [5])),
+        ];
+        let source = "def fact(n: TInteger) -> TInteger:\n    if n == 0:\n        return 1\n    else:\n        return n * fact(n - 1)\ngreeting = \"hi\"\nx = fact(5)";
+        assert_eq!(Ok(program.clone()), parse_program(source));
+
+        let mut env = Environment::new();
+        let checked = CheckedProgram::check(program).unwrap();
+        execute_program(&checked, &mut env, &mut Stats::default()).unwrap();
+        assert_eq!(
+            env.search_frame("x".to_string()),
+            Some(&crate::interpreter::interpreter::EnvValue::Exp(int(120)))
+        );
+    }
+
+    #[test]
+    fn expressions_convert_into_boxes() {
+        let sum: Expression = Expression::Add(int(1).into(), boxed(2));
+        assert_eq!(sum, add(1, 2));
+        assert_eq!(and(true, not(false)), Expression::And(Box::new(Expression::CTrue), Box::new(not(boolean(false)))));
+        assert_eq!(just(list([1, 2])), Expression::CJust(Box::new(Expression::CList(vec![int(1), int(2)]))));
+        assert_eq!(perform(call("print", ["hi"])), Statement::Call(Box::new(call("print", [string("hi")]))));
+    }
+}
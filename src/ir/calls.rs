@@ -0,0 +1,196 @@
+use crate::ir::ast::{CallSite, Clause, Expression, Function, Statement};
+
+/// Turns every function call into a `CachedCall`, whose site remembers the
+/// callee it found among the global functions, so that the calls of a
+/// recursive function stop looking its name up once they found it.
+pub fn cache(stmts: Vec<Statement>) -> Vec<Statement> {
+    stmts.into_iter().map(statement).collect()
+}
+
+pub fn statement(stmt: Statement) -> Statement {
+    let boxed = |stmt: Box<Statement>| Box::new(statement(*stmt));
+    let exp = |e: Box<Expression>| Box::new(expression(*e));
+
+    match stmt {
+        Statement::ConstDeclaration(name, kind, e) => Statement::ConstDeclaration(name, kind, exp(e)),
+        Statement::Assignment(name, e, kind) => Statement::Assignment(name, exp(e), kind),
+        Statement::AugmentedAssignment(name, operator, e) => Statement::AugmentedAssignment(name, operator, exp(e)),
+        Statement::Destructure(pattern, e) => Statement::Destructure(pattern, exp(e)),
+        Statement::IfThenElse(cond, then_stmt, else_stmt) => {
+            Statement::IfThenElse(exp(cond), boxed(then_stmt), else_stmt.map(boxed))
+        }
+        Statement::IfChain(branches, else_stmt) => Statement::IfChain(
+            branches
+                .into_iter()
+                .map(|(cond, stmt)| (expression(cond), statement(stmt)))
+                .collect(),
+            else_stmt.map(boxed),
+        ),
+        Statement::While(cond, body) => Statement::While(exp(cond), boxed(body)),
+        Statement::Block(stmts) => Statement::Block(cache(stmts)),
+        Statement::Sequence(s1, s2) => Statement::Sequence(boxed(s1), boxed(s2)),
+        Statement::AssertTrue(e, msg) => Statement::AssertTrue(exp(e), msg),
+        Statement::AssertFalse(e, msg) => Statement::AssertFalse(exp(e), msg),
+        Statement::AssertEQ(lhs, rhs, msg) => Statement::AssertEQ(exp(lhs), exp(rhs), msg),
+        Statement::AssertNEQ(lhs, rhs, msg) => Statement::AssertNEQ(exp(lhs), exp(rhs), msg),
+        Statement::TestDef(func) => Statement::TestDef(function(func)),
+        Statement::ModTestDef(name, stmt) => Statement::ModTestDef(name, boxed(stmt)),
+        Statement::FuncDef(func) => Statement::FuncDef(function(func)),
+        Statement::Return(e) => Statement::Return(exp(e)),
+        Statement::Requires(e) => Statement::Requires(exp(e)),
+        Statement::Ensures(e) => Statement::Ensures(exp(e)),
+        Statement::Invariant(e) => Statement::Invariant(exp(e)),
+        Statement::Decreases(e) => Statement::Decreases(exp(e)),
+        Statement::Locals(layout) => Statement::Locals(layout),
+        Statement::Call(e) => Statement::Call(exp(e)),
+        Statement::Match(e, cases) => Statement::Match(
+            exp(e),
+            cases
+                .into_iter()
+                .map(|(pattern, guard, stmt)| (pattern, guard.map(expression), boxed(stmt)))
+                .collect(),
+        ),
+        Statement::FuncClauses(name, kind, clauses) => Statement::FuncClauses(
+            name,
+            kind,
+            clauses
+                .into_iter()
+                .map(|clause| Clause {
+                    guard: clause.guard.map(expression),
+                    value: expression(clause.value),
+                    ..clause
+                })
+                .collect(),
+        ),
+        Statement::InstanceDeclaration(class, adt_name, methods) => Statement::InstanceDeclaration(
+            class,
+            adt_name,
+            methods.into_iter().map(function).collect(),
+        ),
+        Statement::At(position, stmt) => Statement::At(position, boxed(stmt)),
+        Statement::VarDeclaration(_)
+        | Statement::ValDeclaration(_)
+        | Statement::AssertFails(_)
+        | Statement::Import(_)
+        | Statement::Module(_)
+        | Statement::ADTDeclaration(..)
+        | Statement::FixityDeclaration(..) => stmt,
+    }
+}
+
+fn expression(exp: Expression) -> Expression {
+    let boxed = |e: Box<Expression>| Box::new(expression(*e));
+    let all = |exps: Vec<Expression>| exps.into_iter().map(expression).collect::<Vec<_>>();
+
+    match exp {
+        Expression::CList(items) => Expression::CList(all(items)),
+        Expression::CTuple(items) => Expression::CTuple(all(items)),
+        Expression::CDict(entries) => Expression::CDict(
+            entries
+                .into_iter()
+                .map(|(k, v)| (expression(k), expression(v)))
+                .collect(),
+        ),
+        Expression::FuncCall(name, args) => Expression::CachedCall(CallSite::new(name), all(args)),
+        Expression::CachedCall(site, args) => Expression::CachedCall(site, all(args)),
+        Expression::Pipe(value, name, args) => Expression::Pipe(boxed(value), name, all(args)),
+        Expression::Infix(first, rest) => Expression::Infix(
+            boxed(first),
+            rest.into_iter()
+                .map(|(op, operand)| (op, expression(operand)))
+                .collect(),
+        ),
+        Expression::Add(l, r) => Expression::Add(boxed(l), boxed(r)),
+        Expression::Sub(l, r) => Expression::Sub(boxed(l), boxed(r)),
+        Expression::Mul(l, r) => Expression::Mul(boxed(l), boxed(r)),
+        Expression::Div(l, r) => Expression::Div(boxed(l), boxed(r)),
+        Expression::And(l, r) => Expression::And(boxed(l), boxed(r)),
+        Expression::Or(l, r) => Expression::Or(boxed(l), boxed(r)),
+        Expression::Not(e) => Expression::Not(boxed(e)),
+        Expression::EQ(l, r) => Expression::EQ(boxed(l), boxed(r)),
+        Expression::GT(l, r) => Expression::GT(boxed(l), boxed(r)),
+        Expression::LT(l, r) => Expression::LT(boxed(l), boxed(r)),
+        Expression::GTE(l, r) => Expression::GTE(boxed(l), boxed(r)),
+        Expression::LTE(l, r) => Expression::LTE(boxed(l), boxed(r)),
+        Expression::COk(e) => Expression::COk(boxed(e)),
+        Expression::CErr(e) => Expression::CErr(boxed(e)),
+        Expression::CJust(e) => Expression::CJust(boxed(e)),
+        Expression::Unwrap(e) => Expression::Unwrap(boxed(e)),
+        Expression::IsError(e) => Expression::IsError(boxed(e)),
+        Expression::IsNothing(e) => Expression::IsNothing(boxed(e)),
+        Expression::Propagate(e) => Expression::Propagate(boxed(e)),
+        Expression::ADTConstructor(adt_name, constructor_name, args) => {
+            Expression::ADTConstructor(adt_name, constructor_name, args.into_iter().map(boxed).collect())
+        }
+        Expression::CTrue
+        | Expression::CFalse
+        | Expression::CInt(_)
+        | Expression::CReal(_)
+        | Expression::CDecimal(_)
+        | Expression::CComplex(_)
+        | Expression::CString(_)
+        | Expression::CBytes(_)
+        | Expression::CVoid
+        | Expression::CNothing
+        | Expression::Var(_)
+        | Expression::Slot(..)
+        | Expression::ADTValue(..)
+        | Expression::Closure(..) => exp,
+    }
+}
+
+fn function(func: Function) -> Function {
+    Function {
+        body: func.body.map(|body| Box::new(statement(*body))),
+        ..func
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interpreter::interpreter::EnvValue;
+    use crate::interpreter::program::run_program;
+    use crate::parser::parser::dedent;
+    use crate::tc::type_checker::CheckedProgram;
+
+    #[test]
+    fn sites_cache_their_callee_until_the_snapshot_changes() {
+        let site = CallSite::new("f".to_string());
+        let callee = || Some((Function::new(), vec![]));
+
+        assert_eq!(site.resolve(1, || panic!("the first lookup is not cached")), None);
+        assert!(site.resolve(1, callee).is_some());
+        assert!(site.resolve(1, || panic!("the callee is cached")).is_some());
+
+        // A new snapshot may bind the name to something else
+        assert_eq!(site.resolve(2, || panic!("the first lookup is not cached")), None);
+        assert!(site.resolve(2, callee).is_some());
+
+        // Clones share the cache
+        assert!(site.clone().resolve(2, || panic!("the callee is cached")).is_some());
+    }
+
+    #[test]
+    fn checked_calls_go_through_cached_sites() {
+        let source = "
+            def fib(n: TInteger) -> TInteger:
+                if n < 2:
+                    return n
+                return fib(n - 1) + fib(n - 2)
+            x = fib(15)
+            ";
+        let program = CheckedProgram::check(crate::rpy!(source)).unwrap();
+        assert!(matches!(
+            &program.statements()[1],
+            Statement::Assignment(_, call, _) if matches!(**call, Expression::CachedCall(..))
+        ));
+
+        let outcome = run_program(&dedent(source));
+        assert_eq!(outcome.diagnostics, Vec::<String>::new());
+        assert_eq!(
+            outcome.final_env.search_frame("x".to_string()),
+            Some(&EnvValue::Exp(Expression::CInt(610)))
+        );
+    }
+}
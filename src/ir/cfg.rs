@@ -0,0 +1,443 @@
+use crate::ir::ast::{Expression, Function, Name, Pattern, Position, Statement};
+use std::collections::BTreeSet;
+
+/// Control-flow graph of one function body, or of the top-level program.
+///
+/// Blocks are numbered in the order they are created; `entry` is always 0
+/// and `exit` is the single block every `Return` and the end of the body
+/// lead to. Compound statements never appear inside a block: they are
+/// lowered into terminators and successor blocks. Empty blocks nothing
+/// leads to, such as the join after an `if` whose branches both return,
+/// are left out.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Cfg {
+    pub name: Name,
+    pub blocks: Vec<BasicBlock>,
+    pub entry: usize,
+    pub exit: usize,
+}
+
+/// Positions are those of the statements `parse_located` wraps the code
+/// in, and `None` for code that did not come from it.
+#[derive(Debug, PartialEq, Clone)]
+pub struct BasicBlock {
+    pub id: usize,
+    /// Each statement with where it starts.
+    pub statements: Vec<(Statement, Option<Position>)>,
+    pub terminator: Terminator,
+    /// Where the `if`, `while`, `match` or `return` the terminator comes
+    /// from starts.
+    pub terminator_at: Option<Position>,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum Terminator {
+    Goto(usize),
+    /// Condition, block when true, block when false.
+    Branch(Expression, usize, usize),
+    /// Scrutinee and the block of each arm's pattern and guard.
+    Match(Expression, Vec<(Pattern, Option<Expression>, usize)>),
+    Return(Expression, usize),
+    /// Terminator of the exit block.
+    Exit,
+}
+
+impl Terminator {
+    /// Every block control may go to next.
+    pub fn successors(&self) -> Vec<usize> {
+        match self {
+            Terminator::Goto(target) => vec![*target],
+            Terminator::Branch(_, then_block, else_block) => vec![*then_block, *else_block],
+            Terminator::Match(_, arms) => arms.iter().map(|(_, _, block)| *block).collect(),
+            Terminator::Return(_, exit) => vec![*exit],
+            Terminator::Exit => vec![],
+        }
+    }
+
+    /// The same terminator, going to `ids[block]` instead of each `block`.
+    fn renumbered(self, ids: &[usize]) -> Terminator {
+        match self {
+            Terminator::Goto(target) => Terminator::Goto(ids[target]),
+            Terminator::Branch(cond, then_block, else_block) => {
+                Terminator::Branch(cond, ids[then_block], ids[else_block])
+            }
+            Terminator::Match(exp, arms) => Terminator::Match(
+                exp,
+                arms.into_iter()
+                    .map(|(pattern, guard, block)| (pattern, guard, ids[block]))
+                    .collect(),
+            ),
+            Terminator::Return(exp, exit) => Terminator::Return(exp, ids[exit]),
+            Terminator::Exit => Terminator::Exit,
+        }
+    }
+
+    /// The successors that can actually be taken: a branch on a literal
+    /// `True` or `False` only ever goes one way.
+    pub fn live_successors(&self) -> Vec<usize> {
+        match self {
+            Terminator::Branch(Expression::CTrue, then_block, _) => vec![*then_block],
+            Terminator::Branch(Expression::CFalse, _, else_block) => vec![*else_block],
+            _ => self.successors(),
+        }
+    }
+}
+
+impl Cfg {
+    /// Builds the graph of `body`. Functions defined inside it get graphs
+    /// of their own from `functions`.
+    pub fn build(name: &str, body: &Statement) -> Cfg {
+        let mut builder = Builder {
+            blocks: vec![],
+            current: 0,
+            position: None,
+        };
+        let entry = builder.new_block();
+        let exit = builder.new_block();
+        builder.current = entry;
+
+        builder.lower(body, exit);
+        builder.position = None;
+        builder.finish(Terminator::Goto(exit));
+        builder.blocks[exit].terminator = Terminator::Exit;
+
+        let mut cfg = Cfg {
+            name: name.to_string(),
+            blocks: builder.blocks,
+            entry,
+            exit,
+        };
+        cfg.prune();
+        cfg
+    }
+
+    /// Removes the empty blocks without predecessors the builder leaves
+    /// behind, until there are none, and numbers the rest again in order.
+    fn prune(&mut self) {
+        let mut removed = vec![false; self.blocks.len()];
+        loop {
+            let preds = self.predecessors();
+            let dangling: Vec<usize> = self
+                .blocks
+                .iter()
+                .filter(|block| !removed[block.id] && block.id != self.entry && block.id != self.exit)
+                .filter(|block| preds[block.id].iter().all(|pred| removed[*pred]))
+                .filter(|block| block.statements.is_empty() && matches!(block.terminator, Terminator::Goto(_)))
+                .map(|block| block.id)
+                .collect();
+            if dangling.is_empty() {
+                break;
+            }
+            dangling.into_iter().for_each(|id| removed[id] = true);
+        }
+
+        let mut ids = vec![0; self.blocks.len()];
+        let mut next = 0;
+        for id in 0..self.blocks.len() {
+            ids[id] = next;
+            next += usize::from(!removed[id]);
+        }
+        let blocks = std::mem::take(&mut self.blocks);
+        self.blocks = blocks
+            .into_iter()
+            .filter(|block| !removed[block.id])
+            .map(|block| BasicBlock {
+                id: ids[block.id],
+                terminator: block.terminator.renumbered(&ids),
+                ..block
+            })
+            .collect();
+        self.entry = ids[self.entry];
+        self.exit = ids[self.exit];
+    }
+
+    /// The blocks that may run right before each block, indexed by block id.
+    pub fn predecessors(&self) -> Vec<Vec<usize>> {
+        let mut preds = vec![vec![]; self.blocks.len()];
+        for block in &self.blocks {
+            for succ in block.terminator.successors() {
+                preds[succ].push(block.id);
+            }
+        }
+        preds
+    }
+
+    /// The blocks some run of the body can reach from the entry.
+    pub fn reachable(&self) -> BTreeSet<usize> {
+        let mut seen = BTreeSet::new();
+        let mut pending = vec![self.entry];
+        while let Some(id) = pending.pop() {
+            if seen.insert(id) {
+                pending.extend(self.blocks[id].terminator.live_successors());
+            }
+        }
+        seen
+    }
+
+    /// Blocks holding code that never runs, e.g. statements after a
+    /// `return` or the body of `while False`. Empty blocks the builder adds
+    /// as join points are not reported.
+    pub fn dead_blocks(&self) -> Vec<usize> {
+        let reachable = self.reachable();
+        self.blocks
+            .iter()
+            .filter(|block| !reachable.contains(&block.id))
+            .filter(|block| {
+                !block.statements.is_empty()
+                    || !matches!(block.terminator, Terminator::Goto(_) | Terminator::Exit)
+            })
+            .map(|block| block.id)
+            .collect()
+    }
+
+    /// Whether some run reaches the end of the body without a `return`.
+    pub fn falls_through(&self) -> bool {
+        let reachable = self.reachable();
+        self.blocks.iter().any(|block| {
+            reachable.contains(&block.id)
+                && block.terminator.live_successors().contains(&self.exit)
+                && !matches!(block.terminator, Terminator::Return(..))
+        })
+    }
+}
+
+/// One graph for the top-level statements, named `__main__`, followed by one
+/// for every function the program defines, nested ones and instance
+/// methods included.
+pub fn program(stmts: &[Statement]) -> Vec<Cfg> {
+    let main = Statement::Block(stmts.to_vec());
+    let mut cfgs = vec![Cfg::build("__main__", &main)];
+    let mut funcs = vec![];
+    functions(&main, &mut funcs);
+    cfgs.extend(
+        funcs
+            .into_iter()
+            .filter_map(|func| func.body.as_ref().map(|body| Cfg::build(&func.name, body))),
+    );
+    cfgs
+}
+
+/// Functions defined anywhere in `stmt`, in source order.
+pub fn functions<'a>(stmt: &'a Statement, found: &mut Vec<&'a Function>) {
+    match stmt {
+        Statement::At(_, stmt) => functions(stmt, found),
+        Statement::FuncDef(func) => {
+            found.push(func);
+            if let Some(body) = &func.body {
+                functions(body, found);
+            }
+        }
+        Statement::InstanceDeclaration(_, _, methods) => {
+            for method in methods {
+                found.push(method);
+                if let Some(body) = &method.body {
+                    functions(body, found);
+                }
+            }
+        }
+        Statement::Block(stmts) => stmts.iter().for_each(|s| functions(s, found)),
+        Statement::Sequence(s1, s2) => {
+            functions(s1, found);
+            functions(s2, found);
+        }
+        Statement::IfThenElse(_, then_stmt, else_stmt) => {
+            functions(then_stmt, found);
+            if let Some(else_stmt) = else_stmt {
+                functions(else_stmt, found);
+            }
+        }
+        Statement::While(_, body) => functions(body, found),
+        Statement::Match(_, cases) => cases.iter().for_each(|(_, _, s)| functions(s, found)),
+        _ => {}
+    }
+}
+
+struct Builder {
+    blocks: Vec<BasicBlock>,
+    current: usize,
+    /// Where the innermost located statement being lowered starts.
+    position: Option<Position>,
+}
+
+impl Builder {
+    fn new_block(&mut self) -> usize {
+        let id = self.blocks.len();
+        self.blocks.push(BasicBlock {
+            id,
+            statements: vec![],
+            terminator: Terminator::Exit,
+            terminator_at: None,
+        });
+        id
+    }
+
+    /// Ends the current block with `terminator`, which the statement being
+    /// lowered gives rise to.
+    fn finish(&mut self, terminator: Terminator) {
+        self.blocks[self.current].terminator = terminator;
+        self.blocks[self.current].terminator_at = self.position;
+    }
+
+    fn lower(&mut self, stmt: &Statement, exit: usize) {
+        match stmt {
+            Statement::At(position, stmt) => {
+                let outer = self.position.replace(*position);
+                self.lower(stmt, exit);
+                self.position = outer;
+            }
+            Statement::Block(stmts) => stmts.iter().for_each(|s| self.lower(s, exit)),
+            Statement::Sequence(s1, s2) => {
+                self.lower(s1, exit);
+                self.lower(s2, exit);
+            }
+            Statement::IfThenElse(cond, then_stmt, else_stmt) => {
+                let then_block = self.new_block();
+                let else_block = else_stmt.as_ref().map(|_| self.new_block());
+                let join = self.new_block();
+                self.finish(Terminator::Branch(
+                    *cond.clone(),
+                    then_block,
+                    else_block.unwrap_or(join),
+                ));
+
+                self.current = then_block;
+                self.lower(then_stmt, exit);
+                self.finish(Terminator::Goto(join));
+
+                if let (Some(else_block), Some(else_stmt)) = (else_block, else_stmt) {
+                    self.current = else_block;
+                    self.lower(else_stmt, exit);
+                    self.finish(Terminator::Goto(join));
+                }
+                self.current = join;
+            }
+            Statement::While(cond, body) => {
+                let header = self.new_block();
+                let body_block = self.new_block();
+                let after = self.new_block();
+                self.finish(Terminator::Goto(header));
+
+                self.current = header;
+                self.finish(Terminator::Branch(*cond.clone(), body_block, after));
+
+                self.current = body_block;
+                self.lower(body, exit);
+                self.finish(Terminator::Goto(header));
+                self.current = after;
+            }
+            Statement::Match(exp, cases) => {
+                let arms: Vec<(Pattern, Option<Expression>, usize)> = cases
+                    .iter()
+                    .map(|(pattern, guard, _)| (pattern.clone(), guard.clone(), self.new_block()))
+                    .collect();
+                let join = self.new_block();
+                self.finish(Terminator::Match(*exp.clone(), arms.clone()));
+
+                for ((_, _, block), (_, _, stmt)) in arms.iter().zip(cases) {
+                    self.current = *block;
+                    self.lower(stmt, exit);
+                    self.finish(Terminator::Goto(join));
+                }
+                self.current = join;
+            }
+            Statement::Return(exp) => {
+                self.finish(Terminator::Return(*exp.clone(), exit));
+                // Whatever follows a return is unreachable, but still gets
+                // a block so that analyses can report it
+                self.current = self.new_block();
+            }
+            _ => self.blocks[self.current].statements.push((stmt.clone(), self.position)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ir::ast::Expression::*;
+    use crate::ir::ast::Statement::*;
+
+    #[test]
+    fn loops_and_branches_become_blocks() {
+        let body = Block(vec![
+            Assignment("x".to_string(), Box::new(CInt(0)), None),
+            While(
+                Box::new(Var("go".to_string())),
+                Box::new(IfThenElse(
+                    Box::new(Var("x".to_string())),
+                    Box::new(Return(Box::new(CInt(1)))),
+                    None,
+                )),
+            ),
+            Return(Box::new(CInt(2))),
+        ]);
+        let cfg = Cfg::build("f", &body);
+
+        let terminators: Vec<&Terminator> = cfg.blocks.iter().map(|b| &b.terminator).collect();
+        assert_eq!(
+            terminators,
+            vec![
+                &Terminator::Goto(2),
+                &Terminator::Exit,
+                &Terminator::Branch(Var("go".to_string()), 3, 4),
+                &Terminator::Branch(Var("x".to_string()), 5, 6),
+                &Terminator::Return(CInt(2), 1),
+                &Terminator::Return(CInt(1), 1),
+                &Terminator::Goto(2),
+            ]
+        );
+        assert_eq!(cfg.blocks[0].statements.len(), 1);
+    }
+
+    #[test]
+    fn analyses_find_dead_code_and_missing_returns() {
+        let ret = |value: i32| Return(Box::new(CInt(value)));
+
+        let returns_in_both_branches = Block(vec![
+            IfThenElse(
+                Box::new(Var("c".to_string())),
+                Box::new(ret(1)),
+                Some(Box::new(ret(2))),
+            ),
+            Assignment("x".to_string(), Box::new(CInt(0)), None),
+        ]);
+        let cfg = Cfg::build("f", &returns_in_both_branches);
+        assert!(!cfg.falls_through());
+        assert_eq!(cfg.dead_blocks(), vec![4]);
+        assert_eq!(cfg.predecessors()[cfg.exit], vec![2, 3, 4]);
+
+        let missing_else = IfThenElse(Box::new(Var("c".to_string())), Box::new(ret(1)), None);
+        assert!(Cfg::build("g", &missing_else).falls_through());
+
+        let forever = While(Box::new(CTrue), Box::new(ret(1)));
+        let cfg = Cfg::build("h", &forever);
+        assert!(!cfg.falls_through());
+        assert!(cfg.dead_blocks().is_empty());
+
+        let never = While(Box::new(CFalse), Box::new(ret(1)));
+        assert_eq!(Cfg::build("k", &never).dead_blocks(), vec![3]);
+    }
+
+    #[test]
+    fn located_code_keeps_its_positions_and_leaves_no_dangling_blocks() {
+        let source = "def f(c: TBool) -> TInteger:\n    x = 1\n    if c:\n        return x\n    else:\n        return 2";
+        let stmts = crate::ir::desugar::program(crate::parser::parser::parse_located(source).unwrap());
+        let cfg = &program(&stmts)[1];
+        let at = |line, column| Some(Position { line, column });
+
+        // The join after the `if` and the blocks after each `return` are gone
+        let terminators: Vec<&Terminator> = cfg.blocks.iter().map(|b| &b.terminator).collect();
+        assert_eq!(
+            terminators,
+            vec![
+                &Terminator::Branch(Var("c".to_string()), 2, 3),
+                &Terminator::Exit,
+                &Terminator::Return(Var("x".to_string()), 1),
+                &Terminator::Return(CInt(2), 1),
+            ]
+        );
+        assert_eq!(cfg.blocks[0].statements[0].1, at(2, 5));
+        let positions: Vec<_> = cfg.blocks.iter().map(|b| b.terminator_at).collect();
+        assert_eq!(positions, vec![at(3, 5), None, at(4, 9), at(6, 9)]);
+    }
+}
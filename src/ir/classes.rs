@@ -0,0 +1,121 @@
+use crate::ir::ast::Type;
+
+/// A built-in type class and the methods its instances must define.
+///
+/// Classes map onto the operators that already exist: `Eq` on `==`, `Ord`
+/// on the relational operators and `Num` on the arithmetic ones. `Show`
+/// backs the `show()` builtin.
+pub struct Class {
+    pub name: &'static str,
+    pub methods: &'static [Method],
+}
+
+pub struct Method {
+    pub name: &'static str,
+    pub arity: usize,
+    pub returns: Returns,
+}
+
+pub enum Returns {
+    Bool,
+    String,
+    Instance,
+}
+
+pub const CLASSES: &[Class] = &[
+    Class {
+        name: "Eq",
+        methods: &[Method {
+            name: "eq",
+            arity: 2,
+            returns: Returns::Bool,
+        }],
+    },
+    Class {
+        name: "Ord",
+        methods: &[Method {
+            name: "lt",
+            arity: 2,
+            returns: Returns::Bool,
+        }],
+    },
+    Class {
+        name: "Show",
+        methods: &[Method {
+            name: "show",
+            arity: 1,
+            returns: Returns::String,
+        }],
+    },
+    Class {
+        name: "Num",
+        methods: &[
+            Method {
+                name: "add",
+                arity: 2,
+                returns: Returns::Instance,
+            },
+            Method {
+                name: "sub",
+                arity: 2,
+                returns: Returns::Instance,
+            },
+            Method {
+                name: "mul",
+                arity: 2,
+                returns: Returns::Instance,
+            },
+            Method {
+                name: "div",
+                arity: 2,
+                returns: Returns::Instance,
+            },
+        ],
+    },
+];
+
+pub fn lookup_class(name: &str) -> Option<&'static Class> {
+    CLASSES.iter().find(|class| class.name == name)
+}
+
+impl Method {
+    /// Signature of the method in an instance for `instance`.
+    pub fn signature(&self, instance: &Type) -> Type {
+        let returns = match self.returns {
+            Returns::Bool => Type::TBool,
+            Returns::String => Type::TString,
+            Returns::Instance => instance.clone(),
+        };
+        Type::TFunction(Box::new(Some(returns)), vec![instance.clone(); self.arity])
+    }
+}
+
+/// Primitive types are instances of the classes whose operators already
+/// work on them. Every ADT is an `Eq` instance through structural equality.
+pub fn builtin_instance(class: &str, kind: &Type) -> bool {
+    match kind {
+        Type::TInteger | Type::TReal | Type::TDecimal => true,
+        Type::TComplex => class != "Ord",
+        Type::TString | Type::TBool => class == "Show",
+        Type::Tadt(..) => class == "Eq",
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn method_signatures_use_the_instance_type() {
+        let num = lookup_class("Num").unwrap();
+
+        assert_eq!(
+            num.methods[0].signature(&Type::TReal),
+            Type::TFunction(Box::new(Some(Type::TReal)), vec![Type::TReal, Type::TReal])
+        );
+        assert!(lookup_class("Functor").is_none());
+        assert!(builtin_instance("Show", &Type::TBool));
+        assert!(!builtin_instance("Ord", &Type::TString));
+    }
+}
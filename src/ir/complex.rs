@@ -0,0 +1,129 @@
+use std::fmt;
+use std::ops::{Add, Div, Mul, Sub};
+
+/// A complex number with real parts, as Python's `complex`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Complex {
+    pub re: f64,
+    pub im: f64,
+}
+
+impl Complex {
+    pub fn new(re: f64, im: f64) -> Complex {
+        Complex { re, im }
+    }
+
+    /// The magnitude, computed without overflowing for large parts.
+    pub fn abs(&self) -> f64 {
+        self.re.hypot(self.im)
+    }
+
+    /// The angle with the positive real axis, in `(-pi, pi]`.
+    pub fn arg(&self) -> f64 {
+        self.im.atan2(self.re)
+    }
+
+    pub fn conj(&self) -> Complex {
+        Complex::new(self.re, -self.im)
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.re == 0.0 && self.im == 0.0
+    }
+}
+
+impl From<f64> for Complex {
+    fn from(re: f64) -> Complex {
+        Complex::new(re, 0.0)
+    }
+}
+
+impl Add for Complex {
+    type Output = Complex;
+
+    fn add(self, other: Complex) -> Complex {
+        Complex::new(self.re + other.re, self.im + other.im)
+    }
+}
+
+impl Sub for Complex {
+    type Output = Complex;
+
+    fn sub(self, other: Complex) -> Complex {
+        Complex::new(self.re - other.re, self.im - other.im)
+    }
+}
+
+impl Mul for Complex {
+    type Output = Complex;
+
+    fn mul(self, other: Complex) -> Complex {
+        Complex::new(
+            self.re * other.re - self.im * other.im,
+            self.re * other.im + self.im * other.re,
+        )
+    }
+}
+
+impl Div for Complex {
+    type Output = Complex;
+
+    /// Smith's algorithm, which scales by the larger part of the divisor
+    /// so the intermediate products do not overflow.
+    fn div(self, other: Complex) -> Complex {
+        if other.re.abs() >= other.im.abs() {
+            let ratio = other.im / other.re;
+            let denominator = other.re + other.im * ratio;
+            Complex::new(
+                (self.re + self.im * ratio) / denominator,
+                (self.im - self.re * ratio) / denominator,
+            )
+        } else {
+            let ratio = other.re / other.im;
+            let denominator = other.re * ratio + other.im;
+            Complex::new(
+                (self.re * ratio + self.im) / denominator,
+                (self.im * ratio - self.re) / denominator,
+            )
+        }
+    }
+}
+
+/// Written as in Python: `2.0j` without a real part, `(1.0-2.0j)` with
+/// one. Both read back as the same value.
+impl fmt::Display for Complex {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.re == 0.0 && self.re.is_sign_positive() {
+            return write!(f, "{:?}j", self.im);
+        }
+        let sign = if self.im.is_sign_negative() { '-' } else { '+' };
+        write!(f, "({:?}{}{:?}j)", self.re, sign, self.im.abs())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx::assert_relative_eq;
+
+    #[test]
+    fn complex_arithmetic() {
+        let (a, b) = (Complex::new(1.0, 2.0), Complex::new(3.0, -1.0));
+
+        assert_eq!(a + b, Complex::new(4.0, 1.0));
+        assert_eq!(a - b, Complex::new(-2.0, 3.0));
+        assert_eq!(a * b, Complex::new(5.0, 5.0));
+        assert_eq!(Complex::new(5.0, 5.0) / a, b);
+        assert_eq!(Complex::new(0.0, 1.0) * Complex::new(0.0, 1.0), Complex::from(-1.0));
+        assert_eq!(Complex::new(3.0, 4.0).abs(), 5.0);
+        assert_relative_eq!(Complex::new(0.0, 1.0).arg(), std::f64::consts::FRAC_PI_2);
+        assert_eq!(a.conj(), Complex::new(1.0, -2.0));
+    }
+
+    #[test]
+    fn complex_numbers_are_written_as_in_python() {
+        assert_eq!(Complex::new(0.0, 2.0).to_string(), "2.0j");
+        assert_eq!(Complex::new(1.0, -2.5).to_string(), "(1.0-2.5j)");
+        assert_eq!(Complex::new(-1.0, 0.0).to_string(), "(-1.0+0.0j)");
+    }
+}
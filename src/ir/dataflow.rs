@@ -0,0 +1,359 @@
+use crate::ir::ast::{Expression, Name, Position, Statement};
+use crate::ir::cfg::{self, Cfg, Terminator};
+use crate::ir::pretty;
+use std::collections::BTreeMap;
+
+/// What constant propagation knows about a name at some point. Names it
+/// knows nothing about are left out of the state.
+#[derive(Debug, PartialEq, Clone)]
+pub enum Const {
+    Int(i32),
+    Bool(bool),
+    /// A bytes value of known length.
+    Bytes(usize),
+}
+
+type State = BTreeMap<Name, Const>;
+
+/// A likely mistake found without running the program: the function, the
+/// place and the code it is about. The place is known for programs that
+/// `parse_located` parsed.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Warning {
+    pub function: Name,
+    pub position: Option<Position>,
+    pub code: String,
+    pub message: String,
+}
+
+impl Warning {
+    pub fn render(&self) -> String {
+        match self.position {
+            Some(Position { line, column }) => format!(
+                "[Warning] in '{}', line {}, column {}: `{}`: {}",
+                self.function, line, column, self.code, self.message
+            ),
+            None => format!("[Warning] in '{}': `{}`: {}", self.function, self.code, self.message),
+        }
+    }
+}
+
+/// Warnings for every function of a desugared program, top level first.
+pub fn program(stmts: &[Statement]) -> Vec<Warning> {
+    cfg::program(stmts).iter().flat_map(warnings).collect()
+}
+
+/// Flags conditions that are always true or always false, divisions by a
+/// zero constant and `byte_at` calls whose index is out of range.
+pub fn warnings(cfg: &Cfg) -> Vec<Warning> {
+    let states = constants(cfg);
+    let mut found = vec![];
+
+    for block in &cfg.blocks {
+        let Some(state) = &states[block.id] else {
+            continue;
+        };
+        let mut state = state.clone();
+        let mut warn = |position: Option<Position>, code: String, message: String| {
+            found.push(Warning {
+                function: cfg.name.clone(),
+                position,
+                code,
+                message,
+            })
+        };
+
+        for (stmt, position) in &block.statements {
+            for exp in expressions(stmt) {
+                for message in check_expression(exp, &state) {
+                    warn(*position, pretty::statement(stmt), message);
+                }
+            }
+            transfer(stmt, &mut state);
+        }
+        let at = block.terminator_at;
+
+        match &block.terminator {
+            Terminator::Branch(cond, _, _) => {
+                for message in check_expression(cond, &state) {
+                    warn(at, format!("if {}", pretty::expression(cond)), message);
+                }
+                // Literal conditions, as in `while True`, are deliberate
+                if !matches!(cond, Expression::CTrue | Expression::CFalse) {
+                    if let Some(Const::Bool(value)) = eval(cond, &state) {
+                        let always = if value { "true" } else { "false" };
+                        warn(
+                            at,
+                            format!("if {}", pretty::expression(cond)),
+                            format!("condition is always {}.", always),
+                        );
+                    }
+                }
+            }
+            Terminator::Match(exp, _) | Terminator::Return(exp, _) => {
+                for message in check_expression(exp, &state) {
+                    warn(at, pretty::expression(exp), message);
+                }
+            }
+            Terminator::Goto(_) | Terminator::Exit => {}
+        }
+    }
+    found
+}
+
+/// The constants known on entry to each block, or `None` for blocks that
+/// never run. Solved forwards to a fixed point: a name keeps its constant
+/// at a join only if every incoming path agrees on it.
+pub fn constants(cfg: &Cfg) -> Vec<Option<State>> {
+    let preds = cfg.predecessors();
+    let mut entry: Vec<Option<State>> = vec![None; cfg.blocks.len()];
+    let mut exit: Vec<Option<State>> = vec![None; cfg.blocks.len()];
+
+    let mut pending = vec![cfg.entry];
+    while let Some(id) = pending.pop() {
+        let incoming = if id == cfg.entry {
+            Some(State::new())
+        } else {
+            preds[id]
+                .iter()
+                .filter(|pred| cfg.blocks[**pred].terminator.live_successors().contains(&id))
+                .filter_map(|pred| exit[*pred].clone())
+                .reduce(|a, b| {
+                    a.into_iter()
+                        .filter(|(name, value)| b.get(name) == Some(value))
+                        .collect()
+                })
+        };
+        let Some(state) = incoming else {
+            continue;
+        };
+
+        let mut out = state.clone();
+        for (stmt, _) in &cfg.blocks[id].statements {
+            transfer(stmt, &mut out);
+        }
+        // The arms of a match rebind the variables of their patterns
+        if let Terminator::Match(_, arms) = &cfg.blocks[id].terminator {
+            for name in arms.iter().flat_map(|(pattern, _, _)| pattern.variables()) {
+                out.remove(&name);
+            }
+        }
+        entry[id] = Some(state);
+        if exit[id].as_ref() != Some(&out) {
+            exit[id] = Some(out);
+            pending.extend(cfg.blocks[id].terminator.live_successors());
+        }
+    }
+    entry
+}
+
+fn transfer(stmt: &Statement, state: &mut State) {
+    match stmt {
+        Statement::Assignment(name, exp, _) | Statement::ConstDeclaration(name, _, exp) => {
+            match eval(exp, state) {
+                Some(value) => state.insert(name.clone(), value),
+                None => state.remove(name),
+            };
+        }
+        Statement::Destructure(pattern, _) => pattern.variables().iter().for_each(|name| {
+            state.remove(name);
+        }),
+        _ => {}
+    }
+}
+
+fn eval(exp: &Expression, state: &State) -> Option<Const> {
+    let ints = |l: &Expression, r: &Expression| match (eval(l, state), eval(r, state)) {
+        (Some(Const::Int(l)), Some(Const::Int(r))) => Some((l, r)),
+        _ => None,
+    };
+    let bools = |l: &Expression, r: &Expression| match (eval(l, state), eval(r, state)) {
+        (Some(Const::Bool(l)), Some(Const::Bool(r))) => Some((l, r)),
+        _ => None,
+    };
+
+    match exp {
+        Expression::CInt(value) => Some(Const::Int(*value)),
+        Expression::CTrue => Some(Const::Bool(true)),
+        Expression::CFalse => Some(Const::Bool(false)),
+        Expression::CBytes(bytes) => Some(Const::Bytes(bytes.len())),
+        Expression::Var(name) => state.get(name).cloned(),
+        Expression::Add(l, r) => ints(l, r).and_then(|(l, r)| l.checked_add(r)).map(Const::Int),
+        Expression::Sub(l, r) => ints(l, r).and_then(|(l, r)| l.checked_sub(r)).map(Const::Int),
+        Expression::Mul(l, r) => ints(l, r).and_then(|(l, r)| l.checked_mul(r)).map(Const::Int),
+        Expression::Div(l, r) => ints(l, r).and_then(|(l, r)| l.checked_div(r)).map(Const::Int),
+        Expression::EQ(l, r) => match (eval(l, state), eval(r, state)) {
+            (Some(l), Some(r)) => Some(Const::Bool(l == r)),
+            _ => None,
+        },
+        Expression::GT(l, r) => ints(l, r).map(|(l, r)| Const::Bool(l > r)),
+        Expression::LT(l, r) => ints(l, r).map(|(l, r)| Const::Bool(l < r)),
+        Expression::GTE(l, r) => ints(l, r).map(|(l, r)| Const::Bool(l >= r)),
+        Expression::LTE(l, r) => ints(l, r).map(|(l, r)| Const::Bool(l <= r)),
+        Expression::And(l, r) => bools(l, r).map(|(l, r)| Const::Bool(l && r)),
+        Expression::Or(l, r) => bools(l, r).map(|(l, r)| Const::Bool(l || r)),
+        Expression::Not(e) => match eval(e, state) {
+            Some(Const::Bool(value)) => Some(Const::Bool(!value)),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Problems certain to happen whenever `exp` is evaluated in `state`.
+fn check_expression(exp: &Expression, state: &State) -> Vec<String> {
+    let mut found = vec![];
+    let mut pending = vec![exp];
+
+    while let Some(exp) = pending.pop() {
+        match exp {
+            Expression::Div(_, r) if eval(r, state) == Some(Const::Int(0)) => {
+                found.push(format!("division by zero: '{}' is always 0.", pretty::expression(r)))
+            }
+            Expression::FuncCall(name, args) if name == "byte_at" && args.len() == 2 => {
+                if let (Some(Const::Bytes(len)), Some(Const::Int(index))) =
+                    (eval(&args[0], state), eval(&args[1], state))
+                {
+                    if index < 0 || index as usize >= len {
+                        found.push(format!(
+                            "index {} is out of range for bytes of length {}.",
+                            index, len
+                        ));
+                    }
+                }
+            }
+            _ => {}
+        }
+        pending.extend(subexpressions(exp));
+    }
+    found
+}
+
+fn subexpressions(exp: &Expression) -> Vec<&Expression> {
+    match exp {
+        Expression::Add(l, r)
+        | Expression::Sub(l, r)
+        | Expression::Mul(l, r)
+        | Expression::Div(l, r)
+        | Expression::And(l, r)
+        | Expression::Or(l, r)
+        | Expression::EQ(l, r)
+        | Expression::GT(l, r)
+        | Expression::LT(l, r)
+        | Expression::GTE(l, r)
+        | Expression::LTE(l, r) => vec![l, r],
+        Expression::Not(e)
+        | Expression::COk(e)
+        | Expression::CErr(e)
+        | Expression::CJust(e)
+        | Expression::Unwrap(e)
+        | Expression::IsError(e)
+        | Expression::IsNothing(e)
+        | Expression::Propagate(e) => vec![e],
+        Expression::CList(items)
+        | Expression::CTuple(items)
+        | Expression::FuncCall(_, items)
+        | Expression::CachedCall(_, items) => items.iter().collect(),
+        Expression::CDict(entries) => entries.iter().flat_map(|(k, v)| [k, v]).collect(),
+        Expression::ADTConstructor(_, _, args) => args.iter().map(|arg| arg.as_ref()).collect(),
+        _ => vec![],
+    }
+}
+
+/// The expressions a simple statement evaluates.
+fn expressions(stmt: &Statement) -> Vec<&Expression> {
+    match stmt {
+        Statement::Assignment(_, exp, _)
+        | Statement::ConstDeclaration(_, _, exp)
+        | Statement::Destructure(_, exp)
+        | Statement::Call(exp)
+        | Statement::AssertTrue(exp, _)
+        | Statement::AssertFalse(exp, _) => vec![exp],
+        Statement::AssertEQ(lhs, rhs, _) | Statement::AssertNEQ(lhs, rhs, _) => vec![lhs, rhs],
+        _ => vec![],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ir::desugar;
+    use crate::parser::parser::parse_located;
+
+    fn messages(source: &str) -> Vec<String> {
+        let program = desugar::program(parse_located(source).unwrap());
+        super::program(&program).iter().map(Warning::render).collect()
+    }
+
+    #[test]
+    fn constants_flow_through_branches_and_loops() {
+        assert_eq!(
+            messages("x = 1\nif x > 0:\n    y = 2\nz = 10 / (x - 1)"),
+            vec![
+                "[Warning] in '__main__', line 2, column 1: `if x > 0`: condition is always true.",
+                "[Warning] in '__main__', line 4, column 1: `z = 10 / (x - 1)`: division by zero: 'x - 1' is always 0.",
+            ]
+        );
+
+        // Both branches agree on `n`
+        assert_eq!(
+            messages("if c:\n    n = 0\nelse:\n    n = 0\ny = 5 / n"),
+            vec!["[Warning] in '__main__', line 5, column 1: `y = 5 / n`: division by zero: 'n' is always 0."]
+        );
+
+        // Nested code is found where it is written
+        assert_eq!(
+            messages("def f(n: TInteger) -> TInteger:\n    while n > 0:\n        n = 0\n        m = 1 / n\n    return n"),
+            vec!["[Warning] in 'f', line 4, column 9: `m = 1 / n`: division by zero: 'n' is always 0."]
+        );
+    }
+
+    #[test]
+    fn loops_forget_names_they_change() {
+        let var = |name: &str| Box::new(Expression::Var(name.to_string()));
+        let set = |name: &str, exp: Expression| Statement::Assignment(name.to_string(), Box::new(exp), None);
+        let count_to = |cond: Expression| {
+            Statement::Block(vec![
+                set("i", Expression::CInt(0)),
+                Statement::While(
+                    Box::new(cond),
+                    Box::new(set("i", Expression::Add(var("i"), Box::new(Expression::CInt(1))))),
+                ),
+                set("k", Expression::Div(Box::new(Expression::CInt(1)), var("i"))),
+            ])
+        };
+
+        // `i` changes inside the loop, so nothing is known about it there
+        let cond = Expression::LT(var("i"), Box::new(Expression::CInt(10)));
+        assert!(warnings(&Cfg::build("f", &count_to(cond))).is_empty());
+
+        // Literal conditions, as in `while True`, are deliberate
+        assert!(warnings(&Cfg::build("f", &count_to(Expression::CTrue))).is_empty());
+    }
+
+    #[test]
+    fn out_of_range_indexing() {
+        let data = Expression::CBytes(vec![1, 2, 3]);
+        let body = Statement::Block(vec![
+            Statement::Assignment("data".to_string(), Box::new(data), None),
+            Statement::Assignment(
+                "b".to_string(),
+                Box::new(Expression::FuncCall(
+                    "byte_at".to_string(),
+                    vec![Expression::Var("data".to_string()), Expression::CInt(3)],
+                )),
+                None,
+            ),
+        ]);
+
+        assert_eq!(
+            warnings(&Cfg::build("f", &body)),
+            vec![Warning {
+                function: "f".to_string(),
+                position: None,
+                code: "b = byte_at(data, 3)".to_string(),
+                message: "index 3 is out of range for bytes of length 3.".to_string(),
+            }]
+        );
+    }
+}
@@ -0,0 +1,239 @@
+use std::cmp::Ordering;
+use std::fmt;
+
+/// An exact decimal number: `units / 10^scale`. The scale is kept as
+/// written, so `1.50d` prints as `1.50`, but it never affects comparisons:
+/// `1.50d == 1.5d`.
+#[derive(Clone, Copy, Debug)]
+pub struct Decimal {
+    units: i128,
+    scale: u32,
+}
+
+/// Results are rounded to this many places, which also keeps `10^scale`
+/// well inside an `i128`.
+pub const MAX_SCALE: u32 = 28;
+
+/// Quotients that do not terminate get this many places more than their
+/// operands.
+const DIVISION_PLACES: u32 = 10;
+
+fn pow10(exp: u32) -> i128 {
+    10i128.pow(exp)
+}
+
+impl Decimal {
+    pub fn new(units: i128, scale: u32) -> Decimal {
+        Decimal { units, scale }.rounded(MAX_SCALE)
+    }
+
+    pub fn units(&self) -> i128 {
+        self.units
+    }
+
+    pub fn scale(&self) -> u32 {
+        self.scale
+    }
+
+    /// Digits with an optional sign and fractional part, as in `-12.50`.
+    pub fn parse(text: &str) -> Option<Decimal> {
+        let (negative, digits) = match text.strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None => (false, text),
+        };
+        let (whole, fraction) = digits.split_once('.').unwrap_or((digits, ""));
+        if whole.is_empty() || !whole.chars().chain(fraction.chars()).all(|c| c.is_ascii_digit()) {
+            return None;
+        }
+        let scale = fraction.len() as u32;
+        if scale > MAX_SCALE {
+            return None;
+        }
+        let units: i128 = format!("{}{}", whole, fraction).parse().ok()?;
+        Some(Decimal {
+            units: if negative { -units } else { units },
+            scale,
+        })
+    }
+
+    /// The same value with `scale` places, if that does not lose digits.
+    fn rescaled(&self, scale: u32) -> Option<Decimal> {
+        let units = self.units.checked_mul(pow10(scale.checked_sub(self.scale)?))?;
+        Some(Decimal { units, scale })
+    }
+
+    /// Both values with the larger of their scales.
+    fn aligned(self, other: Decimal) -> Option<(i128, i128, u32)> {
+        let scale = self.scale.max(other.scale);
+        Some((self.rescaled(scale)?.units, other.rescaled(scale)?.units, scale))
+    }
+
+    pub fn checked_add(self, other: Decimal) -> Option<Decimal> {
+        let (a, b, scale) = self.aligned(other)?;
+        Some(Decimal { units: a.checked_add(b)?, scale })
+    }
+
+    pub fn checked_sub(self, other: Decimal) -> Option<Decimal> {
+        let (a, b, scale) = self.aligned(other)?;
+        Some(Decimal { units: a.checked_sub(b)?, scale })
+    }
+
+    pub fn checked_mul(self, other: Decimal) -> Option<Decimal> {
+        let units = self.units.checked_mul(other.units)?;
+        Some(Decimal::new(units, self.scale + other.scale))
+    }
+
+    /// Exact when the quotient terminates within `DIVISION_PLACES` more
+    /// places than the operands, rounded half to even otherwise; trailing
+    /// zeros past the operands' scale are dropped, so `1.00d / 4` is
+    /// `0.25` and `10.00d / 4` is `2.50`. `None` on division by zero.
+    pub fn checked_div(self, other: Decimal) -> Option<Decimal> {
+        if other.units == 0 {
+            return None;
+        }
+        let scale = (self.scale.max(other.scale) + DIVISION_PLACES).min(MAX_SCALE);
+        // units / 10^scale = (a / 10^sa) / (b / 10^sb)
+        let shift = scale + other.scale - self.scale;
+        let numerator = self.units.checked_mul(pow10(shift))?;
+        let quotient = divide_rounded(numerator, other.units);
+
+        let mut result = Decimal { units: quotient, scale };
+        while result.scale > self.scale.max(other.scale) && result.units % 10 == 0 {
+            result = Decimal { units: result.units / 10, scale: result.scale - 1 };
+        }
+        Some(result)
+    }
+
+    /// Rounded half to even to `places` decimal places, the rounding used
+    /// for money. Values with fewer places are kept as they are.
+    pub fn rounded(self, places: u32) -> Decimal {
+        if self.scale <= places {
+            return self;
+        }
+        Decimal {
+            units: divide_rounded(self.units, pow10(self.scale - places)),
+            scale: places,
+        }
+    }
+
+    pub fn abs(&self) -> Decimal {
+        Decimal { units: self.units.saturating_abs(), scale: self.scale }
+    }
+
+    /// The integer part, rounding towards zero.
+    pub fn trunc(&self) -> i128 {
+        self.units / pow10(self.scale)
+    }
+
+    pub fn to_f64(&self) -> f64 {
+        self.units as f64 / pow10(self.scale) as f64
+    }
+
+    /// The value without trailing fractional zeros, so that equal values
+    /// have equal parts.
+    pub fn normalized(&self) -> Decimal {
+        let mut result = *self;
+        while result.scale > 0 && result.units % 10 == 0 {
+            result = Decimal { units: result.units / 10, scale: result.scale - 1 };
+        }
+        result
+    }
+}
+
+/// `a / b` rounded half to even.
+fn divide_rounded(a: i128, b: i128) -> i128 {
+    let (quotient, remainder) = (a / b, a % b);
+    let twice = (remainder.abs() * 2).cmp(&b.abs());
+    let away = match twice {
+        Ordering::Greater => true,
+        Ordering::Equal => quotient % 2 != 0,
+        Ordering::Less => false,
+    };
+    match (away, (a < 0) != (b < 0)) {
+        (false, _) => quotient,
+        (true, false) => quotient + 1,
+        (true, true) => quotient - 1,
+    }
+}
+
+impl From<i32> for Decimal {
+    fn from(n: i32) -> Decimal {
+        Decimal { units: n as i128, scale: 0 }
+    }
+}
+
+impl PartialEq for Decimal {
+    fn eq(&self, other: &Decimal) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Decimal {}
+
+impl PartialOrd for Decimal {
+    fn partial_cmp(&self, other: &Decimal) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Decimal {
+    fn cmp(&self, other: &Decimal) -> Ordering {
+        let (a, b) = (self.normalized(), other.normalized());
+        match a.aligned(b) {
+            Some((a, b, _)) => a.cmp(&b),
+            // Aligning only overflows for values far apart
+            None => a.to_f64().total_cmp(&b.to_f64()),
+        }
+    }
+}
+
+impl fmt::Display for Decimal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let sign = if self.units < 0 { "-" } else { "" };
+        let digits = self.units.unsigned_abs().to_string();
+        let scale = self.scale as usize;
+        if scale == 0 {
+            return write!(f, "{}{}", sign, digits);
+        }
+        let digits = format!("{:0>width$}", digits, width = scale + 1);
+        let (whole, fraction) = digits.split_at(digits.len() - scale);
+        write!(f, "{}{}.{}", sign, whole, fraction)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn d(text: &str) -> Decimal {
+        Decimal::parse(text).unwrap()
+    }
+
+    #[test]
+    fn decimals_keep_their_places() {
+        assert_eq!(d("1.50").to_string(), "1.50");
+        assert_eq!(d("-0.05").to_string(), "-0.05");
+        assert_eq!(d("0.10").checked_add(d("0.2")).unwrap().to_string(), "0.30");
+        assert_eq!(d("1.5").checked_mul(d("1.5")).unwrap().to_string(), "2.25");
+        assert_eq!(d("19.99").checked_sub(Decimal::from(20)).unwrap().to_string(), "-0.01");
+        assert_eq!(d("1.50"), d("1.5"));
+        assert!(d("0.3") > d("0.29"));
+        assert_eq!(Decimal::parse("1."), Some(d("1")));
+        assert_eq!(Decimal::parse(".5"), None);
+        assert_eq!(Decimal::parse("1e3"), None);
+    }
+
+    #[test]
+    fn decimal_division_and_rounding() {
+        assert_eq!(d("10.00").checked_div(Decimal::from(4)).unwrap().to_string(), "2.50");
+        assert_eq!(d("1").checked_div(Decimal::from(8)).unwrap().to_string(), "0.125");
+        assert_eq!(d("2").checked_div(Decimal::from(3)).unwrap().to_string(), "0.6666666667");
+        assert_eq!(d("1").checked_div(Decimal::from(0)), None);
+
+        assert_eq!(d("2.345").rounded(2).to_string(), "2.34");
+        assert_eq!(d("2.355").rounded(2).to_string(), "2.36");
+        assert_eq!(d("-2.345").rounded(2).to_string(), "-2.34");
+        assert_eq!(d("2.5").rounded(0).to_string(), "2");
+        assert_eq!(d("-7.9").trunc(), -7);
+    }
+}
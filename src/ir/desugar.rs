@@ -0,0 +1,448 @@
+use std::collections::BTreeMap;
+use std::iter::Peekable;
+use std::vec::IntoIter;
+
+use crate::ir::ast::{
+    ArithmeticOperator, Associativity, Clause, Expression, Fixity, Function, InfixOperator, Name, Pattern, Statement,
+    Type,
+};
+
+/// Rewrites the surface syntax the parser produces into the core language
+/// the type checker and the interpreter consume:
+///
+/// * `if`/`elif`/`else` chains become nested `IfThenElse`;
+/// * `x op= e` becomes `x = x op e`;
+/// * `v |> f(args)` becomes `f(v, args)`;
+/// * ``a `max` b`` becomes `max(a, b)`, grouped with the operators around
+///   it by the fixities that `infixl` and `infixr` declare;
+/// * a function defined by clauses becomes one function whose body
+///   matches its arguments against the patterns of each clause in turn.
+///
+/// Core programs are left unchanged, so the pass can run more than once.
+pub fn program(stmts: Vec<Statement>) -> Vec<Statement> {
+    Desugar::default().block(stmts)
+}
+
+/// Error reported by the checker and the interpreter when a surface node
+/// reaches them, i.e. when a program was not desugared.
+pub fn surface_error(node: &str) -> String {
+    format!(
+        "[Internal Error] '{}' is surface syntax: desugar the program before checking or running it.",
+        node
+    )
+}
+
+pub fn statement(stmt: Statement) -> Statement {
+    Desugar::default().statement(stmt)
+}
+
+pub fn expression(exp: Expression) -> Expression {
+    Desugar::default().expression(exp)
+}
+
+/// The tree of an infix chain whose operators have their default fixity,
+/// as the parser builds for `a + b * c`.
+pub fn infix(first: Expression, rest: Vec<(InfixOperator, Expression)>) -> Expression {
+    Desugar::default().infix(first, rest)
+}
+
+type Operands = Peekable<IntoIter<(InfixOperator, Expression)>>;
+
+/// The fixities declared in the blocks around the code being rewritten; a
+/// declaration holds in its whole block, before and after it, and in the
+/// blocks nested in it.
+#[derive(Default)]
+struct Desugar {
+    fixities: BTreeMap<Name, Fixity>,
+}
+
+impl Desugar {
+    fn block(&self, stmts: Vec<Statement>) -> Vec<Statement> {
+        let mut scope = Desugar {
+            fixities: self.fixities.clone(),
+        };
+        for stmt in &stmts {
+            if let Statement::FixityDeclaration(name, fixity) = stmt.unlocated() {
+                scope.fixities.insert(name.clone(), *fixity);
+            }
+        }
+        stmts.into_iter().map(|stmt| scope.statement(stmt)).collect()
+    }
+
+    fn statement(&self, stmt: Statement) -> Statement {
+        let boxed = |stmt: Box<Statement>| Box::new(self.statement(*stmt));
+        let exp = |e: Box<Expression>| Box::new(self.expression(*e));
+
+        match stmt {
+            Statement::IfChain(branches, else_stmt) => {
+                let else_stmt = else_stmt.map(boxed);
+                branches
+                    .into_iter()
+                    .rev()
+                    .fold(else_stmt, |else_stmt, (cond, then_stmt)| {
+                        Some(Box::new(Statement::IfThenElse(
+                            Box::new(self.expression(cond)),
+                            Box::new(self.statement(then_stmt)),
+                            else_stmt,
+                        )))
+                    })
+                    .map(|stmt| *stmt)
+                    .unwrap_or(Statement::Block(vec![]))
+            }
+            Statement::AugmentedAssignment(name, operator, value) => {
+                let lhs = Box::new(Expression::Var(name.clone()));
+                let value = arithmetic(operator, lhs, exp(value));
+                Statement::Assignment(name, Box::new(value), None)
+            }
+            Statement::FuncClauses(name, kind, clauses) => {
+                Statement::FuncDef(self.function(clauses_function(name, kind, clauses)))
+            }
+            Statement::FixityDeclaration(..) => Statement::Block(vec![]),
+            Statement::At(position, stmt) => Statement::At(position, boxed(stmt)),
+            Statement::Assignment(name, e, kind) => Statement::Assignment(name, exp(e), kind),
+            Statement::ConstDeclaration(name, kind, e) => Statement::ConstDeclaration(name, kind, exp(e)),
+            Statement::Destructure(pattern, e) => Statement::Destructure(pattern, exp(e)),
+            Statement::IfThenElse(cond, then_stmt, else_stmt) => {
+                Statement::IfThenElse(exp(cond), boxed(then_stmt), else_stmt.map(boxed))
+            }
+            Statement::While(cond, body) => Statement::While(exp(cond), boxed(body)),
+            Statement::Block(stmts) => Statement::Block(self.block(stmts)),
+            Statement::Sequence(s1, s2) => Statement::Sequence(boxed(s1), boxed(s2)),
+            Statement::AssertTrue(e, msg) => Statement::AssertTrue(exp(e), msg),
+            Statement::AssertFalse(e, msg) => Statement::AssertFalse(exp(e), msg),
+            Statement::AssertEQ(lhs, rhs, msg) => Statement::AssertEQ(exp(lhs), exp(rhs), msg),
+            Statement::AssertNEQ(lhs, rhs, msg) => Statement::AssertNEQ(exp(lhs), exp(rhs), msg),
+            Statement::TestDef(func) => Statement::TestDef(self.function(func)),
+            Statement::ModTestDef(name, stmt) => Statement::ModTestDef(name, boxed(stmt)),
+            Statement::FuncDef(func) => Statement::FuncDef(self.function(func)),
+            Statement::Return(e) => Statement::Return(exp(e)),
+            Statement::Requires(e) => Statement::Requires(exp(e)),
+            Statement::Ensures(e) => Statement::Ensures(exp(e)),
+            Statement::Invariant(e) => Statement::Invariant(exp(e)),
+            Statement::Decreases(e) => Statement::Decreases(exp(e)),
+            Statement::Locals(layout) => Statement::Locals(layout),
+            Statement::Call(e) => Statement::Call(exp(e)),
+            Statement::Match(e, cases) => Statement::Match(
+                exp(e),
+                cases
+                    .into_iter()
+                    .map(|(pattern, guard, stmt)| (pattern, guard.map(|guard| self.expression(guard)), boxed(stmt)))
+                    .collect(),
+            ),
+            Statement::InstanceDeclaration(class, adt_name, methods) => Statement::InstanceDeclaration(
+                class,
+                adt_name,
+                methods.into_iter().map(|method| self.function(method)).collect(),
+            ),
+            Statement::VarDeclaration(_)
+            | Statement::ValDeclaration(_)
+            | Statement::AssertFails(_)
+            | Statement::Import(_)
+            | Statement::Module(_)
+            | Statement::ADTDeclaration(..) => stmt,
+        }
+    }
+
+    fn expression(&self, exp: Expression) -> Expression {
+        let boxed = |e: Box<Expression>| Box::new(self.expression(*e));
+        let all = |exps: Vec<Expression>| exps.into_iter().map(|e| self.expression(e)).collect::<Vec<_>>();
+
+        match exp {
+            Expression::Pipe(value, name, args) => Expression::FuncCall(
+                name,
+                std::iter::once(self.expression(*value)).chain(all(args)).collect(),
+            ),
+            Expression::Infix(first, rest) => {
+                let rest = rest.into_iter().map(|(op, operand)| (op, self.expression(operand))).collect();
+                self.infix(self.expression(*first), rest)
+            }
+            Expression::CList(items) => Expression::CList(all(items)),
+            Expression::CTuple(items) => Expression::CTuple(all(items)),
+            Expression::CDict(entries) => Expression::CDict(
+                entries
+                    .into_iter()
+                    .map(|(k, v)| (self.expression(k), self.expression(v)))
+                    .collect(),
+            ),
+            Expression::FuncCall(name, args) => Expression::FuncCall(name, all(args)),
+            Expression::CachedCall(site, args) => Expression::CachedCall(site, all(args)),
+            Expression::Add(l, r) => Expression::Add(boxed(l), boxed(r)),
+            Expression::Sub(l, r) => Expression::Sub(boxed(l), boxed(r)),
+            Expression::Mul(l, r) => Expression::Mul(boxed(l), boxed(r)),
+            Expression::Div(l, r) => Expression::Div(boxed(l), boxed(r)),
+            Expression::And(l, r) => Expression::And(boxed(l), boxed(r)),
+            Expression::Or(l, r) => Expression::Or(boxed(l), boxed(r)),
+            Expression::Not(e) => Expression::Not(boxed(e)),
+            Expression::EQ(l, r) => Expression::EQ(boxed(l), boxed(r)),
+            Expression::GT(l, r) => Expression::GT(boxed(l), boxed(r)),
+            Expression::LT(l, r) => Expression::LT(boxed(l), boxed(r)),
+            Expression::GTE(l, r) => Expression::GTE(boxed(l), boxed(r)),
+            Expression::LTE(l, r) => Expression::LTE(boxed(l), boxed(r)),
+            Expression::COk(e) => Expression::COk(boxed(e)),
+            Expression::CErr(e) => Expression::CErr(boxed(e)),
+            Expression::CJust(e) => Expression::CJust(boxed(e)),
+            Expression::Unwrap(e) => Expression::Unwrap(boxed(e)),
+            Expression::IsError(e) => Expression::IsError(boxed(e)),
+            Expression::IsNothing(e) => Expression::IsNothing(boxed(e)),
+            Expression::Propagate(e) => Expression::Propagate(boxed(e)),
+            Expression::ADTConstructor(adt_name, constructor_name, args) => {
+                Expression::ADTConstructor(adt_name, constructor_name, args.into_iter().map(boxed).collect())
+            }
+            Expression::CTrue
+            | Expression::CFalse
+            | Expression::CInt(_)
+            | Expression::CReal(_)
+            | Expression::CDecimal(_)
+            | Expression::CComplex(_)
+            | Expression::CString(_)
+            | Expression::CBytes(_)
+            | Expression::CVoid
+            | Expression::CNothing
+            | Expression::Var(_)
+            | Expression::Slot(..)
+            | Expression::ADTValue(..)
+            | Expression::Closure(..) => exp,
+        }
+    }
+
+    fn function(&self, func: Function) -> Function {
+        Function {
+            body: func.body.map(|body| Box::new(self.statement(*body))),
+            ..func
+        }
+    }
+
+    fn fixity(&self, op: &InfixOperator) -> Fixity {
+        match op {
+            InfixOperator::Arithmetic(operator) => operator.fixity(),
+            InfixOperator::Function(name) => self.fixities.get(name).copied().unwrap_or(Fixity::DEFAULT),
+        }
+    }
+
+    fn infix(&self, first: Expression, rest: Vec<(InfixOperator, Expression)>) -> Expression {
+        self.group(first, &mut rest.into_iter().peekable(), 0)
+    }
+
+    /// Groups `lhs` with the operators ahead that bind at least as tight
+    /// as `min`, by precedence climbing: an operand goes to the tighter of
+    /// the operators around it, and to the right one on a tie only when
+    /// that one is right associative.
+    fn group(&self, mut lhs: Expression, rest: &mut Operands, min: u8) -> Expression {
+        let next = |rest: &mut Operands| rest.peek().map(|(op, _)| self.fixity(op));
+        while let Some(fixity) = next(rest).filter(|fixity| fixity.precedence >= min) {
+            let (op, mut rhs) = rest.next().unwrap();
+            while let Some(ahead) = next(rest).filter(|ahead| {
+                ahead.precedence > fixity.precedence
+                    || (ahead.precedence == fixity.precedence && ahead.associativity == Associativity::Right)
+            }) {
+                let min = fixity.precedence + u8::from(ahead.precedence > fixity.precedence);
+                rhs = self.group(rhs, rest, min);
+            }
+            lhs = match op {
+                InfixOperator::Arithmetic(operator) => arithmetic(operator, Box::new(lhs), Box::new(rhs)),
+                InfixOperator::Function(name) => Expression::FuncCall(name, vec![lhs, rhs]),
+            };
+        }
+        lhs
+    }
+}
+
+fn arithmetic(operator: ArithmeticOperator, lhs: Box<Expression>, rhs: Box<Expression>) -> Expression {
+    match operator {
+        ArithmeticOperator::Add => Expression::Add(lhs, rhs),
+        ArithmeticOperator::Sub => Expression::Sub(lhs, rhs),
+        ArithmeticOperator::Mul => Expression::Mul(lhs, rhs),
+        ArithmeticOperator::Div => Expression::Div(lhs, rhs),
+    }
+}
+
+/// The function the clauses of `name` define. Its parameters are `_1`,
+/// `_2`, ..., matched as a tuple when there are several. A parameter has
+/// the type the first clause to write one gives it, or else the type its
+/// patterns imply, as `List` for `List Nil` or `TInteger` for `0`; a
+/// parameter no clause says anything about takes any value.
+fn clauses_function(name: Name, kind: Option<Type>, clauses: Vec<Clause>) -> Function {
+    let arity = clauses.first().map_or(0, |clause| clause.params.len());
+    let params: Vec<(Name, Type)> = (0..arity)
+        .map(|i| {
+            let written = clauses.iter().find_map(|clause| clause.params[i].1.clone());
+            let implied = || clauses.iter().find_map(|clause| pattern_type(&clause.params[i].0));
+            (format!("_{}", i + 1), written.or_else(implied).unwrap_or(Type::TAny))
+        })
+        .collect();
+
+    let subject = match params.as_slice() {
+        [(param, _)] => Expression::Var(param.clone()),
+        _ => Expression::CTuple(params.iter().map(|(param, _)| Expression::Var(param.clone())).collect()),
+    };
+    let arms = clauses
+        .into_iter()
+        .map(|clause| {
+            let mut patterns: Vec<Pattern> = clause.params.into_iter().map(|(pattern, _)| pattern).collect();
+            let pattern = match patterns.len() {
+                1 => patterns.remove(0),
+                _ => Pattern::Tuple(patterns),
+            };
+            (pattern, clause.guard, Box::new(Statement::Return(Box::new(clause.value))))
+        })
+        .collect();
+
+    Function {
+        name,
+        kind: Some(kind.unwrap_or(Type::TAny)),
+        params: Some(params),
+        body: Some(Box::new(Statement::Block(vec![Statement::Match(Box::new(subject), arms)]))),
+    }
+}
+
+/// The type of the values `pattern` can match, when it tells.
+fn pattern_type(pattern: &Pattern) -> Option<Type> {
+    match pattern {
+        Pattern::Constructor(adt_name, _, _) | Pattern::Tagged(adt_name, _, _, _) => Some(Type::Tadt(adt_name.clone(), vec![])),
+        Pattern::Literal(literal) => match literal {
+            Expression::CInt(_) => Some(Type::TInteger),
+            Expression::CReal(_) => Some(Type::TReal),
+            Expression::CDecimal(_) => Some(Type::TDecimal),
+            Expression::CString(_) => Some(Type::TString),
+            Expression::CTrue | Expression::CFalse => Some(Type::TBool),
+            _ => None,
+        },
+        Pattern::StartsWith(_) | Pattern::EndsWith(_) => Some(Type::TString),
+        Pattern::Tuple(items) => items.iter().map(pattern_type).collect::<Option<Vec<Type>>>().map(Type::TTuple),
+        Pattern::List(items) | Pattern::ListRest(items, _) => {
+            items.iter().find_map(pattern_type).map(|item| Type::TList(Box::new(item)))
+        }
+        Pattern::As(pattern, _) => pattern_type(pattern),
+        Pattern::Variable(_) | Pattern::Wildcard => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ir::ast::Expression::*;
+    use crate::ir::ast::Statement::*;
+
+    fn var(name: &str) -> Box<Expression> {
+        Box::new(Var(name.to_string()))
+    }
+
+    fn set(name: &str, value: i32) -> Statement {
+        Assignment(name.to_string(), Box::new(CInt(value)), None)
+    }
+
+    #[test]
+    fn elif_chains_become_nested_ifs() {
+        let chain = IfChain(
+            vec![(*var("a"), set("x", 1)), (*var("b"), set("x", 2))],
+            Some(Box::new(set("x", 3))),
+        );
+
+        assert_eq!(
+            statement(chain),
+            IfThenElse(
+                var("a"),
+                Box::new(set("x", 1)),
+                Some(Box::new(IfThenElse(
+                    var("b"),
+                    Box::new(set("x", 2)),
+                    Some(Box::new(set("x", 3)))
+                )))
+            )
+        );
+    }
+
+    #[test]
+    fn sugar_is_removed_everywhere() {
+        let body = While(
+            var("go"),
+            Box::new(Block(vec![AugmentedAssignment(
+                "x".to_string(),
+                ArithmeticOperator::Mul,
+                Box::new(Pipe(var("y"), "inc".to_string(), vec![CInt(2)])),
+            )])),
+        );
+        let expected = While(
+            var("go"),
+            Box::new(Block(vec![Assignment(
+                "x".to_string(),
+                Box::new(Mul(
+                    var("x"),
+                    Box::new(FuncCall("inc".to_string(), vec![Var("y".to_string()), CInt(2)])),
+                )),
+                None,
+            )])),
+        );
+
+        assert_eq!(statement(body), expected);
+        assert_eq!(statement(expected.clone()), expected);
+    }
+
+    #[test]
+    fn infix_calls_group_by_their_declared_fixity() {
+        let call = |name: &str, l: Expression, r: Expression| FuncCall(name.to_string(), vec![l, r]);
+        let program = crate::rpy!(
+            "
+            a = 1 `max` 2 + 3
+            infixr 5 cons
+            def f() -> TInteger:
+                infixl 5 max
+                return 1 `max` 2 + 3
+            b = 1 `cons` 2 `cons` 3 `min` 4
+            "
+        );
+
+        let sum = |l: Expression| Add(Box::new(l), Box::new(CInt(3)));
+        assert_eq!(program[0], Assignment("a".to_string(), Box::new(sum(call("max", CInt(1), CInt(2)))), None));
+        assert_eq!(program[1], Block(vec![]));
+        let FuncDef(f) = &program[2] else {
+            panic!("expected a function");
+        };
+        assert_eq!(
+            f.body.as_deref(),
+            Some(&Block(vec![Block(vec![]), Return(Box::new(call("max", CInt(1), sum(CInt(2)))))]))
+        );
+        let min = call("min", CInt(3), CInt(4));
+        assert_eq!(
+            program[3],
+            Assignment("b".to_string(), Box::new(call("cons", CInt(1), call("cons", CInt(2), min))), None)
+        );
+    }
+
+    #[test]
+    fn clauses_become_one_function_matching_its_arguments() {
+        let program = crate::rpy!(
+            "
+            def pick(True, x: TInteger, _) -> TInteger = x
+            def pick(False, _, y) if y > 0 = y
+            "
+        );
+        let arm = |patterns: Vec<Pattern>, guard, value: &str| {
+            (Pattern::Tuple(patterns), guard, Box::new(Return(var(value))))
+        };
+        let bind = |name: &str| Pattern::Variable(name.to_string());
+        let arms = vec![
+            arm(vec![Pattern::Literal(CTrue), bind("x"), Pattern::Wildcard], None, "x"),
+            arm(
+                vec![Pattern::Literal(CFalse), Pattern::Wildcard, bind("y")],
+                Some(GT(var("y"), Box::new(CInt(0)))),
+                "y",
+            ),
+        ];
+        let subject = CTuple(vec![*var("_1"), *var("_2"), *var("_3")]);
+
+        assert_eq!(
+            program,
+            vec![FuncDef(Function {
+                name: "pick".to_string(),
+                kind: Some(Type::TInteger),
+                params: Some(vec![
+                    ("_1".to_string(), Type::TBool),
+                    ("_2".to_string(), Type::TInteger),
+                    ("_3".to_string(), Type::TAny),
+                ]),
+                body: Some(Box::new(Block(vec![Match(Box::new(subject), arms)]))),
+            })]
+        );
+    }
+}
@@ -0,0 +1,225 @@
+use std::collections::BTreeMap;
+
+use crate::ir::ast::{Clause, Expression, Function, Name, Statement};
+
+/// Replaces every use of a top-level constant with its value, so that
+/// `const TAU: TReal = PI * 2.0` followed by `x = TAU` becomes
+/// `x = 3.14159 * 2.0`. The declarations stay, so the constants are still
+/// bound when the program runs.
+///
+/// Only sound on programs the type checker accepted, where a constant is
+/// used after its declaration and nothing binds its name again.
+pub fn constants(stmts: Vec<Statement>) -> Vec<Statement> {
+    let mut values = BTreeMap::new();
+    stmts
+        .into_iter()
+        .map(|stmt| {
+            let stmt = statement(stmt, &values);
+            if let Statement::ConstDeclaration(name, _, exp) = &stmt {
+                values.insert(name.clone(), (**exp).clone());
+            }
+            stmt
+        })
+        .collect()
+}
+
+type Values = BTreeMap<Name, Expression>;
+
+fn statement(stmt: Statement, values: &Values) -> Statement {
+    let boxed = |stmt: Box<Statement>| Box::new(statement(*stmt, values));
+    let exp = |e: Box<Expression>| Box::new(expression(*e, values));
+
+    match stmt {
+        Statement::ConstDeclaration(name, kind, e) => Statement::ConstDeclaration(name, kind, exp(e)),
+        Statement::Assignment(name, e, kind) => Statement::Assignment(name, exp(e), kind),
+        Statement::AugmentedAssignment(name, operator, e) => Statement::AugmentedAssignment(name, operator, exp(e)),
+        Statement::Destructure(pattern, e) => Statement::Destructure(pattern, exp(e)),
+        Statement::IfThenElse(cond, then_stmt, else_stmt) => {
+            Statement::IfThenElse(exp(cond), boxed(then_stmt), else_stmt.map(boxed))
+        }
+        Statement::IfChain(branches, else_stmt) => Statement::IfChain(
+            branches
+                .into_iter()
+                .map(|(cond, stmt)| (expression(cond, values), statement(stmt, values)))
+                .collect(),
+            else_stmt.map(boxed),
+        ),
+        Statement::While(cond, body) => Statement::While(exp(cond), boxed(body)),
+        Statement::Block(stmts) => Statement::Block(stmts.into_iter().map(|stmt| statement(stmt, values)).collect()),
+        Statement::Sequence(s1, s2) => Statement::Sequence(boxed(s1), boxed(s2)),
+        Statement::AssertTrue(e, msg) => Statement::AssertTrue(exp(e), msg),
+        Statement::AssertFalse(e, msg) => Statement::AssertFalse(exp(e), msg),
+        Statement::AssertEQ(lhs, rhs, msg) => Statement::AssertEQ(exp(lhs), exp(rhs), msg),
+        Statement::AssertNEQ(lhs, rhs, msg) => Statement::AssertNEQ(exp(lhs), exp(rhs), msg),
+        Statement::TestDef(func) => Statement::TestDef(function(func, values)),
+        Statement::ModTestDef(name, stmt) => Statement::ModTestDef(name, boxed(stmt)),
+        Statement::FuncDef(func) => Statement::FuncDef(function(func, values)),
+        Statement::Return(e) => Statement::Return(exp(e)),
+        Statement::Requires(e) => Statement::Requires(exp(e)),
+        Statement::Ensures(e) => Statement::Ensures(exp(e)),
+        Statement::Invariant(e) => Statement::Invariant(exp(e)),
+        Statement::Decreases(e) => Statement::Decreases(exp(e)),
+        Statement::Locals(layout) => Statement::Locals(layout),
+        Statement::Call(e) => Statement::Call(exp(e)),
+        Statement::Match(e, cases) => Statement::Match(
+            exp(e),
+            cases
+                .into_iter()
+                .map(|(pattern, guard, stmt)| (pattern, guard.map(|guard| expression(guard, values)), boxed(stmt)))
+                .collect(),
+        ),
+        Statement::FuncClauses(name, kind, clauses) => Statement::FuncClauses(
+            name,
+            kind,
+            clauses
+                .into_iter()
+                .map(|clause| Clause {
+                    guard: clause.guard.map(|guard| expression(guard, values)),
+                    value: expression(clause.value, values),
+                    ..clause
+                })
+                .collect(),
+        ),
+        Statement::InstanceDeclaration(class, adt_name, methods) => Statement::InstanceDeclaration(
+            class,
+            adt_name,
+            methods.into_iter().map(|method| function(method, values)).collect(),
+        ),
+        Statement::At(position, stmt) => Statement::At(position, boxed(stmt)),
+        Statement::VarDeclaration(_)
+        | Statement::ValDeclaration(_)
+        | Statement::AssertFails(_)
+        | Statement::Import(_)
+        | Statement::Module(_)
+        | Statement::ADTDeclaration(..)
+        | Statement::FixityDeclaration(..) => stmt,
+    }
+}
+
+fn expression(exp: Expression, values: &Values) -> Expression {
+    let boxed = |e: Box<Expression>| Box::new(expression(*e, values));
+    let all = |exps: Vec<Expression>| exps.into_iter().map(|e| expression(e, values)).collect::<Vec<_>>();
+
+    match exp {
+        Expression::Var(name) => match values.get(&name) {
+            Some(value) => value.clone(),
+            None => Expression::Var(name),
+        },
+        Expression::CList(items) => Expression::CList(all(items)),
+        Expression::CTuple(items) => Expression::CTuple(all(items)),
+        Expression::CDict(entries) => Expression::CDict(
+            entries
+                .into_iter()
+                .map(|(k, v)| (expression(k, values), expression(v, values)))
+                .collect(),
+        ),
+        Expression::FuncCall(name, args) => Expression::FuncCall(name, all(args)),
+        Expression::CachedCall(site, args) => Expression::CachedCall(site, all(args)),
+        Expression::Pipe(value, name, args) => Expression::Pipe(boxed(value), name, all(args)),
+        Expression::Infix(first, rest) => Expression::Infix(
+            boxed(first),
+            rest.into_iter()
+                .map(|(op, operand)| (op, expression(operand, values)))
+                .collect(),
+        ),
+        Expression::Add(l, r) => Expression::Add(boxed(l), boxed(r)),
+        Expression::Sub(l, r) => Expression::Sub(boxed(l), boxed(r)),
+        Expression::Mul(l, r) => Expression::Mul(boxed(l), boxed(r)),
+        Expression::Div(l, r) => Expression::Div(boxed(l), boxed(r)),
+        Expression::And(l, r) => Expression::And(boxed(l), boxed(r)),
+        Expression::Or(l, r) => Expression::Or(boxed(l), boxed(r)),
+        Expression::Not(e) => Expression::Not(boxed(e)),
+        Expression::EQ(l, r) => Expression::EQ(boxed(l), boxed(r)),
+        Expression::GT(l, r) => Expression::GT(boxed(l), boxed(r)),
+        Expression::LT(l, r) => Expression::LT(boxed(l), boxed(r)),
+        Expression::GTE(l, r) => Expression::GTE(boxed(l), boxed(r)),
+        Expression::LTE(l, r) => Expression::LTE(boxed(l), boxed(r)),
+        Expression::COk(e) => Expression::COk(boxed(e)),
+        Expression::CErr(e) => Expression::CErr(boxed(e)),
+        Expression::CJust(e) => Expression::CJust(boxed(e)),
+        Expression::Unwrap(e) => Expression::Unwrap(boxed(e)),
+        Expression::IsError(e) => Expression::IsError(boxed(e)),
+        Expression::IsNothing(e) => Expression::IsNothing(boxed(e)),
+        Expression::Propagate(e) => Expression::Propagate(boxed(e)),
+        Expression::ADTConstructor(adt_name, constructor_name, args) => {
+            Expression::ADTConstructor(adt_name, constructor_name, args.into_iter().map(boxed).collect())
+        }
+        Expression::CTrue
+        | Expression::CFalse
+        | Expression::CInt(_)
+        | Expression::CReal(_)
+        | Expression::CDecimal(_)
+        | Expression::CComplex(_)
+        | Expression::CString(_)
+        | Expression::CBytes(_)
+        | Expression::CVoid
+        | Expression::CNothing
+        | Expression::Slot(..)
+        | Expression::ADTValue(..)
+        | Expression::Closure(..) => exp,
+    }
+}
+
+fn function(func: Function, values: &Values) -> Function {
+    Function {
+        body: func.body.map(|body| Box::new(statement(*body, values))),
+        ..func
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interpreter::interpreter::EnvValue;
+    use crate::interpreter::program::run_program;
+    use crate::ir::ast::{CallSite, Type};
+    use std::sync::Arc;
+    use crate::parser::parser::dedent;
+    use crate::tc::type_checker::CheckedProgram;
+
+    #[test]
+    fn constant_uses_become_their_values() {
+        let source = "
+            const HALF: TReal = 0.5
+            const WHOLE: TReal = HALF * 2.0
+            def turns(x: TReal) -> TReal:
+                return x / WHOLE
+            y = turns(WHOLE)
+            ";
+        let program = CheckedProgram::check(crate::rpy!(source)).unwrap().inline_constants();
+        let whole = Expression::Mul(Box::new(Expression::CReal(0.5)), Box::new(Expression::CReal(2.0)));
+
+        assert_eq!(
+            program.statements()[1],
+            Statement::ConstDeclaration("WHOLE".to_string(), Type::TReal, Box::new(whole.clone()))
+        );
+        let Statement::FuncDef(func) = &program.statements()[2] else {
+            panic!("expected a function");
+        };
+        assert_eq!(
+            func.body.as_deref(),
+            Some(&Statement::Block(vec![
+                Statement::Locals(Arc::new(vec!["x".to_string()])),
+                Statement::Return(Box::new(Expression::Div(
+                    Box::new(Expression::Slot(0, "x".to_string())),
+                    Box::new(whole.clone())
+                )))
+            ]))
+        );
+        assert_eq!(
+            program.statements()[3],
+            Statement::Assignment(
+                "y".to_string(),
+                Box::new(Expression::CachedCall(CallSite::new("turns".to_string()), vec![whole])),
+                None
+            )
+        );
+
+        let outcome = run_program(&dedent(source));
+        assert_eq!(outcome.diagnostics, Vec::<String>::new());
+        assert_eq!(
+            outcome.final_env.search_frame("y".to_string()),
+            Some(&EnvValue::Exp(Expression::CReal(1.0)))
+        );
+    }
+}
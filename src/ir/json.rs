@@ -0,0 +1,391 @@
+use crate::ir::ast::{CallSite, Expression, Function, InfixOperator, Name, Pattern, Statement, Type, ValueConstructor};
+use crate::ir::pretty;
+
+/// JSON encoding of the syntax tree, for tools that consume the program
+/// outside Rust.
+///
+/// Nodes are externally tagged: a variant without fields is its name as a
+/// string, and any other variant is an object with its name as the only key
+/// and its fields as an array, e.g. `{"Add": [{"CInt": [1]}, {"Var": ["x"]}]}`.
+/// Absent optional fields are `null`.
+pub fn statements(stmts: &[Statement]) -> String {
+    array(stmts.iter().map(statement))
+}
+
+pub fn statement(stmt: &Statement) -> String {
+    match stmt {
+        Statement::VarDeclaration(name) => node("VarDeclaration", vec![string(name)]),
+        Statement::ValDeclaration(name) => node("ValDeclaration", vec![string(name)]),
+        Statement::ConstDeclaration(name, kind, exp) => {
+            node("ConstDeclaration", vec![string(name), type_(kind), expression(exp)])
+        }
+        Statement::Assignment(name, exp, kind) => node(
+            "Assignment",
+            vec![string(name), expression(exp), optional(kind.as_ref().map(type_))],
+        ),
+        Statement::Destructure(p, exp) => node("Destructure", vec![pattern(p), expression(exp)]),
+        Statement::IfThenElse(cond, then_stmt, else_stmt) => node(
+            "IfThenElse",
+            vec![
+                expression(cond),
+                statement(then_stmt),
+                optional(else_stmt.as_deref().map(statement)),
+            ],
+        ),
+        Statement::While(cond, body) => node("While", vec![expression(cond), statement(body)]),
+        Statement::Block(stmts) => node("Block", vec![statements(stmts)]),
+        Statement::Sequence(s1, s2) => node("Sequence", vec![statement(s1), statement(s2)]),
+        Statement::AssertTrue(exp, msg) => node("AssertTrue", vec![expression(exp), string(msg)]),
+        Statement::AssertFalse(exp, msg) => node("AssertFalse", vec![expression(exp), string(msg)]),
+        Statement::AssertEQ(lhs, rhs, msg) => {
+            node("AssertEQ", vec![expression(lhs), expression(rhs), string(msg)])
+        }
+        Statement::AssertNEQ(lhs, rhs, msg) => {
+            node("AssertNEQ", vec![expression(lhs), expression(rhs), string(msg)])
+        }
+        Statement::TestDef(func) => node("TestDef", vec![function(func)]),
+        Statement::ModTestDef(name, stmt) => node("ModTestDef", vec![string(name), statement(stmt)]),
+        Statement::AssertFails(msg) => node("AssertFails", vec![string(msg)]),
+        Statement::FuncDef(func) => node("FuncDef", vec![function(func)]),
+        Statement::Return(exp) => node("Return", vec![expression(exp)]),
+        Statement::Requires(exp) => node("Requires", vec![expression(exp)]),
+        Statement::Ensures(exp) => node("Ensures", vec![expression(exp)]),
+        Statement::Invariant(exp) => node("Invariant", vec![expression(exp)]),
+        Statement::Decreases(exp) => node("Decreases", vec![expression(exp)]),
+        Statement::Locals(layout) => node("Locals", vec![array(layout.iter().map(|name| string(name)))]),
+        Statement::Call(exp) => node("Call", vec![expression(exp)]),
+        Statement::Import(module) => node("Import", vec![string(module)]),
+        Statement::Module(module) => node("Module", vec![string(module)]),
+        Statement::ADTDeclaration(name, params, constructors) => node(
+            "ADTDeclaration",
+            vec![
+                string(name),
+                array(params.iter().map(|param| string(param))),
+                array(constructors.iter().map(value_constructor)),
+            ],
+        ),
+        Statement::Match(exp, cases) => node(
+            "Match",
+            vec![
+                expression(exp),
+                array(
+                    cases
+                        .iter()
+                        .map(|(p, guard, stmt)| {
+                            array([pattern(p), optional(guard.as_ref().map(expression)), statement(stmt)].into_iter())
+                        }),
+                ),
+            ],
+        ),
+        Statement::InstanceDeclaration(class, adt_name, methods) => node(
+            "InstanceDeclaration",
+            vec![string(class), string(adt_name), array(methods.iter().map(function))],
+        ),
+        Statement::IfChain(branches, else_stmt) => node(
+            "IfChain",
+            vec![
+                array(
+                    branches
+                        .iter()
+                        .map(|(cond, stmt)| array([expression(cond), statement(stmt)].into_iter())),
+                ),
+                optional(else_stmt.as_deref().map(statement)),
+            ],
+        ),
+        Statement::AugmentedAssignment(name, operator, exp) => node(
+            "AugmentedAssignment",
+            vec![string(name), string(&format!("{:?}", operator)), expression(exp)],
+        ),
+        Statement::At(position, stmt) => node(
+            "At",
+            vec![position.line.to_string(), position.column.to_string(), statement(stmt)],
+        ),
+        Statement::FixityDeclaration(name, fixity) => node(
+            "FixityDeclaration",
+            vec![
+                string(name),
+                string(&format!("{:?}", fixity.associativity)),
+                fixity.precedence.to_string(),
+            ],
+        ),
+        Statement::FuncClauses(name, kind, clauses) => node(
+            "FuncClauses",
+            vec![
+                string(name),
+                optional(kind.as_ref().map(type_)),
+                array(clauses.iter().map(|clause| {
+                    let params = clause
+                        .params
+                        .iter()
+                        .map(|(p, kind)| array([pattern(p), optional(kind.as_ref().map(type_))].into_iter()));
+                    array(
+                        [array(params), optional(clause.guard.as_ref().map(expression)), expression(&clause.value)]
+                            .into_iter(),
+                    )
+                })),
+            ],
+        ),
+    }
+}
+
+pub fn expression(exp: &Expression) -> String {
+    let boxed = |tag: &str, exps: &[&Expression]| node(tag, exps.iter().map(|e| expression(e)).collect());
+
+    match exp {
+        Expression::CTrue => string("CTrue"),
+        Expression::CFalse => string("CFalse"),
+        Expression::CVoid => string("CVoid"),
+        Expression::CNothing => string("CNothing"),
+        Expression::CInt(value) => node("CInt", vec![value.to_string()]),
+        Expression::CReal(value) => node("CReal", vec![real(*value)]),
+        Expression::CDecimal(value) => node("CDecimal", vec![string(&value.to_string())]),
+        Expression::CComplex(value) => node("CComplex", vec![real(value.re), real(value.im)]),
+        Expression::CString(value) => node("CString", vec![string(value)]),
+        Expression::CBytes(bytes) => {
+            node("CBytes", vec![array(bytes.iter().map(|b| b.to_string()))])
+        }
+        Expression::CList(items) => node("CList", vec![array(items.iter().map(expression))]),
+        Expression::CTuple(items) => node("CTuple", vec![array(items.iter().map(expression))]),
+        Expression::CDict(entries) => node(
+            "CDict",
+            vec![array(
+                entries
+                    .iter()
+                    .map(|(k, v)| array([expression(k), expression(v)].into_iter())),
+            )],
+        ),
+        Expression::Var(name) => node("Var", vec![string(name)]),
+        Expression::Slot(slot, name) => node("Slot", vec![slot.to_string(), string(name)]),
+        Expression::FuncCall(name, args) | Expression::CachedCall(CallSite { name, .. }, args) => {
+            node("FuncCall", vec![string(name), array(args.iter().map(expression))])
+        }
+        Expression::Add(l, r) => boxed("Add", &[l, r]),
+        Expression::Sub(l, r) => boxed("Sub", &[l, r]),
+        Expression::Mul(l, r) => boxed("Mul", &[l, r]),
+        Expression::Div(l, r) => boxed("Div", &[l, r]),
+        Expression::And(l, r) => boxed("And", &[l, r]),
+        Expression::Or(l, r) => boxed("Or", &[l, r]),
+        Expression::EQ(l, r) => boxed("EQ", &[l, r]),
+        Expression::GT(l, r) => boxed("GT", &[l, r]),
+        Expression::LT(l, r) => boxed("LT", &[l, r]),
+        Expression::GTE(l, r) => boxed("GTE", &[l, r]),
+        Expression::LTE(l, r) => boxed("LTE", &[l, r]),
+        Expression::Not(e) => boxed("Not", &[e]),
+        Expression::COk(e) => boxed("COk", &[e]),
+        Expression::CErr(e) => boxed("CErr", &[e]),
+        Expression::CJust(e) => boxed("CJust", &[e]),
+        Expression::Unwrap(e) => boxed("Unwrap", &[e]),
+        Expression::IsError(e) => boxed("IsError", &[e]),
+        Expression::IsNothing(e) => boxed("IsNothing", &[e]),
+        Expression::Propagate(e) => boxed("Propagate", &[e]),
+        Expression::ADTConstructor(adt_name, constructor_name, args) => node(
+            "ADTConstructor",
+            vec![
+                string(adt_name),
+                string(constructor_name),
+                array(args.iter().map(|arg| expression(arg))),
+            ],
+        ),
+        Expression::ADTValue(value) => node(
+            "ADTValue",
+            vec![
+                string(&value.adt),
+                string(&value.constructor),
+                value.tag.to_string(),
+                array(value.fields.iter().map(|arg| expression(arg))),
+            ],
+        ),
+        Expression::Closure(func, captured) => node(
+            "Closure",
+            vec![
+                function(func),
+                array(
+                    captured
+                        .iter()
+                        .map(|(name, value)| array([string(name), expression(value)].into_iter())),
+                ),
+            ],
+        ),
+        Expression::Pipe(value, name, args) => node(
+            "Pipe",
+            vec![expression(value), string(name), array(args.iter().map(expression))],
+        ),
+        Expression::Infix(first, rest) => node(
+            "Infix",
+            vec![
+                expression(first),
+                array(rest.iter().map(|(op, operand)| {
+                    let op = match op {
+                        InfixOperator::Arithmetic(operator) => node("Arithmetic", vec![string(&format!("{:?}", operator))]),
+                        InfixOperator::Function(name) => node("Function", vec![string(name)]),
+                    };
+                    array([op, expression(operand)].into_iter())
+                })),
+            ],
+        ),
+    }
+}
+
+pub fn pattern(pattern: &Pattern) -> String {
+    let all = |patterns: &[Pattern]| array(patterns.iter().map(self::pattern));
+    match pattern {
+        Pattern::Literal(exp) => node("Literal", vec![expression(exp)]),
+        Pattern::Wildcard => string("Wildcard"),
+        Pattern::Variable(name) => node("Variable", vec![string(name)]),
+        Pattern::Constructor(adt_name, constructor_name, args) => {
+            node("Constructor", vec![string(adt_name), string(constructor_name), all(args)])
+        }
+        Pattern::Tagged(adt_name, constructor_name, tag, args) => node(
+            "Tagged",
+            vec![string(adt_name), string(constructor_name), tag.to_string(), all(args)],
+        ),
+        Pattern::Tuple(items) => node("Tuple", vec![all(items)]),
+        Pattern::List(items) => node("List", vec![all(items)]),
+        Pattern::ListRest(items, rest) => node("ListRest", vec![all(items), optional(rest.as_deref().map(string))]),
+        Pattern::As(p, whole) => node("As", vec![self::pattern(p), string(whole)]),
+        Pattern::StartsWith(prefix) => node("StartsWith", vec![string(prefix)]),
+        Pattern::EndsWith(suffix) => node("EndsWith", vec![string(suffix)]),
+    }
+}
+
+pub fn type_(kind: &Type) -> String {
+    match kind {
+        Type::TInteger => string("TInteger"),
+        Type::TBool => string("TBool"),
+        Type::TReal => string("TReal"),
+        Type::TDecimal => string("TDecimal"),
+        Type::TComplex => string("TComplex"),
+        Type::TString => string("TString"),
+        Type::TBytes => string("TBytes"),
+        Type::TVoid => string("TVoid"),
+        Type::TAny => string("TAny"),
+        Type::TFunction(ret, params) => node(
+            "TFunction",
+            vec![
+                optional(ret.as_ref().as_ref().map(type_)),
+                array(params.iter().map(type_)),
+            ],
+        ),
+        Type::TList(t) => node("TList", vec![type_(t)]),
+        Type::TTuple(ts) => node("TTuple", vec![array(ts.iter().map(type_))]),
+        Type::TDict(k, v) => node("TDict", vec![type_(k), type_(v)]),
+        Type::TMaybe(t) => node("TMaybe", vec![type_(t)]),
+        Type::TResult(ok, err) => node("TResult", vec![type_(ok), type_(err)]),
+        Type::Tadt(name, constructors) => node(
+            "Tadt",
+            vec![string(name), array(constructors.iter().map(value_constructor))],
+        ),
+        Type::TVar(name, classes) => node(
+            "TVar",
+            vec![string(name), array(classes.iter().map(|c| string(c)))],
+        ),
+    }
+}
+
+pub fn function(func: &Function) -> String {
+    let params = func.params.as_ref().map(|params| {
+        array(
+            params
+                .iter()
+                .map(|(name, kind)| array([string(name), type_(kind)].into_iter())),
+        )
+    });
+
+    format!(
+        "{{\"name\": {}, \"kind\": {}, \"params\": {}, \"body\": {}}}",
+        string(&func.name),
+        optional(func.kind.as_ref().map(type_)),
+        optional(params),
+        optional(func.body.as_deref().map(statement))
+    )
+}
+
+fn value_constructor(vc: &ValueConstructor) -> String {
+    format!(
+        "{{\"name\": {}, \"types\": {}}}",
+        string(&vc.name),
+        array(vc.types.iter().map(type_))
+    )
+}
+
+/// Encodes `(name, type)` bindings as an object, keeping their order.
+pub fn bindings(bindings: &[(Name, Type)]) -> String {
+    let fields: Vec<String> = bindings
+        .iter()
+        .map(|(name, kind)| format!("{}: {}", string(name), type_(kind)))
+        .collect();
+    format!("{{{}}}", fields.join(", "))
+}
+
+fn node(tag: &str, fields: Vec<String>) -> String {
+    format!("{{{}: [{}]}}", string(tag), fields.join(", "))
+}
+
+// JSON has no infinities or NaN, so they are written as strings
+fn real(value: f64) -> String {
+    if value.is_finite() {
+        pretty::real(value)
+    } else {
+        string(&value.to_string())
+    }
+}
+
+fn array(items: impl Iterator<Item = String>) -> String {
+    format!("[{}]", items.collect::<Vec<String>>().join(", "))
+}
+
+fn optional(value: Option<String>) -> String {
+    value.unwrap_or_else(|| String::from("null"))
+}
+
+pub fn string(value: &str) -> String {
+    let mut out = String::from("\"");
+    for c in value.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ir::ast::Expression::*;
+
+    #[test]
+    fn nodes_are_externally_tagged() {
+        let stmt = Statement::Assignment(
+            "x".to_string(),
+            Box::new(Add(Box::new(CInt(1)), Box::new(CString("a\"b\n".to_string())))),
+            Some(Type::TList(Box::new(Type::TInteger))),
+        );
+
+        assert_eq!(
+            statement(&stmt),
+            r#"{"Assignment": ["x", {"Add": [{"CInt": [1]}, {"CString": ["a\"b\n"]}]}, {"TList": ["TInteger"]}]}"#
+        );
+        assert_eq!(expression(&CReal(1.0)), r#"{"CReal": [1.0]}"#);
+        assert_eq!(expression(&CReal(f64::NAN)), r#"{"CReal": ["NaN"]}"#);
+        assert_eq!(
+            statement(&Statement::IfThenElse(
+                Box::new(CTrue),
+                Box::new(Statement::Return(Box::new(CVoid))),
+                None
+            )),
+            r#"{"IfThenElse": ["CTrue", {"Return": ["CVoid"]}, null]}"#
+        );
+        let option = crate::parser::parser::parse_program("adt Option a = None | Some a").unwrap();
+        assert_eq!(
+            statement(&option[0]),
+            r#"{"ADTDeclaration": ["Option", ["a"], [{"name": "None", "types": []}, {"name": "Some", "types": [{"TVar": ["a", []]}]}]]}"#
+        );
+    }
+}
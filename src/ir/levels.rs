@@ -0,0 +1,290 @@
+use crate::ir::ast::{Expression, Function, Statement, Type};
+use crate::ir::pretty;
+
+/// The constructs a course allows, in the order it introduces them. Each
+/// level allows everything the levels below it allow.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Default)]
+#[non_exhaustive]
+pub enum LanguageLevel {
+    /// Expressions, assignments, `if` and `while`.
+    Expressions = 1,
+    /// Function definitions and `return`.
+    Functions = 2,
+    /// Algebraic data types, `match`, destructuring and instances.
+    Adts = 3,
+    /// Functions defined inside functions, and functions as values.
+    #[default]
+    Closures = 4,
+}
+
+impl LanguageLevel {
+    /// A level by its number, as written in a manifest.
+    pub fn parse(text: &str) -> Option<LanguageLevel> {
+        match text {
+            "1" => Some(LanguageLevel::Expressions),
+            "2" => Some(LanguageLevel::Functions),
+            "3" => Some(LanguageLevel::Adts),
+            "4" => Some(LanguageLevel::Closures),
+            _ => None,
+        }
+    }
+
+    fn describe(self) -> &'static str {
+        match self {
+            LanguageLevel::Expressions => "expressions, if and while",
+            LanguageLevel::Functions => "functions",
+            LanguageLevel::Adts => "data types and match",
+            LanguageLevel::Closures => "closures",
+        }
+    }
+}
+
+/// Rejects the first construct of a surface program above `level`.
+pub fn check(program: &[Statement], level: LanguageLevel) -> Result<(), String> {
+    let gate = Gate { level };
+    program.iter().try_for_each(|stmt| gate.statement(stmt, false))
+}
+
+struct Gate {
+    level: LanguageLevel,
+}
+
+impl Gate {
+    fn require(&self, needed: LanguageLevel, construct: &str, code: String) -> Result<(), String> {
+        if needed <= self.level {
+            return Ok(());
+        }
+        Err(format!(
+            "[Level Error] {} need language level {} ({}), but this course is at level {} ({}): `{}`.",
+            construct,
+            needed as u8,
+            needed.describe(),
+            self.level as u8,
+            self.level.describe(),
+            code
+        ))
+    }
+
+    /// `nested` is set inside function bodies, where definitions make
+    /// closures.
+    fn statement(&self, stmt: &Statement, nested: bool) -> Result<(), String> {
+        match stmt {
+            Statement::FuncDef(func) | Statement::TestDef(func) => {
+                match nested {
+                    true => self.require(LanguageLevel::Closures, "functions inside functions", pretty::statement(stmt))?,
+                    false => self.require(LanguageLevel::Functions, "function definitions", pretty::statement(stmt))?,
+                }
+                self.function(func, stmt)
+            }
+            Statement::Return(exp) => {
+                self.require(LanguageLevel::Functions, "return statements", format!("return {}", pretty::expression(exp)))?;
+                self.expression(exp)
+            }
+            Statement::ADTDeclaration(..) => {
+                self.require(LanguageLevel::Adts, "data type declarations", pretty::statement(stmt))
+            }
+            Statement::InstanceDeclaration(_, _, methods) => {
+                self.require(LanguageLevel::Adts, "instance declarations", pretty::statement(stmt))?;
+                methods.iter().try_for_each(|method| self.function(method, stmt))
+            }
+            Statement::Match(exp, cases) => {
+                self.require(LanguageLevel::Adts, "match statements", format!("match {}", pretty::expression(exp)))?;
+                self.expression(exp)?;
+                cases.iter().try_for_each(|(_, guard, body)| {
+                    guard.iter().try_for_each(|guard| self.expression(guard))?;
+                    self.statement(body, nested)
+                })
+            }
+            Statement::Destructure(_, exp) => {
+                self.require(LanguageLevel::Adts, "destructuring assignments", pretty::statement(stmt))?;
+                self.expression(exp)
+            }
+            Statement::Assignment(_, exp, kind) => {
+                if let Some(kind) = kind {
+                    self.kind(kind, stmt)?;
+                }
+                self.expression(exp)
+            }
+            Statement::ConstDeclaration(_, kind, exp) => {
+                self.kind(kind, stmt)?;
+                self.expression(exp)
+            }
+            Statement::IfThenElse(cond, then, otherwise) => {
+                self.expression(cond)?;
+                self.statement(then, nested)?;
+                otherwise.iter().try_for_each(|stmt| self.statement(stmt, nested))
+            }
+            Statement::FuncClauses(_, kind, clauses) => {
+                match nested {
+                    true => self.require(LanguageLevel::Closures, "functions inside functions", pretty::statement(stmt))?,
+                    false => self.require(LanguageLevel::Functions, "function definitions", pretty::statement(stmt))?,
+                }
+                self.require(LanguageLevel::Adts, "functions defined by clauses", pretty::statement(stmt))?;
+                let kinds = clauses.iter().flat_map(|clause| &clause.params).filter_map(|(_, kind)| kind.as_ref());
+                kinds.chain(kind).try_for_each(|kind| self.kind(kind, stmt))?;
+                clauses.iter().try_for_each(|clause| {
+                    clause.guard.iter().try_for_each(|guard| self.expression(guard))?;
+                    self.expression(&clause.value)
+                })
+            }
+            Statement::IfChain(branches, otherwise) => {
+                for (cond, body) in branches {
+                    self.expression(cond)?;
+                    self.statement(body, nested)?;
+                }
+                otherwise.iter().try_for_each(|stmt| self.statement(stmt, nested))
+            }
+            Statement::While(cond, body) => {
+                self.expression(cond)?;
+                self.statement(body, nested)
+            }
+            Statement::Block(stmts) => stmts.iter().try_for_each(|stmt| self.statement(stmt, nested)),
+            Statement::Sequence(first, second) => {
+                self.statement(first, nested)?;
+                self.statement(second, nested)
+            }
+            Statement::ModTestDef(_, body) => self.statement(body, nested),
+            Statement::AssertEQ(lhs, rhs, _) | Statement::AssertNEQ(lhs, rhs, _) => {
+                self.expression(lhs)?;
+                self.expression(rhs)
+            }
+            Statement::AssertTrue(exp, _)
+            | Statement::AssertFalse(exp, _)
+            | Statement::Call(exp)
+            | Statement::Requires(exp)
+            | Statement::Ensures(exp)
+            | Statement::Invariant(exp)
+            | Statement::Decreases(exp)
+            | Statement::AugmentedAssignment(_, _, exp) => self.expression(exp),
+            _ => Ok(()),
+        }
+    }
+
+    fn function(&self, func: &Function, stmt: &Statement) -> Result<(), String> {
+        for (_, kind) in func.params.iter().flatten() {
+            self.kind(kind, stmt)?;
+        }
+        if let Some(kind) = &func.kind {
+            self.kind(kind, stmt)?;
+        }
+        func.body.iter().try_for_each(|body| self.statement(body, true))
+    }
+
+    fn kind(&self, kind: &Type, stmt: &Statement) -> Result<(), String> {
+        if mentions_function(kind) {
+            return self.require(LanguageLevel::Closures, "function types", pretty::statement(stmt));
+        }
+        Ok(())
+    }
+
+    fn expression(&self, exp: &Expression) -> Result<(), String> {
+        match exp {
+            Expression::ADTConstructor(_, _, args) => {
+                self.require(LanguageLevel::Adts, "data type values", pretty::expression(exp))?;
+                args.iter().try_for_each(|arg| self.expression(arg))
+            }
+            Expression::Closure(..) => {
+                self.require(LanguageLevel::Closures, "function values", pretty::expression(exp))
+            }
+            Expression::FuncCall(_, args)
+            | Expression::CachedCall(_, args)
+            | Expression::CList(args)
+            | Expression::CTuple(args) => {
+                args.iter().try_for_each(|arg| self.expression(arg))
+            }
+            Expression::Pipe(value, _, args) => {
+                self.expression(value)?;
+                args.iter().try_for_each(|arg| self.expression(arg))
+            }
+            Expression::Infix(first, rest) => {
+                self.expression(first)?;
+                rest.iter().try_for_each(|(_, operand)| self.expression(operand))
+            }
+            Expression::CDict(entries) => entries.iter().try_for_each(|(key, value)| {
+                self.expression(key)?;
+                self.expression(value)
+            }),
+            Expression::Add(l, r)
+            | Expression::Sub(l, r)
+            | Expression::Mul(l, r)
+            | Expression::Div(l, r)
+            | Expression::And(l, r)
+            | Expression::Or(l, r)
+            | Expression::EQ(l, r)
+            | Expression::GT(l, r)
+            | Expression::LT(l, r)
+            | Expression::GTE(l, r)
+            | Expression::LTE(l, r) => {
+                self.expression(l)?;
+                self.expression(r)
+            }
+            Expression::Not(e)
+            | Expression::COk(e)
+            | Expression::CErr(e)
+            | Expression::CJust(e)
+            | Expression::Unwrap(e)
+            | Expression::IsError(e)
+            | Expression::IsNothing(e)
+            | Expression::Propagate(e) => self.expression(e),
+            _ => Ok(()),
+        }
+    }
+}
+
+fn mentions_function(kind: &Type) -> bool {
+    match kind {
+        Type::TFunction(..) => true,
+        Type::TList(t) | Type::TMaybe(t) => mentions_function(t),
+        Type::TResult(t1, t2) | Type::TDict(t1, t2) => mentions_function(t1) || mentions_function(t2),
+        Type::TTuple(ts) => ts.iter().any(mentions_function),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parser::{dedent, parse_program};
+
+    fn lowest_level(source: &str) -> LanguageLevel {
+        let program = parse_program(&dedent(source)).unwrap();
+        [LanguageLevel::Expressions, LanguageLevel::Functions, LanguageLevel::Adts]
+            .into_iter()
+            .find(|level| check(&program, *level).is_ok())
+            .unwrap_or(LanguageLevel::Closures)
+    }
+
+    #[test]
+    fn each_level_adds_constructs() {
+        assert_eq!(lowest_level("x = 1\nwhile x < 10:\n    x = x * 2"), LanguageLevel::Expressions);
+        assert_eq!(lowest_level("def double(n: TInteger) -> TInteger:\n    return n * 2"), LanguageLevel::Functions);
+        assert_eq!(
+            lowest_level("adt Shape = Circle Real | Square Real"),
+            LanguageLevel::Adts
+        );
+        assert_eq!(
+            lowest_level(
+                "
+                def outer(n: TInteger) -> TInteger:
+                    def inner(m: TInteger) -> TInteger:
+                        return m + n
+                    return inner(1)
+                "
+            ),
+            LanguageLevel::Closures
+        );
+    }
+
+    #[test]
+    fn levels_explain_what_they_reject() {
+        let program = parse_program("x = 2\ndef double(n: TInteger) -> TInteger:\n    return n * 2").unwrap();
+        assert_eq!(
+            check(&program, LanguageLevel::Expressions),
+            Err(String::from(
+                "[Level Error] function definitions need language level 2 (functions), but this course is at level 1 (expressions, if and while): `def double(...)`."
+            ))
+        );
+        assert_eq!(LanguageLevel::parse("3"), Some(LanguageLevel::Adts));
+        assert_eq!(LanguageLevel::parse("5"), None);
+    }
+}
@@ -0,0 +1,412 @@
+use crate::ir::ast::{Clause, Expression, Function, InfixOperator, Name, Pattern, Statement};
+use std::collections::BTreeSet;
+
+/// The name a file declares with a `module a.b` header, which must be its
+/// first statement.
+pub fn header(stmts: &[Statement]) -> Result<Option<Name>, String> {
+    let misplaced = stmts
+        .iter()
+        .skip(1)
+        .any(|stmt| matches!(stmt, Statement::Module(_)));
+    if misplaced {
+        return Err(String::from(
+            "[Syntax Error] 'module' is only allowed as the first statement of a file.",
+        ));
+    }
+    match stmts.first() {
+        Some(Statement::Module(name)) => Ok(Some(name.clone())),
+        _ => Ok(None),
+    }
+}
+
+/// Registers the names `module` binds at the top level (functions, globals
+/// and ADTs) as `module.name`, and rewrites the module's own references to
+/// them. Names bound inside a function shadow the top-level ones.
+pub fn qualify(module: &str, stmts: Vec<Statement>) -> Vec<Statement> {
+    let top = definitions(&stmts);
+    let mut rename = |name: &Name| {
+        if top.contains(name) {
+            Some(format!("{}.{}", module, name))
+        } else {
+            None
+        }
+    };
+    let scope = Scope {
+        global: true,
+        locals: BTreeSet::new(),
+    };
+    stmts
+        .into_iter()
+        .map(|stmt| statement(stmt, &scope, &mut rename))
+        .collect()
+}
+
+/// The names bound at the top level of a program, including inside its
+/// conditionals and loops, but not inside functions.
+pub fn definitions(stmts: &[Statement]) -> BTreeSet<Name> {
+    let mut found = BTreeSet::new();
+    let mut pending: Vec<&Statement> = stmts.iter().collect();
+
+    while let Some(stmt) = pending.pop() {
+        match stmt {
+            Statement::Assignment(name, _, _)
+            | Statement::AugmentedAssignment(name, _, _)
+            | Statement::VarDeclaration(name)
+            | Statement::ValDeclaration(name)
+            | Statement::ConstDeclaration(name, _, _)
+            | Statement::ADTDeclaration(name, ..) => {
+                found.insert(name.clone());
+            }
+            Statement::Destructure(pattern, _) => found.extend(pattern.variables()),
+            Statement::FuncDef(func) => {
+                found.insert(func.name.clone());
+            }
+            Statement::FuncClauses(name, _, _) => {
+                found.insert(name.clone());
+            }
+            Statement::IfThenElse(_, then_stmt, else_stmt) => {
+                pending.push(then_stmt);
+                pending.extend(else_stmt.as_deref());
+            }
+            Statement::IfChain(branches, else_stmt) => {
+                pending.extend(branches.iter().map(|(_, stmt)| stmt));
+                pending.extend(else_stmt.as_deref());
+            }
+            Statement::While(_, body) => pending.push(body),
+            Statement::Block(stmts) => pending.extend(stmts),
+            Statement::Sequence(s1, s2) => pending.extend([s1.as_ref(), s2.as_ref()]),
+            _ => {}
+        }
+    }
+    found
+}
+
+/// The qualified names (`a.b`) a program refers to.
+pub fn qualified_references(stmts: &[Statement]) -> BTreeSet<Name> {
+    references(stmts).into_iter().filter(|name| name.contains('.')).collect()
+}
+
+/// The top-level names a program refers to or binds; the names local to a
+/// function are left out.
+pub fn references(stmts: &[Statement]) -> BTreeSet<Name> {
+    let mut found = BTreeSet::new();
+    let mut record = |name: &Name| {
+        found.insert(name.clone());
+        None
+    };
+    let scope = Scope {
+        global: true,
+        locals: BTreeSet::new(),
+    };
+    for stmt in stmts {
+        statement(stmt.clone(), &scope, &mut record);
+    }
+    found
+}
+
+type Rename<'a> = dyn FnMut(&Name) -> Option<Name> + 'a;
+
+/// Whether names are resolved at the top level or inside a function, and
+/// the names bound locally, which are never renamed.
+#[derive(Clone)]
+struct Scope {
+    global: bool,
+    locals: BTreeSet<Name>,
+}
+
+impl Scope {
+    fn reference(&self, name: Name, rename: &mut Rename) -> Name {
+        if self.locals.contains(&name) {
+            return name;
+        }
+        rename(&name).unwrap_or(name)
+    }
+
+    fn target(&self, name: Name, rename: &mut Rename) -> Name {
+        if self.global {
+            self.reference(name, rename)
+        } else {
+            name
+        }
+    }
+
+    fn with(&self, names: impl IntoIterator<Item = Name>) -> Scope {
+        let mut scope = self.clone();
+        scope.locals.extend(names);
+        scope
+    }
+}
+
+fn function(mut func: Function, scope: &Scope, rename: &mut Rename) -> Function {
+    let params = func.params.iter().flatten().map(|(name, _)| name.clone());
+    let mut body_scope = scope.with(params);
+    body_scope.global = false;
+    if let Some(body) = &func.body {
+        body_scope.locals.extend(definitions(std::slice::from_ref(body)));
+    }
+    func.body = func.body.map(|body| Box::new(statement(*body, &body_scope, rename)));
+    func
+}
+
+/// Qualifies the ADTs and the names a clause refers to; the variables of
+/// its patterns are local to it.
+fn clause(clause: Clause, scope: &Scope, rename: &mut Rename) -> Clause {
+    let clause_scope = scope.with(clause.params.iter().flat_map(|(pattern, _)| pattern.variables()));
+    Clause {
+        params: clause
+            .params
+            .into_iter()
+            .map(|(pattern, kind)| (self::pattern(pattern, scope, rename), kind))
+            .collect(),
+        guard: clause.guard.map(|guard| expression(guard, &clause_scope, rename)),
+        value: expression(clause.value, &clause_scope, rename),
+    }
+}
+
+fn statement(stmt: Statement, scope: &Scope, rename: &mut Rename) -> Statement {
+    let stmt_in = |stmt: Box<Statement>, rename: &mut Rename| Box::new(statement(*stmt, scope, rename));
+    let exp = |e: Box<Expression>, rename: &mut Rename| Box::new(expression(*e, scope, rename));
+
+    match stmt {
+        Statement::VarDeclaration(name) => Statement::VarDeclaration(scope.target(name, rename)),
+        Statement::ValDeclaration(name) => Statement::ValDeclaration(scope.target(name, rename)),
+        Statement::Assignment(name, e, kind) => {
+            let e = exp(e, rename);
+            Statement::Assignment(scope.target(name, rename), e, kind)
+        }
+        Statement::ConstDeclaration(name, kind, e) => {
+            let e = exp(e, rename);
+            Statement::ConstDeclaration(scope.target(name, rename), kind, e)
+        }
+        Statement::Destructure(pattern, e) => {
+            let e = exp(e, rename);
+            Statement::Destructure(targets(self::pattern(pattern, scope, rename), scope, rename), e)
+        }
+        Statement::AugmentedAssignment(name, op, e) => {
+            let e = exp(e, rename);
+            Statement::AugmentedAssignment(scope.target(name, rename), op, e)
+        }
+        Statement::IfThenElse(cond, then_stmt, else_stmt) => Statement::IfThenElse(
+            exp(cond, rename),
+            stmt_in(then_stmt, rename),
+            else_stmt.map(|s| stmt_in(s, rename)),
+        ),
+        Statement::IfChain(branches, else_stmt) => Statement::IfChain(
+            branches
+                .into_iter()
+                .map(|(cond, stmt)| (expression(cond, scope, rename), statement(stmt, scope, rename)))
+                .collect(),
+            else_stmt.map(|s| stmt_in(s, rename)),
+        ),
+        Statement::While(cond, body) => Statement::While(exp(cond, rename), stmt_in(body, rename)),
+        Statement::Block(stmts) => Statement::Block(
+            stmts
+                .into_iter()
+                .map(|stmt| statement(stmt, scope, rename))
+                .collect(),
+        ),
+        Statement::Sequence(s1, s2) => Statement::Sequence(stmt_in(s1, rename), stmt_in(s2, rename)),
+        Statement::AssertTrue(e, msg) => Statement::AssertTrue(exp(e, rename), msg),
+        Statement::AssertFalse(e, msg) => Statement::AssertFalse(exp(e, rename), msg),
+        Statement::AssertEQ(lhs, rhs, msg) => Statement::AssertEQ(exp(lhs, rename), exp(rhs, rename), msg),
+        Statement::AssertNEQ(lhs, rhs, msg) => {
+            Statement::AssertNEQ(exp(lhs, rename), exp(rhs, rename), msg)
+        }
+        Statement::FuncDef(func) => {
+            let name = scope.target(func.name.clone(), rename);
+            Statement::FuncDef(Function {
+                name,
+                ..function(func, scope, rename)
+            })
+        }
+        Statement::TestDef(func) => Statement::TestDef(function(func, scope, rename)),
+        Statement::ModTestDef(name, stmt) => Statement::ModTestDef(name, stmt_in(stmt, rename)),
+        Statement::Return(e) => Statement::Return(exp(e, rename)),
+        Statement::Call(e) => Statement::Call(exp(e, rename)),
+        Statement::Requires(e) => Statement::Requires(exp(e, rename)),
+        Statement::Ensures(e) => Statement::Ensures(exp(e, rename)),
+        Statement::Invariant(e) => Statement::Invariant(exp(e, rename)),
+        Statement::Decreases(e) => Statement::Decreases(exp(e, rename)),
+        Statement::Locals(layout) => Statement::Locals(layout),
+        Statement::ADTDeclaration(name, params, constructors) => {
+            Statement::ADTDeclaration(scope.target(name, rename), params, constructors)
+        }
+        Statement::Match(e, cases) => {
+            let e = exp(e, rename);
+            let cases = cases
+                .into_iter()
+                .map(|(pattern, guard, stmt)| {
+                    let case_scope = scope.with(pattern.variables());
+                    let pattern = self::pattern(pattern, scope, rename);
+                    let guard = guard.map(|guard| expression(guard, &case_scope, rename));
+                    (pattern, guard, Box::new(statement(*stmt, &case_scope, rename)))
+                })
+                .collect();
+            Statement::Match(e, cases)
+        }
+        Statement::FixityDeclaration(name, fixity) => Statement::FixityDeclaration(scope.reference(name, rename), fixity),
+        Statement::FuncClauses(name, kind, clauses) => {
+            let name = scope.target(name, rename);
+            let clauses = clauses.into_iter().map(|clause| self::clause(clause, scope, rename)).collect();
+            Statement::FuncClauses(name, kind, clauses)
+        }
+        Statement::InstanceDeclaration(class, adt, methods) => Statement::InstanceDeclaration(
+            class,
+            scope.reference(adt, rename),
+            methods
+                .into_iter()
+                .map(|method| function(method, scope, rename))
+                .collect(),
+        ),
+        Statement::At(position, stmt) => Statement::At(position, stmt_in(stmt, rename)),
+        Statement::AssertFails(_) | Statement::Import(_) | Statement::Module(_) => stmt,
+    }
+}
+
+/// Qualifies the variables a destructuring pattern binds, which, unlike
+/// those of a match arm, outlive the statement.
+fn targets(pattern: Pattern, scope: &Scope, rename: &mut Rename) -> Pattern {
+    let mut all = |patterns: Vec<Pattern>| {
+        patterns.into_iter().map(|p| targets(p, scope, rename)).collect()
+    };
+    match pattern {
+        Pattern::Variable(name) => Pattern::Variable(scope.target(name, rename)),
+        Pattern::Constructor(adt, constructor, args) => Pattern::Constructor(adt, constructor, all(args)),
+        Pattern::Tuple(items) => Pattern::Tuple(all(items)),
+        Pattern::List(items) => Pattern::List(all(items)),
+        Pattern::ListRest(items, rest) => {
+            let items = all(items);
+            Pattern::ListRest(items, rest.map(|name| scope.target(name, rename)))
+        }
+        Pattern::As(pattern, whole) => {
+            let pattern = targets(*pattern, scope, rename);
+            Pattern::As(Box::new(pattern), scope.target(whole, rename))
+        }
+        pattern => pattern,
+    }
+}
+
+/// Qualifies the ADTs a pattern names; its variables are local to the arm.
+fn pattern(pattern: Pattern, scope: &Scope, rename: &mut Rename) -> Pattern {
+    let mut all = |patterns: Vec<Pattern>| {
+        patterns.into_iter().map(|p| self::pattern(p, scope, rename)).collect()
+    };
+    match pattern {
+        Pattern::Constructor(adt, constructor, args) => {
+            let args = all(args);
+            Pattern::Constructor(scope.reference(adt, rename), constructor, args)
+        }
+        Pattern::Tuple(items) => Pattern::Tuple(all(items)),
+        Pattern::List(items) => Pattern::List(all(items)),
+        Pattern::ListRest(items, rest) => Pattern::ListRest(all(items), rest),
+        Pattern::As(pattern, whole) => Pattern::As(Box::new(self::pattern(*pattern, scope, rename)), whole),
+        pattern => pattern,
+    }
+}
+
+fn expression(exp: Expression, scope: &Scope, rename: &mut Rename) -> Expression {
+    let boxed = |e: Box<Expression>, rename: &mut Rename| Box::new(expression(*e, scope, rename));
+    let all = |exps: Vec<Expression>, rename: &mut Rename| {
+        exps.into_iter()
+            .map(|e| expression(e, scope, rename))
+            .collect::<Vec<_>>()
+    };
+
+    match exp {
+        Expression::Var(name) => Expression::Var(scope.reference(name, rename)),
+        Expression::FuncCall(name, args) => {
+            let name = scope.reference(name, rename);
+            Expression::FuncCall(name, all(args, rename))
+        }
+        Expression::Pipe(value, name, args) => {
+            let value = boxed(value, rename);
+            let name = scope.reference(name, rename);
+            Expression::Pipe(value, name, all(args, rename))
+        }
+        Expression::Infix(first, rest) => {
+            let first = boxed(first, rename);
+            let rest = rest
+                .into_iter()
+                .map(|(op, operand)| {
+                    let op = match op {
+                        InfixOperator::Function(name) => InfixOperator::Function(scope.reference(name, rename)),
+                        op => op,
+                    };
+                    (op, expression(operand, scope, rename))
+                })
+                .collect();
+            Expression::Infix(first, rest)
+        }
+        Expression::ADTConstructor(adt, constructor, args) => {
+            let adt = scope.reference(adt, rename);
+            let args = args.into_iter().map(|arg| boxed(arg, rename)).collect();
+            Expression::ADTConstructor(adt, constructor, args)
+        }
+        Expression::CList(items) => Expression::CList(all(items, rename)),
+        Expression::CTuple(items) => Expression::CTuple(all(items, rename)),
+        Expression::CDict(entries) => Expression::CDict(
+            entries
+                .into_iter()
+                .map(|(k, v)| (expression(k, scope, rename), expression(v, scope, rename)))
+                .collect(),
+        ),
+        Expression::Add(l, r) => Expression::Add(boxed(l, rename), boxed(r, rename)),
+        Expression::Sub(l, r) => Expression::Sub(boxed(l, rename), boxed(r, rename)),
+        Expression::Mul(l, r) => Expression::Mul(boxed(l, rename), boxed(r, rename)),
+        Expression::Div(l, r) => Expression::Div(boxed(l, rename), boxed(r, rename)),
+        Expression::And(l, r) => Expression::And(boxed(l, rename), boxed(r, rename)),
+        Expression::Or(l, r) => Expression::Or(boxed(l, rename), boxed(r, rename)),
+        Expression::EQ(l, r) => Expression::EQ(boxed(l, rename), boxed(r, rename)),
+        Expression::GT(l, r) => Expression::GT(boxed(l, rename), boxed(r, rename)),
+        Expression::LT(l, r) => Expression::LT(boxed(l, rename), boxed(r, rename)),
+        Expression::GTE(l, r) => Expression::GTE(boxed(l, rename), boxed(r, rename)),
+        Expression::LTE(l, r) => Expression::LTE(boxed(l, rename), boxed(r, rename)),
+        Expression::Not(e) => Expression::Not(boxed(e, rename)),
+        Expression::COk(e) => Expression::COk(boxed(e, rename)),
+        Expression::CErr(e) => Expression::CErr(boxed(e, rename)),
+        Expression::CJust(e) => Expression::CJust(boxed(e, rename)),
+        Expression::Unwrap(e) => Expression::Unwrap(boxed(e, rename)),
+        Expression::IsError(e) => Expression::IsError(boxed(e, rename)),
+        Expression::IsNothing(e) => Expression::IsNothing(boxed(e, rename)),
+        Expression::Propagate(e) => Expression::Propagate(boxed(e, rename)),
+        exp => exp,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parser::parse_program;
+
+    #[test]
+    fn qualify_renames_top_level_names_but_not_locals() {
+        let source = "module shapes\nscale = 2\ndef area(side: TInteger) -> TInteger:\n    scale = 3\n    return side * side * scale\ndef double(x: TInteger) -> TInteger:\n    return area(x) * scale";
+        let program = qualify("shapes", parse_program(source).unwrap());
+
+        assert_eq!(
+            definitions(&program),
+            ["shapes.area", "shapes.double", "shapes.scale"]
+                .map(String::from)
+                .into()
+        );
+        // `area` binds its own `scale`; `double` reads the global one
+        assert_eq!(
+            qualified_references(&program),
+            ["shapes.area", "shapes.double", "shapes.scale"]
+                .map(String::from)
+                .into()
+        );
+        let Statement::FuncDef(area) = &program[2] else {
+            panic!("expected a function");
+        };
+        assert!(!qualified_references(std::slice::from_ref(area.body.as_ref().unwrap()))
+            .contains("shapes.scale"));
+    }
+
+    #[test]
+    fn header_must_come_first() {
+        assert_eq!(header(&parse_program("module a\nx = 1").unwrap()), Ok(Some(String::from("a"))));
+        assert_eq!(header(&parse_program("x = 1").unwrap()), Ok(None));
+        assert!(header(&parse_program("x = 1\nmodule a").unwrap()).is_err());
+    }
+}
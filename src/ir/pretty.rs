@@ -0,0 +1,194 @@
+use crate::ir::ast::{ArithmeticOperator, Associativity, CallSite, Expression, InfixOperator, Pattern, Statement};
+use crate::tc::type_checker::type_display;
+
+/// Source-like text of a statement that can appear inside a basic block;
+/// compound statements fall back to their debug form.
+pub fn statement(stmt: &Statement) -> String {
+    match stmt {
+        Statement::VarDeclaration(name) => format!("var {}", name),
+        Statement::ValDeclaration(name) => format!("val {}", name),
+        Statement::ConstDeclaration(name, kind, exp) => {
+            format!("const {}: {} = {}", name, type_display(kind), expression(exp))
+        }
+        Statement::Assignment(name, exp, _) => format!("{} = {}", name, expression(exp)),
+        Statement::Destructure(p, exp) => format!("{} = {}", pattern(p), expression(exp)),
+        Statement::AssertTrue(exp, _) => format!("assert {}", expression(exp)),
+        Statement::AssertFalse(exp, _) => format!("assert not {}", expression(exp)),
+        Statement::AssertEQ(lhs, rhs, _) => format!(
+            "assert {} == {}",
+            expression(lhs),
+            expression(rhs)
+        ),
+        Statement::AssertNEQ(lhs, rhs, _) => format!(
+            "assert {} != {}",
+            expression(lhs),
+            expression(rhs)
+        ),
+        Statement::Requires(exp) => format!("requires {}", expression(exp)),
+        Statement::Ensures(exp) => format!("ensures {}", expression(exp)),
+        Statement::Invariant(exp) => format!("invariant {}", expression(exp)),
+        Statement::Decreases(exp) => format!("decreases {}", expression(exp)),
+        Statement::Locals(layout) => format!("locals {}", layout.join(", ")),
+        Statement::Call(exp) => expression(exp),
+        Statement::Import(module) => format!("import {}", module),
+        Statement::Module(module) => format!("module {}", module),
+        Statement::AssertFails(msg) => format!("assert fails {:?}", msg),
+        Statement::FuncDef(func) => format!("def {}(...)", func.name),
+        Statement::FuncClauses(name, _, _) => format!("def {}(...)", name),
+        Statement::At(_, stmt) => statement(stmt),
+        Statement::FixityDeclaration(name, fixity) => format!(
+            "{} {} {}",
+            match fixity.associativity {
+                Associativity::Left => "infixl",
+                Associativity::Right => "infixr",
+            },
+            fixity.precedence,
+            name
+        ),
+        Statement::TestDef(func) => format!("test {}(...)", func.name),
+        Statement::ModTestDef(name, _) => format!("modtest {}", name),
+        Statement::ADTDeclaration(name, params, _) if params.is_empty() => format!("data {}", name),
+        Statement::ADTDeclaration(name, params, _) => format!("data {} {}", name, params.join(" ")),
+        Statement::InstanceDeclaration(class, adt_name, _) => {
+            format!("instance {} {}", class, adt_name)
+        }
+        _ => format!("{:?}", stmt),
+    }
+}
+
+/// The shortest digits that read back as the same value, so printed
+/// programs and values survive a round trip through the parser. Large and
+/// small magnitudes use an exponent, as in `1e16` and `1.5e-7`.
+pub fn real(value: f64) -> String {
+    format!("{:?}", value)
+}
+
+/// A pattern as it is written in a `match` arm: `Shape Circle r`, with
+/// nested constructors in parentheses.
+pub fn pattern(pattern: &Pattern) -> String {
+    let all = |patterns: &[Pattern]| patterns.iter().map(self::pattern).collect::<Vec<String>>().join(", ");
+    match pattern {
+        Pattern::Literal(exp) => expression(exp),
+        Pattern::Wildcard => String::from("_"),
+        Pattern::Variable(name) => name.clone(),
+        Pattern::Constructor(adt_name, constructor_name, args) | Pattern::Tagged(adt_name, constructor_name, _, args) => {
+            let args = args.iter().map(|arg| match arg {
+                Pattern::Constructor(..) | Pattern::Tagged(..) | Pattern::As(..) => format!(" ({})", self::pattern(arg)),
+                arg => format!(" {}", self::pattern(arg)),
+            });
+            format!("{} {}{}", adt_name, constructor_name, args.collect::<String>())
+        }
+        Pattern::Tuple(items) => format!("({})", all(items)),
+        Pattern::List(items) => format!("[{}]", all(items)),
+        Pattern::ListRest(items, rest) => {
+            let rest = rest.as_ref().map_or(String::from(".."), |name| format!("{} @ ..", name));
+            match items.is_empty() {
+                true => format!("[{}]", rest),
+                false => format!("[{}, {}]", all(items), rest),
+            }
+        }
+        Pattern::As(pattern, whole) => format!("{} as {}", self::pattern(pattern), whole),
+        Pattern::StartsWith(prefix) => format!("startswith {}", expression(&Expression::CString(prefix.clone()))),
+        Pattern::EndsWith(suffix) => format!("endswith {}", expression(&Expression::CString(suffix.clone()))),
+    }
+}
+
+/// The head of a `match` arm: its pattern, and its guard if it has one.
+pub fn arm(pattern: &Pattern, guard: Option<&Expression>) -> String {
+    match guard {
+        Some(guard) => format!("{} if {}", self::pattern(pattern), expression(guard)),
+        None => self::pattern(pattern),
+    }
+}
+
+pub fn expression(exp: &Expression) -> String {
+    let all = |exps: &mut dyn Iterator<Item = &Expression>| {
+        exps.map(expression).collect::<Vec<String>>().join(", ")
+    };
+    let operand = |e: &Expression| match e {
+        Expression::Add(..)
+        | Expression::Sub(..)
+        | Expression::Mul(..)
+        | Expression::Div(..)
+        | Expression::And(..)
+        | Expression::Or(..)
+        | Expression::EQ(..)
+        | Expression::GT(..)
+        | Expression::LT(..)
+        | Expression::GTE(..)
+        | Expression::LTE(..)
+        | Expression::Infix(..) => format!("({})", expression(e)),
+        _ => expression(e),
+    };
+    let binary = |l: &Expression, op: &str, r: &Expression| format!("{} {} {}", operand(l), op, operand(r));
+
+    match exp {
+        Expression::CTrue => String::from("True"),
+        Expression::CFalse => String::from("False"),
+        Expression::CVoid => String::from("None"),
+        Expression::CNothing => String::from("Nothing"),
+        Expression::CInt(value) => value.to_string(),
+        Expression::CReal(value) => real(*value),
+        Expression::CDecimal(value) => format!("{}d", value),
+        Expression::CComplex(value) => value.to_string(),
+        Expression::CString(value) => format!("{:?}", value),
+        Expression::CBytes(bytes) => format!("b{:?}", String::from_utf8_lossy(bytes)),
+        Expression::CList(items) => format!("[{}]", all(&mut items.iter())),
+        Expression::CTuple(items) => format!("({})", all(&mut items.iter())),
+        Expression::CDict(entries) => format!(
+            "{{{}}}",
+            entries
+                .iter()
+                .map(|(k, v)| format!("{}: {}", expression(k), expression(v)))
+                .collect::<Vec<String>>()
+                .join(", ")
+        ),
+        Expression::Var(name) | Expression::Slot(_, name) => name.clone(),
+        Expression::FuncCall(name, args) | Expression::CachedCall(CallSite { name, .. }, args) => format!("{}({})", name, all(&mut args.iter())),
+        Expression::Pipe(value, name, args) => format!(
+            "{} |> {}({})",
+            expression(value),
+            name,
+            all(&mut args.iter())
+        ),
+        Expression::Infix(first, rest) => rest.iter().fold(operand(first), |acc, (op, e)| {
+            let op = match op {
+                InfixOperator::Arithmetic(ArithmeticOperator::Add) => String::from("+"),
+                InfixOperator::Arithmetic(ArithmeticOperator::Sub) => String::from("-"),
+                InfixOperator::Arithmetic(ArithmeticOperator::Mul) => String::from("*"),
+                InfixOperator::Arithmetic(ArithmeticOperator::Div) => String::from("/"),
+                InfixOperator::Function(name) => format!("`{}`", name),
+            };
+            format!("{} {} {}", acc, op, operand(e))
+        }),
+        Expression::Add(l, r) => binary(l, "+", r),
+        Expression::Sub(l, r) => binary(l, "-", r),
+        Expression::Mul(l, r) => binary(l, "*", r),
+        Expression::Div(l, r) => binary(l, "/", r),
+        Expression::And(l, r) => binary(l, "and", r),
+        Expression::Or(l, r) => binary(l, "or", r),
+        Expression::EQ(l, r) => binary(l, "==", r),
+        Expression::GT(l, r) => binary(l, ">", r),
+        Expression::LT(l, r) => binary(l, "<", r),
+        Expression::GTE(l, r) => binary(l, ">=", r),
+        Expression::LTE(l, r) => binary(l, "<=", r),
+        Expression::Not(e) => format!("not {}", expression(e)),
+        Expression::COk(e) => format!("Ok({})", expression(e)),
+        Expression::CErr(e) => format!("Err({})", expression(e)),
+        Expression::CJust(e) => format!("Just({})", expression(e)),
+        Expression::Unwrap(e) => format!("unwrap({})", expression(e)),
+        Expression::IsError(e) => format!("isError({})", expression(e)),
+        Expression::IsNothing(e) => format!("isNothing({})", expression(e)),
+        Expression::Propagate(e) => format!("{}?", expression(e)),
+        Expression::ADTConstructor(_, constructor_name, args) if args.is_empty() => {
+            constructor_name.clone()
+        }
+        Expression::ADTConstructor(_, constructor_name, args) => format!(
+            "{}({})",
+            constructor_name,
+            all(&mut args.iter().map(|arg| arg.as_ref()))
+        ),
+        Expression::ADTValue(value) => value.constructor.clone(),
+        Expression::Closure(func, _) => format!("<function {}>", func.name),
+    }
+}
@@ -0,0 +1,318 @@
+use std::sync::Arc;
+
+use crate::ir::ast::{Clause, Expression, Function, Name, Statement};
+
+/// Numbers the parameters and local variables of every function, in the
+/// order they are first bound, and gives the function that layout first in
+/// its body. Reads of a local become `Slot` reads, so the interpreter finds
+/// the value at its index in the call's frame instead of looking its name
+/// up; the name stays for diagnostics and for lookups by name.
+///
+/// Each function has a layout of its own: a nested function reads the
+/// locals of the function around it by name, as it captured them.
+pub fn assign(stmts: Vec<Statement>) -> Vec<Statement> {
+    stmts.into_iter().map(statement).collect()
+}
+
+/// Numbers the locals of the functions `stmt` defines; code outside
+/// functions keeps its variables by name.
+pub fn statement(stmt: Statement) -> Statement {
+    let boxed = |stmt: Box<Statement>| Box::new(statement(*stmt));
+
+    match stmt {
+        Statement::FuncDef(func) => Statement::FuncDef(function(func)),
+        Statement::TestDef(func) => Statement::TestDef(function(func)),
+        Statement::InstanceDeclaration(class, adt_name, methods) => {
+            Statement::InstanceDeclaration(class, adt_name, methods.into_iter().map(function).collect())
+        }
+        Statement::Match(e, arms) => Statement::Match(
+            e,
+            arms.into_iter()
+                .map(|(pattern, guard, stmt)| (pattern, guard, boxed(stmt)))
+                .collect(),
+        ),
+        Statement::At(position, stmt) => Statement::At(position, boxed(stmt)),
+        Statement::IfThenElse(cond, then_stmt, else_stmt) => {
+            Statement::IfThenElse(cond, boxed(then_stmt), else_stmt.map(boxed))
+        }
+        Statement::While(cond, body) => Statement::While(cond, boxed(body)),
+        Statement::Block(stmts) => Statement::Block(assign(stmts)),
+        Statement::Sequence(s1, s2) => Statement::Sequence(boxed(s1), boxed(s2)),
+        Statement::ModTestDef(name, stmt) => Statement::ModTestDef(name, boxed(stmt)),
+        stmt => stmt,
+    }
+}
+
+fn function(func: Function) -> Function {
+    let mut layout: Vec<Name> = func.params.iter().flatten().map(|(param, _)| param.clone()).collect();
+    if let Some(body) = &func.body {
+        bound(body, &mut layout);
+    }
+    let body = func.body.map(|body| {
+        let body = local(*body, &layout);
+        if layout.is_empty() {
+            return Box::new(body);
+        }
+        let mut stmts = vec![Statement::Locals(Arc::new(layout))];
+        match body {
+            Statement::Block(rest) => stmts.extend(rest),
+            body => stmts.push(body),
+        }
+        Box::new(Statement::Block(stmts))
+    });
+    Function { body, ..func }
+}
+
+/// Adds the variables `stmt` binds to `layout`, skipping those of the
+/// functions it defines.
+fn bound(stmt: &Statement, layout: &mut Vec<Name>) {
+    match stmt {
+        Statement::Assignment(name, _, _) | Statement::VarDeclaration(name) | Statement::ValDeclaration(name) => {
+            add(name, layout)
+        }
+        Statement::FuncDef(func) => add(&func.name, layout),
+        Statement::Destructure(pattern, _) => pattern.variables().iter().for_each(|name| add(name, layout)),
+        Statement::Match(_, arms) => {
+            for (pattern, _, stmt) in arms {
+                pattern.variables().iter().for_each(|name| add(name, layout));
+                bound(stmt, layout);
+            }
+        }
+        Statement::IfThenElse(_, then_stmt, else_stmt) => {
+            bound(then_stmt, layout);
+            if let Some(else_stmt) = else_stmt {
+                bound(else_stmt, layout);
+            }
+        }
+        Statement::While(_, body) | Statement::At(_, body) => bound(body, layout),
+        Statement::Block(stmts) => stmts.iter().for_each(|stmt| bound(stmt, layout)),
+        Statement::Sequence(s1, s2) => {
+            bound(s1, layout);
+            bound(s2, layout);
+        }
+        _ => {}
+    }
+}
+
+fn add(name: &Name, layout: &mut Vec<Name>) {
+    if !layout.contains(name) {
+        layout.push(name.clone());
+    }
+}
+
+fn local(stmt: Statement, layout: &[Name]) -> Statement {
+    let boxed = |stmt: Box<Statement>| Box::new(local(*stmt, layout));
+    let exp = |e: Box<Expression>| Box::new(expression(*e, layout));
+
+    match stmt {
+        Statement::ConstDeclaration(name, kind, e) => Statement::ConstDeclaration(name, kind, exp(e)),
+        Statement::Assignment(name, e, kind) => Statement::Assignment(name, exp(e), kind),
+        Statement::AugmentedAssignment(name, operator, e) => Statement::AugmentedAssignment(name, operator, exp(e)),
+        Statement::Destructure(pattern, e) => Statement::Destructure(pattern, exp(e)),
+        Statement::IfThenElse(cond, then_stmt, else_stmt) => {
+            Statement::IfThenElse(exp(cond), boxed(then_stmt), else_stmt.map(boxed))
+        }
+        Statement::IfChain(branches, else_stmt) => Statement::IfChain(
+            branches
+                .into_iter()
+                .map(|(cond, stmt)| (expression(cond, layout), local(stmt, layout)))
+                .collect(),
+            else_stmt.map(boxed),
+        ),
+        Statement::While(cond, body) => Statement::While(exp(cond), boxed(body)),
+        Statement::Block(stmts) => Statement::Block(stmts.into_iter().map(|stmt| local(stmt, layout)).collect()),
+        Statement::Sequence(s1, s2) => Statement::Sequence(boxed(s1), boxed(s2)),
+        Statement::AssertTrue(e, msg) => Statement::AssertTrue(exp(e), msg),
+        Statement::AssertFalse(e, msg) => Statement::AssertFalse(exp(e), msg),
+        Statement::AssertEQ(lhs, rhs, msg) => Statement::AssertEQ(exp(lhs), exp(rhs), msg),
+        Statement::AssertNEQ(lhs, rhs, msg) => Statement::AssertNEQ(exp(lhs), exp(rhs), msg),
+        Statement::TestDef(func) => Statement::TestDef(function(func)),
+        Statement::ModTestDef(name, stmt) => Statement::ModTestDef(name, boxed(stmt)),
+        Statement::FuncDef(func) => Statement::FuncDef(function(func)),
+        Statement::Return(e) => Statement::Return(exp(e)),
+        Statement::Requires(e) => Statement::Requires(exp(e)),
+        Statement::Ensures(e) => Statement::Ensures(exp(e)),
+        Statement::Invariant(e) => Statement::Invariant(exp(e)),
+        Statement::Decreases(e) => Statement::Decreases(exp(e)),
+        Statement::Locals(layout) => Statement::Locals(layout),
+        Statement::Call(e) => Statement::Call(exp(e)),
+        Statement::Match(e, cases) => Statement::Match(
+            exp(e),
+            cases
+                .into_iter()
+                .map(|(pattern, guard, stmt)| (pattern, guard.map(|guard| expression(guard, layout)), boxed(stmt)))
+                .collect(),
+        ),
+        Statement::FuncClauses(name, kind, clauses) => Statement::FuncClauses(
+            name,
+            kind,
+            clauses
+                .into_iter()
+                .map(|clause| Clause {
+                    guard: clause.guard.map(|guard| expression(guard, layout)),
+                    value: expression(clause.value, layout),
+                    ..clause
+                })
+                .collect(),
+        ),
+        Statement::InstanceDeclaration(class, adt_name, methods) => Statement::InstanceDeclaration(
+            class,
+            adt_name,
+            methods.into_iter().map(function).collect(),
+        ),
+        Statement::At(position, stmt) => Statement::At(position, boxed(stmt)),
+        Statement::VarDeclaration(_)
+        | Statement::ValDeclaration(_)
+        | Statement::AssertFails(_)
+        | Statement::Import(_)
+        | Statement::Module(_)
+        | Statement::ADTDeclaration(..)
+        | Statement::FixityDeclaration(..) => stmt,
+    }
+}
+
+fn expression(exp: Expression, layout: &[Name]) -> Expression {
+    let boxed = |e: Box<Expression>| Box::new(expression(*e, layout));
+    let all = |exps: Vec<Expression>| exps.into_iter().map(|e| expression(e, layout)).collect::<Vec<_>>();
+
+    match exp {
+        Expression::CList(items) => Expression::CList(all(items)),
+        Expression::CTuple(items) => Expression::CTuple(all(items)),
+        Expression::CDict(entries) => Expression::CDict(
+            entries
+                .into_iter()
+                .map(|(k, v)| (expression(k, layout), expression(v, layout)))
+                .collect(),
+        ),
+        Expression::Var(name) => match layout.iter().position(|local| *local == name) {
+            Some(slot) => Expression::Slot(slot, name),
+            None => Expression::Var(name),
+        },
+        Expression::FuncCall(name, args) => Expression::FuncCall(name, all(args)),
+        Expression::CachedCall(site, args) => Expression::CachedCall(site, all(args)),
+        Expression::Pipe(value, name, args) => Expression::Pipe(boxed(value), name, all(args)),
+        Expression::Infix(first, rest) => Expression::Infix(
+            boxed(first),
+            rest.into_iter()
+                .map(|(op, operand)| (op, expression(operand, layout)))
+                .collect(),
+        ),
+        Expression::Add(l, r) => Expression::Add(boxed(l), boxed(r)),
+        Expression::Sub(l, r) => Expression::Sub(boxed(l), boxed(r)),
+        Expression::Mul(l, r) => Expression::Mul(boxed(l), boxed(r)),
+        Expression::Div(l, r) => Expression::Div(boxed(l), boxed(r)),
+        Expression::And(l, r) => Expression::And(boxed(l), boxed(r)),
+        Expression::Or(l, r) => Expression::Or(boxed(l), boxed(r)),
+        Expression::Not(e) => Expression::Not(boxed(e)),
+        Expression::EQ(l, r) => Expression::EQ(boxed(l), boxed(r)),
+        Expression::GT(l, r) => Expression::GT(boxed(l), boxed(r)),
+        Expression::LT(l, r) => Expression::LT(boxed(l), boxed(r)),
+        Expression::GTE(l, r) => Expression::GTE(boxed(l), boxed(r)),
+        Expression::LTE(l, r) => Expression::LTE(boxed(l), boxed(r)),
+        Expression::COk(e) => Expression::COk(boxed(e)),
+        Expression::CErr(e) => Expression::CErr(boxed(e)),
+        Expression::CJust(e) => Expression::CJust(boxed(e)),
+        Expression::Unwrap(e) => Expression::Unwrap(boxed(e)),
+        Expression::IsError(e) => Expression::IsError(boxed(e)),
+        Expression::IsNothing(e) => Expression::IsNothing(boxed(e)),
+        Expression::Propagate(e) => Expression::Propagate(boxed(e)),
+        Expression::ADTConstructor(adt_name, constructor_name, args) => {
+            Expression::ADTConstructor(adt_name, constructor_name, args.into_iter().map(boxed).collect())
+        }
+        Expression::CTrue
+        | Expression::CFalse
+        | Expression::CInt(_)
+        | Expression::CReal(_)
+        | Expression::CDecimal(_)
+        | Expression::CComplex(_)
+        | Expression::CString(_)
+        | Expression::CBytes(_)
+        | Expression::CVoid
+        | Expression::CNothing
+        | Expression::Slot(..)
+        | Expression::ADTValue(..)
+        | Expression::Closure(..) => exp,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ir::ast::Variables;
+    use crate::tc::type_checker::CheckedProgram;
+
+    fn layout(names: &[&str]) -> Arc<Vec<Name>> {
+        Arc::new(names.iter().map(|name| name.to_string()).collect())
+    }
+
+    #[test]
+    fn each_function_numbers_its_own_locals() {
+        let source = "
+            def outer(k: TInteger) -> TInteger:
+                base = k * 3
+                def inner(x: TInteger) -> TInteger:
+                    return x + base
+                return inner(base)
+        ";
+        let program = CheckedProgram::check(crate::rpy!(source)).unwrap();
+        let Statement::FuncDef(outer) = &program.statements()[0] else {
+            panic!("expected the definition of 'outer'");
+        };
+        let Some(Statement::Block(body)) = outer.body.as_deref() else {
+            panic!("expected a block");
+        };
+        assert_eq!(body[0], Statement::Locals(layout(&["k", "base", "inner"])));
+        assert_eq!(
+            body[1],
+            Statement::Assignment(
+                "base".to_string(),
+                Box::new(Expression::Mul(
+                    Box::new(Expression::Slot(0, "k".to_string())),
+                    Box::new(Expression::CInt(3))
+                )),
+                None
+            )
+        );
+
+        // `inner` reads `base` by name, from what it captured
+        let Statement::FuncDef(inner) = &body[2] else {
+            panic!("expected the definition of 'inner'");
+        };
+        let Some(Statement::Block(body)) = inner.body.as_deref() else {
+            panic!("expected a block");
+        };
+        assert_eq!(body[0], Statement::Locals(layout(&["x"])));
+        assert_eq!(
+            body[1],
+            Statement::Return(Box::new(Expression::Add(
+                Box::new(Expression::Slot(0, "x".to_string())),
+                Box::new(Expression::Var("base".to_string()))
+            )))
+        );
+    }
+
+    #[test]
+    fn numbered_variables_are_still_found_by_name() {
+        let mut variables: Variables<i32> = Variables::new();
+        variables.number(layout(&["n", "total"]));
+        variables.insert("n".to_string(), 1);
+        variables.insert("f".to_string(), 2);
+        assert_eq!(variables.slot(0), Some(&1));
+        assert_eq!(variables.slot(1), None);
+        assert_eq!(variables.get("n"), Some(&1));
+        assert_eq!(variables.get("f"), Some(&2));
+
+        // Blocks unbind what they bound, by slot or by name
+        let mark = variables.mark();
+        variables.insert("total".to_string(), 3);
+        variables.insert("g".to_string(), 4);
+        variables.insert("n".to_string(), 5);
+        let names: Vec<_> = variables.iter().collect();
+        assert_eq!(
+            names,
+            vec![(&"f".to_string(), &2), (&"g".to_string(), &4), (&"n".to_string(), &5), (&"total".to_string(), &3)]
+        );
+        variables.unbind_since(&mark);
+        assert_eq!(variables.iter().collect::<Vec<_>>(), vec![(&"f".to_string(), &2), (&"n".to_string(), &5)]);
+    }
+}
@@ -0,0 +1,128 @@
+use crate::ir::ast::{Environment, Function, Pattern, Statement};
+
+/// Resolves the constructor of every constructor pattern to its tag, so
+/// that `Shape Circle r` becomes a `Tagged` pattern the interpreter matches
+/// by comparing tags, without looking the constructor up by name.
+///
+/// `env` is where the ADTs the patterns name are declared, as the checker
+/// leaves it; a pattern whose ADT or constructor it does not know stays as
+/// it is.
+pub fn resolve<T>(stmts: Vec<Statement>, env: &Environment<T>) -> Vec<Statement> {
+    stmts.into_iter().map(|stmt| statement(stmt, env)).collect()
+}
+
+pub fn statement<T>(stmt: Statement, env: &Environment<T>) -> Statement {
+    let boxed = |stmt: Box<Statement>| Box::new(statement(*stmt, env));
+
+    match stmt {
+        Statement::Destructure(p, e) => Statement::Destructure(pattern(p, env), e),
+        Statement::Match(e, arms) => Statement::Match(
+            e,
+            arms.into_iter()
+                .map(|(p, guard, stmt)| (pattern(p, env), guard, boxed(stmt)))
+                .collect(),
+        ),
+        Statement::At(position, stmt) => Statement::At(position, boxed(stmt)),
+        Statement::IfThenElse(cond, then_stmt, else_stmt) => {
+            Statement::IfThenElse(cond, boxed(then_stmt), else_stmt.map(boxed))
+        }
+        Statement::IfChain(branches, else_stmt) => Statement::IfChain(
+            branches
+                .into_iter()
+                .map(|(cond, stmt)| (cond, statement(stmt, env)))
+                .collect(),
+            else_stmt.map(boxed),
+        ),
+        Statement::While(cond, body) => Statement::While(cond, boxed(body)),
+        Statement::Block(stmts) => Statement::Block(resolve(stmts, env)),
+        Statement::Sequence(s1, s2) => Statement::Sequence(boxed(s1), boxed(s2)),
+        Statement::TestDef(func) => Statement::TestDef(function(func, env)),
+        Statement::ModTestDef(name, stmt) => Statement::ModTestDef(name, boxed(stmt)),
+        Statement::FuncDef(func) => Statement::FuncDef(function(func, env)),
+        Statement::InstanceDeclaration(class, adt_name, methods) => Statement::InstanceDeclaration(
+            class,
+            adt_name,
+            methods.into_iter().map(|method| function(method, env)).collect(),
+        ),
+        stmt => stmt,
+    }
+}
+
+fn pattern<T>(p: Pattern, env: &Environment<T>) -> Pattern {
+    let all = |patterns: Vec<Pattern>| patterns.into_iter().map(|p| pattern(p, env)).collect();
+
+    match p {
+        Pattern::Constructor(adt_name, constructor_name, args) => {
+            match env.constructor_tag(&adt_name, &constructor_name) {
+                Some(tag) => Pattern::Tagged(adt_name, constructor_name, tag, all(args)),
+                None => Pattern::Constructor(adt_name, constructor_name, all(args)),
+            }
+        }
+        Pattern::Tagged(adt_name, constructor_name, tag, args) => {
+            Pattern::Tagged(adt_name, constructor_name, tag, all(args))
+        }
+        Pattern::Tuple(items) => Pattern::Tuple(all(items)),
+        Pattern::List(items) => Pattern::List(all(items)),
+        Pattern::ListRest(items, rest) => Pattern::ListRest(all(items), rest),
+        Pattern::As(p, whole) => Pattern::As(Box::new(pattern(*p, env)), whole),
+        p => p,
+    }
+}
+
+fn function<T>(func: Function, env: &Environment<T>) -> Function {
+    Function {
+        body: func.body.map(|body| Box::new(statement(*body, env))),
+        ..func
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interpreter::interpreter::{eval, EnvValue};
+    use crate::interpreter::program::{execute_program, Stats};
+    use crate::ir::ast::Expression;
+    use crate::tc::type_checker::CheckedProgram;
+
+    #[test]
+    fn checked_patterns_carry_their_tags() {
+        let source = "
+            adt Shape = Circle Real | Rectangle Real Real
+            def width(s: Shape) -> TReal:
+                match s {
+                    Shape Rectangle w _ => return w,
+                    Shape Circle r => return r
+                }
+        ";
+        let program = CheckedProgram::check(crate::rpy!(source)).unwrap();
+        let Statement::FuncDef(func) = &program.statements()[1] else {
+            panic!("expected the definition of 'width'");
+        };
+        let Some(Statement::Block(body)) = func.body.as_deref() else {
+            panic!("expected a block");
+        };
+        let Statement::Match(_, arms) = &body[1] else {
+            panic!("expected a match");
+        };
+        let tags: Vec<_> = arms
+            .iter()
+            .map(|(pattern, _, _)| match pattern {
+                Pattern::Tagged(_, name, tag, _) => (name.as_str(), *tag),
+                other => panic!("unresolved pattern {:?}", other),
+            })
+            .collect();
+        assert_eq!(tags, vec![("Rectangle", 1), ("Circle", 0)]);
+
+        let mut env = Environment::new();
+        execute_program(&program, &mut env, &mut Stats::default()).unwrap();
+        let rectangle = Expression::ADTConstructor(
+            "Shape".to_string(),
+            "Rectangle".to_string(),
+            vec![Box::new(Expression::CReal(2.0)), Box::new(Expression::CReal(3.0))],
+        );
+        assert_eq!(
+            eval(Expression::FuncCall("width".to_string(), vec![rectangle]), &env),
+            Ok(EnvValue::Exp(Expression::CReal(2.0)))
+        );
+    }
+}
@@ -0,0 +1,23 @@
+mod api;
+
+pub use api::{
+    check, parse, run, Binding, Capability, Diagnostic, DiagnosticKind, Diagnostics, Engine, EngineBuilder, Program, Run, Value,
+};
+pub use interpreter::replay::{Event, Frame, Recording, Replay};
+pub use ir::ast::Type;
+pub use ir::levels::LanguageLevel;
+
+// The pipeline itself. It changes with the language, so embedders should
+// go through the items above, which keep their shape across releases.
+#[doc(hidden)]
+pub mod builtins;
+#[doc(hidden)]
+pub mod cli;
+#[doc(hidden)]
+pub mod interpreter;
+#[doc(hidden)]
+pub mod ir;
+#[doc(hidden)]
+pub mod parser;
+#[doc(hidden)]
+pub mod tc;
@@ -10,13 +10,18 @@ use std::collections::HashMap;
 use std::fs::File;
 use std::io::Write;*/
 
-pub mod interpreter;
-pub mod ir;
-pub mod parser;
-pub mod tc;
+use r_python::cli;
 
 fn main() {
-    println!("Hello, world!");
+    let args: Vec<String> = std::env::args().skip(1).collect();
+
+    match cli::run(&args) {
+        Ok(output) => print!("{}", output),
+        Err(message) => {
+            eprintln!("{}", message);
+            std::process::exit(1);
+        }
+    }
 }
 /*
 fn run_test(name: &str, program: &str) -> String {
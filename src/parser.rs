@@ -1 +1,2 @@
+pub mod loader;
 pub mod parser;
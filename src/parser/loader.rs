@@ -0,0 +1,373 @@
+use crate::ir::ast::{Name, Statement};
+use crate::ir::modules;
+use crate::parser::parser::parse_program;
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
+
+/// A parsed source file.
+#[derive(Debug, Clone)]
+pub struct Module {
+    pub name: Name,
+    pub path: PathBuf,
+    pub statements: Vec<Statement>,
+}
+
+/// Loads modules from a list of source roots, following their imports.
+/// The module `a.b` lives in `a/b.rpy` under the first root that has it.
+/// Each module is loaded once, and comes after the modules it imports.
+pub struct Loader {
+    roots: Vec<PathBuf>,
+    loaded: BTreeSet<Name>,
+    /// The modules being loaded, each imported by the one before it.
+    loading: Vec<Name>,
+    pub modules: Vec<Module>,
+}
+
+impl Loader {
+    pub fn new(roots: Vec<PathBuf>) -> Loader {
+        Loader {
+            roots,
+            loaded: BTreeSet::new(),
+            loading: vec![],
+            modules: vec![],
+        }
+    }
+
+    /// Loads the module `name` and everything it imports.
+    pub fn load(&mut self, name: &str) -> Result<(), String> {
+        if self.loaded.contains(name) {
+            return Ok(());
+        }
+        let path = self.resolve(name).ok_or_else(|| {
+            format!("[Import Error] cannot find module '{}' in {}.", name, self.searched())
+        })?;
+        self.load_file(name, &path)
+    }
+
+    /// Loads the file at `path` as the module `name`, and everything it
+    /// imports.
+    pub fn load_file(&mut self, name: &str, path: &Path) -> Result<(), String> {
+        self.loading.push(name.to_string());
+        let result = self.load_imports(name, path);
+        self.loading.pop();
+        self.loaded.insert(name.to_string());
+        result
+    }
+
+    fn load_imports(&mut self, name: &str, path: &Path) -> Result<(), String> {
+        let source = std::fs::read_to_string(path)
+            .map_err(|e| format!("cannot read '{}': {}", path.display(), e))?;
+        let statements =
+            parse_program(&source).map_err(|e| format!("in '{}': {}", path.display(), e))?;
+
+        for import in imports(&statements) {
+            if let Some(start) = self.loading.iter().position(|module| module == import) {
+                let mut cycle = self.loading[start..].to_vec();
+                cycle.push(import.clone());
+                return Err(format!("[Import Error] import cycle: {}.", cycle.join(" -> ")));
+            }
+            if self.loaded.contains(import) {
+                continue;
+            }
+            let path = self.resolve(import).ok_or_else(|| {
+                format!(
+                    "[Import Error] cannot find module '{}' imported by '{}' in {}.",
+                    import,
+                    name,
+                    self.searched()
+                )
+            })?;
+            self.load_file(import, &path)?;
+        }
+
+        self.modules.push(Module {
+            name: name.to_string(),
+            path: path.to_path_buf(),
+            statements,
+        });
+        Ok(())
+    }
+
+    /// The statements of every loaded module, in load order.
+    pub fn program(&self) -> Result<Vec<Statement>, String> {
+        Ok(self
+            .linked()?
+            .into_iter()
+            .flat_map(|module| module.statements)
+            .collect())
+    }
+
+    /// The loaded modules, in load order, ready to run one after another.
+    /// The names of a module with a `module` header are qualified, and
+    /// other modules may only refer to them after importing it.
+    pub fn linked(&self) -> Result<Vec<Module>, String> {
+        let mut exported: BTreeMap<&Name, BTreeSet<Name>> = BTreeMap::new();
+        let mut linked = vec![];
+
+        for module in &self.modules {
+            let statements = match modules::header(&module.statements)? {
+                Some(declared) if declared != module.name => {
+                    return Err(format!(
+                        "[Import Error] '{}' declares module '{}' but is loaded as '{}'.",
+                        module.path.display(),
+                        declared,
+                        module.name
+                    ))
+                }
+                Some(_) => {
+                    let statements = modules::qualify(&module.name, module.statements.clone());
+                    exported.insert(&module.name, modules::definitions(&statements));
+                    statements
+                }
+                None => module.statements.clone(),
+            };
+
+            let imported = imports(&module.statements);
+            for reference in modules::qualified_references(&statements) {
+                let (prefix, name) = reference.rsplit_once('.').unwrap();
+                if prefix == module.name {
+                    continue;
+                }
+                match exported.get(&prefix.to_string()) {
+                    Some(_) if !imported.iter().any(|import| *import == prefix) => {
+                        return Err(format!(
+                            "[Name Error] '{}' is used in '{}' without 'import {}'.",
+                            reference, module.name, prefix
+                        ))
+                    }
+                    Some(names) if !names.contains(&reference) => {
+                        return Err(format!("[Name Error] module '{}' has no '{}'.", prefix, name))
+                    }
+                    Some(_) => {}
+                    None => {
+                        return Err(format!(
+                            "[Name Error] '{}' is used in '{}', but no loaded module declares 'module {}'.",
+                            reference, module.name, prefix
+                        ))
+                    }
+                }
+            }
+            linked.push(Module {
+                statements,
+                ..module.clone()
+            });
+        }
+        Ok(linked)
+    }
+
+    /// Warnings about the imports of each module that it never uses, and
+    /// about the names a module with a header exports that no module
+    /// importing it uses. A module nobody imports, as the entry one, is
+    /// not expected to export anything.
+    pub fn unused(&self) -> Result<Vec<String>, String> {
+        let linked = self.linked()?;
+        let references: Vec<BTreeSet<Name>> = linked
+            .iter()
+            .map(|module| modules::references(&module.statements))
+            .collect();
+        let mut warnings = vec![];
+
+        for (module, used) in linked.iter().zip(&references) {
+            for import in imports(&module.statements) {
+                let Some(imported) = linked.iter().find(|m| m.name == *import) else {
+                    continue;
+                };
+                let needed = match modules::header(&imported.statements)? {
+                    Some(_) => used.iter().any(|name| name.rsplit_once('.').is_some_and(|(prefix, _)| prefix == import)),
+                    None => modules::definitions(&imported.statements).iter().any(|name| used.contains(name)),
+                };
+                if !needed {
+                    warnings.push(format!("[Warning] in '{}': import '{}' is never used.", module.name, import));
+                }
+            }
+        }
+
+        for module in &linked {
+            let importers: Vec<usize> = (0..linked.len())
+                .filter(|&i| imports(&linked[i].statements).contains(&&module.name))
+                .collect();
+            if importers.is_empty() || modules::header(&module.statements)?.is_none() {
+                continue;
+            }
+            for name in modules::definitions(&module.statements) {
+                if !importers.iter().any(|&i| references[i].contains(&name)) {
+                    warnings.push(format!(
+                        "[Warning] in '{}': '{}' is never used outside module '{}'.",
+                        module.name, name, module.name
+                    ));
+                }
+            }
+        }
+        Ok(warnings)
+    }
+
+    fn resolve(&self, name: &str) -> Option<PathBuf> {
+        let relative = format!("{}.rpy", name.replace('.', "/"));
+        self.roots
+            .iter()
+            .map(|root| root.join(&relative))
+            .find(|path| path.is_file())
+    }
+
+    fn searched(&self) -> String {
+        self.roots
+            .iter()
+            .map(|root| format!("'{}'", root.display()))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
+
+fn imports(statements: &[Statement]) -> Vec<&Name> {
+    statements
+        .iter()
+        .filter_map(|stmt| match stmt {
+            Statement::Import(name) => Some(name),
+            _ => None,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn project(name: &str, files: &[(&str, &str)]) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("rpython-{}-{}", name, std::process::id()));
+        for (path, source) in files {
+            let path = dir.join(path);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, source).unwrap();
+        }
+        dir
+    }
+
+    #[test]
+    fn modules_come_after_their_imports() {
+        let dir = project(
+            "loader",
+            &[
+                ("src/main.rpy", "import geometry.area\nimport util\nx = 1"),
+                ("src/geometry/area.rpy", "import util\ny = 2"),
+                ("lib/util.rpy", "z = 3"),
+            ],
+        );
+        let mut loader = Loader::new(vec![dir.join("src"), dir.join("lib")]);
+        let loaded = loader.load("main");
+        let missing = loader.load("nowhere");
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(loaded, Ok(()));
+        let names: Vec<&str> = loader.modules.iter().map(|m| m.name.as_str()).collect();
+        assert_eq!(names, vec!["util", "geometry.area", "main"]);
+        assert_eq!(loader.program().map(|program| program.len()), Ok(6));
+
+        let missing = missing.unwrap_err();
+        assert!(missing.starts_with("[Import Error] cannot find module 'nowhere' in '"));
+    }
+
+    #[test]
+    fn qualified_names_need_an_import() {
+        let dir = project(
+            "qualified",
+            &[
+                ("shapes.rpy", "module shapes
+sides = 4
+def perimeter(side: TInteger) -> TInteger:
+    return side * sides"),
+                ("main.rpy", "import shapes
+p = shapes.perimeter(shapes.sides)"),
+                ("sneaky.rpy", "import main
+q = shapes.sides"),
+                ("typo.rpy", "import shapes
+q = shapes.side"),
+            ],
+        );
+        let load = |entry: &str| {
+            let mut loader = Loader::new(vec![dir.clone()]);
+            loader.load(entry)?;
+            loader.program()
+        };
+        let (main, sneaky, typo) = (load("main"), load("sneaky"), load("typo"));
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let main = main.unwrap();
+        assert_eq!(modules::definitions(&main), ["p", "shapes.perimeter", "shapes.sides"].map(String::from).into());
+        assert_eq!(
+            sneaky,
+            Err(String::from("[Name Error] 'shapes.sides' is used in 'sneaky' without 'import shapes'."))
+        );
+        assert_eq!(typo, Err(String::from("[Name Error] module 'shapes' has no 'side'.")));
+    }
+
+    #[test]
+    fn unused_imports_and_exports_are_reported() {
+        let dir = project(
+            "unused",
+            &[
+                ("shapes.rpy", "module shapes
+sides = 4
+def perimeter(side: TInteger) -> TInteger:
+    return side * sides
+def area(side: TInteger) -> TInteger:
+    return side * side"),
+                ("colors.rpy", "module colors
+red = 1"),
+                ("util.rpy", "def twice(n: TInteger) -> TInteger:
+    return n * 2"),
+                ("flat.rpy", "unit = 1"),
+                ("main.rpy", "import shapes
+import colors
+import util
+import flat
+def area(side: TInteger) -> TInteger:
+    return twice(side)
+p = shapes.perimeter(area(shapes.sides))"),
+            ],
+        );
+        let mut loader = Loader::new(vec![dir.clone()]);
+        let loaded = loader.load("main");
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(loaded, Ok(()));
+        assert_eq!(
+            loader.unused(),
+            Ok(vec![
+                String::from("[Warning] in 'main': import 'colors' is never used."),
+                String::from("[Warning] in 'main': import 'flat' is never used."),
+                String::from("[Warning] in 'shapes': 'shapes.area' is never used outside module 'shapes'."),
+                String::from("[Warning] in 'colors': 'colors.red' is never used outside module 'colors'."),
+            ])
+        );
+    }
+
+    #[test]
+    fn import_cycles_are_reported_with_their_path() {
+        let dir = project(
+            "cycles",
+            &[
+                ("narcissus.rpy", "import narcissus
+x = 1"),
+                ("main.rpy", "import a
+x = 1"),
+                ("a.rpy", "import b
+x = 1"),
+                ("b.rpy", "import util
+import c
+x = 1"),
+                ("c.rpy", "import a
+x = 1"),
+                ("util.rpy", "x = 1"),
+            ],
+        );
+        let load = |entry: &str| Loader::new(vec![dir.clone()]).load(entry);
+        let (narcissus, main) = (load("narcissus"), load("main"));
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(
+            narcissus,
+            Err(String::from("[Import Error] import cycle: narcissus -> narcissus."))
+        );
+        assert_eq!(main, Err(String::from("[Import Error] import cycle: a -> b -> c -> a.")));
+    }
+}
@@ -1,23 +1,27 @@
+use std::cell::RefCell;
+
 use nom::{
     branch::alt,
     bytes::complete::{tag, take_while, take_while1},
-    character::complete::{char, digit1, line_ending, space0, space1, multispace0},
-    combinator::{map, map_res, opt, recognize},
+    character::complete::{char, digit1, line_ending, one_of, satisfy, space0, space1, multispace0},
+    combinator::{map, map_opt, map_res, not, opt, recognize, verify},
     error::Error,
     multi::{many0, many1, separated_list0, separated_list1},
-    sequence::{delimited, pair, preceded, tuple},
+    sequence::{delimited, pair, preceded, terminated, tuple},
     IResult,
 };
 
 type ParseResult<'a, T> = IResult<&'a str, T, Error<&'a str>>;
 
-const KEYWORDS: &[&str] = &[
+pub const KEYWORDS: &[&str] = &[
     "if",
     "else",
+    "elif",
     "def",
     "while",
     "val",
     "var",
+    "const",
     "return",
     "Ok",
     "Err",
@@ -32,11 +36,26 @@ const KEYWORDS: &[&str] = &[
     "not",
     "True",
     "False",
+    "instance",
+    "where",
+    "requires",
+    "ensures",
+    "invariant",
+    "decreases",
+    "import",
+    "module",
+    "as",
 ];
 
 use crate::ir::ast::Function;
 use crate::ir::ast::Type;
-use crate::ir::ast::{Expression, Name, Statement, ValueConstructor};
+use crate::ir::ast::{
+    ArithmeticOperator, Associativity, Clause, Expression, Fixity, InfixOperator, Name, Pattern, Position,
+    Statement, ValueConstructor,
+};
+use crate::ir::desugar;
+use crate::ir::complex::Complex;
+use crate::ir::decimal::Decimal;
 
 fn identifier(input: &str) -> IResult<&str, Name> {
     let (input, id) = take_while1(|c: char| c.is_alphanumeric() || c == '_')(input)?;
@@ -98,23 +117,94 @@ fn term(input: &str) -> ParseResult<Expression> {
 //expression parser to include if statements
 fn statement(input: &str) -> IResult<&str, Statement> {
     let (input, _) = space0(input)?;
-    alt((
+    located(alt((
         function_def,
+        function_clauses,
         if_statement,
+        while_statement,
         return_statement,
+        contract_clause,
+        annotation_clause,
+        augmented_assignment,
+        fixity_declaration,
         assignment,
         declaration,
+        const_declaration,
         adt_declaration, // Add ADT declaration
         match_expression, // Add pattern matching
-    ))(input)
+        instance_declaration,
+        import_statement,
+        module_header,
+        destructure,
+        map(function_call, |call| Statement::Call(Box::new(call))),
+    )))(input)
 }
 
-// Parse basic expressions
+// Wrap the statement `parser` parses in the place it starts, when
+// `parse_located` is running
+fn located<'a>(
+    mut parser: impl FnMut(&'a str) -> IResult<&'a str, Statement>,
+) -> impl FnMut(&'a str) -> IResult<&'a str, Statement> {
+    move |input| {
+        let position = position(input);
+        let (input, stmt) = parser(input)?;
+        match position {
+            Some(position) => Ok((input, Statement::At(position, Box::new(stmt)))),
+            None => Ok((input, stmt)),
+        }
+    }
+}
+
+thread_local! {
+    // Where the source `parse_located` is parsing starts in memory and the
+    // offsets its lines start at. `None` unless it is running.
+    static LOCATING: RefCell<Option<(usize, Vec<usize>)>> = const { RefCell::new(None) };
+}
+
+// Where `input` starts in the source `parse_located` is parsing
+fn position(input: &str) -> Option<Position> {
+    LOCATING.with(|locating| {
+        let locating = locating.borrow();
+        let (start, lines) = locating.as_ref()?;
+        let offset = (input.as_ptr() as usize).checked_sub(*start)?;
+        let line = lines.partition_point(|line_start| *line_start <= offset);
+        Some(Position {
+            line,
+            column: offset - lines[line - 1] + 1,
+        })
+    })
+}
+
+// Parse expressions, including pipelines: `x |> f |> g(2)` is sugar for
+// `g(f(x), 2)`, so the piped value is always the first argument
 fn expression(input: &str) -> IResult<&str, Expression> {
+    let (input, first) = operand_expression(input)?;
+    let (input, stages) = many0(preceded(
+        delimited(space0, tag("|>"), space0),
+        alt((
+            function_call,
+            map(qualified_name, |name| Expression::FuncCall(name, vec![])),
+        )),
+    ))(input)?;
+
+    Ok((
+        input,
+        stages.into_iter().fold(first, |acc, stage| match stage {
+            Expression::FuncCall(name, args) => Expression::Pipe(Box::new(acc), name, args),
+            _ => unreachable!(),
+        }),
+    ))
+}
+
+// Parse basic expressions
+fn operand_expression(input: &str) -> IResult<&str, Expression> {
     alt((
+        tuple_expression,
         boolean_expression,
         comparison_expression,
         arithmetic_expression,
+        decimal,
+        imaginary,
         real,
         integer,
         ok_expression,
@@ -126,10 +216,22 @@ fn expression(input: &str) -> IResult<&str, Expression> {
         iserror_expression,
         isnothing_expression,
         string,
-        map(identifier, Expression::Var),
+        map(qualified_name, Expression::Var),
     ))(input)
 }
 
+// Parse a tuple such as `(1, "a")` or `(x,)`; the comma tells it from a
+// parenthesized expression
+fn tuple_expression(input: &str) -> IResult<&str, Expression> {
+    let comma = || tuple((space0, char(','), space0));
+    let (input, _) = pair(char('('), space0)(input)?;
+    let (input, first) = expression(input)?;
+    let (input, _) = comma()(input)?;
+    let (input, rest) = separated_list0(comma(), expression)(input)?;
+    let (input, _) = tuple((opt(comma()), space0, char(')')))(input)?;
+    Ok((input, Expression::CTuple(std::iter::once(first).chain(rest).collect())))
+}
+
 // Parse arithmetic operators (unused)
 //fn operator(input: &str) -> IResult<&str, &str> {
 //alt((tag("+"), tag("-"), tag("*"), tag("/")))(input)
@@ -162,37 +264,59 @@ fn comparison_expression(input: &str) -> IResult<&str, Expression> {
             ">=" => Expression::GTE(Box::new(left), Box::new(right)),
             "<=" => Expression::LTE(Box::new(left), Box::new(right)),
             "==" => Expression::EQ(Box::new(left), Box::new(right)),
+            "!=" => Expression::Not(Box::new(Expression::EQ(Box::new(left), Box::new(right)))),
             _ => unreachable!(),
         },
     ))
 }
 
-// Parse expressions with operator precedence
+// Parse a chain of arithmetic operators and functions applied infix, as
+// `a + b * c` or ``a `max` b + 1``. Without backticks the chain is grouped
+// here; with them it is left to `desugar`, which knows their fixities
 fn arithmetic_expression(input: &str) -> ParseResult<Expression> {
-    let (mut input, mut expr) = term(input)?;
+    let (mut input, first) = factor(input)?;
+    let mut rest = vec![];
 
-    loop {
-        let op_result = delimited::<_, _, _, _, Error<&str>, _, _, _>(
-            space0::<&str, Error<&str>>,
-            alt((tag("+"), tag("-"))),
-            space0::<&str, Error<&str>>,
-        )(input);
+    while let Ok((new_input, op)) = delimited(space0, infix_operator, space0)(input) {
+        let (newer_input, operand) = factor(new_input)?;
+        rest.push((op, operand));
+        input = newer_input;
+    }
 
-        match op_result {
-            Ok((new_input, op)) => {
-                let (newer_input, term2) = term(new_input)?;
-                expr = match op {
-                    "+" => Expression::Add(Box::new(expr), Box::new(term2)),
-                    "-" => Expression::Sub(Box::new(expr), Box::new(term2)),
-                    _ => unreachable!(),
-                };
-                input = newer_input;
-            }
-            Err(_) => break,
-        }
+    if rest.iter().any(|(op, _)| matches!(op, InfixOperator::Function(_))) {
+        return Ok((input, Expression::Infix(Box::new(first), rest)));
     }
+    Ok((input, desugar::infix(first, rest)))
+}
 
-    Ok((input, expr))
+fn infix_operator(input: &str) -> IResult<&str, InfixOperator> {
+    alt((
+        map(char('+'), |_| InfixOperator::Arithmetic(ArithmeticOperator::Add)),
+        map(char('-'), |_| InfixOperator::Arithmetic(ArithmeticOperator::Sub)),
+        map(char('*'), |_| InfixOperator::Arithmetic(ArithmeticOperator::Mul)),
+        map(char('/'), |_| InfixOperator::Arithmetic(ArithmeticOperator::Div)),
+        map(backticked, InfixOperator::Function),
+    ))(input)
+}
+
+// Parse a function name between backticks: `max`
+fn backticked(input: &str) -> IResult<&str, Name> {
+    delimited(char('`'), qualified_name, char('`'))(input)
+}
+
+// Parse a fixity declaration, as `infixl 6 max` or `infixr 5 `append``
+fn fixity_declaration(input: &str) -> IResult<&str, Statement> {
+    let (input, associativity) = alt((
+        map(tag("infixl"), |_| Associativity::Left),
+        map(tag("infixr"), |_| Associativity::Right),
+    ))(input)?;
+    let (input, precedence) = preceded(space1, one_of("0123456789"))(input)?;
+    let (input, name) = preceded(space1, alt((backticked, qualified_name)))(input)?;
+    let fixity = Fixity {
+        associativity,
+        precedence: precedence as u8 - b'0',
+    };
+    Ok((input, Statement::FixityDeclaration(name, fixity)))
 }
 
 // Add to imports
@@ -206,14 +330,47 @@ fn boolean(input: &str) -> IResult<&str, Expression> {
     ))(input)
 }
 
-// Parse real numbers
+// Parse exponents of real numbers: `e16`, `E-7`
+fn exponent(input: &str) -> IResult<&str, &str> {
+    recognize(tuple((one_of("eE"), opt(one_of("+-")), digit1)))(input)
+}
+
+// Parse real numbers, which have a fractional part, an exponent or both:
+// `3.14`, `1e16`, `-1.5e-7`
 fn real(input: &str) -> IResult<&str, Expression> {
     map_res(
-        recognize(tuple((opt(char('-')), digit1, char('.'), digit1))),
+        recognize(tuple((
+            opt(char('-')),
+            digit1,
+            alt((recognize(pair(pair(char('.'), digit1), opt(exponent))), exponent)),
+        ))),
         |num_str: &str| num_str.parse::<f64>().map(Expression::CReal),
     )(input)
 }
 
+// Parse exact decimals, written with a `d` suffix: `1.50d`, `-3d`
+fn decimal(input: &str) -> IResult<&str, Expression> {
+    map_opt(
+        terminated(
+            recognize(tuple((opt(char('-')), digit1, opt(pair(char('.'), digit1))))),
+            pair(char('d'), not(satisfy(|c| c.is_alphanumeric() || c == '_'))),
+        ),
+        |num_str: &str| Decimal::parse(num_str).map(Expression::CDecimal),
+    )(input)
+}
+
+// Parse imaginary numbers, written with a `j` suffix as in Python: `2j`,
+// `-0.5j`. Complex numbers are sums such as `1 + 2j`
+fn imaginary(input: &str) -> IResult<&str, Expression> {
+    map_res(
+        terminated(
+            recognize(tuple((opt(char('-')), digit1, opt(pair(char('.'), digit1)), opt(exponent)))),
+            pair(char('j'), not(satisfy(|c| c.is_alphanumeric() || c == '_'))),
+        ),
+        |num_str: &str| num_str.parse::<f64>().map(|im| Expression::CComplex(Complex::new(0.0, im))),
+    )(input)
+}
+
 // Parse strings
 fn string(input: &str) -> IResult<&str, Expression> {
     delimited(
@@ -225,6 +382,39 @@ fn string(input: &str) -> IResult<&str, Expression> {
     )(input)
 }
 
+// Parse bytes literals: b"..." with \\, \", \n, \t and \xNN escapes
+fn bytes_literal(input: &str) -> IResult<&str, Expression> {
+    let (mut input, _) = tag("b\"")(input)?;
+    let mut bytes = vec![];
+
+    loop {
+        let mut chars = input.chars();
+        match chars.next() {
+            Some('"') => return Ok((&input[1..], Expression::CBytes(bytes))),
+            Some('\\') => {
+                let (escaped, len) = match chars.next() {
+                    Some('\\') => (b'\\', 2),
+                    Some('"') => (b'"', 2),
+                    Some('n') => (b'\n', 2),
+                    Some('t') => (b'\t', 2),
+                    Some('x') => match input.get(2..4).map(|hex| u8::from_str_radix(hex, 16)) {
+                        Some(Ok(byte)) => (byte, 4),
+                        _ => return Err(nom::Err::Error(Error::new(input, nom::error::ErrorKind::Escaped))),
+                    },
+                    _ => return Err(nom::Err::Error(Error::new(input, nom::error::ErrorKind::Escaped))),
+                };
+                bytes.push(escaped);
+                input = &input[len..];
+            }
+            Some(c) if c.is_ascii() => {
+                bytes.push(c as u8);
+                input = &input[1..];
+            }
+            _ => return Err(nom::Err::Error(Error::new(input, nom::error::ErrorKind::Char))),
+        }
+    }
+}
+
 fn ok_expression(input: &str) -> IResult<&str, Expression> {
     let (input, _) = tag("Ok")(input)?;
     let (input, _) = space0(input)?;
@@ -342,8 +532,8 @@ fn boolean_term(input: &str) -> IResult<&str, Expression> {
 
 fn boolean_factor(input: &str) -> IResult<&str, Expression> {
     alt((
-        boolean,
         comparison_expression,
+        boolean,
         unwrap_expression,
         tryunwrap_expression,
         iserror_expression,
@@ -356,13 +546,28 @@ fn boolean_factor(input: &str) -> IResult<&str, Expression> {
     ))(input)
 }
 
+// Parse a factor, which a `?` after it unwraps as `tryUnwrap` does:
+// `half(n)?`
 fn factor(input: &str) -> IResult<&str, Expression> {
+    let (input, exp) = primary(input)?;
+    let (input, marks) = many0(char('?'))(input)?;
+    Ok((
+        input,
+        marks.into_iter().fold(exp, |exp, _| Expression::Propagate(Box::new(exp))),
+    ))
+}
+
+fn primary(input: &str) -> IResult<&str, Expression> {
     alt((
+        tuple_expression,
         delimited(
             tuple((char('('), space0)),
             arithmetic_expression,
             tuple((space0, char(')'))),
         ),
+        bytes_literal,
+        string,
+        boolean,
         function_call,
         ok_expression,
         err_expression,
@@ -372,31 +577,91 @@ fn factor(input: &str) -> IResult<&str, Expression> {
         tryunwrap_expression,
         iserror_expression,
         isnothing_expression,
+        decimal,
+        imaginary,
         real,
         integer,
         map(tuple((char('-'), space0, factor)), |(_, _, expr)| {
             Expression::Mul(Box::new(Expression::CInt(-1)), Box::new(expr))
         }),
-        map(identifier, Expression::Var),
+        map(qualified_name, Expression::Var),
     ))(input)
 }
 
-//indented block parser
+// Parse an indented block. Its statements are indented like the first one,
+// so a block nested in it ends at the first line indented less than its own
+// statements, and an `elif` or `else` in it belongs to the `if` it lines up with
 fn indented_block(input: &str) -> IResult<&str, Vec<Statement>> {
     let (input, _) = line_ending(input)?;
-    let (input, statements) = separated_list1(
+    let (_, indent) = space1(input)?; // Require at least one space for indentation
+    separated_list1(
         line_ending,
         preceded(
-            space1, // Require at least one space for indentation
-            statement,
+            tuple((tag(indent), not(space1))),
+            alt((located(if_at(Some(indent))), statement)),
         ),
-    )(input)?;
-    Ok((input, statements))
+    )(input)
 }
 
 fn if_statement(input: &str) -> IResult<&str, Statement> {
-    let (input, _) = tag("if")(input)?;
+    if_at(None)(input)
+}
+
+// Parse an `if` whose `elif` and `else` clauses are indented by `indent`,
+// or by anything at the top level, where the indentation is not known
+fn if_at<'a>(indent: Option<&'a str>) -> impl FnMut(&'a str) -> IResult<&'a str, Statement> {
+    move |input| {
+        let clause = |keyword: &'static str| {
+            move |input: &'a str| {
+                let (input, _) = line_ending(input)?;
+                let (input, _) = match indent {
+                    Some(indent) => tag(indent)(input)?,
+                    None => space0(input)?,
+                };
+                tag(keyword)(input)
+            }
+        };
+
+        let (input, _) = tag("if")(input)?;
+        let (input, _) = space1(input)?;
+        let (input, (condition, then_block)) = guarded_block(input)?;
+
+        let (input, elif_blocks) = many0(preceded(tuple((clause("elif"), space1)), guarded_block))(input)?;
+
+        let (input, else_block) = opt(preceded(tuple((clause("else"), char(':'))), indented_block))(input)?;
+        let else_block = else_block.map(|stmts| Box::new(Statement::Block(stmts)));
+
+        if elif_blocks.is_empty() {
+            return Ok((
+                input,
+                Statement::IfThenElse(
+                    Box::new(condition),
+                    Box::new(Statement::Block(then_block)),
+                    else_block,
+                ),
+            ));
+        }
+
+        let branches = std::iter::once((condition, then_block))
+            .chain(elif_blocks)
+            .map(|(condition, block)| (condition, Statement::Block(block)))
+            .collect();
+        Ok((input, Statement::IfChain(branches, else_block)))
+    }
+}
+
+fn while_statement(input: &str) -> IResult<&str, Statement> {
+    let (input, _) = tag("while")(input)?;
     let (input, _) = space1(input)?;
+    let (input, (condition, body)) = guarded_block(input)?;
+    Ok((
+        input,
+        Statement::While(Box::new(condition), Box::new(Statement::Block(body))),
+    ))
+}
+
+// Parse the condition and block of an `if`, `elif` or `while`
+fn guarded_block(input: &str) -> IResult<&str, (Expression, Vec<Statement>)> {
     let (input, condition) = alt((
         comparison_expression,
         boolean_expression,
@@ -404,21 +669,8 @@ fn if_statement(input: &str) -> IResult<&str, Statement> {
     ))(input)?;
     let (input, _) = space0(input)?;
     let (input, _) = char(':')(input)?;
-    let (input, then_block) = indented_block(input)?;
-
-    let (input, else_block) = opt(preceded(
-        tuple((line_ending, space0, tag("else"), char(':'))),
-        indented_block,
-    ))(input)?;
-
-    Ok((
-        input,
-        Statement::IfThenElse(
-            Box::new(condition),
-            Box::new(Statement::Block(then_block)),
-            else_block.map(|stmts| Box::new(Statement::Block(stmts))),
-        ),
-    ))
+    let (input, block) = indented_block(input)?;
+    Ok((input, (condition, block)))
 }
 
 fn declaration(input: &str) -> IResult<&str, Statement> {
@@ -436,6 +688,38 @@ fn declaration(input: &str) -> IResult<&str, Statement> {
     ))
 }
 
+// Parse a constant declaration: `const PI: TReal = 3.14159`
+fn const_declaration(input: &str) -> IResult<&str, Statement> {
+    let (input, _) = pair(tag("const"), space1)(input)?;
+    let (input, name) = identifier(input)?;
+    let (input, kind) = preceded(delimited(space0, char(':'), space0), function_type)(input)?;
+    let (input, _) = delimited(space0, char('='), space0)(input)?;
+    let (input, exp) = expression(input)?;
+
+    Ok((input, Statement::ConstDeclaration(name, kind, Box::new(exp))))
+}
+
+// Parse augmented assignments: `x += e`, `x -= e`, `x *= e` and `x /= e`
+fn augmented_assignment(input: &str) -> IResult<&str, Statement> {
+    let (input, name) = identifier(input)?;
+    let (input, operator) = delimited(
+        space0,
+        alt((
+            map(tag("+="), |_| ArithmeticOperator::Add),
+            map(tag("-="), |_| ArithmeticOperator::Sub),
+            map(tag("*="), |_| ArithmeticOperator::Mul),
+            map(tag("/="), |_| ArithmeticOperator::Div),
+        )),
+        space0,
+    )(input)?;
+    let (input, expr) = expression(input)?;
+
+    Ok((
+        input,
+        Statement::AugmentedAssignment(name, operator, Box::new(expr)),
+    ))
+}
+
 // Parse assignment statements
 fn assignment(input: &str) -> IResult<&str, Statement> {
     let (input, name) = identifier(input)?;
@@ -446,7 +730,10 @@ fn assignment(input: &str) -> IResult<&str, Statement> {
     let inferred_type = match &expr {
         Expression::CInt(_) => Some(Type::TInteger),
         Expression::CReal(_) => Some(Type::TReal),
+        Expression::CDecimal(_) => Some(Type::TDecimal),
+        Expression::CComplex(_) => Some(Type::TComplex),
         Expression::CString(_) => Some(Type::TString),
+        Expression::CBytes(_) => Some(Type::TBytes),
         Expression::CTrue | Expression::CFalse => Some(Type::TBool),
         _ => None,
     };
@@ -457,12 +744,105 @@ fn assignment(input: &str) -> IResult<&str, Statement> {
     ))
 }
 
+// Parse a destructuring assignment such as `Shape Circle r = s` or
+// `(a, b) = pair`; a lone variable is a plain assignment
+fn destructure(input: &str) -> IResult<&str, Statement> {
+    let (input, pattern) = verify(pattern, |pattern| {
+        matches!(
+            pattern,
+            Pattern::Constructor(..) | Pattern::Tuple(_) | Pattern::List(_) | Pattern::ListRest(..) | Pattern::As(..)
+        )
+    })(input)?;
+    let (input, _) = delimited(space0, terminated(char('='), not(char('='))), space0)(input)?;
+    let (input, exp) = expression(input)?;
+    Ok((input, Statement::Destructure(pattern, Box::new(exp))))
+}
+
 fn parse_type(type_name: &str) -> Type {
     match type_name {
         "TInteger" => Type::TInteger,
         "TBool" => Type::TBool,
         "TReal" => Type::TReal,
-        _ => Type::TInteger, // Default case
+        "TDecimal" => Type::TDecimal,
+        "TComplex" => Type::TComplex,
+        "TString" => Type::TString,
+        "TVoid" => Type::TVoid,
+        "TBytes" => Type::TBytes,
+        "TAny" => Type::TAny,
+        // Lowercase names are type variables, as in `def id(x: a) -> a`
+        _ if type_name.starts_with(|c: char| c.is_lowercase()) => {
+            Type::TVar(type_name.to_string(), vec![])
+        }
+        // Other names are ADTs, as in `def area(s: Shape) -> TReal`
+        _ => Type::Tadt(type_name.to_string(), vec![]),
+    }
+}
+
+// Parse a parameter or return type: a type name, a list of a type, `[a]`,
+// a tuple of types, `(TInteger, [a])`, or a maybe or result of types,
+// `TMaybe[TInteger]` and `TResult[TInteger, TString]`
+fn function_type(input: &str) -> IResult<&str, Type> {
+    let brackets = |input| {
+        delimited(
+            tuple((char('['), space0)),
+            separated_list1(tuple((space0, char(','), space0)), function_type),
+            tuple((space0, char(']'))),
+        )(input)
+    };
+    alt((
+        map_opt(preceded(tag("TMaybe"), brackets), |mut kinds| match kinds.len() {
+            1 => Some(Type::TMaybe(Box::new(kinds.remove(0)))),
+            _ => None,
+        }),
+        map_opt(preceded(tag("TResult"), brackets), |mut kinds| match kinds.len() {
+            2 => {
+                let error = kinds.remove(1);
+                Some(Type::TResult(Box::new(kinds.remove(0)), Box::new(error)))
+            }
+            _ => None,
+        }),
+        map(
+            delimited(tuple((char('['), space0)), function_type, tuple((space0, char(']')))),
+            |kind| Type::TList(Box::new(kind)),
+        ),
+        map(
+            delimited(
+                tuple((char('('), space0)),
+                separated_list1(tuple((space0, char(','), space0)), function_type),
+                tuple((space0, char(')'))),
+            ),
+            Type::TTuple,
+        ),
+        map(identifier, |name| parse_type(&name)),
+    ))(input)
+}
+
+// Parse a where clause: `where Ord a, Show b`
+fn where_clause(input: &str) -> IResult<&str, Vec<(Name, Name)>> {
+    preceded(
+        tuple((space1, tag("where"), space1)),
+        separated_list1(
+            delimited(space0, char(','), space0),
+            tuple((identifier, preceded(space1, identifier))),
+        ),
+    )(input)
+}
+
+// Attach the classes of a where clause to every occurrence of their
+// type variables
+fn constrain(kind: Type, constraints: &[(Name, Name)]) -> Type {
+    match kind {
+        Type::TVar(name, _) => {
+            let classes = constraints
+                .iter()
+                .filter(|(_, var)| *var == name)
+                .map(|(class, _)| class.clone())
+                .collect();
+            Type::TVar(name, classes)
+        }
+        Type::TList(t) => Type::TList(Box::new(constrain(*t, constraints))),
+        Type::TTuple(ts) => Type::TTuple(ts.into_iter().map(|t| constrain(t, constraints)).collect()),
+        other => other,
     }
 }
 
@@ -476,14 +856,16 @@ fn function_def(input: &str) -> IResult<&str, Statement> {
         delimited(space0, char(','), space0),
         tuple((
             identifier,
-            preceded(tuple((space0, char(':'), space0)), identifier),
+            preceded(tuple((space0, char(':'), space0)), function_type),
         )),
     )(input)?;
     let (input, _) = char(')')(input)?;
     let (input, _) = space0(input)?;
     let (input, _) = tag("->")(input)?;
     let (input, _) = space0(input)?;
-    let (input, return_type) = identifier(input)?;
+    let (input, return_type) = function_type(input)?;
+    let (input, constraints) = opt(where_clause)(input)?;
+    let constraints = constraints.unwrap_or_default();
     let (input, _) = char(':')(input)?;
     let (input, body) = indented_block(input)?;
 
@@ -491,11 +873,11 @@ fn function_def(input: &str) -> IResult<&str, Statement> {
         input,
         Statement::FuncDef(Function {
             name: name.clone(),                   // Provide the name field
-            kind: Some(parse_type(&return_type)), // Wrap in Some
+            kind: Some(constrain(return_type, &constraints)), // Wrap in Some
             params: Some(
                 params
                     .into_iter()
-                    .map(|(name, type_name)| (name, parse_type(&type_name)))
+                    .map(|(name, kind)| (name, constrain(kind, &constraints)))
                     .collect(),
             ),
             body: Some(Box::new(Statement::Block(body))), // Wrap in Some
@@ -503,12 +885,126 @@ fn function_def(input: &str) -> IResult<&str, Statement> {
     ))
 }
 
+// Parse a function defined by clauses, one per line, each returning a
+// value when the arguments match its patterns and its guard holds:
+//   def sign(n: TInteger) -> TInteger if n < 0 = 0 - 1
+//   def sign(0) = 0
+//   def sign(_) = 1
+// Only the first clause may give the return type. A clause of the same
+// function that takes another number of arguments, or does not parse, is
+// an error rather than the start of the next statement
+fn function_clauses(input: &str) -> IResult<&str, Statement> {
+    let (input, (name, params)) = clause_head(input)?;
+    let (input, kind) = opt(preceded(delimited(space0, tag("->"), space0), function_type))(input)?;
+    let (mut input, (guard, value)) = clause_body(input)?;
+    let arity = params.len();
+    let mut clauses = vec![Clause { params, guard, value }];
+
+    loop {
+        let next_line: IResult<&str, _> = pair(line_ending, space0)(input);
+        let Ok((start, _)) = next_line else {
+            break;
+        };
+        match clause_head(start) {
+            Ok((rest, (next, params))) if next == name => {
+                if params.len() != arity {
+                    return Err(nom::Err::Failure(Error::new(start, nom::error::ErrorKind::Verify)));
+                }
+                let (rest, (guard, value)) = clause_body(rest).map_err(|e| match e {
+                    nom::Err::Error(e) => nom::Err::Failure(e),
+                    e => e,
+                })?;
+                clauses.push(Clause { params, guard, value });
+                input = rest;
+            }
+            _ => break,
+        }
+    }
+    Ok((input, Statement::FuncClauses(name, kind, clauses)))
+}
+
+type ClauseParams = Vec<(Pattern, Option<Type>)>;
+
+// Parse `def name(pattern, n: TInteger, ...)`, the start of a clause
+fn clause_head(input: &str) -> IResult<&str, (Name, ClauseParams)> {
+    let (input, _) = tag("def")(input)?;
+    let (input, _) = space1(input)?;
+    let (input, name) = identifier(input)?;
+    let (input, params) = delimited(
+        pair(char('('), space0),
+        separated_list0(
+            delimited(space0, char(','), space0),
+            pair(pattern, opt(preceded(tuple((space0, char(':'), space0)), function_type))),
+        ),
+        pair(space0, char(')')),
+    )(input)?;
+    Ok((input, (name, params)))
+}
+
+// Parse the rest of a clause: an optional guard and `= value`
+fn clause_body(input: &str) -> IResult<&str, (Option<Expression>, Expression)> {
+    let (input, guard) = opt(preceded(tuple((space1, tag("if"), space1)), expression))(input)?;
+    let (input, _) = delimited(space0, terminated(char('='), not(char('='))), space0)(input)?;
+    let (input, value) = expression(input)?;
+    Ok((input, (guard, value)))
+}
+
 //return statement parsing
 fn return_statement(input: &str) -> IResult<&str, Statement> {
     let (input, _) = tag("return")(input)?;
+    if input.starts_with(|c: char| c.is_alphanumeric() || c == '_') {
+        return Err(nom::Err::Error(Error::new(input, nom::error::ErrorKind::Tag)));
+    }
+    // A bare `return` leaves a void function early
+    let (input, expr) = opt(preceded(space1, expression))(input)?;
+    Ok((input, Statement::Return(Box::new(expr.unwrap_or(Expression::CVoid)))))
+}
+
+// `requires cond` and `ensures cond` at the start of a function body
+fn contract_clause(input: &str) -> IResult<&str, Statement> {
+    let (input, keyword) = alt((tag("requires"), tag("ensures")))(input)?;
     let (input, _) = space1(input)?;
-    let (input, expr) = expression(input)?;
-    Ok((input, Statement::Return(Box::new(expr))))
+    let (input, condition) = alt((comparison_expression, boolean_expression, expression))(input)?;
+    let condition = Box::new(condition);
+    match keyword {
+        "requires" => Ok((input, Statement::Requires(condition))),
+        _ => Ok((input, Statement::Ensures(condition))),
+    }
+}
+
+// `import a.b` names the module in `a/b.rpy` under one of the source roots
+fn import_statement(input: &str) -> IResult<&str, Statement> {
+    let (input, _) = tag("import")(input)?;
+    let (input, _) = space1(input)?;
+    let (input, module) = qualified_name(input)?;
+    Ok((input, Statement::Import(module)))
+}
+
+// `module a.b` at the start of a file
+fn module_header(input: &str) -> IResult<&str, Statement> {
+    let (input, _) = tag("module")(input)?;
+    let (input, _) = space1(input)?;
+    let (input, module) = qualified_name(input)?;
+    Ok((input, Statement::Module(module)))
+}
+
+// A name, possibly qualified by a module: `area` or `geometry.area`
+fn qualified_name(input: &str) -> IResult<&str, Name> {
+    let (input, parts) = separated_list1(char('.'), identifier)(input)?;
+    Ok((input, parts.join(".")))
+}
+
+// `invariant cond` at the start of a while body, and `decreases measure`
+// at the start of a while or function body
+fn annotation_clause(input: &str) -> IResult<&str, Statement> {
+    let (input, keyword) = alt((tag("invariant"), tag("decreases")))(input)?;
+    let (input, _) = space1(input)?;
+    let (input, condition) = alt((comparison_expression, boolean_expression, expression))(input)?;
+    let condition = Box::new(condition);
+    match keyword {
+        "invariant" => Ok((input, Statement::Invariant(condition))),
+        _ => Ok((input, Statement::Decreases(condition))),
+    }
 }
 
 // Parse multiple statements
@@ -524,7 +1020,7 @@ pub fn parse_statements(input: &str) -> IResult<&str, Vec<Statement>> {
 
 // function call parsing
 fn function_call(input: &str) -> IResult<&str, Expression> {
-    let (input, name) = identifier(input)?;
+    let (input, name) = qualified_name(input)?;
     let (input, _) = char('(')(input)?;
     let (input, args) = separated_list0(delimited(space0, char(','), space0), expression)(input)?;
     let (input, _) = char(')')(input)?;
@@ -540,21 +1036,153 @@ pub fn parse(input: &str) -> IResult<&str, Vec<Statement>> {
     Ok((input, statements))
 }
 
+// Parse a whole program, reporting the line where parsing stopped when
+// some of the input is left over
+pub fn parse_program(source: &str) -> Result<Vec<Statement>, String> {
+    let line_of = |rest: &str| source[..source.len() - rest.len()].matches('\n').count() + 1;
+
+    match parse(source) {
+        Ok(("", statements)) => Ok(statements),
+        Ok((rest, _)) => Err(format!(
+            "[Syntax Error] unexpected input on line {}: '{}'.",
+            line_of(rest),
+            rest.lines().next().unwrap_or("").trim()
+        )),
+        Err(nom::Err::Error(e)) | Err(nom::Err::Failure(e)) => Err(format!(
+            "[Syntax Error] unexpected input on line {}: '{}'.",
+            line_of(e.input),
+            e.input.lines().next().unwrap_or("").trim()
+        )),
+        Err(nom::Err::Incomplete(_)) => Err(String::from("[Syntax Error] unexpected end of input.")),
+    }
+}
+
+/// Like `parse_program`, but every statement, nested ones included, comes
+/// wrapped in `Statement::At` with the place it starts, for the analyses
+/// that report where the code they flag is. The checker and the interpreter
+/// take the statements `parse_program` returns.
+pub fn parse_located(source: &str) -> Result<Vec<Statement>, String> {
+    let lines = std::iter::once(0)
+        .chain(source.match_indices('\n').map(|(i, _)| i + 1))
+        .collect();
+    let outer = LOCATING.with(|locating| locating.replace(Some((source.as_ptr() as usize, lines))));
+    let program = parse_program(source);
+    LOCATING.with(|locating| locating.replace(outer));
+    program
+}
+
+// Remove the indentation all lines of a snippet share, and the blank lines
+// around it, so programs can be written indented inside Rust code
+pub fn dedent(source: &str) -> String {
+    let indent = source
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| line.len() - line.trim_start().len())
+        .min()
+        .unwrap_or(0);
+    let lines: Vec<&str> = source.lines().map(|line| line.get(indent..).unwrap_or("")).collect();
+    lines.join("\n").trim_matches('\n').trim_end().to_string()
+}
+
+/// Parses and desugars a program written inline in Rust, such as a test
+/// case. The snippet may be indented as a whole:
+///
+/// ```ignore
+/// let program: Vec<Statement> = rpy!("
+///     x = 10
+///     while x > 0:
+///         x = x - 1
+/// ");
+/// let exp: Expression = rpy!(expr "x * 2");
+/// ```
+///
+/// A snippet that does not parse panics with the syntax error and the place
+/// of the macro call.
+#[macro_export]
+macro_rules! rpy {
+    (expr $source:expr) => {
+        match $crate::parser::parser::parse_expression(&$crate::parser::parser::dedent($source)) {
+            Ok(exp) => $crate::ir::desugar::expression(exp),
+            Err(e) => panic!("{} in rpy! at {}:{}", e, file!(), line!()),
+        }
+    };
+    ($source:expr) => {
+        match $crate::parser::parser::parse_program(&$crate::parser::parser::dedent($source)) {
+            Ok(program) => $crate::ir::desugar::program(program),
+            Err(e) => panic!("{} in rpy! at {}:{}", e, file!(), line!()),
+        }
+    };
+}
+
+// Parse a single expression, as typed at the REPL
+pub fn parse_expression(source: &str) -> Result<Expression, String> {
+    let source = source.trim();
+    match alt((comparison_expression, boolean_expression, expression))(source) {
+        Ok(("", exp)) => Ok(exp),
+        Ok((rest, _)) => Err(format!("[Syntax Error] unexpected input: '{}'.", rest.trim())),
+        Err(_) => Err(format!("[Syntax Error] not an expression: '{}'.", source)),
+    }
+}
+
+
+// Parse instance declarations: `instance Show Shape:` followed by the
+// method definitions
+fn instance_declaration(input: &str) -> IResult<&str, Statement> {
+    let (input, _) = tag("instance")(input)?;
+    let (input, class) = preceded(space1, identifier)(input)?;
+    let (input, adt_name) = preceded(space1, identifier)(input)?;
+    let (input, _) = tuple((space0, char(':')))(input)?;
+    let (rest, body) = indented_block(input)?;
+
+    let mut methods = vec![];
+    for stmt in body {
+        match stmt.unlocated() {
+            Statement::FuncDef(func) => methods.push(func.clone()),
+            _ => {
+                return Err(nom::Err::Error(Error {
+                    input,
+                    code: nom::error::ErrorKind::Verify,
+                }))
+            }
+        }
+    }
+
+    Ok((rest, Statement::InstanceDeclaration(class, adt_name, methods)))
+}
 
 fn adt_declaration(input: &str) -> IResult<&str, Statement> {
     let (input, _) = tag("adt")(input)?;
     let (input, _) = space1(input)?;
     let (input, name) = identifier(input)?;
-    let (input, _) = space0(input)?;
-    let (input, _) = char('=')(input)?;
-    let (input, _) = space0(input)?;
-    let (input, constructors) = separated_list1(
-        preceded(space0, char('|')), // Match `|`, allowing leading spaces
-        preceded(space0, value_constructor), // Consume extra spaces before each constructor
-    )(input)?;
-    
+    // Type parameters, as in `adt List a = Nil | Cons a List`
+    let (input, params) = many0(preceded(
+        space1,
+        verify(identifier, |param: &Name| param.starts_with(|c: char| c.is_lowercase())),
+    ))(input)?;
+    // An ADT without constructors, as `adt Never`, has no values
+    let (after, constructors) = opt(preceded(
+        tuple((space0, char('='), space0)),
+        separated_list1(
+            preceded(space0, char('|')), // Match `|`, allowing leading spaces
+            preceded(space0, value_constructor), // Consume extra spaces before each constructor
+        ),
+    ))(input)?;
+    let constructors = constructors.unwrap_or_default();
+
+    // Every type variable of a constructor must be a parameter, declared once
+    let undeclared = constructors
+        .iter()
+        .flat_map(|constructor| &constructor.types)
+        .any(|kind| matches!(kind, Type::TVar(var, _) if !params.contains(var)));
+    let repeated = params.iter().enumerate().any(|(i, param)| params[..i].contains(param));
+    if undeclared || repeated {
+        return Err(nom::Err::Error(Error {
+            input,
+            code: nom::error::ErrorKind::Verify,
+        }));
+    }
 
-    Ok((input, Statement::ADTDeclaration(name, constructors)))
+    Ok((after, Statement::ADTDeclaration(name, params, constructors)))
 }
 
 fn value_constructor(input: &str) -> IResult<&str, ValueConstructor> {
@@ -564,14 +1192,20 @@ fn value_constructor(input: &str) -> IResult<&str, ValueConstructor> {
     Ok((input, ValueConstructor { name, types }))
 }
 
+// Parse the type of a constructor field: a builtin type, a type parameter
+// or another ADT, the one being declared included
 fn type_annotation(input: &str) -> IResult<&str, Type> {
-    alt((
-        map(tag("Int"), |_| Type::TInteger),
-        map(tag("Bool"), |_| Type::TBool),
-        map(tag("Real"), |_| Type::TReal),
-        map(tag("String"), |_| Type::TString),
-        map(tag("Any"), |_| Type::TAny),
-    ))(input)
+    map(identifier, |name| match name.as_str() {
+        "Int" => Type::TInteger,
+        "Bool" => Type::TBool,
+        "Real" => Type::TReal,
+        "Decimal" => Type::TDecimal,
+        "Complex" => Type::TComplex,
+        "String" => Type::TString,
+        "Any" => Type::TAny,
+        _ if name.starts_with(|c: char| c.is_lowercase()) => Type::TVar(name, vec![]),
+        _ => Type::Tadt(name, vec![]),
+    })(input)
 }
 
 fn match_expression(input: &str) -> IResult<&str, Statement> {
@@ -595,12 +1229,13 @@ fn match_expression(input: &str) -> IResult<&str, Statement> {
     Ok((input, Statement::Match(Box::new(exp), cases)))
 }
 
-fn match_case(input: &str) -> IResult<&str, (Expression, Box<Statement>)> {
+fn match_case(input: &str) -> IResult<&str, (Pattern, Option<Expression>, Box<Statement>)> {
     //println!("Parsing match case: {}", input); // Debug print
     let (input, _) = multispace0(input)?; // Skip spaces & newlines
     //println!("After skipping spaces: {}", input); // Debug print
     let (input, pattern) = pattern(input)?; 
     //println!("Parsed pattern: {:?}", pattern); // Debug print
+    let (input, guard) = opt(preceded(tuple((space1, tag("if"), space1)), expression))(input)?; // Parse the guard, if any
     let (input, _) = space0(input)?; // Skip optional spaces
     //println!("After skipping spaces before =>: {}", input); // Debug print
     let (input, _) = tag("=>")(input)?; // Parse the "=>" operator
@@ -610,26 +1245,115 @@ fn match_case(input: &str) -> IResult<&str, (Expression, Box<Statement>)> {
     let (input, stmt) = statement(input)?; 
     //println!("Parsed statement: {:?}", stmt); // Debug print
 
-    Ok((input, (pattern, Box::new(stmt))))
+    Ok((input, (pattern, guard, Box::new(stmt))))
+}
+// Parse a match pattern: a constructor with its argument patterns, as in
+// `Shape Circle r` or `List Nil`, or any pattern allowed as an argument.
+// `x :: xs` matches a list whose first item is `x`, the rest of it bound
+// to `xs`, and is short for `[x, xs @ ..]`; `p as whole` also binds the
+// whole value `p` matches
+fn pattern(input: &str) -> IResult<&str, Pattern> {
+    let (input, pattern) = cons_pattern(input)?;
+    let (input, whole) = opt(preceded(tuple((space1, tag("as"), space1)), identifier))(input)?;
+    match whole {
+        Some(whole) => Ok((input, Pattern::As(Box::new(pattern), whole))),
+        None => Ok((input, pattern)),
+    }
+}
+
+fn cons_pattern(input: &str) -> IResult<&str, Pattern> {
+    let (input, first) = alt((adt_pattern, arg_pattern))(input)?;
+    let (rest_input, tail) = opt(preceded(delimited(space0, tag("::"), space0), cons_pattern))(input)?;
+    let cons = match tail {
+        None => return Ok((input, first)),
+        Some(Pattern::Variable(rest)) => Pattern::ListRest(vec![first], Some(rest)),
+        Some(Pattern::Wildcard) => Pattern::ListRest(vec![first], None),
+        Some(Pattern::ListRest(items, rest)) => Pattern::ListRest(std::iter::once(first).chain(items).collect(), rest),
+        Some(Pattern::List(items)) => Pattern::List(std::iter::once(first).chain(items).collect()),
+        Some(_) => return Ok((input, first)),
+    };
+    Ok((rest_input, cons))
 }
-fn pattern(input: &str) -> IResult<&str, Expression> {
+
+// Parse the patterns allowed as constructor arguments: `_`, literals,
+// `startswith "GET "` and `endswith ".py"`, variables, lists such as
+// `[x, _]` or, ending in the rest of the list, `[x, rest @ ..]` and
+// `[x, ..]`, and parenthesized patterns, which nest constructors as in
+// `(Tree Leaf n)` and make tuples as in `(a, b)`
+fn arg_pattern(input: &str) -> IResult<&str, Pattern> {
+    let word_end = || not(satisfy(|c: char| c.is_alphanumeric() || c == '_'));
+    let text = |keyword| {
+        map(
+            preceded(pair(tag(keyword), space1), delimited(char('"'), take_while(|c| c != '"'), char('"'))),
+            |text: &str| text.to_string(),
+        )
+    };
+    let items = |open, close| {
+        delimited(
+            pair(char(open), space0),
+            separated_list0(tuple((space0, char(','), space0)), pattern),
+            pair(space0, char(close)),
+        )
+    };
     alt((
-        adt_pattern, // Handle ADT patterns first (e.g., "Circle r")
-        map(identifier, Expression::Var), // Fallback to variables
+        map(terminated(char('_'), word_end()), |_| Pattern::Wildcard),
+        map(
+            terminated(
+                alt((decimal, real, integer, boolean, string, map(tag("Nothing"), |_| Expression::CNothing))),
+                word_end(),
+            ),
+            Pattern::Literal,
+        ),
+        map(text("startswith"), Pattern::StartsWith),
+        map(text("endswith"), Pattern::EndsWith),
+        map(identifier, Pattern::Variable),
+        list_pattern,
+        map(items('(', ')'), |mut patterns| match patterns.len() {
+            1 => patterns.remove(0),
+            _ => Pattern::Tuple(patterns),
+        }),
     ))(input)
 }
 
-fn arg_pattern(input: &str) -> IResult<&str, Expression> {
-    map(identifier, Expression::Var)(input) // Only parse variables
+fn list_pattern(input: &str) -> IResult<&str, Pattern> {
+    let rest = alt((
+        map(terminated(identifier, tuple((space0, char('@'), space0, tag("..")))), Some),
+        map(tag(".."), |_| None),
+    ));
+    let (input, _) = pair(char('['), space0)(input)?;
+    let (input, elements) = separated_list0(
+        tuple((space0, char(','), space0)),
+        alt((map(rest, Err), map(pattern, Ok))),
+    )(input)?;
+    let (input, _) = pair(space0, char(']'))(input)?;
+
+    // Only the last element may stand for the rest of the list
+    let (rest, items) = match elements.split_last() {
+        Some((Err(rest), items)) => (Some(rest.clone()), items),
+        _ => (None, elements.as_slice()),
+    };
+    let Ok(items) = items.iter().cloned().collect::<Result<Vec<Pattern>, _>>() else {
+        return Err(nom::Err::Error(Error {
+            input,
+            code: nom::error::ErrorKind::Verify,
+        }));
+    };
+    Ok((
+        input,
+        match rest {
+            Some(rest) => Pattern::ListRest(items, rest),
+            None => Pattern::List(items),
+        },
+    ))
 }
 
-fn adt_pattern(input: &str) -> IResult<&str, Expression> {
-    let (input, adt_name) = identifier(input)?; // Parse the ADT name 
+fn adt_pattern(input: &str) -> IResult<&str, Pattern> {
+    let (input, adt_name) = qualified_name(input)?; // Parse the ADT name, possibly qualified
     let (input, _) = space0(input)?; // Skip optional spaces
     let (input, constructor_name) = identifier(input)?; // Parse the constructor name 
-    let (input, args) = many1(preceded(space1, arg_pattern))(input)?; // Parse the arguments 
+    let (input, args) = many0(preceded(space1, arg_pattern))(input)?; // Parse the arguments, if any
 
-    Ok((input, Expression::ADTConstructor(adt_name, constructor_name, args.into_iter().map(Box::new).collect())))
+    Ok((input, Pattern::Constructor(adt_name, constructor_name, args)))
 }
 
 
@@ -1067,6 +1791,9 @@ mod tests {
             ("3.14", Expression::CReal(3.14)),
             ("-2.5", Expression::CReal(-2.5)),
             ("0.0", Expression::CReal(0.0)),
+            ("1e16", Expression::CReal(1e16)),
+            ("-1.5e-7", Expression::CReal(-1.5e-7)),
+            ("2.5E+3", Expression::CReal(2500.0)),
         ];
 
         for (input, expected) in cases {
@@ -1076,6 +1803,90 @@ mod tests {
         }
     }
 
+    #[test]
+    fn printed_reals_read_back_identically() {
+        let mut values = vec![
+            0.1,
+            -0.0,
+            1e16,
+            1.5e-7,
+            123456789.125,
+            f64::MAX,
+            f64::MIN_POSITIVE,
+            5e-324,
+            std::f64::consts::PI,
+        ];
+        // Reals with arbitrary bits, from a fixed xorshift sequence
+        let mut state: u64 = 0x9E37_79B9_7F4A_7C15;
+        while values.len() < 10_000 {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            let value = f64::from_bits(state);
+            if value.is_finite() {
+                values.push(value);
+            }
+        }
+
+        for value in values {
+            let printed = crate::ir::pretty::real(value);
+            match real(&printed) {
+                Ok(("", Expression::CReal(parsed))) => {
+                    assert_eq!(parsed.to_bits(), value.to_bits(), "{} read back as {:?}", printed, parsed)
+                }
+                other => panic!("{} did not read back: {:?}", printed, other),
+            }
+            let imaginary_part = match imaginary(&format!("{}j", printed)) {
+                Ok(("", Expression::CComplex(z))) => z.im,
+                other => panic!("{}j did not read back: {:?}", printed, other),
+            };
+            assert_eq!(imaginary_part.to_bits(), value.to_bits());
+        }
+    }
+
+    #[test]
+    fn test_decimal_numbers() {
+        for (input, expected) in [("1.50d", "1.50"), ("-3d", "-3"), ("0.05d", "0.05")] {
+            let (rest, result) = decimal(input).unwrap();
+            assert_eq!(rest, "");
+            assert_eq!(result, Expression::CDecimal(Decimal::parse(expected).unwrap()));
+            assert_eq!(crate::ir::pretty::expression(&result), input);
+        }
+        assert!(decimal("3days").is_err());
+        assert_eq!(
+            parse_program("price = 1.50d * 2"),
+            Ok(vec![Statement::Assignment(
+                "price".to_string(),
+                Box::new(Expression::Mul(
+                    Box::new(Expression::CDecimal(Decimal::parse("1.50").unwrap())),
+                    Box::new(Expression::CInt(2))
+                )),
+                None
+            )])
+        );
+    }
+
+    #[test]
+    fn test_imaginary_numbers() {
+        for (input, im) in [("2j", 2.0), ("-0.5j", -0.5)] {
+            let (rest, result) = imaginary(input).unwrap();
+            assert_eq!(rest, "");
+            assert_eq!(result, Expression::CComplex(Complex::new(0.0, im)));
+        }
+        assert!(imaginary("2just").is_err());
+        assert_eq!(
+            parse_program("z = 1 + 2j"),
+            Ok(vec![Statement::Assignment(
+                "z".to_string(),
+                Box::new(Expression::Add(
+                    Box::new(Expression::CInt(1)),
+                    Box::new(Expression::CComplex(Complex::new(0.0, 2.0)))
+                )),
+                None
+            )])
+        );
+    }
+
     #[test]
     fn test_string_literals() {
         let cases = vec![
@@ -1462,10 +2273,10 @@ mod tests {
         assert_eq!(remaining_input, ""); // Ensure the entire input is consumed
         assert_eq!(
             parsed_expr,
-            Expression::ADTConstructor(
+            Pattern::Constructor(
                 "Shape".to_string(), // ADT name
                 "Circle".to_string(), // Constructor name
-                vec![Box::new(Expression::Var("r".to_string()))] // Argument
+                vec![Pattern::Variable("r".to_string())] // Argument
             )
         );
     
@@ -1479,12 +2290,12 @@ mod tests {
         assert_eq!(remaining_input, ""); // Ensure the entire input is consumed
         assert_eq!(
             parsed_expr,
-            Expression::ADTConstructor(
+            Pattern::Constructor(
                 "Shape".to_string(), // ADT name
                 "Rectangle".to_string(), // Constructor name
                 vec![
-                    Box::new(Expression::Var("w".to_string())), // First argument
-                    Box::new(Expression::Var("h".to_string()))  // Second argument
+                    Pattern::Variable("w".to_string()), // First argument
+                    Pattern::Variable("h".to_string())  // Second argument
                 ]
             )
         );
@@ -1497,21 +2308,140 @@ mod tests {
         assert_eq!(remaining_input, ""); // Ensure the entire input is consumed
         assert_eq!(
             parsed_expr,
-            Expression::ADTConstructor(
+            Pattern::Constructor(
                 "Shape".to_string(), // ADT name
                 "Triangle".to_string(), // Constructor name
                 vec![
-                    Box::new(Expression::Var("b".to_string())), // First argument
-                    Box::new(Expression::Var("h".to_string())), // Second argument
-                    Box::new(Expression::Var("s".to_string()))  // Third argument
+                    Pattern::Variable("b".to_string()), // First argument
+                    Pattern::Variable("h".to_string()), // Second argument
+                    Pattern::Variable("s".to_string())  // Third argument
                 ]
             )
         );
     
-        // Test case 4: Invalid input (missing argument)
+        // Test case 4: a constructor without arguments
         let input = "Shape Circle";
         let result = adt_pattern(input);
-        assert!(result.is_err()); // Expect an error because the argument is missing
+        assert_eq!(result, Ok(("", Pattern::Constructor("Shape".to_string(), "Circle".to_string(), vec![]))));
+    }
+
+    #[test]
+    fn test_destructuring_assignments() {
+        let var = |name: &str| Pattern::Variable(name.to_string());
+        let program = parse_program("Shape Circle r = s\n(a, b) = pair\nx = 1\n").unwrap();
+        assert_eq!(
+            program,
+            vec![
+                Statement::Destructure(
+                    Pattern::Constructor("Shape".to_string(), "Circle".to_string(), vec![var("r")]),
+                    Box::new(Expression::Var("s".to_string()))
+                ),
+                Statement::Destructure(Pattern::Tuple(vec![var("a"), var("b")]), Box::new(Expression::Var("pair".to_string()))),
+                Statement::Assignment("x".to_string(), Box::new(Expression::CInt(1)), Some(Type::TInteger)),
+            ]
+        );
+        assert!(destructure("[a, b] == xs").is_err());
+    }
+
+    #[test]
+    fn test_tuple_expressions() {
+        let tuple = |items| Expression::CTuple(items);
+        assert_eq!(
+            expression("(1, \"a\", (x, y + 1))"),
+            Ok((
+                "",
+                tuple(vec![
+                    Expression::CInt(1),
+                    Expression::CString("a".to_string()),
+                    tuple(vec![
+                        Expression::Var("x".to_string()),
+                        Expression::Add(Box::new(Expression::Var("y".to_string())), Box::new(Expression::CInt(1)))
+                    ])
+                ])
+            ))
+        );
+        assert_eq!(expression("( x , )"), Ok(("", tuple(vec![Expression::Var("x".to_string())]))));
+        assert_eq!(expression("(x + 1)"), Ok(("", Expression::Add(Box::new(Expression::Var("x".to_string())), Box::new(Expression::CInt(1))))));
+    }
+
+    #[test]
+    fn test_match_guards() {
+        let (rest, (pattern, guard, _)) = match_case("Shape Circle r if r > 1.0 => return r").unwrap();
+        assert_eq!(rest, "");
+        assert_eq!(
+            (pattern, guard),
+            (
+                Pattern::Constructor("Shape".to_string(), "Circle".to_string(), vec![Pattern::Variable("r".to_string())]),
+                Some(Expression::GT(Box::new(Expression::Var("r".to_string())), Box::new(Expression::CReal(1.0))))
+            )
+        );
+        assert_eq!(match_case("x => return x").map(|(_, (_, guard, _))| guard), Ok(None));
+    }
+
+    #[test]
+    fn test_const_declarations() {
+        assert_eq!(
+            parse_program("const TAU: TReal = PI * 2.0\n").unwrap(),
+            vec![Statement::ConstDeclaration(
+                "TAU".to_string(),
+                Type::TReal,
+                Box::new(Expression::Mul(Box::new(Expression::Var("PI".to_string())), Box::new(Expression::CReal(2.0))))
+            )]
+        );
+        assert!(const_declaration("const N = 3").is_err());
+        assert!(assignment("const = 3").is_err());
+    }
+
+    #[test]
+    fn test_nested_patterns() {
+        let var = |name: &str| Pattern::Variable(name.to_string());
+        assert_eq!(
+            pattern("Tree Node (Tree Leaf n) _ 3"),
+            Ok((
+                "",
+                Pattern::Constructor(
+                    "Tree".to_string(),
+                    "Node".to_string(),
+                    vec![
+                        Pattern::Constructor("Tree".to_string(), "Leaf".to_string(), vec![var("n")]),
+                        Pattern::Wildcard,
+                        Pattern::Literal(Expression::CInt(3)),
+                    ]
+                )
+            ))
+        );
+        assert_eq!(pattern("[head, _]"), Ok(("", Pattern::List(vec![var("head"), Pattern::Wildcard]))));
+        assert_eq!(
+            pattern("(a, True)"),
+            Ok(("", Pattern::Tuple(vec![var("a"), Pattern::Literal(Expression::CTrue)])))
+        );
+        assert_eq!(pattern("_x"), Ok(("", var("_x"))));
+        let list = |name: &str, args| Pattern::Constructor("List".to_string(), name.to_string(), args);
+        assert_eq!(
+            pattern("List Cons x (List Cons y (List Nil))"),
+            Ok(("", list("Cons", vec![var("x"), list("Cons", vec![var("y"), list("Nil", vec![])])])))
+        );
+        assert_eq!(pattern("startswith \"GET \""), Ok(("", Pattern::StartsWith("GET ".to_string()))));
+        assert_eq!(pattern("endswith \".py\""), Ok(("", Pattern::EndsWith(".py".to_string()))));
+        assert_eq!(pattern("startswith"), Ok(("", var("startswith"))));
+        let rest = |items, rest: Option<&str>| Pattern::ListRest(items, rest.map(str::to_string));
+        assert_eq!(pattern("[x, y, rest @ ..]"), Ok(("", rest(vec![var("x"), var("y")], Some("rest")))));
+        assert_eq!(pattern("[_, ..]"), Ok(("", rest(vec![Pattern::Wildcard], None))));
+        assert_eq!(pattern("x :: y :: ys"), Ok(("", rest(vec![var("x"), var("y")], Some("ys")))));
+        assert_eq!(pattern("x :: [y]"), Ok(("", Pattern::List(vec![var("x"), var("y")]))));
+        assert_eq!(pattern("(x :: _)"), Ok(("", rest(vec![var("x")], None))));
+        assert!(pattern("[rest @ .., x]").is_err());
+
+        let whole = |pattern, name: &str| Pattern::As(Box::new(pattern), name.to_string());
+        assert_eq!(pattern("x :: xs as all"), Ok(("", whole(rest(vec![var("x")], Some("xs")), "all"))));
+        assert_eq!(
+            pattern("List Cons (List Nil as empty) rest"),
+            Ok(("", list("Cons", vec![whole(list("Nil", vec![]), "empty"), var("rest")])))
+        );
+        assert_eq!(
+            match_case("(a, b) as pair if a == b => return pair").map(|(_, (pattern, _, _))| pattern),
+            Ok(whole(Pattern::Tuple(vec![var("a"), var("b")]), "pair"))
+        );
     }
     
 
@@ -1544,6 +2474,7 @@ mod tests {
             adt,
             Statement::ADTDeclaration(
                 "FG".to_string(),
+                vec![],
                 vec![
                     ValueConstructor {
                         name: "Circle".to_string(),
@@ -1570,11 +2501,12 @@ mod tests {
                 Box::new(Expression::Var("shape".to_string())),
                 vec![
                     (
-                        Expression::ADTConstructor(
+                        Pattern::Constructor(
                             "FG".to_string(),
                             "Circle".to_string(),
-                            vec![Box::new(Expression::Var("r".to_string()))]
+                            vec![Pattern::Variable("r".to_string())]
                         ),
+                        None,
                         Box::new(Statement::Return(Box::new(Expression::Mul(
                             Box::new(Expression::Mul(
                                 Box::new(Expression::CReal(3.14)),
@@ -1585,14 +2517,15 @@ mod tests {
                     ),
                 ),
                     (
-                        Expression::ADTConstructor(
+                        Pattern::Constructor(
                             "FG".to_string(),
                             "Rectangle".to_string(),
                             vec![
-                                Box::new(Expression::Var("w".to_string())),
-                                Box::new(Expression::Var("h".to_string()))
+                                Pattern::Variable("w".to_string()),
+                                Pattern::Variable("h".to_string())
                             ]
                         ),
+                        None,
                         Box::new(Statement::Return(Box::new(Expression::Mul(
                             Box::new(Expression::Var("w".to_string())),
                             Box::new(Expression::Var("h".to_string()))
@@ -1600,15 +2533,16 @@ mod tests {
                     ),
                     ),
                     (
-                        Expression::ADTConstructor(
+                        Pattern::Constructor(
                             "FG".to_string(),
                             "Triangle".to_string(),
                             vec![
-                                Box::new(Expression::Var("b".to_string())),
-                                Box::new(Expression::Var("h".to_string())),
-                                Box::new(Expression::Var("s".to_string()))
+                                Pattern::Variable("b".to_string()),
+                                Pattern::Variable("h".to_string()),
+                                Pattern::Variable("s".to_string())
                             ]
                         ),
+                        None,
                         Box::new(Statement::Return(Box::new(Expression::Mul(
                             Box::new(Expression::Mul(
                             Box::new(Expression::CReal(0.5)),
@@ -1625,8 +2559,353 @@ mod tests {
     }
 
 
-}
 
 
+    #[test]
+    fn test_question_mark_propagation() {
+        let propagate = |exp: Expression| Expression::Propagate(Box::new(exp));
+        let call = Expression::FuncCall("half".to_string(), vec![Expression::Var("n".to_string())]);
+        assert_eq!(parse_expression("half(n)?"), Ok(propagate(call.clone())));
+        assert_eq!(
+            parse_expression("half(n)?? * 2"),
+            Ok(Expression::Mul(Box::new(propagate(propagate(call))), Box::new(Expression::CInt(2))))
+        );
+
+        let (_, stmts) = parse("def f(x: TMaybe[[TInteger]]) -> TResult[TInteger, TString]:\n    return Ok(1)").unwrap();
+        let Statement::FuncDef(func) = &stmts[0] else {
+            panic!("expected a function");
+        };
+        assert_eq!(func.params, Some(vec![("x".to_string(), Type::TMaybe(Box::new(Type::TList(Box::new(Type::TInteger)))))]));
+        assert_eq!(func.kind, Some(Type::TResult(Box::new(Type::TInteger), Box::new(Type::TString))));
+        assert!(parse_program("def f(x: TMaybe[TInteger, TString]) -> TInteger:\n    return 1").is_err());
+    }
+
+    #[test]
+    fn test_function_clauses() {
+        let source = "def sign(n: TInteger) -> TInteger if n < 0 = 0 - 1\ndef sign(0) = 0\n  def sign(_) = 1\nx = sign(2)";
+        let program = parse_program(source).unwrap();
+        let clause = |params, guard, value| Clause { params, guard, value };
+        assert_eq!(
+            program[0],
+            Statement::FuncClauses(
+                "sign".to_string(),
+                Some(Type::TInteger),
+                vec![
+                    clause(
+                        vec![(Pattern::Variable("n".to_string()), Some(Type::TInteger))],
+                        Some(Expression::LT(Box::new(Expression::Var("n".to_string())), Box::new(Expression::CInt(0)))),
+                        Expression::Sub(Box::new(Expression::CInt(0)), Box::new(Expression::CInt(1)))
+                    ),
+                    clause(vec![(Pattern::Literal(Expression::CInt(0)), None)], None, Expression::CInt(0)),
+                    clause(vec![(Pattern::Wildcard, None)], None, Expression::CInt(1)),
+                ]
+            )
+        );
+        assert_eq!(program.len(), 2);
+
+        // The clauses of a function take the same number of arguments
+        assert_eq!(
+            parse_program("def f(0) -> TInteger = 1\ndef f(a, b) = 2"),
+            Err(String::from("[Syntax Error] unexpected input on line 2: 'def f(a, b) = 2'."))
+        );
+        assert_eq!(
+            parse_program("def f(0) -> TInteger = 1\ndef f(1) -> TInteger = 2"),
+            Err(String::from("[Syntax Error] unexpected input on line 2: '-> TInteger = 2'."))
+        );
+    }
 
-    
\ No newline at end of file
+    #[test]
+    fn test_polymorphic_adt_declarations() {
+        let constructor = |name: &str, types| ValueConstructor { name: name.to_string(), types };
+        let a = Type::TVar("a".to_string(), vec![]);
+        assert_eq!(
+            adt_declaration("adt List a = Nil | Cons a List"),
+            Ok((
+                "",
+                Statement::ADTDeclaration(
+                    "List".to_string(),
+                    vec!["a".to_string()],
+                    vec![constructor("Nil", vec![]), constructor("Cons", vec![a.clone(), Type::Tadt("List".to_string(), vec![])])]
+                )
+            ))
+        );
+        assert_eq!(
+            adt_declaration("adt Pair a b = Pair a Int"),
+            Ok((
+                "",
+                Statement::ADTDeclaration(
+                    "Pair".to_string(),
+                    vec!["a".to_string(), "b".to_string()],
+                    vec![constructor("Pair", vec![a, Type::TInteger])]
+                )
+            ))
+        );
+        assert!(adt_declaration("adt Box = Full a").is_err());
+        assert!(adt_declaration("adt Pair a a = Pair a a").is_err());
+        assert_eq!(adt_declaration("adt Never"), Ok(("", Statement::ADTDeclaration("Never".to_string(), vec![], vec![]))));
+    }
+    #[test]
+    fn test_bytes_literal() {
+        let (rest, stmts) = parse("data = b\"Hi\\x00\\\"\"").unwrap();
+        assert_eq!(rest, "");
+        assert_eq!(
+            stmts[0],
+            Statement::Assignment(
+                "data".to_string(),
+                Box::new(Expression::CBytes(vec![b'H', b'i', 0, b'"'])),
+                Some(Type::TBytes)
+            )
+        );
+
+        assert!(bytes_literal("b\"\\xZZ\"").is_err());
+        assert!(bytes_literal("b\"olá\"").is_err());
+    }
+
+    #[test]
+    fn test_generic_function_with_where_clause() {
+        let input = "def larger(xs: [a], x: a) -> a where Ord a:\n    return x";
+        let (rest, stmts) = parse(input).unwrap();
+        assert_eq!(rest, "");
+
+        let ord_a = Type::TVar("a".to_string(), vec!["Ord".to_string()]);
+        match &stmts[0] {
+            Statement::FuncDef(func) => {
+                assert_eq!(func.kind, Some(ord_a.clone()));
+                assert_eq!(
+                    func.params,
+                    Some(vec![
+                        ("xs".to_string(), Type::TList(Box::new(ord_a.clone()))),
+                        ("x".to_string(), ord_a),
+                    ])
+                );
+            }
+            _ => panic!("Expected FuncDef"),
+        }
+    }
+
+    #[test]
+    fn test_instance_declaration() {
+        let input = "instance Show Shape:\n    def show(s: Shape) -> TString:\n        return \"shape\"";
+        let (rest, stmts) = parse(input).unwrap();
+        assert_eq!(rest, "");
+
+        match &stmts[0] {
+            Statement::InstanceDeclaration(class, adt_name, methods) => {
+                assert_eq!(class, "Show");
+                assert_eq!(adt_name, "Shape");
+                assert_eq!(methods.len(), 1);
+                assert_eq!(methods[0].name, "show");
+                assert_eq!(methods[0].kind, Some(Type::TString));
+            }
+            _ => panic!("Expected InstanceDeclaration"),
+        }
+    }
+
+    #[test]
+    fn test_void_function_with_bare_return() {
+        let input = "def log(x: TInteger) -> TVoid:\n    return\nreturned = 1";
+        let (rest, stmts) = parse(input).unwrap();
+        assert_eq!(rest, "");
+
+        match &stmts[0] {
+            Statement::FuncDef(func) => {
+                assert_eq!(func.kind, Some(Type::TVoid));
+                assert_eq!(
+                    func.body,
+                    Some(Box::new(Statement::Block(vec![Statement::Return(Box::new(
+                        Expression::CVoid
+                    ))])))
+                );
+            }
+            _ => panic!("Expected FuncDef"),
+        }
+        assert_eq!(
+            stmts[1],
+            Statement::Assignment(
+                "returned".to_string(),
+                Box::new(Expression::CInt(1)),
+                Some(Type::TInteger)
+            )
+        );
+    }
+
+    #[test]
+    fn test_elif_and_augmented_assignment() {
+        let input = "if x > 1:\n    y += 1\nelif x > 0:\n    y *= 2\nelse:\n    y = 0";
+        let (rest, stmts) = parse(input).unwrap();
+        assert_eq!(rest, "");
+
+        let gt = |n: i32| {
+            Expression::GT(
+                Box::new(Expression::Var("x".to_string())),
+                Box::new(Expression::CInt(n)),
+            )
+        };
+        let update = |operator: ArithmeticOperator, n: i32| {
+            Statement::Block(vec![Statement::AugmentedAssignment(
+                "y".to_string(),
+                operator,
+                Box::new(Expression::CInt(n)),
+            )])
+        };
+        assert_eq!(
+            stmts[0],
+            Statement::IfChain(
+                vec![
+                    (gt(1), update(ArithmeticOperator::Add, 1)),
+                    (gt(0), update(ArithmeticOperator::Mul, 2)),
+                ],
+                Some(Box::new(Statement::Block(vec![Statement::Assignment(
+                    "y".to_string(),
+                    Box::new(Expression::CInt(0)),
+                    Some(Type::TInteger)
+                )])))
+            )
+        );
+    }
+
+    #[test]
+    fn test_pipe_operator() {
+        let (rest, stmts) = parse("y = x |> double |> add(2)").unwrap();
+        assert_eq!(rest, "");
+        assert_eq!(
+            crate::ir::desugar::statement(stmts[0].clone()),
+            Statement::Assignment(
+                "y".to_string(),
+                Box::new(Expression::FuncCall(
+                    "add".to_string(),
+                    vec![
+                        Expression::FuncCall(
+                            "double".to_string(),
+                            vec![Expression::Var("x".to_string())]
+                        ),
+                        Expression::CInt(2),
+                    ]
+                )),
+                None
+            )
+        );
+    }
+
+    #[test]
+    fn test_infix_function_calls() {
+        let var = |name: &str| Expression::Var(name.to_string());
+        let (rest, stmts) = parse("infixr 5 `append`\ny = a `max` b * 2").unwrap();
+        assert_eq!(rest, "");
+        assert_eq!(
+            stmts[0],
+            Statement::FixityDeclaration(
+                "append".to_string(),
+                Fixity {
+                    associativity: Associativity::Right,
+                    precedence: 5
+                }
+            )
+        );
+        assert_eq!(
+            stmts[1],
+            Statement::Assignment(
+                "y".to_string(),
+                Box::new(Expression::Infix(
+                    Box::new(var("a")),
+                    vec![
+                        (InfixOperator::Function("max".to_string()), var("b")),
+                        (InfixOperator::Arithmetic(ArithmeticOperator::Mul), Expression::CInt(2)),
+                    ]
+                )),
+                None
+            )
+        );
+
+        let (_, stmts) = parse("y = a - b * 2").unwrap();
+        assert_eq!(
+            stmts[0],
+            Statement::Assignment(
+                "y".to_string(),
+                Box::new(Expression::Sub(
+                    Box::new(var("a")),
+                    Box::new(Expression::Mul(Box::new(var("b")), Box::new(Expression::CInt(2))))
+                )),
+                None
+            )
+        );
+        assert!(parse_program("infixl 10 max").is_err());
+    }
+
+    #[test]
+    fn test_contract_clauses() {
+        let input = "def half(n: TInteger) -> TInteger:\n    requires n >= 0\n    ensures result * 2 <= n\n    return n / 2";
+        let (rest, stmts) = parse(input).unwrap();
+        assert_eq!(rest, "");
+
+        let Statement::FuncDef(func) = &stmts[0] else {
+            panic!("expected a function definition");
+        };
+        let (annotations, body) = func.contracts();
+        assert_eq!(
+            annotations.requires,
+            vec![Expression::GTE(
+                Box::new(Expression::Var("n".to_string())),
+                Box::new(Expression::CInt(0))
+            )]
+        );
+        assert_eq!(
+            annotations.ensures,
+            vec![Expression::LTE(
+                Box::new(Expression::Mul(
+                    Box::new(Expression::Var("result".to_string())),
+                    Box::new(Expression::CInt(2))
+                )),
+                Box::new(Expression::Var("n".to_string()))
+            )]
+        );
+        assert!(matches!(body, Statement::Block(stmts) if stmts.len() == 1));
+    }
+
+    #[test]
+    fn test_while_with_invariant() {
+        let (rest, stmts) = parse("while i < n:\n    invariant i <= n\n    i += 1").unwrap();
+        assert_eq!(rest, "");
+
+        let var = |name: &str| Box::new(Expression::Var(name.to_string()));
+        assert_eq!(
+            stmts[0],
+            Statement::While(
+                Box::new(Expression::LT(var("i"), var("n"))),
+                Box::new(Statement::Block(vec![
+                    Statement::Invariant(Box::new(Expression::LTE(var("i"), var("n")))),
+                    Statement::AugmentedAssignment(
+                        "i".to_string(),
+                        ArithmeticOperator::Add,
+                        Box::new(Expression::CInt(1))
+                    ),
+                ]))
+            )
+        );
+    }
+
+    #[test]
+    fn test_nested_blocks_end_at_their_indentation() {
+        use crate::ir::build::*;
+
+        let source = dedent("
+            if x > 0:
+                def double(n: TInteger) -> TInteger:
+                    return n * 2
+                if x > 1:
+                    y = double(x)
+                z = 1
+            else:
+                z = 2
+        ");
+        let double = func("double").param("n", Type::TInteger).returns(Type::TInteger).body(vec![ret(mul(var("n"), 2))]);
+        assert_eq!(
+            parse_program(&source),
+            Ok(vec![if_else(
+                gt(var("x"), 0),
+                vec![double, if_then(gt(var("x"), 1), vec![assign("y", call("double", [var("x")]))]), assign("z", 1)],
+                vec![assign("z", 2)],
+            )])
+        );
+    }
+}
@@ -0,0 +1,204 @@
+use std::cell::RefCell;
+
+use crate::ir::ast::{Statement, Type};
+use crate::ir::pretty;
+use crate::tc::type_checker::type_display;
+
+/// A judgement of the type checker: `subject` has type `result` (statements
+/// have none), derived from `premises`. `expected` is the type the context
+/// required, when it required one.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Step {
+    pub subject: String,
+    pub expected: Option<Type>,
+    pub result: Result<Option<Type>, String>,
+    pub premises: Vec<Step>,
+}
+
+thread_local! {
+    // The open steps, innermost last, under a root that collects the
+    // finished top-level steps. `None` unless `record` is running.
+    static OPEN: RefCell<Option<Vec<Step>>> = const { RefCell::new(None) };
+}
+
+/// Runs `check` and returns what it returns with the derivation of every
+/// expression and statement it checked.
+pub fn record<T>(check: impl FnOnce() -> T) -> (T, Vec<Step>) {
+    let root = Step {
+        subject: String::new(),
+        expected: None,
+        result: Ok(None),
+        premises: vec![],
+    };
+    let outer = OPEN.with(|open| open.borrow_mut().replace(vec![root]));
+    let result = check();
+    let steps = OPEN.with(|open| std::mem::replace(&mut *open.borrow_mut(), outer));
+    (result, steps.and_then(|mut open| open.pop()).map(|root| root.premises).unwrap_or_default())
+}
+
+pub fn recording() -> bool {
+    OPEN.with(|open| open.borrow().is_some())
+}
+
+pub fn enter(subject: String) {
+    OPEN.with(|open| {
+        if let Some(open) = open.borrow_mut().as_mut() {
+            open.push(Step {
+                subject,
+                expected: None,
+                result: Ok(None),
+                premises: vec![],
+            });
+        }
+    })
+}
+
+/// Closes the innermost open step with `result`.
+pub fn exit(result: Result<Option<Type>, String>) {
+    OPEN.with(|open| {
+        if let Some(open) = open.borrow_mut().as_mut() {
+            if open.len() > 1 {
+                let mut step = open.pop().unwrap();
+                step.result = result;
+                open.last_mut().unwrap().premises.push(step);
+            }
+        }
+    })
+}
+
+/// Records that the step that just finished was checked against `kind`.
+pub fn expect(kind: &Type) {
+    OPEN.with(|open| {
+        if let Some(step) = open
+            .borrow_mut()
+            .as_mut()
+            .and_then(|open| open.last_mut())
+            .and_then(|parent| parent.premises.last_mut())
+        {
+            step.expected = Some(kind.clone());
+        }
+    })
+}
+
+/// What a statement step is about: its first line, as written.
+pub fn subject(stmt: &Statement) -> String {
+    match stmt {
+        Statement::IfThenElse(cond, _, _) => format!("if {}", pretty::expression(cond)),
+        Statement::While(cond, _) => format!("while {}", pretty::expression(cond)),
+        Statement::Match(exp, _) => format!("match {}", pretty::expression(exp)),
+        Statement::Return(exp) => format!("return {}", pretty::expression(exp)),
+        _ => pretty::statement(stmt),
+    }
+}
+
+/// The derivation as an indented tree, premises under their conclusion:
+///
+/// ```text
+/// x = 1 + 2.5
+///   1 + 2.5 : TReal
+///     1 : TInteger
+///     2.5 : TReal
+/// ```
+///
+/// A failed step is marked with `!`, and the step where the error arose
+/// also shows it.
+pub fn render(steps: &[Step]) -> String {
+    let mut out = String::new();
+    for step in steps {
+        render_step(step, 0, &mut out);
+    }
+    out
+}
+
+fn render_step(step: &Step, depth: usize, out: &mut String) {
+    out.push_str(&"  ".repeat(depth));
+    out.push_str(&step.subject);
+    match &step.result {
+        Ok(Some(kind)) => out.push_str(&format!(" : {}", type_display(kind))),
+        Ok(None) => {}
+        Err(_) if step.premises.iter().any(|premise| premise.result.is_err()) => out.push_str(" !"),
+        Err(message) => {
+            let indented = message.replace('\n', &format!("\n{}", "  ".repeat(depth)));
+            out.push_str(&format!(" ! {}", indented))
+        }
+    }
+    if let Some(expected) = &step.expected {
+        out.push_str(&format!(" (expected {})", type_display(expected)));
+    }
+    out.push('\n');
+
+    for premise in &step.premises {
+        render_step(premise, depth + 1, out);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parser::{dedent, parse_program};
+    use crate::tc::type_checker::check_program;
+
+    fn derivation(source: &str) -> String {
+        let program = parse_program(&dedent(source)).unwrap();
+        render(&record(|| check_program(&program)).1)
+    }
+
+    #[test]
+    fn derivations_show_every_step() {
+        assert_eq!(
+            derivation(
+                "
+                def inc(n: TInteger) -> TInteger:
+                    return n + 1
+                x = inc(2) * 2.5
+                "
+            ),
+            "\
+def inc(...)
+  return n + 1
+    n + 1 : TInteger (expected TInteger)
+      n : TInteger
+      1 : TInteger
+x = inc(2) * 2.5
+  inc(2) * 2.5 : TReal
+    inc(2) : TInteger
+      2 : TInteger (expected TInteger)
+    2.5 : TReal
+"
+        );
+    }
+
+    #[test]
+    fn derivations_show_where_checking_failed() {
+        assert_eq!(
+            derivation(
+                "
+                def inc(n: TInteger) -> TInteger:
+                    return n + 1
+                x = 2.5
+                if x > 0:
+                    y = inc(x)
+                "
+            ),
+            "\
+def inc(...)
+  return n + 1
+    n + 1 : TInteger (expected TInteger)
+      n : TInteger
+      1 : TInteger
+x = 2.5
+  2.5 : TReal (expected TReal)
+if x > 0 !
+  x > 0 : TBool (expected TBool)
+    x : TReal
+    0 : TInteger
+  y = inc(x) !
+    inc(x) ! [Type Error on '__main__()'] 'inc()' has mismatched arguments: expected 'TInteger', found 'TReal'.
+      expected because of 'def inc(n: TInteger) -> TInteger'
+      found from 'x = 2.5'
+      x : TReal (expected TInteger)
+"
+        );
+        assert!(!recording());
+    }
+}
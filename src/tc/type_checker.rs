@@ -1,4 +1,17 @@
-use crate::ir::ast::{Environment, Expression, Name, Statement, Type};
+use std::collections::HashMap;
+
+use crate::builtins::registry;
+use crate::ir::ast::{
+    annotations, Environment, Expression, Function, Level, Name, Pattern, Statement, Strictness, Type, ValueConstructor,
+};
+use crate::ir::calls;
+use crate::ir::classes::{builtin_instance, lookup_class};
+use crate::ir::desugar;
+use crate::ir::inline;
+use crate::ir::pretty;
+use crate::ir::slots;
+use crate::ir::tags;
+use crate::tc::trace;
 
 type ErrorMessage = String;
 
@@ -8,13 +21,29 @@ pub enum ControlFlow {
 }
 
 pub fn check_exp(exp: Expression, env: &Environment<Type>) -> Result<Type, ErrorMessage> {
+    if !trace::recording() {
+        return check_exp_untraced(exp, env);
+    }
+    trace::enter(pretty::expression(&exp));
+    let result = check_exp_untraced(exp, env);
+    trace::exit(result.clone().map(Some));
+    result
+}
+
+fn check_exp_untraced(exp: Expression, env: &Environment<Type>) -> Result<Type, ErrorMessage> {
     match exp {
         Expression::CTrue => Ok(Type::TBool),
         Expression::CFalse => Ok(Type::TBool),
         Expression::CVoid => Ok(Type::TVoid),
         Expression::CInt(_) => Ok(Type::TInteger),
         Expression::CReal(_) => Ok(Type::TReal),
+        Expression::CDecimal(_) => Ok(Type::TDecimal),
+        Expression::CComplex(_) => Ok(Type::TComplex),
         Expression::CString(_) => Ok(Type::TString),
+        Expression::CBytes(_) => Ok(Type::TBytes),
+        Expression::CList(items) => check_list(items, env),
+        Expression::CTuple(items) => Ok(Type::TTuple(items.into_iter().map(|item| check_exp(item, env)).collect::<Result<_, _>>()?)),
+        Expression::CDict(entries) => check_dict(entries, env),
         Expression::Add(l, r) => check_bin_arithmetic_expression(*l, *r, env),
         Expression::Sub(l, r) => check_bin_arithmetic_expression(*l, *r, env),
         Expression::Mul(l, r) => check_bin_arithmetic_expression(*l, *r, env),
@@ -22,12 +51,12 @@ pub fn check_exp(exp: Expression, env: &Environment<Type>) -> Result<Type, Error
         Expression::And(l, r) => check_bin_boolean_expression(*l, *r, env),
         Expression::Or(l, r) => check_bin_boolean_expression(*l, *r, env),
         Expression::Not(e) => check_not_expression(*e, env),
-        Expression::EQ(l, r) => check_bin_relational_expression(*l, *r, env),
+        Expression::EQ(l, r) => check_eq_expression(*l, *r, env),
         Expression::GT(l, r) => check_bin_relational_expression(*l, *r, env),
         Expression::LT(l, r) => check_bin_relational_expression(*l, *r, env),
         Expression::GTE(l, r) => check_bin_relational_expression(*l, *r, env),
         Expression::LTE(l, r) => check_bin_relational_expression(*l, *r, env),
-        Expression::Var(name) => check_var_name(name, env, false),
+        Expression::Var(name) | Expression::Slot(_, name) => check_var_name(name, env, false),
 
         Expression::COk(e) => check_result_ok(*e, env),
         Expression::CErr(e) => check_result_err(*e, env),
@@ -38,37 +67,170 @@ pub fn check_exp(exp: Expression, env: &Environment<Type>) -> Result<Type, Error
         Expression::Unwrap(e) => check_unwrap_type(*e, env),
         Expression::Propagate(e) => check_propagate_type(*e, env),
         Expression::FuncCall(name, args) => check_func_call(name, args, env),
+        Expression::CachedCall(site, args) => check_func_call(site.name, args, env),
         Expression::ADTConstructor(adt_name,constructor_name,args ) => check_adt_constructor(adt_name,constructor_name, args, env),
+        Expression::ADTValue(value) => check_adt_constructor(value.adt, value.constructor, value.fields.into_vec(), env),
+        Expression::Closure(func, _) => Ok(Type::TFunction(
+            Box::new(func.kind.clone()),
+            func.params.clone().unwrap_or_default().into_iter().map(|(_, kind)| kind).collect(),
+        )),
+        Expression::Pipe(..) => Err(desugar::surface_error("Pipe")),
+        Expression::Infix(..) => Err(desugar::surface_error("Infix")),
         
         //_ => Err(String::from("not implemented yet")),
     }
 }
 
+/// A desugared program the type checker accepted. `check` is the only way
+/// to build one, so whatever takes a `CheckedProgram` runs checked code.
+#[derive(Debug, Clone)]
+pub struct CheckedProgram {
+    statements: Vec<Statement>,
+    types: Environment<Type>,
+    lints: Vec<String>,
+}
+
+impl CheckedProgram {
+    pub fn check(statements: Vec<Statement>) -> Result<CheckedProgram, ErrorMessage> {
+        CheckedProgram::check_with(statements, Strictness::default())
+    }
+
+    pub fn check_with(statements: Vec<Statement>, strictness: Strictness) -> Result<CheckedProgram, ErrorMessage> {
+        let (types, lints) = check_program_with(&statements, strictness)?;
+        let statements = calls::cache(slots::assign(tags::resolve(statements, &types)));
+        Ok(CheckedProgram { statements, types, lints })
+    }
+
+    pub fn statements(&self) -> &[Statement] {
+        &self.statements
+    }
+
+    /// The same program with the uses of its constants replaced by their
+    /// values, which the checker made safe by refusing to rebind them.
+    pub fn inline_constants(self) -> CheckedProgram {
+        CheckedProgram {
+            statements: inline::constants(self.statements),
+            ..self
+        }
+    }
+
+    /// The types of the top-level bindings.
+    pub fn types(&self) -> &Environment<Type> {
+        &self.types
+    }
+
+    /// The warnings the strictness settings asked for.
+    pub fn lints(&self) -> &[String] {
+        &self.lints
+    }
+}
+
+/// Checks the top-level statements of a desugared program in order and
+/// returns the environment they leave. Every statement is checked once;
+/// function bodies, nested definitions included, when they are defined.
+pub fn check_program(program: &[Statement]) -> Result<Environment<Type>, ErrorMessage> {
+    check_program_with(program, Strictness::default()).map(|(env, _)| env)
+}
+
+/// Like `check_program`, under `strictness`, and with the warnings it asks
+/// for in the order they were found.
+pub fn check_program_with(
+    program: &[Statement],
+    strictness: Strictness,
+) -> Result<(Environment<Type>, Vec<String>), ErrorMessage> {
+    let mut env: Environment<Type> = Environment::new();
+    env.strictness = strictness;
+    let lints = env.lints.clone();
+
+    for stmt in program {
+        match check_stmt(stmt.clone(), &env)? {
+            ControlFlow::Continue(new_env) => env = new_env,
+            ControlFlow::Return(_) => {
+                return Err(String::from("[Syntax Error] return statement outside function."))
+            }
+        }
+    }
+    Ok((env, lints.take()))
+}
+
 pub fn check_stmt(stmt: Statement, env: &Environment<Type>) -> Result<ControlFlow, ErrorMessage> {
+    // Sequences and blocks only group the steps of their statements
+    if !trace::recording() || matches!(stmt, Statement::Sequence(..) | Statement::Block(_)) {
+        return check_stmt_untraced(stmt, env);
+    }
+    trace::enter(trace::subject(&stmt));
+    let result = check_stmt_untraced(stmt, env);
+    trace::exit(result.as_ref().map(|_| None).map_err(Clone::clone));
+    result
+}
+
+fn check_stmt_untraced(stmt: Statement, env: &Environment<Type>) -> Result<ControlFlow, ErrorMessage> {
     let mut new_env = env.clone();
 
     match stmt {
         Statement::Assignment(name, exp, kind) => {
+            check_rebinding(&name, &new_env)?;
+            let mut origin = origin(&exp, &new_env);
+            origin.push(pretty::statement(&Statement::Assignment(name.clone(), exp.clone(), kind.clone())));
             let exp_type = check_exp(*exp, &new_env)?;
 
             if let Some(state_type) = kind {
+                trace::expect(&state_type);
                 if exp_type != state_type {
                     return Err(format!("[Type Error on '{}()'] '{}' has mismatched types: expected '{:?}', found '{:?}'.", new_env.scope_name(), name, state_type, exp_type));
                 }
-            } else {
-                let stated_type = check_var_name(name.clone(), &new_env, true)?;
-
+            } else if let Some(stated_type) = declared(&name, &new_env)? {
+                trace::expect(&stated_type);
                 if exp_type != stated_type {
-                    return Err(format!("[Type Error on '{}()'] '{}' has mismatched types: expected '{:?}', found '{:?}'.", new_env.scope_name(), name, stated_type, exp_type));
+                    return Err(format!(
+                        "[Type Error on '{}()'] '{}' has mismatched types: expected '{:?}', found '{:?}'.{}",
+                        new_env.scope_name(),
+                        name,
+                        stated_type,
+                        exp_type,
+                        slice(new_env.origin(&name), &origin)
+                    ));
                 }
+                // The first binding fixed the type
+                origin = new_env.origin(&name).cloned().unwrap_or(origin);
             }
 
-            new_env.insert_variable(name, exp_type);
+            new_env.insert_variable(name.clone(), exp_type);
+            new_env.insert_origin(name, origin);
 
             Ok(ControlFlow::Continue(new_env))
         }
+        Statement::Destructure(pattern, exp) => {
+            let span = pretty::statement(&Statement::Destructure(pattern.clone(), exp.clone()));
+            let kind = check_exp(*exp, &new_env)?;
+            let mut bindings = vec![];
+            check_pattern(&pattern, &kind, &new_env, &mut bindings)?;
+            check_irrefutable(&pattern, &kind, &new_env)?;
+
+            for (name, kind) in bindings {
+                check_rebinding(&name, &new_env)?;
+                let mut origin = vec![span.clone()];
+                if let Some(stated_type) = declared(&name, &new_env)? {
+                    if kind != stated_type {
+                        return Err(format!(
+                            "[Type Error on '{}()'] '{}' has mismatched types: expected '{:?}', found '{:?}'.",
+                            new_env.scope_name(),
+                            name,
+                            stated_type,
+                            kind
+                        ));
+                    }
+                    origin = new_env.origin(&name).cloned().unwrap_or(origin);
+                }
+                new_env.insert_variable(name.clone(), kind);
+                new_env.insert_origin(name, origin);
+            }
+            Ok(ControlFlow::Continue(new_env))
+        }
+        Statement::ConstDeclaration(name, kind, exp) => check_const_declaration(name, kind, *exp, new_env),
         Statement::IfThenElse(exp, stmt_then, option) => {
             let exp_type = check_exp(*exp, &new_env)?;
+            trace::expect(&Type::TBool);
 
             if exp_type != Type::TBool {
                 return Err(format!(
@@ -77,9 +239,9 @@ pub fn check_stmt(stmt: Statement, env: &Environment<Type>) -> Result<ControlFlo
                 ));
             }
 
-            let stmt_then_result = check_stmt(*stmt_then, &new_env)?;
+            let stmt_then_result = check_block(*stmt_then, &new_env)?;
             let stmt_else_result = match option {
-                Some(stmt_else) => check_stmt(*stmt_else, &new_env)?,
+                Some(stmt_else) => check_block(*stmt_else, &new_env)?,
                 None => return Ok(ControlFlow::Continue(new_env)),
             };
 
@@ -97,69 +259,173 @@ pub fn check_stmt(stmt: Statement, env: &Environment<Type>) -> Result<ControlFlo
             }
         }
         Statement::While(exp, stmt_while) => {
-            let exp_type = check_exp(*exp, &new_env)?;
+            // The body is checked once, against the bindings before the loop.
+            // Names first bound in an iteration go out of scope at its end
+            // and the others may not change type, so every iteration starts
+            // from those same bindings.
+            let (annotations, body) = annotations(&stmt_while);
+            if !annotations.requires.is_empty() || !annotations.ensures.is_empty() {
+                return Err(String::from(
+                    "[Syntax Error] contracts are only allowed at the start of a function body.",
+                ));
+            }
 
+            let exp_type = check_exp(*exp.clone(), &new_env)?;
+            trace::expect(&Type::TBool);
             if exp_type != Type::TBool {
                 return Err(format!(
                     "[Type Error on '{}()'] while expression must be boolean.",
                     new_env.scope_name()
                 ));
             }
+            check_measure(&annotations.decreases, &new_env)?;
+            for invariant in &annotations.invariants {
+                let kind = check_exp(invariant.clone(), &new_env)?;
+                if kind != Type::TBool {
+                    return Err(format!(
+                        "[Type Error on '{}()'] loop invariant must be boolean, found '{:?}'.",
+                        new_env.scope_name(),
+                        kind
+                    ));
+                }
+            }
+
+            if let ControlFlow::Continue(iteration_env) = check_sequence(statements(body), new_env.clone())? {
+                if let Some((name, before, after)) = changed_type(&new_env, &iteration_env) {
+                    return Err(format!(
+                        "[Type Error on '{}()'] '{}' changes type across while iterations: '{:?}' before an iteration, '{:?}' after it.",
+                        new_env.scope_name(),
+                        name,
+                        before,
+                        after
+                    ));
+                }
+            }
 
-            match check_stmt(*stmt_while, &new_env)? {
-                ControlFlow::Continue(_) => Ok(ControlFlow::Continue(new_env)),
-                ControlFlow::Return(kind) => Ok(ControlFlow::Return(kind)),
+            // The body may run zero times, so a return inside it does not
+            // make the loop return. Only a return can end `while True`, so
+            // whatever follows it is unreachable.
+            match new_env.scope_return() {
+                Some(Type::TFunction(kind, _)) if *exp == Expression::CTrue => {
+                    Ok(ControlFlow::Return(kind.clone().unwrap_or(Type::TVoid)))
+                }
+                _ => Ok(ControlFlow::Continue(new_env)),
             }
         }
-        Statement::Sequence(stmt1, stmt2) => {
-            if let ControlFlow::Continue(control_env) = check_stmt(*stmt1, &new_env)? {
-                new_env = control_env;
+        Statement::Sequence(stmt1, stmt2) => check_sequence(vec![*stmt1, *stmt2], new_env),
+        Statement::Block(stmts) => check_block(Statement::Block(stmts), &new_env),
+        Statement::Match(exp, cases) => {
+            let kind = check_exp(*exp, &new_env)?;
+
+            // A match returns only if every arm does; no arm matching is a
+            // runtime failure, not a fall through
+            let mut returns = None;
+            let mut all_return = !cases.is_empty();
+            let mut arms = vec![];
+            for (pattern, guard, stmt) in cases {
+                let mut bindings = vec![];
+                check_pattern(&pattern, &kind, &new_env, &mut bindings)?;
+                let mut arm_env = new_env.clone();
+                for (name, kind) in bindings {
+                    check_rebinding(&name, &new_env)?;
+                    arm_env.insert_variable(name.clone(), kind);
+                    arm_env.insert_origin(name, vec![pretty::pattern(&pattern)]);
+                }
+                if let Some(guard) = &guard {
+                    if check_exp(guard.clone(), &arm_env)? != Type::TBool {
+                        return Err(format!(
+                            "[Type Error on '{}()'] the guard of '{}' must be boolean.",
+                            new_env.scope_name(),
+                            pretty::arm(&pattern, Some(guard))
+                        ));
+                    }
+                }
+                match check_stmt(*stmt, &arm_env)? {
+                    ControlFlow::Return(kind) => returns = returns.or(Some(kind)),
+                    ControlFlow::Continue(_) => all_return = false,
+                }
+                arms.push((pattern, guard));
+            }
+            check_exhaustive(&arms, &kind, &new_env)?;
+
+            match returns {
+                Some(kind) if all_return => Ok(ControlFlow::Return(kind)),
+                _ => Ok(ControlFlow::Continue(new_env)),
             }
-            check_stmt(*stmt2, &new_env)
         }
         Statement::FuncDef(func) => {
+            check_rebinding(&func.name, &new_env)?;
             new_env.insert_frame(func.clone());
 
             let mut type_vec = vec![];
+            let signature = signature(&func);
 
             if let Some(params) = func.params.clone() {
                 // Adicionamos a verificação de parâmetros duplicados
                 check_duplicate_params(&params)?;
 
                 for (param_name, param_kind) in params {
-                    new_env.insert_variable(param_name, param_kind.clone());
+                    check_rebinding(&param_name, &new_env)?;
+                    new_env.insert_variable(param_name.clone(), param_kind.clone());
+                    new_env.insert_origin(param_name, vec![signature.clone()]);
                     type_vec.push(param_kind);
                 }
             }
 
+            let returns_void = func.kind == Some(Type::TVoid);
+            let (annotations, body) = func.contracts();
+            let result_type = func.kind.clone().unwrap_or(Type::TAny);
             let func_type = Type::TFunction(Box::new(func.kind), type_vec);
 
             if let None = new_env.search_frame(func.name.clone()) {
                 new_env.insert_variable(func.name.clone(), func_type.clone());
+                new_env.insert_origin(func.name.clone(), vec![signature.clone()]);
+            }
+
+            if !annotations.invariants.is_empty() {
+                return Err(String::from(
+                    "[Syntax Error] invariants are only allowed at the start of a while body.",
+                ));
+            }
+            check_measure(&annotations.decreases, &new_env)?;
+            for clause in annotations.requires {
+                check_contract(&func.name, "requires", clause, &new_env)?;
+            }
+            if !annotations.ensures.is_empty() {
+                let mut post_env = new_env.clone();
+                post_env.insert_variable(String::from("result"), result_type);
+                for clause in annotations.ensures {
+                    check_contract(&func.name, "ensures", clause, &post_env)?;
+                }
             }
 
-            match check_stmt(*func.body.unwrap(), &new_env)? {
-                ControlFlow::Continue(_) => Err(format!(
+            // Void functions may end without a return: they yield `CVoid`
+            match check_sequence(statements(body), new_env.clone())? {
+                ControlFlow::Continue(_) if !returns_void => Err(format!(
                     "[Syntax Error] '{}()' does not have a return statement.",
                     func.name
                 )),
-                ControlFlow::Return(_) => {
+                _ => {
                     new_env.remove_frame();
+                    new_env.insert_origin(func.name.clone(), vec![signature]);
                     new_env.insert_variable(func.name, func_type);
                     Ok(ControlFlow::Continue(new_env))
                 }
             }
         }
         Statement::Return(exp) => {
+            let origin = origin(&exp, &new_env);
             let exp_type = check_exp(*exp, &new_env)?;
 
             if let Some(Type::TFunction(func_type, _)) = new_env.scope_return() {
-                if exp_type != func_type.clone().unwrap() {
+                trace::expect(func_type.as_ref().as_ref().unwrap());
+                if !returnable(&exp_type, func_type.as_ref().as_ref().unwrap()) {
                     return Err(format!(
-                        "[Type Error] '{}()' has mismatched types: expected '{:?}', found '{:?}'.",
+                        "[Type Error] '{}()' has mismatched types: expected '{:?}', found '{:?}'.{}",
                         new_env.scope_name(),
                         func_type.clone().unwrap(),
-                        exp_type
+                        exp_type,
+                        slice(new_env.origin(&new_env.scope_name()), &origin)
                     ));
                 }
 
@@ -168,14 +434,592 @@ pub fn check_stmt(stmt: Statement, env: &Environment<Type>) -> Result<ControlFlo
                 Err(format!("[Syntax Error] return statement outside function."))
             }
         }
-        Statement::ADTDeclaration(name, constructors) => {
+        Statement::Call(exp) => {
+            check_exp(*exp, &new_env)?;
+            Ok(ControlFlow::Continue(new_env))
+        }
+        Statement::Import(_) | Statement::Module(_) => Ok(ControlFlow::Continue(new_env)),
+        Statement::ADTDeclaration(name, _, constructors) => {
             new_env.insert_type(name.clone(), constructors.clone());
             Ok(ControlFlow::Continue(new_env))
         }
+        Statement::InstanceDeclaration(class, adt_name, methods) => {
+            check_instance_declaration(&class, &adt_name, &methods, &new_env)?;
+            new_env.insert_instance(class, adt_name, methods);
+            Ok(ControlFlow::Continue(new_env))
+        }
+        Statement::Invariant(_) | Statement::Decreases(_) => Err(String::from(
+            "[Syntax Error] invariants are only allowed at the start of a while body.",
+        )),
+        Statement::Requires(_) | Statement::Ensures(_) => Err(String::from(
+            "[Syntax Error] contracts are only allowed at the start of a function body.",
+        )),
+        Statement::IfChain(..) => Err(desugar::surface_error("IfChain")),
+        Statement::AugmentedAssignment(..) => Err(desugar::surface_error("AugmentedAssignment")),
+        Statement::FuncClauses(..) => Err(desugar::surface_error("FuncClauses")),
+        Statement::FixityDeclaration(..) => Err(desugar::surface_error("FixityDeclaration")),
+        Statement::At(_, stmt) => check_stmt(*stmt, env),
+        // A layout is only added after checking, and says nothing of types
+        Statement::Locals(_) => Ok(ControlFlow::Continue(new_env)),
         _ => Err(String::from("not implemented yet.")),
     }
 }
 
+/// A body may declare one `decreases` measure, which must be an integer.
+fn check_measure(decreases: &[Expression], env: &Environment<Type>) -> Result<(), ErrorMessage> {
+    if decreases.len() > 1 {
+        return Err(String::from("[Syntax Error] only one 'decreases' clause is allowed."));
+    }
+    for clause in decreases {
+        let kind = check_exp(clause.clone(), env)?;
+        if kind != Type::TInteger {
+            return Err(format!(
+                "[Type Error on '{}()'] 'decreases' measure must be an integer, found '{:?}'.",
+                env.scope_name(),
+                kind
+            ));
+        }
+    }
+    Ok(())
+}
+
+fn check_contract(
+    func_name: &str,
+    keyword: &str,
+    clause: Expression,
+    env: &Environment<Type>,
+) -> Result<(), ErrorMessage> {
+    match check_exp(clause, env)? {
+        Type::TBool => Ok(()),
+        kind => Err(format!(
+            "[Type Error] '{}' clause of '{}()' must be a boolean, found '{:?}'.",
+            keyword, func_name, kind
+        )),
+    }
+}
+
+/// Checks a block, or the body of a branch, which is a block even when it
+/// is a single statement. Names first bound in it, functions included, go
+/// out of scope with it. The names it rebinds outlive it, so they must keep
+/// their type.
+fn check_block(body: Statement, env: &Environment<Type>) -> Result<ControlFlow, ErrorMessage> {
+    match check_sequence(statements(body), env.clone())? {
+        ControlFlow::Continue(block_env) => match changed_type(env, &block_env) {
+            Some((name, before, after)) => Err(format!(
+                "[Type Error on '{}()'] '{}' changes type inside a block: '{:?}' before it, '{:?}' after it.",
+                env.scope_name(),
+                name,
+                before,
+                after
+            )),
+            None => Ok(ControlFlow::Continue(env.clone())),
+        },
+        ControlFlow::Return(kind) => Ok(ControlFlow::Return(kind)),
+    }
+}
+
+fn statements(body: Statement) -> Vec<Statement> {
+    match body {
+        Statement::Block(stmts) => stmts,
+        stmt => vec![stmt],
+    }
+}
+
+/// The first name, in order, of the current scope of `before` that has
+/// another type in `after`.
+fn changed_type(before: &Environment<Type>, after: &Environment<Type>) -> Option<(Name, Type, Type)> {
+    let before = &before.get_frame(before.scope_key()).variables;
+    let after = &after.get_frame(after.scope_key()).variables;
+
+    before.iter().find_map(|(name, old)| match after.get(name) {
+        Some(kind) if kind != old => Some((name.clone(), old.clone(), kind.clone())),
+        _ => None,
+    })
+}
+
+/// Statements after a return are still checked, but the sequence returns.
+/// Sequences are the bodies of functions, loops and blocks, so they may not
+/// declare constants.
+fn check_sequence(stmts: Vec<Statement>, env: Environment<Type>) -> Result<ControlFlow, ErrorMessage> {
+    let mut env = env;
+    let mut returns = None;
+
+    for stmt in stmts {
+        if let Statement::ConstDeclaration(name, _, _) = &stmt {
+            return Err(format!(
+                "[Syntax Error] constants are only allowed at the top level; '{}' is declared in '{}()'.",
+                name,
+                env.scope_name()
+            ));
+        }
+        match check_stmt(stmt, &env)? {
+            ControlFlow::Continue(new_env) => env = new_env,
+            ControlFlow::Return(kind) => returns = returns.or(Some(kind)),
+        }
+    }
+
+    match returns {
+        Some(kind) => Ok(ControlFlow::Return(kind)),
+        None => Ok(ControlFlow::Continue(env)),
+    }
+}
+
+/// A constant is bound once, at the top level, to a value known before the
+/// program runs, so passes may replace its uses with its value.
+fn check_const_declaration(
+    name: Name,
+    kind: Type,
+    exp: Expression,
+    env: Environment<Type>,
+) -> Result<ControlFlow, ErrorMessage> {
+    let mut env = env;
+    if env.search_frame(name.clone()).is_some() {
+        return Err(format!(
+            "[Type Error on '{}()'] the constant '{}' is already bound; constants need a name of their own.",
+            env.scope_name(),
+            name
+        ));
+    }
+    if let Some(part) = unfoldable(&exp, &env) {
+        return Err(format!(
+            "[Type Error on '{}()'] the value of the constant '{}' must be known before the program runs, but '{}' is not.",
+            env.scope_name(),
+            name,
+            pretty::expression(part)
+        ));
+    }
+    let span = pretty::statement(&Statement::ConstDeclaration(name.clone(), kind.clone(), Box::new(exp.clone())));
+    let exp_type = check_exp(exp, &env)?;
+    trace::expect(&kind);
+    if exp_type != kind {
+        return Err(format!(
+            "[Type Error on '{}()'] '{}' has mismatched types: expected '{:?}', found '{:?}'.",
+            env.scope_name(),
+            name,
+            kind,
+            exp_type
+        ));
+    }
+
+    env.insert_variable(name.clone(), kind);
+    env.insert_origin(name.clone(), vec![span]);
+    env.insert_constant(name);
+    Ok(ControlFlow::Continue(env))
+}
+
+/// The first part of `exp` that is not a literal, an earlier constant or an
+/// operator applied to them.
+fn unfoldable<'a>(exp: &'a Expression, env: &Environment<Type>) -> Option<&'a Expression> {
+    match exp {
+        Expression::Var(name) if env.is_constant(name) => None,
+        Expression::Add(l, r)
+        | Expression::Sub(l, r)
+        | Expression::Mul(l, r)
+        | Expression::Div(l, r)
+        | Expression::And(l, r)
+        | Expression::Or(l, r)
+        | Expression::EQ(l, r)
+        | Expression::GT(l, r)
+        | Expression::LT(l, r)
+        | Expression::GTE(l, r)
+        | Expression::LTE(l, r) => unfoldable(l, env).or_else(|| unfoldable(r, env)),
+        Expression::Not(e) | Expression::CJust(e) | Expression::COk(e) | Expression::CErr(e) => unfoldable(e, env),
+        Expression::CList(items) | Expression::CTuple(items) => items.iter().find_map(|item| unfoldable(item, env)),
+        Expression::CDict(entries) => entries
+            .iter()
+            .find_map(|(key, value)| unfoldable(key, env).or_else(|| unfoldable(value, env))),
+        Expression::ADTConstructor(_, _, args) => args.iter().find_map(|arg| unfoldable(arg, env)),
+        Expression::CTrue
+        | Expression::CFalse
+        | Expression::CVoid
+        | Expression::CInt(_)
+        | Expression::CReal(_)
+        | Expression::CDecimal(_)
+        | Expression::CComplex(_)
+        | Expression::CString(_)
+        | Expression::CBytes(_)
+        | Expression::CNothing => None,
+        _ => Some(exp),
+    }
+}
+
+/// Constants keep their value wherever they are visible, so nothing there
+/// may bind their name again, not even a parameter or a pattern.
+fn check_rebinding(name: &Name, env: &Environment<Type>) -> Result<(), ErrorMessage> {
+    if env.is_constant(name) {
+        return Err(format!(
+            "[Type Error on '{}()'] '{}' is a constant and cannot be bound again.",
+            env.scope_name(),
+            name
+        ));
+    }
+    Ok(())
+}
+
+/// Checks that `pattern` can match values of type `kind`, collecting the
+/// type of each variable it binds. Values of type `TAny` may match any
+/// pattern, whose variables are then as precise as the pattern says.
+fn check_pattern(
+    pattern: &Pattern,
+    kind: &Type,
+    env: &Environment<Type>,
+    bindings: &mut Vec<(Name, Type)>,
+) -> Result<(), ErrorMessage> {
+    let mismatch = || {
+        Err(format!(
+            "[Type Error in '{}'] the pattern '{}' cannot match a value of type '{:?}'.",
+            env.scope_name(),
+            pretty::pattern(pattern),
+            kind
+        ))
+    };
+    let all = |patterns: &[Pattern], kinds: &[Type], bindings: &mut Vec<(Name, Type)>| {
+        patterns.iter().zip(kinds).try_for_each(|(pattern, kind)| check_pattern(pattern, kind, env, bindings))
+    };
+
+    match (pattern, kind) {
+        (Pattern::Wildcard, _) => Ok(()),
+        (Pattern::Variable(name), _) if bindings.iter().any(|(bound, _)| bound == name) => Err(format!(
+            "[Type Error in '{}'] '{}' is bound twice in the pattern.",
+            env.scope_name(),
+            name
+        )),
+        (Pattern::Variable(name), _) => {
+            bindings.push((name.clone(), kind.clone()));
+            Ok(())
+        }
+        (Pattern::Literal(exp), _) => match (check_exp(exp.clone(), env)?, kind) {
+            (_, Type::TAny) => Ok(()),
+            (Type::TMaybe(_), Type::TMaybe(_)) if *exp == Expression::CNothing => Ok(()),
+            (literal, kind) if literal == *kind => Ok(()),
+            _ => mismatch(),
+        },
+        (
+            Pattern::Constructor(adt_name, constructor_name, args) | Pattern::Tagged(adt_name, constructor_name, _, args),
+            Type::Tadt(..) | Type::TAny,
+        ) => {
+            if matches!(kind, Type::Tadt(name, _) if name != adt_name) {
+                return mismatch();
+            }
+            // A value built from a polymorphic ADT carries its type arguments
+            let constructors = match kind {
+                Type::Tadt(_, instantiated) if !instantiated.is_empty() => instantiated,
+                _ => env.get_type(adt_name).ok_or_else(|| {
+                    format!("[Type Error in '{}'] ADT '{}' is not defined.", env.scope_name(), adt_name)
+                })?,
+            };
+            let constructor = constructors.iter().find(|c| c.name == *constructor_name).ok_or_else(|| {
+                format!(
+                    "[Type Error in '{}'] ADT constructor '{}' not found in ADT '{}'.",
+                    env.scope_name(),
+                    constructor_name,
+                    adt_name
+                )
+            })?;
+            if args.len() != constructor.types.len() {
+                return Err(format!(
+                    "[Type Error in '{}'] ADT constructor '{}' expected {} arguments, found {}.",
+                    env.scope_name(),
+                    constructor_name,
+                    constructor.types.len(),
+                    args.len()
+                ));
+            }
+            let fields: Vec<Type> = constructor
+                .types
+                .iter()
+                .map(|field| match field {
+                    Type::Tadt(name, _) if name == adt_name && matches!(kind, Type::Tadt(..)) => kind.clone(),
+                    field => field.clone(),
+                })
+                .collect();
+            all(args, &fields, bindings)
+        }
+        (Pattern::Tuple(items), Type::TTuple(kinds)) if items.len() == kinds.len() => all(items, kinds, bindings),
+        (Pattern::Tuple(items), Type::TAny) | (Pattern::List(items), Type::TAny) => {
+            all(items, &vec![Type::TAny; items.len()], bindings)
+        }
+        (Pattern::List(items), Type::TList(item)) => all(items, &vec![(**item).clone(); items.len()], bindings),
+        (Pattern::ListRest(items, rest), Type::TList(_) | Type::TAny) => {
+            let item = match kind {
+                Type::TList(item) => (**item).clone(),
+                _ => Type::TAny,
+            };
+            all(items, &vec![item.clone(); items.len()], bindings)?;
+            match rest {
+                Some(name) => check_pattern(&Pattern::Variable(name.clone()), &Type::TList(Box::new(item)), env, bindings),
+                None => Ok(()),
+            }
+        }
+        (Pattern::As(pattern, whole), _) => {
+            check_pattern(pattern, kind, env, bindings)?;
+            check_pattern(&Pattern::Variable(whole.clone()), kind, env, bindings)
+        }
+        (Pattern::StartsWith(_) | Pattern::EndsWith(_), Type::TString | Type::TAny) => Ok(()),
+        _ => mismatch(),
+    }
+}
+
+/// Checks that the arms of a match on a value of type `kind` leave no
+/// constructor of its ADT unmatched, and warns about each arm the arms
+/// before it shadow, since it never runs. An arm with a guard may let the
+/// values it matches through, so it shadows nothing and covers nothing.
+/// A type without values, as an ADT without constructors, needs no arms,
+/// and any arm it has never runs.
+fn check_exhaustive(
+    arms: &[(Pattern, Option<Expression>)],
+    kind: &Type,
+    env: &Environment<Type>,
+) -> Result<(), ErrorMessage> {
+    let empty = !inhabited(kind, env, &mut vec![]);
+    let mut rows: Vec<Vec<&Pattern>> = vec![];
+    for (pattern, guard) in arms {
+        if empty {
+            env.lints.push(format!(
+                "[Warning] in '{}': the arm '{}' is unreachable; no value of type '{}' can be built.",
+                env.scope_name(),
+                pretty::arm(pattern, guard.as_ref()),
+                type_display(kind)
+            ));
+        } else if !useful(&rows, &[pattern], env) {
+            env.lints.push(format!(
+                "[Warning] in '{}': the arm '{}' is unreachable; the arms before it match everything it does.",
+                env.scope_name(),
+                pretty::arm(pattern, guard.as_ref())
+            ));
+        }
+        if guard.is_none() {
+            rows.push(vec![pattern]);
+        }
+    }
+
+    let Type::Tadt(adt_name, _) = kind else {
+        return Ok(());
+    };
+    let missing = unmatched_constructors(&rows, adt_name, env);
+    match missing.is_empty() {
+        true => Ok(()),
+        false => Err(format!(
+            "[Type Error in '{}'] the match on '{}' is not exhaustive; no arm matches {}.",
+            env.scope_name(),
+            adt_name,
+            missing.join(", ")
+        )),
+    }
+}
+
+/// The constructors of the ADT `adt_name` that build values none of the
+/// patterns of `rows` matches. A constructor that cannot build a value
+/// needs no arm.
+fn unmatched_constructors<'a>(rows: &[Vec<&Pattern>], adt_name: &Name, env: &'a Environment<Type>) -> Vec<&'a str> {
+    let constructors = env.get_type(adt_name).map(Vec::as_slice).unwrap_or_default();
+    constructors
+        .iter()
+        .filter(|constructor| constructible(constructor, env, &mut vec![]))
+        .filter(|constructor| {
+            let args = vec![Pattern::Wildcard; constructor.types.len()];
+            let pattern = Pattern::Constructor(adt_name.clone(), constructor.name.clone(), args);
+            useful(rows, &[&pattern], env)
+        })
+        .map(|constructor| constructor.name.as_str())
+        .collect()
+}
+
+/// Checks that `pattern` matches every value of type `kind`, as the
+/// pattern of a destructuring assignment must. A pattern that can fail
+/// belongs in a match, whose other arms say what happens then.
+fn check_irrefutable(pattern: &Pattern, kind: &Type, env: &Environment<Type>) -> Result<(), ErrorMessage> {
+    let rows = [vec![pattern]];
+    if !useful(&rows, &[WILDCARD], env) {
+        return Ok(());
+    }
+    let unmatched = match (kind, pattern) {
+        (Type::Tadt(adt_name, _), _) | (Type::TAny, Pattern::Constructor(adt_name, _, _)) => format!(
+            "the '{}' values built with {}",
+            adt_name,
+            unmatched_constructors(&rows, adt_name, env).join(", ")
+        ),
+        (kind, _) => format!("every value of type '{:?}'", kind),
+    };
+    Err(format!(
+        "[Type Error in '{}'] the pattern '{}' does not match {}; use a match to handle them.",
+        env.scope_name(),
+        pretty::pattern(pattern),
+        unmatched
+    ))
+}
+
+/// What a pattern tests its value for before its fields: the constructor
+/// of an ADT, a literal, the length of a tuple or list, the least length
+/// of a list, or a prefix or suffix of a string.
+#[derive(PartialEq)]
+enum Head {
+    Constructor(Name, Name),
+    Literal(Expression),
+    Tuple(usize),
+    List(usize),
+    ListRest(usize),
+    StartsWith(String),
+    EndsWith(String),
+}
+
+impl Head {
+    /// Whether every value with the head `other` has this head too, as a
+    /// string with a prefix has the shorter prefixes of it, and a list of
+    /// some length is at least as long as any shorter one.
+    fn covers(&self, other: &Head) -> bool {
+        match (self, other) {
+            (Head::ListRest(least), Head::List(length) | Head::ListRest(length)) => length >= least,
+            (Head::StartsWith(prefix), Head::Literal(Expression::CString(text)) | Head::StartsWith(text)) => {
+                text.starts_with(prefix.as_str())
+            }
+            (Head::EndsWith(suffix), Head::Literal(Expression::CString(text)) | Head::EndsWith(text)) => {
+                text.ends_with(suffix.as_str())
+            }
+            _ => self == other,
+        }
+    }
+}
+
+const WILDCARD: &Pattern = &Pattern::Wildcard;
+
+/// The head of `pattern` and the patterns of its fields, or `None` for a
+/// wildcard or a variable, which test for nothing. Naming the whole value
+/// tests for nothing more than the pattern it names.
+fn head(pattern: &Pattern) -> Option<(Head, &[Pattern])> {
+    match pattern {
+        Pattern::Wildcard | Pattern::Variable(_) => None,
+        Pattern::As(pattern, _) => head(pattern),
+        Pattern::Literal(exp) => Some((Head::Literal(exp.clone()), &[])),
+        Pattern::Constructor(adt_name, name, args) | Pattern::Tagged(adt_name, name, _, args) => {
+            Some((Head::Constructor(adt_name.clone(), name.clone()), args))
+        }
+        Pattern::Tuple(items) => Some((Head::Tuple(items.len()), items)),
+        Pattern::List(items) => Some((Head::List(items.len()), items)),
+        Pattern::ListRest(items, _) => Some((Head::ListRest(items.len()), items)),
+        Pattern::StartsWith(prefix) => Some((Head::StartsWith(prefix.clone()), &[])),
+        Pattern::EndsWith(suffix) => Some((Head::EndsWith(suffix.clone()), &[])),
+    }
+}
+
+/// Whether some value matches `row` but none of the rows of `matrix`,
+/// whose columns match the same values as the patterns of `row`. An arm
+/// is reachable when it is useful against the arms before it, and a
+/// match is exhaustive when a wildcard is not useful against its arms.
+fn useful<'a>(matrix: &[Vec<&'a Pattern>], row: &[&'a Pattern], env: &Environment<Type>) -> bool {
+    let Some((first, rest)) = row.split_first() else {
+        return matrix.is_empty();
+    };
+    let mut heads: Vec<(Head, usize)> = matrix
+        .iter()
+        .filter_map(|row| head(row[0]).map(|(head, args)| (head, args.len())))
+        .collect();
+
+    // A list at least some length long is useful if it is under one of
+    // the lengths it splits into, as a wildcard is
+    if let Some((Head::ListRest(least), args)) = head(first) {
+        heads.push((Head::ListRest(least), least));
+        let lengths = complete(&heads, env).unwrap_or_default();
+        return lengths.into_iter().filter(|(length, _)| Head::ListRest(least).covers(length)).any(|(length, arity)| {
+            let fields: Vec<&Pattern> =
+                args.iter().chain(std::iter::repeat(WILDCARD)).take(arity).chain(rest.iter().copied()).collect();
+            useful(&specialize(matrix, &length, arity), &fields, env)
+        });
+    }
+    if let Some((first, args)) = head(first) {
+        let fields: Vec<&Pattern> = args.iter().chain(rest.iter().copied()).collect();
+        return useful(&specialize(matrix, &first, args.len()), &fields, env);
+    }
+
+    // A wildcard is useful if it matches a head the column leaves out, or
+    // when the column names every head, if it is useful under one of them
+    match complete(&heads, env) {
+        Some(all) => all.into_iter().any(|(head, arity)| {
+            let fields: Vec<&Pattern> = std::iter::repeat_n(WILDCARD, arity).chain(rest.iter().copied()).collect();
+            useful(&specialize(matrix, &head, arity), &fields, env)
+        }),
+        None => {
+            let defaults: Vec<Vec<&Pattern>> =
+                matrix.iter().filter(|row| head(row[0]).is_none()).map(|row| row[1..].to_vec()).collect();
+            useful(&defaults, rest, env)
+        }
+    }
+}
+
+/// The rows of `matrix` that match the values `head` builds, their first
+/// pattern replaced by the patterns of its `arity` fields. A row whose
+/// head covers `head` matches them all, with wildcards for the fields it
+/// has no pattern for, as the items past the start of a list.
+fn specialize<'a>(matrix: &[Vec<&'a Pattern>], head: &Head, arity: usize) -> Vec<Vec<&'a Pattern>> {
+    matrix
+        .iter()
+        .filter_map(|row| {
+            let fields: Vec<&Pattern> = match self::head(row[0]) {
+                None => vec![WILDCARD; arity],
+                Some((other, args)) if other.covers(head) => args.iter().chain(std::iter::repeat(WILDCARD)).take(arity).collect(),
+                Some(_) => return None,
+            };
+            Some(fields.into_iter().chain(row[1..].iter().copied()).collect())
+        })
+        .collect()
+}
+
+/// Whether some value has type `kind`. An ADT has none when none of its
+/// constructors can build one, as when it has no constructors, or each
+/// needs a value of a type without values, or of the ADT itself with no
+/// way to end, as in `adt Stream = Next Int Stream`.
+fn inhabited(kind: &Type, env: &Environment<Type>, visiting: &mut Vec<Name>) -> bool {
+    match kind {
+        Type::Tadt(name, _) if visiting.contains(name) => false,
+        Type::Tadt(name, instantiated) => {
+            let constructors = match (instantiated.is_empty(), env.get_type(name)) {
+                (false, _) => instantiated.clone(),
+                (true, Some(declared)) => declared.clone(),
+                (true, None) => return true,
+            };
+            visiting.push(name.clone());
+            let found = constructors.iter().any(|constructor| constructible(constructor, env, visiting));
+            visiting.pop();
+            found
+        }
+        Type::TTuple(items) => items.iter().all(|item| inhabited(item, env, visiting)),
+        _ => true,
+    }
+}
+
+fn constructible(constructor: &ValueConstructor, env: &Environment<Type>, visiting: &mut Vec<Name>) -> bool {
+    constructor.types.iter().all(|field| inhabited(field, env, visiting))
+}
+
+/// Every head of the type of a column with `heads`, with its arity, if
+/// `heads` names them all: the inhabited constructors of an ADT, `True` and
+/// `False`, or the one tuple of a length. Lists split into each length up
+/// to the longest start a pattern names and the lists longer than that.
+/// Other literals and strings have heads without end.
+fn complete(heads: &[(Head, usize)], env: &Environment<Type>) -> Option<Vec<(Head, usize)>> {
+    match heads.first()? {
+        (Head::Constructor(adt_name, _), _) => {
+            let all: Vec<(Head, usize)> = env
+                .get_type(adt_name)?
+                .iter()
+                .filter(|constructor| constructible(constructor, env, &mut vec![]))
+                .map(|constructor| (Head::Constructor(adt_name.clone(), constructor.name.clone()), constructor.types.len()))
+                .collect();
+            all.iter().all(|(head, _)| heads.iter().any(|(other, _)| other == head)).then_some(all)
+        }
+        (Head::Literal(Expression::CTrue | Expression::CFalse), _) => {
+            let all = vec![(Head::Literal(Expression::CTrue), 0), (Head::Literal(Expression::CFalse), 0)];
+            all.iter().all(|(head, _)| heads.iter().any(|(other, _)| other == head)).then_some(all)
+        }
+        (Head::Tuple(arity), _) => Some(vec![(Head::Tuple(*arity), *arity)]),
+        (Head::List(_) | Head::ListRest(_), _) => {
+            let longest = heads.iter().map(|(_, arity)| *arity).max().unwrap_or_default();
+            let lengths = (0..=longest).map(|length| (Head::List(length), length));
+            Some(lengths.chain(std::iter::once((Head::ListRest(longest + 1), longest + 1))).collect())
+        }
+        (Head::Literal(_) | Head::StartsWith(_) | Head::EndsWith(_), _) => None,
+    }
+}
 
 fn check_adt_constructor(
     adt_name: Name,          // Name of the ADT
@@ -185,6 +1029,13 @@ fn check_adt_constructor(
 ) -> Result<Type, ErrorMessage> {
     // Retrieve the ADT definition from the environment
     if let Some(constructors) = env.get_type(&adt_name) {
+        if constructors.is_empty() {
+            return Err(format!(
+                "[Type Error in '{}'] ADT '{}' has no constructors, so no value of it can be built.",
+                env.scope_name(),
+                adt_name
+            ));
+        }
         // Find the correct constructor by name
         if let Some(constructor) = constructors.iter().find(|c| c.name == constructor_name) {
             // Check if the number of arguments matches the expected number
@@ -198,22 +1049,54 @@ fn check_adt_constructor(
                 ));
             }
 
-            // Check if the arguments match the expected constructor types
-            for (arg, expected_type) in args.iter().zip(&constructor.types) {
+            // Check the arguments against the constructor types, inferring the
+            // type arguments of a polymorphic ADT on the way
+            let mut bindings = HashMap::new();
+            for (position, (arg, expected_type)) in args.iter().zip(&constructor.types).enumerate() {
                 let arg_type = check_exp(*arg.clone(), env)?;
-                if arg_type != *expected_type {
-                    return Err(format!(
-                        "[Type Error in '{}'] ADT constructor '{}' has mismatched argument types: expected '{:?}', found '{:?}'.",
-                        env.scope_name(),
-                        constructor_name,
-                        expected_type,
-                        arg_type
-                    ));
+                let earlier = bindings.clone();
+                if instantiate(expected_type, &arg_type, &adt_name, env, &mut bindings) {
+                    continue;
                 }
+                let mut alone = HashMap::new();
+                if instantiate(expected_type, &arg_type, &adt_name, env, &mut alone) {
+                    let mut clashes: Vec<(&Name, &Type, &Type)> = alone
+                        .iter()
+                        .filter_map(|(name, found)| earlier.get(name).filter(|bound| *bound != found).map(|bound| (name, bound, found)))
+                        .collect();
+                    clashes.sort_by_key(|(name, _, _)| *name);
+                    let clash = clashes.first();
+                    if let Some((name, bound, found)) = clash {
+                        return Err(format!(
+                            "[Type Error in '{}'] ADT constructor '{}' needs one type for '{}', but the arguments before argument {} make it '{}' and argument {} makes it '{}'.",
+                            env.scope_name(),
+                            constructor_name,
+                            name,
+                            position + 1,
+                            type_display(bound),
+                            position + 1,
+                            type_display(found)
+                        ));
+                    }
+                }
+                return Err(format!(
+                    "[Type Error in '{}'] ADT constructor '{}' has mismatched argument types: expected '{:?}', found '{:?}'.",
+                    env.scope_name(),
+                    constructor_name,
+                    substitute(expected_type, &earlier),
+                    arg_type
+                ));
             }
 
-            // Return the ADT type
-            Ok(Type::Tadt(adt_name.clone(), constructors.clone()))
+            // Return the ADT type, with the type arguments it was built with
+            let constructors = constructors
+                .iter()
+                .map(|c| ValueConstructor {
+                    name: c.name.clone(),
+                    types: c.types.iter().map(|t| substitute(t, &bindings)).collect(),
+                })
+                .collect();
+            Ok(Type::Tadt(adt_name.clone(), constructors))
         } else {
             Err(format!(
                 "[Type Error in '{}'] ADT constructor '{}' not found in ADT '{}'.",
@@ -249,15 +1132,34 @@ fn check_func_call(
                 ));
             }
 
+            if type_vec.iter().any(is_generic) {
+                return check_generic_call(&name, *kind, type_vec, args, env);
+            }
+
             for (arg, param_type) in args.iter().zip(type_vec) {
                 let arg_type = check_exp(arg.clone(), env)?;
+                trace::expect(&param_type);
                 if arg_type != param_type {
-                    return Err(format!("[Type Error on '{}()'] '{}()' has mismatched arguments: expected '{:?}', found '{:?}'.", env.scope_name(), name, param_type, arg_type));
+                    return Err(format!(
+                        "[Type Error on '{}()'] '{}()' has mismatched arguments: expected '{:?}', found '{:?}'.{}",
+                        env.scope_name(),
+                        name,
+                        param_type,
+                        arg_type,
+                        slice(env.origin(&name), &origin(arg, env))
+                    ));
                 }
             }
 
             Ok(kind.unwrap())
         }
+        Err(_) if env.strictness.prelude && registry::lookup(&name).is_some() => {
+            let mut arg_types = vec![];
+            for arg in args {
+                arg_types.push(check_exp(arg, env)?);
+            }
+            (registry::lookup(&name).unwrap().check)(&arg_types, env)
+        }
         _ => Err(format!(
             "[Name Error on '{}()'] '{}()' is not defined.",
             env.scope_name(),
@@ -266,26 +1168,130 @@ fn check_func_call(
     }
 }
 
-fn check_duplicate_params(params: &Vec<(Name, Type)>) -> Result<(), ErrorMessage> {
-    let mut seen_params = std::collections::HashSet::new();
+/// The statements that gave `exp` its type, when it is a variable: its
+/// binding, after the bindings of the variables it was copied from.
+fn origin(exp: &Expression, env: &Environment<Type>) -> Vec<String> {
+    match exp {
+        Expression::Var(name) => env.origin(name).cloned().unwrap_or_default(),
+        _ => vec![],
+    }
+}
 
-    for (name, _) in params {
-        if !seen_params.insert(name.clone()) {
+/// The two ends of a type conflict, for the end of an error message: the
+/// statements that made a type expected and those the other type came
+/// from. Empty unless the other type came from elsewhere, since otherwise
+/// the failing expression shows it.
+fn slice(expected: Option<&Vec<String>>, found: &[String]) -> String {
+    let quoted = |origin: &[String]| {
+        origin.iter().map(|stmt| format!("'{}'", stmt)).collect::<Vec<String>>().join(", then ")
+    };
+    if found.is_empty() {
+        return String::new();
+    }
+    match expected.filter(|origin| !origin.is_empty()) {
+        Some(origin) => format!("\n  expected because of {}\n  found from {}", quoted(origin), quoted(found)),
+        None => format!("\n  found from {}", quoted(found)),
+    }
+}
+
+/// The first line of a function definition, as written.
+fn signature(func: &Function) -> String {
+    let params: Vec<String> = func
+        .params
+        .iter()
+        .flatten()
+        .map(|(name, kind)| format!("{}: {}", name, type_display(kind)))
+        .collect();
+    match &func.kind {
+        Some(kind) => format!("def {}({}) -> {}", func.name, params.join(", "), type_display(kind)),
+        None => format!("def {}({})", func.name, params.join(", ")),
+    }
+}
+
+fn check_list(items: Vec<Expression>, env: &Environment<Type>) -> Result<Type, ErrorMessage> {
+    let mut element_type = Type::TAny;
+
+    for item in items {
+        let item_type = check_exp(item, env)?;
+        if element_type == Type::TAny {
+            element_type = item_type;
+            continue;
+        }
+        trace::expect(&element_type);
+        if item_type != element_type {
             return Err(format!(
-                "[Parameter Error] Duplicate parameter name '{}'",
-                name
+                "[Type Error] list elements have mismatched types: expected '{:?}', found '{:?}'.",
+                element_type, item_type
             ));
         }
     }
 
-    Ok(())
+    Ok(Type::TList(Box::new(element_type)))
 }
 
-fn check_var_name(name: Name, env: &Environment<Type>, scoped: bool) -> Result<Type, ErrorMessage> {
-    let mut curr_scope = env.scope_key();
-
-    loop {
-        let frame = env.get_frame(curr_scope.clone());
+fn check_dict(entries: Vec<(Expression, Expression)>, env: &Environment<Type>) -> Result<Type, ErrorMessage> {
+    let mut key_type = Type::TAny;
+    let mut value_type = Type::TAny;
+
+    for (key, value) in entries {
+        let (k, v) = (check_exp(key, env)?, check_exp(value, env)?);
+        if !hashable(&k) {
+            return Err(format!("[Type Error] dict keys must be hashable, found '{:?}'.", k));
+        }
+        if key_type == Type::TAny {
+            (key_type, value_type) = (k, v);
+        } else if k != key_type || v != value_type {
+            return Err(format!(
+                "[Type Error] dict entries have mismatched types: expected '{:?}: {:?}', found '{:?}: {:?}'.",
+                key_type, value_type, k, v
+            ));
+        }
+    }
+
+    Ok(Type::TDict(Box::new(key_type), Box::new(value_type)))
+}
+
+/// Whether values of `kind` may key a dict, as `hash_key` decides at
+/// runtime. Only the nullary constructors of an ADT are hashable, which the
+/// type does not tell, so ADTs are let through.
+fn hashable(kind: &Type) -> bool {
+    match kind {
+        Type::TReal | Type::TComplex | Type::TList(_) | Type::TDict(..) | Type::TFunction(..) => false,
+        Type::TTuple(items) => items.iter().all(hashable),
+        _ => true,
+    }
+}
+
+fn check_duplicate_params(params: &Vec<(Name, Type)>) -> Result<(), ErrorMessage> {
+    let mut seen_params = std::collections::HashSet::new();
+
+    for (name, _) in params {
+        if !seen_params.insert(name.clone()) {
+            return Err(format!(
+                "[Parameter Error] Duplicate parameter name '{}'",
+                name
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+/// The type `name` was declared with, or `None` when an assignment to it
+/// declares it.
+fn declared(name: &Name, env: &Environment<Type>) -> Result<Option<Type>, ErrorMessage> {
+    match check_var_name(name.clone(), env, true) {
+        Ok(kind) => Ok(Some(kind)),
+        Err(message) if message.starts_with("[Name Error") => Ok(None),
+        Err(message) => Err(message),
+    }
+}
+
+fn check_var_name(name: Name, env: &Environment<Type>, scoped: bool) -> Result<Type, ErrorMessage> {
+    let mut curr_scope = env.scope_key();
+
+    loop {
+        let frame = env.get_frame(curr_scope.clone());
 
         match frame.variables.get(&name) {
             Some(kind) => {
@@ -318,18 +1324,82 @@ fn check_bin_arithmetic_expression(
     right: Expression,
     env: &Environment<Type>,
 ) -> Result<Type, ErrorMessage> {
+    let operands = (env.strictness.promotions != Level::Allow).then(|| [left.clone(), right.clone()]);
     let left_type = check_exp(left, env)?;
     let right_type = check_exp(right, env)?;
 
+    if let Some(promoted) = numeric_promotion(&left_type, &right_type) {
+        let promoted = promoted?;
+        if let Some(operands) = operands {
+            check_promotions(&operands, [&left_type, &right_type], &promoted, env)?;
+        }
+        return Ok(promoted);
+    }
     match (left_type, right_type) {
-        (Type::TInteger, Type::TInteger) => Ok(Type::TInteger),
-        (Type::TInteger, Type::TReal) => Ok(Type::TReal),
-        (Type::TReal, Type::TInteger) => Ok(Type::TReal),
-        (Type::TReal, Type::TReal) => Ok(Type::TReal),
+        (left, right) if same_class_operand(&left, &right, "Num", env) => Ok(left),
         _ => Err(String::from("[Type Error] expecting numeric type values.")),
     }
 }
 
+/// The type of an operation on two numbers. Integers promote to reals and
+/// to decimals, and both to complex numbers, but decimals mix with no
+/// other fractions: the result would lose the exactness decimals are for.
+/// `None` when either side is no number.
+fn numeric_promotion(left: &Type, right: &Type) -> Option<Result<Type, ErrorMessage>> {
+    match (left, right) {
+        (Type::TInteger, Type::TInteger) => Some(Ok(Type::TInteger)),
+        (Type::TInteger | Type::TReal, Type::TInteger | Type::TReal) => Some(Ok(Type::TReal)),
+        (Type::TInteger | Type::TDecimal, Type::TInteger | Type::TDecimal) => Some(Ok(Type::TDecimal)),
+        (Type::TInteger | Type::TReal | Type::TComplex, Type::TInteger | Type::TReal | Type::TComplex) => {
+            Some(Ok(Type::TComplex))
+        }
+        (Type::TDecimal, other @ (Type::TReal | Type::TComplex))
+        | (other @ (Type::TReal | Type::TComplex), Type::TDecimal) => {
+            let hint = match other {
+                Type::TReal => "one side with decimal() or to_real()",
+                _ => "the decimal with to_real()",
+            };
+            Some(Err(format!("[Type Error] cannot mix 'TDecimal' and '{:?}'; convert {}.", other, hint)))
+        }
+        _ => None,
+    }
+}
+
+/// Reports the operands promoted to `promoted` as the strictness settings
+/// ask, each with the explicit conversion that would replace the promotion.
+fn check_promotions(
+    operands: &[Expression; 2],
+    kinds: [&Type; 2],
+    promoted: &Type,
+    env: &Environment<Type>,
+) -> Result<(), ErrorMessage> {
+    for (operand, kind) in operands.iter().zip(kinds) {
+        if kind == promoted {
+            continue;
+        }
+        let conversion = match (operand, promoted) {
+            (Expression::CInt(n), Type::TReal) => format!("{}.0", n),
+            (Expression::CInt(n), Type::TDecimal) => format!("{}d", n),
+            (_, Type::TReal) => format!("to_real({})", pretty::expression(operand)),
+            (_, Type::TDecimal) => format!("decimal({})", pretty::expression(operand)),
+            _ => format!("complex({}, 0)", pretty::expression(operand)),
+        };
+        let problem = format!(
+            "implicit promotion of `{}` from '{:?}' to '{:?}'; write `{}`.",
+            pretty::expression(operand),
+            kind,
+            promoted,
+            conversion
+        );
+        match env.strictness.promotions {
+            Level::Allow => {}
+            Level::Warn => env.lints.push(format!("[Warning] in '{}': {}", env.scope_name(), problem)),
+            Level::Deny => return Err(format!("[Type Error on '{}()'] {}", env.scope_name(), problem)),
+        }
+    }
+    Ok(())
+}
+
 fn check_bin_boolean_expression(
     left: Expression,
     right: Expression,
@@ -357,18 +1427,394 @@ fn check_bin_relational_expression(
     right: Expression,
     env: &Environment<Type>,
 ) -> Result<Type, ErrorMessage> {
+    let operands = (env.strictness.promotions != Level::Allow).then(|| [left.clone(), right.clone()]);
     let left_type = check_exp(left, env)?;
     let right_type = check_exp(right, env)?;
 
+    if let Some(promoted) = numeric_promotion(&left_type, &right_type) {
+        let promoted = promoted?;
+        if promoted == Type::TComplex {
+            return Err(String::from("[Type Error] complex numbers are not ordered."));
+        }
+        if let Some(operands) = operands {
+            check_promotions(&operands, [&left_type, &right_type], &promoted, env)?;
+        }
+        return Ok(Type::TBool);
+    }
     match (left_type, right_type) {
-        (Type::TInteger, Type::TInteger) => Ok(Type::TBool),
-        (Type::TInteger, Type::TReal) => Ok(Type::TBool),
-        (Type::TReal, Type::TInteger) => Ok(Type::TBool),
-        (Type::TReal, Type::TReal) => Ok(Type::TBool),
+        (left, right) if same_class_operand(&left, &right, "Ord", env) => Ok(Type::TBool),
         _ => Err(String::from("[Type Error] expecting numeric type values.")),
     }
 }
 
+fn check_eq_expression(
+    left: Expression,
+    right: Expression,
+    env: &Environment<Type>,
+) -> Result<Type, ErrorMessage> {
+    let operands = (env.strictness.real_equality != Level::Allow).then(|| [left.clone(), right.clone()]);
+    let left_type = check_exp(left, env)?;
+    let right_type = check_exp(right, env)?;
+    check_equatable(&left_type, &right_type, env)?;
+    if let Some(operands) = operands {
+        if matches!((&left_type, &right_type), (Type::TReal, Type::TReal | Type::TInteger) | (Type::TInteger, Type::TReal)) {
+            check_real_equality(&operands, env)?;
+        }
+    }
+    Ok(Type::TBool)
+}
+
+/// Reports `==` on reals as the strictness settings ask. `approx_eq`
+/// compares them with a tolerance, and `exact_eq` says the exact
+/// comparison is meant, which silences the report for that expression.
+fn check_real_equality(operands: &[Expression; 2], env: &Environment<Type>) -> Result<(), ErrorMessage> {
+    let [left, right] = operands.each_ref().map(pretty::expression);
+    let problem = format!(
+        "`{} == {}` compares reals exactly, which rounding defeats; write `approx_eq({}, {})`, or `exact_eq({}, {})` if exact equality is meant.",
+        left, right, left, right, left, right
+    );
+    match env.strictness.real_equality {
+        Level::Allow => {}
+        Level::Warn => env.lints.push(format!("[Warning] in '{}': {}", env.scope_name(), problem)),
+        Level::Deny => return Err(format!("[Type Error on '{}()'] {}", env.scope_name(), problem)),
+    }
+    Ok(())
+}
+
+/// Values compare with values of the same type: numbers with any numbers,
+/// strings, booleans and bytes with their own kind, and tuples, lists and
+/// dictionaries whose items compare. ADTs compare with the same ADT.
+/// Maybe and Result values compare with values of the same wrapper whose
+/// payloads compare; `Nothing` and the missing side of `Ok` and `Err` are
+/// `TAny`, which compares with anything. A wrapper never compares with its
+/// payload: that comparison almost always lacks an `unwrap`.
+fn check_equatable(left: &Type, right: &Type, env: &Environment<Type>) -> Result<(), ErrorMessage> {
+    let payload = |kind: &Type| match kind {
+        Type::TMaybe(item) => Some((**item).clone()),
+        Type::TResult(ok, _) => Some((**ok).clone()),
+        _ => None,
+    };
+
+    match (left, right) {
+        (Type::TAny, _) | (_, Type::TAny) => Ok(()),
+        (
+            Type::TInteger | Type::TReal | Type::TDecimal | Type::TComplex,
+            Type::TInteger | Type::TReal | Type::TDecimal | Type::TComplex,
+        ) => numeric_promotion(left, right).unwrap().map(|_| ()),
+        (Type::Tadt(name1, _), Type::Tadt(name2, _)) if name1 == name2 => Ok(()),
+        (Type::Tadt(name1, _), Type::Tadt(name2, _)) => Err(format!(
+            "[Type Error] cannot compare values of ADT '{}' with values of ADT '{}'.",
+            name1, name2
+        )),
+        (Type::TString, Type::TString) | (Type::TBool, Type::TBool) | (Type::TBytes, Type::TBytes) => Ok(()),
+        (Type::TTuple(items1), Type::TTuple(items2)) if items1.len() == items2.len() => items1
+            .iter()
+            .zip(items2)
+            .try_for_each(|(item1, item2)| check_equatable(item1, item2, env)),
+        (Type::TList(item1), Type::TList(item2)) => check_equatable(item1, item2, env),
+        (Type::TDict(key1, value1), Type::TDict(key2, value2)) => {
+            check_equatable(key1, key2, env)?;
+            check_equatable(value1, value2, env)
+        }
+        (Type::TMaybe(item1), Type::TMaybe(item2)) => check_equatable(item1, item2, env),
+        (Type::TResult(ok1, err1), Type::TResult(ok2, err2)) => {
+            check_equatable(ok1, ok2, env)?;
+            check_equatable(err1, err2, env)
+        }
+        (wrapper, other) | (other, wrapper) if payload(wrapper).is_some() => {
+            let unwrapped = payload(wrapper).unwrap();
+            let hint = match payload(other) {
+                None if check_equatable(&unwrapped, other, env).is_ok() => "; did you mean unwrap?",
+                _ => ".",
+            };
+            Err(format!(
+                "[Type Error] cannot compare '{}' with '{}'{}",
+                type_display(left),
+                type_display(right),
+                hint
+            ))
+        }
+        (left, right) if same_class_operand(left, right, "Eq", env) => Ok(()),
+        _ => Err(format!(
+            "[Type Error] cannot compare '{}' with '{}'.",
+            type_display(left),
+            type_display(right)
+        )),
+    }
+}
+
+/// Operators on type variables and ADTs go through type classes, and both
+/// operands must have the same type.
+fn same_class_operand(left: &Type, right: &Type, class: &str, env: &Environment<Type>) -> bool {
+    match (left, right) {
+        (Type::TVar(name1, _), Type::TVar(name2, _)) => name1 == name2 && satisfies(left, class, env),
+        (Type::Tadt(name1, _), Type::Tadt(name2, _)) => name1 == name2 && satisfies(left, class, env),
+        _ => false,
+    }
+}
+
+/// Type variables belong to the classes they are constrained by (`Ord`
+/// implies `Eq`); ADTs to the classes they declare instances of.
+pub fn satisfies(kind: &Type, class: &str, env: &Environment<Type>) -> bool {
+    match kind {
+        Type::TVar(_, classes) => classes
+            .iter()
+            .any(|c| c == class || (class == "Eq" && c == "Ord")),
+        Type::Tadt(name, _) => env.has_instance(class, name) || builtin_instance(class, kind),
+        _ => builtin_instance(class, kind),
+    }
+}
+
+pub(crate) fn type_display(kind: &Type) -> String {
+    match kind {
+        Type::Tadt(name, _) => name.clone(),
+        Type::TVar(name, _) => name.clone(),
+        _ => format!("{:?}", kind),
+    }
+}
+
+fn is_generic(kind: &Type) -> bool {
+    match kind {
+        Type::TVar(..) => true,
+        Type::TList(t) | Type::TMaybe(t) => is_generic(t),
+        Type::TResult(t1, t2) | Type::TDict(t1, t2) => is_generic(t1) || is_generic(t2),
+        Type::TTuple(ts) => ts.iter().any(is_generic),
+        Type::TFunction(ret, params) => {
+            ret.as_ref().as_ref().is_some_and(is_generic) || params.iter().any(is_generic)
+        }
+        _ => false,
+    }
+}
+
+/// Whether a function declared to return `declared` may return a value of
+/// type `found`. Outside generic signatures, the `TAny` in a value's type
+/// (as in `Nothing` or `Err("no")`) stands for whatever `declared` says.
+fn returnable(found: &Type, declared: &Type) -> bool {
+    found == declared || (!is_generic(declared) && unify(declared, found, &mut HashMap::new()))
+}
+
+/// Binds the type variables of `param` so that it matches `arg`. `TAny`
+/// (as in the type of `[]`) matches anything.
+fn unify(param: &Type, arg: &Type, bindings: &mut HashMap<Name, Type>) -> bool {
+    match (param, arg) {
+        (Type::TVar(name, _), _) => match bindings.get(name) {
+            Some(bound) => bound == arg || *arg == Type::TAny,
+            None => {
+                bindings.insert(name.clone(), arg.clone());
+                true
+            }
+        },
+        (_, Type::TAny) => true,
+        (Type::TList(p), Type::TList(a)) | (Type::TMaybe(p), Type::TMaybe(a)) => {
+            unify(p, a, bindings)
+        }
+        (Type::TResult(p1, p2), Type::TResult(a1, a2))
+        | (Type::TDict(p1, p2), Type::TDict(a1, a2)) => {
+            unify(p1, a1, bindings) && unify(p2, a2, bindings)
+        }
+        (Type::TTuple(ps), Type::TTuple(args)) => {
+            ps.len() == args.len() && ps.iter().zip(args).all(|(p, a)| unify(p, a, bindings))
+        }
+        _ => param == arg,
+    }
+}
+
+/// Binds the type variables of `param`, a field of the ADT `adt_name`, so
+/// that the field takes an argument of type `arg`. A field that refers to
+/// the ADT itself, as the tail of a list, binds them from the constructors
+/// of `arg`, where a variable left unbound, as in the type of `Nil`, takes
+/// anything. Other ADTs compare by name.
+fn instantiate(param: &Type, arg: &Type, adt_name: &Name, env: &Environment<Type>, bindings: &mut HashMap<Name, Type>) -> bool {
+    let all = |params: &[Type], args: &[Type], bindings: &mut HashMap<Name, Type>| {
+        params.len() == args.len()
+            && params.iter().zip(args).all(|(p, a)| p == a || instantiate(p, a, adt_name, env, bindings))
+    };
+    match (param, arg) {
+        (Type::TVar(..), _) => unify(param, arg, bindings),
+        (_, Type::TAny) => true,
+        (Type::TList(p), Type::TList(a)) | (Type::TMaybe(p), Type::TMaybe(a)) => {
+            instantiate(p, a, adt_name, env, bindings)
+        }
+        (Type::TResult(p1, p2), Type::TResult(a1, a2)) | (Type::TDict(p1, p2), Type::TDict(a1, a2)) => {
+            instantiate(p1, a1, adt_name, env, bindings) && instantiate(p2, a2, adt_name, env, bindings)
+        }
+        (Type::TTuple(ps), Type::TTuple(args)) => all(ps, args, bindings),
+        (Type::Tadt(name, _), Type::Tadt(other, instantiated)) if name == other => {
+            let declared = env.get_type(name).map(Vec::as_slice).unwrap_or_default();
+            name != adt_name
+                || instantiated.is_empty()
+                || declared.len() == instantiated.len()
+                    && declared.iter().zip(instantiated).all(|(d, i)| all(&d.types, &i.types, bindings))
+        }
+        _ => param == arg,
+    }
+}
+
+fn substitute(kind: &Type, bindings: &HashMap<Name, Type>) -> Type {
+    match kind {
+        Type::TVar(name, _) => bindings.get(name).cloned().unwrap_or(kind.clone()),
+        Type::TList(t) => Type::TList(Box::new(substitute(t, bindings))),
+        Type::TMaybe(t) => Type::TMaybe(Box::new(substitute(t, bindings))),
+        Type::TResult(t1, t2) => Type::TResult(
+            Box::new(substitute(t1, bindings)),
+            Box::new(substitute(t2, bindings)),
+        ),
+        Type::TDict(t1, t2) => Type::TDict(
+            Box::new(substitute(t1, bindings)),
+            Box::new(substitute(t2, bindings)),
+        ),
+        Type::TTuple(ts) => Type::TTuple(ts.iter().map(|t| substitute(t, bindings)).collect()),
+        Type::TFunction(ret, params) => Type::TFunction(
+            Box::new(ret.as_ref().as_ref().map(|t| substitute(t, bindings))),
+            params.iter().map(|t| substitute(t, bindings)).collect(),
+        ),
+        _ => kind.clone(),
+    }
+}
+
+fn type_variables(kind: &Type, found: &mut Vec<(Name, Vec<Name>)>) {
+    match kind {
+        Type::TVar(name, classes) if !found.iter().any(|(n, _)| n == name) => {
+            found.push((name.clone(), classes.clone()));
+        }
+        Type::TList(t) | Type::TMaybe(t) => type_variables(t, found),
+        Type::TResult(t1, t2) | Type::TDict(t1, t2) => {
+            type_variables(t1, found);
+            type_variables(t2, found);
+        }
+        Type::TTuple(ts) => ts.iter().for_each(|t| type_variables(t, found)),
+        Type::TFunction(ret, params) => {
+            if let Some(ret) = ret.as_ref() {
+                type_variables(ret, found);
+            }
+            params.iter().for_each(|t| type_variables(t, found));
+        }
+        _ => {}
+    }
+}
+
+/// Calls to generic functions instantiate the type variables from the
+/// argument types, then check every constraint against the instances.
+fn check_generic_call(
+    name: &Name,
+    kind: Option<Type>,
+    params: Vec<Type>,
+    args: Vec<Expression>,
+    env: &Environment<Type>,
+) -> Result<Type, ErrorMessage> {
+    let mut bindings = HashMap::new();
+
+    for (arg, param_type) in args.into_iter().zip(params.iter()) {
+        let origin = origin(&arg, env);
+        let arg_type = check_exp(arg, env)?;
+        let unified = unify(param_type, &arg_type, &mut bindings);
+        trace::expect(&substitute(param_type, &bindings));
+        if !unified {
+            return Err(format!(
+                "[Type Error on '{}()'] '{}()' has mismatched arguments: expected '{:?}', found '{:?}'.{}",
+                env.scope_name(),
+                name,
+                substitute(param_type, &bindings),
+                arg_type,
+                slice(env.origin(name), &origin)
+            ));
+        }
+    }
+
+    let mut variables = vec![];
+    params.iter().for_each(|p| type_variables(p, &mut variables));
+    for (variable, classes) in variables {
+        if let Some(bound) = bindings.get(&variable) {
+            for class in classes {
+                if !satisfies(bound, &class, env) {
+                    return Err(format!(
+                        "[Type Error on '{}()'] '{}()' requires an instance of '{}' for '{}'.",
+                        env.scope_name(),
+                        name,
+                        class,
+                        type_display(bound)
+                    ));
+                }
+            }
+        }
+    }
+
+    Ok(substitute(&kind.unwrap(), &bindings))
+}
+
+/// An instance must define exactly the methods of its class, each with
+/// the class signature instantiated at the ADT.
+fn check_instance_declaration(
+    class_name: &Name,
+    adt_name: &Name,
+    methods: &[Function],
+    env: &Environment<Type>,
+) -> Result<(), ErrorMessage> {
+    let class = lookup_class(class_name)
+        .ok_or_else(|| format!("[Type Error] unknown type class '{}'.", class_name))?;
+    let constructors = env.get_type(adt_name).ok_or_else(|| {
+        format!(
+            "[Type Error] instances can only be declared for ADTs, and '{}' is not one.",
+            adt_name
+        )
+    })?;
+    if env.has_instance(class_name, adt_name) {
+        return Err(format!(
+            "[Type Error] duplicate instance of '{}' for '{}'.",
+            class_name, adt_name
+        ));
+    }
+
+    let instance_type = Type::Tadt(adt_name.clone(), constructors.clone());
+
+    for func in methods {
+        let method = class
+            .methods
+            .iter()
+            .find(|m| m.name == func.name)
+            .ok_or_else(|| {
+                format!(
+                    "[Type Error] '{}' is not a method of '{}'.",
+                    func.name, class_name
+                )
+            })?;
+
+        let params: Vec<Type> = func
+            .params
+            .iter()
+            .flatten()
+            .map(|(_, kind)| kind.clone())
+            .collect();
+        let func_type = Type::TFunction(Box::new(func.kind.clone()), params);
+        if func_type != method.signature(&instance_type) {
+            return Err(format!(
+                "[Type Error] '{}' in the '{}' instance for '{}' must take {} '{}' arguments and return '{}'.",
+                func.name,
+                class_name,
+                adt_name,
+                method.arity,
+                adt_name,
+                match method.signature(&instance_type) {
+                    Type::TFunction(ret, _) => type_display(&ret.unwrap()),
+                    _ => unreachable!(),
+                }
+            ));
+        }
+
+        check_stmt(Statement::FuncDef(func.clone()), env)?;
+    }
+
+    for method in class.methods {
+        if !methods.iter().any(|func| func.name == method.name) {
+            return Err(format!(
+                "[Type Error] the '{}' instance for '{}' does not define '{}'.",
+                class_name, adt_name, method.name
+            ));
+        }
+    }
+    Ok(())
+}
+
 fn check_result_ok(exp: Expression, env: &Environment<Type>) -> Result<Type, ErrorMessage> {
     let exp_type = check_exp(exp, env)?;
     return Ok(Type::TResult(Box::new(exp_type), Box::new(Type::TAny)));
@@ -391,12 +1837,29 @@ fn check_unwrap_type(exp: Expression, env: &Environment<Type>) -> Result<Type, E
     }
 }
 
+/// `e?` has the type inside the maybe or result `e`. In a function, the
+/// `Nothing` or `Err` it may return must fit what the function returns.
 fn check_propagate_type(exp: Expression, env: &Environment<Type>) -> Result<Type, ErrorMessage> {
     let exp_type = check_exp(exp, env)?;
-
-    match exp_type {
-        Type::TMaybe(t) => Ok(*t),
-        Type::TResult(tl, _) => Ok(*tl),
+    let declared = match env.scope_return() {
+        Some(Type::TFunction(kind, _)) => kind.as_ref().clone(),
+        _ => None,
+    };
+
+    let fits = match (&exp_type, &declared) {
+        (Type::TMaybe(_) | Type::TResult(..), None | Some(Type::TAny)) => true,
+        (Type::TMaybe(_), Some(Type::TMaybe(_))) => true,
+        (Type::TResult(_, error), Some(Type::TResult(_, declared_error))) => returnable(error, declared_error),
+        _ => false,
+    };
+    match (exp_type, declared) {
+        (Type::TMaybe(t) | Type::TResult(t, _), _) if fits => Ok(*t),
+        (kind @ (Type::TMaybe(_) | Type::TResult(..)), Some(declared)) => Err(format!(
+            "[Type Error] '?' on a '{}' cannot return from '{}()', which returns '{}'.",
+            type_display(&kind),
+            env.scope_name(),
+            type_display(&declared)
+        )),
         _ => Err(String::from(
             "[Type Error] expecting a maybe or result type value.",
         )),
@@ -435,95 +1898,392 @@ mod tests {
     use crate::ir::ast::Function;
     use crate::ir::ast::Statement::*;
     use crate::ir::ast::Type::*;
+    use crate::ir::ast::ValueConstructor;
+    use crate::ir::desugar;
+    use crate::parser::parser::{dedent, parse_program};
+
+    /// A checker error split into its parts: `[Type Error on 'f()'] ...`
+    /// has the code `Type Error`, the scope `f` and the message after it.
+    #[derive(Debug, PartialEq)]
+    struct Diagnostic {
+        code: String,
+        scope: Option<String>,
+        message: String,
+    }
 
-    #[test]
-    fn check_tlist_comparison() {
-        let t_list1 = TList(Box::new(TInteger));
-        let t_list2 = TList(Box::new(TInteger));
-
-        assert_eq!(t_list1 == t_list2, true);
+    fn diagnostic(code: &str, scope: Option<&str>, message: &str) -> Diagnostic {
+        Diagnostic {
+            code: code.to_string(),
+            scope: scope.map(String::from),
+            message: message.to_string(),
+        }
     }
 
-    #[test]
-    fn check_tlist_comparison_different_types() {
-        let t_list1 = TList(Box::new(TInteger));
-        let t_list2 = TList(Box::new(TBool));
+    impl Diagnostic {
+        fn parse(error: &str) -> Diagnostic {
+            let (head, message) = error
+                .strip_prefix('[')
+                .and_then(|rest| rest.split_once("] "))
+                .unwrap_or_else(|| panic!("not a diagnostic: {}", error));
+            let (code, scope) = match head.split_once(" on '") {
+                Some((code, scope)) => (code, Some(scope.trim_end_matches('\'').trim_end_matches("()"))),
+                None => (head, None),
+            };
+            diagnostic(code, scope, message)
+        }
+    }
 
-        assert_eq!(t_list1 == t_list2, false);
+    /// Parses, desugars and checks a program. The snippet may be indented
+    /// as a whole, so it can be written inline in a test.
+    fn check_source(source: &str) -> Result<Environment<Type>, Diagnostic> {
+        let program = parse_program(&dedent(source)).map_err(|e| Diagnostic::parse(&e))?;
+        check_program(&desugar::program(program)).map_err(|e| Diagnostic::parse(&e))
     }
 
-    #[test]
-    fn check_ttuple_comparison() {
-        let t_tuple1 = TTuple(vec![TInteger, TBool]);
-        let t_tuple2 = TTuple(vec![TInteger, TBool]);
+    fn accepts(source: &str) -> Environment<Type> {
+        check_source(source).unwrap_or_else(|d| panic!("rejected with {:?}", d))
+    }
 
-        assert_eq!(t_tuple1 == t_tuple2, true);
+    fn rejects(source: &str) -> Diagnostic {
+        match check_source(source) {
+            Ok(_) => panic!("accepted:\n{}", source),
+            Err(diagnostic) => diagnostic,
+        }
     }
 
     #[test]
-    fn check_ttuple_comparison_different_types() {
-        let t_tuple1 = TTuple(vec![TInteger, TBool]);
-        let t_tuple2 = TTuple(vec![TBool, TInteger]);
-
-        assert_eq!(t_tuple1 == t_tuple2, false);
+    fn check_func_def_error() {
+        let source = "
+            def add(a: TInteger, b: TInteger) -> TInteger:
+                return True
+        ";
+        assert_eq!(
+            rejects(source),
+            diagnostic("Type Error", None, "'add()' has mismatched types: expected 'TInteger', found 'TBool'.")
+        );
     }
 
     #[test]
-    fn check_constant() {
-        let env = Environment::new();
-
-        let c10 = CInt(10);
-
-        assert_eq!(check_exp(c10, &env), Ok(TInteger));
+    fn check_return_outside_function() {
+        assert_eq!(
+            rejects("return 1"),
+            diagnostic("Syntax Error", None, "return statement outside function.")
+        );
     }
 
     #[test]
-    fn check_add_integers() {
-        let env = Environment::new();
-
-        let c10 = CInt(10);
-        let c20 = CInt(20);
-        let add = Add(Box::new(c10), Box::new(c20));
-
-        assert_eq!(check_exp(add, &env), Ok(TInteger));
+    fn check_function_calls() {
+        let add = "
+            def add(a: TInteger, b: TInteger) -> TInteger:
+                c = 0
+                c = a + b
+                return c
+            total = 0
+        ";
+        let call = |call: &str| format!("{}\n            total = {}", add, call);
+
+        accepts(&call("add(1, 2)"));
+        assert_eq!(
+            rejects(&call("add(1)")),
+            diagnostic("Type Error", Some("__main__"), "'add()' expected 2 arguments, found 1.")
+        );
+        assert_eq!(
+            rejects(&call("add(1, 2, 3)")),
+            diagnostic("Type Error", Some("__main__"), "'add()' expected 2 arguments, found 3.")
+        );
+        assert_eq!(
+            rejects(&call("add(1, True)")),
+            diagnostic(
+                "Type Error",
+                Some("__main__"),
+                "'add()' has mismatched arguments: expected 'TInteger', found 'TBool'."
+            )
+        );
     }
 
     #[test]
-    fn check_add_reals() {
-        let env = Environment::new();
-
-        let c10 = CReal(10.5);
-        let c20 = CReal(20.3);
-        let add = Add(Box::new(c10), Box::new(c20));
-
-        assert_eq!(check_exp(add, &env), Ok(TReal));
+    fn check_assignments_declare_new_names() {
+        let env = accepts(
+            "
+            def double(n: TInteger) -> TInteger:
+                return n * 2
+            x = double(4)
+            x = x + 1
+            ",
+        );
+        assert_eq!(env.search_frame("x".to_string()), Some(&TInteger));
+        assert_eq!(
+            rejects("def double(n: TInteger) -> TInteger:\n    return n * 2\nx = double(4)\nflag = True\nx = flag"),
+            diagnostic(
+                "Type Error",
+                Some("__main__"),
+                "'x' has mismatched types: expected 'TInteger', found 'TBool'.\n  expected because of 'x = double(4)'\n  found from 'flag = True', then 'x = flag'"
+            )
+        );
     }
 
     #[test]
-    fn check_add_real_and_integer() {
-        let env = Environment::new();
-
-        let c10 = CInt(10);
-        let c20 = CReal(20.3);
-        let add = Add(Box::new(c10), Box::new(c20));
+    fn check_promotions_as_strictly_as_asked() {
+        let program = desugar::program(
+            parse_program(&dedent(
+                "
+                n = 3
+                half = n / 2.0
+                big = 1 > half
+                same = half + 0.5
+                ",
+            ))
+            .unwrap(),
+        );
+        let strictness = |promotions| Strictness { promotions, ..Strictness::default() };
 
-        assert_eq!(check_exp(add, &env), Ok(TReal));
+        assert_eq!(check_program_with(&program, strictness(Level::Allow)).map(|(_, lints)| lints), Ok(vec![]));
+        assert_eq!(
+            check_program_with(&program, strictness(Level::Warn)).map(|(_, lints)| lints),
+            Ok(vec![
+                String::from("[Warning] in '__main__': implicit promotion of `n` from 'TInteger' to 'TReal'; write `to_real(n)`."),
+                String::from("[Warning] in '__main__': implicit promotion of `1` from 'TInteger' to 'TReal'; write `1.0`."),
+            ])
+        );
+        assert_eq!(
+            check_program_with(&program, strictness(Level::Deny)).map(|(_, lints)| lints),
+            Err(String::from(
+                "[Type Error on '__main__()'] implicit promotion of `n` from 'TInteger' to 'TReal'; write `to_real(n)`."
+            ))
+        );
     }
 
     #[test]
-    fn check_add_integer_and_real() {
-        let env = Environment::new();
-
-        let c10 = CReal(10.5);
-        let c20 = CInt(20);
-        let add = Add(Box::new(c10), Box::new(c20));
+    fn check_real_equality_as_strictly_as_asked() {
+        let program = desugar::program(
+            parse_program(&dedent(
+                "
+                total = 0.1 + 0.2
+                same = total == 0.3
+                close = approx_eq(total, 0.3)
+                meant = exact_eq(total, 0.3)
+                counted = 3 == 3
+                ",
+            ))
+            .unwrap(),
+        );
+        let strictness = |real_equality| Strictness { real_equality, ..Strictness::default() };
+        let problem = "`total == 0.3` compares reals exactly, which rounding defeats; write `approx_eq(total, 0.3)`, or `exact_eq(total, 0.3)` if exact equality is meant.";
 
-        assert_eq!(check_exp(add, &env), Ok(TReal));
+        assert_eq!(check_program_with(&program, strictness(Level::Allow)).map(|(_, lints)| lints), Ok(vec![]));
+        assert_eq!(
+            check_program_with(&program, Strictness::default()).map(|(_, lints)| lints),
+            Ok(vec![format!("[Warning] in '__main__': {}", problem)])
+        );
+        assert_eq!(
+            check_program_with(&program, strictness(Level::Deny)).map(|(_, lints)| lints),
+            Err(format!("[Type Error on '__main__()'] {}", problem))
+        );
     }
 
     #[test]
-    fn check_type_error_arithmetic_expression() {
-        let env = Environment::new();
+    fn check_errors_point_at_both_ends_of_a_conflict() {
+        let source = "
+            def double(n: TInteger) -> TInteger:
+                return n * 2
+            def half(r: TReal) -> TInteger:
+                return r
+            x = 2.5
+            y = x
+            z = double(y)
+        ";
+        assert_eq!(
+            rejects(source),
+            diagnostic(
+                "Type Error",
+                None,
+                "'half()' has mismatched types: expected 'TInteger', found 'TReal'.\n  expected because of 'def half(r: TReal) -> TInteger'\n  found from 'def half(r: TReal) -> TInteger'"
+            )
+        );
+        assert_eq!(
+            rejects(&source.replace("return r", "return 1")),
+            diagnostic(
+                "Type Error",
+                Some("__main__"),
+                "'double()' has mismatched arguments: expected 'TInteger', found 'TReal'.\n  expected because of 'def double(n: TInteger) -> TInteger'\n  found from 'x = 2.5', then 'y = x'"
+            )
+        );
+    }
+
+    #[test]
+    fn check_function_call_non_function() {
+        assert_eq!(
+            rejects("a = 1\nb = 0\nb = a()"),
+            diagnostic("Name Error", Some("__main__"), "'a()' is not defined.")
+        );
+        assert_eq!(
+            rejects("a = 0\na = func()"),
+            diagnostic("Name Error", Some("__main__"), "'func()' is not defined.")
+        );
+    }
+
+    #[test]
+    fn check_recursive_function() {
+        let env = accepts(
+            "
+            def factorial(n: TInteger) -> TInteger:
+                if n == 0:
+                    return 1
+                else:
+                    return n * factorial(n - 1)
+            ",
+        );
+        assert_eq!(
+            env.search_frame("factorial".to_string()),
+            Some(&TFunction(Box::new(Some(TInteger)), vec![TInteger]))
+        );
+    }
+
+    #[test]
+    fn check_function_multiple_return_paths() {
+        accepts(
+            "
+            def max(a: TInteger, b: TInteger) -> TInteger:
+                if a > b:
+                    return a
+                else:
+                    return b
+            ",
+        );
+    }
+
+    #[test]
+    fn check_functions_need_a_return() {
+        assert_eq!(
+            rejects("def f(n: TInteger) -> TInteger:\n    x = 1"),
+            diagnostic("Syntax Error", None, "'f()' does not have a return statement.")
+        );
+        accepts("def f(n: TInteger) -> TVoid:\n    x = 1");
+    }
+
+    #[test]
+    fn test_function_wrong_return_type() {
+        assert_eq!(
+            rejects("def wrong_return() -> TInteger:\n    return 1.0"),
+            diagnostic(
+                "Type Error",
+                None,
+                "'wrong_return()' has mismatched types: expected 'TInteger', found 'TReal'."
+            )
+        );
+    }
+
+    #[test]
+    fn check_propagation_returns_what_the_function_returns() {
+        let half = "def half(n: TInteger) -> TMaybe[TInteger]:\n    if n == 0:\n        return Nothing\n    return Just(n / 2)\n";
+        accepts(&format!("{}def quarter(n: TInteger) -> TMaybe[TInteger]:\n    return Just(half(half(n)?)?)", half));
+        accepts("def check(n: TInteger) -> TResult[TInteger, TString]:\n    return Err(\"no\")\ndef twice(n: TInteger) -> TResult[TInteger, TString]:\n    return Ok(check(n)? * 2)");
+        assert_eq!(
+            rejects(&format!("{}def wrong(n: TInteger) -> TResult[TInteger, TString]:\n    return Ok(half(n)?)", half)),
+            diagnostic(
+                "Type Error",
+                None,
+                "'?' on a 'TMaybe(TInteger)' cannot return from 'wrong()', which returns 'TResult(TInteger, TString)'."
+            )
+        );
+    }
+
+    #[test]
+    fn test_function_parameter_shadowing() {
+        assert_eq!(
+            rejects("def shadow_test(x: TInteger, x: TInteger) -> TInteger:\n    return x"),
+            diagnostic("Parameter Error", None, "Duplicate parameter name 'x'")
+        );
+    }
+
+    #[test]
+    fn check_tlist_comparison() {
+        let t_list1 = TList(Box::new(TInteger));
+        let t_list2 = TList(Box::new(TInteger));
+
+        assert_eq!(t_list1 == t_list2, true);
+    }
+
+    #[test]
+    fn check_tlist_comparison_different_types() {
+        let t_list1 = TList(Box::new(TInteger));
+        let t_list2 = TList(Box::new(TBool));
+
+        assert_eq!(t_list1 == t_list2, false);
+    }
+
+    #[test]
+    fn check_ttuple_comparison() {
+        let t_tuple1 = TTuple(vec![TInteger, TBool]);
+        let t_tuple2 = TTuple(vec![TInteger, TBool]);
+
+        assert_eq!(t_tuple1 == t_tuple2, true);
+    }
+
+    #[test]
+    fn check_ttuple_comparison_different_types() {
+        let t_tuple1 = TTuple(vec![TInteger, TBool]);
+        let t_tuple2 = TTuple(vec![TBool, TInteger]);
+
+        assert_eq!(t_tuple1 == t_tuple2, false);
+    }
+
+    #[test]
+    fn check_constant() {
+        let env = Environment::new();
+
+        let c10 = CInt(10);
+
+        assert_eq!(check_exp(c10, &env), Ok(TInteger));
+    }
+
+    #[test]
+    fn check_add_integers() {
+        let env = Environment::new();
+
+        let c10 = CInt(10);
+        let c20 = CInt(20);
+        let add = Add(Box::new(c10), Box::new(c20));
+
+        assert_eq!(check_exp(add, &env), Ok(TInteger));
+    }
+
+    #[test]
+    fn check_add_reals() {
+        let env = Environment::new();
+
+        let c10 = CReal(10.5);
+        let c20 = CReal(20.3);
+        let add = Add(Box::new(c10), Box::new(c20));
+
+        assert_eq!(check_exp(add, &env), Ok(TReal));
+    }
+
+    #[test]
+    fn check_add_real_and_integer() {
+        let env = Environment::new();
+
+        let c10 = CInt(10);
+        let c20 = CReal(20.3);
+        let add = Add(Box::new(c10), Box::new(c20));
+
+        assert_eq!(check_exp(add, &env), Ok(TReal));
+    }
+
+    #[test]
+    fn check_add_integer_and_real() {
+        let env = Environment::new();
+
+        let c10 = CReal(10.5);
+        let c20 = CInt(20);
+        let add = Add(Box::new(c10), Box::new(c20));
+
+        assert_eq!(check_exp(add, &env), Ok(TReal));
+    }
+
+    #[test]
+    fn check_type_error_arithmetic_expression() {
+        let env = Environment::new();
 
         let c10 = CInt(10);
         let bool = CFalse;
@@ -648,6 +2408,116 @@ mod tests {
         assert_eq!(check_exp(is_nothing, &env), Ok(TBool));
     }
 
+    #[test]
+    fn check_eq_on_maybe_and_result() {
+        let env: Environment<Type> = Environment::new();
+        let eq = |l: Expression, r: Expression| check_exp(EQ(Box::new(l), Box::new(r)), &env);
+        let just = |e: Expression| CJust(Box::new(e));
+        let ok = |e: Expression| COk(Box::new(e));
+        let error = |message: &str| Err(format!("[Type Error] {}", message));
+
+        assert_eq!(eq(just(CInt(1)), just(CReal(2.5))), Ok(TBool));
+        assert_eq!(eq(CNothing, just(CInt(1))), Ok(TBool));
+        assert_eq!(eq(ok(CInt(1)), CErr(Box::new(CInt(2)))), Ok(TBool));
+
+        assert_eq!(
+            eq(just(CInt(1)), CInt(1)),
+            error("cannot compare 'TMaybe(TInteger)' with 'TInteger'; did you mean unwrap?")
+        );
+        assert_eq!(
+            eq(CReal(1.0), ok(CInt(1))),
+            error("cannot compare 'TReal' with 'TResult(TInteger, TAny)'; did you mean unwrap?")
+        );
+        assert_eq!(
+            eq(just(CInt(1)), CString("1".to_string())),
+            error("cannot compare 'TMaybe(TInteger)' with 'TString'.")
+        );
+        assert_eq!(
+            eq(just(CInt(1)), ok(CInt(1))),
+            error("cannot compare 'TMaybe(TInteger)' with 'TResult(TInteger, TAny)'.")
+        );
+    }
+
+    #[test]
+    fn check_eq_on_values_of_the_same_type() {
+        let env: Environment<Type> = Environment::new();
+        let eq = |l: Expression, r: Expression| check_exp(EQ(Box::new(l), Box::new(r)), &env);
+        let text = |s: &str| CString(s.to_string());
+
+        assert_eq!(eq(text("a"), text("b")), Ok(TBool));
+        assert_eq!(eq(CTrue, CFalse), Ok(TBool));
+        assert_eq!(eq(CTuple(vec![CInt(1), text("a")]), CTuple(vec![CReal(1.0), text("b")])), Ok(TBool));
+        assert_eq!(eq(CList(vec![CJust(Box::new(CInt(1)))]), CList(vec![CNothing])), Ok(TBool));
+
+        assert_eq!(
+            eq(text("1"), CInt(1)),
+            Err(String::from("[Type Error] cannot compare 'TString' with 'TInteger'."))
+        );
+        assert_eq!(
+            eq(CTuple(vec![CInt(1), CTrue]), CTuple(vec![CInt(1)])),
+            Err(String::from("[Type Error] cannot compare 'TTuple([TInteger, TBool])' with 'TTuple([TInteger])'."))
+        );
+        assert_eq!(
+            eq(CTuple(vec![text("a")]), CTuple(vec![CTrue])),
+            Err(String::from("[Type Error] cannot compare 'TString' with 'TBool'."))
+        );
+    }
+
+    #[test]
+    fn check_dict_keys_are_hashable() {
+        let env: Environment<Type> = Environment::new();
+        let dict = |key: Expression| check_exp(CDict(vec![(key, CTrue)]), &env);
+
+        assert_eq!(
+            dict(CTuple(vec![CInt(1), CString("a".to_string())])),
+            Ok(TDict(Box::new(TTuple(vec![TInteger, TString])), Box::new(TBool)))
+        );
+        assert_eq!(
+            dict(CReal(0.5)),
+            Err(String::from("[Type Error] dict keys must be hashable, found 'TReal'."))
+        );
+        assert_eq!(
+            dict(CTuple(vec![CInt(1), CList(vec![])])),
+            Err(String::from("[Type Error] dict keys must be hashable, found 'TTuple([TInteger, TList(TAny)])'."))
+        );
+    }
+
+    #[test]
+    fn check_decimal_promotion() {
+        let env: Environment<Type> = Environment::new();
+        let price = || Box::new(CDecimal(crate::ir::decimal::Decimal::parse("1.50").unwrap()));
+
+        assert_eq!(check_exp(Mul(price(), Box::new(CInt(3))), &env), Ok(TDecimal));
+        assert_eq!(check_exp(Add(price(), price()), &env), Ok(TDecimal));
+        assert_eq!(check_exp(LT(Box::new(CInt(1)), price()), &env), Ok(TBool));
+        assert_eq!(check_exp(EQ(price(), Box::new(CInt(1))), &env), Ok(TBool));
+
+        let mixed = Err(String::from(
+            "[Type Error] cannot mix 'TDecimal' and 'TReal'; convert one side with decimal() or to_real().",
+        ));
+        assert_eq!(check_exp(Add(price(), Box::new(CReal(0.5))), &env), mixed);
+        assert_eq!(check_exp(GT(Box::new(CReal(0.5)), price()), &env), mixed);
+        assert_eq!(check_exp(EQ(price(), Box::new(CReal(1.5))), &env), mixed);
+    }
+
+    #[test]
+    fn check_complex_promotion() {
+        let env: Environment<Type> = Environment::new();
+        let i = || Box::new(CComplex(crate::ir::complex::Complex::new(0.0, 1.0)));
+
+        assert_eq!(check_exp(Add(Box::new(CInt(1)), i()), &env), Ok(TComplex));
+        assert_eq!(check_exp(Div(i(), Box::new(CReal(2.0))), &env), Ok(TComplex));
+        assert_eq!(check_exp(EQ(i(), Box::new(CInt(1))), &env), Ok(TBool));
+        assert_eq!(
+            check_exp(LT(i(), Box::new(CInt(1))), &env),
+            Err(String::from("[Type Error] complex numbers are not ordered."))
+        );
+        assert_eq!(
+            check_exp(Mul(i(), Box::new(CDecimal(crate::ir::decimal::Decimal::from(2)))), &env),
+            Err(String::from("[Type Error] cannot mix 'TDecimal' and 'TComplex'; convert the decimal with to_real()."))
+        );
+    }
+
     #[test]
     fn check_is_nothing_type_error() {
         let env = Environment::new();
@@ -770,7 +2640,7 @@ mod tests {
             Ok(_) => assert!(false),
             Err(s) => assert_eq!(
                 s,
-                "[Type Error on '__main__()'] 'a' has mismatched types: expected 'TBool', found 'TInteger'."
+                "[Type Error on '__main__()'] 'a' has mismatched types: expected 'TBool', found 'TInteger'.\n  expected because of 'a = True'\n  found from 'a = 1'"
             ),
         }
     }
@@ -830,6 +2700,111 @@ mod tests {
         }
     }
 
+    #[test]
+    fn check_while_type_change_across_iterations() {
+        let env: Environment<Type> = Environment::new();
+
+        let assignment = Assignment("x".to_string(), Box::new(CInt(0)), Some(TInteger));
+        let while_stmt = While(
+            Box::new(CTrue),
+            Box::new(Assignment("x".to_string(), Box::new(CReal(0.5)), Some(TReal))),
+        );
+        let program = Sequence(Box::new(assignment), Box::new(while_stmt));
+
+        match check_stmt(program, &env) {
+            Ok(_) => panic!("expected a type error"),
+            Err(s) => assert_eq!(
+                s,
+                "[Type Error on '__main__()'] 'x' changes type across while iterations: 'TInteger' before an iteration, 'TReal' after it."
+            ),
+        }
+    }
+
+    #[test]
+    fn check_while_variable_declared_in_body() {
+        let env: Environment<Type> = Environment::new();
+
+        // Redeclaring 'y' inside an iteration is fine, as long as every
+        // iteration starts and ends with the same type.
+        let while_stmt = While(
+            Box::new(CTrue),
+            Box::new(Sequence(
+                Box::new(Assignment("y".to_string(), Box::new(CInt(1)), Some(TInteger))),
+                Box::new(Assignment("y".to_string(), Box::new(CTrue), Some(TBool))),
+            )),
+        );
+
+        match check_stmt(while_stmt, &env) {
+            Ok(ControlFlow::Continue(new_env)) => assert_eq!(new_env.search_frame("y".to_string()), None),
+            Ok(_) => panic!("unexpected return"),
+            Err(s) => panic!("{}", s),
+        }
+    }
+
+    #[test]
+    fn check_while_definitions_in_body() {
+        let source = "
+            total = 0
+            while total < 10:
+                total = total + 1
+                def step(n: TInteger) -> TInteger:
+                    return n + 1
+        ";
+        let env = accepts(source);
+        assert_eq!(env.search_frame("total".to_string()), Some(&TInteger));
+        assert_eq!(env.search_frame("step".to_string()), None);
+
+        assert_eq!(
+            rejects(&source.replace("n + 1", "n == 1")),
+            diagnostic("Type Error", None, "'step()' has mismatched types: expected 'TInteger', found 'TBool'.")
+        );
+    }
+
+    #[test]
+    fn check_definitions_in_branches() {
+        let inner = "
+            def outer(k: TInteger) -> TInteger:
+                if k > 0:
+                    def inner(n: TInteger) -> TInteger:
+                        return n + k
+                    return inner(1)
+                else:
+                    return 0
+        ";
+        assert!(accepts(inner).search_frame("inner".to_string()).is_none());
+
+        let after_branch = "
+            x = 3
+            if x > 2:
+                def double(n: TInteger) -> TInteger:
+                    return n * 2
+            else:
+                def double(n: TInteger) -> TInteger:
+                    return n * 3
+            y = double(x)
+        ";
+        assert_eq!(
+            rejects(after_branch),
+            diagnostic("Name Error", Some("__main__"), "'double()' is not defined.")
+        );
+
+        let retyped = "
+            def double(n: TInteger) -> TInteger:
+                return n * 2
+            if double(1) > 0:
+                def double(n: TInteger) -> TBool:
+                    return n > 0
+        ";
+        assert_eq!(
+            rejects(retyped),
+            diagnostic(
+                "Type Error",
+                Some("__main__"),
+                "'double' changes type inside a block: 'TFunction(Some(TInteger), [TInteger])' before it, 'TFunction(Some(TBool), [TInteger])' after it."
+            )
+        );
+    }
+
     #[test]
     fn check_func_def() {
         let env: Environment<Type> = Environment::new();
@@ -864,270 +2839,716 @@ mod tests {
     }
 
     #[test]
-    fn check_func_def_error() {
-        let env: Environment<Type> = Environment::new();
-
-        let func = FuncDef(Function {
-            name: "add".to_string(),
-            kind: Some(TInteger),
-            params: Some(vec![
-                ("a".to_string(), TInteger),
-                ("b".to_string(), TInteger),
-            ]),
-            body: Some(Box::new(Return(Box::new(CTrue)))),
-        });
-
-        match check_stmt(func, &env) {
-            Ok(_) => assert!(false),
-            Err(s) => assert_eq!(
-                s,
-                "[Type Error] 'add()' has mismatched types: expected 'TInteger', found 'TBool'."
-            ),
-        }
+    fn check_adt_equality() {
+        let mut env: Environment<Type> = Environment::new();
+        let shape = vec![
+            ValueConstructor { name: "Circle".to_string(), types: vec![TReal] },
+            ValueConstructor { name: "Square".to_string(), types: vec![TReal] },
+        ];
+        env.insert_type("Shape".to_string(), shape);
+        env.insert_type("Unit".to_string(), vec![ValueConstructor { name: "U".to_string(), types: vec![] }]);
+
+        let circle = Box::new(ADTConstructor("Shape".to_string(), "Circle".to_string(), vec![Box::new(CReal(1.0))]));
+        let square = Box::new(ADTConstructor("Shape".to_string(), "Square".to_string(), vec![Box::new(CReal(1.0))]));
+        let unit = Box::new(ADTConstructor("Unit".to_string(), "U".to_string(), vec![]));
+
+        assert_eq!(check_exp(EQ(circle.clone(), square), &env), Ok(TBool));
+        assert_eq!(
+            check_exp(EQ(circle, unit), &env),
+            Err(String::from("[Type Error] cannot compare values of ADT 'Shape' with values of ADT 'Unit'."))
+        );
     }
 
     #[test]
-    fn check_return_outside_function() {
-        let env: Environment<Type> = Environment::new();
-
-        let retrn = Return(Box::new(CInt(1)));
+    fn check_polymorphic_adts_are_instantiated_where_built() {
+        let mut env: Environment<Type> = Environment::new();
+        let a = TVar("a".to_string(), vec![]);
+        let list = |item: Type| {
+            Tadt(
+                "List".to_string(),
+                vec![
+                    ValueConstructor { name: "Nil".to_string(), types: vec![] },
+                    ValueConstructor { name: "Cons".to_string(), types: vec![item, Tadt("List".to_string(), vec![])] },
+                ],
+            )
+        };
+        let Tadt(_, constructors) = list(a.clone()) else { unreachable!() };
+        env.insert_type("List".to_string(), constructors);
+        let nil = || ADTConstructor("List".to_string(), "Nil".to_string(), vec![]);
+        let cons = |head: Expression, tail: Expression| {
+            ADTConstructor("List".to_string(), "Cons".to_string(), vec![Box::new(head), Box::new(tail)])
+        };
+
+        assert_eq!(check_exp(nil(), &env), Ok(list(a)));
+        assert_eq!(check_exp(cons(CInt(1), nil()), &env), Ok(list(TInteger)));
+        assert_eq!(check_exp(cons(CInt(1), cons(CInt(2), nil())), &env), Ok(list(TInteger)));
+        assert_eq!(
+            check_exp(cons(CInt(1), cons(CTrue, nil())), &env),
+            Err(String::from(
+                "[Type Error in '__main__'] ADT constructor 'Cons' needs one type for 'a', but the arguments before argument 2 make it 'TInteger' and argument 2 makes it 'TBool'."
+            ))
+        );
+        assert_eq!(
+            check_exp(cons(CInt(1), CInt(2)), &env),
+            Err(String::from(
+                "[Type Error in '__main__'] ADT constructor 'Cons' has mismatched argument types: expected 'Tadt(\"List\", [])', found 'TInteger'."
+            ))
+        );
 
-        match check_stmt(retrn, &env) {
-            Ok(_) => assert!(false),
-            Err(s) => assert_eq!(s, "[Syntax Error] return statement outside function."),
-        }
+        // The fields of a match arm take the type arguments of the value
+        env.insert_variable("xs".to_string(), list(TString));
+        let head = Pattern::Constructor(
+            "List".to_string(),
+            "Cons".to_string(),
+            vec![Pattern::Variable("h".to_string()), Pattern::Variable("t".to_string())],
+        );
+        let mut bindings = vec![];
+        assert_eq!(check_pattern(&head, &list(TString), &env, &mut bindings), Ok(()));
+        assert_eq!(bindings, vec![("h".to_string(), TString), ("t".to_string(), list(TString))]);
     }
 
     #[test]
-    fn check_function_call_wrong_args() {
+    fn check_closures_returned_and_stored() {
         let env: Environment<Type> = Environment::new();
+        let adder_type = TFunction(Box::new(Some(TInteger)), vec![TInteger]);
 
-        let func = FuncDef(Function {
+        let add = Function {
             name: "add".to_string(),
             kind: Some(TInteger),
-            params: Some(vec![
-                ("a".to_string(), TInteger),
-                ("b".to_string(), TInteger),
-            ]),
+            params: Some(vec![("x".to_string(), TInteger)]),
+            body: Some(Box::new(Return(Box::new(Add(
+                Box::new(Var("x".to_string())),
+                Box::new(Var("n".to_string())),
+            ))))),
+        };
+        let make_adder = FuncDef(Function {
+            name: "make_adder".to_string(),
+            kind: Some(adder_type.clone()),
+            params: Some(vec![("n".to_string(), TInteger)]),
             body: Some(Box::new(Sequence(
+                Box::new(FuncDef(add)),
+                Box::new(Return(Box::new(Var("add".to_string())))),
+            ))),
+        });
+        let program = Sequence(
+            Box::new(make_adder),
+            Box::new(Sequence(
                 Box::new(Assignment(
-                    "c".to_string(),
-                    Box::new(Add(
-                        Box::new(Var("a".to_string())),
-                        Box::new(Var("b".to_string())),
+                    "adders".to_string(),
+                    Box::new(CList(vec![FuncCall("make_adder".to_string(), vec![CInt(1)])])),
+                    Some(TList(Box::new(adder_type.clone()))),
+                )),
+                Box::new(Sequence(
+                    Box::new(Assignment(
+                        "add10".to_string(),
+                        Box::new(FuncCall("make_adder".to_string(), vec![CInt(10)])),
+                        Some(adder_type),
+                    )),
+                    Box::new(Assignment(
+                        "r".to_string(),
+                        Box::new(FuncCall("add10".to_string(), vec![CReal(1.0)])),
+                        Some(TInteger),
                     )),
-                    Some(TInteger),
                 )),
-                Box::new(Return(Box::new(Var("c".to_string())))),
-            ))),
-        });
-        let program1 = Sequence(
-            Box::new(func.clone()),
-            Box::new(Assignment(
-                "var".to_string(),
-                Box::new(FuncCall("add".to_string(), vec![CInt(1)])),
-                Some(TInteger),
-            )),
-        );
-        let program2 = Sequence(
-            Box::new(func),
-            Box::new(Assignment(
-                "var".to_string(),
-                Box::new(FuncCall("add".to_string(), vec![CInt(1), CInt(2), CInt(3)])),
-                Some(TInteger),
             )),
         );
 
-        match check_stmt(program1, &env.clone()) {
-            Ok(_) => assert!(false),
-            Err(s) => assert_eq!(
-                s,
-                "[Type Error on '__main__()'] 'add()' expected 2 arguments, found 1."
-            ),
-        }
-        match check_stmt(program2, &env) {
-            Ok(_) => assert!(false),
+        match check_stmt(program, &env) {
+            Ok(_) => panic!("expected a type error"),
             Err(s) => assert_eq!(
                 s,
-                "[Type Error on '__main__()'] 'add()' expected 2 arguments, found 3."
+                "[Type Error on '__main__()'] 'add10()' has mismatched arguments: expected 'TInteger', found 'TReal'."
             ),
         }
     }
 
+    fn size_env() -> (Environment<Type>, Type) {
+        let mut env: Environment<Type> = Environment::new();
+        let constructors = vec![
+            ValueConstructor { name: "Small".to_string(), types: vec![] },
+            ValueConstructor { name: "Large".to_string(), types: vec![] },
+        ];
+        env.insert_type("Size".to_string(), constructors.clone());
+        (env, Tadt("Size".to_string(), constructors))
+    }
+
     #[test]
-    fn check_function_call_wrong_type() {
-        let env: Environment<Type> = Environment::new();
+    fn check_generic_calls_against_constraints() {
+        let (mut env, size_type) = size_env();
+        let ord_a = TVar("a".to_string(), vec!["Ord".to_string()]);
+        env.insert_variable(
+            "larger".to_string(),
+            TFunction(Box::new(Some(ord_a.clone())), vec![ord_a.clone(), ord_a]),
+        );
+        let larger = |args: Vec<Expression>| check_exp(FuncCall("larger".to_string(), args), &env);
+        let small = ADTConstructor("Size".to_string(), "Small".to_string(), vec![]);
 
-        let func = FuncDef(Function {
-            name: "add".to_string(),
-            kind: Some(TInteger),
-            params: Some(vec![
-                ("a".to_string(), TInteger),
-                ("b".to_string(), TInteger),
-            ]),
-            body: Some(Box::new(Sequence(
-                Box::new(Assignment(
-                    "c".to_string(),
-                    Box::new(Add(
-                        Box::new(Var("a".to_string())),
-                        Box::new(Var("b".to_string())),
-                    )),
-                    Some(TInteger),
-                )),
-                Box::new(Return(Box::new(Var("c".to_string())))),
-            ))),
-        });
-        let program = Sequence(
-            Box::new(func.clone()),
-            Box::new(Assignment(
-                "var".to_string(),
-                Box::new(FuncCall("add".to_string(), vec![CInt(1), CTrue])),
-                Some(TInteger),
-            )),
+        assert_eq!(larger(vec![CInt(1), CInt(2)]), Ok(TInteger));
+        assert_eq!(
+            larger(vec![CInt(1), CReal(2.0)]),
+            Err(String::from("[Type Error on '__main__()'] 'larger()' has mismatched arguments: expected 'TInteger', found 'TReal'."))
+        );
+        assert_eq!(
+            larger(vec![CString("a".to_string()), CString("b".to_string())]),
+            Err(String::from("[Type Error on '__main__()'] 'larger()' requires an instance of 'Ord' for 'TString'."))
+        );
+        assert_eq!(
+            larger(vec![small.clone(), small]),
+            Err(String::from("[Type Error on '__main__()'] 'larger()' requires an instance of 'Ord' for 'Size'."))
         );
 
-        match check_stmt(program, &env.clone()) {
-            Ok(_) => assert!(false),
-            Err(s) => assert_eq!(s, "[Type Error on '__main__()'] 'add()' has mismatched arguments: expected 'TInteger', found 'TBool'."),
-        }
+        let lt = Function {
+            name: "lt".to_string(),
+            kind: Some(TBool),
+            params: Some(vec![("a".to_string(), size_type.clone()), ("b".to_string(), size_type)]),
+            body: Some(Box::new(Return(Box::new(CFalse)))),
+        };
+        let env = match check_stmt(InstanceDeclaration("Ord".to_string(), "Size".to_string(), vec![lt]), &env) {
+            Ok(ControlFlow::Continue(new_env)) => new_env,
+            _ => panic!("expected the instance to type check"),
+        };
+        let small = ADTConstructor("Size".to_string(), "Small".to_string(), vec![]);
+        assert!(matches!(
+            check_exp(FuncCall("larger".to_string(), vec![small.clone(), small]), &env),
+            Ok(Tadt(name, _)) if name == "Size"
+        ));
     }
 
     #[test]
-    fn check_function_call_non_function() {
+    fn check_generic_body_uses_constraints() {
         let env: Environment<Type> = Environment::new();
+        let body = |class: &str| {
+            let a = TVar("a".to_string(), vec![class.to_string()]);
+            FuncDef(Function {
+                name: "double".to_string(),
+                kind: Some(a.clone()),
+                params: Some(vec![("x".to_string(), a)]),
+                body: Some(Box::new(Return(Box::new(Add(
+                    Box::new(Var("x".to_string())),
+                    Box::new(Var("x".to_string())),
+                ))))),
+            })
+        };
 
-        let program = Sequence(
-            Box::new(Assignment(
-                "a".to_string(),
-                Box::new(CInt(1)),
-                Some(TInteger),
-            )),
-            Box::new(Assignment(
-                "b".to_string(),
-                Box::new(FuncCall("a".to_string(), vec![])),
-                Some(TInteger),
-            )),
+        assert!(check_stmt(body("Num"), &env).is_ok());
+        match check_stmt(body("Show"), &env) {
+            Ok(_) => panic!("expected a type error"),
+            Err(s) => assert_eq!(s, "[Type Error] expecting numeric type values."),
+        }
+    }
+
+    #[test]
+    fn check_instance_declaration_errors() {
+        let (env, size_type) = size_env();
+        let show = |kind: Type| Function {
+            name: "show".to_string(),
+            kind: Some(kind),
+            params: Some(vec![("s".to_string(), size_type.clone())]),
+            body: Some(Box::new(Return(Box::new(CString("size".to_string()))))),
+        };
+        let check = |class: &str, adt: &str, methods: Vec<Function>| {
+            match check_stmt(InstanceDeclaration(class.to_string(), adt.to_string(), methods), &env) {
+                Ok(_) => String::from("ok"),
+                Err(s) => s,
+            }
+        };
+
+        assert_eq!(check("Show", "Size", vec![show(TString)]), "ok");
+        assert_eq!(check("Functor", "Size", vec![]), "[Type Error] unknown type class 'Functor'.");
+        assert_eq!(
+            check("Show", "Point", vec![]),
+            "[Type Error] instances can only be declared for ADTs, and 'Point' is not one."
         );
+        assert_eq!(
+            check("Show", "Size", vec![show(TInteger)]),
+            "[Type Error] 'show' in the 'Show' instance for 'Size' must take 1 'Size' arguments and return 'TString'."
+        );
+        assert_eq!(
+            check("Show", "Size", vec![]),
+            "[Type Error] the 'Show' instance for 'Size' does not define 'show'."
+        );
+    }
 
-        match check_stmt(program, &env.clone()) {
-            Ok(_) => assert!(false),
-            Err(s) => assert_eq!(s, "[Name Error on '__main__()'] 'a()' is not defined."),
+    #[test]
+    fn check_void_functions_need_no_return() {
+        let env: Environment<Type> = Environment::new();
+        let func = |kind: Type| Function {
+            name: "log".to_string(),
+            kind: Some(kind),
+            params: Some(vec![("x".to_string(), TInteger)]),
+            body: Some(Box::new(Assignment(
+                "y".to_string(),
+                Box::new(Var("x".to_string())),
+                Some(TInteger),
+            ))),
+        };
+
+        let env = match check_stmt(FuncDef(func(TVoid)), &env) {
+            Ok(ControlFlow::Continue(new_env)) => new_env,
+            _ => panic!("expected the void function to type check"),
+        };
+        match check_stmt(
+            Assignment(
+                "r".to_string(),
+                Box::new(FuncCall("log".to_string(), vec![CInt(1)])),
+                Some(TVoid),
+            ),
+            &env,
+        ) {
+            Ok(ControlFlow::Continue(new_env)) => {
+                assert_eq!(new_env.search_frame("r".to_string()), Some(&TVoid))
+            }
+            _ => panic!("expected void to be bound"),
         }
+        assert_eq!(
+            check_stmt(FuncDef(func(TInteger)), &env).err(),
+            Some(String::from("[Syntax Error] 'log()' does not have a return statement."))
+        );
     }
 
     #[test]
-    fn check_function_call_undefined() {
-        let env: Environment<Type> = Environment::new();
+    fn check_patterns_bind_the_types_they_match() {
+        let mut env: Environment<Type> = Environment::new();
+        env.insert_type(
+            "Pair".to_string(),
+            vec![ValueConstructor { name: "Pair".to_string(), types: vec![TInteger, TList(Box::new(TReal))] }],
+        );
+        let pair_type = Tadt("Pair".to_string(), vec![]);
+        let var = |name: &str| Pattern::Variable(name.to_string());
+        let pair = |args: Vec<Pattern>| Pattern::Constructor("Pair".to_string(), "Pair".to_string(), args);
+        let check = |pattern: Pattern, kind: &Type| {
+            let mut bindings = vec![];
+            check_pattern(&pattern, kind, &env, &mut bindings).map(|_| bindings)
+        };
+
+        assert_eq!(
+            check(pair(vec![var("n"), Pattern::List(vec![var("head"), Pattern::Wildcard])]), &pair_type),
+            Ok(vec![("n".to_string(), TInteger), ("head".to_string(), TReal)])
+        );
+        assert_eq!(
+            check(pair(vec![Pattern::Literal(CTrue), Pattern::Wildcard]), &pair_type),
+            Err(String::from("[Type Error in '__main__'] the pattern 'True' cannot match a value of type 'TInteger'."))
+        );
+        assert_eq!(
+            check(pair(vec![var("n")]), &pair_type),
+            Err(String::from("[Type Error in '__main__'] ADT constructor 'Pair' expected 2 arguments, found 1."))
+        );
+        assert_eq!(
+            check(pair(vec![var("n"), var("n")]), &pair_type),
+            Err(String::from("[Type Error in '__main__'] 'n' is bound twice in the pattern."))
+        );
+        assert!(check(pair(vec![var("n"), var("m")]), &TInteger).unwrap_err().contains("cannot match"));
+    }
 
-        let program = Assignment(
-            "a".to_string(),
-            Box::new(FuncCall("func".to_string(), vec![])),
-            Some(TInteger),
+    #[test]
+    fn check_matches_are_exhaustive_and_reachable() {
+        let mut env: Environment<Type> = Environment::new();
+        env.insert_type(
+            "Shape".to_string(),
+            vec![
+                ValueConstructor { name: "Circle".to_string(), types: vec![TReal] },
+                ValueConstructor { name: "Square".to_string(), types: vec![TBool] },
+                ValueConstructor { name: "Point".to_string(), types: vec![] },
+            ],
         );
+        env.insert_variable("s".to_string(), Tadt("Shape".to_string(), vec![]));
+        let shape = |name: &str, args: Vec<Pattern>| Pattern::Constructor("Shape".to_string(), name.to_string(), args);
+        let check = |patterns: Vec<Pattern>| {
+            let cases = patterns.into_iter().map(|pattern| (pattern, None, Box::new(Block(vec![])))).collect();
+            check_stmt(Match(Box::new(Var("s".to_string())), cases), &env).map(|_| env.lints.take())
+        };
 
-        match check_stmt(program, &env.clone()) {
-            Ok(_) => assert!(false),
-            Err(s) => assert_eq!(s, "[Name Error on '__main__()'] 'func()' is not defined."),
-        }
+        assert_eq!(
+            check(vec![shape("Circle", vec![Pattern::Variable("r".to_string())])]),
+            Err(String::from("[Type Error in '__main__'] the match on 'Shape' is not exhaustive; no arm matches Square, Point."))
+        );
+        assert_eq!(
+            check(vec![
+                shape("Square", vec![Pattern::Literal(CTrue)]),
+                shape("Circle", vec![Pattern::Wildcard]),
+                shape("Point", vec![]),
+            ]),
+            Err(String::from("[Type Error in '__main__'] the match on 'Shape' is not exhaustive; no arm matches Square."))
+        );
+        assert_eq!(
+            check(vec![
+                shape("Square", vec![Pattern::Literal(CTrue)]),
+                shape("Square", vec![Pattern::Literal(CFalse)]),
+                Pattern::Variable("other".to_string()),
+            ]),
+            Ok(vec![])
+        );
+        let positive = || Some(GT(Box::new(Var("r".to_string())), Box::new(CReal(0.0))));
+        let guarded = |patterns: Vec<(Pattern, Option<Expression>)>| {
+            let cases = patterns.into_iter().map(|(pattern, guard)| (pattern, guard, Box::new(Block(vec![])))).collect();
+            check_stmt(Match(Box::new(Var("s".to_string())), cases), &env).map(|_| env.lints.take())
+        };
+        let circle = || shape("Circle", vec![Pattern::Variable("r".to_string())]);
+        assert_eq!(
+            guarded(vec![(circle(), positive()), (Pattern::Wildcard, Some(CTrue))]),
+            Err(String::from("[Type Error in '__main__'] the match on 'Shape' is not exhaustive; no arm matches Circle, Square, Point."))
+        );
+        assert_eq!(guarded(vec![(circle(), positive()), (circle(), None), (Pattern::Wildcard, None)]), Ok(vec![]));
+        assert_eq!(
+            guarded(vec![(circle(), Some(Var("r".to_string()))), (Pattern::Wildcard, None)]),
+            Err(String::from("[Type Error on '__main__()'] the guard of 'Shape Circle r if r' must be boolean."))
+        );
+
+        assert_eq!(
+            check(vec![
+                shape("Circle", vec![Pattern::Wildcard]),
+                Pattern::Wildcard,
+                shape("Circle", vec![Pattern::Literal(CReal(1.0))]),
+                shape("Point", vec![]),
+            ]),
+            Ok(vec![
+                String::from("[Warning] in '__main__': the arm 'Shape Circle 1.0' is unreachable; the arms before it match everything it does."),
+                String::from("[Warning] in '__main__': the arm 'Shape Point' is unreachable; the arms before it match everything it does."),
+            ])
+        );
     }
+
     #[test]
-    fn check_recursive_function() {
-        let env: Environment<Type> = Environment::new();
+    fn check_nested_constructor_patterns() {
+        let mut env: Environment<Type> = Environment::new();
+        let list_type = Tadt("List".to_string(), vec![]);
+        env.insert_type(
+            "List".to_string(),
+            vec![
+                ValueConstructor { name: "Nil".to_string(), types: vec![] },
+                ValueConstructor { name: "Cons".to_string(), types: vec![TInteger, list_type.clone()] },
+            ],
+        );
+        env.insert_variable("xs".to_string(), list_type);
+        let var = |name: &str| Pattern::Variable(name.to_string());
+        let cons = |head, tail| Pattern::Constructor("List".to_string(), "Cons".to_string(), vec![head, tail]);
+        let nil = || Pattern::Constructor("List".to_string(), "Nil".to_string(), vec![]);
+        let check = |patterns: Vec<Pattern>| {
+            let cases = patterns.into_iter().map(|pattern| (pattern, None, Box::new(Block(vec![])))).collect();
+            check_stmt(Match(Box::new(Var("xs".to_string())), cases), &env).map(|_| env.lints.take())
+        };
+
+        let pairs = cons(var("x"), cons(var("y"), var("rest")));
+        let mut bindings = vec![];
+        assert_eq!(check_pattern(&pairs, &Tadt("List".to_string(), vec![]), &env, &mut bindings), Ok(()));
+        assert_eq!(
+            bindings,
+            vec![("x".to_string(), TInteger), ("y".to_string(), TInteger), ("rest".to_string(), Tadt("List".to_string(), vec![]))]
+        );
 
-        // Definição de função fatorial recursiva
-        let factorial = FuncDef(Function {
-            name: "factorial".to_string(),
-            kind: Some(TInteger),
-            params: Some(vec![("n".to_string(), TInteger)]),
-            body: Some(Box::new(IfThenElse(
-                Box::new(EQ(Box::new(Var("n".to_string())), Box::new(CInt(0)))),
-                Box::new(Return(Box::new(CInt(1)))),
-                Some(Box::new(Return(Box::new(Mul(
-                    Box::new(Var("n".to_string())),
-                    Box::new(FuncCall(
-                        "factorial".to_string(),
-                        vec![Sub(Box::new(Var("n".to_string())), Box::new(CInt(1)))],
-                    )),
-                ))))),
-            ))),
-        });
+        assert_eq!(check(vec![nil(), cons(var("x"), nil()), pairs.clone()]), Ok(vec![]));
+        assert_eq!(
+            check(vec![nil(), pairs]),
+            Err(String::from("[Type Error in '__main__'] the match on 'List' is not exhaustive; no arm matches Cons."))
+        );
+    }
 
-        match check_stmt(factorial, &env) {
+    #[test]
+    fn check_destructuring_needs_irrefutable_patterns() {
+        let mut env: Environment<Type> = Environment::new();
+        env.insert_type("Point".to_string(), vec![ValueConstructor { name: "P".to_string(), types: vec![TInteger, TReal] }]);
+        env.insert_type(
+            "Shape".to_string(),
+            vec![
+                ValueConstructor { name: "Circle".to_string(), types: vec![TReal] },
+                ValueConstructor { name: "Square".to_string(), types: vec![TReal] },
+            ],
+        );
+        env.insert_variable("p".to_string(), Tadt("Point".to_string(), vec![]));
+        env.insert_variable("s".to_string(), Tadt("Shape".to_string(), vec![]));
+        env.insert_variable("pair".to_string(), TTuple(vec![TBool, TString]));
+        env.insert_variable("xs".to_string(), TList(Box::new(TInteger)));
+        let var = |name: &str| Pattern::Variable(name.to_string());
+        let check = |pattern: Pattern, exp: &str| check_stmt(Destructure(pattern, Box::new(Var(exp.to_string()))), &env);
+
+        let point = Pattern::Constructor("Point".to_string(), "P".to_string(), vec![var("x"), var("y")]);
+        match check(point, "p") {
             Ok(ControlFlow::Continue(new_env)) => {
-                assert_eq!(
-                    new_env.search_frame("factorial".to_string()),
-                    Some(TFunction(Box::new(Some(TInteger)), vec![TInteger])).as_ref()
-                );
+                assert_eq!(new_env.search_frame("x".to_string()), Some(&TInteger));
+                assert_eq!(new_env.search_frame("y".to_string()), Some(&TReal));
             }
-            _ => assert!(false, "Recursive function definition failed"),
+            _ => panic!("expected the fields of the point to be bound"),
         }
+        assert!(check(Pattern::Tuple(vec![var("a"), Pattern::Wildcard]), "pair").is_ok());
+
+        let circle = Pattern::Constructor("Shape".to_string(), "Circle".to_string(), vec![var("r")]);
+        assert_eq!(
+            check(circle, "s").err(),
+            Some(String::from(
+                "[Type Error in '__main__'] the pattern 'Shape Circle r' does not match the 'Shape' values built with Square; use a match to handle them."
+            ))
+        );
+        assert_eq!(
+            check(Pattern::List(vec![var("a"), var("b")]), "xs").err(),
+            Some(String::from(
+                "[Type Error in '__main__'] the pattern '[a, b]' does not match every value of type 'TList(TInteger)'; use a match to handle them."
+            ))
+        );
+        assert!(check(Pattern::Tuple(vec![Pattern::Literal(CTrue), var("b")]), "pair").is_err());
     }
 
     #[test]
-    fn check_function_multiple_return_paths() {
-        let env: Environment<Type> = Environment::new();
+    fn check_string_patterns_shadow_the_strings_they_cover() {
+        let mut env: Environment<Type> = Environment::new();
+        env.insert_variable("command".to_string(), TString);
+        let cases = vec![
+            Pattern::StartsWith("GE".to_string()),
+            Pattern::Literal(CString("GET".to_string())),
+            Pattern::StartsWith("GET ".to_string()),
+            Pattern::StartsWith("G".to_string()),
+            Pattern::EndsWith("?".to_string()),
+        ];
+        let cases = cases.into_iter().map(|pattern| (pattern, None, Box::new(Block(vec![])))).collect();
+        assert!(check_stmt(Match(Box::new(Var("command".to_string())), cases), &env).is_ok());
+        let unreachable = |arm: &str| {
+            format!("[Warning] in '__main__': the arm '{}' is unreachable; the arms before it match everything it does.", arm)
+        };
+        assert_eq!(env.lints.take(), vec![unreachable("\"GET\""), unreachable("startswith \"GET \"")]);
+
+        let cases = vec![(Pattern::EndsWith("?".to_string()), None, Box::new(Block(vec![])))];
+        assert!(check_stmt(Match(Box::new(CInt(1)), cases), &env).err().unwrap().contains("cannot match"));
+    }
 
-        // Função com múltiplos caminhos de retorno
-        let func = FuncDef(Function {
-            name: "max".to_string(),
-            kind: Some(TInteger),
-            params: Some(vec![
-                ("a".to_string(), TInteger),
-                ("b".to_string(), TInteger),
-            ]),
-            body: Some(Box::new(IfThenElse(
-                Box::new(GT(
-                    Box::new(Var("a".to_string())),
-                    Box::new(Var("b".to_string())),
-                )),
-                Box::new(Return(Box::new(Var("a".to_string())))),
-                Some(Box::new(Return(Box::new(Var("b".to_string()))))),
-            ))),
-        });
+    #[test]
+    fn check_list_rest_patterns_bind_the_rest_of_the_list() {
+        let mut env: Environment<Type> = Environment::new();
+        env.insert_variable("xs".to_string(), TList(Box::new(TInteger)));
+        let var = |name: &str| Pattern::Variable(name.to_string());
+        let cons = Pattern::ListRest(vec![var("x")], Some("rest".to_string()));
+
+        let mut bindings = vec![];
+        assert_eq!(check_pattern(&cons, &TList(Box::new(TInteger)), &env, &mut bindings), Ok(()));
+        assert_eq!(bindings, vec![("x".to_string(), TInteger), ("rest".to_string(), TList(Box::new(TInteger)))]);
+
+        let cases = vec![
+            Pattern::List(vec![]),
+            cons.clone(),
+            Pattern::List(vec![var("a"), var("b")]),
+            Pattern::ListRest(vec![], None),
+        ];
+        let cases = cases.into_iter().map(|pattern| (pattern, None, Box::new(Block(vec![])))).collect();
+        assert!(check_stmt(Match(Box::new(Var("xs".to_string())), cases), &env).is_ok());
+        let unreachable = |arm: &str| {
+            format!("[Warning] in '__main__': the arm '{}' is unreachable; the arms before it match everything it does.", arm)
+        };
+        assert_eq!(env.lints.take(), vec![unreachable("[a, b]"), unreachable("[..]")]);
+
+        let cases = vec![
+            (Pattern::ListRest(vec![var("a"), var("b")], None), None, Box::new(Block(vec![]))),
+            (Pattern::List(vec![var("a")]), None, Box::new(Block(vec![]))),
+            (Pattern::Wildcard, None, Box::new(Block(vec![]))),
+        ];
+        assert!(check_stmt(Match(Box::new(Var("xs".to_string())), cases), &env).is_ok());
+        assert_eq!(env.lints.take(), Vec::<String>::new());
 
-        match check_stmt(func, &env) {
-            Ok(ControlFlow::Continue(_)) => assert!(true),
-            _ => assert!(false, "Multiple return paths function failed"),
-        }
+        assert_eq!(
+            check_stmt(Destructure(cons, Box::new(Var("xs".to_string()))), &env).err(),
+            Some(String::from(
+                "[Type Error in '__main__'] the pattern '[x, rest @ ..]' does not match every value of type 'TList(TInteger)'; use a match to handle them."
+            ))
+        );
     }
 
     #[test]
-    fn test_function_wrong_return_type() {
+    fn check_matches_on_types_without_values() {
+        let mut env: Environment<Type> = Environment::new();
+        let constructor = |name: &str, types| ValueConstructor { name: name.to_string(), types };
+        let adt = |name: &str| Tadt(name.to_string(), vec![]);
+        env.insert_type("Never".to_string(), vec![]);
+        env.insert_type("Stream".to_string(), vec![constructor("Next", vec![TInteger, adt("Stream")])]);
+        env.insert_type(
+            "Outcome".to_string(),
+            vec![constructor("Done", vec![TInteger]), constructor("Failed", vec![adt("Never")])],
+        );
+        env.insert_variable("never".to_string(), adt("Never"));
+        env.insert_variable("stream".to_string(), adt("Stream"));
+        env.insert_variable("outcome".to_string(), adt("Outcome"));
+        let arm = |pattern| (pattern, None, Box::new(Block(vec![])));
+        let check = |name: &str, arms| {
+            check_stmt(Match(Box::new(Var(name.to_string())), arms), &env).map(|_| env.lints.take())
+        };
+
+        assert_eq!(check("never", vec![]), Ok(vec![]));
+        assert_eq!(
+            check("stream", vec![arm(Pattern::Wildcard)]),
+            Ok(vec![String::from(
+                "[Warning] in '__main__': the arm '_' is unreachable; no value of type 'Stream' can be built."
+            )])
+        );
+        let done = Pattern::Constructor("Outcome".to_string(), "Done".to_string(), vec![Pattern::Variable("n".to_string())]);
+        assert_eq!(
+            check("outcome", vec![arm(done.clone()), arm(Pattern::Wildcard)]),
+            Ok(vec![String::from(
+                "[Warning] in '__main__': the arm '_' is unreachable; the arms before it match everything it does."
+            )])
+        );
+        assert_eq!(check("outcome", vec![arm(done)]), Ok(vec![]));
+
+        assert_eq!(
+            check_exp(ADTConstructor("Never".to_string(), "Never".to_string(), vec![]), &env),
+            Err(String::from("[Type Error in '__main__'] ADT 'Never' has no constructors, so no value of it can be built."))
+        );
+    }
+
+    #[test]
+    fn check_constants_are_folded_and_never_rebound() {
+        let constants = "
+            const PI: TReal = 3.14159
+            const TAU: TReal = PI * 2.0
+            const UNIT: TString = \"cm\"
+        ";
+        let env = accepts(&format!("{}\n            def area(r: TReal) -> TReal:\n                return PI * r * r", constants));
+        assert_eq!(env.search_frame("TAU".to_string()), Some(&TReal));
+        assert!(env.is_constant(&"TAU".to_string()));
+        let with = |rest: &str| rejects(&format!("{}\n            {}", constants, rest));
+        let error = |message: &str| diagnostic("Type Error", Some("__main__"), message);
+
+        assert_eq!(with("PI = 3.0"), error("'PI' is a constant and cannot be bound again."));
+        assert_eq!(
+            with("const PI: TReal = 3.0"),
+            error("the constant 'PI' is already bound; constants need a name of their own.")
+        );
+        assert_eq!(
+            with("x = 2\n            const TWICE: TInteger = x * 2"),
+            error("the value of the constant 'TWICE' must be known before the program runs, but 'x' is not.")
+        );
+        assert_eq!(
+            with("const E: TInteger = 2.71828"),
+            error("'E' has mismatched types: expected 'TInteger', found 'TReal'.")
+        );
+        assert_eq!(
+            with("def f(TAU: TReal) -> TReal:\n                return TAU"),
+            diagnostic("Type Error", Some("f"), "'TAU' is a constant and cannot be bound again.")
+        );
+        assert_eq!(
+            with("def f() -> TReal:\n                const E: TReal = 2.71828\n                return E"),
+            diagnostic("Syntax Error", None, "constants are only allowed at the top level; 'E' is declared in 'f()'.")
+        );
+    }
+
+    #[test]
+    fn check_returns_through_nested_statements() {
         let env: Environment<Type> = Environment::new();
+        let x = || Box::new(Var("x".to_string()));
+        let return_x = || Box::new(Return(x()));
+        let x_above_2 = || Box::new(GT(x(), Box::new(CInt(2))));
+        let returns = |body: Statement| {
+            let func = Function {
+                name: "f".to_string(),
+                kind: Some(TInteger),
+                params: Some(vec![("x".to_string(), TInteger)]),
+                body: Some(Box::new(body)),
+            };
+            check_stmt(FuncDef(func), &env).map(|_| ())
+        };
+        let missing = Err(String::from("[Syntax Error] 'f()' does not have a return statement."));
+
+        let loop_with = |cond: Expression| {
+            While(
+                Box::new(cond),
+                Box::new(Block(vec![IfThenElse(x_above_2(), return_x(), None)])),
+            )
+        };
+        assert_eq!(returns(loop_with(CTrue)), Ok(()));
+        assert_eq!(returns(loop_with(CFalse)), missing);
+        assert_eq!(
+            returns(While(Box::new(GT(x(), Box::new(CInt(0)))), return_x())),
+            missing
+        );
 
-        let func = FuncDef(Function {
-            name: "wrong_return".to_string(),
-            kind: Some(TInteger),
-            params: None,
-            body: Some(Box::new(Return(Box::new(CReal(1.0))))),
-        });
+        let match_with = |else_arm: Statement| {
+            Match(x_above_2(), vec![(Pattern::Literal(CTrue), None, return_x()), (Pattern::Literal(CFalse), None, Box::new(else_arm))])
+        };
+        assert_eq!(returns(match_with(Return(Box::new(CInt(0))))), Ok(()));
+        assert_eq!(
+            returns(match_with(Assignment("x".to_string(), Box::new(CInt(0)), None))),
+            missing
+        );
 
-        match check_stmt(func, &env) {
-            Ok(_) => assert!(false, "Should fail due to wrong return type"),
-            Err(msg) => assert_eq!(
-                msg,
-                "[Type Error] 'wrong_return()' has mismatched types: expected 'TInteger', found 'TReal'."
-            ),
-        }
+        assert_eq!(
+            returns(Block(vec![
+                Return(x()),
+                Assignment("x".to_string(), Box::new(CInt(1)), None),
+            ])),
+            Ok(())
+        );
+        assert_eq!(
+            returns(Block(vec![Return(x()), Return(Box::new(CTrue))])),
+            Err(String::from(
+                "[Type Error] 'f()' has mismatched types: expected 'TInteger', found 'TBool'."
+            ))
+        );
     }
 
     #[test]
-    fn test_function_parameter_shadowing() {
+    fn check_contract_clauses_are_boolean() {
         let env: Environment<Type> = Environment::new();
+        let def = |clause: Statement| {
+            FuncDef(Function {
+                name: "f".to_string(),
+                kind: Some(TInteger),
+                params: Some(vec![("n".to_string(), TInteger)]),
+                body: Some(Box::new(Block(vec![clause, Return(Box::new(Var("n".to_string())))]))),
+            })
+        };
+        let result_gt = |n: i32| GT(Box::new(Var("result".to_string())), Box::new(CInt(n)));
+
+        assert!(check_stmt(def(Requires(Box::new(result_gt(0)))), &env).is_err());
+        assert!(check_stmt(def(Ensures(Box::new(result_gt(0)))), &env).is_ok());
+        assert_eq!(
+            check_stmt(def(Requires(Box::new(Var("n".to_string())))), &env).err(),
+            Some(String::from("[Type Error] 'requires' clause of 'f()' must be a boolean, found 'TInteger'."))
+        );
+        assert_eq!(
+            check_stmt(Requires(Box::new(CTrue)), &env).err(),
+            Some(String::from("[Syntax Error] contracts are only allowed at the start of a function body."))
+        );
+    }
 
-        let func = FuncDef(Function {
-            name: "shadow_test".to_string(),
-            kind: Some(TInteger),
-            params: Some(vec![
-                ("x".to_string(), TInteger),
-                ("x".to_string(), TInteger), // Mesmo nome de parâmetro
-            ]),
-            body: Some(Box::new(Return(Box::new(Var("x".to_string()))))),
-        });
+    #[test]
+    fn check_loop_invariants_are_boolean() {
+        let mut env: Environment<Type> = Environment::new();
+        env.insert_variable("i".to_string(), TInteger);
+        let loop_with = |invariant: Expression| {
+            While(
+                Box::new(LT(Box::new(Var("i".to_string())), Box::new(CInt(3)))),
+                Box::new(Block(vec![
+                    Invariant(Box::new(invariant)),
+                    Assignment("i".to_string(), Box::new(CInt(3)), Some(TInteger)),
+                ])),
+            )
+        };
+
+        assert!(check_stmt(loop_with(GTE(Box::new(Var("i".to_string())), Box::new(CInt(0)))), &env).is_ok());
+        assert_eq!(
+            check_stmt(loop_with(Var("i".to_string())), &env).err(),
+            Some(String::from("[Type Error on '__main__()'] loop invariant must be boolean, found 'TInteger'."))
+        );
+    }
 
-        match check_stmt(func, &env) {
-            Ok(_) => panic!("Should not accept duplicate parameter names"),
-            Err(msg) => assert_eq!(msg, "[Parameter Error] Duplicate parameter name 'x'"),
-        }
+    #[test]
+    fn check_decreases_measures() {
+        let env: Environment<Type> = Environment::new();
+        let def = |clauses: Vec<Statement>| {
+            let mut body = clauses;
+            body.push(Return(Box::new(Var("n".to_string()))));
+            FuncDef(Function {
+                name: "f".to_string(),
+                kind: Some(TInteger),
+                params: Some(vec![("n".to_string(), TInteger)]),
+                body: Some(Box::new(Block(body))),
+            })
+        };
+        let decreases = |exp: Expression| Decreases(Box::new(exp));
+
+        assert!(check_stmt(def(vec![decreases(Var("n".to_string()))]), &env).is_ok());
+        assert_eq!(
+            check_stmt(def(vec![decreases(CTrue)]), &env).err(),
+            Some(String::from("[Type Error on 'f()'] 'decreases' measure must be an integer, found 'TBool'."))
+        );
+        assert_eq!(
+            check_stmt(def(vec![decreases(CInt(1)), decreases(CInt(2))]), &env).err(),
+            Some(String::from("[Syntax Error] only one 'decreases' clause is allowed."))
+        );
     }
 }